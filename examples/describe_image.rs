@@ -0,0 +1,38 @@
+//! Describe Image - Vision Input Example
+//!
+//! Sends an image (a local file or a URL) to a vision model and prints the
+//! model's description.
+//!
+//! Build: cargo build --example describe_image --release
+//! Run: OPENAI_API_KEY=sk-... ./target/release/examples/describe_image photo.jpg
+//! Or:  OPENAI_API_KEY=sk-... ./target/release/examples/describe_image https://example.com/cat.png "How many cats?"
+
+use patinox::prelude::*;
+use patinox::provider::{ImageInput, OpenAIProvider, ProviderConfig};
+
+#[tokio::main]
+async fn main() -> patinox::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let Some(source) = args.next() else {
+        eprintln!("Usage: describe_image <file-or-url> [question]");
+        std::process::exit(2);
+    };
+    let question = args
+        .next()
+        .unwrap_or_else(|| "Describe this image in a few sentences.".to_string());
+
+    let image = if source.starts_with("http://") || source.starts_with("https://") {
+        ImageInput::url(source)
+    } else {
+        ImageInput::from_file(&source)?
+    };
+
+    // gpt-4o-mini accepts images; the agent refuses to send them to models
+    // without vision
+    let config = ProviderConfig::new(Provider::OpenAI).model("gpt-4o-mini");
+    let agent = create_agent("describer").with_provider(Box::new(OpenAIProvider::new(config)?));
+
+    let description = agent.run_with_images(question, vec![image]).await?;
+    println!("{}", description);
+    Ok(())
+}
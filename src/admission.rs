@@ -0,0 +1,184 @@
+//! Concurrent run limiting
+//!
+//! [`Admission`] caps how many runs of an agent execute at once. Runs over
+//! the limit wait in a queue of bounded depth; once the queue is full, new
+//! runs fail fast with [`AgentError::ResourceExhausted`]. A queue depth of
+//! zero means no waiting at all.
+//!
+//! Wait times are tracked in [`AdmissionStats`] so an overloaded agent shows
+//! up as growing queue waits before it starts rejecting work.
+//!
+//! # Example
+//! ```ignore
+//! let agent = create_agent("support").with_concurrency_limit(8, 32);
+//! // ... serve traffic ...
+//! let stats = agent.admission_stats().unwrap();
+//! println!("avg wait {:?}, rejected {}", stats.average_wait(), stats.rejected);
+//! ```
+
+use crate::error::AgentError;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Counters for admitted and rejected runs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdmissionStats {
+    pub admitted: u64,
+    pub rejected: u64,
+    /// Total time admitted runs spent queued
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+
+impl AdmissionStats {
+    /// Mean queue wait of admitted runs
+    pub fn average_wait(&self) -> Duration {
+        match self.admitted {
+            0 => Duration::ZERO,
+            n => self.total_wait / n as u32,
+        }
+    }
+}
+
+/// Semaphore-based admission controller shared by all runs of an agent
+#[derive(Debug)]
+pub struct Admission {
+    permits: Arc<Semaphore>,
+    limit: usize,
+    max_queue: usize,
+    queued: AtomicUsize,
+    stats: Mutex<AdmissionStats>,
+}
+
+impl Admission {
+    /// Allow `limit` concurrent runs with up to `max_queue` more waiting
+    pub fn new(limit: usize, max_queue: usize) -> Self {
+        let limit = limit.max(1);
+        Self {
+            permits: Arc::new(Semaphore::new(limit)),
+            limit,
+            max_queue,
+            queued: AtomicUsize::new(0),
+            stats: Mutex::new(AdmissionStats::default()),
+        }
+    }
+
+    /// Maximum concurrent runs
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Maximum runs waiting for a slot
+    pub fn max_queue(&self) -> usize {
+        self.max_queue
+    }
+
+    /// Runs currently executing
+    pub fn in_flight(&self) -> usize {
+        self.limit - self.permits.available_permits()
+    }
+
+    /// Runs currently waiting for a slot
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Acquire)
+    }
+
+    pub fn stats(&self) -> AdmissionStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Wait for a slot, or fail if the queue is full
+    ///
+    /// The returned permit frees the slot when dropped.
+    pub async fn acquire(&self) -> Result<OwnedSemaphorePermit, AgentError> {
+        if let Ok(permit) = self.permits.clone().try_acquire_owned() {
+            self.record_admitted(Duration::ZERO);
+            return Ok(permit);
+        }
+
+        // `fetch_update` is `try_update` on newer toolchains, past our MSRV
+        #[allow(deprecated)]
+        let joined = self
+            .queued
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |queued| {
+                (queued < self.max_queue).then_some(queued + 1)
+            });
+        if joined.is_err() {
+            self.stats.lock().unwrap().rejected += 1;
+            tracing::warn!(
+                "admission: rejecting run, {} in flight and {} queued",
+                self.in_flight(),
+                self.queued()
+            );
+            return Err(AgentError::ResourceExhausted);
+        }
+
+        // Leave the queue even if the waiting run is cancelled
+        struct Leave<'a>(&'a AtomicUsize);
+        impl Drop for Leave<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Ordering::AcqRel);
+            }
+        }
+        let _leave = Leave(&self.queued);
+
+        let started = Instant::now();
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("admission semaphore is never closed");
+        self.record_admitted(started.elapsed());
+        Ok(permit)
+    }
+
+    fn record_admitted(&self, wait: Duration) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.admitted += 1;
+        stats.total_wait += wait;
+        stats.max_wait = stats.max_wait.max(wait);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fail_fast_without_queue() {
+        let admission = Admission::new(1, 0);
+        let held = admission.acquire().await.unwrap();
+        assert_eq!(admission.in_flight(), 1);
+        assert_eq!(
+            admission.acquire().await.unwrap_err(),
+            AgentError::ResourceExhausted
+        );
+
+        drop(held);
+        assert!(admission.acquire().await.is_ok());
+        let stats = admission.stats();
+        assert_eq!((stats.admitted, stats.rejected), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn test_queued_run_waits_for_a_slot() {
+        let admission = Arc::new(Admission::new(1, 1));
+        let held = admission.acquire().await.unwrap();
+
+        let waiter = {
+            let admission = admission.clone();
+            tokio::spawn(async move { admission.acquire().await.map(|_| ()) })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(admission.queued(), 1);
+        assert!(admission.acquire().await.is_err());
+
+        drop(held);
+        waiter.await.unwrap().unwrap();
+        assert_eq!(admission.queued(), 0);
+        assert!(admission.stats().max_wait >= Duration::from_millis(20));
+    }
+}
@@ -3,14 +3,64 @@
 //! The Agent is the central orchestrator that combines tools, providers,
 //! and execution logic into a working AI agent.
 
-use crate::lifecycle::AgentLifecycle;
+use crate::admission::{Admission, AdmissionStats};
+use crate::approval::{ApprovalGate, ApprovalRequest};
+use crate::audit::{AuditEvent, AuditLog};
+use crate::context::ContextManager;
+#[cfg(feature = "timezones")]
+use crate::date_context::DateContext;
+use crate::error::{AgentError, AggregateError};
+use crate::escalation::{
+    Escalation, EscalationRequest, RejectionStreaks, DEFAULT_ESCALATION_THRESHOLD, ESCALATE_TOOL,
+};
+use crate::events::{emit, AgentEvent, EventSender, TurnUsage};
+use crate::execution::ExecutionContext;
+use crate::lifecycle::{AgentLifecycle, HookAction};
+use crate::loop_guard::LoopGuard;
+use crate::memory::MemoryGuard;
+use crate::prompt::PromptTemplate;
 use crate::provider::{
-    LLMProvider, Message, Provider, ProviderConfig, ProviderResponse, ToolDefinition,
+    Capability, CapabilityWarning, ImageInput, LLMProvider, Message, ModelCapabilities, Provider,
+    ProviderConfig, ProviderResponse, RequestOptions, ToolCall, ToolChoice, ToolDefinition,
+    UnsupportedInput,
 };
-use crate::tool::Tool;
-use serde_json::json;
-use std::collections::HashMap;
+use crate::session::{Session, SessionStore};
+use crate::tenancy::{Tenancy, ANONYMOUS_USER};
+use crate::tokens::{estimate_message_tokens, estimate_tokens};
+use crate::tool::output::ToolOutputLimit;
+use crate::tool::{Tool, ToolRegistry, ToolResult};
+use crate::transcript::ToolTranscript;
+use futures::{Stream, StreamExt};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinError;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+/// Tool calls from one turn run at once unless
+/// [`Agent::with_tool_parallelism`] says otherwise
+const DEFAULT_TOOL_PARALLELISM: usize = 4;
+
+/// Run one tool call off the async thread, timing it
+async fn execute_tool(
+    events: Option<&EventSender>,
+    call: ToolCall,
+    tool: Arc<dyn Tool>,
+) -> (ToolCall, Result<ToolResult, JoinError>, Duration) {
+    emit(events, || AgentEvent::ToolCallStarted {
+        name: call.name.clone(),
+        arguments: call.arguments.clone(),
+    });
+    let arguments = call.arguments.clone();
+    let started = std::time::Instant::now();
+    let span = tracing::info_span!("tool.call", tool = %call.name);
+    let context = ExecutionContext::current().unwrap_or_default();
+    let outcome = tokio::task::spawn_blocking(move || {
+        context.sync_scope(|| span.in_scope(|| tool.execute(arguments)))
+    })
+    .await;
+    (call, outcome, started.elapsed())
+}
 
 /// Agent configuration
 #[derive(Debug, Clone)]
@@ -19,6 +69,8 @@ pub struct AgentConfig {
     pub description: Option<String>,
     pub system_prompt: Option<String>,
     pub provider_config: ProviderConfig,
+    /// Limit on a whole run, including every provider and tool call
+    pub timeout: Option<Duration>,
 }
 
 impl AgentConfig {
@@ -29,6 +81,7 @@ impl AgentConfig {
             description: None,
             system_prompt: Some("You are a helpful AI assistant.".to_string()),
             provider_config: ProviderConfig::new(Provider::Anthropic),
+            timeout: None,
         }
     }
 
@@ -55,14 +108,44 @@ impl AgentConfig {
         self.provider_config = self.provider_config.model(model);
         self
     }
+
+    /// Fail runs that take longer than `timeout`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
 }
 
 /// Agent - the core orchestrator
 pub struct Agent {
     pub(crate) config: AgentConfig,
-    pub(crate) tools: HashMap<String, Arc<dyn Tool>>,
+    pub(crate) tools: ToolRegistry,
     provider: Option<Box<dyn LLMProvider>>,
-    lifecycle: Vec<Arc<dyn AgentLifecycle>>,
+    /// Providers for requests that override [`RequestOptions::provider`]
+    alternate_providers: Vec<(Provider, Box<dyn LLMProvider>)>,
+    pub(crate) lifecycle: Vec<Arc<dyn AgentLifecycle>>,
+    escalation: Option<Arc<dyn Escalation>>,
+    rejections: RejectionStreaks,
+    memory_guard: Option<MemoryGuard>,
+    tool_output_limit: Option<ToolOutputLimit>,
+    pub(crate) approval: Option<Arc<dyn ApprovalGate>>,
+    /// Run dangerous tools even though no approval gate is configured
+    dangerous_without_approval: bool,
+    transcript: Option<ToolTranscript>,
+    audit: Option<AuditLog>,
+    loop_guard: LoopGuard,
+    /// Tool calls from one model turn that may run at once
+    tool_parallelism: usize,
+    pub(crate) sessions: Option<Arc<dyn SessionStore>>,
+    admission: Option<Arc<Admission>>,
+    tenancy: Option<Arc<Tenancy>>,
+    prompt_template: Option<(PromptTemplate, serde_json::Value)>,
+    context_manager: Option<(Arc<dyn ContextManager>, usize)>,
+    #[cfg(feature = "timezones")]
+    date_context: Option<DateContext>,
+    capabilities: Option<ModelCapabilities>,
+    /// Names of the plugins applied with [`with_plugin`](Self::with_plugin)
+    pub(crate) plugins: Vec<String>,
 }
 
 impl Agent {
@@ -70,18 +153,66 @@ impl Agent {
     pub fn new(config: AgentConfig) -> Self {
         Self {
             config,
-            tools: HashMap::new(),
+            tools: ToolRegistry::new(),
             provider: None,
+            alternate_providers: Vec::new(),
             lifecycle: Vec::new(),
+            escalation: None,
+            rejections: RejectionStreaks::new(DEFAULT_ESCALATION_THRESHOLD),
+            memory_guard: None,
+            tool_output_limit: None,
+            approval: None,
+            dangerous_without_approval: false,
+            transcript: None,
+            audit: None,
+            loop_guard: LoopGuard::default(),
+            tool_parallelism: DEFAULT_TOOL_PARALLELISM,
+            sessions: None,
+            admission: None,
+            tenancy: None,
+            prompt_template: None,
+            context_manager: None,
+            #[cfg(feature = "timezones")]
+            date_context: None,
+            capabilities: None,
+            plugins: Vec::new(),
         }
     }
 
-    /// Add a tool to the agent
+    /// Add a tool to the agent (replaces any tool with the same name)
     pub fn tool(mut self, tool: impl Tool + 'static) -> Self {
-        self.tools.insert(tool.name().to_string(), Arc::new(tool));
+        self.add_tool(Arc::new(tool));
+        self
+    }
+
+    /// Add a tool under a namespace, exposed to the model as `namespace.name`
+    pub fn tool_in(mut self, namespace: &str, tool: impl Tool + 'static) -> Self {
+        self.add_tool(crate::tool::namespaced(namespace, tool));
         self
     }
 
+    /// Replace the agent's tools with a prepared registry
+    pub fn with_tools(mut self, registry: ToolRegistry) -> Self {
+        self.tools = registry;
+        self
+    }
+
+    /// The agent's tools
+    pub fn tools(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    /// Mutable access to the agent's tools, for registering or removing tools between runs
+    pub fn tools_mut(&mut self) -> &mut ToolRegistry {
+        &mut self.tools
+    }
+
+    fn add_tool(&mut self, tool: Arc<dyn Tool>) {
+        if let Some(previous) = self.tools.insert(tool) {
+            tracing::warn!("Tool '{}' replaced an existing tool", previous.name());
+        }
+    }
+
     /// Add a tool from a closure (convenience method)
     pub fn tool_fn<F>(
         mut self,
@@ -94,7 +225,146 @@ impl Agent {
     {
         use crate::tool::FnTool;
         let tool = FnTool::from_string_fn(name, description, handler);
-        self.tools.insert(tool.name().to_string(), Arc::new(tool));
+        self.add_tool(Arc::new(tool));
+        self
+    }
+
+    /// Add a tool from a closure taking typed parameters
+    ///
+    /// The argument schema is generated from `P`, and the model's arguments
+    /// are deserialized into it before the handler runs. See
+    /// [`TypedTool`](crate::tool::TypedTool).
+    pub fn tool_fn_typed<P, R, F>(
+        mut self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        handler: F,
+    ) -> Self
+    where
+        P: schemars::JsonSchema + serde::de::DeserializeOwned + 'static,
+        R: serde::Serialize + 'static,
+        F: Fn(P) -> Result<R, Box<dyn std::error::Error + Send + Sync>> + Send + Sync + 'static,
+    {
+        let tool = crate::tool::TypedTool::new(name, description, handler);
+        self.add_tool(Arc::new(tool));
+        self
+    }
+
+    /// Fail runs that take longer than `timeout`
+    ///
+    /// The deadline covers the whole run. A provider request in flight when it
+    /// passes is aborted; a tool that is executing is abandoned (it finishes
+    /// on its blocking thread, but its result is discarded).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    /// Run at most `limit` requests at once, queueing up to `max_queue` more
+    ///
+    /// Runs beyond the queue fail with [`AgentError::ResourceExhausted`].
+    /// Time spent queued counts toward the run's timeout. See
+    /// [`admission`](crate::admission).
+    pub fn with_concurrency_limit(mut self, limit: usize, max_queue: usize) -> Self {
+        self.admission = Some(Arc::new(Admission::new(limit, max_queue)));
+        self
+    }
+
+    /// Queue wait and rejection counters, if a concurrency limit is set
+    pub fn admission_stats(&self) -> Option<AdmissionStats> {
+        self.admission.as_ref().map(|admission| admission.stats())
+    }
+
+    /// Enforce per-user quotas, by the `user_id` in each run's
+    /// [`ExecutionContext`]
+    ///
+    /// Runs without one are metered together as
+    /// [`ANONYMOUS_USER`](crate::tenancy::ANONYMOUS_USER).
+    ///
+    /// Runs are counted once they get a concurrency slot, so runs refused
+    /// a slot or cancelled while queued are free. Runs of users at a limit
+    /// fail with [`QuotaExceeded`](crate::tenancy::QuotaExceeded). See
+    /// [`tenancy`](crate::tenancy).
+    pub fn with_tenancy(mut self, tenancy: Tenancy) -> Self {
+        self.tenancy = Some(Arc::new(tenancy));
+        self
+    }
+
+    /// The quotas set with [`with_tenancy`](Self::with_tenancy)
+    pub fn tenancy(&self) -> Option<&Tenancy> {
+        self.tenancy.as_deref()
+    }
+
+    /// Build the system prompt from a template at the start of every run
+    ///
+    /// The template sees `vars` plus `agent`, `description`, `date`
+    /// (`YYYY-MM-DD`) and `tools` (a list of `{name, description}`); keys in
+    /// `vars` take precedence. Replaces any plain system prompt. A render
+    /// error fails the run. See [`prompt`](crate::prompt).
+    pub fn system_prompt_template(
+        mut self,
+        template: PromptTemplate,
+        vars: serde_json::Value,
+    ) -> Self {
+        self.prompt_template = Some((template, vars));
+        self
+    }
+
+    /// Append the current date, timezone and locale to the system prompt
+    ///
+    /// Rendered for every request. See [`date_context`](crate::date_context).
+    #[cfg(feature = "timezones")]
+    pub fn with_date_context(mut self, context: DateContext) -> Self {
+        self.date_context = Some(context);
+        self
+    }
+
+    /// The system prompt for a run, with the date context if configured
+    pub(crate) fn system_prompt(&self) -> crate::Result<Option<String>> {
+        let prompt = self.base_system_prompt()?;
+        #[cfg(feature = "timezones")]
+        if let Some(date_context) = &self.date_context {
+            let context = date_context.render()?;
+            return Ok(Some(match prompt {
+                Some(prompt) => format!("{}\n\n{}", prompt, context),
+                None => context,
+            }));
+        }
+        Ok(prompt)
+    }
+
+    /// The configured system prompt, rendering the template if there is one
+    fn base_system_prompt(&self) -> crate::Result<Option<String>> {
+        let Some((template, vars)) = &self.prompt_template else {
+            return Ok(self.config.system_prompt.clone());
+        };
+
+        let tools: Vec<_> = self
+            .tools
+            .iter()
+            .map(|tool| serde_json::json!({"name": tool.name(), "description": tool.description()}))
+            .collect();
+        let mut context = serde_json::json!({
+            "agent": self.config.name,
+            "description": self.config.description,
+            "date": chrono::Local::now().format("%Y-%m-%d").to_string(),
+            "tools": tools,
+        });
+        if let (Some(context), Some(vars)) = (context.as_object_mut(), vars.as_object()) {
+            context.extend(vars.clone());
+        }
+        Ok(Some(template.render(&context)?))
+    }
+
+    /// Keep the conversation within `max_tokens` before every model call
+    ///
+    /// See [`context`](crate::context) for the available strategies.
+    pub fn with_context_manager(
+        mut self,
+        manager: impl ContextManager + 'static,
+        max_tokens: usize,
+    ) -> Self {
+        self.context_manager = Some((Arc::new(manager), max_tokens));
         self
     }
 
@@ -104,6 +374,111 @@ impl Agent {
         self
     }
 
+    /// Declare what the model supports instead of guessing from its name
+    ///
+    /// See [`capabilities`](crate::provider::capabilities).
+    pub fn with_capabilities(mut self, capabilities: ModelCapabilities) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
+    /// The configured model
+    pub(crate) fn model(&self) -> &str {
+        &self.provider_config().model
+    }
+
+    /// The attached provider's configuration, or the agent's without one
+    pub(crate) fn provider_config(&self) -> &ProviderConfig {
+        self.provider
+            .as_ref()
+            .and_then(|p| p.config())
+            .unwrap_or(&self.config.provider_config)
+    }
+
+    /// Limits runs work within
+    pub(crate) fn budgets(&self) -> crate::manifest::BudgetManifest {
+        crate::manifest::BudgetManifest {
+            timeout_ms: self.config.timeout.map(|t| t.as_millis() as u64),
+            concurrency_limit: self.admission.as_ref().map(|a| a.limit()),
+            max_queue: self.admission.as_ref().map(|a| a.max_queue()),
+            max_iterations: self.loop_guard.iteration_limit(),
+            tool_output_tokens: self.tool_output_limit.as_ref().map(|l| l.max_tokens()),
+            memory_item_bytes: self.memory_guard.as_ref().map(|g| g.max_item_bytes()),
+            memory_budget_bytes: self
+                .memory_guard
+                .as_ref()
+                .and_then(|g| g.shared_budget())
+                .map(|b| b.limit()),
+            quota: self.tenancy.as_ref().map(|t| t.base_quota()),
+            users_with_quotas: self.tenancy.as_ref().map_or(0, |t| t.users_with_quotas()),
+        }
+    }
+
+    /// The main provider followed by those added with
+    /// [`with_provider_for`](Self::with_provider_for)
+    pub(crate) fn providers(&self) -> Vec<Provider> {
+        let main = match self.provider.as_ref().and_then(|p| p.config()) {
+            Some(config) => config.provider,
+            None => self.config.provider_config.provider,
+        };
+        std::iter::once(main)
+            .chain(
+                self.alternate_providers
+                    .iter()
+                    .map(|(provider, _)| *provider),
+            )
+            .collect()
+    }
+
+    /// Tools hidden from the configured model because it lacks capabilities
+    pub fn capability_warnings(&self) -> Vec<CapabilityWarning> {
+        self.capability_warnings_for(self.model())
+    }
+
+    fn capability_warnings_for(&self, model: &str) -> Vec<CapabilityWarning> {
+        let capabilities = self
+            .capabilities
+            .unwrap_or_else(|| ModelCapabilities::for_model(model));
+        self.tools
+            .iter()
+            .filter_map(|tool| {
+                let mut required = vec![Capability::ToolCalling];
+                required.extend(tool.required_capabilities());
+                let missing = capabilities.missing(&required);
+                (!missing.is_empty()).then(|| CapabilityWarning {
+                    tool: tool.name().to_string(),
+                    model: model.to_string(),
+                    missing,
+                })
+            })
+            .collect()
+    }
+
+    /// Register the provider used when a request overrides the provider
+    ///
+    /// See [`run_with_options`](Self::run_with_options).
+    pub fn with_provider_for(mut self, provider: Provider, llm: Box<dyn LLMProvider>) -> Self {
+        self.alternate_providers.retain(|(p, _)| *p != provider);
+        self.alternate_providers.push((provider, llm));
+        self
+    }
+
+    /// Switch to another model of the same provider
+    ///
+    /// Fails if the configured provider can't change models (see
+    /// [`LLMProvider::with_model`]).
+    pub fn set_model(&mut self, model: impl Into<String>) -> crate::Result<()> {
+        let model = model.into();
+        if let Some(provider) = &self.provider {
+            let switched = provider
+                .with_model(&model)
+                .ok_or("The configured provider cannot switch models")?;
+            self.provider = Some(switched);
+        }
+        self.config.provider_config.model = model;
+        Ok(())
+    }
+
     /// Add a lifecycle hook to this agent
     ///
     /// Hooks are executed in registration order. Multiple hooks can be chained
@@ -120,6 +495,312 @@ impl Agent {
         self
     }
 
+    /// Hand conversations to a human through an escalation channel
+    ///
+    /// The agent escalates automatically when lifecycle hooks reject
+    /// responses repeatedly (see
+    /// [`with_escalation_threshold`](Self::with_escalation_threshold)), and
+    /// the model gets an `escalate` tool it can call when it decides a human
+    /// should take over.
+    pub fn with_escalation(mut self, escalation: impl Escalation + 'static) -> Self {
+        self.escalation = Some(Arc::new(escalation));
+        self
+    }
+
+    /// Escalate once hooks have rejected this many responses in a row in
+    /// one conversation (default 3)
+    ///
+    /// Conversations are told apart by the run's session id, then user id;
+    /// a run with neither is a conversation of its own. Streaks idle for an
+    /// hour are forgotten.
+    pub fn with_escalation_threshold(mut self, rejections: usize) -> Self {
+        self.rejections = RejectionStreaks::new(rejections);
+        self
+    }
+
+    /// Limit the size of tool results kept in the conversation
+    ///
+    /// Oversized results are truncated or spilled to a temp file according to
+    /// the guard's policy. See [`memory`](crate::memory).
+    pub fn with_memory_guard(mut self, guard: MemoryGuard) -> Self {
+        self.memory_guard = Some(guard);
+        self
+    }
+
+    /// Record hook verdicts, approvals, escalations and dangerous tool calls
+    ///
+    /// See [`audit`](crate::audit). A run fails if its audit entry can't be
+    /// written.
+    pub fn with_audit_log(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Cap the estimated tokens of each tool result before the model sees it
+    ///
+    /// Applied before the memory guard. See
+    /// [`tool::output`](crate::tool::output).
+    pub fn with_tool_output_limit(mut self, limit: ToolOutputLimit) -> Self {
+        self.tool_output_limit = Some(limit);
+        self
+    }
+
+    /// Ask a human before running dangerous tools or when a hook escalates
+    ///
+    /// Without a gate, calls to dangerous tools fail the run unless
+    /// [`allow_dangerous_without_approval`](Self::allow_dangerous_without_approval)
+    /// is set. See [`approval`](crate::approval).
+    pub fn with_approval_gate(mut self, gate: impl ApprovalGate + 'static) -> Self {
+        self.approval = Some(Arc::new(gate));
+        self
+    }
+
+    /// Run dangerous tools without asking anyone when no approval gate is
+    /// configured
+    ///
+    /// For unattended agents whose tools are already confined some other
+    /// way. With a gate configured, dangerous calls are still approved.
+    pub fn allow_dangerous_without_approval(mut self) -> Self {
+        self.dangerous_without_approval = true;
+        self
+    }
+
+    /// Record every tool call into a transcript
+    ///
+    /// See [`transcript`](crate::transcript) for exporting it as a script or test.
+    pub fn with_transcript(mut self, transcript: ToolTranscript) -> Self {
+        self.transcript = Some(transcript);
+        self
+    }
+
+    /// Limit model turns and repeated tool calls per run
+    ///
+    /// See [`loop_guard`](crate::loop_guard); every agent has the default guard.
+    pub fn with_loop_guard(mut self, guard: LoopGuard) -> Self {
+        self.loop_guard = guard;
+        self
+    }
+
+    /// Run up to `limit` of a turn's tool calls concurrently
+    ///
+    /// Results are still handed to the model in the order it asked for them,
+    /// and [`AgentEvent::ToolCallFinished`] events arrive in that order with
+    /// each call's own duration. Use 1 for tools that must not overlap.
+    ///
+    /// Every call runs to completion even if another fails. A turn with one
+    /// failed call fails with that call's error; with several calls, any
+    /// failure fails the run with an [`AggregateError`] of every call.
+    pub fn with_tool_parallelism(mut self, limit: usize) -> Self {
+        self.tool_parallelism = limit.max(1);
+        self
+    }
+
+    /// Keep chat sessions in `store`; the CLI chat saves every turn there
+    /// and can list and resume them
+    pub fn with_session_store(mut self, store: Arc<dyn SessionStore>) -> Self {
+        self.sessions = Some(store);
+        self
+    }
+
+    /// Whether a provider is configured (running without one fails with
+    /// [`AgentError::NoProvider`])
+    pub fn has_provider(&self) -> bool {
+        self.provider.is_some()
+    }
+
+    /// Add `event` to the audit log, if there is one
+    fn audit(&self, event: impl FnOnce() -> AuditEvent) -> crate::Result<()> {
+        if let Some(audit) = &self.audit {
+            audit.record(&self.config.name, event())?;
+        }
+        Ok(())
+    }
+
+    /// Charge a model call to `user_id` (no-op without one)
+    async fn meter(
+        &self,
+        user_id: Option<&str>,
+        model: &str,
+        usage: &TurnUsage,
+    ) -> crate::Result<()> {
+        if let (Some(tenancy), Some(user_id)) = (&self.tenancy, user_id) {
+            tenancy
+                .record(user_id, model, usage.prompt_tokens, usage.completion_tokens)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Deliver an escalation with the transcript so far (no-op without a channel)
+    async fn escalate(&self, reason: &str, transcript: &[Message]) -> crate::Result<()> {
+        if let Some(escalation) = &self.escalation {
+            self.audit(|| AuditEvent::Escalated {
+                reason: reason.to_string(),
+            })?;
+            let request = EscalationRequest {
+                agent: self.config.name.clone(),
+                reason: reason.to_string(),
+                transcript: transcript.to_vec(),
+                context: ExecutionContext::current()
+                    .map(|context| context.to_map())
+                    .unwrap_or_default(),
+            };
+            escalation.escalate(&request).await?;
+        }
+        Ok(())
+    }
+
+    /// The registered tool for `call`, once approved if it is dangerous
+    async fn authorize_tool(&self, call: &ToolCall) -> crate::Result<Arc<dyn Tool>> {
+        let tool = self
+            .tools
+            .get(&call.name)
+            .cloned()
+            .ok_or_else(|| format!("Tool '{}' not found", call.name))?;
+
+        if tool.dangerous() && !self.dangerous_without_approval {
+            self.require_approval(
+                "tool is marked dangerous",
+                Some(&call.name),
+                &call.arguments,
+            )
+            .await?;
+        }
+        Ok(tool)
+    }
+
+    /// Audit and transcribe a finished tool call
+    fn record_tool_call(
+        &self,
+        call: &ToolCall,
+        outcome: &ToolResult,
+        elapsed: Duration,
+    ) -> crate::Result<()> {
+        if self
+            .tools
+            .get(&call.name)
+            .is_some_and(|tool| tool.dangerous())
+        {
+            self.audit(|| AuditEvent::DangerousToolExecuted {
+                tool: call.name.clone(),
+                arguments: call.arguments.clone(),
+                succeeded: outcome.is_ok(),
+            })?;
+        }
+        if let Some(transcript) = &self.transcript {
+            let output = match outcome {
+                Ok(output) => Ok(output.as_str()),
+                Err(e) => Err(e.to_string()),
+            };
+            transcript.record(&call.name, call.arguments.clone(), output, elapsed);
+        }
+        Ok(())
+    }
+
+    /// Run one tool call made from outside the agent loop (e.g. MCP
+    /// `tools/call`) with the checks the loop applies to model calls
+    ///
+    /// `after_model` hooks see the call as a one-call response, dangerous
+    /// tools need approval, and the call is audited, transcribed and run
+    /// inside `context`. Hooks can't rewrite direct calls, so a `Modify`
+    /// verdict refuses the call.
+    #[cfg(feature = "mcp")]
+    pub(crate) async fn call_tool(
+        &self,
+        call: ToolCall,
+        mut context: ExecutionContext,
+    ) -> crate::Result<String> {
+        context.execution_id = uuid::Uuid::new_v4().to_string();
+        let run = async {
+            let response = ProviderResponse::ToolCalls(vec![call.clone()]);
+            for hook in &self.lifecycle {
+                match hook.after_model(&response).await? {
+                    HookAction::Continue => {}
+                    HookAction::Approve => self.audit(|| AuditEvent::HookApproved)?,
+                    HookAction::Reject(reason) => {
+                        self.audit(|| AuditEvent::HookRejected {
+                            reason: reason.clone(),
+                        })?;
+                        return Err(reason.into());
+                    }
+                    HookAction::Escalate(reason) => {
+                        self.require_approval(&reason, Some(&call.name), &call.arguments)
+                            .await?;
+                    }
+                    HookAction::Modify(_) => {
+                        return Err(
+                            format!("Call to '{}' refused: a hook rewrote it", call.name).into(),
+                        );
+                    }
+                }
+            }
+
+            let tool = self.authorize_tool(&call).await?;
+            let (call, outcome, elapsed) = execute_tool(None, call, tool).await;
+            let outcome = outcome?;
+            self.record_tool_call(&call, &outcome, elapsed)?;
+            let mut result = outcome?;
+            for hook in &self.lifecycle {
+                result = hook.after_tool(&call.name, &result).await?;
+            }
+            if let Some(limit) = &self.tool_output_limit {
+                result = limit.apply(&call.name, result).await;
+            }
+            Ok(result)
+        };
+        context.scope(run).await
+    }
+
+    /// Wait for a human decision, failing the run if it is denied
+    async fn require_approval(
+        &self,
+        reason: &str,
+        tool: Option<&str>,
+        arguments: &serde_json::Value,
+    ) -> crate::Result<()> {
+        let gate = self.approval.as_ref().ok_or_else(|| {
+            format!(
+                "Approval required but no approval gate is configured: {}",
+                reason
+            )
+        })?;
+        let request = ApprovalRequest {
+            agent: self.config.name.clone(),
+            reason: reason.to_string(),
+            tool: tool.map(str::to_string),
+            arguments: arguments.clone(),
+        };
+        let decision = gate.request_approval(&request).await?;
+        self.audit(|| AuditEvent::ApprovalDecided {
+            reason: request.reason.clone(),
+            tool: request.tool.clone(),
+            arguments: request.arguments.clone(),
+            approved: decision.approved,
+            note: decision.reason.clone(),
+        })?;
+        let verdict = if decision.approved {
+            "approved"
+        } else {
+            "denied"
+        };
+        tracing::info!(
+            "approval: {} - {} ({})",
+            request.summary(),
+            verdict,
+            decision.reason.as_deref().unwrap_or("no reason given")
+        );
+
+        if decision.approved {
+            Ok(())
+        } else {
+            Err(format!(
+                "Denied by human: {}",
+                decision.reason.as_deref().unwrap_or(reason)
+            )
+            .into())
+        }
+    }
+
     /// Apply a plugin to extend agent capabilities
     ///
     /// Plugins transform the agent to add optional functionality. Each plugin
@@ -139,86 +820,495 @@ impl Agent {
     /// - Multiple plugins can be composed together
     /// - Plugins should be zero-cost abstractions when possible
     pub fn with_plugin(self, plugin: impl crate::plugin::AgentPlugin) -> Self {
-        plugin.apply(self)
+        let name = plugin.name().to_string();
+        let mut agent = plugin.apply(self);
+        agent.plugins.push(name);
+        agent
     }
 
     /// Run the agent with a single input
     pub async fn run(&self, input: impl Into<String>) -> crate::Result<String> {
-        use crate::lifecycle::HookAction;
+        self.run_cancellable(input, CancellationToken::new()).await
+    }
+
+    /// Run the agent on `input` as the next turn of an earlier conversation
+    ///
+    /// `history` holds the previous user and assistant messages; the system
+    /// prompt is added as usual.
+    pub async fn run_with_history(
+        &self,
+        history: Vec<Message>,
+        input: impl Into<String>,
+    ) -> crate::Result<String> {
+        self.run_with(
+            Message::user(input),
+            history,
+            RequestOptions::default(),
+            ExecutionContext::default(),
+            CancellationToken::new(),
+            None,
+        )
+        .await
+    }
+
+    /// Run the agent on `input` as the next turn of `session`, recording
+    /// the exchange, its tool calls and token usage in the session
+    ///
+    /// Saves the session to the [session store](Self::with_session_store),
+    /// if there is one. A failed run leaves the conversation unchanged.
+    pub async fn run_session(
+        &self,
+        session: &mut Session,
+        input: impl Into<String>,
+    ) -> crate::Result<String> {
+        let input = input.into();
+        let mut result: crate::Result<String> = Err("Run ended without a result".into());
+        let mut events = Box::pin(self.execute_streaming_in(
+            ExecutionContext::new().session(&session.id),
+            session.messages.clone(),
+            input.clone(),
+        ));
+        while let Some(event) = events.next().await {
+            session.record_event(&event);
+            match event {
+                AgentEvent::Completed { output } => result = Ok(output),
+                AgentEvent::Failed { error } => result = Err(error.into()),
+                _ => {}
+            }
+        }
+        let output = result?;
+        session.push_turn(input, output.clone());
+        if let Some(store) = &self.sessions {
+            store.save(session).await?;
+        }
+        Ok(output)
+    }
+
+    /// Run the agent on `input` with images attached, for vision models
+    ///
+    /// Fails with [`UnsupportedInput`] if the model's capabilities (see
+    /// [`with_capabilities`](Self::with_capabilities)) lack vision.
+    pub async fn run_with_images(
+        &self,
+        input: impl Into<String>,
+        images: Vec<ImageInput>,
+    ) -> crate::Result<String> {
+        let mut message = Message::user(input);
+        message.images = images;
+        self.run_with(
+            message,
+            Vec::new(),
+            RequestOptions::default(),
+            ExecutionContext::default(),
+            CancellationToken::new(),
+            None,
+        )
+        .await
+    }
+
+    /// Run the agent with per-request overrides of model, temperature,
+    /// max tokens or provider
+    ///
+    /// Overrides cascade over the agent's provider config (see
+    /// [`RequestOptions`]). A provider override needs a provider registered
+    /// with [`with_provider_for`](Self::with_provider_for). The resolved
+    /// settings are logged and reported as [`AgentEvent::RunStarted`].
+    ///
+    /// # Example
+    /// ```ignore
+    /// let options = RequestOptions::new().model("gpt-4o").temperature(0.0);
+    /// let answer = agent.run_with_options("Classify this ticket", options).await?;
+    /// ```
+    pub async fn run_with_options(
+        &self,
+        input: impl Into<String>,
+        options: RequestOptions,
+    ) -> crate::Result<String> {
+        self.run_with(
+            Message::user(input),
+            Vec::new(),
+            options,
+            ExecutionContext::default(),
+            CancellationToken::new(),
+            None,
+        )
+        .await
+    }
+
+    /// Run the agent on behalf of the user, session or trace in `context`
+    ///
+    /// Tools, lifecycle hooks and provider middleware can read the context
+    /// with [`ExecutionContext::current`]; it is also reported in
+    /// [`AgentEvent::RunStarted`] and attached to escalations.
+    pub async fn run_with_context(
+        &self,
+        input: impl Into<String>,
+        context: ExecutionContext,
+    ) -> crate::Result<String> {
+        self.run_with(
+            Message::user(input),
+            Vec::new(),
+            RequestOptions::default(),
+            context,
+            CancellationToken::new(),
+            None,
+        )
+        .await
+    }
+
+    /// Run the agent until it finishes or `cancel` is triggered
+    ///
+    /// Cancelling aborts an in-flight provider request and stops before the
+    /// next tool call (a tool that is already executing runs to completion).
+    /// The run then fails with [`AgentError::Cancelled`], and everything it
+    /// held, such as memory budget reservations, is released.
+    pub async fn run_cancellable(
+        &self,
+        input: impl Into<String>,
+        cancel: CancellationToken,
+    ) -> crate::Result<String> {
+        self.run_with(
+            Message::user(input),
+            Vec::new(),
+            RequestOptions::default(),
+            ExecutionContext::default(),
+            cancel,
+            None,
+        )
+        .await
+    }
+
+    /// Run the agent, reporting progress as a stream of [`AgentEvent`]s
+    ///
+    /// The stream ends after [`AgentEvent::Completed`] or
+    /// [`AgentEvent::Failed`]. Dropping it cancels the run.
+    pub fn execute_streaming(
+        &self,
+        input: impl Into<String>,
+    ) -> impl Stream<Item = AgentEvent> + Send + '_ {
+        self.execute_streaming_with_history(Vec::new(), input)
+    }
 
+    /// [`execute_streaming`](Self::execute_streaming) continuing a conversation
+    pub fn execute_streaming_with_history(
+        &self,
+        history: Vec<Message>,
+        input: impl Into<String>,
+    ) -> impl Stream<Item = AgentEvent> + Send + '_ {
+        self.execute_streaming_in(ExecutionContext::default(), history, input)
+    }
+
+    /// [`execute_streaming_with_history`](Self::execute_streaming_with_history)
+    /// on behalf of the user, session or trace in `context`
+    pub fn execute_streaming_in(
+        &self,
+        context: ExecutionContext,
+        history: Vec<Message>,
+        input: impl Into<String>,
+    ) -> impl Stream<Item = AgentEvent> + Send + '_ {
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        // The run owns the sender, so the receiver ends once the run is done
+        let run = futures::stream::once(self.run_with(
+            Message::user(input),
+            history,
+            RequestOptions::default(),
+            context,
+            CancellationToken::new(),
+            Some(sender),
+        ))
+        .filter_map(|_| futures::future::ready(None));
+        futures::stream::select(receiver, run)
+    }
+
+    async fn run_with(
+        &self,
+        input: Message,
+        history: Vec<Message>,
+        options: RequestOptions,
+        mut context: ExecutionContext,
+        cancel: CancellationToken,
+        events: Option<EventSender>,
+    ) -> crate::Result<String> {
+        context.execution_id = uuid::Uuid::new_v4().to_string();
+        let user_id = context.user_id.clone();
+        let run = async {
+            let _permit = match &self.admission {
+                Some(admission) => Some(tokio::select! {
+                    _ = cancel.cancelled() => return Err(AgentError::Cancelled.into()),
+                    permit = admission.acquire() => permit?,
+                }),
+                None => None,
+            };
+            // Counted once the run holds a slot, so runs refused or cancelled
+            // while queued don't use up the user's requests
+            if let Some(tenancy) = &self.tenancy {
+                tenancy
+                    .admit(user_id.as_deref().unwrap_or(ANONYMOUS_USER))
+                    .await?;
+            }
+            self.run_inner(input, history, &options, &cancel, events.as_ref())
+                .await
+        };
+        // Model and tool calls are child spans, so log lines carry the run
+        let span = tracing::info_span!(
+            "agent.run",
+            agent = %self.config.name,
+            execution_id = %context.execution_id,
+            user_id = tracing::field::Empty,
+            session_id = tracing::field::Empty,
+        );
+        if let Some(user_id) = &context.user_id {
+            span.record("user_id", user_id.as_str());
+        }
+        if let Some(session_id) = &context.session_id {
+            span.record("session_id", session_id.as_str());
+        }
+        let run = context.scope(run).instrument(span.clone());
+        let result = match self.config.timeout {
+            None => run.await,
+            Some(limit) => match tokio::time::timeout(limit, run).await {
+                Ok(result) => result,
+                Err(_) => {
+                    tracing::warn!(
+                        parent: &span,
+                        "Agent '{}' run timed out after {:?}",
+                        self.config.name,
+                        limit
+                    );
+                    Err(AgentError::Timeout(limit).into())
+                }
+            },
+        };
+        if cancel.is_cancelled() {
+            tracing::info!(parent: &span, "Agent '{}' run cancelled", self.config.name);
+        }
+        emit(events.as_ref(), || match &result {
+            Ok(output) => AgentEvent::Completed {
+                output: output.clone(),
+            },
+            Err(e) => AgentEvent::Failed {
+                error: e.to_string(),
+            },
+        });
+        result
+    }
+
+    async fn run_inner(
+        &self,
+        mut input: Message,
+        history: Vec<Message>,
+        options: &RequestOptions,
+        cancel: &CancellationToken,
+        events: Option<&EventSender>,
+    ) -> crate::Result<String> {
         let provider = self
             .provider
             .as_ref()
             .map(|p| p.as_ref())
-            .unwrap_or_else(|| {
-                panic!(
-                    "No provider configured. Use with_provider() or set up environment variables."
-                );
-            });
+            .ok_or(AgentError::NoProvider)?;
+        let base = provider.config().unwrap_or(&self.config.provider_config);
+        let provider = match options.provider.filter(|p| *p != base.provider) {
+            None => provider,
+            Some(other) => self
+                .alternate_providers
+                .iter()
+                .find(|(p, _)| *p == other)
+                .map(|(_, llm)| llm.as_ref())
+                .ok_or_else(|| {
+                    format!(
+                        "No provider registered for {:?}. Use with_provider_for().",
+                        other
+                    )
+                })?,
+        };
+        let resolved = match provider.config() {
+            Some(config) => config.resolve(options),
+            None => self.config.provider_config.resolve(options),
+        };
+        // A forced tool choice applies to the first tool turn only; after
+        // that the model must be free to answer
+        let forces_tool = matches!(
+            resolved.tool_choice,
+            Some(ToolChoice::Required | ToolChoice::Tool(_))
+        );
+        let mut options = options.clone();
+        let effective = resolved.effective();
+        tracing::debug!("Agent '{}' running with {:?}", self.config.name, effective);
+        let hidden = self.capability_warnings_for(&effective.model);
+        for warning in &hidden {
+            tracing::debug!("{}", warning);
+        }
+        let capabilities = self
+            .capabilities
+            .unwrap_or_else(|| ModelCapabilities::for_model(&effective.model));
+        let has_images = !input.images.is_empty() || history.iter().any(|m| !m.images.is_empty());
+        if has_images && !capabilities.supports_vision {
+            return Err(UnsupportedInput {
+                model: effective.model,
+                capability: Capability::Vision,
+            }
+            .into());
+        }
+        let supports_tools = capabilities.supports_tools;
+        let model = effective.model.clone();
+        emit(events, || AgentEvent::RunStarted {
+            config: effective,
+            context: ExecutionContext::current().unwrap_or_default(),
+        });
+
+        if let Some(transcript) = &self.transcript {
+            transcript.start(&input.content);
+        }
 
         // Hook 1: before_agent - Transform input before processing
-        let mut input = input.into();
         for hook in &self.lifecycle {
-            input = hook.before_agent(&input).await?;
+            input.content = hook.before_agent(&input.content).await?;
         }
 
         // Build initial messages
         let mut messages = Vec::new();
 
-        if let Some(sys_prompt) = &self.config.system_prompt {
+        if let Some(sys_prompt) = self.system_prompt()? {
             messages.push(Message::system(sys_prompt));
         }
 
-        messages.push(Message::user(input));
+        messages.extend(history);
+        messages.push(input);
 
         // Convert tools to ToolDefinitions
-        let tool_defs: Vec<ToolDefinition> = self
-            .tools
-            .values()
-            .map(|tool| ToolDefinition {
-                name: tool.name().to_string(),
-                description: tool.description().to_string(),
-                parameters: json!({
+        let mut tool_defs = self.tools.definitions();
+        tool_defs.retain(|def| !hidden.iter().any(|warning| warning.tool == def.name));
+
+        if self.escalation.is_some() && supports_tools {
+            tool_defs.push(ToolDefinition {
+                name: ESCALATE_TOOL.to_string(),
+                description: "Hand this conversation to a human when you cannot or should not \
+                              resolve it yourself"
+                    .to_string(),
+                parameters: serde_json::json!({
                     "type": "object",
-                    "properties": {},
-                    "required": []
+                    "properties": {
+                        "reason": {"type": "string", "description": "Why a human is needed"}
+                    },
+                    "required": ["reason"]
                 }),
+            });
+        }
+
+        // Budget and spill files held by this run's tool results, released
+        // when the run ends
+        let mut held = Vec::new();
+
+        // The user model calls are charged to, if the agent meters usage
+        let metered_user = self.tenancy.as_ref().map(|_| {
+            ExecutionContext::current()
+                .and_then(|context| context.user_id)
+                .unwrap_or_else(|| ANONYMOUS_USER.to_string())
+        });
+
+        // Conversation that hook rejections count against for escalation;
+        // runs outside a session or user are their own conversation
+        let conversation = ExecutionContext::current()
+            .map(|context| {
+                context
+                    .session_id
+                    .or(context.user_id)
+                    .unwrap_or(context.execution_id)
             })
-            .collect();
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        // Tool calling loop, bounded by the loop guard
+        let mut tracker = self.loop_guard.start();
+        for iteration in 0..tracker.max_iterations() {
+            if let Some((manager, max_tokens)) = &self.context_manager {
+                messages = manager.fit(messages, *max_tokens).await?;
+            }
 
-        // Tool calling loop (max 10 iterations to prevent infinite loops)
-        let max_iterations = 10;
-        for iteration in 0..max_iterations {
             // Hook 2: before_model - Transform messages before LLM call
             for hook in &self.lifecycle {
                 messages = hook.before_model(messages).await?;
             }
 
+            let turn = iteration + 1;
+            let prompt_tokens = if events.is_some() || metered_user.is_some() {
+                estimate_message_tokens(&messages)
+            } else {
+                0
+            };
+            let turn_started = std::time::Instant::now();
+            emit(events, || AgentEvent::TurnStarted { turn });
+
             // Hook 3: wrap_model_call - Wrap the LLM call
             // For simplicity, we call the provider directly and let hooks observe
             // Full wrapping with retry/fallback can be added in future iterations
-            let mut response = provider
-                .complete(messages.clone(), tool_defs.clone())
-                .await?;
+            // Dropping the provider future aborts its request
+            let request = provider
+                .complete_with_usage(messages.clone(), tool_defs.clone(), &options)
+                .instrument(tracing::info_span!("provider.complete", turn, model = %model));
+            let (mut response, reported) = tokio::select! {
+                biased;
+                _ = cancel.cancelled() => return Err(AgentError::Cancelled.into()),
+                response = request => response?,
+            };
 
             // Hook 4: after_model - Inspect/modify response, or reject
             for hook in &self.lifecycle {
                 match hook.after_model(&response).await? {
-                    HookAction::Continue | HookAction::Approve => {
+                    HookAction::Continue => {
                         // Continue normally
                     }
+                    HookAction::Approve => {
+                        self.audit(|| AuditEvent::HookApproved)?;
+                    }
                     HookAction::Reject(reason) => {
+                        emit(events, || AgentEvent::ValidationRejected {
+                            reason: reason.clone(),
+                        });
+                        self.audit(|| AuditEvent::HookRejected {
+                            reason: reason.clone(),
+                        })?;
+                        if let Some(streak) = self.rejections.reject(&conversation) {
+                            let escalation =
+                                format!("{} (rejected {} times in a row)", reason, streak);
+                            if let Err(e) = self.escalate(&escalation, &messages).await {
+                                tracing::error!("Escalation failed: {}", e);
+                            }
+                        }
                         return Err(reason.into());
                     }
+                    HookAction::Escalate(reason) => {
+                        self.require_approval(&reason, None, &serde_json::Value::Null)
+                            .await?;
+                    }
                     HookAction::Modify(new_response) => {
                         response = new_response;
                     }
                 }
             }
+            self.rejections.reset(&conversation);
 
             match response {
                 ProviderResponse::Text(text) => {
-                    // Hook 6: after_agent - Transform final result
+                    let usage = reported.map(TurnUsage::from).unwrap_or_else(|| TurnUsage {
+                        prompt_tokens,
+                        completion_tokens: estimate_tokens(&text),
+                        reasoning_tokens: 0,
+                    });
+                    self.meter(metered_user.as_deref(), &model, &usage).await?;
+                    emit(events, || AgentEvent::LlmDelta {
+                        content: text.clone(),
+                    });
+                    emit(events, || AgentEvent::TurnFinished {
+                        turn,
+                        usage,
+                        duration_ms: turn_started.elapsed().as_millis() as u64,
+                    });
+
+                    if let Some(transcript) = &self.transcript {
+                        transcript.finish(&text);
+                    }
+
+                    // Hook 7: after_agent - Transform final result
                     let mut result = text;
                     for hook in &self.lifecycle {
                         result = hook.after_agent(&result).await?;
@@ -228,17 +1318,104 @@ impl Agent {
                     return Ok(result);
                 }
                 ProviderResponse::ToolCalls(calls) => {
-                    // Execute each tool call
+                    let usage = reported.map(TurnUsage::from).unwrap_or_else(|| TurnUsage {
+                        prompt_tokens,
+                        completion_tokens: calls
+                            .iter()
+                            .map(|c| {
+                                estimate_tokens(&c.name) + estimate_tokens(&c.arguments.to_string())
+                            })
+                            .sum(),
+                        reasoning_tokens: 0,
+                    });
+                    self.meter(metered_user.as_deref(), &model, &usage).await?;
+
+                    if forces_tool {
+                        options.tool_choice = Some(ToolChoice::Auto);
+                    }
+
+                    // Check every call before running any
+                    let mut pending = Vec::with_capacity(calls.len());
                     for call in calls {
-                        let tool = self
-                            .tools
-                            .get(&call.name)
-                            .ok_or_else(|| format!("Tool '{}' not found", call.name))?;
+                        if cancel.is_cancelled() {
+                            return Err(AgentError::Cancelled.into());
+                        }
+
+                        if call.name == ESCALATE_TOOL && self.escalation.is_some() {
+                            let reason = call
+                                .arguments
+                                .get("reason")
+                                .and_then(|r| r.as_str())
+                                .unwrap_or("No reason given");
+                            self.escalate(reason, &messages).await?;
+
+                            let mut result = format!("Escalated to a human: {}", reason);
+                            for hook in &self.lifecycle {
+                                result = hook.after_agent(&result).await?;
+                            }
+                            return Ok(result);
+                        }
+
+                        tracker.record(turn, &call.name, &call.arguments)?;
+
+                        let tool = self.authorize_tool(&call).await?;
+                        pending.push((call, tool));
+                    }
 
-                        // Hook 5: wrap_tool_call - Wrap tool execution
-                        // Note: For now, hooks are called directly without complex chaining
-                        // to avoid lifetime issues with tool trait objects
-                        let result = tool.execute(call.arguments)?;
+                    // Hook 5: wrap_tool_call - Wrap tool execution
+                    // Note: For now, hooks are called directly without complex chaining
+                    // to avoid lifetime issues with tool trait objects
+                    // Run up to `tool_parallelism` calls at once, off the async
+                    // thread so a timeout can fire while tools run; results
+                    // come back in the order the model asked for them
+                    let mut runs = Vec::with_capacity(pending.len());
+                    for (call, tool) in pending {
+                        runs.push(execute_tool(events, call, tool));
+                    }
+                    let finished: Vec<_> = futures::stream::iter(runs)
+                        .buffered(self.tool_parallelism)
+                        .collect()
+                        .await;
+
+                    // Every call runs to completion; failures are reported
+                    // together once the turn's results are in
+                    let calls_run = finished.len();
+                    let mut failures = AggregateError::new();
+                    for (call, outcome, elapsed) in finished {
+                        let outcome = outcome.unwrap_or_else(|e| {
+                            Err(format!("Tool '{}' did not finish: {}", call.name, e).into())
+                        });
+                        if let Err(e) = &outcome {
+                            tracing::debug!(tool = %call.name, "Tool failed: {}", e);
+                        }
+                        emit(events, || AgentEvent::ToolCallFinished {
+                            name: call.name.clone(),
+                            output: outcome.as_ref().ok().cloned(),
+                            error: outcome.as_ref().err().map(|e| e.to_string()),
+                            duration_ms: elapsed.as_millis() as u64,
+                        });
+                        self.record_tool_call(&call, &outcome, elapsed)?;
+                        let branch = format!("{} ({})", call.name, call.id);
+                        let mut result = match outcome {
+                            Ok(result) => result,
+                            Err(e) => {
+                                failures.push_failure(branch, e);
+                                continue;
+                            }
+                        };
+                        failures.push_success(branch);
+                        // Hook 6: after_tool - Check or rewrite the tool's output
+                        for hook in &self.lifecycle {
+                            result = hook.after_tool(&call.name, &result).await?;
+                        }
+                        if let Some(limit) = &self.tool_output_limit {
+                            result = limit.apply(&call.name, result).await;
+                        }
+                        if let Some(guard) = &self.memory_guard {
+                            let admitted = guard.admit(&call.name, result)?;
+                            held.push((admitted.reservation, admitted.spill));
+                            result = admitted.content;
+                        }
 
                         // For simplicity in V1, we don't chain wrap_tool_call hooks
                         // due to complexity with trait object lifetimes.
@@ -251,16 +1428,59 @@ impl Agent {
                             call.name, result
                         )));
                     }
-                }
-            }
+                    if calls_run == 1 {
+                        if let Some(failure) = failures.failures.pop() {
+                            return Err(failure.error);
+                        }
+                    } else if !failures.failures.is_empty() {
+                        return Err(Box::new(failures));
+                    }
 
-            // If we got here, we had tool calls and need to continue the loop
-            if iteration == max_iterations - 1 {
-                return Err("Max tool calling iterations reached".into());
+                    emit(events, || AgentEvent::TurnFinished {
+                        turn,
+                        usage,
+                        duration_ms: turn_started.elapsed().as_millis() as u64,
+                    });
+                }
             }
         }
 
-        Err("Tool calling loop ended unexpectedly".into())
+        // Every turn asked for more tool calls
+        Err(tracker.exhausted().into())
+    }
+
+    /// Snapshot the agent's configuration as a manifest
+    ///
+    /// Use [`AgentManifest::to_markdown`](crate::manifest::AgentManifest::to_markdown)
+    /// to generate human-readable documentation from it.
+    pub fn manifest(&self) -> crate::manifest::AgentManifest {
+        crate::manifest::AgentManifest::from_agent(self)
+    }
+
+    /// Estimated prompt tokens by section for `input` after `history`
+    ///
+    /// Mirrors how a run assembles its first request; see
+    /// [`PromptBreakdown`](crate::tokens::PromptBreakdown).
+    pub fn prompt_breakdown(
+        &self,
+        history: &[Message],
+        input: Option<&str>,
+    ) -> crate::Result<crate::tokens::PromptBreakdown> {
+        let system = self.system_prompt()?;
+        let mut tools = self.tools.definitions();
+        let hidden = self.capability_warnings();
+        tools.retain(|def| !hidden.iter().any(|warning| warning.tool == def.name));
+        Ok(crate::tokens::PromptBreakdown::from_parts(
+            system.as_deref(),
+            &tools,
+            history,
+            input,
+        ))
+    }
+
+    /// Version, features, providers, tools and plugins of this agent's build
+    pub fn runtime_info(&self) -> crate::info::RuntimeInfo {
+        crate::info::runtime_info(self)
     }
 
     /// Run the agent with CLI interface
@@ -278,7 +1498,7 @@ pub fn create_agent(name: impl Into<String>) -> Agent {
 mod tests {
     use super::*;
     use crate::lifecycle::AgentLifecycle;
-    use crate::provider::MockProvider;
+    use crate::provider::{MockProvider, ProviderUsage};
     use async_trait::async_trait;
 
     #[test]
@@ -295,6 +1515,18 @@ mod tests {
         assert!(agent.tools.contains_key("hello"));
     }
 
+    #[test]
+    fn test_agent_with_namespaced_tool() {
+        let mut agent = create_agent("test").tool_in(
+            "fs",
+            crate::tool::FnTool::new("read", "Read", |_| Ok(String::new())),
+        );
+        assert!(agent.tools().contains_key("fs.read"));
+
+        agent.tools_mut().unregister("fs.read");
+        assert!(agent.tools().is_empty());
+    }
+
     #[tokio::test]
     async fn test_agent_with_mock_provider() {
         let agent =
@@ -511,6 +1743,1012 @@ mod tests {
         assert_eq!(before_agent_count, 2);
     }
 
+    // Provider that always asks to call one tool
+    struct ToolCallProvider {
+        name: String,
+        arguments: serde_json::Value,
+    }
+
+    #[async_trait]
+    impl LLMProvider for ToolCallProvider {
+        async fn complete(
+            &self,
+            _messages: Vec<Message>,
+            _tools: Vec<ToolDefinition>,
+        ) -> crate::provider::ProviderResult<ProviderResponse> {
+            Ok(ProviderResponse::ToolCalls(vec![
+                crate::provider::ToolCall {
+                    id: "call_1".to_string(),
+                    name: self.name.clone(),
+                    arguments: self.arguments.clone(),
+                },
+            ]))
+        }
+    }
+
+    fn recording_escalation(
+        log: Arc<Mutex<Vec<String>>>,
+    ) -> crate::escalation::CallbackEscalation<
+        impl Fn(&EscalationRequest) -> crate::Result<()> + Send + Sync,
+    > {
+        crate::escalation::CallbackEscalation::new(move |request: &EscalationRequest| {
+            log.lock().unwrap().push(request.reason.clone());
+            Ok(())
+        })
+    }
+
+    // TEST: Repeated hook rejections in a conversation escalate to a human
+    #[tokio::test]
+    async fn test_repeated_rejections_trigger_escalation() {
+        let escalations = Arc::new(Mutex::new(Vec::new()));
+        let agent = create_agent("test")
+            .with_provider(Box::new(MockProvider::new("response")))
+            .with_lifecycle(RejectHook)
+            .with_escalation(recording_escalation(escalations.clone()))
+            .with_escalation_threshold(2);
+
+        let in_session = || ExecutionContext::new().session("s1");
+        assert!(agent.run_with_context("test", in_session()).await.is_err());
+        assert!(escalations.lock().unwrap().is_empty());
+        // Another conversation has its own streak
+        let other = ExecutionContext::new().session("s2");
+        assert!(agent.run_with_context("test", other).await.is_err());
+        assert!(escalations.lock().unwrap().is_empty());
+
+        assert!(agent.run_with_context("test", in_session()).await.is_err());
+        assert_eq!(
+            *escalations.lock().unwrap(),
+            vec!["Rejected by hook (rejected 2 times in a row)"]
+        );
+    }
+
+    // TEST: Model can escalate explicitly through the escalate tool
+    #[tokio::test]
+    async fn test_escalate_tool_hands_off() {
+        let escalations = Arc::new(Mutex::new(Vec::new()));
+        let agent = create_agent("test")
+            .with_provider(Box::new(ToolCallProvider {
+                name: ESCALATE_TOOL.to_string(),
+                arguments: serde_json::json!({"reason": "legal question"}),
+            }))
+            .with_escalation(recording_escalation(escalations.clone()));
+
+        let result = agent.run("can I sue?").await.unwrap();
+        assert_eq!(result, "Escalated to a human: legal question");
+        assert_eq!(*escalations.lock().unwrap(), vec!["legal question"]);
+    }
+
+    // TEST: The loop guard stops a model repeating the same call
+    #[tokio::test]
+    async fn test_loop_guard_stops_repeated_calls() {
+        let agent = create_agent("test")
+            .tool_fn("search", "Search", |_| Ok("nothing".to_string()))
+            .with_provider(Box::new(ToolCallProvider {
+                name: "search".to_string(),
+                arguments: serde_json::json!({"q": "rust"}),
+            }))
+            .with_loop_guard(crate::loop_guard::LoopGuard::new().max_repeats(2));
+
+        let error = agent.run("find it").await.unwrap_err();
+        let detected = error
+            .downcast_ref::<crate::loop_guard::LoopDetected>()
+            .unwrap();
+        assert_eq!(detected.history.len(), 3);
+        assert_eq!(detected.history[2].turn, 3);
+        assert_eq!(
+            crate::error::recovery_strategy(error.as_ref()),
+            crate::error::RecoveryStrategy::Escalate
+        );
+    }
+
+    /// Asks for three slow tool calls at once, then answers
+    fn fan_out() -> Arc<MockProvider> {
+        let calls = ["300", "100", "200"]
+            .iter()
+            .map(|ms| crate::provider::ToolCall {
+                id: format!("call_{}", ms),
+                name: "sleep".to_string(),
+                arguments: serde_json::json!({"input": ms}),
+            })
+            .collect();
+        Arc::new(
+            MockProvider::scripted()
+                .then_tool_calls(calls)
+                .then_text("Slept."),
+        )
+    }
+
+    // TEST: A turn's tool calls run concurrently and keep their order
+    #[tokio::test]
+    async fn test_parallel_tool_calls() {
+        let agent = |provider: &Arc<MockProvider>, parallelism| {
+            create_agent("test")
+                .tool_fn("sleep", "Sleep for some milliseconds", |ms| {
+                    std::thread::sleep(Duration::from_millis(ms.parse().unwrap()));
+                    Ok(ms)
+                })
+                .with_provider(Box::new(provider.clone()))
+                .with_tool_parallelism(parallelism)
+        };
+
+        let provider = fan_out();
+        let started = std::time::Instant::now();
+        let events: Vec<AgentEvent> = agent(&provider, 3).execute_streaming("go").collect().await;
+        assert!(started.elapsed() < Duration::from_millis(550));
+        let finished: Vec<(String, u64)> = events
+            .iter()
+            .filter_map(|event| match event {
+                AgentEvent::ToolCallFinished {
+                    output,
+                    duration_ms,
+                    ..
+                } => Some((output.clone().unwrap(), *duration_ms)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            finished.iter().map(|(o, _)| o.as_str()).collect::<Vec<_>>(),
+            vec!["300", "100", "200"]
+        );
+        // Each call reports its own duration, not the time it waited
+        assert!(finished[1].1 < 250);
+        // The model sees the results in the order it asked for them
+        let results: Vec<String> = provider.requests()[1]
+            .iter()
+            .filter(|m| m.role == "assistant")
+            .map(|m| m.content.clone())
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                "Tool 'sleep' returned: 300",
+                "Tool 'sleep' returned: 100",
+                "Tool 'sleep' returned: 200"
+            ]
+        );
+        assert_eq!(
+            events.last(),
+            Some(&AgentEvent::Completed {
+                output: "Slept.".to_string()
+            })
+        );
+
+        let started = std::time::Instant::now();
+        agent(&fan_out(), 1).run("go").await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(600));
+    }
+
+    // TEST: Every failed call of a turn is reported, not just the first
+    #[tokio::test]
+    async fn test_parallel_tool_failures_are_aggregated() {
+        let agent = create_agent("test")
+            .tool_fn("sleep", "Sleep for some milliseconds", |ms| {
+                match ms.as_str() {
+                    "300" => Ok(ms),
+                    _ => Err(format!("no time for {}", ms).into()),
+                }
+            })
+            .with_provider(Box::new(fan_out()))
+            .with_tool_parallelism(3);
+
+        let error = agent.run("go").await.unwrap_err();
+        let aggregate = error.downcast_ref::<AggregateError>().unwrap();
+        assert_eq!(aggregate.succeeded, vec!["sleep (call_300)"]);
+        let failed: Vec<String> = aggregate
+            .failures
+            .iter()
+            .map(|f| format!("{}: {}", f.id, f.error))
+            .collect();
+        assert_eq!(
+            failed,
+            vec![
+                "sleep (call_100): no time for 100",
+                "sleep (call_200): no time for 200"
+            ]
+        );
+    }
+
+    // TEST: Tool results over the output limit are cut before the model sees them
+    #[tokio::test]
+    async fn test_tool_output_limit() {
+        let agent = create_agent("test")
+            .tool_fn("dump", "Dump everything", |_| Ok("x".repeat(400)))
+            .with_provider(Box::new(CallOnceProvider {
+                name: "dump".to_string(),
+            }))
+            .with_tool_output_limit(ToolOutputLimit::new(20));
+
+        let result = agent.run("go").await.unwrap();
+        assert!(result.starts_with("Tool 'dump' returned: xxxx"));
+        assert!(result.contains("[... ~80 of 100 tokens omitted ...]"));
+        assert!(estimate_tokens(&result) < 40);
+    }
+
+    // Calls `name` once, then answers with the last message it saw
+    struct CallOnceProvider {
+        name: String,
+    }
+
+    #[async_trait]
+    impl LLMProvider for CallOnceProvider {
+        async fn complete(
+            &self,
+            messages: Vec<Message>,
+            _tools: Vec<ToolDefinition>,
+        ) -> crate::provider::ProviderResult<ProviderResponse> {
+            let last = messages.last().unwrap();
+            if last.role == "user" {
+                Ok(ProviderResponse::ToolCalls(vec![
+                    crate::provider::ToolCall {
+                        id: "call_1".to_string(),
+                        name: self.name.clone(),
+                        arguments: serde_json::json!({}),
+                    },
+                ]))
+            } else {
+                Ok(ProviderResponse::Text(last.content.clone()))
+            }
+        }
+    }
+
+    struct DangerousTool;
+
+    impl Tool for DangerousTool {
+        fn name(&self) -> &str {
+            "wipe"
+        }
+        fn description(&self) -> &str {
+            "Delete everything"
+        }
+        fn dangerous(&self) -> bool {
+            true
+        }
+        fn execute(&self, _args: serde_json::Value) -> crate::tool::ToolResult {
+            Ok("wiped".to_string())
+        }
+    }
+
+    fn approval_gate(
+        approve: bool,
+    ) -> crate::approval::CallbackApproval<
+        impl Fn(
+                ApprovalRequest,
+            ) -> std::future::Ready<crate::Result<crate::approval::ApprovalDecision>>
+            + Send
+            + Sync,
+    > {
+        use crate::approval::{ApprovalDecision, CallbackApproval};
+        CallbackApproval::new(move |_| {
+            std::future::ready(Ok(if approve {
+                ApprovalDecision::approve()
+            } else {
+                ApprovalDecision::deny("too risky")
+            }))
+        })
+    }
+
+    // TEST: Dangerous tools wait for the approval gate
+    #[tokio::test]
+    async fn test_dangerous_tool_requires_approval() {
+        let agent = |approve| {
+            create_agent("test")
+                .tool(DangerousTool)
+                .with_provider(Box::new(CallOnceProvider {
+                    name: "wipe".to_string(),
+                }))
+                .with_approval_gate(approval_gate(approve))
+        };
+
+        let result = agent(true).run("clean up").await.unwrap();
+        assert!(result.contains("wiped"));
+
+        let err = agent(false).run("clean up").await.unwrap_err();
+        assert_eq!(err.to_string(), "Denied by human: too risky");
+    }
+
+    // TEST: Without a gate, dangerous tools fail closed unless opted out
+    #[tokio::test]
+    async fn test_dangerous_tool_without_gate_fails_closed() {
+        let agent = || {
+            create_agent("test")
+                .tool(DangerousTool)
+                .with_provider(Box::new(CallOnceProvider {
+                    name: "wipe".to_string(),
+                }))
+        };
+
+        let err = agent().run("clean up").await.unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Approval required but no approval gate is configured"));
+
+        let result = agent()
+            .allow_dangerous_without_approval()
+            .run("clean up")
+            .await
+            .unwrap();
+        assert!(result.contains("wiped"));
+    }
+
+    // TEST: Approvals and dangerous tool calls are audited
+    #[tokio::test]
+    async fn test_audit_log_records_decisions() {
+        let audit = AuditLog::new();
+        let agent = create_agent("test")
+            .tool(DangerousTool)
+            .with_provider(Box::new(CallOnceProvider {
+                name: "wipe".to_string(),
+            }))
+            .with_approval_gate(approval_gate(true))
+            .with_audit_log(audit.clone());
+        agent.run("clean up").await.unwrap();
+
+        let events: Vec<AuditEvent> = audit.entries().into_iter().map(|e| e.event).collect();
+        assert!(matches!(
+            &events[0],
+            AuditEvent::ApprovalDecided { tool: Some(tool), approved: true, .. } if tool == "wipe"
+        ));
+        assert_eq!(
+            events[1],
+            AuditEvent::DangerousToolExecuted {
+                tool: "wipe".to_string(),
+                arguments: serde_json::json!({}),
+                succeeded: true,
+            }
+        );
+        audit.verify().unwrap();
+    }
+
+    // TEST: Tools see the execution context of their run
+    #[tokio::test]
+    async fn test_execution_context_reaches_tools() {
+        let agent = create_agent("test")
+            .tool_fn("whoami", "Current user", |_| {
+                let context = ExecutionContext::current().unwrap_or_default();
+                Ok(format!(
+                    "{} on {}",
+                    context.user_id.as_deref().unwrap_or_default(),
+                    context.get("plan").unwrap_or_default()
+                ))
+            })
+            .with_provider(Box::new(CallOnceProvider {
+                name: "whoami".to_string(),
+            }));
+        let context = ExecutionContext::new().user("u-42").value("plan", "pro");
+        let output = agent
+            .run_with_context("who?", context.clone())
+            .await
+            .unwrap();
+        assert!(output.ends_with("u-42 on pro"));
+        // Runs without a user leave it empty
+        assert!(agent.run("who?").await.unwrap().ends_with(" on "));
+
+        let events: Vec<AgentEvent> = agent
+            .execute_streaming_in(context, Vec::new(), "who?")
+            .collect()
+            .await;
+        match &events[0] {
+            AgentEvent::RunStarted { context, .. } => {
+                assert_eq!(context.user_id.as_deref(), Some("u-42"));
+                assert!(!context.execution_id.is_empty());
+            }
+            other => panic!("unexpected first event {:?}", other),
+        }
+    }
+
+    // TEST: A hook can escalate to the approval gate, failing closed without one
+    struct EscalateHook;
+
+    #[async_trait]
+    impl AgentLifecycle for EscalateHook {
+        async fn after_model(&self, _: &ProviderResponse) -> crate::Result<HookAction> {
+            Ok(HookAction::Escalate("low confidence".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hook_escalation_uses_approval_gate() {
+        let approved = create_agent("test")
+            .with_provider(Box::new(MockProvider::new("response")))
+            .with_lifecycle(EscalateHook)
+            .with_approval_gate(approval_gate(true));
+        assert_eq!(approved.run("test").await.unwrap(), "response");
+
+        let ungated = create_agent("test")
+            .with_provider(Box::new(MockProvider::new("response")))
+            .with_lifecycle(EscalateHook);
+        assert!(ungated.run("test").await.is_err());
+    }
+
+    // Provider that never answers
+    struct HangingProvider;
+
+    #[async_trait]
+    impl LLMProvider for HangingProvider {
+        async fn complete(
+            &self,
+            _messages: Vec<Message>,
+            _tools: Vec<ToolDefinition>,
+        ) -> crate::provider::ProviderResult<ProviderResponse> {
+            std::future::pending().await
+        }
+    }
+
+    // TEST: Cancelling aborts an in-flight provider call
+    #[tokio::test]
+    async fn test_cancel_aborts_provider_call() {
+        let agent = create_agent("test").with_provider(Box::new(HangingProvider));
+        let cancel = CancellationToken::new();
+
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            trigger.cancel();
+        });
+
+        let err = agent.run_cancellable("hello", cancel).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<AgentError>(),
+            Some(&AgentError::Cancelled)
+        );
+    }
+
+    // TEST: A run past its timeout fails with a typed error, even inside a tool
+    #[tokio::test]
+    async fn test_timeout_covers_provider_and_tools() {
+        let limit = std::time::Duration::from_millis(30);
+
+        let hanging = create_agent("test")
+            .with_provider(Box::new(HangingProvider))
+            .with_timeout(limit);
+        let err = hanging.run("hello").await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<AgentError>(),
+            Some(&AgentError::Timeout(limit))
+        );
+
+        let slow_tool = create_agent("test")
+            .tool_fn("slow", "Sleeps", |_| {
+                std::thread::sleep(std::time::Duration::from_millis(300));
+                Ok("done".to_string())
+            })
+            .with_provider(Box::new(CallOnceProvider {
+                name: "slow".to_string(),
+            }))
+            .with_timeout(limit);
+        let started = std::time::Instant::now();
+        assert!(slow_tool.run("hello").await.is_err());
+        assert!(started.elapsed() < std::time::Duration::from_millis(250));
+    }
+
+    // TEST: Tool calls are recorded into the transcript
+    #[tokio::test]
+    async fn test_transcript_records_tool_calls() {
+        let transcript = ToolTranscript::new();
+        let agent = create_agent("test")
+            .tool_fn("hello", "Say hello", |_| Ok("hi there".to_string()))
+            .with_provider(Box::new(CallOnceProvider {
+                name: "hello".to_string(),
+            }))
+            .with_transcript(transcript.clone());
+
+        agent.run("greet").await.unwrap();
+        let records = transcript.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].tool, "hello");
+        assert_eq!(records[0].output, Ok("hi there".to_string()));
+        assert_eq!(transcript.input().as_deref(), Some("greet"));
+        assert!(transcript.answer().is_some());
+    }
+
+    // TEST: Running without a provider is an error, not a panic
+    #[tokio::test]
+    async fn test_run_without_provider_fails() {
+        let err = create_agent("test").run("hello").await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<AgentError>(),
+            Some(&AgentError::NoProvider)
+        );
+    }
+
+    // TEST: Runs beyond the concurrency limit and queue are rejected
+    #[tokio::test]
+    async fn test_concurrency_limit_rejects_overflow() {
+        let agent = Arc::new(
+            create_agent("test")
+                .with_provider(Box::new(HangingProvider))
+                .with_concurrency_limit(1, 0),
+        );
+        let cancel = CancellationToken::new();
+        let running = {
+            let agent = agent.clone();
+            let cancel = cancel.clone();
+            tokio::spawn(async move { agent.run_cancellable("first", cancel).await.is_err() })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        let err = agent.run("second").await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<AgentError>(),
+            Some(&AgentError::ResourceExhausted)
+        );
+
+        cancel.cancel();
+        assert!(running.await.unwrap());
+        assert_eq!(agent.admission_stats().unwrap().rejected, 1);
+    }
+
+    // TEST: Runs refused for lack of a slot don't count against the quota
+    #[tokio::test]
+    async fn test_rejected_runs_keep_their_quota() {
+        let tenancy = Tenancy::new(Arc::new(crate::tenancy::InMemoryQuotaStore::new()));
+        let agent = Arc::new(
+            create_agent("test")
+                .with_provider(Box::new(HangingProvider))
+                .with_concurrency_limit(1, 0)
+                .with_tenancy(tenancy),
+        );
+        let context = ExecutionContext::new().user("u-1");
+        let cancel = CancellationToken::new();
+        let running = {
+            let (agent, context, cancel) = (agent.clone(), context.clone(), cancel.clone());
+            tokio::spawn(async move {
+                let run = agent.run_with_context("first", context);
+                tokio::select! {
+                    _ = cancel.cancelled() => {}
+                    _ = run => {}
+                }
+            })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        let err = agent.run_with_context("second", context).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<AgentError>(),
+            Some(&AgentError::ResourceExhausted)
+        );
+        let (today, _) = agent.tenancy().unwrap().usage("u-1").await.unwrap();
+        assert_eq!(today.requests, 1);
+
+        cancel.cancel();
+        running.await.unwrap();
+    }
+
+    // Answers with the system prompt it was given
+    struct EchoSystemProvider;
+
+    #[async_trait]
+    impl LLMProvider for EchoSystemProvider {
+        async fn complete(
+            &self,
+            messages: Vec<Message>,
+            _tools: Vec<ToolDefinition>,
+        ) -> crate::provider::ProviderResult<ProviderResponse> {
+            Ok(ProviderResponse::Text(messages[0].content.clone()))
+        }
+    }
+
+    // TEST: System prompt templates see the agent's tools and caller vars
+    #[tokio::test]
+    async fn test_system_prompt_template() {
+        let template = PromptTemplate::parse(
+            "{{agent}} helps {{user}}.{{#each tools}} Tool: {{name}}.{{/each}}",
+        )
+        .unwrap();
+        let agent = create_agent("planner")
+            .tool_fn("calendar", "Check the calendar", |_| Ok(String::new()))
+            .with_provider(Box::new(EchoSystemProvider))
+            .system_prompt_template(template, serde_json::json!({"user": "Ada"}));
+
+        assert_eq!(
+            agent.run("hi").await.unwrap(),
+            "planner helps Ada. Tool: calendar."
+        );
+    }
+
+    // Reports how many messages it received
+    struct CountingProvider;
+
+    #[async_trait]
+    impl LLMProvider for CountingProvider {
+        async fn complete(
+            &self,
+            messages: Vec<Message>,
+            _tools: Vec<ToolDefinition>,
+        ) -> crate::provider::ProviderResult<ProviderResponse> {
+            let tool_results = messages.iter().filter(|m| m.role == "assistant").count();
+            if tool_results < 5 {
+                return Ok(ProviderResponse::ToolCalls(vec![
+                    crate::provider::ToolCall {
+                        id: "call".to_string(),
+                        name: "noop".to_string(),
+                        arguments: serde_json::json!({}),
+                    },
+                ]));
+            }
+            Ok(ProviderResponse::Text(messages.len().to_string()))
+        }
+    }
+
+    // TEST: The context manager trims the conversation between model calls
+    #[tokio::test]
+    async fn test_context_manager_trims_history() {
+        let run = |window| {
+            let mut agent = create_agent("test")
+                .tool_fn("noop", "Does nothing", |_| Ok("ok".to_string()))
+                .with_provider(Box::new(CountingProvider));
+            if let Some(window) = window {
+                agent =
+                    agent.with_context_manager(crate::context::SlidingWindow::new(window), 10_000);
+            }
+            agent
+        };
+
+        assert_eq!(run(None).run("go").await.unwrap(), "7");
+        // The window keeps the system prompt and the newest five messages,
+        // which are enough tool results to finish
+        assert_eq!(run(Some(5)).run("go").await.unwrap(), "6");
+    }
+
+    // TEST: Streaming reports turns, tool calls and the final answer in order
+    #[tokio::test]
+    async fn test_execute_streaming_events() {
+        let agent = create_agent("test")
+            .tool_fn("lookup", "Look something up", |_| Ok("42".to_string()))
+            .with_provider(Box::new(CallOnceProvider {
+                name: "lookup".to_string(),
+            }));
+
+        let events: Vec<AgentEvent> = agent.execute_streaming("question").collect().await;
+        let kinds: Vec<String> = events
+            .iter()
+            .map(|e| {
+                serde_json::to_value(e).unwrap()["type"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "run_started",
+                "turn_started",
+                "tool_call_started",
+                "tool_call_finished",
+                "turn_finished",
+                "turn_started",
+                "llm_delta",
+                "turn_finished",
+                "completed",
+            ]
+        );
+        assert!(matches!(
+            &events[3],
+            AgentEvent::ToolCallFinished { output: Some(o), error: None, .. } if o == "42"
+        ));
+        assert_eq!(
+            events.last(),
+            Some(&AgentEvent::Completed {
+                output: "Tool 'lookup' returned: 42".to_string()
+            })
+        );
+
+        let failing = create_agent("test").with_provider(Box::new(CallOnceProvider {
+            name: "missing".to_string(),
+        }));
+        let events: Vec<AgentEvent> = failing.execute_streaming("question").collect().await;
+        assert!(matches!(events.last(), Some(AgentEvent::Failed { .. })));
+    }
+
+    struct TranscriptProvider;
+
+    #[async_trait]
+    impl LLMProvider for TranscriptProvider {
+        async fn complete(
+            &self,
+            messages: Vec<Message>,
+            _tools: Vec<ToolDefinition>,
+        ) -> crate::provider::ProviderResult<ProviderResponse> {
+            let lines: Vec<String> = messages
+                .iter()
+                .map(|m| format!("{}: {}", m.role, m.content))
+                .collect();
+            Ok(ProviderResponse::Text(lines.join("\n")))
+        }
+    }
+
+    // TEST: Earlier turns are sent between the system prompt and the new input
+    #[tokio::test]
+    async fn test_run_with_history() {
+        let agent = Agent::new(AgentConfig::new("test").system_prompt("Be brief."))
+            .with_provider(Box::new(TranscriptProvider));
+        let history = vec![Message::user("hi"), Message::assistant("hello")];
+        assert_eq!(
+            agent.run_with_history(history, "again").await.unwrap(),
+            "system: Be brief.\nuser: hi\nassistant: hello\nuser: again"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_session() {
+        use crate::session::InMemorySessionStore;
+
+        let store = Arc::new(InMemorySessionStore::new());
+        let agent = create_agent("test")
+            .with_provider(Box::new(TranscriptProvider))
+            .with_session_store(store.clone());
+        let mut session = Session::new("test");
+        agent.run_session(&mut session, "hi").await.unwrap();
+        let second = agent.run_session(&mut session, "again").await.unwrap();
+
+        assert!(second.contains("user: hi\nassistant: "));
+        assert_eq!(session.messages.len(), 4);
+        assert_eq!((session.usage.runs, session.usage.model_calls), (2, 2));
+        let saved = store.load(&session.id).await.unwrap().unwrap();
+        assert_eq!(saved.messages.len(), 4);
+    }
+
+    // TEST: Switching models needs provider support
+    #[test]
+    fn test_set_model() {
+        let mut agent = create_agent("test");
+        agent.set_model("gpt-4.1").unwrap();
+        assert_eq!(agent.config.provider_config.model, "gpt-4.1");
+
+        let mut agent = agent.with_provider(Box::new(MockProvider::new("hi")));
+        assert!(agent.set_model("gpt-4o").is_err());
+        assert_eq!(agent.config.provider_config.model, "gpt-4.1");
+    }
+
+    /// Answers with the settings each request resolved to
+    struct ConfiguredProvider(ProviderConfig);
+
+    #[async_trait]
+    impl LLMProvider for ConfiguredProvider {
+        async fn complete(
+            &self,
+            messages: Vec<Message>,
+            tools: Vec<ToolDefinition>,
+        ) -> crate::provider::ProviderResult<ProviderResponse> {
+            self.complete_with_options(messages, tools, &RequestOptions::new())
+                .await
+        }
+
+        async fn complete_with_options(
+            &self,
+            _messages: Vec<Message>,
+            _tools: Vec<ToolDefinition>,
+            options: &RequestOptions,
+        ) -> crate::provider::ProviderResult<ProviderResponse> {
+            let config = self.0.resolve(options);
+            Ok(ProviderResponse::Text(format!(
+                "{:?} {} {:?}",
+                config.provider, config.model, config.temperature
+            )))
+        }
+
+        fn config(&self) -> Option<&ProviderConfig> {
+            Some(&self.0)
+        }
+    }
+
+    // TEST: Reported usage, reasoning included, wins over estimates
+    #[tokio::test]
+    async fn test_provider_usage_is_metered() {
+        let tenancy = Tenancy::new(Arc::new(crate::tenancy::InMemoryQuotaStore::new()));
+        let reasoning_model = MockProvider::new("4").usage(ProviderUsage {
+            prompt_tokens: 1_000,
+            completion_tokens: 900,
+            reasoning_tokens: 896,
+            ..Default::default()
+        });
+        let agent = create_agent("test")
+            .with_provider(Box::new(reasoning_model))
+            .with_tenancy(tenancy);
+
+        let context = ExecutionContext::new().user("u-1");
+        let events: Vec<AgentEvent> = agent
+            .execute_streaming_in(context, Vec::new(), "2 + 2?")
+            .collect()
+            .await;
+        let usage = events
+            .iter()
+            .find_map(|event| match event {
+                AgentEvent::TurnFinished { usage, .. } => Some(*usage),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            usage,
+            TurnUsage {
+                prompt_tokens: 1_000,
+                completion_tokens: 900,
+                reasoning_tokens: 896,
+            }
+        );
+
+        let (today, _) = agent.tenancy().unwrap().usage("u-1").await.unwrap();
+        assert_eq!(today.tokens, 1_900);
+    }
+
+    // TEST: A content policy stops a run whose tool returns blocked content
+    #[tokio::test]
+    async fn test_content_policy_checks_tool_output() {
+        use crate::hooks::{ContentPolicy, Severity};
+
+        let provider = Arc::new(
+            MockProvider::scripted()
+                .then_tool_call("lookup", serde_json::json!({}))
+                .then_text("done"),
+        );
+        let agent = create_agent("test")
+            .tool_fn("lookup", "Look it up", |_| {
+                Ok("the code is hunter2".to_string())
+            })
+            .with_lifecycle(ContentPolicy::new().deny_keywords(
+                "secrets",
+                Severity::High,
+                ["hunter2"],
+            ))
+            .with_provider(Box::new(provider.clone()));
+
+        let err = agent.run("What is the code?").await.unwrap_err();
+        assert!(err.to_string().contains("output of 'lookup'"));
+        assert_eq!(provider.calls(), 1);
+    }
+
+    // TEST: A forced tool choice holds for the first tool turn only
+    #[tokio::test]
+    async fn test_required_tool_choice_run_completes() {
+        let model = || {
+            Arc::new(
+                MockProvider::scripted()
+                    .then_tool_call("lookup", serde_json::json!({}))
+                    .then_text("The answer is 42."),
+            )
+        };
+        let sent_choices = |provider: &MockProvider| -> Vec<Option<ToolChoice>> {
+            provider
+                .options()
+                .into_iter()
+                .map(|options| options.tool_choice)
+                .collect()
+        };
+
+        let mut config = AgentConfig::new("test");
+        config.provider_config = config.provider_config.tool_choice(ToolChoice::Required);
+        let provider = model();
+        let agent = Agent::new(config)
+            .tool_fn("lookup", "Look it up", |_| Ok("42".to_string()))
+            .with_provider(Box::new(provider.clone()));
+        agent.run("What is the answer?").await.unwrap();
+        assert_eq!(sent_choices(&provider), vec![None, Some(ToolChoice::Auto)]);
+
+        let provider = model();
+        let agent = create_agent("test")
+            .tool_fn("lookup", "Look it up", |_| Ok("42".to_string()))
+            .with_provider(Box::new(provider.clone()));
+        let options = RequestOptions::new().tool_choice(ToolChoice::Required);
+        agent.run_with_options("hi", options).await.unwrap();
+        assert_eq!(
+            sent_choices(&provider),
+            vec![Some(ToolChoice::Required), Some(ToolChoice::Auto)]
+        );
+    }
+
+    // TEST: Request options cascade over the provider's config
+    #[tokio::test]
+    async fn test_run_with_options() {
+        let agent = create_agent("test")
+            .with_provider(Box::new(ConfiguredProvider(
+                ProviderConfig::new(Provider::OpenAI).model("gpt-4o"),
+            )))
+            .with_provider_for(
+                Provider::Ollama,
+                Box::new(ConfiguredProvider(ProviderConfig::new(Provider::Ollama))),
+            );
+
+        assert_eq!(agent.run("hi").await.unwrap(), "OpenAI gpt-4o Some(0.7)");
+        let options = RequestOptions::new().model("gpt-4.1").temperature(0.0);
+        assert_eq!(
+            agent.run_with_options("hi", options).await.unwrap(),
+            "OpenAI gpt-4.1 Some(0.0)"
+        );
+        let options = RequestOptions::new().provider(Provider::Ollama);
+        assert_eq!(
+            agent.run_with_options("hi", options).await.unwrap(),
+            "Ollama llama3.1:8b Some(0.7)"
+        );
+
+        let options = RequestOptions::new().provider(Provider::Anthropic);
+        let err = agent.run_with_options("hi", options).await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("No provider registered for Anthropic"));
+
+        let events: Vec<AgentEvent> = agent.execute_streaming("hi").collect().await;
+        match &events[0] {
+            AgentEvent::RunStarted { config, .. } => {
+                assert_eq!(config.provider, Provider::OpenAI);
+                assert_eq!(config.model, "gpt-4o");
+            }
+            other => panic!("unexpected first event {:?}", other),
+        }
+    }
+
+    // TEST: The date context is appended to the system prompt
+    #[cfg(feature = "timezones")]
+    #[tokio::test]
+    async fn test_date_context() {
+        let agent = Agent::new(AgentConfig::new("test").system_prompt("Be brief."))
+            .with_provider(Box::new(TranscriptProvider))
+            .with_date_context(
+                DateContext::new()
+                    .timezone(chrono_tz::Europe::Berlin)
+                    .locale("de-DE"),
+            );
+        let output = agent.run("when?").await.unwrap();
+        assert!(output.starts_with("system: Be brief.\n\nCurrent date and time: "));
+        assert!(output.contains("(Europe/Berlin, UTC+0"));
+        assert!(output.contains("The user's locale is de-DE.\nuser: when?"));
+    }
+
+    // TEST: Tools the model can't use are not offered to it
+    #[tokio::test]
+    async fn test_capability_gating() {
+        let provider = Arc::new(MockProvider::new("ok"));
+        let agent = create_agent("test")
+            .with_provider(Box::new(provider.clone()))
+            .tool_fn("a", "A", |_| Ok(String::new()))
+            .tool_fn("b", "B", |_| Ok(String::new()));
+        let offered = || -> Vec<String> {
+            let tools = provider.tools().pop().unwrap();
+            tools.into_iter().map(|tool| tool.name).collect()
+        };
+        agent.run("hi").await.unwrap();
+        assert_eq!(offered(), vec!["a", "b"]);
+        assert!(agent.capability_warnings().is_empty());
+
+        let agent = agent.with_capabilities(ModelCapabilities {
+            supports_tools: false,
+            ..Default::default()
+        });
+        agent.run("hi").await.unwrap();
+        assert!(offered().is_empty());
+        assert_eq!(agent.capability_warnings().len(), 2);
+    }
+
+    // TEST: Images reach vision models and are refused for the rest
+    #[tokio::test]
+    async fn test_run_with_images() {
+        let provider = Arc::new(MockProvider::new("a cat"));
+        let agent = create_agent("test")
+            .with_provider(Box::new(provider.clone()))
+            .with_capabilities(ModelCapabilities::default());
+        let image = ImageInput::url("https://example.com/cat.png");
+
+        let err = agent
+            .run_with_images("What is this?", vec![image.clone()])
+            .await
+            .unwrap_err();
+        let err = err.downcast_ref::<UnsupportedInput>().unwrap();
+        assert_eq!(err.capability, Capability::Vision);
+        assert_eq!(provider.calls(), 0);
+
+        let agent = agent.with_capabilities(ModelCapabilities {
+            supports_vision: true,
+            ..Default::default()
+        });
+        let answer = agent
+            .run_with_images("What is this?", vec![image.clone()])
+            .await
+            .unwrap();
+        assert_eq!(answer, "a cat");
+        let sent = provider.requests().pop().unwrap().pop().unwrap();
+        assert_eq!(sent.images, vec![image]);
+    }
+
     // TEST: Agent works without any hooks (regression test)
     #[tokio::test]
     async fn test_agent_works_without_hooks() {
@@ -0,0 +1,320 @@
+//! Human-in-the-loop approval
+//!
+//! An [`ApprovalGate`] pauses a run until a person decides whether it may
+//! continue. Agents with a gate ask it before executing any tool marked
+//! [`dangerous`](crate::tool::Tool::dangerous), and whenever a lifecycle hook
+//! returns [`HookAction::Escalate`](crate::lifecycle::HookAction::Escalate).
+//! An approved request resumes the run; a denied one aborts it with an error.
+//! Agents without a gate refuse dangerous tools outright unless built with
+//! [`Agent::allow_dangerous_without_approval`](crate::Agent::allow_dangerous_without_approval).
+//!
+//! Every decision is logged at `info` level for auditing.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::approval::CliApproval;
+//!
+//! let agent = create_agent("ops")
+//!     .tool(ShellTool::new().allow(["git"]))
+//!     .with_approval_gate(CliApproval::new());
+//! ```
+
+use crate::sanitize::sanitize_for_terminal;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::future::Future;
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+
+/// What a human is asked to approve
+#[derive(Debug, Clone, Serialize)]
+pub struct ApprovalRequest {
+    pub agent: String,
+    pub reason: String,
+    /// The tool about to run, if the request is for a tool call
+    pub tool: Option<String>,
+    pub arguments: Value,
+}
+
+impl ApprovalRequest {
+    /// One-line description for prompts and logs
+    pub fn summary(&self) -> String {
+        match &self.tool {
+            Some(tool) => format!(
+                "Agent '{}' wants to run '{}' with {} ({})",
+                self.agent, tool, self.arguments, self.reason
+            ),
+            None => format!("Agent '{}' needs approval: {}", self.agent, self.reason),
+        }
+    }
+}
+
+/// A human's answer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApprovalDecision {
+    pub approved: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl ApprovalDecision {
+    pub fn approve() -> Self {
+        Self {
+            approved: true,
+            reason: None,
+        }
+    }
+
+    pub fn deny(reason: impl Into<String>) -> Self {
+        Self {
+            approved: false,
+            reason: Some(reason.into()),
+        }
+    }
+}
+
+/// A channel that asks a human to approve or deny
+#[async_trait]
+pub trait ApprovalGate: Send + Sync {
+    /// Wait for a decision
+    async fn request_approval(&self, request: &ApprovalRequest) -> crate::Result<ApprovalDecision>;
+}
+
+/// Asks on the terminal (prompt on stderr, answer on stdin)
+///
+/// Answering `a` approves the call and every later call of the same tool
+/// for as long as this gate (and its clones) lives.
+#[derive(Debug, Clone, Default)]
+pub struct CliApproval {
+    always_allowed: Arc<Mutex<HashSet<String>>>,
+}
+
+impl CliApproval {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// A typed answer to the terminal prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Answer {
+    Yes,
+    No,
+    /// Yes, and don't ask again for this tool
+    Always,
+}
+
+/// Interpret a typed answer; `None` if it should be asked again
+pub(crate) fn parse_answer(answer: &str) -> Option<Answer> {
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Some(Answer::Yes),
+        "n" | "no" | "" => Some(Answer::No),
+        "a" | "always" => Some(Answer::Always),
+        _ => None,
+    }
+}
+
+/// The prompt shown for a request; arguments come from the model, so they
+/// are sanitized before reaching the terminal
+fn cli_prompt(request: &ApprovalRequest) -> String {
+    if let Some(tool) = &request.tool {
+        let mut prompt = sanitize_for_terminal(&format!(
+            "Agent '{}' wants to run '{}' ({}) with:",
+            request.agent, tool, request.reason
+        ));
+        let arguments =
+            serde_json::to_string_pretty(&request.arguments).unwrap_or_else(|_| "{}".into());
+        for line in sanitize_for_terminal(&arguments).lines() {
+            prompt.push_str("\n    ");
+            prompt.push_str(line);
+        }
+        prompt.push_str("\nApprove? [y]es / [N]o / [a]lways for this session: ");
+        prompt
+    } else {
+        format!(
+            "{}\nApprove? [y/N] ",
+            sanitize_for_terminal(&request.summary())
+        )
+    }
+}
+
+#[async_trait]
+impl ApprovalGate for CliApproval {
+    async fn request_approval(&self, request: &ApprovalRequest) -> crate::Result<ApprovalDecision> {
+        if let Some(tool) = &request.tool {
+            if self.always_allowed.lock().unwrap().contains(tool) {
+                return Ok(ApprovalDecision::approve());
+            }
+        }
+
+        let prompt = cli_prompt(request);
+        let answer = tokio::task::spawn_blocking(move || -> std::io::Result<Answer> {
+            let stdin = std::io::stdin();
+            let mut stderr = std::io::stderr();
+            loop {
+                write!(stderr, "{}", prompt)?;
+                stderr.flush()?;
+                let mut line = String::new();
+                if stdin.lock().read_line(&mut line)? == 0 {
+                    return Ok(Answer::No);
+                }
+                if let Some(answer) = parse_answer(&line) {
+                    return Ok(answer);
+                }
+            }
+        })
+        .await??;
+
+        Ok(match answer {
+            Answer::Yes => ApprovalDecision::approve(),
+            Answer::Always => {
+                if let Some(tool) = &request.tool {
+                    self.always_allowed.lock().unwrap().insert(tool.clone());
+                }
+                ApprovalDecision::approve()
+            }
+            Answer::No => ApprovalDecision::deny("Denied at the terminal"),
+        })
+    }
+}
+
+/// POSTs the request as JSON and reads an [`ApprovalDecision`] from the response
+///
+/// The endpoint is expected to hold the request open until a human answers.
+pub struct WebhookApproval {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl WebhookApproval {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl ApprovalGate for WebhookApproval {
+    async fn request_approval(&self, request: &ApprovalRequest) -> crate::Result<ApprovalDecision> {
+        let decision = self
+            .client
+            .post(&self.url)
+            .json(request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(decision)
+    }
+}
+
+/// Hands approval requests to an async closure (chat UIs, queues, tests)
+pub struct CallbackApproval<F> {
+    callback: F,
+}
+
+impl<F, Fut> CallbackApproval<F>
+where
+    F: Fn(ApprovalRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = crate::Result<ApprovalDecision>> + Send,
+{
+    pub fn new(callback: F) -> Self {
+        Self { callback }
+    }
+}
+
+#[async_trait]
+impl<F, Fut> ApprovalGate for CallbackApproval<F>
+where
+    F: Fn(ApprovalRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = crate::Result<ApprovalDecision>> + Send,
+{
+    async fn request_approval(&self, request: &ApprovalRequest) -> crate::Result<ApprovalDecision> {
+        (self.callback)(request.clone()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request() -> ApprovalRequest {
+        ApprovalRequest {
+            agent: "ops".to_string(),
+            reason: "tool is marked dangerous".to_string(),
+            tool: Some("shell".to_string()),
+            arguments: json!({"command": "git"}),
+        }
+    }
+
+    #[test]
+    fn test_parse_answer() {
+        assert_eq!(parse_answer("Y\n"), Some(Answer::Yes));
+        assert_eq!(parse_answer("no"), Some(Answer::No));
+        assert_eq!(parse_answer("\n"), Some(Answer::No));
+        assert_eq!(parse_answer("a"), Some(Answer::Always));
+        assert_eq!(parse_answer("maybe"), None);
+    }
+
+    #[tokio::test]
+    async fn test_cli_prompt_and_always_allow() {
+        let mut request = request();
+        request.arguments = json!({"command": "git \u{202e}push"});
+        let prompt = cli_prompt(&request);
+        assert!(prompt.starts_with(
+            "Agent 'ops' wants to run 'shell' (tool is marked dangerous) with:\n    {\n"
+        ));
+        assert!(prompt.contains("\n      \"command\": \"git push\""));
+        assert!(prompt.ends_with("[a]lways for this session: "));
+
+        // Already allowed tools are approved without reading stdin
+        let gate = CliApproval::new();
+        gate.always_allowed
+            .lock()
+            .unwrap()
+            .insert("shell".to_string());
+        let decision = gate.clone().request_approval(&request).await.unwrap();
+        assert!(decision.approved);
+    }
+
+    #[test]
+    fn test_summary_names_tool() {
+        assert!(request().summary().contains("run 'shell'"));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_reads_decision() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/approve")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"agent":"ops","tool":"shell"}"#.to_string(),
+            ))
+            .with_body(r#"{"approved": false, "reason": "not today"}"#)
+            .create_async()
+            .await;
+
+        let gate = WebhookApproval::new(format!("{}/approve", server.url()));
+        let decision = gate.request_approval(&request()).await.unwrap();
+        assert_eq!(decision, ApprovalDecision::deny("not today"));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_callback_gate() {
+        let gate = CallbackApproval::new(|request: ApprovalRequest| async move {
+            Ok(if request.tool.as_deref() == Some("shell") {
+                ApprovalDecision::approve()
+            } else {
+                ApprovalDecision::deny("unknown tool")
+            })
+        });
+        assert!(gate.request_approval(&request()).await.unwrap().approved);
+    }
+}
@@ -0,0 +1,362 @@
+//! Tamper-evident audit log
+//!
+//! An [`AuditLog`] records the security-relevant decisions of every run:
+//! lifecycle hooks approving or rejecting a response, approval-gate
+//! outcomes, escalations to a human and each execution of a dangerous tool.
+//! Entries are hash-chained: each carries the SHA-256 of its content and of
+//! the entry before it, so editing, removing or reordering any entry breaks
+//! [`verify`](AuditLog::verify) from that point on.
+//!
+//! The log lives in memory and, with [`AuditLog::open`], is also appended to
+//! a JSONL file one entry per line. Reopening the file verifies it and
+//! continues the chain. Export with [`to_jsonl`](AuditLog::to_jsonl) and
+//! check an exported file with [`verify_jsonl`].
+//!
+//! # Example
+//! ```ignore
+//! use patinox::audit::AuditLog;
+//!
+//! let audit = AuditLog::open("audit.jsonl")?;
+//! let agent = create_agent("ops")
+//!     .with_approval(CliApproval::new())
+//!     .with_audit_log(audit.clone());
+//! agent.run("rotate the logs").await?;
+//!
+//! audit.verify()?;
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// `prev_hash` of the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// A security-relevant decision
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// A lifecycle hook explicitly approved the model's response
+    HookApproved,
+    /// A lifecycle hook rejected the model's response
+    HookRejected { reason: String },
+    /// An approval gate decided on a request
+    ApprovalDecided {
+        reason: String,
+        tool: Option<String>,
+        arguments: Value,
+        approved: bool,
+        /// Why the gate decided as it did, if it said
+        note: Option<String>,
+    },
+    /// The run was handed to a human
+    Escalated { reason: String },
+    /// A tool marked [`dangerous`](crate::tool::Tool::dangerous) ran
+    DangerousToolExecuted {
+        tool: String,
+        arguments: Value,
+        succeeded: bool,
+    },
+}
+
+/// One link of the chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position in the log, counting from 0
+    pub sequence: u64,
+    pub timestamp: DateTime<Utc>,
+    pub agent: String,
+    pub event: AuditEvent,
+    /// Hash of the previous entry
+    pub prev_hash: String,
+    /// SHA-256 of `prev_hash` and this entry's content, hex encoded
+    pub hash: String,
+}
+
+/// The hashed part of an entry
+#[derive(Serialize)]
+struct Content<'a> {
+    sequence: u64,
+    timestamp: &'a DateTime<Utc>,
+    agent: &'a str,
+    event: &'a AuditEvent,
+}
+
+impl AuditEntry {
+    fn compute_hash(&self) -> String {
+        let content = Content {
+            sequence: self.sequence,
+            timestamp: &self.timestamp,
+            agent: &self.agent,
+            event: &self.event,
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(self.prev_hash.as_bytes());
+        hasher.update(serde_json::to_vec(&content).unwrap_or_default());
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+/// Where and why a chain stopped verifying
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditTampered {
+    /// Sequence number (or line index) of the first bad entry
+    pub sequence: u64,
+    pub reason: String,
+}
+
+impl fmt::Display for AuditTampered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Audit log fails verification at entry {}: {}",
+            self.sequence, self.reason
+        )
+    }
+}
+
+impl std::error::Error for AuditTampered {}
+
+/// Check that `entries` form an unbroken chain from the start
+pub fn verify_entries(entries: &[AuditEntry]) -> Result<(), AuditTampered> {
+    let mut prev_hash = GENESIS_HASH;
+    for (index, entry) in entries.iter().enumerate() {
+        let tampered = |reason: &str| AuditTampered {
+            sequence: index as u64,
+            reason: reason.to_string(),
+        };
+        if entry.sequence != index as u64 {
+            return Err(tampered("sequence number out of order"));
+        }
+        if entry.prev_hash != prev_hash {
+            return Err(tampered("previous hash does not match"));
+        }
+        if entry.hash != entry.compute_hash() {
+            return Err(tampered("content does not match its hash"));
+        }
+        prev_hash = &entry.hash;
+    }
+    Ok(())
+}
+
+/// Parse and verify an exported JSONL log
+pub fn verify_jsonl(jsonl: &str) -> Result<Vec<AuditEntry>, AuditTampered> {
+    let entries = jsonl
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| AuditTampered {
+                sequence: index as u64,
+                reason: format!("unreadable entry: {}", e),
+            })
+        })
+        .collect::<Result<Vec<AuditEntry>, _>>()?;
+    verify_entries(&entries)?;
+    Ok(entries)
+}
+
+#[derive(Debug, Default)]
+struct Chain {
+    entries: Vec<AuditEntry>,
+    file: Option<File>,
+}
+
+/// Shared, append-only, hash-chained log (clones record into the same log)
+#[derive(Debug, Clone, Default)]
+pub struct AuditLog {
+    chain: Arc<Mutex<Chain>>,
+}
+
+impl AuditLog {
+    /// A log kept in memory only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Continue the log in the JSONL file at `path`, creating it if missing
+    ///
+    /// Fails if the existing file doesn't verify.
+    pub fn open(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let entries = match std::fs::read_to_string(path) {
+            Ok(jsonl) => verify_jsonl(&jsonl)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            chain: Arc::new(Mutex::new(Chain {
+                entries,
+                file: Some(file),
+            })),
+        })
+    }
+
+    /// Append `event`, writing it through to the file if there is one
+    pub fn record(&self, agent: &str, event: AuditEvent) -> crate::Result<AuditEntry> {
+        let mut chain = self.chain.lock().unwrap();
+        let prev_hash = chain
+            .entries
+            .last()
+            .map_or(GENESIS_HASH.to_string(), |entry| entry.hash.clone());
+        let mut entry = AuditEntry {
+            sequence: chain.entries.len() as u64,
+            timestamp: Utc::now(),
+            agent: agent.to_string(),
+            event,
+            prev_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+
+        if let Some(file) = &mut chain.file {
+            let mut line = serde_json::to_string(&entry)?;
+            line.push('\n');
+            file.write_all(line.as_bytes())?;
+            file.flush()?;
+        }
+        chain.entries.push(entry.clone());
+        Ok(entry)
+    }
+
+    /// Entries recorded so far, oldest first
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        self.chain.lock().unwrap().entries.clone()
+    }
+
+    /// Check the chain in memory
+    pub fn verify(&self) -> Result<(), AuditTampered> {
+        verify_entries(&self.chain.lock().unwrap().entries)
+    }
+
+    /// Every entry as one JSON object per line
+    pub fn to_jsonl(&self) -> String {
+        self.entries()
+            .iter()
+            .filter_map(|entry| serde_json::to_string(entry).ok())
+            .map(|line| line + "\n")
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample_log() -> AuditLog {
+        let log = AuditLog::new();
+        log.record(
+            "ops",
+            AuditEvent::ApprovalDecided {
+                reason: "tool is marked dangerous".to_string(),
+                tool: Some("shell".to_string()),
+                arguments: json!({"command": "rm -rf /tmp/cache"}),
+                approved: true,
+                note: None,
+            },
+        )
+        .unwrap();
+        log.record(
+            "ops",
+            AuditEvent::DangerousToolExecuted {
+                tool: "shell".to_string(),
+                arguments: json!({"command": "rm -rf /tmp/cache"}),
+                succeeded: true,
+            },
+        )
+        .unwrap();
+        log.record(
+            "ops",
+            AuditEvent::HookRejected {
+                reason: "contains a secret".to_string(),
+            },
+        )
+        .unwrap();
+        log
+    }
+
+    #[test]
+    fn test_chain_detects_tampering() {
+        let log = sample_log();
+        log.verify().unwrap();
+        let entries = log.entries();
+        assert_eq!(entries[0].prev_hash, GENESIS_HASH);
+        assert_eq!(entries[1].prev_hash, entries[0].hash);
+
+        let jsonl = log.to_jsonl();
+        assert_eq!(verify_jsonl(&jsonl).unwrap(), entries);
+
+        let edited = jsonl.replace("rm -rf /tmp/cache", "ls");
+        assert_eq!(
+            verify_jsonl(&edited).unwrap_err(),
+            AuditTampered {
+                sequence: 0,
+                reason: "content does not match its hash".to_string()
+            }
+        );
+
+        let mut lines: Vec<&str> = jsonl.lines().collect();
+        lines.remove(1);
+        let dropped = verify_jsonl(&lines.join("\n")).unwrap_err();
+        assert_eq!(dropped.sequence, 1);
+        assert_eq!(dropped.reason, "sequence number out of order");
+    }
+
+    #[test]
+    fn test_file_log_continues_chain() {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()));
+        let log = AuditLog::open(&path).unwrap();
+        log.record("ops", AuditEvent::HookApproved).unwrap();
+        drop(log);
+
+        let reopened = AuditLog::open(&path).unwrap();
+        let entry = reopened
+            .record(
+                "ops",
+                AuditEvent::Escalated {
+                    reason: "legal question".to_string(),
+                },
+            )
+            .unwrap();
+        assert_eq!(entry.sequence, 1);
+        let on_disk = verify_jsonl(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(on_disk, reopened.entries());
+
+        std::fs::write(&path, "not json\n").unwrap();
+        assert!(AuditLog::open(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_file_log_reopens_with_float_arguments() {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()));
+        let log = AuditLog::open(&path).unwrap();
+        log.record(
+            "ops",
+            AuditEvent::DangerousToolExecuted {
+                tool: "transfer".to_string(),
+                arguments: json!({"amount": 212.91890726713459_f64}),
+                succeeded: true,
+            },
+        )
+        .unwrap();
+        drop(log);
+
+        let reopened = AuditLog::open(&path).unwrap();
+        reopened.verify().unwrap();
+        assert_eq!(reopened.entries().len(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,365 @@
+//! Agent-to-agent message bus
+//!
+//! Typed publish/subscribe topics for wiring agents into pipelines
+//! (researcher → writer → reviewer) without hand-rolled channel plumbing.
+//!
+//! Every subscriber gets its own bounded queue. When a queue is full the
+//! topic's [`Backpressure`] policy decides whether the publisher waits, the
+//! message is dropped for that subscriber, or the publish fails. Observers
+//! registered with [`MessageBus::observe`] see a [`BusEvent`] for every
+//! published message, which is the hook for monitoring.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::bus::{MessageBus, Topic};
+//!
+//! let bus = MessageBus::new();
+//! let questions = Topic::<String>::new("questions");
+//! let drafts = Topic::<String>::new("drafts");
+//!
+//! bus.connect_agent(Arc::new(researcher), questions.clone(), drafts.clone())?;
+//! let mut output = bus.subscribe(&drafts)?;
+//!
+//! bus.publish(&questions, "Why is the sky blue?".to_string()).await?;
+//! let draft = output.recv().await;
+//! ```
+
+use crate::Agent;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Default per-subscriber queue size
+const DEFAULT_CAPACITY: usize = 64;
+
+/// What publishing does when a subscriber's queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Wait for space (slow consumers slow the publisher)
+    Block,
+    /// Drop the message for that subscriber
+    DropNewest,
+    /// Fail the publish with [`BusError::Full`]
+    Fail,
+}
+
+/// Error publishing or subscribing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BusError {
+    /// The topic name is already in use with a different message type
+    TypeMismatch(String),
+    /// A subscriber's queue was full under [`Backpressure::Fail`]
+    Full(String),
+}
+
+impl fmt::Display for BusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusError::TypeMismatch(topic) => {
+                write!(f, "Topic '{}' is registered with a different type", topic)
+            }
+            BusError::Full(topic) => write!(f, "A subscriber of '{}' is full", topic),
+        }
+    }
+}
+
+impl std::error::Error for BusError {}
+
+/// A named topic carrying messages of type `T`
+pub struct Topic<T> {
+    name: String,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> Topic<T> {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            _type: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<T> Clone for Topic<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.name.clone())
+    }
+}
+
+/// Delivery report for one published message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusEvent {
+    pub topic: String,
+    pub delivered: usize,
+    pub dropped: usize,
+}
+
+/// Receiving end of a subscription
+pub struct Subscription<T> {
+    receiver: mpsc::Receiver<T>,
+}
+
+impl<T> Subscription<T> {
+    /// Wait for the next message (`None` once the bus is gone)
+    pub async fn recv(&mut self) -> Option<T> {
+        self.receiver.recv().await
+    }
+
+    /// Take a message if one is waiting
+    pub fn try_recv(&mut self) -> Option<T> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+type Observer = Arc<dyn Fn(&BusEvent) + Send + Sync>;
+
+/// Publish/subscribe hub shared by agents
+pub struct MessageBus {
+    topics: Mutex<HashMap<String, Box<dyn Any + Send>>>,
+    policies: Mutex<HashMap<String, (usize, Backpressure)>>,
+    observers: Mutex<Vec<Observer>>,
+}
+
+impl MessageBus {
+    /// Create a bus with default capacity and blocking backpressure
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            topics: Mutex::new(HashMap::new()),
+            policies: Mutex::new(HashMap::new()),
+            observers: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Set the queue size and backpressure policy for a topic's future subscribers
+    pub fn configure<T>(&self, topic: &Topic<T>, capacity: usize, backpressure: Backpressure) {
+        self.policies
+            .lock()
+            .unwrap()
+            .insert(topic.name.clone(), (capacity.max(1), backpressure));
+    }
+
+    fn policy(&self, topic: &str) -> (usize, Backpressure) {
+        self.policies
+            .lock()
+            .unwrap()
+            .get(topic)
+            .copied()
+            .unwrap_or((DEFAULT_CAPACITY, Backpressure::Block))
+    }
+
+    /// Call `observer` for every published message
+    pub fn observe(&self, observer: impl Fn(&BusEvent) + Send + Sync + 'static) {
+        self.observers.lock().unwrap().push(Arc::new(observer));
+    }
+
+    /// Subscribe to a topic
+    pub fn subscribe<T: Clone + Send + 'static>(
+        &self,
+        topic: &Topic<T>,
+    ) -> Result<Subscription<T>, BusError> {
+        let (capacity, _) = self.policy(&topic.name);
+        let (sender, receiver) = mpsc::channel(capacity);
+
+        let mut topics = self.topics.lock().unwrap();
+        let senders = topics
+            .entry(topic.name.clone())
+            .or_insert_with(|| Box::new(Vec::<mpsc::Sender<T>>::new()))
+            .downcast_mut::<Vec<mpsc::Sender<T>>>()
+            .ok_or_else(|| BusError::TypeMismatch(topic.name.clone()))?;
+        senders.push(sender);
+
+        Ok(Subscription { receiver })
+    }
+
+    fn senders<T: Send + 'static>(&self, topic: &str) -> Result<Vec<mpsc::Sender<T>>, BusError> {
+        let topics = self.topics.lock().unwrap();
+        match topics.get(topic) {
+            None => Ok(Vec::new()),
+            Some(entry) => entry
+                .downcast_ref::<Vec<mpsc::Sender<T>>>()
+                .cloned()
+                .ok_or_else(|| BusError::TypeMismatch(topic.to_string())),
+        }
+    }
+
+    /// Publish a message to every subscriber, returning how many received it
+    pub async fn publish<T: Clone + Send + 'static>(
+        &self,
+        topic: &Topic<T>,
+        message: T,
+    ) -> Result<usize, BusError> {
+        let (_, backpressure) = self.policy(&topic.name);
+        let senders = self.senders::<T>(&topic.name)?;
+
+        let mut delivered = 0;
+        let mut dropped = 0;
+        let mut closed = false;
+        for sender in senders {
+            let outcome = match backpressure {
+                Backpressure::Block => sender.send(message.clone()).await.map_err(|_| None),
+                Backpressure::DropNewest | Backpressure::Fail => {
+                    sender.try_send(message.clone()).map_err(|e| match e {
+                        mpsc::error::TrySendError::Full(_) => Some(()),
+                        mpsc::error::TrySendError::Closed(_) => None,
+                    })
+                }
+            };
+            match outcome {
+                Ok(()) => delivered += 1,
+                Err(Some(())) if backpressure == Backpressure::Fail => {
+                    return Err(BusError::Full(topic.name.clone()));
+                }
+                Err(Some(())) => dropped += 1,
+                Err(None) => closed = true,
+            }
+        }
+
+        if closed {
+            self.prune::<T>(&topic.name);
+        }
+
+        let event = BusEvent {
+            topic: topic.name.clone(),
+            delivered,
+            dropped,
+        };
+        tracing::debug!(
+            "bus: '{}' delivered to {}, dropped for {}",
+            event.topic,
+            delivered,
+            dropped
+        );
+        let observers = self.observers.lock().unwrap().clone();
+        for observer in observers {
+            observer(&event);
+        }
+
+        Ok(delivered)
+    }
+
+    /// Forget subscribers that have been dropped
+    fn prune<T: Send + 'static>(&self, topic: &str) {
+        let mut topics = self.topics.lock().unwrap();
+        if let Some(senders) = topics
+            .get_mut(topic)
+            .and_then(|entry| entry.downcast_mut::<Vec<mpsc::Sender<T>>>())
+        {
+            senders.retain(|sender| !sender.is_closed());
+        }
+    }
+
+    /// Run `agent` on every message from `input`, publishing results to `output`
+    ///
+    /// Failed runs are logged and skipped. The task ends when the bus is dropped.
+    pub fn connect_agent(
+        self: &Arc<Self>,
+        agent: Arc<Agent>,
+        input: Topic<String>,
+        output: Topic<String>,
+    ) -> Result<JoinHandle<()>, BusError> {
+        let mut subscription = self.subscribe(&input)?;
+        let bus = Arc::downgrade(self);
+
+        Ok(tokio::spawn(async move {
+            while let Some(message) = subscription.recv().await {
+                let result = match agent.run(message).await {
+                    Ok(result) => result,
+                    Err(e) => {
+                        tracing::warn!("bus: agent on '{}' failed: {}", input.name, e);
+                        continue;
+                    }
+                };
+                let Some(bus) = bus.upgrade() else {
+                    break;
+                };
+                if let Err(e) = bus.publish(&output, result).await {
+                    tracing::warn!("bus: publishing to '{}' failed: {}", output.name, e);
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_agent;
+    use crate::provider::MockProvider;
+
+    #[tokio::test]
+    async fn test_publish_fans_out_to_subscribers() {
+        let bus = MessageBus::new();
+        let topic = Topic::<u32>::new("numbers");
+        let mut a = bus.subscribe(&topic).unwrap();
+        let mut b = bus.subscribe(&topic).unwrap();
+
+        assert_eq!(bus.publish(&topic, 7).await.unwrap(), 2);
+        assert_eq!(a.recv().await, Some(7));
+        assert_eq!(b.recv().await, Some(7));
+
+        drop(b);
+        assert_eq!(bus.publish(&topic, 8).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_topic_types_are_enforced() {
+        let bus = MessageBus::new();
+        let _numbers = bus.subscribe(&Topic::<u32>::new("shared")).unwrap();
+        let strings = Topic::<String>::new("shared");
+
+        assert!(matches!(
+            bus.subscribe(&strings),
+            Err(BusError::TypeMismatch(_))
+        ));
+        assert!(bus.publish(&strings, "x".to_string()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_backpressure_policies_and_observer() {
+        let bus = MessageBus::new();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        bus.observe(move |event| seen.lock().unwrap().push(event.clone()));
+
+        let lossy = Topic::<u8>::new("lossy");
+        bus.configure(&lossy, 1, Backpressure::DropNewest);
+        let _slow = bus.subscribe(&lossy).unwrap();
+        bus.publish(&lossy, 1).await.unwrap();
+        assert_eq!(bus.publish(&lossy, 2).await.unwrap(), 0);
+        assert_eq!(events.lock().unwrap()[1].dropped, 1);
+
+        let strict = Topic::<u8>::new("strict");
+        bus.configure(&strict, 1, Backpressure::Fail);
+        let _slow = bus.subscribe(&strict).unwrap();
+        bus.publish(&strict, 1).await.unwrap();
+        assert_eq!(
+            bus.publish(&strict, 2).await,
+            Err(BusError::Full("strict".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_agent_pipeline() {
+        let bus = MessageBus::new();
+        let questions = Topic::<String>::new("questions");
+        let answers = Topic::<String>::new("answers");
+
+        let agent = create_agent("researcher").with_provider(Box::new(MockProvider::new("42")));
+        bus.connect_agent(Arc::new(agent), questions.clone(), answers.clone())
+            .unwrap();
+        let mut output = bus.subscribe(&answers).unwrap();
+
+        bus.publish(&questions, "meaning of life?".to_string())
+            .await
+            .unwrap();
+        assert_eq!(output.recv().await.as_deref(), Some("42"));
+    }
+}
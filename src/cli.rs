@@ -1,10 +1,28 @@
 //! CLI interface for Patinox agents
 //!
-//! Provides command-line argument parsing and execution for agents.
+//! Provides command-line argument parsing and execution for agents. With no
+//! arguments and an interactive terminal (or with `--chat`), the agent runs
+//! as a multi-turn chat REPL with slash commands. If the agent has dangerous
+//! tools and no approval gate, the REPL asks on the terminal before each
+//! dangerous call (see [`CliApproval`]). With a session store
+//! ([`Agent::with_session_store`]), every chat turn is saved and earlier
+//! chats can be listed and resumed. Sessions owned by a user, such as
+//! WebSocket chats of identified users and Slack threads, are listed but
+//! can't be resumed from the terminal.
 
+use crate::approval::CliApproval;
+use crate::events::AgentEvent;
+use crate::provider::PricingCatalog;
+use crate::sanitize::sanitize_for_terminal;
+use crate::session::Session;
 use crate::Agent;
+use futures::StreamExt;
 use std::env;
-use std::io::{self, Read};
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::sync::Arc;
+
+/// Sessions listed per `/sessions` page
+const SESSIONS_PER_PAGE: usize = 20;
 
 /// Run an agent with CLI interface
 pub fn run_cli(agent: Agent) -> crate::Result<()> {
@@ -16,10 +34,14 @@ pub fn run_cli(agent: Agent) -> crate::Result<()> {
 }
 
 /// Internal async implementation of CLI
-async fn async_run_cli(agent: Agent) -> crate::Result<()> {
+async fn async_run_cli(mut agent: Agent) -> crate::Result<()> {
     // Parse command line arguments
     let args: Vec<String> = env::args().collect();
 
+    if env::var_os("PATINOX_BANNER").is_some() {
+        eprintln!("{}", agent.runtime_info());
+    }
+
     // Handle special flags
     if args.len() > 1 {
         match args[1].as_str() {
@@ -35,8 +57,25 @@ async fn async_run_cli(agent: Agent) -> crate::Result<()> {
                 print_tools(&agent);
                 return Ok(());
             }
+            "--manifest" => {
+                print!("{}", agent.manifest().to_markdown());
+                return Ok(());
+            }
+            "--info" => {
+                println!("{}", serde_json::to_string_pretty(&agent.runtime_info())?);
+                return Ok(());
+            }
+            #[cfg(feature = "mcp")]
+            "--mcp" => {
+                return crate::mcp::serve(agent).await;
+            }
+            "--chat" => {
+                return run_repl(&mut agent).await;
+            }
             _ => {}
         }
+    } else if io::stdin().is_terminal() {
+        return run_repl(&mut agent).await;
     }
 
     // Get input from args or stdin
@@ -67,11 +106,12 @@ async fn async_run_cli(agent: Agent) -> crate::Result<()> {
     // Run the agent (async)
     match agent.run(input).await {
         Ok(output) => {
-            println!("{}", output);
+            // Model output may contain escape sequences aimed at the terminal
+            println!("{}", sanitize_for_terminal(&output));
             Ok(())
         }
         Err(e) => {
-            eprintln!("Error: {}", e);
+            eprintln!("Error: {}", sanitize_for_terminal(&e.to_string()));
             std::process::exit(1);
         }
     }
@@ -91,19 +131,303 @@ fn print_help(agent: &Agent) {
     println!("    -h, --help       Show this help message");
     println!("    -v, --version    Show version information");
     println!("    --tools          List available tools");
+    println!("    --manifest       Print agent documentation as markdown");
+    println!("    --info           Print version, features, providers and tools as JSON");
+    #[cfg(feature = "mcp")]
+    println!("    --mcp            Serve tools and the agent over MCP (stdio)");
+    println!("    --chat           Start an interactive chat (default on a terminal)");
     println!();
     println!("EXAMPLES:");
     println!("    {} \"Hello, world!\"", agent.config.name);
     println!("    echo \"process this\" | {}", agent.config.name);
 }
 
+/// Slash command entered in the chat REPL
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    Reset,
+    Tools,
+    /// Show the current model, or switch to another
+    Model(Option<String>),
+    /// Show what the next prompt spends its tokens on
+    Context(Option<String>),
+    Save(String),
+    Load(String),
+    /// List saved sessions, from a cursor printed by the previous page
+    Sessions(Option<String>),
+    Resume(String),
+    Delete(String),
+    /// Write the current session as markdown
+    Export(String),
+    Help,
+    Quit,
+}
+
+/// Parse a REPL line; `None` if it is a chat message rather than a command
+fn parse_command(line: &str) -> Option<Result<Command, String>> {
+    let rest = line.trim().strip_prefix('/')?;
+    let (name, argument) = match rest.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, Some(argument.trim().to_string())),
+        None => (rest, None),
+    };
+    let argument = argument.filter(|a| !a.is_empty());
+    let required = |usage: &str| argument.clone().ok_or_else(|| format!("Usage: {}", usage));
+
+    Some(match name {
+        "reset" => Ok(Command::Reset),
+        "tools" => Ok(Command::Tools),
+        "model" => Ok(Command::Model(argument.clone())),
+        "context" => Ok(Command::Context(argument.clone())),
+        "save" => required("/save <file>").map(Command::Save),
+        "load" => required("/load <file>").map(Command::Load),
+        "sessions" => Ok(Command::Sessions(argument.clone())),
+        "resume" => required("/resume <session id>").map(Command::Resume),
+        "delete" => required("/delete <session id>").map(Command::Delete),
+        "export" => required("/export <file>").map(Command::Export),
+        "help" => Ok(Command::Help),
+        "quit" | "exit" => Ok(Command::Quit),
+        other => Err(format!("Unknown command /{} (try /help)", other)),
+    })
+}
+
+fn print_repl_help() {
+    println!("Commands:");
+    println!("  /reset           Forget the conversation so far");
+    println!("  /tools           List available tools");
+    println!("  /model [name]    Show or switch the model");
+    println!("  /context [text]  Show prompt tokens by section");
+    println!("  /save <file>     Save the conversation as JSON");
+    println!("  /load <file>     Load a conversation saved with /save");
+    println!("  /sessions [more] List saved sessions, or the next page");
+    println!("  /resume <id>     Continue a saved session");
+    println!("  /delete <id>     Delete a saved session");
+    println!("  /export <file>   Write this session as markdown");
+    println!("  /quit            Leave the chat");
+}
+
+/// Token and cost summary of one chat turn
+fn usage_line(
+    pricing: &PricingCatalog,
+    model: &str,
+    calls: usize,
+    prompt_tokens: usize,
+    completion_tokens: usize,
+) -> String {
+    let cost = match pricing.estimate_cost(model, prompt_tokens, completion_tokens) {
+        Some(cost) => format!(", ~${:.4}", cost),
+        None => String::new(),
+    };
+    format!(
+        "[{} model call{}, {} prompt + {} completion tokens{}]",
+        calls,
+        if calls == 1 { "" } else { "s" },
+        prompt_tokens,
+        completion_tokens,
+        cost
+    )
+}
+
+/// Interactive multi-turn chat
+async fn run_repl(agent: &mut Agent) -> crate::Result<()> {
+    let pricing = PricingCatalog::baseline();
+    let mut session = Session::new(&agent.config.name);
+    let stdin = io::stdin();
+
+    if agent.approval.is_none() && agent.tools.iter().any(|tool| tool.dangerous()) {
+        agent.approval = Some(Arc::new(CliApproval::new()));
+    }
+
+    println!(
+        "{} ({}). Type /help for commands.",
+        agent.config.name,
+        agent.model()
+    );
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            println!();
+            return Ok(());
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match parse_command(line) {
+            Some(Err(message)) => eprintln!("{}", message),
+            Some(Ok(command)) => match command {
+                Command::Reset => {
+                    session = Session::new(&agent.config.name);
+                    println!("Conversation cleared.");
+                }
+                Command::Tools => print_tools(agent),
+                Command::Model(None) => println!("{}", agent.model()),
+                Command::Model(Some(model)) => match agent.set_model(&model) {
+                    Ok(()) => println!("Switched to {}.", model),
+                    Err(e) => eprintln!("Error: {}", e),
+                },
+                Command::Context(input) => {
+                    match agent.prompt_breakdown(&session.messages, input.as_deref()) {
+                        Ok(breakdown) => println!("{}", breakdown),
+                        Err(e) => eprintln!("Error: {}", e),
+                    }
+                }
+                Command::Save(path) => {
+                    match serde_json::to_string_pretty(&session.messages)
+                        .map_err(io::Error::from)
+                        .and_then(|json| std::fs::write(&path, json))
+                    {
+                        Ok(()) => {
+                            println!("Saved {} messages to {}.", session.messages.len(), path)
+                        }
+                        Err(e) => eprintln!("Error: {}", e),
+                    }
+                }
+                Command::Load(path) => {
+                    match std::fs::read_to_string(&path)
+                        .and_then(|json| serde_json::from_str(&json).map_err(io::Error::from))
+                    {
+                        Ok(loaded) => {
+                            session.messages = loaded;
+                            println!("Loaded {} messages from {}.", session.messages.len(), path);
+                        }
+                        Err(e) => eprintln!("Error: {}", e),
+                    }
+                }
+                Command::Sessions(_) | Command::Resume(_) | Command::Delete(_)
+                    if agent.sessions.is_none() =>
+                {
+                    eprintln!("No session store configured.");
+                }
+                Command::Sessions(cursor) => match agent
+                    .sessions
+                    .as_ref()
+                    .unwrap()
+                    .list(cursor.as_deref(), SESSIONS_PER_PAGE)
+                    .await
+                {
+                    Ok(page) if page.items.is_empty() => println!("No saved sessions."),
+                    Ok(page) => {
+                        for summary in page.items {
+                            println!(
+                                "{}  {}  {:>3} messages  {}",
+                                summary.id,
+                                summary.updated_at.format("%Y-%m-%d %H:%M"),
+                                summary.message_count,
+                                sanitize_for_terminal(&summary.title)
+                            );
+                        }
+                        if let Some(next) = page.next_cursor {
+                            println!("More: /sessions {}", next);
+                        }
+                    }
+                    Err(e) => eprintln!("Error: {}", e),
+                },
+                Command::Resume(id) => match agent.sessions.as_ref().unwrap().load(&id).await {
+                    // The CLI runs without a user, so it can't continue
+                    // conversations that belong to one
+                    Ok(Some(loaded)) if !loaded.belongs_to(None) => {
+                        eprintln!("Session {} belongs to another user.", id)
+                    }
+                    Ok(Some(loaded)) => {
+                        session = loaded;
+                        println!("Resumed session with {} messages.", session.messages.len());
+                    }
+                    Ok(None) => eprintln!("No session {}.", id),
+                    Err(e) => eprintln!("Error: {}", e),
+                },
+                Command::Delete(id) => {
+                    let sessions = agent.sessions.as_ref().unwrap();
+                    // Same rule as /resume: sessions of users are theirs
+                    match sessions.load(&id).await {
+                        Ok(Some(loaded)) if !loaded.belongs_to(None) => {
+                            eprintln!("Session {} belongs to another user.", id)
+                        }
+                        Ok(Some(_)) => match sessions.delete(&id).await {
+                            Ok(_) => println!("Deleted session {}.", id),
+                            Err(e) => eprintln!("Error: {}", e),
+                        },
+                        Ok(None) => eprintln!("No session {}.", id),
+                        Err(e) => eprintln!("Error: {}", e),
+                    }
+                }
+                Command::Export(path) => match std::fs::write(&path, session.to_markdown()) {
+                    Ok(()) => println!("Exported session to {}.", path),
+                    Err(e) => eprintln!("Error: {}", e),
+                },
+                Command::Help => print_repl_help(),
+                Command::Quit => return Ok(()),
+            },
+            None => {
+                let (mut calls, mut prompt_tokens, mut completion_tokens) = (0, 0, 0);
+                let mut events =
+                    Box::pin(agent.execute_streaming_with_history(session.messages.clone(), line));
+                while let Some(event) = events.next().await {
+                    session.record_event(&event);
+                    match event {
+                        AgentEvent::ToolCallStarted { name, .. } => {
+                            eprintln!("  ... {}", sanitize_for_terminal(&name));
+                        }
+                        AgentEvent::ToolCallFinished {
+                            name,
+                            error: Some(error),
+                            ..
+                        } => {
+                            eprintln!(
+                                "  {} failed: {}",
+                                sanitize_for_terminal(&name),
+                                sanitize_for_terminal(&error)
+                            );
+                        }
+                        AgentEvent::TurnFinished { usage, .. } => {
+                            calls += 1;
+                            prompt_tokens += usage.prompt_tokens;
+                            completion_tokens += usage.completion_tokens;
+                        }
+                        AgentEvent::Completed { output } => {
+                            println!("{}", sanitize_for_terminal(&output));
+                            session.push_turn(line, output);
+                            if let Some(store) = &agent.sessions {
+                                if let Err(e) = store.save(&session).await {
+                                    eprintln!("Warning: session not saved: {}", e);
+                                }
+                            }
+                        }
+                        AgentEvent::Failed { error } => {
+                            eprintln!("Error: {}", sanitize_for_terminal(&error));
+                        }
+                        _ => {}
+                    }
+                }
+                eprintln!(
+                    "{}",
+                    usage_line(
+                        &pricing,
+                        agent.model(),
+                        calls,
+                        prompt_tokens,
+                        completion_tokens
+                    )
+                );
+            }
+        }
+    }
+}
+
 fn print_tools(agent: &Agent) {
     println!("Available tools:");
     if agent.tools.is_empty() {
         println!("  (none)");
     } else {
-        for tool in agent.tools.values() {
-            println!("  {} - {}", tool.name(), tool.description());
+        for tool in agent.tools.iter() {
+            // Descriptions can come from remote MCP servers
+            println!(
+                "  {} - {}",
+                sanitize_for_terminal(tool.name()),
+                sanitize_for_terminal(tool.description())
+            );
         }
     }
 }
@@ -120,6 +444,64 @@ mod tests {
         print_help(&agent);
     }
 
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("hello there"), None);
+        assert_eq!(parse_command("/reset"), Some(Ok(Command::Reset)));
+        assert_eq!(parse_command(" /model "), Some(Ok(Command::Model(None))));
+        assert_eq!(
+            parse_command("/model gpt-4.1"),
+            Some(Ok(Command::Model(Some("gpt-4.1".to_string()))))
+        );
+        assert_eq!(
+            parse_command("/save  chat log.json"),
+            Some(Ok(Command::Save("chat log.json".to_string())))
+        );
+        assert_eq!(
+            parse_command("/resume 1f0c"),
+            Some(Ok(Command::Resume("1f0c".to_string())))
+        );
+        assert!(matches!(parse_command("/export"), Some(Err(_))));
+        assert!(matches!(parse_command("/load"), Some(Err(_))));
+        assert!(matches!(parse_command("/frobnicate"), Some(Err(_))));
+        assert_eq!(parse_command("/exit"), Some(Ok(Command::Quit)));
+        assert_eq!(
+            parse_command("/sessions"),
+            Some(Ok(Command::Sessions(None)))
+        );
+        assert_eq!(
+            parse_command("/context next question"),
+            Some(Ok(Command::Context(Some("next question".to_string()))))
+        );
+    }
+
+    #[test]
+    fn test_usage_line() {
+        let pricing = PricingCatalog::baseline();
+        assert_eq!(
+            usage_line(&pricing, "gpt-4o", 2, 1000, 100),
+            "[2 model calls, 1000 prompt + 100 completion tokens, ~$0.0035]"
+        );
+        assert_eq!(
+            usage_line(&pricing, "local-model", 1, 10, 5),
+            "[1 model call, 10 prompt + 5 completion tokens]"
+        );
+    }
+
+    #[test]
+    fn test_model_comes_from_the_provider() {
+        use crate::provider::{OpenAIProvider, Provider, ProviderConfig};
+
+        let config = ProviderConfig::new(Provider::OpenAI)
+            .model("gpt-4o")
+            .api_key("sk-test");
+        let agent =
+            create_agent("test").with_provider(Box::new(OpenAIProvider::new(config).unwrap()));
+        // The banner and cost line use this, not the agent's default config
+        assert_eq!(agent.model(), "gpt-4o");
+        assert_ne!(agent.config.provider_config.model, "gpt-4o");
+    }
+
     #[test]
     fn test_cli_tools_list() {
         let agent =
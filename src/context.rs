@@ -0,0 +1,286 @@
+//! Context-window management
+//!
+//! Long tool loops grow the conversation until it no longer fits the model's
+//! context window. A [`ContextManager`] shrinks the messages before each
+//! model call so the estimated prompt (see [`tokens`](crate::tokens)) stays
+//! under a budget. Leading system messages and the newest message are always
+//! kept.
+//!
+//! - [`TruncateOldest`] - drop the oldest turns until the rest fits
+//! - [`SlidingWindow`] - keep only the last N turns (then truncate if needed)
+//! - [`Summarize`] - replace older turns with a summary written by a
+//!   (usually cheaper) model, falling back to truncation; with
+//!   [`Summarize::by_topic`] each topic gets its own summary
+//!
+//! # Example
+//! ```ignore
+//! use patinox::context::Summarize;
+//!
+//! let summarizer = OpenAIProvider::new(ProviderConfig::new(Provider::OpenAI).model("gpt-4o-mini"))?;
+//! let agent = create_agent("researcher")
+//!     .with_context_manager(Summarize::new(Arc::new(summarizer)), 16_000);
+//! ```
+
+use crate::provider::{LLMProvider, Message, ProviderResponse};
+use crate::tokens::estimate_message_tokens;
+use crate::topics::TopicSegmenter;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Strategy for fitting a conversation into a token budget
+#[async_trait]
+pub trait ContextManager: Send + Sync {
+    /// Return messages whose estimated size is within `max_tokens` where possible
+    async fn fit(&self, messages: Vec<Message>, max_tokens: usize) -> crate::Result<Vec<Message>>;
+}
+
+/// Split off the leading system messages
+fn split_system(mut messages: Vec<Message>) -> (Vec<Message>, Vec<Message>) {
+    let count = messages.iter().take_while(|m| m.role == "system").count();
+    let rest = messages.split_off(count);
+    (messages, rest)
+}
+
+/// Drop the oldest non-system messages until the conversation fits
+///
+/// The newest message is kept even if it alone is over budget.
+fn truncate_oldest(messages: Vec<Message>, max_tokens: usize) -> Vec<Message> {
+    let (mut kept, mut rest) = split_system(messages);
+    let fixed = estimate_message_tokens(&kept);
+
+    let mut drop = 0;
+    while drop + 1 < rest.len() && fixed + estimate_message_tokens(&rest[drop..]) > max_tokens {
+        drop += 1;
+    }
+    if drop > 0 {
+        tracing::debug!("context: dropping {} oldest messages", drop);
+    }
+    kept.extend(rest.drain(drop..));
+    kept
+}
+
+/// Drop the oldest turns until the conversation fits
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TruncateOldest;
+
+#[async_trait]
+impl ContextManager for TruncateOldest {
+    async fn fit(&self, messages: Vec<Message>, max_tokens: usize) -> crate::Result<Vec<Message>> {
+        Ok(truncate_oldest(messages, max_tokens))
+    }
+}
+
+/// Keep only the most recent `max_messages` non-system messages
+#[derive(Debug, Clone, Copy)]
+pub struct SlidingWindow {
+    max_messages: usize,
+}
+
+impl SlidingWindow {
+    pub fn new(max_messages: usize) -> Self {
+        Self {
+            max_messages: max_messages.max(1),
+        }
+    }
+}
+
+#[async_trait]
+impl ContextManager for SlidingWindow {
+    async fn fit(&self, messages: Vec<Message>, max_tokens: usize) -> crate::Result<Vec<Message>> {
+        let (mut kept, rest) = split_system(messages);
+        let skip = rest.len().saturating_sub(self.max_messages);
+        kept.extend(rest.into_iter().skip(skip));
+        Ok(truncate_oldest(kept, max_tokens))
+    }
+}
+
+/// Default instructions for the summarizing model
+const SUMMARY_INSTRUCTIONS: &str = "Summarize the conversation below for an assistant that will \
+    continue it. Keep facts, decisions, tool results and open questions. Be concise.";
+
+/// Replace older turns with a model-written summary
+///
+/// The summary is inserted as a system message after the original system
+/// prompt, followed by the `keep_recent` newest messages. If the result still
+/// doesn't fit, the oldest turns are truncated.
+pub struct Summarize {
+    provider: Arc<dyn LLMProvider>,
+    keep_recent: usize,
+    instructions: String,
+    topics: Option<TopicSegmenter>,
+}
+
+impl Summarize {
+    /// Summarize with `provider`, keeping the 4 newest messages verbatim
+    pub fn new(provider: Arc<dyn LLMProvider>) -> Self {
+        Self {
+            provider,
+            keep_recent: 4,
+            instructions: SUMMARY_INSTRUCTIONS.to_string(),
+            topics: None,
+        }
+    }
+
+    /// Number of newest messages left unsummarized
+    pub fn keep_recent(mut self, count: usize) -> Self {
+        self.keep_recent = count.max(1);
+        self
+    }
+
+    /// Replace the summarization instructions
+    pub fn instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = instructions.into();
+        self
+    }
+
+    /// Split older turns into topics and summarize each one separately
+    pub fn by_topic(mut self, segmenter: TopicSegmenter) -> Self {
+        self.topics = Some(segmenter);
+        self
+    }
+
+    async fn summarize(&self, messages: &[Message]) -> crate::Result<String> {
+        let transcript = messages
+            .iter()
+            .map(|m| format!("{}: {}", m.role, m.content))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let request = vec![
+            Message::system(&self.instructions),
+            Message::user(transcript),
+        ];
+        match self.provider.complete(request, Vec::new()).await? {
+            ProviderResponse::Text(text) => Ok(text),
+            ProviderResponse::ToolCalls(_) => {
+                Err("Summarizer returned tool calls instead of a summary".into())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ContextManager for Summarize {
+    async fn fit(&self, messages: Vec<Message>, max_tokens: usize) -> crate::Result<Vec<Message>> {
+        if estimate_message_tokens(&messages) <= max_tokens {
+            return Ok(messages);
+        }
+
+        let (mut kept, mut rest) = split_system(messages);
+        if rest.len() <= self.keep_recent {
+            kept.extend(rest);
+            return Ok(truncate_oldest(kept, max_tokens));
+        }
+
+        let recent = rest.split_off(rest.len() - self.keep_recent);
+        let segments = match &self.topics {
+            Some(segmenter) => segmenter.segment(&rest).await?.segments,
+            None => Vec::new(),
+        };
+        let summary = if segments.len() > 1 {
+            let mut parts = Vec::new();
+            for segment in &segments {
+                let summary = self.summarize(segment.messages(&rest)).await?;
+                parts.push(format!("Topic: {}\n{}", segment.label, summary));
+            }
+            parts.join("\n\n")
+        } else {
+            self.summarize(&rest).await?
+        };
+        tracing::debug!("context: summarized {} older messages", rest.len());
+
+        kept.push(Message::system(format!(
+            "Summary of the earlier conversation:\n{}",
+            summary
+        )));
+        kept.extend(recent);
+        Ok(truncate_oldest(kept, max_tokens))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+
+    fn conversation(turns: usize) -> Vec<Message> {
+        let mut messages = vec![Message::system("You are helpful.")];
+        for i in 0..turns {
+            messages.push(Message::user(format!("question {} {}", i, "x".repeat(40))));
+            messages.push(Message::assistant(format!(
+                "answer {} {}",
+                i,
+                "y".repeat(40)
+            )));
+        }
+        messages
+    }
+
+    #[tokio::test]
+    async fn test_truncate_keeps_system_and_newest() {
+        let fitted = TruncateOldest.fit(conversation(10), 60).await.unwrap();
+        assert_eq!(fitted[0].role, "system");
+        assert!(fitted.last().unwrap().content.starts_with("answer 9"));
+        assert!(estimate_message_tokens(&fitted) <= 60);
+
+        // Newest message survives even when it alone is over budget
+        let fitted = TruncateOldest.fit(conversation(1), 1).await.unwrap();
+        assert_eq!(fitted.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sliding_window() {
+        let fitted = SlidingWindow::new(3)
+            .fit(conversation(5), 10_000)
+            .await
+            .unwrap();
+        let contents: Vec<_> = fitted.iter().map(|m| &m.content[..8]).collect();
+        assert_eq!(
+            contents,
+            vec!["You are ", "answer 3", "question", "answer 4"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_summarize_replaces_older_turns() {
+        let summarizer =
+            Summarize::new(Arc::new(MockProvider::new("they discussed x and y"))).keep_recent(2);
+
+        let untouched = summarizer.fit(conversation(1), 10_000).await.unwrap();
+        assert_eq!(untouched.len(), 3);
+
+        let fitted = summarizer.fit(conversation(10), 100).await.unwrap();
+        assert_eq!(fitted.len(), 4);
+        assert_eq!(
+            fitted[1].content,
+            "Summary of the earlier conversation:\nthey discussed x and y"
+        );
+        assert!(fitted[2].content.starts_with("question 9"));
+    }
+
+    #[tokio::test]
+    async fn test_summarize_by_topic() {
+        let provider = Arc::new(
+            MockProvider::scripted()
+                .then_text("migration plan agreed")
+                .then_text("double charge refunded"),
+        );
+        let summarizer =
+            Summarize::new(provider.clone())
+                .keep_recent(2)
+                .by_topic(TopicSegmenter::new(Arc::new(
+                    crate::topics::tests::KeywordEmbedder,
+                )));
+
+        let fitted = summarizer
+            .fit(crate::topics::tests::session(), 10)
+            .await
+            .unwrap();
+        assert_eq!(provider.calls(), 2);
+        assert_eq!(
+            fitted[1].content,
+            "Summary of the earlier conversation:\n\
+             Topic: Plan the database migration to the new schema\nmigration plan agreed\n\n\
+             Topic: Now a billing question: why was this invoice charged twice?\ndouble charge refunded"
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Date, timezone and locale context for system prompts
+//!
+//! Without being told, models assume it is still their training cutoff,
+//! which breaks anything involving "tomorrow", deadlines or scheduling.
+//! [`Agent::with_date_context`](crate::Agent::with_date_context) appends the
+//! current date and time, the user's timezone and locale to the system
+//! prompt, rendered afresh for every request.
+//!
+//! The text comes from a [`PromptTemplate`] with these variables: `date`,
+//! `time`, `weekday`, `timezone`, `utc_offset`, `iso` and `locale`.
+//!
+//! Pair it with the [`current_time`](crate::tool::time::CurrentTimeTool)
+//! tool for long runs where the prompt's time goes stale.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::date_context::DateContext;
+//!
+//! let context = DateContext::new()
+//!     .timezone("America/Chicago".parse()?)
+//!     .locale("en-US");
+//! let agent = create_agent("scheduler")
+//!     .tool(context.tool())
+//!     .with_date_context(context);
+//! ```
+
+use crate::prompt::PromptTemplate;
+use crate::tool::time::{system_timezone, time_fields, CurrentTimeTool};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+/// Default text appended to the system prompt
+const DEFAULT_TEMPLATE: &str = "Current date and time: {{weekday}}, {{date}} {{time}} \
+    ({{timezone}}, UTC{{utc_offset}}). The user's locale is {{locale}}.";
+
+/// Locale used when the environment doesn't name one
+const DEFAULT_LOCALE: &str = "en-US";
+
+/// Current date, timezone and locale injected into each request
+#[derive(Debug, Clone)]
+pub struct DateContext {
+    timezone: Tz,
+    locale: String,
+    template: PromptTemplate,
+}
+
+impl Default for DateContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DateContext {
+    /// Use the system timezone and the locale from `LC_ALL` / `LANG`
+    pub fn new() -> Self {
+        Self {
+            timezone: system_timezone(),
+            locale: environment_locale(),
+            template: PromptTemplate::parse(DEFAULT_TEMPLATE)
+                .expect("default date template is valid"),
+        }
+    }
+
+    /// Set the user's IANA timezone
+    pub fn timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Set the user's locale, such as `de-DE`
+    pub fn locale(mut self, locale: impl Into<String>) -> Self {
+        self.locale = locale.into();
+        self
+    }
+
+    /// Replace the text appended to the system prompt
+    pub fn template(mut self, template: PromptTemplate) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// A `current_time` tool using the same timezone
+    pub fn tool(&self) -> CurrentTimeTool {
+        CurrentTimeTool::new().timezone(self.timezone)
+    }
+
+    /// Render the context for the current moment
+    pub fn render(&self) -> crate::Result<String> {
+        self.render_at(Utc::now())
+    }
+
+    /// Render the context as of `now`
+    pub fn render_at(&self, now: DateTime<Utc>) -> crate::Result<String> {
+        let mut fields = time_fields(now, self.timezone);
+        fields["locale"] = self.locale.clone().into();
+        Ok(self.template.render(&fields)?)
+    }
+}
+
+/// `en_US.UTF-8` style locale from the environment as a BCP 47 tag
+fn environment_locale() -> String {
+    ["LC_ALL", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find_map(|value| parse_posix_locale(&value))
+        .unwrap_or_else(|| DEFAULT_LOCALE.to_string())
+}
+
+fn parse_posix_locale(value: &str) -> Option<String> {
+    let tag = value.split(['.', '@']).next()?.replace('_', "-");
+    match tag.as_str() {
+        "" | "C" | "POSIX" => None,
+        _ => Some(tag),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_render_at() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 31, 15, 5, 0).unwrap();
+        let context = DateContext::new()
+            .timezone(chrono_tz::America::New_York)
+            .locale("en-GB");
+        assert_eq!(
+            context.render_at(now).unwrap(),
+            "Current date and time: Friday, 2025-01-31 10:05 (America/New_York, UTC-05:00). \
+             The user's locale is en-GB."
+        );
+
+        let custom =
+            context.template(PromptTemplate::parse("Today: {{date}} [{{locale}}]").unwrap());
+        assert_eq!(custom.render_at(now).unwrap(), "Today: 2025-01-31 [en-GB]");
+    }
+
+    #[test]
+    fn test_parse_posix_locale() {
+        assert_eq!(parse_posix_locale("de_DE.UTF-8").as_deref(), Some("de-DE"));
+        assert_eq!(parse_posix_locale("sr_RS@latin").as_deref(), Some("sr-RS"));
+        assert_eq!(parse_posix_locale("C.UTF-8"), None);
+        assert_eq!(parse_posix_locale(""), None);
+    }
+}
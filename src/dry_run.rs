@@ -0,0 +1,380 @@
+//! Plan-only execution
+//!
+//! [`DryRun`] runs an agent's full loop (hooks, context management, approval
+//! checks, transcript recording) without spending anything: the provider is
+//! replaced by a stub that answers with estimated token counts, and every
+//! tool is replaced by a stand-in that reports it was not executed. The
+//! result is a [`DryRunReport`] with the predicted steps, tokens, cost and
+//! latency, useful for pre-flighting batch jobs before paying for them.
+//!
+//! The stub model calls every tool once per tool turn and then answers, so
+//! the report is an estimate of a typical run, not a replay of a real one.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::dry_run::DryRun;
+//!
+//! let report = DryRun::new()
+//!     .tool_turns(3)
+//!     .completion_tokens(400)
+//!     .run(build_agent(), "Triage the open tickets")
+//!     .await;
+//! println!("{}", report);
+//! if report.estimated_cost.unwrap_or(0.0) * jobs as f64 > budget { ... }
+//! ```
+
+use crate::escalation::ESCALATE_TOOL;
+use crate::provider::{
+    LLMProvider, Message, PricingCatalog, ProviderResponse, ProviderResult, ToolCall,
+    ToolDefinition,
+};
+use crate::tokens::{estimate_message_tokens, estimate_tokens};
+use crate::tool::{Tool, ToolResult};
+use crate::Agent;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// One predicted model call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DryRunStep {
+    /// Model call number, from 1
+    pub turn: usize,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    /// Tools the model asked for in this turn
+    pub tool_calls: Vec<String>,
+}
+
+/// Predicted cost and latency of a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct DryRunReport {
+    pub model: String,
+    pub steps: Vec<DryRunStep>,
+    /// The run's final output, or its error, with stubbed responses
+    pub outcome: Result<String, String>,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub tool_calls: usize,
+    /// USD, if the model's price is in the catalog
+    pub estimated_cost: Option<f64>,
+    pub estimated_latency: Duration,
+}
+
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Dry run for {}", self.model)?;
+        for step in &self.steps {
+            write!(
+                f,
+                "  turn {}: {} prompt + {} completion tokens",
+                step.turn, step.prompt_tokens, step.completion_tokens
+            )?;
+            if !step.tool_calls.is_empty() {
+                write!(f, ", tools: {}", step.tool_calls.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        write!(
+            f,
+            "Total: {} model calls, {} tool calls, {} prompt + {} completion tokens, ~{:.1}s",
+            self.steps.len(),
+            self.tool_calls,
+            self.prompt_tokens,
+            self.completion_tokens,
+            self.estimated_latency.as_secs_f64()
+        )?;
+        match self.estimated_cost {
+            Some(cost) => write!(f, ", ~${:.4}", cost),
+            None => write!(f, ", cost unknown (no price for {})", self.model),
+        }
+    }
+}
+
+/// Settings for a plan-only run
+#[derive(Debug, Clone)]
+pub struct DryRun {
+    pricing: PricingCatalog,
+    tool_turns: usize,
+    completion_tokens: usize,
+    tokens_per_second: f64,
+    call_overhead: Duration,
+    tool_latency: Duration,
+}
+
+impl Default for DryRun {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DryRun {
+    /// One tool turn, 256-token answers, baseline prices and typical latencies
+    pub fn new() -> Self {
+        Self {
+            pricing: PricingCatalog::baseline(),
+            tool_turns: 1,
+            completion_tokens: 256,
+            tokens_per_second: 50.0,
+            call_overhead: Duration::from_millis(500),
+            tool_latency: Duration::from_millis(100),
+        }
+    }
+
+    /// Prices used for the cost estimate
+    pub fn pricing(mut self, pricing: PricingCatalog) -> Self {
+        self.pricing = pricing;
+        self
+    }
+
+    /// Model turns that call tools before the final answer
+    pub fn tool_turns(mut self, turns: usize) -> Self {
+        self.tool_turns = turns;
+        self
+    }
+
+    /// Assumed length of the final answer
+    pub fn completion_tokens(mut self, tokens: usize) -> Self {
+        self.completion_tokens = tokens;
+        self
+    }
+
+    /// Assumed generation speed
+    pub fn tokens_per_second(mut self, rate: f64) -> Self {
+        self.tokens_per_second = rate.max(f64::MIN_POSITIVE);
+        self
+    }
+
+    /// Assumed fixed latency of each model call
+    pub fn call_overhead(mut self, overhead: Duration) -> Self {
+        self.call_overhead = overhead;
+        self
+    }
+
+    /// Assumed duration of each tool call
+    pub fn tool_latency(mut self, latency: Duration) -> Self {
+        self.tool_latency = latency;
+        self
+    }
+
+    /// Run `agent` on `input` with stubbed provider and tools
+    ///
+    /// The agent is consumed because its provider and tools are replaced.
+    pub async fn run(&self, agent: Agent, input: impl Into<String>) -> DryRunReport {
+        let model = agent.config.provider_config.model.clone();
+        let steps = Arc::new(Mutex::new(Vec::new()));
+
+        let mut agent = agent.with_provider(Box::new(StubProvider {
+            steps: steps.clone(),
+            tool_turns: self.tool_turns,
+            completion_tokens: self.completion_tokens,
+        }));
+        let stubs: Vec<Arc<dyn Tool>> = agent
+            .tools()
+            .iter()
+            .map(|tool| Arc::new(StubTool::new(tool.as_ref())) as Arc<dyn Tool>)
+            .collect();
+        for stub in stubs {
+            agent.tools_mut().insert(stub);
+        }
+
+        let outcome = agent.run(input).await.map_err(|e| e.to_string());
+        let steps = steps.lock().unwrap().clone();
+        self.report(model, steps, outcome)
+    }
+
+    fn report(
+        &self,
+        model: String,
+        steps: Vec<DryRunStep>,
+        outcome: Result<String, String>,
+    ) -> DryRunReport {
+        let prompt_tokens = steps.iter().map(|s| s.prompt_tokens).sum();
+        let completion_tokens = steps.iter().map(|s| s.completion_tokens).sum();
+        let tool_calls = steps.iter().map(|s| s.tool_calls.len()).sum::<usize>();
+        let generation = Duration::from_secs_f64(completion_tokens as f64 / self.tokens_per_second);
+        let estimated_latency = self.call_overhead * steps.len() as u32
+            + generation
+            + self.tool_latency * tool_calls as u32;
+
+        DryRunReport {
+            estimated_cost: self
+                .pricing
+                .estimate_cost(&model, prompt_tokens, completion_tokens),
+            model,
+            steps,
+            outcome,
+            prompt_tokens,
+            completion_tokens,
+            tool_calls,
+            estimated_latency,
+        }
+    }
+}
+
+/// Model stand-in: calls every tool for `tool_turns` turns, then answers
+struct StubProvider {
+    steps: Arc<Mutex<Vec<DryRunStep>>>,
+    tool_turns: usize,
+    completion_tokens: usize,
+}
+
+#[async_trait]
+impl LLMProvider for StubProvider {
+    async fn complete(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+    ) -> ProviderResult<ProviderResponse> {
+        let mut steps = self.steps.lock().unwrap();
+        let turn = steps.len() + 1;
+        // Tool definitions are part of the prompt too
+        let prompt_tokens = estimate_message_tokens(&messages)
+            + tools
+                .iter()
+                .map(|t| {
+                    estimate_tokens(&t.description) + estimate_tokens(&t.parameters.to_string())
+                })
+                .sum::<usize>();
+
+        let calls: Vec<ToolCall> = if turn <= self.tool_turns {
+            tools
+                .iter()
+                .filter(|t| t.name != ESCALATE_TOOL)
+                .enumerate()
+                .map(|(i, t)| ToolCall {
+                    id: format!("dry_run_{}_{}", turn, i),
+                    name: t.name.clone(),
+                    arguments: Value::Object(Default::default()),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        if calls.is_empty() {
+            steps.push(DryRunStep {
+                turn,
+                prompt_tokens,
+                completion_tokens: self.completion_tokens,
+                tool_calls: Vec::new(),
+            });
+            return Ok(ProviderResponse::Text(format!(
+                "[dry run] final answer (~{} tokens)",
+                self.completion_tokens
+            )));
+        }
+
+        steps.push(DryRunStep {
+            turn,
+            prompt_tokens,
+            completion_tokens: calls
+                .iter()
+                .map(|c| estimate_tokens(&c.name) + estimate_tokens(&c.arguments.to_string()))
+                .sum(),
+            tool_calls: calls.iter().map(|c| c.name.clone()).collect(),
+        });
+        Ok(ProviderResponse::ToolCalls(calls))
+    }
+}
+
+/// Tool stand-in with the real tool's name and schema
+///
+/// Never dangerous, so approval gates are not prompted during a dry run.
+struct StubTool {
+    name: String,
+    description: String,
+    parameters: Value,
+}
+
+impl StubTool {
+    fn new(tool: &dyn Tool) -> Self {
+        Self {
+            name: tool.name().to_string(),
+            description: tool.description().to_string(),
+            parameters: tool.parameters(),
+        }
+    }
+}
+
+impl Tool for StubTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Value {
+        self.parameters.clone()
+    }
+
+    fn execute(&self, _args: Value) -> ToolResult {
+        Ok(format!("[dry run] '{}' was not executed", self.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::ModelPrice;
+    use crate::{create_agent, AgentConfig};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[tokio::test]
+    async fn test_dry_run_report() {
+        let executed = Arc::new(AtomicBool::new(false));
+        let flag = executed.clone();
+        let agent = Agent::new(AgentConfig::new("batch").model("priced-model"))
+            .tool_fn("search", "Search the web", move |_| {
+                flag.store(true, Ordering::SeqCst);
+                Ok("results".to_string())
+            })
+            .tool_fn("fetch", "Fetch a page", |_| Ok("page".to_string()));
+
+        let report = DryRun::new()
+            .tool_turns(2)
+            .completion_tokens(100)
+            .tokens_per_second(100.0)
+            .call_overhead(Duration::from_millis(200))
+            .tool_latency(Duration::from_millis(50))
+            .pricing(PricingCatalog::new().with_override("priced-model", ModelPrice::new(1.0, 2.0)))
+            .run(agent, "Find the answer")
+            .await;
+
+        assert!(!executed.load(Ordering::SeqCst));
+        assert_eq!(report.steps.len(), 3);
+        assert_eq!(report.steps[0].tool_calls, vec!["fetch", "search"]);
+        assert!(report.steps[1].prompt_tokens > report.steps[0].prompt_tokens);
+        assert_eq!(report.tool_calls, 4);
+        assert_eq!(
+            report.outcome,
+            Ok("[dry run] final answer (~100 tokens)".to_string())
+        );
+
+        let expected_cost = report.prompt_tokens as f64 / 1000.0 * 1.0
+            + report.completion_tokens as f64 / 1000.0 * 2.0;
+        assert!((report.estimated_cost.unwrap() - expected_cost).abs() < 1e-9);
+        // 3 calls of overhead + 4 tool calls + generation time
+        let generation = Duration::from_secs_f64(report.completion_tokens as f64 / 100.0);
+        assert_eq!(
+            report.estimated_latency,
+            Duration::from_millis(800) + generation
+        );
+        assert!(report.to_string().contains("3 model calls, 4 tool calls"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_price() {
+        let report = DryRun::new()
+            .pricing(PricingCatalog::new())
+            .run(create_agent("plain"), "hi")
+            .await;
+        assert_eq!(report.steps.len(), 1);
+        assert_eq!(report.estimated_cost, None);
+        assert!(report.to_string().contains("cost unknown"));
+    }
+}
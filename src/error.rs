@@ -0,0 +1,574 @@
+//! Error types for agent runs, provider requests and multi-branch operations
+//!
+//! Patinox APIs return `Box<dyn Error>` so any error can flow through
+//! `crate::Result`. The types here are concrete errors that callers can
+//! downcast to when they need more than a message.
+//!
+//! [`recovery_strategy`] tells orchestration code what to do about any of
+//! them (retry, fall back, abort or escalate) without reading error text.
+
+use crate::loop_guard::LoopDetected;
+use crate::provider::image::UnsupportedInput;
+use crate::provider::NoConsensus;
+use crate::tenancy::QuotaExceeded;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// Boxed error used throughout the crate
+pub type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Why an agent run stopped early
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AgentError {
+    /// The run's cancellation token was triggered
+    Cancelled,
+    /// The run exceeded its configured timeout
+    Timeout(Duration),
+    /// Too many runs in flight and the admission queue is full
+    ResourceExhausted,
+    /// The agent was run without a provider
+    NoProvider,
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentError::Cancelled => write!(f, "Run was cancelled"),
+            AgentError::Timeout(limit) => write!(f, "Run exceeded its {:?} timeout", limit),
+            AgentError::ResourceExhausted => {
+                write!(f, "Too many concurrent runs; the queue is full")
+            }
+            AgentError::NoProvider => {
+                write!(f, "No provider configured; use Agent::with_provider()")
+            }
+        }
+    }
+}
+
+impl Error for AgentError {}
+
+impl AgentError {
+    pub fn recovery_strategy(&self) -> RecoveryStrategy {
+        match self {
+            AgentError::Cancelled => RecoveryStrategy::Abort,
+            // A run that used its whole budget will likely do so again
+            AgentError::Timeout(_) => RecoveryStrategy::Escalate,
+            AgentError::ResourceExhausted => RecoveryStrategy::Retry {
+                after: TRANSIENT_BACKOFF,
+            },
+            AgentError::NoProvider => RecoveryStrategy::Abort,
+        }
+    }
+}
+
+/// What to do about a failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryStrategy {
+    /// Send the same request again after waiting
+    Retry { after: Duration },
+    /// This provider or model can't serve the request; try another
+    Fallback,
+    /// Retrying won't help; give up and report the error
+    Abort,
+    /// Hand the decision to a human
+    Escalate,
+}
+
+/// How to recover from `error`, judged by the first error in its chain of
+/// sources that Patinox knows; anything else aborts
+pub fn recovery_strategy(error: &(dyn Error + 'static)) -> RecoveryStrategy {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if let Some(e) = error.downcast_ref::<ProviderError>() {
+            return e.recovery_strategy();
+        }
+        if let Some(e) = error.downcast_ref::<AgentError>() {
+            return e.recovery_strategy();
+        }
+        if error.is::<UnsupportedInput>() {
+            return RecoveryStrategy::Fallback;
+        }
+        if error.is::<NoConsensus>() || error.is::<LoopDetected>() {
+            return RecoveryStrategy::Escalate;
+        }
+        // The user is out of quota until `resets_at`, whatever serves them
+        if error.is::<QuotaExceeded>() {
+            return RecoveryStrategy::Abort;
+        }
+        current = error.source();
+    }
+    RecoveryStrategy::Abort
+}
+
+/// Backoff suggested for rate limits when the provider doesn't say
+const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Backoff suggested for server errors, timeouts and network failures
+const TRANSIENT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// What went wrong with a provider request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderErrorKind {
+    /// Too many requests or tokens per minute (HTTP 429)
+    RateLimited,
+    /// The account is out of credit; waiting won't help
+    QuotaExceeded,
+    /// Missing, invalid or unauthorized API key (HTTP 401/403)
+    Authentication,
+    /// The provider rejected the request itself (HTTP 4xx)
+    InvalidRequest,
+    /// The provider failed or is overloaded (HTTP 5xx)
+    Server,
+    /// The request or its response took too long
+    Timeout,
+    /// The provider couldn't be reached
+    Network,
+    /// Anything else, e.g. a response that couldn't be parsed
+    Other,
+}
+
+impl ProviderErrorKind {
+    /// Classify an HTTP status code
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            401 | 403 => ProviderErrorKind::Authentication,
+            408 => ProviderErrorKind::Timeout,
+            429 => ProviderErrorKind::RateLimited,
+            400..=499 => ProviderErrorKind::InvalidRequest,
+            500..=599 => ProviderErrorKind::Server,
+            _ => ProviderErrorKind::Other,
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            ProviderErrorKind::RateLimited
+                | ProviderErrorKind::Server
+                | ProviderErrorKind::Timeout
+                | ProviderErrorKind::Network
+        )
+    }
+}
+
+/// A failed provider request, with what retry and circuit-breaker logic
+/// needs to decide without parsing the message
+///
+/// Providers return these inside their boxed errors; find one with
+/// [`ProviderError::find`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderError {
+    /// Provider that failed, e.g. `"openai"`
+    pub provider: String,
+    pub kind: ProviderErrorKind,
+    /// HTTP status, when the provider answered
+    pub status: Option<u16>,
+    /// The provider's id for the request, for support tickets and logs
+    pub request_id: Option<String>,
+    /// How long the provider asked callers to wait (`Retry-After`)
+    pub retry_after: Option<Duration>,
+    pub message: String,
+}
+
+impl ProviderError {
+    pub fn new(
+        provider: impl Into<String>,
+        kind: ProviderErrorKind,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            provider: provider.into(),
+            kind,
+            status: None,
+            request_id: None,
+            retry_after: None,
+            message: message.into(),
+        }
+    }
+
+    /// An error for an HTTP response, classified by its status
+    pub fn from_status(
+        provider: impl Into<String>,
+        status: u16,
+        message: impl Into<String>,
+    ) -> Self {
+        Self::new(provider, ProviderErrorKind::from_status(status), message).status(status)
+    }
+
+    /// An error for a failed HTTP response, reading the request id and
+    /// `Retry-After` from its headers
+    pub fn from_http(
+        provider: impl Into<String>,
+        status: reqwest::StatusCode,
+        headers: &reqwest::header::HeaderMap,
+        message: impl Into<String>,
+    ) -> Self {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let mut error = Self::from_status(provider, status.as_u16(), message);
+        error.request_id = header("x-request-id")
+            .or_else(|| header("request-id"))
+            .map(str::to_string);
+        error.retry_after = retry_after(headers);
+        error
+    }
+
+    /// An error for a request that failed in transport or in its response
+    pub fn from_reqwest(provider: impl Into<String>, error: &reqwest::Error) -> Self {
+        let kind = if error.is_timeout() {
+            ProviderErrorKind::Timeout
+        } else if error.is_connect() || error.is_request() {
+            ProviderErrorKind::Network
+        } else if let Some(status) = error.status() {
+            ProviderErrorKind::from_status(status.as_u16())
+        } else {
+            ProviderErrorKind::Other
+        };
+        let mut provider_error = Self::new(provider, kind, error.to_string());
+        provider_error.status = error.status().map(|status| status.as_u16());
+        provider_error
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn request_id(mut self, id: impl Into<String>) -> Self {
+        self.request_id = Some(id.into());
+        self
+    }
+
+    pub fn retry_after(mut self, wait: Duration) -> Self {
+        self.retry_after = Some(wait);
+        self
+    }
+
+    /// Whether the same request may succeed if sent again later
+    pub fn is_retryable(&self) -> bool {
+        self.kind.is_retryable()
+    }
+
+    /// How long to wait before retrying, or `None` if retrying won't help
+    ///
+    /// Uses the provider's `Retry-After` when it sent one.
+    pub fn backoff(&self) -> Option<Duration> {
+        if !self.is_retryable() {
+            return None;
+        }
+        Some(self.retry_after.unwrap_or(match self.kind {
+            ProviderErrorKind::RateLimited => RATE_LIMIT_BACKOFF,
+            _ => TRANSIENT_BACKOFF,
+        }))
+    }
+
+    pub fn recovery_strategy(&self) -> RecoveryStrategy {
+        if let Some(after) = self.backoff() {
+            return RecoveryStrategy::Retry { after };
+        }
+        match self.kind {
+            // Another provider (or account) can still serve the request
+            ProviderErrorKind::QuotaExceeded | ProviderErrorKind::Authentication => {
+                RecoveryStrategy::Fallback
+            }
+            _ => RecoveryStrategy::Abort,
+        }
+    }
+
+    /// The provider error in `error` or its chain of sources
+    pub fn find<'a>(error: &'a (dyn Error + 'static)) -> Option<&'a ProviderError> {
+        let mut current = Some(error);
+        while let Some(error) = current {
+            if let Some(found) = error.downcast_ref::<ProviderError>() {
+                return Some(found);
+            }
+            current = error.source();
+        }
+        None
+    }
+}
+
+/// The `Retry-After` of a response, in seconds
+pub(crate) fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    headers
+        .get("retry-after")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} request failed", self.provider)?;
+        if let Some(status) = self.status {
+            write!(f, " ({})", status)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(id) = &self.request_id {
+            write!(f, " [request {}]", id)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ProviderError {}
+
+/// How many branches of a fan-out must succeed for the whole to succeed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuccessPolicy {
+    /// Every branch must succeed
+    All,
+    /// At least one branch must succeed
+    Any,
+    /// At least this many branches must succeed
+    Quorum(usize),
+}
+
+/// A failed branch of a parallel operation
+#[derive(Debug)]
+pub struct BranchFailure {
+    pub id: String,
+    pub error: BoxError,
+}
+
+/// Failures from parallel operations, kept per branch instead of collapsed
+///
+/// Records which branches failed (with their errors) and which succeeded,
+/// so callers can render every failure and decide overall success with a
+/// [`SuccessPolicy`].
+#[derive(Debug, Default)]
+pub struct AggregateError {
+    pub failures: Vec<BranchFailure>,
+    pub succeeded: Vec<String>,
+}
+
+impl AggregateError {
+    /// Create an empty aggregate
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a failed branch
+    pub fn push_failure(&mut self, id: impl Into<String>, error: impl Into<BoxError>) {
+        self.failures.push(BranchFailure {
+            id: id.into(),
+            error: error.into(),
+        });
+    }
+
+    /// Record a successful branch
+    pub fn push_success(&mut self, id: impl Into<String>) {
+        self.succeeded.push(id.into());
+    }
+
+    /// Total number of branches recorded
+    pub fn total(&self) -> usize {
+        self.failures.len() + self.succeeded.len()
+    }
+
+    /// Whether the recorded outcomes satisfy a policy
+    pub fn satisfies(&self, policy: SuccessPolicy) -> bool {
+        match policy {
+            SuccessPolicy::All => self.failures.is_empty(),
+            SuccessPolicy::Any => !self.succeeded.is_empty(),
+            SuccessPolicy::Quorum(n) => self.succeeded.len() >= n,
+        }
+    }
+
+    /// Split branch results into successes, or an aggregate if the policy fails
+    ///
+    /// Successful values are returned in input order. When the policy is not
+    /// satisfied, the error still lists which branches succeeded.
+    pub fn collect<T>(
+        results: impl IntoIterator<Item = (String, Result<T, BoxError>)>,
+        policy: SuccessPolicy,
+    ) -> Result<Vec<(String, T)>, AggregateError> {
+        let mut aggregate = AggregateError::new();
+        let mut values = Vec::new();
+
+        for (id, result) in results {
+            match result {
+                Ok(value) => {
+                    aggregate.push_success(id.clone());
+                    values.push((id, value));
+                }
+                Err(error) => aggregate.push_failure(id, error),
+            }
+        }
+
+        if aggregate.satisfies(policy) {
+            Ok(values)
+        } else {
+            Err(aggregate)
+        }
+    }
+}
+
+impl fmt::Display for AggregateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} branches failed",
+            self.failures.len(),
+            self.total()
+        )?;
+        for failure in &self.failures {
+            write!(f, "\n  - {}: {}", failure.id, failure.error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for AggregateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.failures
+            .first()
+            .map(|failure| failure.error.as_ref() as &(dyn Error + 'static))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results() -> Vec<(String, Result<u32, BoxError>)> {
+        vec![
+            ("a".to_string(), Ok(1)),
+            ("b".to_string(), Err("boom".into())),
+            ("c".to_string(), Ok(3)),
+        ]
+    }
+
+    #[test]
+    fn test_policies() {
+        let err = AggregateError::collect(results(), SuccessPolicy::All).unwrap_err();
+        assert_eq!(err.succeeded, vec!["a", "c"]);
+        assert!(err.satisfies(SuccessPolicy::Any));
+        assert!(err.satisfies(SuccessPolicy::Quorum(2)));
+        assert!(!err.satisfies(SuccessPolicy::Quorum(3)));
+    }
+
+    #[test]
+    fn test_collect_returns_successes_in_order() {
+        let values = AggregateError::collect(results(), SuccessPolicy::Quorum(2)).unwrap();
+        assert_eq!(values, vec![("a".to_string(), 1), ("c".to_string(), 3)]);
+    }
+
+    #[test]
+    fn test_provider_error_from_http() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-request-id", "req_123".parse().unwrap());
+        headers.insert("retry-after", "20".parse().unwrap());
+        let err = ProviderError::from_http(
+            "openai",
+            reqwest::StatusCode::TOO_MANY_REQUESTS,
+            &headers,
+            "Rate limit reached",
+        );
+        assert_eq!(err.kind, ProviderErrorKind::RateLimited);
+        assert_eq!(err.request_id.as_deref(), Some("req_123"));
+        assert_eq!(err.backoff(), Some(Duration::from_secs(20)));
+        assert_eq!(
+            err.to_string(),
+            "openai request failed (429): Rate limit reached [request req_123]"
+        );
+
+        let err = ProviderError::from_status("anthropic", 503, "Overloaded");
+        assert!(err.is_retryable());
+        assert_eq!(err.backoff(), Some(TRANSIENT_BACKOFF));
+        let err = ProviderError::from_status("anthropic", 401, "invalid x-api-key");
+        assert_eq!(err.kind, ProviderErrorKind::Authentication);
+        assert_eq!(err.backoff(), None);
+    }
+
+    #[test]
+    fn test_find_provider_error_in_chain() {
+        let mut aggregate = AggregateError::new();
+        aggregate.push_failure("primary", ProviderError::from_status("openai", 500, "oops"));
+        let boxed: BoxError = Box::new(aggregate);
+        assert_eq!(
+            ProviderError::find(boxed.as_ref()).unwrap().status,
+            Some(500)
+        );
+        let plain: BoxError = "no provider here".into();
+        assert!(ProviderError::find(plain.as_ref()).is_none());
+    }
+
+    #[test]
+    fn test_recovery_strategy() {
+        let cases: Vec<(BoxError, RecoveryStrategy)> = vec![
+            (
+                Box::new(ProviderError::from_status("openai", 429, "slow down")),
+                RecoveryStrategy::Retry {
+                    after: RATE_LIMIT_BACKOFF,
+                },
+            ),
+            (
+                Box::new(ProviderError::new(
+                    "openai",
+                    ProviderErrorKind::QuotaExceeded,
+                    "out of credit",
+                )),
+                RecoveryStrategy::Fallback,
+            ),
+            (
+                Box::new(ProviderError::from_status("openai", 400, "bad schema")),
+                RecoveryStrategy::Abort,
+            ),
+            (Box::new(AgentError::Cancelled), RecoveryStrategy::Abort),
+            (
+                Box::new(AgentError::Timeout(Duration::from_secs(30))),
+                RecoveryStrategy::Escalate,
+            ),
+            (
+                Box::new(NoConsensus {
+                    required: 2,
+                    answers: Vec::new(),
+                }),
+                RecoveryStrategy::Escalate,
+            ),
+            (
+                Box::new(QuotaExceeded {
+                    user_id: "u-1".to_string(),
+                    limit: crate::tenancy::QuotaLimit::RequestsPerDay {
+                        allowed: 1,
+                        used: 1,
+                    },
+                    resets_at: chrono::Utc::now(),
+                }),
+                RecoveryStrategy::Abort,
+            ),
+            ("unknown".into(), RecoveryStrategy::Abort),
+        ];
+        for (error, expected) in cases {
+            assert_eq!(recovery_strategy(error.as_ref()), expected, "{}", error);
+        }
+
+        // Wrapped errors are judged by their source
+        let mut aggregate = AggregateError::new();
+        aggregate.push_failure(
+            "vision",
+            UnsupportedInput {
+                model: "gpt-3.5-turbo".to_string(),
+                capability: crate::provider::capabilities::Capability::Vision,
+            },
+        );
+        assert_eq!(recovery_strategy(&aggregate), RecoveryStrategy::Fallback);
+    }
+
+    #[test]
+    fn test_display_lists_every_failure() {
+        let mut err = AggregateError::new();
+        err.push_failure("search", "timeout");
+        err.push_failure("fetch", "404");
+        err.push_success("summarize");
+
+        let rendered = err.to_string();
+        assert!(rendered.starts_with("2 of 3 branches failed"));
+        assert!(rendered.contains("search: timeout"));
+        assert!(rendered.contains("fetch: 404"));
+        assert_eq!(err.source().unwrap().to_string(), "timeout");
+    }
+}
@@ -0,0 +1,429 @@
+//! Human escalation channels
+//!
+//! When an agent can't or shouldn't continue on its own, it hands the
+//! conversation to a human. An [`Escalation`] packages the reason, the
+//! transcript so far, and structured context, and delivers it somewhere a
+//! person will see it.
+//!
+//! Agents escalate automatically when lifecycle hooks keep rejecting
+//! responses in a conversation (see
+//! [`Agent::with_escalation_threshold`](crate::Agent::with_escalation_threshold)),
+//! and the model can escalate explicitly through the `escalate` tool that
+//! [`Agent::with_escalation`](crate::Agent::with_escalation) registers.
+//!
+//! Channels: [`WebhookEscalation`], [`SlackEscalation`],
+//! [`CallbackEscalation`], and `EmailEscalation` with the `email` feature.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::escalation::WebhookEscalation;
+//!
+//! let agent = create_agent("support")
+//!     .with_escalation(WebhookEscalation::new("https://ops.example.com/handoff"));
+//! ```
+
+use crate::provider::Message;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Rejection streaks not added to for this long are forgotten
+const STREAK_IDLE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Conversations tracked at once; the longest idle is forgotten first
+const MAX_STREAKS: usize = 10_000;
+
+/// Name of the tool the model calls to escalate explicitly
+pub const ESCALATE_TOOL: &str = "escalate";
+
+/// Consecutive rejected responses in a conversation before it escalates
+pub const DEFAULT_ESCALATION_THRESHOLD: usize = 3;
+
+/// Everything a human needs to pick up a conversation
+#[derive(Debug, Clone, Serialize)]
+pub struct EscalationRequest {
+    pub agent: String,
+    pub reason: String,
+    pub transcript: Vec<Message>,
+    pub context: HashMap<String, String>,
+}
+
+impl EscalationRequest {
+    /// Short plain-text summary for chat-style channels
+    pub fn summary(&self) -> String {
+        let last_user = self
+            .transcript
+            .iter()
+            .rev()
+            .find(|m| m.role == "user")
+            .map(|m| m.content.as_str())
+            .unwrap_or("(no user message)");
+        format!(
+            "Agent '{}' escalated: {}\nLast user message: {}",
+            self.agent, self.reason, last_user
+        )
+    }
+
+    /// Summary, full transcript and context as plain text (for email)
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n\nTranscript:\n", self.summary());
+        for message in &self.transcript {
+            text.push_str(&format!("[{}] {}\n", message.role, message.content));
+        }
+        if !self.context.is_empty() {
+            let mut context: Vec<_> = self.context.iter().collect();
+            context.sort();
+            text.push_str("\nContext:\n");
+            for (key, value) in context {
+                text.push_str(&format!("{}: {}\n", key, value));
+            }
+        }
+        text
+    }
+}
+
+/// Consecutive hook rejections per conversation
+///
+/// Streaks idle for [`STREAK_IDLE_TIMEOUT`] are dropped, and at most
+/// [`MAX_STREAKS`] conversations are tracked.
+pub(crate) struct RejectionStreaks {
+    threshold: usize,
+    idle_timeout: Duration,
+    max_streaks: usize,
+    /// Rejections in a row and when the last one happened
+    streaks: Mutex<HashMap<String, (usize, Instant)>>,
+}
+
+impl RejectionStreaks {
+    pub(crate) fn new(threshold: usize) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            idle_timeout: STREAK_IDLE_TIMEOUT,
+            max_streaks: MAX_STREAKS,
+            streaks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a rejection in `conversation`, returning the streak length
+    /// when it reaches the threshold (which starts a new streak)
+    pub(crate) fn reject(&self, conversation: &str) -> Option<usize> {
+        let mut streaks = self.streaks.lock().unwrap();
+        streaks.retain(|_, (_, last)| last.elapsed() < self.idle_timeout);
+        if streaks.len() >= self.max_streaks && !streaks.contains_key(conversation) {
+            let oldest = streaks
+                .iter()
+                .min_by_key(|(_, (_, last))| *last)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                streaks.remove(&oldest);
+            }
+        }
+
+        let streak = streaks
+            .entry(conversation.to_string())
+            .or_insert((0, Instant::now()));
+        *streak = (streak.0 + 1, Instant::now());
+        let reached = streak.0;
+        if reached < self.threshold {
+            return None;
+        }
+        streaks.remove(conversation);
+        Some(reached)
+    }
+
+    /// A response in `conversation` passed every hook
+    pub(crate) fn reset(&self, conversation: &str) {
+        self.streaks.lock().unwrap().remove(conversation);
+    }
+}
+
+/// A channel that delivers escalations to a human
+#[async_trait]
+pub trait Escalation: Send + Sync {
+    /// Deliver the escalation
+    async fn escalate(&self, request: &EscalationRequest) -> crate::Result<()>;
+}
+
+/// POSTs the full escalation request as JSON to a URL
+pub struct WebhookEscalation {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl WebhookEscalation {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Escalation for WebhookEscalation {
+    async fn escalate(&self, request: &EscalationRequest) -> crate::Result<()> {
+        self.client
+            .post(&self.url)
+            .json(request)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Posts a summary to a Slack incoming webhook
+pub struct SlackEscalation {
+    webhook_url: String,
+    client: reqwest::Client,
+}
+
+impl SlackEscalation {
+    pub fn new(webhook_url: impl Into<String>) -> Self {
+        Self {
+            webhook_url: webhook_url.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Escalation for SlackEscalation {
+    async fn escalate(&self, request: &EscalationRequest) -> crate::Result<()> {
+        self.client
+            .post(&self.webhook_url)
+            .json(&serde_json::json!({ "text": request.summary() }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Emails the summary, transcript and context over SMTP
+///
+/// Connects with STARTTLS on port 587 unless told otherwise.
+///
+/// ```ignore
+/// let email = EmailEscalation::new("smtp.example.com", "agent@example.com", "oncall@example.com")
+///     .credentials("agent@example.com", std::env::var("SMTP_PASSWORD")?);
+/// ```
+#[cfg(feature = "email")]
+pub struct EmailEscalation {
+    relay: String,
+    port: Option<u16>,
+    plaintext: bool,
+    credentials: Option<lettre::transport::smtp::authentication::Credentials>,
+    from: String,
+    to: String,
+}
+
+#[cfg(feature = "email")]
+impl EmailEscalation {
+    /// Send from `from` to `to` (comma-separated for several recipients)
+    pub fn new(relay: impl Into<String>, from: impl Into<String>, to: impl Into<String>) -> Self {
+        Self {
+            relay: relay.into(),
+            port: None,
+            plaintext: false,
+            credentials: None,
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    /// Log in to the relay
+    pub fn credentials(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.credentials = Some(lettre::transport::smtp::authentication::Credentials::new(
+            username.into(),
+            password.into(),
+        ));
+        self
+    }
+
+    /// Connect to a port other than the default
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Skip TLS, for a relay on localhost or a trusted network
+    pub fn plaintext(mut self) -> Self {
+        self.plaintext = true;
+        self
+    }
+}
+
+#[cfg(feature = "email")]
+#[async_trait]
+impl Escalation for EmailEscalation {
+    async fn escalate(&self, request: &EscalationRequest) -> crate::Result<()> {
+        use lettre::message::Mailboxes;
+        use lettre::{AsyncSmtpTransport, AsyncTransport, Message as Email, Tokio1Executor};
+
+        let mut email = Email::builder().from(self.from.parse()?).subject(format!(
+            "Agent '{}' escalated: {}",
+            request.agent, request.reason
+        ));
+        for to in self.to.parse::<Mailboxes>()? {
+            email = email.to(to);
+        }
+        let email = email.body(request.to_text())?;
+
+        let mut transport = if self.plaintext {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.relay)
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.relay)?
+        };
+        if let Some(port) = self.port {
+            transport = transport.port(port);
+        }
+        if let Some(credentials) = &self.credentials {
+            transport = transport.credentials(credentials.clone());
+        }
+        transport.build().send(email).await?;
+        Ok(())
+    }
+}
+
+/// Hands escalations to a closure (queues, custom integrations, tests)
+pub struct CallbackEscalation<F> {
+    callback: F,
+}
+
+impl<F> CallbackEscalation<F>
+where
+    F: Fn(&EscalationRequest) -> crate::Result<()> + Send + Sync,
+{
+    pub fn new(callback: F) -> Self {
+        Self { callback }
+    }
+}
+
+#[async_trait]
+impl<F> Escalation for CallbackEscalation<F>
+where
+    F: Fn(&EscalationRequest) -> crate::Result<()> + Send + Sync,
+{
+    async fn escalate(&self, request: &EscalationRequest) -> crate::Result<()> {
+        (self.callback)(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejection_streaks_are_bounded() {
+        let mut streaks = RejectionStreaks::new(2);
+        streaks.max_streaks = 2;
+        assert_eq!(streaks.reject("a"), None);
+        assert_eq!(streaks.reject("b"), None);
+        // A third conversation pushes out the longest idle one
+        assert_eq!(streaks.reject("c"), None);
+        assert_eq!(streaks.reject("a"), None);
+        assert_eq!(streaks.reject("c"), Some(2));
+
+        streaks.idle_timeout = Duration::ZERO;
+        assert_eq!(streaks.reject("d"), None);
+        assert_eq!(streaks.reject("d"), None);
+        assert_eq!(streaks.streaks.lock().unwrap().len(), 1);
+    }
+
+    fn request() -> EscalationRequest {
+        EscalationRequest {
+            agent: "support".to_string(),
+            reason: "refund over limit".to_string(),
+            transcript: vec![Message::system("sys"), Message::user("I want $5000 back")],
+            context: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_summary_includes_reason_and_last_user_message() {
+        let summary = request().summary();
+        assert!(summary.contains("refund over limit"));
+        assert!(summary.contains("I want $5000 back"));
+    }
+
+    #[test]
+    fn test_rejection_streaks_reach_threshold() {
+        let streaks = RejectionStreaks::new(2);
+        assert_eq!(streaks.reject("a"), None);
+        assert_eq!(streaks.reject("b"), None);
+        assert_eq!(streaks.reject("a"), Some(2));
+        // Reaching the threshold starts a new streak
+        assert_eq!(streaks.reject("a"), None);
+        streaks.reset("b");
+        assert_eq!(streaks.reject("b"), None);
+    }
+
+    #[cfg(feature = "email")]
+    #[tokio::test]
+    async fn test_email_sends_transcript() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        // Just enough of an SMTP server to accept one message
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read, mut write) = socket.into_split();
+            let mut lines = BufReader::new(read).lines();
+            write.write_all(b"220 localhost\r\n").await.unwrap();
+            let mut data = String::new();
+            let mut in_data = false;
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let reply: &[u8] = if in_data {
+                    if line != "." {
+                        data.push_str(&line);
+                        data.push('\n');
+                        continue;
+                    }
+                    in_data = false;
+                    b"250 queued\r\n"
+                } else if line.starts_with("DATA") {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if line.starts_with("QUIT") {
+                    write.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                write.write_all(reply).await.unwrap();
+            }
+            data
+        });
+
+        EmailEscalation::new("127.0.0.1", "agent@example.com", "oncall@example.com")
+            .port(port)
+            .plaintext()
+            .escalate(&request())
+            .await
+            .unwrap();
+        let data = server.await.unwrap();
+        assert!(data.contains("To: oncall@example.com"));
+        assert!(data.contains("Subject: Agent 'support' escalated: refund over limit"));
+        assert!(data.contains("[user] I want $5000 back"));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_posts_json() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/handoff")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"agent":"support","reason":"refund over limit"}"#.to_string(),
+            ))
+            .create_async()
+            .await;
+
+        let webhook = WebhookEscalation::new(format!("{}/handoff", server.url()));
+        webhook.escalate(&request()).await.unwrap();
+        mock.assert_async().await;
+    }
+}
@@ -0,0 +1,154 @@
+//! Structured run events
+//!
+//! [`Agent::execute_streaming`](crate::Agent::execute_streaming) reports a
+//! run as a stream of [`AgentEvent`]s so a web UI or TUI can show progress as
+//! it happens: each model turn, the text the model produced, every tool call
+//! with its outcome, and how the run ended. Events serialize to JSON with a
+//! `type` tag, ready to forward over SSE or a WebSocket.
+//!
+//! Token counts are what the provider reported, or estimates (see
+//! [`tokens`](crate::tokens)) from providers that report no usage.
+//!
+//! # Example
+//! ```ignore
+//! use futures::StreamExt;
+//! use patinox::events::AgentEvent;
+//!
+//! let mut events = Box::pin(agent.execute_streaming("Summarize the report"));
+//! while let Some(event) = events.next().await {
+//!     match event {
+//!         AgentEvent::ToolCallStarted { name, .. } => println!("running {}...", name),
+//!         AgentEvent::Completed { output } => println!("{}", output),
+//!         other => tracing::debug!("{:?}", other),
+//!     }
+//! }
+//! ```
+
+use crate::execution::ExecutionContext;
+use crate::provider::{EffectiveConfig, ProviderUsage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Token usage of one model turn
+///
+/// What the provider reported when it reports usage, otherwise estimated
+/// (without reasoning tokens).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnUsage {
+    pub prompt_tokens: usize,
+    /// Includes `reasoning_tokens`
+    pub completion_tokens: usize,
+    #[serde(default)]
+    pub reasoning_tokens: usize,
+}
+
+impl From<ProviderUsage> for TurnUsage {
+    fn from(usage: ProviderUsage) -> Self {
+        Self {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            reasoning_tokens: usage.reasoning_tokens,
+        }
+    }
+}
+
+/// Something that happened during a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    /// The run resolved its provider settings (see
+    /// [`RequestOptions`](crate::provider::RequestOptions)); `context` says
+    /// who the run is for
+    RunStarted {
+        config: EffectiveConfig,
+        context: ExecutionContext,
+    },
+    /// A model call is about to be made; turns count from 1
+    TurnStarted {
+        turn: usize,
+    },
+    /// Text produced by the model
+    LlmDelta {
+        content: String,
+    },
+    ToolCallStarted {
+        name: String,
+        arguments: Value,
+    },
+    /// A tool returned; `error` is set when it failed
+    ToolCallFinished {
+        name: String,
+        output: Option<String>,
+        error: Option<String>,
+        duration_ms: u64,
+    },
+    /// A lifecycle hook rejected the model's response
+    ValidationRejected {
+        reason: String,
+    },
+    /// A turn ended; `duration_ms` covers the model call and any tool calls
+    TurnFinished {
+        turn: usize,
+        usage: TurnUsage,
+        duration_ms: u64,
+    },
+    /// The run produced its final answer
+    Completed {
+        output: String,
+    },
+    /// The run ended with an error
+    Failed {
+        error: String,
+    },
+}
+
+/// Sending half used by the agent loop
+pub(crate) type EventSender = futures::channel::mpsc::UnboundedSender<AgentEvent>;
+
+/// Send an event if someone is listening
+///
+/// The event is only built when there is a listener.
+pub(crate) fn emit(events: Option<&EventSender>, event: impl FnOnce() -> AgentEvent) {
+    if let Some(sender) = events {
+        // A dropped receiver just means nobody is watching any more
+        let _ = sender.unbounded_send(event());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_events_serialize_with_type_tag() {
+        let event = AgentEvent::TurnFinished {
+            turn: 2,
+            usage: TurnUsage {
+                prompt_tokens: 120,
+                completion_tokens: 8,
+                reasoning_tokens: 3,
+            },
+            duration_ms: 900,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({
+                "type": "turn_finished",
+                "turn": 2,
+                "usage": {"prompt_tokens": 120, "completion_tokens": 8, "reasoning_tokens": 3},
+                "duration_ms": 900
+            })
+        );
+        let decoded: AgentEvent =
+            serde_json::from_value(serde_json::to_value(&event).unwrap()).unwrap();
+        assert_eq!(decoded, event);
+        assert_eq!(
+            serde_json::to_value(AgentEvent::LlmDelta {
+                content: "hi".into()
+            })
+            .unwrap(),
+            json!({"type": "llm_delta", "content": "hi"})
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Who and what a run is for
+//!
+//! An [`ExecutionContext`] carries the identifiers of a run (user, session,
+//! trace) and any custom key-values the caller wants downstream code to
+//! see. The agent fills in the execution id when a run starts, then makes
+//! the context available everywhere the run goes:
+//!
+//! - tools read it with [`ExecutionContext::current`], including the async
+//!   work built-in tools hand to a thread of their own
+//! - lifecycle hooks and provider middleware read it the same way
+//! - [`AgentEvent::RunStarted`](crate::AgentEvent::RunStarted) reports it
+//! - escalations carry it as [`EscalationRequest::context`](crate::escalation::EscalationRequest::context)
+//! - the run's `agent.run` tracing span records the user and session
+//!
+//! # Example
+//! ```ignore
+//! use patinox::execution::ExecutionContext;
+//!
+//! let agent = create_agent("support").tool_fn("whoami", "Current user", |_| {
+//!     let context = ExecutionContext::current().unwrap_or_default();
+//!     Ok(context.user_id.unwrap_or_else(|| "anonymous".to_string()))
+//! });
+//!
+//! let context = ExecutionContext::new().user("u-42").value("plan", "pro");
+//! let answer = agent.run_with_context("Who am I?", context).await?;
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+
+tokio::task_local! {
+    static CURRENT: ExecutionContext;
+}
+
+/// Identifiers and custom values of one run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionContext {
+    /// Unique per run; set by the agent when the run starts
+    #[serde(default)]
+    pub execution_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Id of a distributed trace the run belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub values: HashMap<String, String>,
+}
+
+impl ExecutionContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn user(mut self, user_id: impl Into<String>) -> Self {
+        self.user_id = Some(user_id.into());
+        self
+    }
+
+    pub fn session(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    pub fn trace(mut self, trace_id: impl Into<String>) -> Self {
+        self.trace_id = Some(trace_id.into());
+        self
+    }
+
+    /// Add a custom key-value
+    pub fn value(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.values.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// The context of the run the caller is part of, if any
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    /// Every identifier and custom value as flat strings
+    ///
+    /// Identifiers use the keys `execution_id`, `user_id`, `session_id` and
+    /// `trace_id`, and win over custom values of the same name.
+    pub fn to_map(&self) -> HashMap<String, String> {
+        let mut map = self.values.clone();
+        map.insert("execution_id".to_string(), self.execution_id.clone());
+        for (key, value) in [
+            ("user_id", &self.user_id),
+            ("session_id", &self.session_id),
+            ("trace_id", &self.trace_id),
+        ] {
+            if let Some(value) = value {
+                map.insert(key.to_string(), value.clone());
+            }
+        }
+        map
+    }
+
+    /// Run `future` with this as the current context
+    pub(crate) async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT.scope(self, future).await
+    }
+
+    /// Run `f` with this as the current context, for synchronous code
+    pub(crate) fn sync_scope<R>(self, f: impl FnOnce() -> R) -> R {
+        CURRENT.sync_scope(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_current_inside_scope() {
+        assert!(ExecutionContext::current().is_none());
+        let context = ExecutionContext::new().user("u-1").value("plan", "pro");
+        let seen = context
+            .clone()
+            .scope(async { ExecutionContext::current() })
+            .await;
+        assert_eq!(seen, Some(context.clone()));
+
+        let map = context.sync_scope(|| ExecutionContext::current().unwrap().to_map());
+        assert_eq!(map.get("user_id").map(String::as_str), Some("u-1"));
+        assert_eq!(map.get("plan").map(String::as_str), Some("pro"));
+        assert!(!map.contains_key("session_id"));
+    }
+}
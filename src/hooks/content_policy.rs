@@ -0,0 +1,330 @@
+//! Rule-based content policy hook
+//!
+//! Enforces a denylist of keywords and regex patterns on everything that
+//! flows through the agent: user input, model text, tool call arguments and
+//! tool results.
+//! Each rule has a category and severity; violations at or above the
+//! blocking severity stop execution, lower ones are logged.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::hooks::{ContentPolicy, Severity};
+//!
+//! let policy = ContentPolicy::new()
+//!     .deny_keywords("profanity", Severity::High, ["darn", "heck"])
+//!     .deny_pattern("pii", Severity::Medium, r"\b\d{3}-\d{2}-\d{4}\b")?
+//!     .allow("heck of a deal")
+//!     .block_at(Severity::Medium);
+//!
+//! let agent = create_agent("support").with_lifecycle(policy);
+//! ```
+
+use crate::lifecycle::{AgentLifecycle, HookAction};
+use crate::provider::ProviderResponse;
+use async_trait::async_trait;
+use regex::Regex;
+use std::fmt;
+
+/// Severity of a policy rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+/// A single denylist rule
+#[derive(Debug, Clone)]
+struct PolicyRule {
+    category: String,
+    severity: Severity,
+    pattern: Regex,
+}
+
+/// A rule match found in checked content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation {
+    pub category: String,
+    pub severity: Severity,
+    pub matched: String,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:?}): '{}'",
+            self.category, self.severity, self.matched
+        )
+    }
+}
+
+/// Content policy hook built from denylist and allowlist rules
+#[derive(Debug, Clone)]
+pub struct ContentPolicy {
+    rules: Vec<PolicyRule>,
+    allowlist: Vec<Regex>,
+    block_at: Severity,
+}
+
+impl Default for ContentPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContentPolicy {
+    /// Create an empty policy that blocks at `Severity::Medium` and above
+    pub fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            allowlist: Vec::new(),
+            block_at: Severity::Medium,
+        }
+    }
+
+    /// Deny whole-word, case-insensitive keywords
+    ///
+    /// Word boundaries are only required where a keyword starts or ends with
+    /// a word character, so keywords like `c++` or `@admin` still match.
+    pub fn deny_keywords<I, S>(mut self, category: &str, severity: Severity, words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for word in words {
+            let word = word.as_ref().trim();
+            if word.is_empty() {
+                continue;
+            }
+            self.rules.push(PolicyRule {
+                category: category.to_string(),
+                severity,
+                pattern: keyword_regex(word),
+            });
+        }
+        self
+    }
+
+    /// Deny keywords from a wordlist (one per line, `#` starts a comment)
+    pub fn deny_wordlist(self, category: &str, severity: Severity, wordlist: &str) -> Self {
+        let words = wordlist
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or("").trim())
+            .filter(|line| !line.is_empty());
+        self.deny_keywords(category, severity, words)
+    }
+
+    /// Deny content matching a regex pattern
+    pub fn deny_pattern(
+        mut self,
+        category: &str,
+        severity: Severity,
+        pattern: &str,
+    ) -> Result<Self, regex::Error> {
+        self.rules.push(PolicyRule {
+            category: category.to_string(),
+            severity,
+            pattern: Regex::new(pattern)?,
+        });
+        Ok(self)
+    }
+
+    /// Exempt a phrase: denylist matches inside it are ignored
+    pub fn allow(mut self, phrase: &str) -> Self {
+        self.allowlist.push(keyword_regex(phrase));
+        self
+    }
+
+    /// Set the minimum severity that blocks execution
+    pub fn block_at(mut self, severity: Severity) -> Self {
+        self.block_at = severity;
+        self
+    }
+
+    /// Check text against all rules, returning every violation
+    pub fn check(&self, text: &str) -> Vec<PolicyViolation> {
+        let allowed: Vec<(usize, usize)> = self
+            .allowlist
+            .iter()
+            .flat_map(|re| re.find_iter(text).map(|m| (m.start(), m.end())))
+            .collect();
+
+        let mut violations = Vec::new();
+        for rule in &self.rules {
+            for m in rule.pattern.find_iter(text) {
+                let exempt = allowed
+                    .iter()
+                    .any(|(start, end)| m.start() >= *start && m.end() <= *end);
+                if !exempt {
+                    violations.push(PolicyViolation {
+                        category: rule.category.clone(),
+                        severity: rule.severity,
+                        matched: m.as_str().to_string(),
+                    });
+                }
+            }
+        }
+        violations
+    }
+
+    /// Check text and return a rejection message if any violation blocks
+    fn enforce(&self, source: &str, text: &str) -> Option<String> {
+        let (blocking, warnings): (Vec<_>, Vec<_>) = self
+            .check(text)
+            .into_iter()
+            .partition(|v| v.severity >= self.block_at);
+
+        for warning in &warnings {
+            tracing::warn!("Content policy warning in {}: {}", source, warning);
+        }
+
+        if blocking.is_empty() {
+            return None;
+        }
+
+        let details: Vec<String> = blocking.iter().map(ToString::to_string).collect();
+        Some(format!(
+            "Content policy violation in {}: {}",
+            source,
+            details.join(", ")
+        ))
+    }
+}
+
+fn keyword_regex(word: &str) -> Regex {
+    // `\b` next to a symbol would demand a word character beside it, so a
+    // boundary is only added on sides that end in a word character
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let start = if word.starts_with(is_word) { r"\b" } else { "" };
+    let end = if word.ends_with(is_word) { r"\b" } else { "" };
+    // Escaped input always forms a valid pattern
+    Regex::new(&format!("(?i){}{}{}", start, regex::escape(word), end))
+        .expect("escaped keyword regex")
+}
+
+#[async_trait]
+impl AgentLifecycle for ContentPolicy {
+    async fn before_agent(&self, input: &str) -> crate::Result<String> {
+        match self.enforce("input", input) {
+            Some(reason) => Err(reason.into()),
+            None => Ok(input.to_string()),
+        }
+    }
+
+    async fn after_model(&self, response: &ProviderResponse) -> crate::Result<HookAction> {
+        let rejection = match response {
+            ProviderResponse::Text(text) => self.enforce("response", text),
+            ProviderResponse::ToolCalls(calls) => calls.iter().find_map(|call| {
+                self.enforce(
+                    &format!("arguments to '{}'", call.name),
+                    &call.arguments.to_string(),
+                )
+            }),
+        };
+
+        Ok(match rejection {
+            Some(reason) => HookAction::Reject(reason),
+            None => HookAction::Continue,
+        })
+    }
+
+    async fn after_tool(&self, name: &str, output: &str) -> crate::Result<String> {
+        match self.enforce(&format!("output of '{}'", name), output) {
+            Some(reason) => Err(reason.into()),
+            None => Ok(output.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::ToolCall;
+    use serde_json::json;
+
+    fn policy() -> ContentPolicy {
+        ContentPolicy::new()
+            .deny_keywords("profanity", Severity::High, ["darn"])
+            .deny_keywords("tone", Severity::Low, ["stupid"])
+            .deny_pattern("pii", Severity::Medium, r"\b\d{3}-\d{2}-\d{4}\b")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_keywords_match_whole_words_case_insensitive() {
+        let policy = policy();
+        assert_eq!(policy.check("DARN it").len(), 1);
+        assert!(policy.check("darnation").is_empty());
+    }
+
+    #[test]
+    fn test_keywords_with_symbols_match() {
+        let policy = ContentPolicy::new().deny_keywords(
+            "blocked",
+            Severity::High,
+            ["$hit", "c++", "@admin"],
+        );
+        assert_eq!(policy.check("that's $hit").len(), 1);
+        assert_eq!(policy.check("I love C++ code").len(), 1);
+        assert_eq!(policy.check("ping @admin now").len(), 1);
+        assert!(policy.check("c and admin").is_empty());
+    }
+
+    #[test]
+    fn test_allowlist_exempts_phrase() {
+        let policy = policy().allow("darn good");
+        assert!(policy.check("that is darn good").is_empty());
+        assert_eq!(policy.check("darn, that's bad").len(), 1);
+    }
+
+    #[test]
+    fn test_wordlist_parsing_skips_comments() {
+        let policy =
+            ContentPolicy::new().deny_wordlist("spam", Severity::High, "# header\nfoo\n\nbar # x");
+        assert_eq!(policy.check("foo and bar").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_input_blocked_at_threshold() {
+        let policy = policy();
+        assert!(policy.before_agent("my ssn is 123-45-6789").await.is_err());
+        // Low severity only warns
+        assert!(policy.before_agent("that's stupid").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_response_and_tool_arguments_rejected() {
+        let policy = policy();
+
+        let text = ProviderResponse::Text("well darn".to_string());
+        assert!(matches!(
+            policy.after_model(&text).await.unwrap(),
+            HookAction::Reject(_)
+        ));
+
+        let calls = ProviderResponse::ToolCalls(vec![ToolCall {
+            id: "1".to_string(),
+            name: "lookup".to_string(),
+            arguments: json!({"ssn": "123-45-6789"}),
+        }]);
+        match policy.after_model(&calls).await.unwrap() {
+            HookAction::Reject(reason) => assert!(reason.contains("lookup")),
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_output_rejected() {
+        let policy = policy();
+        let err = policy
+            .after_tool("lookup", "ssn: 123-45-6789")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("output of 'lookup'"));
+        assert_eq!(
+            policy.after_tool("lookup", "all clear").await.unwrap(),
+            "all clear"
+        );
+    }
+}
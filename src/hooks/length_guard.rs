@@ -0,0 +1,186 @@
+//! Length guard hook - cap prompt and response size
+//!
+//! Prevents runaway costs by checking the estimated prompt size before every
+//! model call and the response size after it. Oversized content is either
+//! rejected or truncated depending on the [`Overflow`] policy.
+//!
+//! Prompt truncation drops the oldest conversation messages (never the
+//! system prompt or the latest message) until the estimate fits.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::hooks::{LengthGuard, Overflow};
+//!
+//! let guard = LengthGuard::new()
+//!     .max_prompt_tokens(8_000)
+//!     .max_response_chars(4_000)
+//!     .overflow(Overflow::Truncate);
+//!
+//! let agent = create_agent("summarizer").with_lifecycle(guard);
+//! ```
+
+use crate::lifecycle::{AgentLifecycle, HookAction};
+use crate::provider::{Message, ProviderResponse};
+use crate::tokens::estimate_message_tokens;
+use async_trait::async_trait;
+
+/// What to do when a limit is exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Fail the request
+    Reject,
+    /// Cut content down to the limit
+    Truncate,
+}
+
+/// Hook enforcing prompt-token and response-size limits
+#[derive(Debug, Clone)]
+pub struct LengthGuard {
+    max_prompt_tokens: Option<usize>,
+    max_response_chars: Option<usize>,
+    overflow: Overflow,
+}
+
+impl Default for LengthGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LengthGuard {
+    /// Create a guard with no limits that rejects on overflow
+    pub fn new() -> Self {
+        Self {
+            max_prompt_tokens: None,
+            max_response_chars: None,
+            overflow: Overflow::Reject,
+        }
+    }
+
+    /// Limit the estimated prompt size sent to the model
+    pub fn max_prompt_tokens(mut self, tokens: usize) -> Self {
+        self.max_prompt_tokens = Some(tokens);
+        self
+    }
+
+    /// Limit the size of text responses
+    pub fn max_response_chars(mut self, chars: usize) -> Self {
+        self.max_response_chars = Some(chars);
+        self
+    }
+
+    /// Set the overflow policy
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+}
+
+#[async_trait]
+impl AgentLifecycle for LengthGuard {
+    async fn before_model(&self, mut messages: Vec<Message>) -> crate::Result<Vec<Message>> {
+        let Some(limit) = self.max_prompt_tokens else {
+            return Ok(messages);
+        };
+
+        if self.overflow == Overflow::Truncate {
+            // Drop the oldest droppable message until we fit
+            while estimate_message_tokens(&messages) > limit {
+                let droppable = messages
+                    .iter()
+                    .enumerate()
+                    .take(messages.len().saturating_sub(1))
+                    .find(|(_, m)| m.role != "system")
+                    .map(|(index, _)| index);
+                match droppable {
+                    Some(index) => {
+                        messages.remove(index);
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        let estimate = estimate_message_tokens(&messages);
+        if estimate > limit {
+            return Err(format!(
+                "Prompt too large: ~{} tokens exceeds limit of {}",
+                estimate, limit
+            )
+            .into());
+        }
+
+        Ok(messages)
+    }
+
+    async fn after_model(&self, response: &ProviderResponse) -> crate::Result<HookAction> {
+        let (Some(limit), ProviderResponse::Text(text)) = (self.max_response_chars, response)
+        else {
+            return Ok(HookAction::Continue);
+        };
+
+        let length = text.chars().count();
+        if length <= limit {
+            return Ok(HookAction::Continue);
+        }
+
+        Ok(match self.overflow {
+            Overflow::Reject => HookAction::Reject(format!(
+                "Response too large: {} characters exceeds limit of {}",
+                length, limit
+            )),
+            Overflow::Truncate => {
+                HookAction::Modify(ProviderResponse::Text(text.chars().take(limit).collect()))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn long_history() -> Vec<Message> {
+        vec![
+            Message::system("be brief"),
+            Message::user("a".repeat(400)),
+            Message::assistant("b".repeat(400)),
+            Message::user("latest question"),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_prompt_over_limit_rejected() {
+        let guard = LengthGuard::new().max_prompt_tokens(50);
+        assert!(guard.before_model(long_history()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_prompt_truncation_keeps_system_and_latest() {
+        let guard = LengthGuard::new()
+            .max_prompt_tokens(50)
+            .overflow(Overflow::Truncate);
+
+        let messages = guard.before_model(long_history()).await.unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].role, "system");
+        assert_eq!(messages[1].content, "latest question");
+    }
+
+    #[tokio::test]
+    async fn test_response_limits() {
+        let response = ProviderResponse::Text("héllo world".to_string());
+
+        let reject = LengthGuard::new().max_response_chars(5);
+        assert!(matches!(
+            reject.after_model(&response).await.unwrap(),
+            HookAction::Reject(_)
+        ));
+
+        let truncate = reject.overflow(Overflow::Truncate);
+        match truncate.after_model(&response).await.unwrap() {
+            HookAction::Modify(ProviderResponse::Text(text)) => assert_eq!(text, "héllo"),
+            other => panic!("Expected Modify, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+//! Built-in lifecycle hooks
+//!
+//! Ready-made [`AgentLifecycle`](crate::lifecycle::AgentLifecycle)
+//! implementations for common guardrails. Like every hook they are opt-in:
+//! attach them with [`Agent::with_lifecycle`](crate::Agent::with_lifecycle).
+//!
+//! ## Available Hooks
+//!
+//! - [`ContentPolicy`] - Rule-based content filtering (keywords and regexes
+//!   with severities), no LLM calls required
+//! - [`ToolPermissions`] - Allowlist/denylist and argument rules for tool calls
+//! - [`LengthGuard`] - Prompt-token and response-size limits
+
+pub mod content_policy;
+pub mod length_guard;
+pub mod tool_permission;
+
+pub use content_policy::{ContentPolicy, PolicyViolation, Severity};
+pub use length_guard::{LengthGuard, Overflow};
+pub use tool_permission::ToolPermissions;
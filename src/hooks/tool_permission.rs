@@ -0,0 +1,218 @@
+//! Tool permission hook
+//!
+//! Checks every tool call the model requests against the agent's declared
+//! policy before anything executes: an optional allowlist, a denylist,
+//! per-tool argument patterns (e.g. deny `shell` commands matching `rm -rf`),
+//! and optionally every tool marked [`dangerous`](crate::Tool::dangerous)
+//! that the allowlist doesn't name.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::hooks::ToolPermissions;
+//!
+//! let agent = create_agent("ops").tool(shell).tool(sandbox.write_tool());
+//! let permissions = ToolPermissions::new()
+//!     .deny_arguments("shell", r"\brm\s+-rf\b")?
+//!     .deny_dangerous(agent.tools());
+//!
+//! let agent = agent.with_lifecycle(permissions);
+//! ```
+
+use crate::lifecycle::{AgentLifecycle, HookAction};
+use crate::provider::{ProviderResponse, ToolCall};
+use crate::tool::ToolRegistry;
+use async_trait::async_trait;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Allowlist/denylist policy for tool calls
+#[derive(Debug, Clone, Default)]
+pub struct ToolPermissions {
+    allowed: Option<HashSet<String>>,
+    denied: HashSet<String>,
+    /// Dangerous tools, denied unless the allowlist names them
+    dangerous: HashSet<String>,
+    argument_rules: Vec<(String, Regex)>,
+}
+
+impl ToolPermissions {
+    /// Create a policy that permits every tool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Permit only the listed tools
+    pub fn allow_only<I, S>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed = Some(tools.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Deny a tool outright (takes precedence over the allowlist)
+    pub fn deny(mut self, tool: impl Into<String>) -> Self {
+        self.denied.insert(tool.into());
+        self
+    }
+
+    /// Deny the dangerous tools in `tools` unless [`allow_only`](Self::allow_only)
+    /// names them explicitly
+    ///
+    /// Tools registered after this call are not covered.
+    pub fn deny_dangerous(mut self, tools: &ToolRegistry) -> Self {
+        self.dangerous.extend(
+            tools
+                .iter()
+                .filter(|tool| tool.dangerous())
+                .map(|tool| tool.name().to_string()),
+        );
+        self
+    }
+
+    /// Deny calls to `tool` where any string argument matches `pattern`
+    pub fn deny_arguments(
+        mut self,
+        tool: impl Into<String>,
+        pattern: &str,
+    ) -> Result<Self, regex::Error> {
+        self.argument_rules
+            .push((tool.into(), Regex::new(pattern)?));
+        Ok(self)
+    }
+
+    /// Check a single tool call, returning the reason it is not permitted
+    pub fn check(&self, call: &ToolCall) -> Result<(), String> {
+        if self.denied.contains(&call.name) {
+            return Err(format!("Tool '{}' is denied by policy", call.name));
+        }
+
+        match &self.allowed {
+            Some(allowed) if !allowed.contains(&call.name) => {
+                return Err(format!("Tool '{}' is not in the allowlist", call.name));
+            }
+            None if self.dangerous.contains(&call.name) => {
+                return Err(format!(
+                    "Tool '{}' is dangerous and not explicitly allowed",
+                    call.name
+                ));
+            }
+            _ => {}
+        }
+
+        let mut strings = Vec::new();
+        collect_strings(&call.arguments, &mut strings);
+
+        for (tool, pattern) in &self.argument_rules {
+            if tool != &call.name {
+                continue;
+            }
+            if let Some(value) = strings.iter().find(|s| pattern.is_match(s)) {
+                return Err(format!(
+                    "Arguments to '{}' rejected by policy (matched /{}/): {}",
+                    call.name, pattern, value
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Gather every string leaf in a JSON value
+fn collect_strings<'a>(value: &'a Value, out: &mut Vec<&'a str>) {
+    match value {
+        Value::String(s) => out.push(s),
+        Value::Array(items) => items.iter().for_each(|item| collect_strings(item, out)),
+        Value::Object(map) => map.values().for_each(|item| collect_strings(item, out)),
+        _ => {}
+    }
+}
+
+#[async_trait]
+impl AgentLifecycle for ToolPermissions {
+    async fn after_model(&self, response: &ProviderResponse) -> crate::Result<HookAction> {
+        if let ProviderResponse::ToolCalls(calls) = response {
+            for call in calls {
+                if let Err(reason) = self.check(call) {
+                    return Ok(HookAction::Reject(reason));
+                }
+            }
+        }
+        Ok(HookAction::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn call(name: &str, arguments: Value) -> ToolCall {
+        ToolCall {
+            id: "call_1".to_string(),
+            name: name.to_string(),
+            arguments,
+        }
+    }
+
+    #[test]
+    fn test_allowlist_and_denylist() {
+        let permissions = ToolPermissions::new()
+            .allow_only(["read_file", "write_file"])
+            .deny("write_file");
+
+        assert!(permissions.check(&call("read_file", json!({}))).is_ok());
+        assert!(permissions.check(&call("write_file", json!({}))).is_err());
+        assert!(permissions.check(&call("shell", json!({}))).is_err());
+    }
+
+    #[test]
+    fn test_dangerous_tools_need_explicit_allow() {
+        let sandbox = crate::tool::fs::FsSandbox::new(std::env::temp_dir()).unwrap();
+        let agent = crate::create_agent("ops")
+            .tool(sandbox.read_tool())
+            .tool(sandbox.write_tool());
+
+        let permissions = ToolPermissions::new().deny_dangerous(agent.tools());
+        assert!(permissions.check(&call("read_file", json!({}))).is_ok());
+        let reason = permissions
+            .check(&call("write_file", json!({})))
+            .unwrap_err();
+        assert!(reason.contains("not explicitly allowed"));
+
+        let permissions = permissions.allow_only(["write_file"]);
+        assert!(permissions.check(&call("write_file", json!({}))).is_ok());
+    }
+
+    #[test]
+    fn test_argument_patterns_scan_nested_strings() {
+        let permissions = ToolPermissions::new()
+            .deny_arguments("shell", r"\brm\s+-rf\b")
+            .unwrap();
+
+        let safe = call("shell", json!({"command": "ls", "args": ["-la"]}));
+        let unsafe_call = call("shell", json!({"steps": [{"command": "rm -rf /"}]}));
+        let other_tool = call("notes", json!({"text": "rm -rf is dangerous"}));
+
+        assert!(permissions.check(&safe).is_ok());
+        assert!(permissions.check(&unsafe_call).is_err());
+        assert!(permissions.check(&other_tool).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_hook_rejects_disallowed_calls() {
+        let permissions = ToolPermissions::new().allow_only(["search"]);
+        let response = ProviderResponse::ToolCalls(vec![
+            call("search", json!({})),
+            call("delete_everything", json!({})),
+        ]);
+
+        match permissions.after_model(&response).await.unwrap() {
+            HookAction::Reject(reason) => assert!(reason.contains("delete_everything")),
+            other => panic!("Expected Reject, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! What a running agent binary is made of
+//!
+//! [`runtime_info`] reports the crate version, compiled-in features, the
+//! configured providers (names only, never keys), tools, plugins and the
+//! available vector store backends. The CLI prints it with `--info` (or as a
+//! startup banner when `PATINOX_BANNER` is set) and the HTTP server exposes
+//! it at `GET /info`, so it is easy to confirm what a deployment runs.
+
+use crate::agent::Agent;
+use serde::Serialize;
+use std::fmt;
+
+/// Snapshot of an agent binary's build and configuration
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeInfo {
+    pub version: String,
+    pub features: Vec<String>,
+    pub agent: String,
+    pub model: String,
+    pub providers: Vec<String>,
+    pub tools: Vec<String>,
+    pub plugins: Vec<String>,
+    pub store_backends: Vec<String>,
+}
+
+/// Cargo features compiled into this build
+fn enabled_features() -> Vec<String> {
+    [
+        ("mcp", cfg!(feature = "mcp")),
+        ("timezones", cfg!(feature = "timezones")),
+        ("server", cfg!(feature = "server")),
+        ("pgvector", cfg!(feature = "pgvector")),
+        ("sqlite", cfg!(feature = "sqlite")),
+        ("subscriber", cfg!(feature = "subscriber")),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| name.to_string())
+    .collect()
+}
+
+/// Vector store backends available in this build
+fn store_backends() -> Vec<String> {
+    let mut backends = vec!["memory".to_string()];
+    if cfg!(feature = "pgvector") {
+        backends.push("pgvector".to_string());
+    }
+    backends
+}
+
+/// Describe `agent` and the build it runs in
+pub fn runtime_info(agent: &Agent) -> RuntimeInfo {
+    RuntimeInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        features: enabled_features(),
+        agent: agent.config.name.clone(),
+        model: agent.model().to_string(),
+        providers: agent
+            .providers()
+            .into_iter()
+            .map(|provider| format!("{:?}", provider))
+            .collect(),
+        tools: agent
+            .tools
+            .iter()
+            .map(|tool| tool.name().to_string())
+            .collect(),
+        plugins: agent.plugins.clone(),
+        store_backends: store_backends(),
+    }
+}
+
+impl fmt::Display for RuntimeInfo {
+    /// Startup banner
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |items: &[String]| {
+            if items.is_empty() {
+                "none".to_string()
+            } else {
+                items.join(", ")
+            }
+        };
+        writeln!(f, "patinox {} - agent '{}'", self.version, self.agent)?;
+        writeln!(f, "  model:     {}", self.model)?;
+        writeln!(f, "  providers: {}", list(&self.providers))?;
+        writeln!(f, "  tools:     {}", list(&self.tools))?;
+        writeln!(f, "  plugins:   {}", list(&self.plugins))?;
+        writeln!(f, "  stores:    {}", list(&self.store_backends))?;
+        write!(f, "  features:  {}", list(&self.features))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_agent;
+    use crate::plugin::AgentPlugin;
+    use crate::provider::{MockProvider, Provider};
+
+    struct Greeter;
+
+    impl AgentPlugin for Greeter {
+        fn name(&self) -> &str {
+            "greeter"
+        }
+
+        fn apply(&self, agent: Agent) -> Agent {
+            agent.tool_fn("greet", "Say hello", |_| Ok("hello".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_runtime_info() {
+        let agent = create_agent("support")
+            .with_plugin(Greeter)
+            .with_provider_for(Provider::Ollama, Box::new(MockProvider::new("")));
+        let info = runtime_info(&agent);
+
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.providers, vec!["Anthropic", "Ollama"]);
+        assert_eq!(info.tools, vec!["greet"]);
+        assert_eq!(info.plugins, vec!["greeter"]);
+        assert_eq!(info.store_backends[0], "memory");
+
+        let banner = info.to_string();
+        assert!(banner.starts_with(&format!("patinox {} - agent 'support'\n", info.version)));
+        assert!(banner.contains("  tools:     greet\n"));
+    }
+}
@@ -16,19 +16,83 @@
 //!     agent.run_cli()
 //! }
 //! ```
+//!
+//! # Features
+//!
+//! The agent loop, tools, hooks and the OpenAI-compatible provider are
+//! always built. Integrations are opt-in:
+//!
+//! | Feature     | Default | Adds |
+//! |-------------|---------|------|
+//! | `mcp`       | yes     | `tool::mcp` client tools and the `--mcp` stdio server |
+//! | `timezones` | yes     | `date_context` and the `current_time` tool |
+//! | `server`    | no      | `serve`, the OpenAI-compatible HTTP server |
+//! | `webhook`   | no      | `webhook`, signed webhooks that trigger runs (implies `server`) |
+//! | `slack`     | no      | `plugin::slack`, a Socket Mode Slack bot |
+//! | `email`     | no      | `escalation::EmailEscalation`, escalations over SMTP |
+//! | `pgvector`  | no      | the Postgres vector store |
+//! | `sqlite`    | no      | `session::sqlite` and `tenancy::sqlite`, SQLite session and quota stores |
+//! | `subscriber`| no      | `telemetry::init_tracing`, console or JSON output for tracing spans |
+//! | `full`      | no      | all of the above |
+//!
+//! `minimal` (or `--no-default-features`) builds only the core. Core types
+//! never mention feature-gated ones, so every combination compiles; CI
+//! checks each feature on its own on top of the minimal build.
 
+pub mod admission;
 pub mod agent;
+pub mod approval;
+pub mod audit;
+pub mod bus;
 pub mod cli;
+pub mod context;
+#[cfg(feature = "timezones")]
+pub mod date_context;
+pub mod dry_run;
+pub mod error;
+pub mod escalation;
+pub mod events;
+pub mod execution;
+pub mod hooks;
+pub mod info;
 pub mod lifecycle;
+pub mod loop_guard;
+pub mod manifest;
+#[cfg(feature = "mcp")]
+pub mod mcp;
+pub mod memory;
 pub mod plugin;
+pub mod prompt;
 pub mod provider;
+pub mod retrieval;
+pub mod runtime;
+pub mod sanitize;
+pub mod scheduler;
+#[cfg(feature = "server")]
+pub mod serve;
+pub mod session;
+#[cfg(feature = "subscriber")]
+pub mod telemetry;
+pub mod tenancy;
+pub mod testing;
+pub mod tokens;
 pub mod tool;
+pub mod topics;
+pub mod trace;
+pub mod transcript;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+pub mod workflow;
+pub mod workspace;
 
 pub use agent::{create_agent, Agent, AgentConfig};
 pub use cli::run_cli;
+pub use error::{AgentError, ProviderError, ProviderErrorKind, RecoveryStrategy};
 pub use lifecycle::{AgentLifecycle, HookAction};
+pub use manifest::AgentManifest;
 pub use plugin::AgentPlugin;
 pub use provider::{LLMProvider, OpenAIProvider, Provider};
+pub use tokio_util::sync::CancellationToken;
 pub use tool::{FnTool, Tool};
 
 /// Prelude module for convenient imports
@@ -1,6 +1,6 @@
 //! Agent lifecycle hooks for middleware and intervention points
 //!
-//! The lifecycle system provides 6 hook points where middleware can intercept,
+//! The lifecycle system provides 7 hook points where middleware can intercept,
 //! modify, or observe agent execution:
 //!
 //! - `before_agent`: Transform input before processing
@@ -8,6 +8,7 @@
 //! - `wrap_model_call`: Wrap LLM calls (retry, fallback, logging)
 //! - `after_model`: Inspect/modify response, HITL approval
 //! - `wrap_tool_call`: Wrap tool execution (retry, logging)
+//! - `after_tool`: Check or rewrite tool results
 //! - `after_agent`: Transform final result
 //!
 //! # Example
@@ -45,6 +46,12 @@ pub enum HookAction {
     /// Reject with error message
     Reject(String),
 
+    /// Pause for a human decision through the agent's approval gate
+    ///
+    /// The run continues if approved and fails if denied or if the agent
+    /// has no gate.
+    Escalate(String),
+
     /// Modify the provider response
     Modify(ProviderResponse),
 }
@@ -61,7 +68,8 @@ pub enum HookAction {
 /// 3. `wrap_model_call` - Wraps the LLM call itself
 /// 4. `after_model` - Called after each LLM response
 /// 5. `wrap_tool_call` - Wraps each tool execution
-/// 6. `after_agent` - Called once before returning final result
+/// 6. `after_tool` - Called with each successful tool result
+/// 7. `after_agent` - Called once before returning final result
 ///
 /// # Example: Logging Hook
 ///
@@ -85,6 +93,13 @@ pub enum HookAction {
 /// ```
 #[async_trait]
 pub trait AgentLifecycle: Send + Sync {
+    /// Name shown in the agent's [manifest](crate::manifest); defaults to
+    /// the type name
+    fn name(&self) -> &str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
     /// Called before agent starts processing input
     ///
     /// Use this for:
@@ -178,6 +193,22 @@ pub trait AgentLifecycle: Send + Sync {
         f.await
     }
 
+    /// Called with each tool's output before the model sees it
+    ///
+    /// Use this for:
+    /// - Content filtering of data the agent fetched
+    /// - Redacting secrets from tool output
+    ///
+    /// # Arguments
+    /// * `name` - Name of the tool that ran
+    /// * `output` - What the tool returned
+    ///
+    /// # Returns
+    /// Transformed output, or an error that fails the run
+    async fn after_tool(&self, _name: &str, output: &str) -> crate::Result<String> {
+        Ok(output.to_string())
+    }
+
     /// Called after agent completes execution
     ///
     /// Use this for:
@@ -316,4 +347,12 @@ mod tests {
             _ => panic!("Expected Modify variant"),
         }
     }
+
+    // TEST 10: after_tool default implementation passes through
+    #[tokio::test]
+    async fn test_after_tool_default_passthrough() {
+        let hook = DefaultHook;
+        let output = hook.after_tool("lookup", "42").await.unwrap();
+        assert_eq!(output, "42");
+    }
 }
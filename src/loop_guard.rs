@@ -0,0 +1,247 @@
+//! Tool loop guard
+//!
+//! A model can get stuck calling the same tool with the same arguments over
+//! and over, burning tokens without making progress. The [`LoopGuard`] caps
+//! the number of model turns in a run and stops the run when one
+//! `(tool, arguments)` pair repeats too often within a window of recent
+//! calls. Either way the run fails with [`LoopDetected`], which carries the
+//! run's tool calls for debugging.
+//!
+//! Every agent has a guard; the default allows 10 turns and 5 identical
+//! calls among the last 10.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::loop_guard::LoopGuard;
+//!
+//! let agent = create_agent("researcher")
+//!     .with_loop_guard(LoopGuard::new().max_iterations(25).max_repeats(2));
+//!
+//! if let Err(e) = agent.run("dig into this").await {
+//!     if let Some(detected) = e.downcast_ref::<LoopDetected>() {
+//!         eprintln!("{}", detected);
+//!     }
+//! }
+//! ```
+
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+const DEFAULT_MAX_ITERATIONS: usize = 10;
+const DEFAULT_WINDOW: usize = 10;
+const DEFAULT_MAX_REPEATS: usize = 5;
+
+/// Limits on how long a run may keep calling tools
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopGuard {
+    max_iterations: usize,
+    window: usize,
+    max_repeats: usize,
+}
+
+impl Default for LoopGuard {
+    fn default() -> Self {
+        Self {
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            window: DEFAULT_WINDOW,
+            max_repeats: DEFAULT_MAX_REPEATS,
+        }
+    }
+}
+
+impl LoopGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Model turns allowed per run
+    pub fn max_iterations(mut self, turns: usize) -> Self {
+        self.max_iterations = turns.max(1);
+        self
+    }
+
+    /// Number of most recent calls checked for repeats
+    pub fn window(mut self, calls: usize) -> Self {
+        self.window = calls.max(1);
+        self
+    }
+
+    /// Identical calls allowed within the window
+    pub fn max_repeats(mut self, calls: usize) -> Self {
+        self.max_repeats = calls.max(1);
+        self
+    }
+
+    /// Model turns allowed per run
+    pub fn iteration_limit(&self) -> usize {
+        self.max_iterations
+    }
+
+    /// Start checking a new run
+    pub(crate) fn start(&self) -> LoopTracker {
+        LoopTracker {
+            guard: *self,
+            history: Vec::new(),
+        }
+    }
+}
+
+/// A tool call made during the run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GuardedCall {
+    /// Model turn the call was made in, counting from 1
+    pub turn: usize,
+    pub tool: String,
+    pub arguments: Value,
+}
+
+/// Why the guard stopped a run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LoopReason {
+    /// The run used all its model turns and still wanted tools
+    MaxIterations { turns: usize },
+    /// The same call was made `count` times within the window
+    RepeatedCall {
+        tool: String,
+        arguments: Value,
+        count: usize,
+    },
+}
+
+/// A run stopped by the [`LoopGuard`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoopDetected {
+    pub reason: LoopReason,
+    /// Every tool call of the run, oldest first
+    pub history: Vec<GuardedCall>,
+}
+
+impl fmt::Display for LoopDetected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reason {
+            LoopReason::MaxIterations { turns } => {
+                write!(f, "Max tool calling iterations reached ({} turns)", turns)?
+            }
+            LoopReason::RepeatedCall {
+                tool,
+                arguments,
+                count,
+            } => write!(
+                f,
+                "Tool loop detected: {}({}) called {} times",
+                tool, arguments, count
+            )?,
+        }
+        for call in self.history.iter().rev().take(5).rev() {
+            write!(
+                f,
+                "\n  turn {}: {}({})",
+                call.turn, call.tool, call.arguments
+            )?;
+        }
+        if self.history.len() > 5 {
+            write!(f, "\n  ({} earlier calls)", self.history.len() - 5)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LoopDetected {}
+
+/// Calls of one run, checked against a [`LoopGuard`]
+#[derive(Debug)]
+pub(crate) struct LoopTracker {
+    guard: LoopGuard,
+    history: Vec<GuardedCall>,
+}
+
+impl LoopTracker {
+    pub(crate) fn max_iterations(&self) -> usize {
+        self.guard.max_iterations
+    }
+
+    /// Record a call about to be made, failing if it repeats too often
+    pub(crate) fn record(
+        &mut self,
+        turn: usize,
+        tool: &str,
+        arguments: &Value,
+    ) -> Result<(), LoopDetected> {
+        self.history.push(GuardedCall {
+            turn,
+            tool: tool.to_string(),
+            arguments: arguments.clone(),
+        });
+        let start = self.history.len().saturating_sub(self.guard.window);
+        let count = self.history[start..]
+            .iter()
+            .filter(|call| call.tool == tool && call.arguments == *arguments)
+            .count();
+        if count > self.guard.max_repeats {
+            return Err(LoopDetected {
+                reason: LoopReason::RepeatedCall {
+                    tool: tool.to_string(),
+                    arguments: arguments.clone(),
+                    count,
+                },
+                history: std::mem::take(&mut self.history),
+            });
+        }
+        Ok(())
+    }
+
+    /// The error for a run that ran out of turns
+    pub(crate) fn exhausted(self) -> LoopDetected {
+        LoopDetected {
+            reason: LoopReason::MaxIterations {
+                turns: self.guard.max_iterations,
+            },
+            history: self.history,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_repeats_within_window() {
+        let mut tracker = LoopGuard::new().window(3).max_repeats(2).start();
+        let same = json!({"q": "rust"});
+        tracker.record(1, "search", &same).unwrap();
+        tracker.record(2, "search", &same).unwrap();
+        // Different arguments are a different call
+        tracker.record(3, "search", &json!({"q": "go"})).unwrap();
+        tracker.record(4, "fetch", &same).unwrap();
+        // The first two calls have left the window
+        tracker.record(5, "search", &same).unwrap();
+        tracker.record(6, "search", &same).unwrap();
+
+        let detected = tracker.record(7, "search", &same).unwrap_err();
+        assert_eq!(
+            detected.reason,
+            LoopReason::RepeatedCall {
+                tool: "search".to_string(),
+                arguments: same,
+                count: 3,
+            }
+        );
+        assert_eq!(detected.history.len(), 7);
+        let rendered = detected.to_string();
+        assert!(rendered.starts_with(r#"Tool loop detected: search({"q":"rust"}) called 3 times"#));
+        assert!(rendered.ends_with("(2 earlier calls)"));
+    }
+
+    #[test]
+    fn test_exhausted() {
+        let mut tracker = LoopGuard::new().max_iterations(2).start();
+        tracker.record(1, "search", &json!({})).unwrap();
+        let detected = tracker.exhausted();
+        assert_eq!(detected.reason, LoopReason::MaxIterations { turns: 2 });
+        assert_eq!(detected.history[0].tool, "search");
+    }
+}
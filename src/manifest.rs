@@ -0,0 +1,488 @@
+//! Agent manifest and documentation generation
+//!
+//! A manifest is a snapshot of how an agent is actually configured - its
+//! provider, model, prompt, tools, hooks and the budgets runs work within.
+//! Provider settings come from the attached provider when it has a
+//! configuration, as requests do. Rendering it as markdown keeps team
+//! wikis and operational docs in sync with the real configuration instead
+//! of a hand-maintained description.
+
+use crate::agent::Agent;
+use crate::provider::CapabilityWarning;
+use crate::tenancy::Quota;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Tool entry in an agent manifest
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolManifest {
+    pub name: String,
+    pub description: String,
+    /// Arguments declared in the tool's JSON schema, sorted by name
+    pub parameters: Vec<ParameterManifest>,
+}
+
+/// One argument of a tool
+#[derive(Debug, Clone, Serialize)]
+pub struct ParameterManifest {
+    pub name: String,
+    /// JSON schema type, e.g. `string` or `integer | null`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub required: bool,
+    pub description: Option<String>,
+}
+
+impl ParameterManifest {
+    /// Parameters from the `properties` of a tool's argument schema
+    fn from_schema(schema: &Value) -> Vec<Self> {
+        let Some(properties) = schema["properties"].as_object() else {
+            return Vec::new();
+        };
+        let required: Vec<&str> = schema["required"]
+            .as_array()
+            .map(|names| names.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+
+        properties
+            .iter()
+            .map(|(name, property)| Self {
+                name: name.clone(),
+                kind: schema_type(property),
+                required: required.contains(&name.as_str()),
+                description: property["description"].as_str().map(str::to_string),
+            })
+            .collect()
+    }
+}
+
+/// Human-readable type of a JSON schema property
+fn schema_type(property: &Value) -> String {
+    match &property["type"] {
+        Value::String(kind) => kind.clone(),
+        Value::Array(kinds) => kinds
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" | "),
+        _ => {
+            if let Some(reference) = property["$ref"].as_str() {
+                reference
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or(reference)
+                    .to_string()
+            } else if property.get("enum").is_some() {
+                "enum".to_string()
+            } else {
+                "any".to_string()
+            }
+        }
+    }
+}
+
+/// Limits runs work within; `None` means unlimited
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BudgetManifest {
+    pub timeout_ms: Option<u64>,
+    /// Runs at once, see [`admission`](crate::admission)
+    pub concurrency_limit: Option<usize>,
+    /// Runs waiting for a slot
+    pub max_queue: Option<usize>,
+    /// Model turns per run, see [`loop_guard`](crate::loop_guard)
+    pub max_iterations: usize,
+    /// Tokens of each tool result passed to the model
+    pub tool_output_tokens: Option<usize>,
+    /// Bytes of each tool result held, see [`memory`](crate::memory)
+    pub memory_item_bytes: Option<usize>,
+    /// Bytes held by all runs sharing the memory budget
+    pub memory_budget_bytes: Option<usize>,
+    /// Quota of users without their own, see [`tenancy`](crate::tenancy)
+    pub quota: Option<Quota>,
+    /// Users with a quota of their own
+    pub users_with_quotas: usize,
+}
+
+impl BudgetManifest {
+    /// Table rows of the budgets that are set
+    fn rows(&self) -> Vec<(&'static str, String)> {
+        let mut rows = Vec::new();
+        if let Some(ms) = self.timeout_ms {
+            rows.push(("Timeout", format!("{} ms", ms)));
+        }
+        if let Some(limit) = self.concurrency_limit {
+            let queue = self.max_queue.unwrap_or(0);
+            rows.push(("Concurrent runs", format!("{} (+{} queued)", limit, queue)));
+        }
+        rows.push(("Model turns per run", self.max_iterations.to_string()));
+        if let Some(tokens) = self.tool_output_tokens {
+            rows.push(("Tool output", format!("{} tokens", tokens)));
+        }
+        if let Some(bytes) = self.memory_item_bytes {
+            rows.push(("Tool result size", format!("{} bytes", bytes)));
+        }
+        if let Some(bytes) = self.memory_budget_bytes {
+            rows.push(("Shared memory budget", format!("{} bytes", bytes)));
+        }
+        if let Some(quota) = self.quota {
+            let mut limits = Vec::new();
+            if let Some(requests) = quota.requests_per_day {
+                limits.push(format!("{} requests/day", requests));
+            }
+            if let Some(tokens) = quota.tokens_per_day {
+                limits.push(format!("{} tokens/day", tokens));
+            }
+            if let Some(cost) = quota.cost_per_month {
+                limits.push(format!("${:.2}/month", cost));
+            }
+            if limits.is_empty() {
+                limits.push("unlimited".to_string());
+            }
+            let mut quota = limits.join(", ");
+            if self.users_with_quotas > 0 {
+                quota.push_str(&format!(
+                    " ({} user{} with their own)",
+                    self.users_with_quotas,
+                    if self.users_with_quotas == 1 { "" } else { "s" }
+                ));
+            }
+            rows.push(("User quota", quota));
+        }
+        rows
+    }
+}
+
+/// Snapshot of an agent's configuration
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentManifest {
+    pub name: String,
+    pub description: Option<String>,
+    pub provider: String,
+    pub model: String,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<usize>,
+    /// The system prompt as sent, with any template and date context
+    /// rendered for today
+    pub system_prompt: Option<String>,
+    pub tools: Vec<ToolManifest>,
+    /// Lifecycle hooks by name, in the order they run
+    pub hooks: Vec<String>,
+    pub budgets: BudgetManifest,
+    /// Tools hidden because the model lacks capabilities they need
+    pub warnings: Vec<CapabilityWarning>,
+}
+
+impl AgentManifest {
+    /// Build a manifest from an agent (tools sorted by name)
+    pub fn from_agent(agent: &Agent) -> Self {
+        let provider_config = agent.provider_config();
+
+        let mut tools: Vec<ToolManifest> = agent
+            .tools
+            .iter()
+            .map(|tool| ToolManifest {
+                name: tool.name().to_string(),
+                description: tool.description().to_string(),
+                parameters: ParameterManifest::from_schema(&tool.parameters()),
+            })
+            .collect();
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            name: agent.config.name.clone(),
+            description: agent.config.description.clone(),
+            provider: format!("{:?}", provider_config.provider),
+            model: provider_config.model.clone(),
+            temperature: provider_config.temperature,
+            max_tokens: provider_config.max_tokens,
+            system_prompt: agent
+                .system_prompt()
+                .unwrap_or_else(|e| Some(format!("(template failed to render: {})", e))),
+            tools,
+            hooks: agent
+                .lifecycle
+                .iter()
+                .map(|hook| hook.name().to_string())
+                .collect(),
+            budgets: agent.budgets(),
+            warnings: agent.capability_warnings(),
+        }
+    }
+
+    /// Render the manifest as a markdown document
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n", self.name);
+
+        if let Some(desc) = &self.description {
+            out.push_str(&format!("{}\n\n", desc));
+        }
+
+        out.push_str("## Configuration\n\n");
+        out.push_str("| Setting | Value |\n|---|---|\n");
+        out.push_str(&format!("| Provider | {} |\n", self.provider));
+        out.push_str(&format!("| Model | `{}` |\n", self.model));
+        if let Some(temp) = self.temperature {
+            out.push_str(&format!("| Temperature | {} |\n", temp));
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            out.push_str(&format!("| Max tokens | {} |\n", max_tokens));
+        }
+        if !self.hooks.is_empty() {
+            let hooks: Vec<String> = self.hooks.iter().map(|h| format!("`{}`", h)).collect();
+            out.push_str(&format!("| Lifecycle hooks | {} |\n", hooks.join(", ")));
+        }
+
+        out.push_str("\n## Budgets\n\n");
+        out.push_str("| Budget | Limit |\n|---|---|\n");
+        for (budget, limit) in self.budgets.rows() {
+            out.push_str(&format!("| {} | {} |\n", budget, limit));
+        }
+
+        if let Some(prompt) = &self.system_prompt {
+            out.push_str("\n## System Prompt\n\n");
+            out.push_str(&format!("```text\n{}\n```\n", prompt));
+        }
+
+        out.push_str("\n## Tools\n\n");
+        if self.tools.is_empty() {
+            out.push_str("_No tools registered._\n");
+        } else {
+            out.push_str("| Name | Description |\n|---|---|\n");
+            for tool in &self.tools {
+                out.push_str(&format!(
+                    "| `{}` | {} |\n",
+                    tool.name,
+                    escape_table_cell(&tool.description)
+                ));
+            }
+
+            for tool in self.tools.iter().filter(|t| !t.parameters.is_empty()) {
+                out.push_str(&format!("\n### `{}`\n\n", tool.name));
+                out.push_str("| Parameter | Type | Required | Description |\n|---|---|---|---|\n");
+                for param in &tool.parameters {
+                    out.push_str(&format!(
+                        "| `{}` | {} | {} | {} |\n",
+                        param.name,
+                        escape_table_cell(&param.kind),
+                        if param.required { "yes" } else { "no" },
+                        escape_table_cell(param.description.as_deref().unwrap_or(""))
+                    ));
+                }
+            }
+        }
+
+        if !self.warnings.is_empty() {
+            out.push_str("\n## Warnings\n\n");
+            for warning in &self.warnings {
+                out.push_str(&format!("- {}\n", warning));
+            }
+        }
+
+        out
+    }
+}
+
+/// Keep free-form text from breaking a markdown table row
+fn escape_table_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::create_agent;
+
+    #[test]
+    fn test_manifest_captures_configuration() {
+        let agent = create_agent("docs")
+            .tool_fn("b_tool", "Second", |_| Ok(String::new()))
+            .tool_fn("a_tool", "First", |_| Ok(String::new()));
+
+        let manifest = agent.manifest();
+        assert_eq!(manifest.name, "docs");
+        assert_eq!(manifest.provider, "Anthropic");
+        assert_eq!(manifest.tools.len(), 2);
+        // Sorted for stable output
+        assert_eq!(manifest.tools[0].name, "a_tool");
+    }
+
+    #[test]
+    fn test_manifest_renders_system_prompt_template() {
+        use crate::prompt::PromptTemplate;
+
+        let template = PromptTemplate::parse("{{agent}} helps {{user}}.").unwrap();
+        let agent = create_agent("planner")
+            .system_prompt_template(template, serde_json::json!({"user": "Ada"}));
+
+        let manifest = agent.manifest();
+        assert_eq!(
+            manifest.system_prompt.as_deref(),
+            Some("planner helps Ada.")
+        );
+        assert!(manifest.to_markdown().contains("planner helps Ada."));
+    }
+
+    #[cfg(feature = "timezones")]
+    #[test]
+    fn test_manifest_includes_date_context() {
+        use crate::agent::{Agent, AgentConfig};
+        use crate::date_context::DateContext;
+
+        let agent = Agent::new(AgentConfig::new("planner").system_prompt("Plan the week."))
+            .with_date_context(DateContext::new().timezone(chrono_tz::Europe::Berlin));
+
+        let prompt = agent.manifest().system_prompt.unwrap();
+        assert!(prompt.starts_with("Plan the week.\n\nCurrent date and time: "));
+    }
+
+    #[test]
+    fn test_manifest_reports_the_attached_provider() {
+        use crate::provider::{OpenAIProvider, Provider, ProviderConfig};
+
+        let config = ProviderConfig::new(Provider::OpenAI)
+            .model("gpt-4o-mini")
+            .api_key("sk-test");
+        let agent =
+            create_agent("docs").with_provider(Box::new(OpenAIProvider::new(config).unwrap()));
+
+        let manifest = agent.manifest();
+        assert_eq!(manifest.provider, "OpenAI");
+        assert_eq!(manifest.model, "gpt-4o-mini");
+    }
+
+    #[test]
+    fn test_manifest_lists_hooks_and_budgets() {
+        use crate::hooks::LengthGuard;
+        use crate::memory::{MemoryBudget, MemoryGuard};
+        use crate::tenancy::{InMemoryQuotaStore, Quota, Tenancy};
+        use crate::tool::output::ToolOutputLimit;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let tenancy = Tenancy::new(Arc::new(InMemoryQuotaStore::new()))
+            .default_quota(Quota::new().requests_per_day(100).cost_per_month(5.0))
+            .quota("vip", Quota::unlimited());
+        let mut agent = create_agent("docs")
+            .with_concurrency_limit(4, 16)
+            .with_tool_output_limit(ToolOutputLimit::new(500))
+            .with_memory_guard(MemoryGuard::new(4096).budget(MemoryBudget::new(1 << 20)))
+            .with_tenancy(tenancy);
+        agent.config.timeout = Some(Duration::from_secs(30));
+        let agent = agent.with_lifecycle(LengthGuard::new().max_prompt_tokens(1000));
+
+        let manifest = agent.manifest();
+        assert_eq!(manifest.hooks, vec!["LengthGuard"]);
+        let budgets = &manifest.budgets;
+        assert_eq!(budgets.timeout_ms, Some(30_000));
+        assert_eq!(
+            (budgets.concurrency_limit, budgets.max_queue),
+            (Some(4), Some(16))
+        );
+        assert_eq!(budgets.max_iterations, 10);
+        assert_eq!(budgets.memory_budget_bytes, Some(1 << 20));
+
+        let markdown = manifest.to_markdown();
+        assert!(markdown.contains("| Lifecycle hooks | `LengthGuard` |"));
+        assert!(markdown.contains("| Concurrent runs | 4 (+16 queued) |"));
+        assert!(markdown.contains("| Tool output | 500 tokens |"));
+        assert!(markdown
+            .contains("| User quota | 100 requests/day, $5.00/month (1 user with their own) |"));
+    }
+
+    #[test]
+    fn test_markdown_contains_tool_table() {
+        let agent =
+            create_agent("docs").tool_fn("greet", "Say hello | wave", |_| Ok(String::new()));
+
+        let markdown = agent.manifest().to_markdown();
+        assert!(markdown.starts_with("# docs\n"));
+        assert!(markdown.contains("| `greet` | Say hello \\| wave |"));
+        assert!(markdown.contains("## System Prompt"));
+    }
+
+    #[test]
+    fn test_markdown_contains_parameter_tables() {
+        use crate::tool::{Tool, ToolResult};
+        use serde_json::{json, Value};
+
+        struct Search;
+        impl Tool for Search {
+            fn name(&self) -> &str {
+                "search"
+            }
+            fn description(&self) -> &str {
+                "Search the docs"
+            }
+            fn parameters(&self) -> Value {
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "query": {"type": "string", "description": "Words to find"},
+                        "limit": {"type": ["integer", "null"]}
+                    },
+                    "required": ["query"]
+                })
+            }
+            fn execute(&self, _args: Value) -> ToolResult {
+                Ok(String::new())
+            }
+        }
+
+        let agent = create_agent("docs")
+            .tool(Search)
+            .tool_fn("ping", "No arguments", |_| Ok(String::new()));
+        let manifest = agent.manifest();
+        let search = &manifest.tools[1];
+        assert_eq!(search.parameters.len(), 2);
+        assert!(manifest.tools[0].parameters.is_empty());
+
+        let markdown = manifest.to_markdown();
+        assert!(markdown.contains(
+            "### `search`\n\n| Parameter | Type | Required | Description |\n|---|---|---|---|\n"
+        ));
+        assert!(markdown.contains("| `query` | string | yes | Words to find |"));
+        assert!(markdown.contains("| `limit` | integer \\| null | no |  |"));
+        // Tools without arguments get no parameter table
+        assert!(!markdown.contains("### `ping`"));
+    }
+
+    #[test]
+    fn test_capability_warnings() {
+        use crate::provider::Capability;
+        use crate::tool::{Tool, ToolResult};
+
+        struct Screenshot;
+        impl Tool for Screenshot {
+            fn name(&self) -> &str {
+                "screenshot"
+            }
+            fn description(&self) -> &str {
+                "Capture the screen"
+            }
+            fn required_capabilities(&self) -> Vec<Capability> {
+                vec![Capability::Vision]
+            }
+            fn execute(&self, _args: serde_json::Value) -> ToolResult {
+                Ok(String::new())
+            }
+        }
+
+        let agent = crate::Agent::new(crate::AgentConfig::new("docs").model("llama3.1:8b"))
+            .tool(Screenshot);
+        let manifest = agent.manifest();
+        assert_eq!(manifest.warnings.len(), 1);
+        assert_eq!(manifest.warnings[0].missing, vec![Capability::Vision]);
+        assert!(manifest.to_markdown().contains(
+            "## Warnings\n\n- Tool 'screenshot' is disabled: model 'llama3.1:8b' does not support vision"
+        ));
+
+        let vision =
+            crate::Agent::new(crate::AgentConfig::new("docs").model("gpt-4o")).tool(Screenshot);
+        assert!(vision.manifest().warnings.is_empty());
+    }
+
+    #[test]
+    fn test_markdown_without_tools() {
+        let markdown = create_agent("empty").manifest().to_markdown();
+        assert!(markdown.contains("_No tools registered._"));
+    }
+}
@@ -0,0 +1,392 @@
+//! MCP server mode
+//!
+//! Exposes an agent over the Model Context Protocol so editors and
+//! assistants (Claude Desktop, Cursor, ...) can call into it. The server
+//! offers:
+//!
+//! - every registered tool, under its own name
+//! - the agent itself as an `ask_<agent>` tool and as a prompt of the same
+//!   name (only when the agent has a provider configured)
+//!
+//! Direct tool calls go through the same guards as calls the model makes:
+//! lifecycle hooks such as `ToolPermissions`, the approval gate for
+//! dangerous tools, the audit log and the execution context.
+//!
+//! Messages are newline-delimited JSON-RPC over stdin/stdout. The client
+//! side lives in [`tool::mcp`](crate::tool::mcp).
+//!
+//! # Example
+//! ```ignore
+//! #[tokio::main]
+//! async fn main() -> patinox::Result<()> {
+//!     let agent = create_agent("notes").tool(sandbox.read_tool());
+//!     patinox::mcp::serve(agent).await
+//! }
+//! ```
+
+use crate::execution::ExecutionContext;
+use crate::provider::ToolCall;
+use crate::tool::mcp::{error_response, response, METHOD_NOT_FOUND, PROTOCOL_VERSION};
+use crate::Agent;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+/// JSON-RPC error code for malformed params
+const INVALID_PARAMS: i64 = -32602;
+
+/// JSON-RPC error code for unparseable messages
+const PARSE_ERROR: i64 = -32700;
+
+/// Serve an agent over stdio until stdin closes
+pub async fn serve(agent: Agent) -> crate::Result<()> {
+    let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+    McpServer::new(agent)
+        .serve_io(stdin, tokio::io::stdout())
+        .await
+}
+
+/// MCP request handler wrapping an agent
+pub struct McpServer {
+    agent: Agent,
+    agent_tool: String,
+}
+
+impl McpServer {
+    pub fn new(agent: Agent) -> Self {
+        let agent_tool = format!(
+            "ask_{}",
+            agent
+                .config
+                .name
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                })
+                .collect::<String>()
+        );
+        Self { agent, agent_tool }
+    }
+
+    /// Name of the tool and prompt that run the agent itself
+    pub fn agent_tool_name(&self) -> &str {
+        &self.agent_tool
+    }
+
+    /// Process newline-delimited messages until the reader is exhausted
+    pub async fn serve_io<R, W>(&self, reader: R, mut writer: W) -> crate::Result<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut lines = reader.lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => self.handle(message).await,
+                Err(e) => Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+            };
+            if let Some(reply) = reply {
+                let mut out = serde_json::to_string(&reply)?;
+                out.push('\n');
+                writer.write_all(out.as_bytes()).await?;
+                writer.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle one JSON-RPC message; notifications produce no reply
+    pub async fn handle(&self, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned()?;
+        let method = message
+            .get("method")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        Some(match method {
+            "initialize" => response(id, self.initialize()),
+            "ping" => response(id, json!({})),
+            "tools/list" => response(id, json!({"tools": self.list_tools()})),
+            "tools/call" => match self.call_tool(&params).await {
+                Ok(result) => response(id, result),
+                Err(message) => error_response(id, INVALID_PARAMS, &message),
+            },
+            "prompts/list" => response(id, json!({"prompts": self.list_prompts()})),
+            "prompts/get" => match self.get_prompt(&params) {
+                Ok(result) => response(id, result),
+                Err(message) => error_response(id, INVALID_PARAMS, &message),
+            },
+            other => error_response(id, METHOD_NOT_FOUND, &format!("Unknown method '{}'", other)),
+        })
+    }
+
+    fn initialize(&self) -> Value {
+        json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {"tools": {}, "prompts": {}},
+            "serverInfo": {
+                "name": self.agent.config.name,
+                "version": env!("CARGO_PKG_VERSION")
+            },
+            "instructions": self.agent.config.description
+        })
+    }
+
+    fn agent_description(&self) -> String {
+        self.agent
+            .config
+            .description
+            .clone()
+            .unwrap_or_else(|| format!("Ask the '{}' agent", self.agent.config.name))
+    }
+
+    fn list_tools(&self) -> Vec<Value> {
+        let mut tools: Vec<Value> = self
+            .agent
+            .tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.name(),
+                    "description": tool.description(),
+                    "inputSchema": tool.parameters()
+                })
+            })
+            .collect();
+
+        if self.agent.has_provider() {
+            tools.push(json!({
+                "name": self.agent_tool,
+                "description": self.agent_description(),
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {"input": {"type": "string"}},
+                    "required": ["input"]
+                }
+            }));
+        }
+        tools
+    }
+
+    async fn call_tool(&self, params: &Value) -> Result<Value, String> {
+        let name = params
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or("Missing tool name")?;
+        let arguments = params
+            .get("arguments")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+
+        let outcome = if name == self.agent_tool && self.agent.has_provider() {
+            let input = arguments
+                .get("input")
+                .and_then(Value::as_str)
+                .ok_or("Missing argument 'input'")?;
+            self.agent.run(input).await.map_err(|e| e.to_string())
+        } else {
+            if !self.agent.tools.contains_key(name) {
+                return Err(format!("Unknown tool '{}'", name));
+            }
+            let call = ToolCall {
+                id: format!("mcp_{}", uuid::Uuid::new_v4()),
+                name: name.to_string(),
+                arguments,
+            };
+            let context = ExecutionContext::new().value("source", "mcp");
+            self.agent
+                .call_tool(call, context)
+                .await
+                .map_err(|e| e.to_string())
+        };
+
+        // Tool failures are results the caller's model should see, not protocol errors
+        Ok(match outcome {
+            Ok(text) => json!({"content": [{"type": "text", "text": text}], "isError": false}),
+            Err(error) => json!({"content": [{"type": "text", "text": error}], "isError": true}),
+        })
+    }
+
+    fn list_prompts(&self) -> Vec<Value> {
+        if !self.agent.has_provider() {
+            return Vec::new();
+        }
+        vec![json!({
+            "name": self.agent_tool,
+            "description": self.agent_description(),
+            "arguments": [{"name": "input", "description": "The request", "required": true}]
+        })]
+    }
+
+    fn get_prompt(&self, params: &Value) -> Result<Value, String> {
+        let name = params.get("name").and_then(Value::as_str);
+        if name != Some(self.agent_tool.as_str()) || !self.agent.has_provider() {
+            return Err(format!("Unknown prompt '{}'", name.unwrap_or_default()));
+        }
+        let input = params
+            .get("arguments")
+            .and_then(|args| args.get("input"))
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+
+        let text = match &self.agent.config.system_prompt {
+            Some(system) => format!("{}\n\n{}", system, input),
+            None => input.to_string(),
+        };
+        Ok(json!({
+            "description": self.agent_description(),
+            "messages": [{"role": "user", "content": {"type": "text", "text": text}}]
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_agent;
+    use crate::provider::MockProvider;
+
+    fn server() -> McpServer {
+        let agent = create_agent("notes bot")
+            .tool_fn("upper", "Uppercase", |s| Ok(s.to_uppercase()))
+            .tool_fn("fail", "Always fails", |_| Err("nope".into()))
+            .with_provider(Box::new(MockProvider::new("agent says hi")));
+        McpServer::new(agent)
+    }
+
+    fn request(method: &str, params: Value) -> Value {
+        json!({"jsonrpc": "2.0", "id": 7, "method": method, "params": params})
+    }
+
+    #[tokio::test]
+    async fn test_initialize_and_list() {
+        let server = server();
+        let init = server
+            .handle(request("initialize", json!({})))
+            .await
+            .unwrap();
+        assert_eq!(init["result"]["serverInfo"]["name"], "notes bot");
+
+        let tools = server
+            .handle(request("tools/list", json!({})))
+            .await
+            .unwrap();
+        let names: Vec<_> = tools["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, vec!["fail", "upper", "ask_notes_bot"]);
+
+        let note = json!({"jsonrpc": "2.0", "method": "notifications/initialized"});
+        assert!(server.handle(note).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_call_tools_and_agent() {
+        let server = server();
+        let call = |name: &str, args: Value| {
+            request("tools/call", json!({"name": name, "arguments": args}))
+        };
+
+        let reply = server
+            .handle(call("upper", json!({"input": "hi"})))
+            .await
+            .unwrap();
+        assert_eq!(reply["result"]["content"][0]["text"], "HI");
+
+        let reply = server.handle(call("fail", json!({}))).await.unwrap();
+        assert_eq!(reply["result"]["isError"], true);
+
+        let reply = server
+            .handle(call("ask_notes_bot", json!({"input": "hello"})))
+            .await
+            .unwrap();
+        assert_eq!(reply["result"]["content"][0]["text"], "agent says hi");
+
+        let reply = server.handle(call("missing", json!({}))).await.unwrap();
+        assert_eq!(reply["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_direct_calls_are_guarded() {
+        use crate::hooks::ToolPermissions;
+        use crate::tool::{Tool, ToolResult};
+
+        struct Wipe;
+        impl Tool for Wipe {
+            fn name(&self) -> &str {
+                "wipe"
+            }
+            fn description(&self) -> &str {
+                "Delete everything"
+            }
+            fn dangerous(&self) -> bool {
+                true
+            }
+            fn execute(&self, _args: Value) -> ToolResult {
+                Ok("wiped".to_string())
+            }
+        }
+
+        let agent = create_agent("ops")
+            .tool(Wipe)
+            .tool_fn("upper", "Uppercase", |s| Ok(s.to_uppercase()))
+            .with_lifecycle(ToolPermissions::new().deny("upper"));
+        let server = McpServer::new(agent);
+        let call = |name: &str| request("tools/call", json!({"name": name, "arguments": {}}));
+
+        let reply = server.handle(call("upper")).await.unwrap();
+        assert_eq!(reply["result"]["isError"], true);
+        assert_eq!(
+            reply["result"]["content"][0]["text"],
+            "Tool 'upper' is denied by policy"
+        );
+
+        // No approval gate, so the dangerous tool never runs
+        let reply = server.handle(call("wipe")).await.unwrap();
+        assert_eq!(reply["result"]["isError"], true);
+        let text = reply["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.starts_with("Approval required"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_io_round_trip() {
+        let input = b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}\nnot json\n".to_vec();
+        let mut output = Vec::new();
+        server()
+            .serve_io(tokio::io::BufReader::new(&input[..]), &mut output)
+            .await
+            .unwrap();
+
+        let lines: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines[0]["result"], json!({}));
+        assert_eq!(lines[1]["error"]["code"], PARSE_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_prompts() {
+        let server = server();
+        let reply = server
+            .handle(request(
+                "prompts/get",
+                json!({"name": "ask_notes_bot", "arguments": {"input": "summarize"}}),
+            ))
+            .await
+            .unwrap();
+        let text = reply["result"]["messages"][0]["content"]["text"]
+            .as_str()
+            .unwrap();
+        assert!(text.ends_with("summarize"));
+    }
+}
@@ -0,0 +1,435 @@
+//! Memory guard for large request data
+//!
+//! A single huge tool result (a whole log file, a large API response) can
+//! exhaust memory once it is copied into the conversation and re-sent on
+//! every model call. The [`MemoryGuard`] caps the size of each item and,
+//! optionally, the total bytes held by all in-flight runs through a shared
+//! [`MemoryBudget`]. Anything over the limit is truncated or spilled to a
+//! temp file, with the file path and a short preview passed along instead
+//! of the bytes.
+//!
+//! Spill files are readable only by their owner and live in a directory of
+//! their own per process. Each is deleted when the [`Admitted`] content it
+//! backs is dropped - for agents, when the run ends. Directories left by
+//! processes that died are swept once they are older than a day.
+//!
+//! Agents apply the guard to tool results. Attachments and retrieved
+//! context should go through [`MemoryGuard::admit`] as they are added.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::memory::{MemoryBudget, MemoryGuard, OversizePolicy};
+//!
+//! let budget = MemoryBudget::new(256 * 1024 * 1024);
+//! let guard = MemoryGuard::new(512 * 1024)
+//!     .policy(OversizePolicy::Spill)
+//!     .budget(budget.clone());
+//!
+//! let agent = create_agent("log-reader").with_memory_guard(guard);
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+/// Bytes of spilled content kept inline as a preview
+const SPILL_PREVIEW_BYTES: usize = 1024;
+
+/// Prefix of per-process spill directories
+const SPILL_DIR_PREFIX: &str = "patinox-spill-";
+
+/// Age after which another process's spill directory is swept
+const SPILL_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What to do with content over the limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversizePolicy {
+    /// Keep the first bytes that fit and drop the rest
+    Truncate,
+    /// Write the full content to a temp file and pass its path
+    Spill,
+}
+
+/// Process-wide cap on bytes held by in-flight requests
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit: usize,
+    in_use: AtomicUsize,
+}
+
+impl MemoryBudget {
+    /// Create a shared budget of `limit` bytes
+    pub fn new(limit: usize) -> Arc<Self> {
+        Arc::new(Self {
+            limit,
+            in_use: AtomicUsize::new(0),
+        })
+    }
+
+    /// Bytes the budget covers
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Bytes currently reserved
+    pub fn in_use(&self) -> usize {
+        self.in_use.load(Ordering::Acquire)
+    }
+
+    /// Bytes still available
+    pub fn available(&self) -> usize {
+        self.limit.saturating_sub(self.in_use())
+    }
+
+    /// Reserve bytes, or `None` if the budget can't cover them
+    // `fetch_update` is `try_update` on newer toolchains, past our MSRV
+    #[allow(deprecated)]
+    pub fn try_reserve(self: &Arc<Self>, bytes: usize) -> Option<Reservation> {
+        self.in_use
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                used.checked_add(bytes).filter(|total| *total <= self.limit)
+            })
+            .ok()
+            .map(|_| Reservation {
+                budget: Arc::clone(self),
+                bytes,
+            })
+    }
+}
+
+/// Bytes held against a budget, released on drop
+#[derive(Debug)]
+pub struct Reservation {
+    budget: Arc<MemoryBudget>,
+    bytes: usize,
+}
+
+impl Reservation {
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.budget.in_use.fetch_sub(self.bytes, Ordering::AcqRel);
+    }
+}
+
+/// A spilled file, deleted on drop
+#[derive(Debug)]
+pub struct SpillFile {
+    path: PathBuf,
+}
+
+impl SpillFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Content that passed the guard, with any budget and spill file it holds
+#[derive(Debug)]
+pub struct Admitted {
+    pub content: String,
+    /// Keep alive while the content is in use
+    pub reservation: Option<Reservation>,
+    /// Keep alive while the spilled path may be read
+    pub spill: Option<SpillFile>,
+}
+
+/// Per-item size limit with an optional shared budget
+#[derive(Debug, Clone)]
+pub struct MemoryGuard {
+    max_item_bytes: usize,
+    policy: OversizePolicy,
+    budget: Option<Arc<MemoryBudget>>,
+    spill_dir: PathBuf,
+}
+
+impl MemoryGuard {
+    /// Limit each item to `max_item_bytes`, truncating by default
+    pub fn new(max_item_bytes: usize) -> Self {
+        Self {
+            max_item_bytes,
+            policy: OversizePolicy::Truncate,
+            budget: None,
+            spill_dir: std::env::temp_dir(),
+        }
+    }
+
+    /// Set the oversize policy
+    pub fn policy(mut self, policy: OversizePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Share a budget across every run using this guard
+    pub fn budget(mut self, budget: Arc<MemoryBudget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Directory for spilled content (defaults to the system temp dir)
+    pub fn spill_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spill_dir = dir.into();
+        self
+    }
+
+    /// Largest item admitted as is
+    pub fn max_item_bytes(&self) -> usize {
+        self.max_item_bytes
+    }
+
+    /// The shared budget, if any
+    pub fn shared_budget(&self) -> Option<&MemoryBudget> {
+        self.budget.as_deref()
+    }
+
+    /// Admit content, applying the policy if it is too large or the budget is short
+    pub fn admit(&self, label: &str, content: String) -> crate::Result<Admitted> {
+        if content.len() <= self.max_item_bytes {
+            match &self.budget {
+                None => {
+                    return Ok(Admitted {
+                        content,
+                        reservation: None,
+                        spill: None,
+                    })
+                }
+                Some(budget) => {
+                    if let Some(reservation) = budget.try_reserve(content.len()) {
+                        return Ok(Admitted {
+                            content,
+                            reservation: Some(reservation),
+                            spill: None,
+                        });
+                    }
+                }
+            }
+        }
+
+        let limit = match &self.budget {
+            Some(budget) => self.max_item_bytes.min(budget.available()),
+            None => self.max_item_bytes,
+        };
+        let total = content.len();
+        let (reduced, spill) = match self.policy {
+            OversizePolicy::Truncate => {
+                let mut kept = truncate_to(content, limit);
+                kept.push_str(&format!(
+                    "\n[{} output truncated from {} bytes]",
+                    label, total
+                ));
+                (kept, None)
+            }
+            OversizePolicy::Spill => {
+                let (reduced, file) = self.spill(label, content, limit)?;
+                (reduced, Some(file))
+            }
+        };
+
+        // The reduced form is small; hold what the budget allows
+        let reservation = self
+            .budget
+            .as_ref()
+            .and_then(|budget| budget.try_reserve(reduced.len()));
+        Ok(Admitted {
+            content: reduced,
+            reservation,
+            spill,
+        })
+    }
+
+    fn spill(
+        &self,
+        label: &str,
+        content: String,
+        limit: usize,
+    ) -> crate::Result<(String, SpillFile)> {
+        let path =
+            process_spill_dir(&self.spill_dir)?.join(format!("{}.txt", uuid::Uuid::new_v4()));
+        let file = SpillFile { path };
+        write_private(&file.path, content.as_bytes())?;
+
+        let total = content.len();
+        let preview = truncate_to(content, limit.min(SPILL_PREVIEW_BYTES));
+        let reduced = format!(
+            "[{} output of {} bytes saved to {}; preview follows]\n{}",
+            label,
+            total,
+            file.path.display(),
+            preview
+        );
+        Ok((reduced, file))
+    }
+}
+
+/// This process's spill directory under `parent`, created owner-only on
+/// first use after sweeping stale ones
+fn process_spill_dir(parent: &Path) -> std::io::Result<PathBuf> {
+    static PROCESS: OnceLock<String> = OnceLock::new();
+    let process = PROCESS.get_or_init(|| uuid::Uuid::new_v4().simple().to_string());
+    let dir = parent.join(format!("{}{}", SPILL_DIR_PREFIX, process));
+    if dir.is_dir() {
+        return Ok(dir);
+    }
+
+    sweep_stale_spill_dirs(parent);
+    std::fs::create_dir_all(parent)?;
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    match builder.create(&dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => Err(e),
+        _ => Ok(dir),
+    }
+}
+
+/// Remove spill directories not modified within [`SPILL_TTL`]
+fn sweep_stale_spill_dirs(parent: &Path) {
+    let Ok(entries) = std::fs::read_dir(parent) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if !entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(SPILL_DIR_PREFIX)
+        {
+            continue;
+        }
+        let stale = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > SPILL_TTL);
+        if stale && entry.path().is_dir() {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+/// Write a new file readable only by its owner
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(bytes)
+}
+
+/// Cut a string to at most `max` bytes on a character boundary
+fn truncate_to(mut text: String, max: usize) -> String {
+    if text.len() > max {
+        let mut end = max;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_content_passes_through() {
+        let guard = MemoryGuard::new(100);
+        let admitted = guard.admit("tool", "hello".to_string()).unwrap();
+        assert_eq!(admitted.content, "hello");
+    }
+
+    #[test]
+    fn test_truncate_respects_char_boundaries() {
+        let guard = MemoryGuard::new(4);
+        let admitted = guard.admit("read_file", "héllo".to_string()).unwrap();
+        assert!(admitted.content.starts_with("hél\n"));
+        assert!(admitted.content.contains("truncated from 6 bytes"));
+    }
+
+    #[test]
+    fn test_spill_writes_full_content() {
+        let guard = MemoryGuard::new(10).policy(OversizePolicy::Spill);
+        let content = "x".repeat(50);
+        let admitted = guard.admit("dump", content.clone()).unwrap();
+
+        let path = admitted
+            .content
+            .split("saved to ")
+            .nth(1)
+            .and_then(|rest| rest.split(';').next())
+            .map(PathBuf::from)
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // The file goes with the content it backs
+        drop(admitted);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stale_spill_dirs_are_swept() {
+        let parent = std::env::temp_dir().join(format!("patinox-spills-{}", uuid::Uuid::new_v4()));
+        let stale = parent.join(format!("{}dead", SPILL_DIR_PREFIX));
+        std::fs::create_dir_all(&stale).unwrap();
+        std::fs::write(stale.join("old.txt"), "left by a crash").unwrap();
+        let two_days_ago = std::time::SystemTime::now() - 2 * SPILL_TTL;
+        std::fs::File::open(&stale)
+            .unwrap()
+            .set_modified(two_days_ago)
+            .unwrap();
+
+        let guard = MemoryGuard::new(1)
+            .policy(OversizePolicy::Spill)
+            .spill_dir(&parent);
+        let admitted = guard.admit("dump", "spilled".to_string()).unwrap();
+        assert!(!stale.exists());
+        assert!(admitted.spill.as_ref().unwrap().path().exists());
+
+        drop(admitted);
+        std::fs::remove_dir_all(&parent).unwrap();
+    }
+
+    #[test]
+    fn test_budget_reserves_and_releases() {
+        let budget = MemoryBudget::new(10);
+        let guard = MemoryGuard::new(100).budget(budget.clone());
+
+        let first = guard.admit("a", "12345678".to_string()).unwrap();
+        assert_eq!(budget.in_use(), 8);
+
+        // Only 2 bytes left: the next item is reduced to fit
+        let second = guard.admit("b", "abcdef".to_string()).unwrap();
+        assert!(second.content.starts_with("ab\n"));
+
+        drop(first);
+        drop(second);
+        assert_eq!(budget.in_use(), 0);
+    }
+}
@@ -21,10 +21,16 @@
 //! **Solution**: Extension methods like `.tool_fn_with()` that capture context automatically
 //!
 //! See: [`tool_context`] module for implementation and usage examples
+//!
+//! ### Slack Bot
+//! **Feature**: `slack`
+//! **Solution**: `slack::SlackBot` answers mentions and DMs over Socket Mode
 
 use crate::agent::Agent;
 
 // Plugin modules
+#[cfg(feature = "slack")]
+pub mod slack;
 pub mod tool_context; // V2-PLUGIN-001-B (Tool Context Helper)
                       // pub mod cli;           // V2-PLUGIN-002 (Future)
                       // pub mod discovery;     // V2-PLUGIN-003 (Future)
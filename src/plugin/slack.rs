@@ -0,0 +1,543 @@
+//! Slack bot for agents
+//!
+//! Enabled with the `slack` feature. A [`SlackBot`] connects an agent to a
+//! Slack workspace over Socket Mode, so no public endpoint is needed. It
+//! answers when mentioned in a channel and in direct messages, replying in
+//! a thread:
+//!
+//! - a "thinking" placeholder is posted right away and replaced by the
+//!   answer (Slack has no typing indicator for bots)
+//! - each tool call is posted to the thread as it starts and updated with
+//!   its outcome when it finishes
+//! - the Slack user id becomes the run's `user_id`, so
+//!   [tenancy](crate::tenancy) quotas apply per Slack user, and the thread
+//!   becomes its `session_id`
+//!
+//! Each thread is one [session](crate::session), kept in the agent's
+//! session store so threads survive restarts. Its id is `slack:` followed
+//! by a hash of the bot token, channel and thread, so it can't be guessed
+//! from the thread, and it is owned by the Slack user who started the
+//! thread, so other front ends sharing the store can't resume it. Rotating
+//! the bot token starts every thread afresh.
+//! Agents without a store keep threads in memory until they are idle for
+//! a day. Messages in one thread are answered one at a time, each seeing
+//! the turns before it. The app needs an app-level token (`xapp-`, with
+//! `connections:write`) and a bot token (`xoxb-`, with `chat:write`,
+//! `app_mentions:read` and `im:history`), plus event subscriptions for
+//! `app_mention` and `message.im`.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::plugin::slack::SlackBot;
+//!
+//! let bot = SlackBot::new(
+//!     Arc::new(agent),
+//!     std::env::var("SLACK_APP_TOKEN")?,
+//!     std::env::var("SLACK_BOT_TOKEN")?,
+//! );
+//! shutdown.track("slack", tokio::spawn(async move {
+//!     if let Err(e) = bot.run(token).await {
+//!         tracing::error!("slack: {}", e);
+//!     }
+//! }));
+//! ```
+
+use crate::events::AgentEvent;
+use crate::execution::ExecutionContext;
+use crate::session::{ExpiringSessionStore, Session, SessionStore};
+use crate::Agent;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_util::sync::CancellationToken;
+
+/// Slack's Web API
+pub const SLACK_API_URL: &str = "https://slack.com/api";
+
+/// Text of the placeholder shown while the agent works
+const THINKING: &str = "_Thinking…_";
+
+/// Longest wait between reconnection attempts
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Threads of agents without a session store are forgotten after this long
+const THREAD_IDLE_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A message the bot should answer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlackMessage {
+    pub user: String,
+    pub channel: String,
+    /// Timestamp of the thread the reply goes to
+    pub thread_ts: String,
+    /// The message text, without leading mentions of the bot
+    pub text: String,
+}
+
+impl SlackMessage {
+    /// The message in an Events API `event`, if it is one the bot answers
+    ///
+    /// Mentions and direct messages qualify; edits, bot messages and other
+    /// subtypes don't.
+    pub fn from_event(event: &Value) -> Option<Self> {
+        let answerable = match event["type"].as_str()? {
+            "app_mention" => true,
+            "message" => event["channel_type"] == "im",
+            _ => false,
+        };
+        if !answerable || event.get("subtype").is_some() || event.get("bot_id").is_some() {
+            return None;
+        }
+        let mut text = event["text"].as_str()?.trim();
+        while let Some(rest) = text.strip_prefix("<@") {
+            text = rest
+                .split_once('>')
+                .map_or("", |(_, rest)| rest)
+                .trim_start();
+        }
+        Some(Self {
+            user: event["user"].as_str()?.to_string(),
+            channel: event["channel"].as_str()?.to_string(),
+            thread_ts: event["thread_ts"]
+                .as_str()
+                .or_else(|| event["ts"].as_str())?
+                .to_string(),
+            text: text.to_string(),
+        })
+    }
+}
+
+/// Minimal Slack Web API client
+struct SlackApi {
+    http: reqwest::Client,
+    url: String,
+}
+
+impl SlackApi {
+    async fn call(&self, method: &str, token: &str, body: Value) -> crate::Result<Value> {
+        let response: Value = self
+            .http
+            .post(format!("{}/{}", self.url, method))
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await?
+            .json()
+            .await?;
+        if response["ok"] != true {
+            let error = response["error"].as_str().unwrap_or("unknown error");
+            return Err(format!("Slack {} failed: {}", method, error).into());
+        }
+        Ok(response)
+    }
+}
+
+/// Runs an agent as a Slack bot
+pub struct SlackBot {
+    agent: Arc<Agent>,
+    app_token: String,
+    bot_token: String,
+    api: SlackApi,
+    show_tool_calls: bool,
+    /// Thread sessions, keyed by [`thread_id`](Self::thread_id)
+    sessions: Arc<dyn SessionStore>,
+    /// Held while a thread's message is answered
+    thread_locks: Mutex<HashMap<String, Weak<tokio::sync::Mutex<()>>>>,
+}
+
+impl SlackBot {
+    /// A bot for `agent`, using an app-level token (`xapp-`) for the
+    /// connection and a bot token (`xoxb-`) for posting
+    pub fn new(
+        agent: Arc<Agent>,
+        app_token: impl Into<String>,
+        bot_token: impl Into<String>,
+    ) -> Self {
+        let sessions = match &agent.sessions {
+            Some(store) => store.clone(),
+            None => Arc::new(ExpiringSessionStore::new(THREAD_IDLE_TIMEOUT)),
+        };
+        Self {
+            agent,
+            app_token: app_token.into(),
+            bot_token: bot_token.into(),
+            api: SlackApi {
+                http: reqwest::Client::new(),
+                url: SLACK_API_URL.to_string(),
+            },
+            show_tool_calls: true,
+            sessions,
+            thread_locks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Use a different Web API base URL (for proxies and tests)
+    pub fn api_url(mut self, url: impl Into<String>) -> Self {
+        self.api.url = url.into();
+        self
+    }
+
+    /// Post tool calls to the thread (default `true`)
+    pub fn show_tool_calls(mut self, show: bool) -> Self {
+        self.show_tool_calls = show;
+        self
+    }
+
+    /// Answer messages until `cancel` is triggered, reconnecting when
+    /// Slack drops the connection
+    ///
+    /// Fails only if Slack refuses the app token.
+    pub async fn run(self, cancel: CancellationToken) -> crate::Result<()> {
+        let bot = Arc::new(self);
+        let mut delay = Duration::from_secs(1);
+        while !cancel.is_cancelled() {
+            let url = match bot
+                .api
+                .call("apps.connections.open", &bot.app_token, json!({}))
+                .await
+            {
+                Ok(response) => response["url"].as_str().unwrap_or_default().to_string(),
+                Err(e) if e.to_string().contains("invalid_auth") => return Err(e),
+                Err(e) => {
+                    tracing::warn!("slack: can't open a connection: {}", e);
+                    String::new()
+                }
+            };
+            if !url.is_empty() {
+                match bot.clone().listen(&url, &cancel).await {
+                    Ok(()) => delay = Duration::from_secs(1),
+                    Err(e) => tracing::warn!("slack: connection lost: {}", e),
+                }
+            }
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = tokio::time::sleep(delay) => {}
+            }
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+        Ok(())
+    }
+
+    /// Read envelopes from one connection until Slack closes it
+    async fn listen(self: Arc<Self>, url: &str, cancel: &CancellationToken) -> crate::Result<()> {
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await?;
+        tracing::info!("slack: connected");
+        loop {
+            let frame = tokio::select! {
+                _ = cancel.cancelled() => return Ok(()),
+                frame = socket.next() => frame,
+            };
+            let text = match frame {
+                Some(Ok(WsMessage::Text(text))) => text,
+                Some(Ok(WsMessage::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e.into()),
+            };
+            let envelope: Value = serde_json::from_str(text.as_str())?;
+            // Unacknowledged envelopes are retried by Slack
+            if let Some(id) = envelope["envelope_id"].as_str() {
+                let ack = json!({"envelope_id": id}).to_string();
+                socket.send(WsMessage::Text(ack.into())).await?;
+            }
+            match envelope["type"].as_str() {
+                Some("disconnect") => return Ok(()),
+                Some("events_api") => {
+                    if let Some(message) = SlackMessage::from_event(&envelope["payload"]["event"]) {
+                        let bot = self.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = bot.answer(message).await {
+                                tracing::warn!("slack: can't answer: {}", e);
+                            }
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    async fn post(&self, channel: &str, thread_ts: &str, text: &str) -> crate::Result<String> {
+        let body = json!({"channel": channel, "thread_ts": thread_ts, "text": text});
+        let response = self
+            .api
+            .call("chat.postMessage", &self.bot_token, body)
+            .await?;
+        Ok(response["ts"].as_str().unwrap_or_default().to_string())
+    }
+
+    async fn update(&self, channel: &str, ts: &str, text: &str) -> crate::Result<()> {
+        let body = json!({"channel": channel, "ts": ts, "text": text});
+        self.api.call("chat.update", &self.bot_token, body).await?;
+        Ok(())
+    }
+
+    /// Session id of the thread `thread_ts` in `channel`
+    fn thread_id(&self, channel: &str, thread_ts: &str) -> String {
+        let mut hasher = Sha256::new();
+        for part in [&self.bot_token, channel, thread_ts] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        let hash: String = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!("slack:{}", hash)
+    }
+
+    /// The lock serializing answers in `thread`
+    fn thread_lock(&self, thread: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut locks = self.thread_locks.lock().unwrap();
+        locks.retain(|_, lock| lock.strong_count() > 0);
+        if let Some(lock) = locks.get(thread).and_then(Weak::upgrade) {
+            return lock;
+        }
+        let lock = Arc::new(tokio::sync::Mutex::new(()));
+        locks.insert(thread.to_string(), Arc::downgrade(&lock));
+        lock
+    }
+
+    /// Run the agent on `message` and reply in its thread
+    pub async fn answer(&self, message: SlackMessage) -> crate::Result<()> {
+        let SlackMessage {
+            user,
+            channel,
+            thread_ts,
+            text,
+        } = message;
+        let placeholder = self.post(&channel, &thread_ts, THINKING).await?;
+
+        let thread = self.thread_id(&channel, &thread_ts);
+        let lock = self.thread_lock(&thread);
+        let _answering = lock.lock().await;
+        let mut session = match self.sessions.load(&thread).await? {
+            Some(session) => session,
+            None => {
+                let mut session =
+                    Session::new(&self.agent.config.name).owned_by(Some(user.clone()));
+                session.id = thread.clone();
+                session
+            }
+        };
+        let context = ExecutionContext::new()
+            .user(&user)
+            .session(&thread)
+            .value("slack_channel", &channel);
+        let mut events = Box::pin(self.agent.execute_streaming_in(
+            context,
+            session.messages.clone(),
+            text.clone(),
+        ));
+
+        // Messages of tool calls in progress, oldest first
+        let mut tool_messages: Vec<(String, String)> = Vec::new();
+        let mut reply = Err("Run ended without a result".to_string());
+        while let Some(event) = events.next().await {
+            session.record_event(&event);
+            match event {
+                AgentEvent::ToolCallStarted { name, arguments } if self.show_tool_calls => {
+                    let text = format!(":hammer_and_wrench: `{}` {}", name, arguments);
+                    match self.post(&channel, &thread_ts, &text).await {
+                        Ok(ts) => tool_messages.push((name, ts)),
+                        Err(e) => tracing::debug!("slack: can't post tool call: {}", e),
+                    }
+                }
+                AgentEvent::ToolCallFinished {
+                    name,
+                    error,
+                    duration_ms,
+                    ..
+                } => {
+                    let Some(i) = tool_messages.iter().position(|(n, _)| *n == name) else {
+                        continue;
+                    };
+                    let (_, ts) = tool_messages.remove(i);
+                    let text = match error {
+                        None => format!(":white_check_mark: `{}` ({} ms)", name, duration_ms),
+                        Some(error) => format!(":x: `{}` failed: {}", name, error),
+                    };
+                    if let Err(e) = self.update(&channel, &ts, &text).await {
+                        tracing::debug!("slack: can't update tool call: {}", e);
+                    }
+                }
+                AgentEvent::Completed { output } => reply = Ok(output),
+                AgentEvent::Failed { error } => reply = Err(error),
+                _ => {}
+            }
+        }
+
+        match reply {
+            Ok(output) => {
+                session.push_turn(text, output.clone());
+                if let Err(e) = self.sessions.save(&session).await {
+                    tracing::warn!("slack: can't save thread {}: {}", thread, e);
+                }
+                self.update(&channel, &placeholder, &output).await?;
+            }
+            Err(error) => {
+                self.update(&channel, &placeholder, &format!(":warning: {}", error))
+                    .await?
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_agent;
+    use crate::provider::MockProvider;
+    use crate::tenancy::{InMemoryQuotaStore, Quota, Tenancy};
+    use mockito::Matcher;
+
+    #[test]
+    fn test_message_from_event() {
+        let mention = json!({
+            "type": "app_mention",
+            "user": "U1",
+            "channel": "C1",
+            "ts": "1700000000.000100",
+            "text": "<@UBOT> what's the status?"
+        });
+        assert_eq!(
+            SlackMessage::from_event(&mention),
+            Some(SlackMessage {
+                user: "U1".to_string(),
+                channel: "C1".to_string(),
+                thread_ts: "1700000000.000100".to_string(),
+                text: "what's the status?".to_string(),
+            })
+        );
+
+        let in_thread = json!({
+            "type": "message", "channel_type": "im", "user": "U1", "channel": "D1",
+            "ts": "2.0", "thread_ts": "1.0", "text": "and now?"
+        });
+        assert_eq!(
+            SlackMessage::from_event(&in_thread).unwrap().thread_ts,
+            "1.0"
+        );
+
+        // Channel chatter, edits and the bot's own messages are ignored
+        for ignored in [
+            json!({"type": "message", "channel_type": "channel", "user": "U1", "channel": "C1", "ts": "1", "text": "hi"}),
+            json!({"type": "message", "channel_type": "im", "subtype": "message_changed", "channel": "D1", "ts": "1"}),
+            json!({"type": "message", "channel_type": "im", "bot_id": "B1", "user": "UBOT", "channel": "D1", "ts": "1", "text": "hi"}),
+        ] {
+            assert_eq!(SlackMessage::from_event(&ignored), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_answer_replaces_placeholder() {
+        let mut server = mockito::Server::new_async().await;
+        let placeholder = server
+            .mock("POST", "/chat.postMessage")
+            .match_header("authorization", "Bearer xoxb-test")
+            .match_body(Matcher::PartialJson(
+                json!({"channel": "C1", "thread_ts": "1.0", "text": THINKING}),
+            ))
+            .with_body(r#"{"ok": true, "ts": "1.1"}"#)
+            .create_async()
+            .await;
+        let answer = server
+            .mock("POST", "/chat.update")
+            .match_body(Matcher::PartialJson(
+                json!({"channel": "C1", "ts": "1.1", "text": "All green."}),
+            ))
+            .with_body(r#"{"ok": true}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let refusal = server
+            .mock("POST", "/chat.update")
+            .match_body(Matcher::Regex("over quota".to_string()))
+            .with_body(r#"{"ok": true}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let tenancy = Tenancy::new(Arc::new(InMemoryQuotaStore::new()))
+            .default_quota(Quota::new().requests_per_day(1));
+        let agent = create_agent("status")
+            .with_provider(Box::new(MockProvider::new("All green.")))
+            .with_tenancy(tenancy);
+        let bot = SlackBot::new(Arc::new(agent), "xapp-test", "xoxb-test").api_url(server.url());
+        let message = SlackMessage {
+            user: "U1".to_string(),
+            channel: "C1".to_string(),
+            thread_ts: "1.0".to_string(),
+            text: "status?".to_string(),
+        };
+        bot.answer(message.clone()).await.unwrap();
+        let id = bot.thread_id("C1", "1.0");
+        assert!(id.starts_with("slack:"));
+        assert!(!id.contains("1.0"));
+        let thread = bot.sessions.load(&id).await.unwrap().unwrap();
+        assert_eq!(thread.messages.len(), 2);
+        assert_eq!(thread.user_id.as_deref(), Some("U1"));
+
+        // Quotas are per Slack user
+        bot.answer(message).await.unwrap();
+        placeholder.expect(2).assert_async().await;
+        answer.assert_async().await;
+        refusal.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_thread_messages_are_answered_in_turn() {
+        use crate::session::InMemorySessionStore;
+
+        let mut server = mockito::Server::new_async().await;
+        let _post = server
+            .mock("POST", "/chat.postMessage")
+            .with_body(r#"{"ok": true, "ts": "1.1"}"#)
+            .create_async()
+            .await;
+        let _update = server
+            .mock("POST", "/chat.update")
+            .with_body(r#"{"ok": true}"#)
+            .create_async()
+            .await;
+
+        let store = Arc::new(InMemorySessionStore::new());
+        let agent = create_agent("status")
+            .with_provider(Box::new(MockProvider::new("All green.")))
+            .with_session_store(store.clone());
+        let bot = SlackBot::new(Arc::new(agent), "xapp-test", "xoxb-test").api_url(server.url());
+        let message = |text: &str| SlackMessage {
+            user: "U1".to_string(),
+            channel: "C1".to_string(),
+            thread_ts: "1.0".to_string(),
+            text: text.to_string(),
+        };
+
+        let (first, second) = tokio::join!(
+            bot.answer(message("status?")),
+            bot.answer(message("and now?"))
+        );
+        first.unwrap();
+        second.unwrap();
+
+        // Neither answer overwrote the other, and the thread is in the agent's store
+        let thread = store
+            .load(&bot.thread_id("C1", "1.0"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(thread.messages.len(), 4);
+        assert_eq!(thread.usage.runs, 2);
+        assert!(bot
+            .thread_locks
+            .lock()
+            .unwrap()
+            .values()
+            .all(|l| l.strong_count() == 0));
+    }
+}
@@ -0,0 +1,841 @@
+//! Prompt templates
+//!
+//! A small Handlebars-style template language for system prompts and other
+//! generated text:
+//!
+//! - `{{name}}`, `{{user.name}}` - interpolate a value (no HTML escaping)
+//! - `{{#if flag}}...{{else}}...{{/if}}` - conditional on a truthy value
+//! - `{{#each items}}...{{/each}}` - repeat for each array item; inside,
+//!   `{{this}}` is the item, `{{@index}}` its position, and names resolve
+//!   against the item before the outer context
+//! - `{{> partial}}` - include a template registered in a [`PromptLibrary`]
+//! - `{{! comment}}` - ignored
+//!
+//! Templates are parsed once, and rendering a variable that is missing from
+//! the context is an error rather than silently empty text.
+//!
+//! Templates from config files or end users should be loaded with
+//! [`PromptTemplate::parse_sandboxed`]. A [`Sandbox`] rejects oversized,
+//! deeply nested or partial-including templates and unknown variables at
+//! load time, and at render time bounds `#each` iterations and output size
+//! and escapes interpolated values so they cannot smuggle in template
+//! syntax or control characters. The language has no code execution in
+//! either mode.
+//!
+//! For prompts used in many places, implement [`TypedPrompt`] on a
+//! `Serialize` struct so the variables travel as named fields.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::prompt::PromptTemplate;
+//!
+//! let template = PromptTemplate::parse(
+//!     "You are {{agent}}. Today is {{date}}.\n\
+//!      {{#if tools}}You can use:\n{{#each tools}}- {{name}}: {{description}}\n{{/each}}{{/if}}",
+//! )?;
+//! let agent = create_agent("planner").system_prompt_template(template, json!({}));
+//! ```
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Nesting limit for partials including partials
+const MAX_PARTIAL_DEPTH: usize = 16;
+
+/// Error parsing or rendering a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptError {
+    /// The template text is malformed
+    Parse { message: String, offset: usize },
+    /// A variable is not present in the context
+    MissingVariable(String),
+    /// A `{{> name}}` partial is not registered
+    UnknownPartial(String),
+    /// Partials nest deeper than the limit (usually a cycle)
+    PartialDepth(String),
+    /// The context could not be serialized
+    Context(String),
+    /// A sandboxed template broke one of its limits
+    Sandbox(String),
+}
+
+impl fmt::Display for PromptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PromptError::Parse { message, offset } => {
+                write!(f, "Template error at byte {}: {}", offset, message)
+            }
+            PromptError::MissingVariable(name) => write!(f, "Missing template variable '{}'", name),
+            PromptError::UnknownPartial(name) => write!(f, "Unknown partial '{}'", name),
+            PromptError::PartialDepth(name) => {
+                write!(f, "Partial '{}' nests too deeply (is it recursive?)", name)
+            }
+            PromptError::Context(message) => write!(f, "Invalid template context: {}", message),
+            PromptError::Sandbox(message) => write!(f, "Template rejected by sandbox: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for PromptError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Var(String),
+    If {
+        path: String,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    Each {
+        path: String,
+        body: Vec<Node>,
+    },
+    Partial(String),
+}
+
+/// Limits for templates that come from untrusted sources
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sandbox {
+    /// Largest accepted template source
+    pub max_source_bytes: usize,
+    /// Deepest `#if`/`#each` nesting
+    pub max_depth: usize,
+    /// Total `#each` iterations per render
+    pub max_iterations: usize,
+    /// Largest rendered output
+    pub max_output_bytes: usize,
+    /// Whether `{{> partial}}` is allowed
+    pub allow_partials: bool,
+    /// Escape template delimiters and strip control characters from values
+    pub escape_variables: bool,
+    /// Top-level context names the template may use; `None` allows any
+    pub variables: Option<Vec<String>>,
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Self {
+            max_source_bytes: 16 * 1024,
+            max_depth: 8,
+            max_iterations: 1_000,
+            max_output_bytes: 64 * 1024,
+            allow_partials: false,
+            escape_variables: true,
+            variables: None,
+        }
+    }
+}
+
+impl Sandbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only expose these top-level context names to the template
+    pub fn allow_variables<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.variables = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    fn validate(&self, nodes: &[Node], depth: usize) -> Result<(), PromptError> {
+        if depth > self.max_depth {
+            return Err(PromptError::Sandbox(format!(
+                "blocks nest deeper than {}",
+                self.max_depth
+            )));
+        }
+        for node in nodes {
+            match node {
+                Node::If {
+                    then, otherwise, ..
+                } => {
+                    self.validate(then, depth + 1)?;
+                    self.validate(otherwise, depth + 1)?;
+                }
+                Node::Each { body, .. } => self.validate(body, depth + 1)?,
+                Node::Partial(name) if !self.allow_partials => {
+                    return Err(PromptError::Sandbox(format!(
+                        "partial '{}' is not allowed",
+                        name
+                    )));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The context with only the allowed top-level names
+    fn restrict(&self, context: Value) -> Value {
+        match (&self.variables, context) {
+            (Some(allowed), Value::Object(map)) => Value::Object(
+                map.into_iter()
+                    .filter(|(key, _)| allowed.contains(key))
+                    .collect(),
+            ),
+            (_, context) => context,
+        }
+    }
+}
+
+/// Neutralize template syntax and control characters in an interpolated value
+fn escape_value(text: &str) -> String {
+    let mut text: String = text
+        .chars()
+        .filter(|c| !c.is_control() || *c == '\n' || *c == '\t')
+        .collect();
+    for (open, spaced) in [("{{", "{ {"), ("}}", "} }")] {
+        while text.contains(open) {
+            text = text.replace(open, spaced);
+        }
+    }
+    text
+}
+
+/// A parsed template
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptTemplate {
+    nodes: Vec<Node>,
+    sandbox: Option<Sandbox>,
+}
+
+/// Block being parsed, waiting for its closing tag
+enum Open {
+    If {
+        path: String,
+        then: Option<Vec<Node>>,
+        offset: usize,
+    },
+    Each {
+        path: String,
+        offset: usize,
+    },
+}
+
+impl PromptTemplate {
+    /// Parse template text
+    pub fn parse(source: &str) -> Result<Self, PromptError> {
+        let mut stack: Vec<(Open, Vec<Node>)> = Vec::new();
+        let mut current: Vec<Node> = Vec::new();
+        let mut rest = source;
+
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                current.push(Node::Text(rest[..start].to_string()));
+            }
+            let offset = source.len() - rest.len() + start;
+            let after = &rest[start + 2..];
+            let end = after.find("}}").ok_or_else(|| PromptError::Parse {
+                message: "Unclosed '{{'".to_string(),
+                offset,
+            })?;
+            let tag = after[..end].trim();
+            rest = &after[end + 2..];
+
+            let parse_error = |message: String| PromptError::Parse { message, offset };
+
+            if tag.starts_with('!') {
+                continue;
+            } else if let Some(path) = tag.strip_prefix("#if") {
+                let open = Open::If {
+                    path: block_argument(path, "#if").map_err(parse_error)?,
+                    then: None,
+                    offset,
+                };
+                stack.push((open, std::mem::take(&mut current)));
+            } else if let Some(path) = tag.strip_prefix("#each") {
+                let open = Open::Each {
+                    path: block_argument(path, "#each").map_err(parse_error)?,
+                    offset,
+                };
+                stack.push((open, std::mem::take(&mut current)));
+            } else if tag == "else" {
+                match stack.last_mut() {
+                    Some((
+                        Open::If {
+                            then: then @ None, ..
+                        },
+                        _,
+                    )) => {
+                        *then = Some(std::mem::take(&mut current));
+                    }
+                    _ => return Err(parse_error("'else' outside an '#if' block".to_string())),
+                }
+            } else if let Some(name) = tag.strip_prefix('/') {
+                let (open, parent) = stack
+                    .pop()
+                    .ok_or_else(|| parse_error(format!("Unexpected '/{}'", name.trim())))?;
+                let body = std::mem::replace(&mut current, parent);
+                let node = match (open, name.trim()) {
+                    (Open::If { path, then, .. }, "if") => match then {
+                        Some(then) => Node::If {
+                            path,
+                            then,
+                            otherwise: body,
+                        },
+                        None => Node::If {
+                            path,
+                            then: body,
+                            otherwise: Vec::new(),
+                        },
+                    },
+                    (Open::Each { path, .. }, "each") => Node::Each { path, body },
+                    (_, other) => {
+                        return Err(parse_error(format!("Mismatched closing tag '/{}'", other)))
+                    }
+                };
+                current.push(node);
+            } else if let Some(name) = tag.strip_prefix('>') {
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(parse_error("Partial needs a name".to_string()));
+                }
+                current.push(Node::Partial(name.to_string()));
+            } else if tag.is_empty() || tag.contains(char::is_whitespace) {
+                return Err(parse_error(format!("Invalid tag '{{{{{}}}}}'", tag)));
+            } else {
+                current.push(Node::Var(tag.to_string()));
+            }
+        }
+
+        if let Some((open, _)) = stack.pop() {
+            let (block, offset) = match open {
+                Open::If { offset, .. } => ("#if", offset),
+                Open::Each { offset, .. } => ("#each", offset),
+            };
+            return Err(PromptError::Parse {
+                message: format!("Unclosed '{}' block", block),
+                offset,
+            });
+        }
+        if !rest.is_empty() {
+            current.push(Node::Text(rest.to_string()));
+        }
+        Ok(Self {
+            nodes: current,
+            sandbox: None,
+        })
+    }
+
+    /// Parse untrusted template text, validating it against `sandbox`
+    ///
+    /// The limits are checked here and again every time the template renders.
+    pub fn parse_sandboxed(source: &str, sandbox: Sandbox) -> Result<Self, PromptError> {
+        if source.len() > sandbox.max_source_bytes {
+            return Err(PromptError::Sandbox(format!(
+                "template is {} bytes, the limit is {}",
+                source.len(),
+                sandbox.max_source_bytes
+            )));
+        }
+        let mut template = Self::parse(source)?;
+        sandbox.validate(&template.nodes, 0)?;
+        if let Some(allowed) = &sandbox.variables {
+            if let Some(name) = template
+                .variables()
+                .into_iter()
+                .find(|name| !allowed.contains(name))
+            {
+                return Err(PromptError::Sandbox(format!(
+                    "variable '{}' is not allowed",
+                    name
+                )));
+            }
+        }
+        template.sandbox = Some(sandbox);
+        Ok(template)
+    }
+
+    /// The sandbox this template was loaded with, if any
+    pub fn sandbox(&self) -> Option<&Sandbox> {
+        self.sandbox.as_ref()
+    }
+
+    /// Names of the top-level variables and block arguments the template uses
+    ///
+    /// Useful in tests to check a context type covers its template.
+    /// Names inside `#each` blocks are excluded since they usually refer to
+    /// the items.
+    pub fn variables(&self) -> Vec<String> {
+        fn collect(nodes: &[Node], out: &mut Vec<String>) {
+            for node in nodes {
+                let path = match node {
+                    Node::Var(path) => path,
+                    Node::If {
+                        path,
+                        then,
+                        otherwise,
+                    } => {
+                        collect(then, out);
+                        collect(otherwise, out);
+                        path
+                    }
+                    Node::Each { path, .. } => path,
+                    Node::Text(_) | Node::Partial(_) => continue,
+                };
+                let root = path.split('.').next().unwrap_or(path).to_string();
+                if !root.starts_with('@') && root != "this" && !out.contains(&root) {
+                    out.push(root);
+                }
+            }
+        }
+        let mut out = Vec::new();
+        collect(&self.nodes, &mut out);
+        out
+    }
+
+    /// Render with a serializable context (no partials available)
+    pub fn render(&self, context: &(impl Serialize + ?Sized)) -> Result<String, PromptError> {
+        self.render_with(context, &HashMap::new())
+    }
+
+    fn render_with(
+        &self,
+        context: &(impl Serialize + ?Sized),
+        partials: &HashMap<String, PromptTemplate>,
+    ) -> Result<String, PromptError> {
+        let mut context =
+            serde_json::to_value(context).map_err(|e| PromptError::Context(e.to_string()))?;
+        if let Some(sandbox) = &self.sandbox {
+            context = sandbox.restrict(context);
+        }
+        let mut renderer = Renderer {
+            partials,
+            scopes: vec![Scope {
+                value: &context,
+                index: None,
+            }],
+            depth: 0,
+            sandbox: self.sandbox.as_ref(),
+            iterations: 0,
+            out: String::new(),
+        };
+        renderer.render(&self.nodes)?;
+        Ok(renderer.out)
+    }
+}
+
+fn block_argument(rest: &str, block: &str) -> Result<String, String> {
+    let argument = rest.trim();
+    if argument.is_empty() || argument.contains(char::is_whitespace) || !rest.starts_with(' ') {
+        return Err(format!("'{}' takes exactly one variable", block));
+    }
+    Ok(argument.to_string())
+}
+
+struct Scope<'a> {
+    value: &'a Value,
+    index: Option<usize>,
+}
+
+struct Renderer<'a> {
+    partials: &'a HashMap<String, PromptTemplate>,
+    scopes: Vec<Scope<'a>>,
+    depth: usize,
+    sandbox: Option<&'a Sandbox>,
+    iterations: usize,
+    out: String,
+}
+
+impl<'a> Renderer<'a> {
+    fn push(&mut self, text: &str, interpolated: bool) -> Result<(), PromptError> {
+        let Some(sandbox) = self.sandbox else {
+            self.out.push_str(text);
+            return Ok(());
+        };
+        if interpolated && sandbox.escape_variables {
+            self.out.push_str(&escape_value(text));
+        } else {
+            self.out.push_str(text);
+        }
+        if self.out.len() > sandbox.max_output_bytes {
+            return Err(PromptError::Sandbox(format!(
+                "output exceeds {} bytes",
+                sandbox.max_output_bytes
+            )));
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, path: &str) -> Result<&'a Value, PromptError> {
+        let innermost = self.scopes.last().expect("root scope");
+        if path == "this" || path == "." {
+            return Ok(innermost.value);
+        }
+        let path = path.strip_prefix("this.").unwrap_or(path);
+        for scope in self.scopes.iter().rev() {
+            let mut value = scope.value;
+            let mut found = true;
+            for segment in path.split('.') {
+                match value.get(segment) {
+                    Some(next) => value = next,
+                    None => {
+                        found = false;
+                        break;
+                    }
+                }
+            }
+            if found {
+                return Ok(value);
+            }
+        }
+        Err(PromptError::MissingVariable(path.to_string()))
+    }
+
+    fn render(&mut self, nodes: &'a [Node]) -> Result<(), PromptError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => self.push(text, false)?,
+                Node::Var(path) if path == "@index" => {
+                    let index = self.scopes.last().and_then(|scope| scope.index);
+                    let index = index.ok_or_else(|| PromptError::MissingVariable(path.clone()))?;
+                    self.push(&index.to_string(), false)?;
+                }
+                Node::Var(path) => match self.lookup(path)? {
+                    Value::String(text) => self.push(text, true)?,
+                    Value::Null => {}
+                    other => self.push(&other.to_string(), true)?,
+                },
+                Node::If {
+                    path,
+                    then,
+                    otherwise,
+                } => {
+                    // A missing value counts as false, so optional context works
+                    let truthy = self.lookup(path).map(is_truthy).unwrap_or(false);
+                    self.render(if truthy { then } else { otherwise })?;
+                }
+                Node::Each { path, body } => {
+                    let items = match self.lookup(path)? {
+                        Value::Array(items) => items.as_slice(),
+                        Value::Null => &[],
+                        _ => return Err(PromptError::Context(format!("'{}' is not a list", path))),
+                    };
+                    for (index, item) in items.iter().enumerate() {
+                        self.iterations += 1;
+                        if let Some(sandbox) = self.sandbox {
+                            if self.iterations > sandbox.max_iterations {
+                                return Err(PromptError::Sandbox(format!(
+                                    "more than {} loop iterations",
+                                    sandbox.max_iterations
+                                )));
+                            }
+                        }
+                        self.scopes.push(Scope {
+                            value: item,
+                            index: Some(index),
+                        });
+                        let result = self.render(body);
+                        self.scopes.pop();
+                        result?;
+                    }
+                }
+                Node::Partial(name) => {
+                    let partial = self
+                        .partials
+                        .get(name)
+                        .ok_or_else(|| PromptError::UnknownPartial(name.clone()))?;
+                    if self.depth >= MAX_PARTIAL_DEPTH {
+                        return Err(PromptError::PartialDepth(name.clone()));
+                    }
+                    self.depth += 1;
+                    let result = self.render(&partial.nodes);
+                    self.depth -= 1;
+                    result?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+/// Named templates that can include each other as partials
+#[derive(Debug, Clone, Default)]
+pub struct PromptLibrary {
+    templates: HashMap<String, PromptTemplate>,
+    sandbox: Option<Sandbox>,
+}
+
+impl PromptLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load every template registered from now on with
+    /// [`PromptTemplate::parse_sandboxed`]
+    pub fn sandboxed(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Parse and register a template (replacing any with the same name)
+    pub fn register(&mut self, name: impl Into<String>, source: &str) -> Result<(), PromptError> {
+        let template = match &self.sandbox {
+            Some(sandbox) => PromptTemplate::parse_sandboxed(source, sandbox.clone())?,
+            None => PromptTemplate::parse(source)?,
+        };
+        self.templates.insert(name.into(), template);
+        Ok(())
+    }
+
+    /// Builder form of [`register`](Self::register)
+    pub fn with(mut self, name: impl Into<String>, source: &str) -> Result<Self, PromptError> {
+        self.register(name, source)?;
+        Ok(self)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PromptTemplate> {
+        self.templates.get(name)
+    }
+
+    /// Render a registered template, resolving partials from this library
+    pub fn render(
+        &self,
+        name: &str,
+        context: &(impl Serialize + ?Sized),
+    ) -> Result<String, PromptError> {
+        let template = self
+            .templates
+            .get(name)
+            .ok_or_else(|| PromptError::UnknownPartial(name.to_string()))?;
+        template.render_with(context, &self.templates)
+    }
+
+    /// Render an unregistered template against this library's partials
+    pub fn render_template(
+        &self,
+        template: &PromptTemplate,
+        context: &(impl Serialize + ?Sized),
+    ) -> Result<String, PromptError> {
+        template.render_with(context, &self.templates)
+    }
+}
+
+/// A prompt whose variables are the fields of a struct
+///
+/// ```ignore
+/// #[derive(Serialize)]
+/// struct Summarize<'a> {
+///     audience: &'a str,
+///     text: &'a str,
+/// }
+///
+/// impl TypedPrompt for Summarize<'_> {
+///     const TEMPLATE: &'static str = "Summarize for {{audience}}:\n\n{{text}}";
+/// }
+///
+/// let prompt = Summarize { audience: "executives", text: &report }.render()?;
+/// ```
+pub trait TypedPrompt: Serialize {
+    /// Template text, with variables named after the struct's fields
+    const TEMPLATE: &'static str;
+
+    fn render(&self) -> Result<String, PromptError> {
+        PromptTemplate::parse(Self::TEMPLATE)?.render(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(source: &str, context: Value) -> Result<String, PromptError> {
+        PromptTemplate::parse(source)?.render(&context)
+    }
+
+    #[test]
+    fn test_variables_and_paths() {
+        let text = render(
+            "Hi {{user.name}}, you have {{count}} tasks{{! private }}.",
+            json!({"user": {"name": "Ada"}, "count": 3}),
+        )
+        .unwrap();
+        assert_eq!(text, "Hi Ada, you have 3 tasks.");
+
+        assert_eq!(
+            render("{{missing}}", json!({})),
+            Err(PromptError::MissingVariable("missing".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_conditionals_and_loops() {
+        let source =
+            "{{#if tools}}Tools:{{#each tools}}\n{{@index}}. {{name}} ({{agent}}){{/each}}\
+                      {{else}}No tools.{{/if}}";
+        let with_tools = render(
+            source,
+            json!({"agent": "ops", "tools": [{"name": "shell"}, {"name": "read_file"}]}),
+        )
+        .unwrap();
+        assert_eq!(with_tools, "Tools:\n0. shell (ops)\n1. read_file (ops)");
+
+        assert_eq!(render(source, json!({"tools": []})).unwrap(), "No tools.");
+        assert_eq!(render(source, json!({})).unwrap(), "No tools.");
+    }
+
+    #[test]
+    fn test_partials() {
+        let library = PromptLibrary::new()
+            .with("persona", "You are {{name}}.")
+            .unwrap()
+            .with("system", "{{> persona}} Be brief.")
+            .unwrap()
+            .with("loop", "{{> loop}}")
+            .unwrap();
+
+        assert_eq!(
+            library
+                .render("system", &json!({"name": "Patinox"}))
+                .unwrap(),
+            "You are Patinox. Be brief."
+        );
+        assert!(matches!(
+            library.render("loop", &json!({})),
+            Err(PromptError::PartialDepth(_))
+        ));
+        assert!(matches!(
+            PromptTemplate::parse("{{> persona}}")
+                .unwrap()
+                .render(&json!({})),
+            Err(PromptError::UnknownPartial(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for bad in [
+            "{{name",
+            "{{#if x}}open",
+            "{{#each}}{{/each}}",
+            "{{/if}}",
+            "{{#if a}}{{/each}}",
+        ] {
+            assert!(
+                matches!(PromptTemplate::parse(bad), Err(PromptError::Parse { .. })),
+                "{} should not parse",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_sandbox_validation() {
+        let sandbox = Sandbox {
+            max_source_bytes: 64,
+            max_depth: 1,
+            ..Sandbox::default()
+        }
+        .allow_variables(["name", "items"]);
+        let rejected = [
+            "{{name}}".repeat(10),
+            "{{#each items}}{{#if this}}x{{/if}}{{/each}}".to_string(),
+            "{{> other}}".to_string(),
+            "Hello {{secret}}".to_string(),
+        ];
+        for source in rejected {
+            assert!(
+                matches!(
+                    PromptTemplate::parse_sandboxed(&source, sandbox.clone()),
+                    Err(PromptError::Sandbox(_))
+                ),
+                "{} should be rejected",
+                source
+            );
+        }
+
+        // Names inside loops fall back to the outer context, which only has
+        // the allowed names
+        let template =
+            PromptTemplate::parse_sandboxed("{{#each items}}{{secret}}{{/each}}", sandbox).unwrap();
+        assert_eq!(
+            template.render(&json!({"items": [1], "secret": "hunter2"})),
+            Err(PromptError::MissingVariable("secret".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_sandbox_render_limits() {
+        let sandbox = Sandbox {
+            max_iterations: 3,
+            max_output_bytes: 20,
+            ..Sandbox::default()
+        };
+        let template = PromptTemplate::parse_sandboxed(
+            "{{#each rows}}{{#each this}}.{{/each}}{{/each}}",
+            sandbox.clone(),
+        )
+        .unwrap();
+        assert!(template.render(&json!({"rows": [[1]]})).is_ok());
+        assert!(matches!(
+            template.render(&json!({"rows": [[1, 2], [3]]})),
+            Err(PromptError::Sandbox(message)) if message.contains("iterations")
+        ));
+
+        let template = PromptTemplate::parse_sandboxed("Say: {{text}}", sandbox).unwrap();
+        assert!(matches!(
+            template.render(&json!({"text": "x".repeat(100)})),
+            Err(PromptError::Sandbox(message)) if message.contains("output")
+        ));
+        assert_eq!(
+            template
+                .render(&json!({"text": "{{{x}}}\u{1b}[2J"}))
+                .unwrap(),
+            "Say: { { {x} } }[2J"
+        );
+        // Unsandboxed templates interpolate as before
+        assert_eq!(
+            render("{{text}}", json!({"text": "{{x}}"})).unwrap(),
+            "{{x}}"
+        );
+    }
+
+    #[derive(Serialize)]
+    struct Summarize<'a> {
+        audience: &'a str,
+        text: &'a str,
+    }
+
+    impl TypedPrompt for Summarize<'_> {
+        const TEMPLATE: &'static str = "Summarize for {{audience}}:\n\n{{text}}";
+    }
+
+    #[test]
+    fn test_typed_prompt_covers_template() {
+        let prompt = Summarize {
+            audience: "executives",
+            text: "Revenue grew.",
+        };
+        assert_eq!(
+            prompt.render().unwrap(),
+            "Summarize for executives:\n\nRevenue grew."
+        );
+
+        let fields = serde_json::to_value(&prompt).unwrap();
+        for variable in PromptTemplate::parse(Summarize::TEMPLATE)
+            .unwrap()
+            .variables()
+        {
+            assert!(fields.get(&variable).is_some(), "{} not a field", variable);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Many completions at once
+//!
+//! [`LLMProvider::complete_batch`] runs a list of independent requests and
+//! returns their results in order. The default sends them concurrently, at
+//! most [`DEFAULT_BATCH_CONCURRENCY`] at a time; use
+//! [`complete_concurrently`] to pick another limit.
+//!
+//! For large offline jobs (bulk classification, evals) where results can wait
+//! hours, [`OpenAIProvider`](super::OpenAIProvider) also speaks OpenAI's
+//! Batch API, which is cheaper per request: `submit_batch` uploads the
+//! requests and returns a [`BatchJob`], `batch_state` polls it and
+//! `batch_results` waits for and downloads the results. A `BatchJob` is
+//! serializable, so a later process can collect what an earlier one
+//! submitted.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::provider::batch::BatchRequest;
+//!
+//! let requests = tickets
+//!     .iter()
+//!     .map(|t| BatchRequest::new(vec![Message::system(CLASSIFY), Message::user(t)]))
+//!     .collect();
+//! for result in provider.complete_batch(requests).await {
+//!     println!("{:?}", result?);
+//! }
+//! ```
+
+use super::{LLMProvider, Message, ProviderResponse, ProviderResult, ToolDefinition};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Requests in flight at once in the default [`LLMProvider::complete_batch`]
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 8;
+
+/// One request of a batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub messages: Vec<Message>,
+    #[serde(default)]
+    pub tools: Vec<ToolDefinition>,
+}
+
+impl BatchRequest {
+    pub fn new(messages: Vec<Message>) -> Self {
+        Self {
+            messages,
+            tools: Vec::new(),
+        }
+    }
+
+    pub fn tools(mut self, tools: Vec<ToolDefinition>) -> Self {
+        self.tools = tools;
+        self
+    }
+}
+
+/// Complete every request with at most `concurrency` in flight, keeping
+/// the results in request order
+pub async fn complete_concurrently<P: LLMProvider + ?Sized>(
+    provider: &P,
+    requests: Vec<BatchRequest>,
+    concurrency: usize,
+) -> Vec<ProviderResult<ProviderResponse>> {
+    futures::stream::iter(requests)
+        .map(|request| provider.complete(request.messages, request.tools))
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+/// A batch submitted to a provider's batch API
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchJob {
+    pub id: String,
+    /// Number of requests in the batch
+    pub size: usize,
+    /// Wire tool names mapped back to the agent's names
+    #[serde(default)]
+    pub tool_names: HashMap<String, String>,
+}
+
+/// Progress of a [`BatchJob`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchState {
+    /// Validating, running or finalizing
+    Pending {
+        completed: usize,
+        failed: usize,
+    },
+    Completed,
+    Failed(String),
+    Expired,
+    Cancelled,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    /// Echoes the request after a delay, tracking the peak concurrency
+    #[derive(Default)]
+    struct SlowEcho {
+        in_flight: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for SlowEcho {
+        async fn complete(
+            &self,
+            messages: Vec<Message>,
+            _tools: Vec<ToolDefinition>,
+        ) -> ProviderResult<ProviderResponse> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(40)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            let input = messages.last().unwrap().content.clone();
+            if input.ends_with('3') {
+                return Err("rate limited".into());
+            }
+            Ok(ProviderResponse::Text(input))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_complete_concurrently() {
+        let provider = SlowEcho::default();
+        let requests: Vec<BatchRequest> = (0..6)
+            .map(|i| BatchRequest::new(vec![Message::user(format!("request {}", i))]))
+            .collect();
+
+        let started = Instant::now();
+        let results = complete_concurrently(&provider, requests.clone(), 3).await;
+        assert_eq!(provider.peak.load(Ordering::SeqCst), 3);
+        assert!(started.elapsed() < Duration::from_millis(200));
+        for (i, result) in results.iter().enumerate() {
+            match result {
+                Ok(ProviderResponse::Text(text)) => assert_eq!(text, &format!("request {}", i)),
+                Err(e) => assert_eq!((i, e.to_string().as_str()), (3, "rate limited")),
+                Ok(other) => panic!("unexpected {:?}", other),
+            }
+        }
+
+        // The trait default uses the same fan-out
+        assert_eq!(provider.complete_batch(requests).await.len(), 6);
+    }
+}
@@ -0,0 +1,193 @@
+//! What a model can do
+//!
+//! Tools that need a capability the model lacks (a screenshot tool on a
+//! text-only model, any tool on a model without function calling) fail in
+//! confusing ways at runtime. Tools declare what they need with
+//! [`Tool::required_capabilities`](crate::tool::Tool::required_capabilities);
+//! the agent hides the ones the model can't use and reports them as
+//! [`CapabilityWarning`]s in its manifest.
+//!
+//! [`ModelCapabilities::for_model`] knows the common model families; set
+//! capabilities explicitly with
+//! [`Agent::with_capabilities`](crate::Agent::with_capabilities) for
+//! anything else.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A feature a tool may depend on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Function calling; every tool needs it
+    ToolCalling,
+    /// Image inputs
+    Vision,
+    /// Audio inputs or outputs
+    Audio,
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Capability::ToolCalling => write!(f, "tool calling"),
+            Capability::Vision => write!(f, "vision"),
+            Capability::Audio => write!(f, "audio"),
+        }
+    }
+}
+
+/// Capabilities of a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelCapabilities {
+    pub supports_tools: bool,
+    pub supports_vision: bool,
+    pub supports_audio: bool,
+    /// Reasoning model: takes a reasoning effort, rejects a temperature
+    #[serde(default)]
+    pub supports_reasoning: bool,
+}
+
+impl Default for ModelCapabilities {
+    /// Tool calling only, the common denominator of current chat models
+    fn default() -> Self {
+        Self {
+            supports_tools: true,
+            supports_vision: false,
+            supports_audio: false,
+            supports_reasoning: false,
+        }
+    }
+}
+
+/// Model name fragments of families that accept images
+const VISION_MODELS: &[&str] = &[
+    "gpt-4o",
+    "gpt-4.1",
+    "gpt-4-turbo",
+    "gpt-4-vision",
+    "gpt-5",
+    "o3",
+    "o4",
+    "claude-3",
+    "claude-sonnet",
+    "claude-opus",
+    "claude-haiku",
+    "gemini",
+    "llava",
+    "vision",
+    "pixtral",
+    "-vl",
+];
+
+/// Model name fragments of families without function calling
+const NO_TOOL_MODELS: &[&str] = &[
+    "o1-mini",
+    "o1-preview",
+    "gpt-4-vision",
+    "claude-2",
+    "claude-instant",
+    "llava",
+    "gemma",
+];
+
+/// Model name prefixes of reasoning model families
+const REASONING_MODELS: &[&str] = &["o1", "o3", "o4", "gpt-5"];
+
+impl ModelCapabilities {
+    /// Best guess from the model name; unknown models get the default
+    pub fn for_model(model: &str) -> Self {
+        let model = model.to_lowercase();
+        let matches = |fragments: &[&str]| fragments.iter().any(|f| model.contains(f));
+        Self {
+            supports_tools: !matches(NO_TOOL_MODELS),
+            supports_vision: matches(VISION_MODELS),
+            supports_audio: model.contains("audio") || model.contains("realtime"),
+            // Match the name after any "openai/" style prefix
+            supports_reasoning: model
+                .rsplit('/')
+                .next()
+                .is_some_and(|name| REASONING_MODELS.iter().any(|p| name.starts_with(p))),
+        }
+    }
+
+    pub fn supports(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::ToolCalling => self.supports_tools,
+            Capability::Vision => self.supports_vision,
+            Capability::Audio => self.supports_audio,
+        }
+    }
+
+    /// The capabilities in `required` this model lacks
+    pub fn missing(&self, required: &[Capability]) -> Vec<Capability> {
+        required
+            .iter()
+            .copied()
+            .filter(|capability| !self.supports(*capability))
+            .collect()
+    }
+}
+
+/// A tool hidden from the model because it lacks capabilities
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapabilityWarning {
+    pub tool: String,
+    pub model: String,
+    pub missing: Vec<Capability>,
+}
+
+impl fmt::Display for CapabilityWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let missing: Vec<String> = self.missing.iter().map(ToString::to_string).collect();
+        write!(
+            f,
+            "Tool '{}' is disabled: model '{}' does not support {}",
+            self.tool,
+            self.model,
+            missing.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_model() {
+        let gpt = ModelCapabilities::for_model("gpt-4o-mini");
+        assert!(gpt.supports_tools && gpt.supports_vision && !gpt.supports_audio);
+
+        let o1 = ModelCapabilities::for_model("o1-mini");
+        assert!(!o1.supports_tools && !o1.supports_vision);
+
+        let llama = ModelCapabilities::for_model("llama3.1:8b");
+        assert_eq!(llama, ModelCapabilities::default());
+
+        assert!(ModelCapabilities::for_model("Claude-3-Haiku-20240307").supports_vision);
+        assert!(ModelCapabilities::for_model("gpt-4o-audio-preview").supports_audio);
+
+        assert!(o1.supports_reasoning);
+        assert!(ModelCapabilities::for_model("openai/o3-mini").supports_reasoning);
+        assert!(!ModelCapabilities::for_model("gpt-4o").supports_reasoning);
+    }
+
+    #[test]
+    fn test_missing() {
+        let text_only = ModelCapabilities::for_model("llama3.1:8b");
+        assert_eq!(
+            text_only.missing(&[Capability::ToolCalling, Capability::Vision]),
+            vec![Capability::Vision]
+        );
+        let warning = CapabilityWarning {
+            tool: "screenshot".into(),
+            model: "llama3.1:8b".into(),
+            missing: vec![Capability::Vision],
+        };
+        assert_eq!(
+            warning.to_string(),
+            "Tool 'screenshot' is disabled: model 'llama3.1:8b' does not support vision"
+        );
+    }
+}
@@ -0,0 +1,534 @@
+//! Record and replay provider calls
+//!
+//! Agent tests that call a real model are slow, cost money and give
+//! different answers each run. [`RecordingProvider`] wraps a real provider
+//! and saves every request and response to a cassette file; later,
+//! [`ReplayProvider`] serves the recorded responses back so the same test
+//! runs offline and deterministically - VCR for LLM calls.
+//!
+//! Cassettes also keep the provider and model recorded against and the
+//! token usage the provider reported, so a replayed agent reports the same
+//! model and meters the same usage as the recorded one.
+//!
+//! Requests are matched against unused recordings in order:
+//!
+//! - [`Matching::Strict`] - messages and tool names must be identical
+//! - [`Matching::Fuzzy`] - system messages and tools are ignored, and
+//!   message text is compared ignoring case and whitespace, so prompts that
+//!   embed the date or small wording changes still replay
+//!
+//! # Example
+//! ```ignore
+//! use patinox::provider::cassette::{RecordingProvider, ReplayProvider};
+//!
+//! // Once, with a real key:
+//! let provider = RecordingProvider::new(Box::new(OpenAIProvider::new(config)?), "tests/cassettes/triage.json");
+//!
+//! // In CI:
+//! let provider = ReplayProvider::from_file("tests/cassettes/triage.json")?;
+//! let agent = create_agent("triage").with_provider(Box::new(provider));
+//! ```
+
+use super::{
+    LLMProvider, Message, Provider, ProviderConfig, ProviderResponse, ProviderResult,
+    ProviderUsage, RequestOptions, ToolCall, ToolDefinition,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A recorded provider response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RecordedResponse {
+    Text { text: String },
+    ToolCalls { calls: Vec<ToolCall> },
+    Error { message: String },
+}
+
+impl RecordedResponse {
+    fn from_result(result: &ProviderResult<ProviderResponse>) -> Self {
+        match result {
+            Ok(ProviderResponse::Text(text)) => RecordedResponse::Text { text: text.clone() },
+            Ok(ProviderResponse::ToolCalls(calls)) => RecordedResponse::ToolCalls {
+                calls: calls.clone(),
+            },
+            Err(e) => RecordedResponse::Error {
+                message: e.to_string(),
+            },
+        }
+    }
+
+    fn into_result(self) -> ProviderResult<ProviderResponse> {
+        match self {
+            RecordedResponse::Text { text } => Ok(ProviderResponse::Text(text)),
+            RecordedResponse::ToolCalls { calls } => Ok(ProviderResponse::ToolCalls(calls)),
+            RecordedResponse::Error { message } => Err(message.into()),
+        }
+    }
+}
+
+/// One request and the response it got
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub messages: Vec<Message>,
+    pub tools: Vec<String>,
+    /// Model the request was sent to, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub response: RecordedResponse,
+    /// Token usage the provider reported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ProviderUsage>,
+}
+
+/// Every interaction of a recording session
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    /// Provider recorded against, when it has a configuration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<Provider>,
+    /// Model the provider was configured with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    /// Configuration to replay with: the recorded provider and model, and
+    /// no API key
+    fn config(&self) -> Option<ProviderConfig> {
+        let mut config = ProviderConfig::new(self.provider?);
+        config.api_key = None;
+        if let Some(model) = &self.model {
+            config.model = model.clone();
+        }
+        Some(config)
+    }
+}
+
+impl Cassette {
+    pub fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let text = std::fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("Cannot read cassette {}: {}", path.as_ref().display(), e))?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> crate::Result<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+fn tool_names(tools: &[ToolDefinition]) -> Vec<String> {
+    tools.iter().map(|tool| tool.name.clone()).collect()
+}
+
+/// Passes calls through to a provider and saves them to a cassette
+///
+/// The cassette file is rewritten after every call, so a test that fails
+/// halfway still leaves what it recorded. Copies made by
+/// [`with_model`](LLMProvider::with_model) record into the same cassette.
+pub struct RecordingProvider {
+    inner: Box<dyn LLMProvider>,
+    path: Arc<PathBuf>,
+    cassette: Arc<Mutex<Cassette>>,
+}
+
+impl RecordingProvider {
+    /// Record calls to `inner` into a new cassette at `path`
+    pub fn new(inner: Box<dyn LLMProvider>, path: impl Into<PathBuf>) -> Self {
+        let cassette = Cassette {
+            provider: inner.config().map(|config| config.provider),
+            model: inner.config().map(|config| config.model.clone()),
+            interactions: Vec::new(),
+        };
+        Self {
+            inner,
+            path: Arc::new(path.into()),
+            cassette: Arc::new(Mutex::new(cassette)),
+        }
+    }
+
+    /// The interactions recorded so far
+    pub fn cassette(&self) -> Cassette {
+        self.cassette.lock().unwrap().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl LLMProvider for RecordingProvider {
+    async fn complete(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+    ) -> ProviderResult<ProviderResponse> {
+        self.complete_with_options(messages, tools, &RequestOptions::default())
+            .await
+    }
+
+    async fn complete_with_options(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<ProviderResponse> {
+        self.complete_with_usage(messages, tools, options)
+            .await
+            .map(|(response, _)| response)
+    }
+
+    async fn complete_with_usage(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<(ProviderResponse, Option<ProviderUsage>)> {
+        let recorded_messages = messages.clone();
+        let names = tool_names(&tools);
+        let model = options
+            .model
+            .clone()
+            .or_else(|| self.inner.config().map(|config| config.model.clone()));
+        let result = self
+            .inner
+            .complete_with_usage(messages, tools, options)
+            .await;
+        let (response, usage) = match result {
+            Ok((response, usage)) => (Ok(response), usage),
+            Err(e) => (Err(e), None),
+        };
+
+        let cassette = {
+            let mut cassette = self.cassette.lock().unwrap();
+            cassette.interactions.push(Interaction {
+                messages: recorded_messages,
+                tools: names,
+                model,
+                response: RecordedResponse::from_result(&response),
+                usage,
+            });
+            cassette.clone()
+        };
+        cassette.save(self.path.as_path())?;
+        response.map(|response| (response, usage))
+    }
+
+    fn config(&self) -> Option<&ProviderConfig> {
+        self.inner.config()
+    }
+
+    fn with_model(&self, model: &str) -> Option<Box<dyn LLMProvider>> {
+        let inner = self.inner.with_model(model)?;
+        Some(Box::new(Self {
+            inner,
+            path: self.path.clone(),
+            cassette: self.cassette.clone(),
+        }))
+    }
+}
+
+/// How a request is matched against recorded interactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Matching {
+    /// Identical messages and tool names
+    #[default]
+    Strict,
+    /// Non-system messages equal ignoring case and whitespace
+    Fuzzy,
+}
+
+impl Matching {
+    fn matches(self, interaction: &Interaction, messages: &[Message], tools: &[String]) -> bool {
+        match self {
+            Matching::Strict => {
+                interaction.tools == tools
+                    && interaction.messages.len() == messages.len()
+                    && interaction
+                        .messages
+                        .iter()
+                        .zip(messages)
+                        .all(|(a, b)| a.role == b.role && a.content == b.content)
+            }
+            Matching::Fuzzy => {
+                let normalized = |messages: &[Message]| -> Vec<(String, String)> {
+                    messages
+                        .iter()
+                        .filter(|m| m.role != "system")
+                        .map(|m| {
+                            let words: Vec<&str> = m.content.split_whitespace().collect();
+                            (m.role.clone(), words.join(" ").to_lowercase())
+                        })
+                        .collect()
+                };
+                normalized(&interaction.messages) == normalized(messages)
+            }
+        }
+    }
+}
+
+/// Serves recorded responses instead of calling a model
+///
+/// Reports the recorded provider and model as its
+/// [`config`](LLMProvider::config). Copies made by
+/// [`with_model`](LLMProvider::with_model) serve from the same recordings.
+pub struct ReplayProvider {
+    interactions: Arc<Vec<Interaction>>,
+    matching: Matching,
+    used: Arc<Mutex<Vec<bool>>>,
+    config: Option<ProviderConfig>,
+}
+
+impl ReplayProvider {
+    pub fn new(cassette: Cassette) -> Self {
+        let used = vec![false; cassette.interactions.len()];
+        Self {
+            config: cassette.config(),
+            interactions: Arc::new(cassette.interactions),
+            matching: Matching::default(),
+            used: Arc::new(Mutex::new(used)),
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> crate::Result<Self> {
+        Ok(Self::new(Cassette::load(path)?))
+    }
+
+    /// Set how requests are matched (strict by default)
+    pub fn matching(mut self, matching: Matching) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    /// Number of recorded interactions not yet replayed
+    pub fn remaining(&self) -> usize {
+        self.used
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|used| !**used)
+            .count()
+    }
+}
+
+#[async_trait::async_trait]
+impl LLMProvider for ReplayProvider {
+    async fn complete(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+    ) -> ProviderResult<ProviderResponse> {
+        self.complete_with_usage(messages, tools, &RequestOptions::default())
+            .await
+            .map(|(response, _)| response)
+    }
+
+    async fn complete_with_options(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<ProviderResponse> {
+        self.complete_with_usage(messages, tools, options)
+            .await
+            .map(|(response, _)| response)
+    }
+
+    async fn complete_with_usage(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        _options: &RequestOptions,
+    ) -> ProviderResult<(ProviderResponse, Option<ProviderUsage>)> {
+        let names = tool_names(&tools);
+        let mut used = self.used.lock().unwrap();
+        let index = self
+            .interactions
+            .iter()
+            .enumerate()
+            .position(|(i, interaction)| {
+                !used[i] && self.matching.matches(interaction, &messages, &names)
+            })
+            .ok_or_else(|| {
+                format!(
+                    "No recorded interaction matches the request ({} messages, last: {:?})",
+                    messages.len(),
+                    messages
+                        .last()
+                        .map(|m| m.content.as_str())
+                        .unwrap_or_default()
+                )
+            })?;
+        used[index] = true;
+        let interaction = &self.interactions[index];
+        let response = interaction.response.clone().into_result()?;
+        Ok((response, interaction.usage))
+    }
+
+    fn config(&self) -> Option<&ProviderConfig> {
+        self.config.as_ref()
+    }
+
+    fn with_model(&self, model: &str) -> Option<Box<dyn LLMProvider>> {
+        Some(Box::new(Self {
+            interactions: self.interactions.clone(),
+            matching: self.matching,
+            used: self.used.clone(),
+            config: self.config.clone().map(|config| config.model(model)),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+
+    fn cassette_path() -> PathBuf {
+        std::env::temp_dir().join(format!("patinox-cassette-{}.json", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let path = cassette_path();
+        let recorder = RecordingProvider::new(Box::new(MockProvider::new("recorded")), &path);
+        let request = vec![Message::system("Today is Monday."), Message::user("Hello")];
+        recorder
+            .complete(request.clone(), Vec::new())
+            .await
+            .unwrap();
+        assert_eq!(recorder.cassette().interactions.len(), 1);
+
+        let replay = ReplayProvider::from_file(&path).unwrap();
+        let response = replay.complete(request.clone(), Vec::new()).await.unwrap();
+        assert!(matches!(response, ProviderResponse::Text(text) if text == "recorded"));
+        assert_eq!(replay.remaining(), 0);
+        // Each recording is served once
+        assert!(replay.complete(request, Vec::new()).await.is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Reports usage and has a configuration, like a real provider
+    struct UsageProvider(ProviderConfig);
+
+    #[async_trait::async_trait]
+    impl LLMProvider for UsageProvider {
+        async fn complete(
+            &self,
+            _messages: Vec<Message>,
+            _tools: Vec<ToolDefinition>,
+        ) -> ProviderResult<ProviderResponse> {
+            Ok(ProviderResponse::Text("4".to_string()))
+        }
+
+        async fn complete_with_usage(
+            &self,
+            messages: Vec<Message>,
+            tools: Vec<ToolDefinition>,
+            _options: &RequestOptions,
+        ) -> ProviderResult<(ProviderResponse, Option<ProviderUsage>)> {
+            let usage = ProviderUsage {
+                prompt_tokens: 12,
+                completion_tokens: 40,
+                reasoning_tokens: 32,
+                ..Default::default()
+            };
+            Ok((self.complete(messages, tools).await?, Some(usage)))
+        }
+
+        fn config(&self) -> Option<&ProviderConfig> {
+            Some(&self.0)
+        }
+
+        fn with_model(&self, model: &str) -> Option<Box<dyn LLMProvider>> {
+            Some(Box::new(Self(self.0.clone().model(model))))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_keeps_model_and_usage() {
+        let path = cassette_path();
+        let config = ProviderConfig::new(Provider::OpenAI).model("o3-mini");
+        let recorder = RecordingProvider::new(Box::new(UsageProvider(config)), &path);
+        let switched = recorder.with_model("o4-mini").unwrap();
+        assert_eq!(switched.config().unwrap().model, "o4-mini");
+        let request = vec![Message::user("2 + 2?")];
+        let (_, usage) = switched
+            .complete_with_usage(request.clone(), Vec::new(), &RequestOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(usage.unwrap().reasoning_tokens, 32);
+
+        // The copy recorded into the same cassette
+        let cassette = recorder.cassette();
+        assert_eq!(cassette.model.as_deref(), Some("o3-mini"));
+        assert_eq!(cassette.interactions[0].model.as_deref(), Some("o4-mini"));
+
+        let replay = ReplayProvider::from_file(&path).unwrap();
+        let config = replay.config().unwrap();
+        assert_eq!(
+            (config.provider, config.model.as_str()),
+            (Provider::OpenAI, "o3-mini")
+        );
+        assert!(config.api_key.is_none());
+        let (response, usage) = replay
+            .complete_with_usage(request, Vec::new(), &RequestOptions::default())
+            .await
+            .unwrap();
+        assert!(matches!(response, ProviderResponse::Text(text) if text == "4"));
+        assert_eq!(
+            usage,
+            Some(ProviderUsage {
+                prompt_tokens: 12,
+                completion_tokens: 40,
+                reasoning_tokens: 32,
+                ..Default::default()
+            })
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_strict_and_fuzzy_matching() {
+        let cassette = Cassette {
+            provider: None,
+            model: None,
+            interactions: vec![Interaction {
+                messages: vec![
+                    Message::system("Today is Monday."),
+                    Message::user("Hello  there"),
+                ],
+                tools: vec!["search".into()],
+                model: None,
+                response: RecordedResponse::ToolCalls {
+                    calls: vec![ToolCall {
+                        id: "call_1".into(),
+                        name: "search".into(),
+                        arguments: serde_json::json!({"q": "x"}),
+                    }],
+                },
+                usage: None,
+            }],
+        };
+        let request = vec![
+            Message::system("Today is Tuesday."),
+            Message::user("hello there"),
+        ];
+
+        let strict = ReplayProvider::new(cassette.clone());
+        let err = strict
+            .complete(request.clone(), Vec::new())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("No recorded interaction matches"));
+
+        let fuzzy = ReplayProvider::new(cassette).matching(Matching::Fuzzy);
+        let response = fuzzy.complete(request, Vec::new()).await.unwrap();
+        assert!(
+            matches!(response, ProviderResponse::ToolCalls(calls) if calls[0].name == "search")
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Image inputs for vision models
+//!
+//! A [`Message`](super::Message) can carry images next to its text, given as
+//! a URL or as base64 data with its mime type. The OpenAI provider sends them
+//! as `image_url` content parts; [`ImageInput::anthropic_block`] builds the
+//! equivalent Anthropic Messages API block for custom providers.
+//!
+//! The agent refuses to send images to a model whose
+//! [`ModelCapabilities`](super::ModelCapabilities) lack vision, failing the
+//! run with [`UnsupportedInput`] instead of letting the provider reject or
+//! silently ignore them.
+
+use super::Capability;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::path::Path;
+
+/// An image attached to a message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageInput {
+    /// An image the provider fetches itself
+    Url { url: String },
+    /// Inline image data
+    Base64 { media_type: String, data: String },
+}
+
+impl ImageInput {
+    pub fn url(url: impl Into<String>) -> Self {
+        ImageInput::Url { url: url.into() }
+    }
+
+    /// Inline `bytes` of the given mime type (e.g. `image/png`)
+    pub fn bytes(media_type: impl Into<String>, bytes: &[u8]) -> Self {
+        ImageInput::Base64 {
+            media_type: media_type.into(),
+            data: base64::engine::general_purpose::STANDARD.encode(bytes),
+        }
+    }
+
+    /// Read an image file, taking the mime type from its extension
+    pub fn from_file(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase)
+            .unwrap_or_default();
+        let media_type = match extension.as_str() {
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "gif" => "image/gif",
+            "webp" => "image/webp",
+            other => return Err(format!("Unsupported image type '{}'", other).into()),
+        };
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Cannot read image {}: {}", path.display(), e))?;
+        Ok(Self::bytes(media_type, &bytes))
+    }
+
+    /// The image as a URL, using a `data:` URL for inline data
+    pub fn to_url(&self) -> String {
+        match self {
+            ImageInput::Url { url } => url.clone(),
+            ImageInput::Base64 { media_type, data } => {
+                format!("data:{};base64,{}", media_type, data)
+            }
+        }
+    }
+
+    /// The image as an Anthropic Messages API content block
+    pub fn anthropic_block(&self) -> Value {
+        let source = match self {
+            ImageInput::Url { url } => json!({"type": "url", "url": url}),
+            ImageInput::Base64 { media_type, data } => {
+                json!({"type": "base64", "media_type": media_type, "data": data})
+            }
+        };
+        json!({"type": "image", "source": source})
+    }
+}
+
+/// A request needs a capability the model does not have
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedInput {
+    pub model: String,
+    pub capability: Capability,
+}
+
+impl fmt::Display for UnsupportedInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Model '{}' does not support {} inputs",
+            self.model, self.capability
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedInput {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_formats() {
+        let inline = ImageInput::bytes("image/png", b"\x89PNG");
+        assert_eq!(inline.to_url(), "data:image/png;base64,iVBORw==");
+        assert_eq!(
+            inline.anthropic_block(),
+            json!({
+                "type": "image",
+                "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw=="}
+            })
+        );
+
+        let remote = ImageInput::url("https://example.com/cat.jpg");
+        assert_eq!(remote.to_url(), "https://example.com/cat.jpg");
+        assert_eq!(remote.anthropic_block()["source"]["type"], "url");
+
+        assert!(ImageInput::from_file("notes.txt").is_err());
+    }
+}
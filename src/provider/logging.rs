@@ -0,0 +1,349 @@
+//! Request/response logging for any provider
+//!
+//! [`LoggingProvider`] wraps another provider and emits a `tracing` event for
+//! every call: the model, message and tool counts, estimated token counts
+//! (see [`tokens`](crate::tokens)), latency and the start of the last message
+//! and the response. Bodies are truncated in the events; with
+//! [`transcript_dir`](LoggingProvider::transcript_dir) the full request and
+//! response are also written there, one JSON file per call.
+//!
+//! Everything logged or written is redacted first: `Authorization` values,
+//! bearer tokens, `sk-...` style keys, the wrapped provider's API key and any
+//! value registered with [`redact`](LoggingProvider::redact).
+//!
+//! # Example
+//! ```ignore
+//! use patinox::create_agent;
+//! use patinox::provider::logging::LoggingProvider;
+//! use patinox::provider::{OpenAIProvider, Provider, ProviderConfig};
+//!
+//! let config = ProviderConfig::new(Provider::OpenAI).model("gpt-4o");
+//! let provider = LoggingProvider::new(Box::new(OpenAIProvider::new(config)?))
+//!     .level(tracing::Level::INFO)
+//!     .transcript_dir("./transcripts");
+//! let agent = create_agent("support").with_provider(Box::new(provider));
+//! ```
+
+use super::{
+    LLMProvider, Message, ProviderConfig, ProviderResponse, ProviderResult, ProviderUsage,
+    RequestOptions, ToolDefinition,
+};
+use crate::tokens::{estimate_message_tokens, estimate_tokens};
+use regex::Regex;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use std::time::Instant;
+use tracing::Level;
+
+/// Default number of characters of a body kept in log lines
+const DEFAULT_MAX_BODY_CHARS: usize = 200;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Emit a `tracing` event at a level chosen at runtime
+///
+/// `tracing` macros need the level as a constant, so dispatch on it.
+macro_rules! event_at {
+    ($level:expr, $($arg:tt)+) => {
+        match $level {
+            Level::ERROR => tracing::error!($($arg)+),
+            Level::WARN => tracing::warn!($($arg)+),
+            Level::INFO => tracing::info!($($arg)+),
+            Level::DEBUG => tracing::debug!($($arg)+),
+            Level::TRACE => tracing::trace!($($arg)+),
+        }
+    };
+}
+
+static CREDENTIAL_PATTERNS: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    vec![
+        (
+            Regex::new(r#"(?i)(authorization"?\s*[:=]\s*"?)(bearer\s+|basic\s+)?[^\s",}]+"#)
+                .unwrap(),
+            "${1}${2}[REDACTED]",
+        ),
+        (
+            Regex::new(r"(?i)\bbearer\s+[a-z0-9._~+/=-]+").unwrap(),
+            "Bearer [REDACTED]",
+        ),
+        (
+            Regex::new(r"\b(sk|pk|rk)-[A-Za-z0-9_-]{16,}").unwrap(),
+            REDACTED,
+        ),
+    ]
+});
+
+/// Provider decorator that logs requests and responses
+pub struct LoggingProvider {
+    inner: Box<dyn LLMProvider>,
+    level: Level,
+    max_body_chars: usize,
+    transcript_dir: Option<PathBuf>,
+    secrets: Arc<Vec<String>>,
+}
+
+impl LoggingProvider {
+    /// Log calls to `inner` at debug level
+    pub fn new(inner: Box<dyn LLMProvider>) -> Self {
+        let secrets = inner
+            .config()
+            .and_then(|config| config.api_key.clone())
+            .into_iter()
+            .collect();
+        Self {
+            inner,
+            level: Level::DEBUG,
+            max_body_chars: DEFAULT_MAX_BODY_CHARS,
+            transcript_dir: None,
+            secrets: Arc::new(secrets),
+        }
+    }
+
+    /// Set the level of request and response events (errors are warnings)
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Truncate logged bodies to `chars` characters
+    pub fn max_body_chars(mut self, chars: usize) -> Self {
+        self.max_body_chars = chars;
+        self
+    }
+
+    /// Also write every full request and response to `dir`
+    pub fn transcript_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.transcript_dir = Some(dir.into());
+        self
+    }
+
+    /// Never log or write `secret`
+    pub fn redact(mut self, secret: impl Into<String>) -> Self {
+        let secret = secret.into();
+        if !secret.is_empty() {
+            Arc::make_mut(&mut self.secrets).push(secret);
+        }
+        self
+    }
+
+    fn scrub(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for secret in self.secrets.iter() {
+            text = text.replace(secret.as_str(), REDACTED);
+        }
+        for (pattern, replacement) in CREDENTIAL_PATTERNS.iter() {
+            text = pattern.replace_all(&text, *replacement).into_owned();
+        }
+        text
+    }
+
+    fn preview(&self, text: &str) -> String {
+        let text = self.scrub(text);
+        match text.char_indices().nth(self.max_body_chars) {
+            Some((cut, _)) => format!("{}... ({} chars)", &text[..cut], text.chars().count()),
+            None => text,
+        }
+    }
+
+    async fn logged(
+        &self,
+        model: &str,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<(ProviderResponse, Option<ProviderUsage>)> {
+        let last = messages
+            .last()
+            .map(|m| m.content.clone())
+            .unwrap_or_default();
+        event_at!(
+            self.level,
+            model,
+            messages = messages.len(),
+            tools = tools.len(),
+            prompt_tokens = estimate_message_tokens(&messages),
+            last = ?self.preview(&last),
+            "provider request"
+        );
+        let transcript = self
+            .transcript_dir
+            .as_ref()
+            .map(|_| json!({"model": model, "messages": &messages, "tools": &tools}));
+
+        let started = Instant::now();
+        let result = self
+            .inner
+            .complete_with_usage(messages, tools, options)
+            .await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        let body = match &result {
+            Ok((ProviderResponse::Text(text), _)) => {
+                event_at!(
+                    self.level,
+                    model,
+                    latency_ms,
+                    completion_tokens = estimate_tokens(text),
+                    text = ?self.preview(text),
+                    "provider response"
+                );
+                json!({"text": text})
+            }
+            Ok((ProviderResponse::ToolCalls(calls), _)) => {
+                let names: Vec<&str> = calls.iter().map(|call| call.name.as_str()).collect();
+                event_at!(
+                    self.level,
+                    model,
+                    latency_ms,
+                    tool_calls = ?names,
+                    "provider response"
+                );
+                json!({"tool_calls": calls})
+            }
+            Err(e) => {
+                tracing::warn!(
+                    model,
+                    latency_ms,
+                    error = %self.scrub(&e.to_string()),
+                    "provider error"
+                );
+                json!({"error": e.to_string()})
+            }
+        };
+
+        if let (Some(dir), Some(mut transcript)) = (&self.transcript_dir, transcript) {
+            transcript["latency_ms"] = latency_ms.into();
+            transcript["response"] = body;
+            if let Err(e) = self.write_transcript(dir, &transcript) {
+                tracing::warn!("Failed to write provider transcript: {}", e);
+            }
+        }
+        result
+    }
+
+    fn write_transcript(&self, dir: &Path, transcript: &Value) -> std::io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        let name = format!(
+            "{}-{}.json",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+            uuid::Uuid::new_v4().simple()
+        );
+        let text = serde_json::to_string_pretty(transcript)?;
+        std::fs::write(dir.join(name), self.scrub(&text))
+    }
+
+    fn model(&self, options: &RequestOptions) -> String {
+        match (&options.model, self.inner.config()) {
+            (Some(model), _) => model.clone(),
+            (None, Some(config)) => config.model.clone(),
+            (None, None) => "unknown".to_string(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl LLMProvider for LoggingProvider {
+    async fn complete(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+    ) -> ProviderResult<ProviderResponse> {
+        self.complete_with_options(messages, tools, &RequestOptions::default())
+            .await
+    }
+
+    async fn complete_with_options(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<ProviderResponse> {
+        self.complete_with_usage(messages, tools, options)
+            .await
+            .map(|(response, _)| response)
+    }
+
+    async fn complete_with_usage(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<(ProviderResponse, Option<ProviderUsage>)> {
+        self.logged(&self.model(options), messages, tools, options)
+            .await
+    }
+
+    fn config(&self) -> Option<&ProviderConfig> {
+        self.inner.config()
+    }
+
+    fn with_model(&self, model: &str) -> Option<Box<dyn LLMProvider>> {
+        let inner = self.inner.with_model(model)?;
+        Some(Box::new(Self {
+            inner,
+            level: self.level,
+            max_body_chars: self.max_body_chars,
+            transcript_dir: self.transcript_dir.clone(),
+            secrets: self.secrets.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+
+    #[test]
+    fn test_scrub() {
+        let provider = LoggingProvider::new(Box::new(MockProvider::new(""))).redact("hunter2");
+        let cases = [
+            (
+                "Authorization: Bearer abc.def-123",
+                "Authorization: Bearer [REDACTED]",
+            ),
+            (
+                r#"{"authorization": "Basic dXNlcjpwYXNz"}"#,
+                r#"{"authorization": "Basic [REDACTED]"}"#,
+            ),
+            ("key sk-proj-abcdefghijklmnop1234 ok", "key [REDACTED] ok"),
+            ("password is hunter2!", "password is [REDACTED]!"),
+            ("nothing secret here", "nothing secret here"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(provider.scrub(input), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_preview_truncates() {
+        let provider = LoggingProvider::new(Box::new(MockProvider::new(""))).max_body_chars(5);
+        assert_eq!(provider.preview("héllo world"), "héllo... (11 chars)");
+        assert_eq!(provider.preview("short"), "short");
+    }
+
+    #[tokio::test]
+    async fn test_transcript_written_and_redacted() {
+        let dir = std::env::temp_dir().join(format!("patinox-log-{}", uuid::Uuid::new_v4()));
+        let provider = LoggingProvider::new(Box::new(MockProvider::new("the answer")))
+            .redact("topsecret")
+            .transcript_dir(&dir);
+
+        let response = provider
+            .complete(vec![Message::user("my key is topsecret")], Vec::new())
+            .await
+            .unwrap();
+        assert!(matches!(response, ProviderResponse::Text(text) if text == "the answer"));
+
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(files.len(), 1);
+        let text = std::fs::read_to_string(files[0].as_ref().unwrap().path()).unwrap();
+        assert!(text.contains("my key is [REDACTED]"));
+        assert!(!text.contains("topsecret"));
+        let transcript: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(transcript["response"]["text"], "the answer");
+        assert_eq!(transcript["model"], "unknown");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
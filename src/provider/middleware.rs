@@ -0,0 +1,351 @@
+//! Request middleware for any provider
+//!
+//! A [`ProviderMiddleware`] sees every request before it reaches the
+//! provider and every result on the way back, so cross-cutting concerns
+//! (custom headers, prompt rewriting, response post-processing, auditing,
+//! request policies) work the same for every backend. [`MiddlewareStack`]
+//! wraps a provider, boxed or not, with any number of them.
+//!
+//! Middleware runs in the order it was added on the way in and in reverse on
+//! the way out, like layers of an onion: the first middleware sees the
+//! request first and the response last. Returning an error from
+//! [`before_request`](ProviderMiddleware::before_request) stops the request
+//! before it is sent.
+//!
+//! Headers are added to [`RequestOptions::headers`]; providers that talk
+//! HTTP send them with the request (see [`OpenAIProvider`](super::OpenAIProvider)).
+//!
+//! # Example
+//! ```ignore
+//! use patinox::provider::middleware::{MiddlewareStack, ProviderMiddleware, ProviderRequest};
+//!
+//! struct Audit;
+//!
+//! #[async_trait]
+//! impl ProviderMiddleware for Audit {
+//!     async fn after_response(
+//!         &self,
+//!         request: &ProviderRequest,
+//!         response: ProviderResult<ProviderResponse>,
+//!     ) -> ProviderResult<ProviderResponse> {
+//!         audit_log.record(&request.messages, &response);
+//!         response
+//!     }
+//! }
+//!
+//! let provider = MiddlewareStack::new(OpenAIProvider::new(config)?).layer(Audit);
+//! let agent = create_agent("support").with_provider(Box::new(provider));
+//! ```
+
+use super::{
+    LLMProvider, Message, ProviderConfig, ProviderResponse, ProviderResult, ProviderUsage,
+    RequestOptions, ToolDefinition,
+};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// A request on its way to the provider
+#[derive(Debug, Clone)]
+pub struct ProviderRequest {
+    pub messages: Vec<Message>,
+    pub tools: Vec<ToolDefinition>,
+    pub options: RequestOptions,
+}
+
+impl ProviderRequest {
+    /// Send an extra HTTP header with this request
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options = self.options.header(name, value);
+        self
+    }
+}
+
+/// Hooks around every provider request
+///
+/// Both hooks default to passing values through unchanged.
+#[async_trait]
+pub trait ProviderMiddleware: Send + Sync {
+    /// Inspect or rewrite a request; an error stops it from being sent
+    async fn before_request(&self, request: ProviderRequest) -> ProviderResult<ProviderRequest> {
+        Ok(request)
+    }
+
+    /// Inspect or rewrite the result of `request`, including errors
+    async fn after_response(
+        &self,
+        _request: &ProviderRequest,
+        response: ProviderResult<ProviderResponse>,
+    ) -> ProviderResult<ProviderResponse> {
+        response
+    }
+}
+
+/// Provider decorator that runs requests through middleware
+pub struct MiddlewareStack<P = Box<dyn LLMProvider>> {
+    inner: P,
+    layers: Vec<Arc<dyn ProviderMiddleware>>,
+}
+
+impl<P: LLMProvider> MiddlewareStack<P> {
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            layers: Vec::new(),
+        }
+    }
+
+    /// Add `middleware` inside the ones already added
+    pub fn layer(mut self, middleware: impl ProviderMiddleware + 'static) -> Self {
+        self.layers.push(Arc::new(middleware));
+        self
+    }
+
+    async fn send(
+        &self,
+        mut request: ProviderRequest,
+    ) -> ProviderResult<(ProviderResponse, Option<ProviderUsage>)> {
+        // Layers that saw the request, so only they see the response
+        let mut entered = 0;
+        let mut result = Ok(());
+        for layer in &self.layers {
+            match layer.before_request(request.clone()).await {
+                Ok(rewritten) => {
+                    request = rewritten;
+                    entered += 1;
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        // Usage of the call made, whatever the layers do with its response
+        let mut usage = None;
+        let mut response = match result {
+            Err(e) => Err(e),
+            Ok(()) => self
+                .inner
+                .complete_with_usage(
+                    request.messages.clone(),
+                    request.tools.clone(),
+                    &request.options,
+                )
+                .await
+                .map(|(response, reported)| {
+                    usage = reported;
+                    response
+                }),
+        };
+        for layer in self.layers[..entered].iter().rev() {
+            response = layer.after_response(&request, response).await;
+        }
+        Ok((response?, usage))
+    }
+}
+
+#[async_trait]
+impl<P: LLMProvider> LLMProvider for MiddlewareStack<P> {
+    async fn complete(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+    ) -> ProviderResult<ProviderResponse> {
+        self.complete_with_options(messages, tools, &RequestOptions::default())
+            .await
+    }
+
+    async fn complete_with_options(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<ProviderResponse> {
+        self.complete_with_usage(messages, tools, options)
+            .await
+            .map(|(response, _)| response)
+    }
+
+    async fn complete_with_usage(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<(ProviderResponse, Option<ProviderUsage>)> {
+        self.send(ProviderRequest {
+            messages,
+            tools,
+            options: options.clone(),
+        })
+        .await
+    }
+
+    fn config(&self) -> Option<&ProviderConfig> {
+        self.inner.config()
+    }
+
+    fn with_model(&self, model: &str) -> Option<Box<dyn LLMProvider>> {
+        let inner = self.inner.with_model(model)?;
+        Some(Box::new(MiddlewareStack {
+            inner,
+            layers: self.layers.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+    use std::sync::Mutex;
+
+    /// Records the order hooks run in
+    struct Trace {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+        reject: bool,
+    }
+
+    #[async_trait]
+    impl ProviderMiddleware for Trace {
+        async fn before_request(
+            &self,
+            mut request: ProviderRequest,
+        ) -> ProviderResult<ProviderRequest> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("before {}", self.name));
+            if self.reject {
+                return Err(format!("{} rejected the request", self.name).into());
+            }
+            request
+                .messages
+                .insert(0, Message::system(format!("via {}", self.name)));
+            Ok(request)
+        }
+
+        async fn after_response(
+            &self,
+            request: &ProviderRequest,
+            response: ProviderResult<ProviderResponse>,
+        ) -> ProviderResult<ProviderResponse> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("after {}", self.name));
+            match response? {
+                ProviderResponse::Text(text) => Ok(ProviderResponse::Text(format!(
+                    "{} [{} saw {} messages]",
+                    text,
+                    self.name,
+                    request.messages.len()
+                ))),
+                other => Ok(other),
+            }
+        }
+    }
+
+    fn trace(name: &'static str, log: &Arc<Mutex<Vec<String>>>, reject: bool) -> Trace {
+        Trace {
+            name,
+            log: log.clone(),
+            reject,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_layers_wrap_in_order() {
+        let mock = Arc::new(MockProvider::new("hi"));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let provider = MiddlewareStack::new(mock.clone())
+            .layer(trace("outer", &log, false))
+            .layer(trace("inner", &log, false));
+
+        let response = provider
+            .complete(vec![Message::user("hello")], Vec::new())
+            .await
+            .unwrap();
+        match response {
+            ProviderResponse::Text(text) => {
+                assert_eq!(text, "hi [inner saw 3 messages] [outer saw 3 messages]")
+            }
+            other => panic!("expected text, got {:?}", other),
+        }
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["before outer", "before inner", "after inner", "after outer"]
+        );
+        let sent = &mock.requests()[0];
+        assert_eq!(sent[0].content, "via inner");
+        assert_eq!(sent[1].content, "via outer");
+    }
+
+    #[tokio::test]
+    async fn test_rejected_request_is_not_sent() {
+        let mock = Arc::new(MockProvider::new("hi"));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let provider = MiddlewareStack::new(mock.clone())
+            .layer(trace("outer", &log, false))
+            .layer(trace("policy", &log, true))
+            .layer(trace("inner", &log, false));
+
+        let error = provider
+            .complete(vec![Message::user("hello")], Vec::new())
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "policy rejected the request");
+        assert_eq!(mock.calls(), 0);
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["before outer", "before policy", "after outer"]
+        );
+    }
+
+    /// Tags every request for an API gateway
+    struct Tenant;
+
+    #[async_trait]
+    impl ProviderMiddleware for Tenant {
+        async fn before_request(
+            &self,
+            request: ProviderRequest,
+        ) -> ProviderResult<ProviderRequest> {
+            Ok(request.header("x-tenant", "acme"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware_headers_reach_the_wire() {
+        use crate::provider::{OpenAIProvider, Provider};
+
+        let mut server = mockito::Server::new_async().await;
+        let completion = server
+            .mock("POST", "/v1/chat/completions")
+            .match_header("x-tenant", "acme")
+            .match_header("x-team", "support")
+            .with_body(
+                serde_json::json!({
+                    "id": "c1", "object": "chat.completion", "created": 1, "model": "gpt-4o-mini",
+                    "choices": [{"index": 0, "finish_reason": "stop",
+                        "message": {"role": "assistant", "content": "hi"}}]
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let config = ProviderConfig::new(Provider::OpenAI)
+            .api_key("sk-test")
+            .header("x-team", "support");
+        let openai = OpenAIProvider::new(config)
+            .unwrap()
+            .api_base(format!("{}/v1", server.url()));
+        let provider = MiddlewareStack::new(openai).layer(Tenant);
+        provider
+            .complete(vec![Message::user("hello")], Vec::new())
+            .await
+            .unwrap();
+        completion.assert_async().await;
+    }
+}
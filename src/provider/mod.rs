@@ -2,15 +2,37 @@
 //!
 //! Minimal provider system supporting multiple LLM backends.
 //! Starts simple, can be enhanced later with retry logic, rate limiting, etc.
+//!
+//! Failed requests carry a [`ProviderError`](crate::error::ProviderError)
+//! with the HTTP status, request id and whether retrying can help; find it
+//! in a boxed error with [`ProviderError::find`](crate::error::ProviderError::find).
 
-mod mock;
+pub mod batch;
+pub mod capabilities;
+pub mod cassette;
+pub mod image;
+pub mod logging;
+pub mod middleware;
+pub mod models;
 mod openai;
+mod pricing;
+mod quorum;
+pub mod secret;
+pub mod speech;
+pub mod testing;
 
-pub use mock::MockProvider;
+pub use capabilities::{Capability, CapabilityWarning, ModelCapabilities};
+pub use image::{ImageInput, UnsupportedInput};
 pub use openai::OpenAIProvider;
+pub use pricing::{
+    parse_feed, parse_openrouter, ModelPrice, PricingCatalog, PricingUpdater, OPENROUTER_MODELS_URL,
+};
+pub use quorum::{Agreement, NoConsensus, QuorumProvider};
+pub use testing::MockProvider;
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::env;
 
 /// Provider result type
@@ -41,8 +63,90 @@ pub enum ProviderResponse {
     ToolCalls(Vec<ToolCall>),
 }
 
+/// Token counts a provider reported for one request
+///
+/// Reasoning tokens are part of `completion_tokens` and billed as such.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderUsage {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub reasoning_tokens: usize,
+    /// Rate-limit headers of the response, when the provider sent them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limits: Option<RateLimits>,
+}
+
+/// Rate-limit state a provider reported with a successful response
+///
+/// Read from the `x-ratelimit-limit-*`, `x-ratelimit-remaining-*` and
+/// `retry-after` headers that OpenAI and OpenRouter send, and returned with
+/// the usage from [`LLMProvider::complete_with_usage`], so callers can slow
+/// down before they get a 429.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimits {
+    /// Requests allowed per window
+    pub limit_requests: Option<u64>,
+    /// Tokens allowed per window
+    pub limit_tokens: Option<u64>,
+    /// Requests left in the current window
+    pub remaining_requests: Option<u64>,
+    /// Tokens left in the current window
+    pub remaining_tokens: Option<u64>,
+    /// How long the provider asked callers to wait
+    pub retry_after: Option<std::time::Duration>,
+}
+
+impl RateLimits {
+    /// Read the rate-limit headers of a response; `None` if it sent none
+    pub fn from_headers(headers: &reqwest::header::HeaderMap) -> Option<Self> {
+        let number = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok())
+        };
+        let limits = Self {
+            limit_requests: number("x-ratelimit-limit-requests"),
+            limit_tokens: number("x-ratelimit-limit-tokens"),
+            remaining_requests: number("x-ratelimit-remaining-requests"),
+            remaining_tokens: number("x-ratelimit-remaining-tokens"),
+            retry_after: crate::error::retry_after(headers),
+        };
+        (limits != Self::default()).then_some(limits)
+    }
+}
+
+/// Whether and which tool the model must call
+///
+/// Serializes as `"auto"`, `"none"`, `"required"` or `{"tool": "name"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolChoice {
+    /// The model decides (the default when tools are offered)
+    Auto,
+    /// Answer in text without calling tools
+    None,
+    /// Call at least one tool
+    Required,
+    /// Call this tool
+    Tool(String),
+}
+
+/// How much reasoning models think before answering
+///
+/// More effort gives better answers to hard problems at the cost of latency
+/// and reasoning tokens, which are billed as completion tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
 /// Supported LLM providers
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Provider {
     /// OpenAI (GPT models)
     OpenAI,
@@ -80,6 +184,15 @@ pub struct ProviderConfig {
     pub api_key: Option<String>,
     pub temperature: Option<f32>,
     pub max_tokens: Option<usize>,
+    pub tool_choice: Option<ToolChoice>,
+    /// Whether the model may ask for several tool calls in one turn
+    pub parallel_tool_calls: Option<bool>,
+    /// Send tool schemas in strict mode, so arguments always match them
+    pub strict_tools: bool,
+    /// How hard reasoning models think before answering
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Extra HTTP headers sent with every request
+    pub headers: BTreeMap<String, String>,
 }
 
 impl ProviderConfig {
@@ -95,6 +208,11 @@ impl ProviderConfig {
             api_key,
             temperature: Some(0.7),
             max_tokens: Some(1000),
+            tool_choice: None,
+            parallel_tool_calls: None,
+            strict_tools: false,
+            reasoning_effort: None,
+            headers: BTreeMap::new(),
         }
     }
 
@@ -104,6 +222,14 @@ impl ProviderConfig {
         self
     }
 
+    /// Set the API key instead of reading it from the environment
+    ///
+    /// See [`secret`] for resolving keys from other stores.
+    pub fn api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
     /// Set the temperature
     pub fn temperature(mut self, temp: f32) -> Self {
         self.temperature = Some(temp);
@@ -115,6 +241,198 @@ impl ProviderConfig {
         self.max_tokens = Some(tokens);
         self
     }
+
+    /// Control whether and which tool the model calls
+    ///
+    /// [`ToolChoice::Required`] and [`ToolChoice::Tool`] apply to the first
+    /// tool turn of an agent run; later turns use [`ToolChoice::Auto`] so the
+    /// model can answer with the tool results.
+    pub fn tool_choice(mut self, choice: ToolChoice) -> Self {
+        self.tool_choice = Some(choice);
+        self
+    }
+
+    /// Allow or forbid several tool calls in one turn
+    pub fn parallel_tool_calls(mut self, allowed: bool) -> Self {
+        self.parallel_tool_calls = Some(allowed);
+        self
+    }
+
+    /// Send tool schemas in strict mode
+    ///
+    /// Schemas are tightened to what strict mode accepts: every object
+    /// forbids extra properties and lists all its properties as required,
+    /// with optional ones made nullable.
+    pub fn strict_tools(mut self, strict: bool) -> Self {
+        self.strict_tools = strict;
+        self
+    }
+
+    /// Set how hard reasoning models think before answering
+    ///
+    /// Ignored by other models. Reasoning models also reject a temperature
+    /// and the `system` role, and take `max_tokens` as a limit on completion
+    /// tokens, reasoning included; providers adjust requests for them (the
+    /// system prompt becomes a `developer` message) based on
+    /// [`ModelCapabilities::supports_reasoning`].
+    pub fn reasoning_effort(mut self, effort: ReasoningEffort) -> Self {
+        self.reasoning_effort = Some(effort);
+        self
+    }
+
+    /// Send an extra HTTP header with every request, e.g. for a gateway
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// This config with per-request overrides applied
+    ///
+    /// Switching provider resets the model to that provider's default unless
+    /// the request also names a model, looks up the new provider's key and
+    /// leaves this provider's headers behind. Request headers are added to
+    /// the config's, replacing any with the same name.
+    pub fn resolve(&self, options: &RequestOptions) -> ProviderConfig {
+        let mut config = match options.provider {
+            Some(provider) if provider != self.provider => ProviderConfig {
+                temperature: self.temperature,
+                max_tokens: self.max_tokens,
+                tool_choice: self.tool_choice.clone(),
+                parallel_tool_calls: self.parallel_tool_calls,
+                strict_tools: self.strict_tools,
+                reasoning_effort: self.reasoning_effort,
+                ..ProviderConfig::new(provider)
+            },
+            _ => self.clone(),
+        };
+        if let Some(model) = &options.model {
+            config.model = model.clone();
+        }
+        if options.temperature.is_some() {
+            config.temperature = options.temperature;
+        }
+        if options.max_tokens.is_some() {
+            config.max_tokens = options.max_tokens;
+        }
+        if options.tool_choice.is_some() {
+            config.tool_choice = options.tool_choice.clone();
+        }
+        if options.parallel_tool_calls.is_some() {
+            config.parallel_tool_calls = options.parallel_tool_calls;
+        }
+        if options.reasoning_effort.is_some() {
+            config.reasoning_effort = options.reasoning_effort;
+        }
+        config.headers.extend(options.headers.clone());
+        config
+    }
+
+    /// The settings a request runs with, without the API key
+    pub fn effective(&self) -> EffectiveConfig {
+        EffectiveConfig {
+            provider: self.provider,
+            model: self.model.clone(),
+            temperature: self.temperature,
+            max_tokens: self.max_tokens,
+            reasoning_effort: self.reasoning_effort,
+        }
+    }
+}
+
+/// Per-request overrides of the agent's provider configuration
+///
+/// Settings cascade from the global defaults ([`ProviderConfig::new`]) through
+/// the agent's config to the request; unset fields keep the value from the
+/// level above.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RequestOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<Provider>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// HTTP headers added to the provider's own for this request
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl RequestOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use another provider for this request
+    pub fn provider(mut self, provider: Provider) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Use another model for this request
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Override the temperature for this request
+    pub fn temperature(mut self, temp: f32) -> Self {
+        self.temperature = Some(temp);
+        self
+    }
+
+    /// Override max tokens for this request
+    pub fn max_tokens(mut self, tokens: usize) -> Self {
+        self.max_tokens = Some(tokens);
+        self
+    }
+
+    /// Control whether and which tool the model calls for this request
+    pub fn tool_choice(mut self, choice: ToolChoice) -> Self {
+        self.tool_choice = Some(choice);
+        self
+    }
+
+    /// Allow or forbid several tool calls in one turn for this request
+    pub fn parallel_tool_calls(mut self, allowed: bool) -> Self {
+        self.parallel_tool_calls = Some(allowed);
+        self
+    }
+
+    /// Set how hard reasoning models think for this request
+    pub fn reasoning_effort(mut self, effort: ReasoningEffort) -> Self {
+        self.reasoning_effort = Some(effort);
+        self
+    }
+
+    /// Send an extra HTTP header with this request
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Whether nothing is overridden
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Resolved provider settings of a run, reported for debugging
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EffectiveConfig {
+    pub provider: Provider,
+    pub model: String,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
 }
 
 /// Message in a conversation
@@ -122,28 +440,36 @@ impl ProviderConfig {
 pub struct Message {
     pub role: String,
     pub content: String,
+    /// Images for vision models (user messages only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageInput>,
 }
 
 impl Message {
-    pub fn user(content: impl Into<String>) -> Self {
+    fn new(role: &str, content: impl Into<String>) -> Self {
         Self {
-            role: "user".to_string(),
+            role: role.to_string(),
             content: content.into(),
+            images: Vec::new(),
         }
     }
 
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new("user", content)
+    }
+
     pub fn assistant(content: impl Into<String>) -> Self {
-        Self {
-            role: "assistant".to_string(),
-            content: content.into(),
-        }
+        Self::new("assistant", content)
     }
 
     pub fn system(content: impl Into<String>) -> Self {
-        Self {
-            role: "system".to_string(),
-            content: content.into(),
-        }
+        Self::new("system", content)
+    }
+
+    /// Attach an image
+    pub fn with_image(mut self, image: ImageInput) -> Self {
+        self.images.push(image);
+        self
     }
 }
 
@@ -159,6 +485,168 @@ pub trait LLMProvider: Send + Sync {
         messages: Vec<Message>,
         tools: Vec<ToolDefinition>,
     ) -> ProviderResult<ProviderResponse>;
+
+    /// A copy of this provider that uses `model`, if the provider supports it
+    ///
+    /// Used to switch models mid-session. Defaults to `None`.
+    fn with_model(&self, _model: &str) -> Option<Box<dyn LLMProvider>> {
+        None
+    }
+
+    /// The configuration requests are sent with, if the provider has one
+    fn config(&self) -> Option<&ProviderConfig> {
+        None
+    }
+
+    /// [`complete`](Self::complete) with per-request overrides
+    ///
+    /// The default honours a model override through
+    /// [`with_model`](Self::with_model) and ignores the rest; providers with
+    /// a [`config`](Self::config) should apply every field.
+    async fn complete_with_options(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<ProviderResponse> {
+        match options.model.as_deref().and_then(|m| self.with_model(m)) {
+            Some(switched) => switched.complete(messages, tools).await,
+            None => self.complete(messages, tools).await,
+        }
+    }
+
+    /// [`complete_with_options`](Self::complete_with_options) with the token
+    /// usage the provider reported
+    ///
+    /// The default reports no usage, and callers fall back to estimates (see
+    /// [`tokens`](crate::tokens)).
+    async fn complete_with_usage(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<(ProviderResponse, Option<ProviderUsage>)> {
+        let response = self.complete_with_options(messages, tools, options).await?;
+        Ok((response, None))
+    }
+
+    /// Complete independent requests, returning results in request order
+    ///
+    /// The default runs up to [`batch::DEFAULT_BATCH_CONCURRENCY`] requests
+    /// at once. See [`batch`] for OpenAI's asynchronous Batch API.
+    async fn complete_batch(
+        &self,
+        requests: Vec<batch::BatchRequest>,
+    ) -> Vec<ProviderResult<ProviderResponse>> {
+        batch::complete_concurrently(self, requests, batch::DEFAULT_BATCH_CONCURRENCY).await
+    }
+}
+
+/// A shared provider, so callers can keep a handle on the one they gave an
+/// agent (e.g. a [`MockProvider`] to inspect its requests)
+#[async_trait::async_trait]
+impl<T: LLMProvider + ?Sized> LLMProvider for std::sync::Arc<T> {
+    async fn complete(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+    ) -> ProviderResult<ProviderResponse> {
+        self.as_ref().complete(messages, tools).await
+    }
+
+    fn with_model(&self, model: &str) -> Option<Box<dyn LLMProvider>> {
+        self.as_ref().with_model(model)
+    }
+
+    fn config(&self) -> Option<&ProviderConfig> {
+        self.as_ref().config()
+    }
+
+    async fn complete_with_options(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<ProviderResponse> {
+        self.as_ref()
+            .complete_with_options(messages, tools, options)
+            .await
+    }
+
+    async fn complete_with_usage(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<(ProviderResponse, Option<ProviderUsage>)> {
+        self.as_ref()
+            .complete_with_usage(messages, tools, options)
+            .await
+    }
+
+    async fn complete_batch(
+        &self,
+        requests: Vec<batch::BatchRequest>,
+    ) -> Vec<ProviderResult<ProviderResponse>> {
+        self.as_ref().complete_batch(requests).await
+    }
+}
+
+/// A boxed provider, so wrappers generic over their inner provider (such as
+/// [`MiddlewareStack`](middleware::MiddlewareStack)) also take trait objects
+#[async_trait::async_trait]
+impl<T: LLMProvider + ?Sized> LLMProvider for Box<T> {
+    async fn complete(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+    ) -> ProviderResult<ProviderResponse> {
+        self.as_ref().complete(messages, tools).await
+    }
+
+    fn with_model(&self, model: &str) -> Option<Box<dyn LLMProvider>> {
+        self.as_ref().with_model(model)
+    }
+
+    fn config(&self) -> Option<&ProviderConfig> {
+        self.as_ref().config()
+    }
+
+    async fn complete_with_options(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<ProviderResponse> {
+        self.as_ref()
+            .complete_with_options(messages, tools, options)
+            .await
+    }
+
+    async fn complete_with_usage(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<(ProviderResponse, Option<ProviderUsage>)> {
+        self.as_ref()
+            .complete_with_usage(messages, tools, options)
+            .await
+    }
+
+    async fn complete_batch(
+        &self,
+        requests: Vec<batch::BatchRequest>,
+    ) -> Vec<ProviderResult<ProviderResponse>> {
+        self.as_ref().complete_batch(requests).await
+    }
+}
+
+/// Turns text into embedding vectors for semantic search
+#[async_trait::async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Embed each text, returning one vector per input in the same order
+    async fn embed(&self, texts: Vec<String>) -> ProviderResult<Vec<Vec<f32>>>;
 }
 
 #[cfg(test)]
@@ -184,6 +672,38 @@ mod tests {
         assert_eq!(config.temperature, Some(0.5));
     }
 
+    #[test]
+    fn test_request_options_cascade() {
+        let agent = ProviderConfig::new(Provider::OpenAI)
+            .model("gpt-4o")
+            .max_tokens(500);
+
+        let same = agent.resolve(&RequestOptions::new());
+        assert_eq!(same.effective(), agent.effective());
+
+        let tuned = agent.resolve(&RequestOptions::new().temperature(0.0));
+        assert_eq!(tuned.model, "gpt-4o");
+        assert_eq!(tuned.temperature, Some(0.0));
+        assert_eq!(tuned.max_tokens, Some(500));
+
+        let switched = agent.resolve(&RequestOptions::new().provider(Provider::Ollama));
+        assert_eq!(switched.provider, Provider::Ollama);
+        assert_eq!(switched.model, "llama3.1:8b");
+        assert_eq!(switched.max_tokens, Some(500));
+
+        let tagged = agent
+            .clone()
+            .header("x-team", "support")
+            .resolve(&RequestOptions::new().header("x-tenant", "acme"));
+        assert_eq!(tagged.headers.len(), 2);
+        assert_eq!(tagged.headers["x-tenant"], "acme");
+
+        let options: RequestOptions =
+            serde_json::from_str(r#"{"provider": "anthropic", "model": "claude-3-opus"}"#).unwrap();
+        assert_eq!(agent.resolve(&options).model, "claude-3-opus");
+        assert!(!options.is_empty());
+    }
+
     #[test]
     fn test_message_creation() {
         let msg = Message::user("Hello");
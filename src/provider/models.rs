@@ -0,0 +1,397 @@
+//! Cached model listings across providers
+//!
+//! Asking a provider which models it serves is a network call, and the
+//! answer rarely changes. A [`ModelCatalog`] keeps each provider's list for
+//! a TTL (default 1 hour), refreshes on demand, and merges every provider
+//! into one view of `provider/model` ids, so checks like
+//! [`supports_model`](ModelCatalog::supports_model) don't cost an HTTP call.
+//!
+//! [`HttpModelSource`] lists models from OpenAI, Anthropic, Ollama and any
+//! OpenAI-compatible API (OpenRouter, vLLM, LM Studio); other sources
+//! implement [`ModelSource`].
+//!
+//! # Example
+//! ```ignore
+//! use patinox::provider::models::{HttpModelSource, ModelCatalog};
+//!
+//! let catalog = ModelCatalog::new()
+//!     .source(HttpModelSource::openai(openai_key))
+//!     .source(HttpModelSource::ollama("http://localhost:11434"));
+//!
+//! if !catalog.supports_model("openai/gpt-4o").await {
+//!     eprintln!("gpt-4o is not available with this key");
+//! }
+//! catalog.refresh("ollama").await?; // after pulling a new model
+//! ```
+
+use super::ProviderResult;
+use crate::error::ProviderError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a fetched list is used before fetching again
+const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// A model offered by a provider
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ModelInfo {
+    /// Name of the [`ModelSource`] that listed the model
+    pub provider: String,
+    /// The model id as the provider knows it
+    pub id: String,
+}
+
+impl ModelInfo {
+    /// `provider/id`, unique across providers
+    pub fn qualified_id(&self) -> String {
+        format!("{}/{}", self.provider, self.id)
+    }
+}
+
+/// Somewhere to ask which models are available
+#[async_trait]
+pub trait ModelSource: Send + Sync {
+    /// Short provider name used to prefix model ids, e.g. `openai`
+    fn name(&self) -> &str;
+
+    /// Ids of every model the provider currently offers
+    async fn list_models(&self) -> ProviderResult<Vec<String>>;
+}
+
+/// Response shapes of the models endpoints
+#[derive(Debug, Clone, Copy)]
+enum ListFormat {
+    /// `{"data": [{"id": ...}]}` (OpenAI, Anthropic, OpenRouter)
+    Data,
+    /// `{"models": [{"name": ...}]}` (Ollama's `/api/tags`)
+    Ollama,
+}
+
+#[derive(Debug, Clone)]
+enum Auth {
+    None,
+    Bearer(String),
+    /// Anthropic's `x-api-key` header
+    ApiKey(String),
+}
+
+/// Lists models over a provider's HTTP API
+#[derive(Debug, Clone)]
+pub struct HttpModelSource {
+    name: String,
+    url: String,
+    auth: Auth,
+    format: ListFormat,
+    client: reqwest::Client,
+}
+
+impl HttpModelSource {
+    fn new(name: &str, url: String, auth: Auth, format: ListFormat) -> Self {
+        Self {
+            name: name.to_string(),
+            url,
+            auth,
+            format,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn openai(api_key: impl Into<String>) -> Self {
+        Self::new(
+            "openai",
+            "https://api.openai.com/v1/models".to_string(),
+            Auth::Bearer(api_key.into()),
+            ListFormat::Data,
+        )
+    }
+
+    pub fn anthropic(api_key: impl Into<String>) -> Self {
+        Self::new(
+            "anthropic",
+            "https://api.anthropic.com/v1/models".to_string(),
+            Auth::ApiKey(api_key.into()),
+            ListFormat::Data,
+        )
+    }
+
+    /// A local Ollama server at `base_url`, e.g. `http://localhost:11434`
+    pub fn ollama(base_url: impl AsRef<str>) -> Self {
+        Self::new(
+            "ollama",
+            format!("{}/api/tags", base_url.as_ref().trim_end_matches('/')),
+            Auth::None,
+            ListFormat::Ollama,
+        )
+    }
+
+    /// An OpenAI-compatible API at `base_url` (ending in `/v1`), listed as
+    /// `name`
+    pub fn openai_compatible(
+        name: impl Into<String>,
+        base_url: impl AsRef<str>,
+        api_key: Option<String>,
+    ) -> Self {
+        Self::new(
+            &name.into(),
+            format!("{}/models", base_url.as_ref().trim_end_matches('/')),
+            api_key.map_or(Auth::None, Auth::Bearer),
+            ListFormat::Data,
+        )
+    }
+
+    /// Send requests to `url` instead of the provider's models endpoint
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+}
+
+#[async_trait]
+impl ModelSource for HttpModelSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn list_models(&self) -> ProviderResult<Vec<String>> {
+        let request = match &self.auth {
+            Auth::None => self.client.get(&self.url),
+            Auth::Bearer(key) => self.client.get(&self.url).bearer_auth(key),
+            Auth::ApiKey(key) => self
+                .client
+                .get(&self.url)
+                .header("x-api-key", key)
+                .header("anthropic-version", ANTHROPIC_VERSION),
+        };
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(&self.name, &e))?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(&self.name, &e))?;
+        if !status.is_success() {
+            return Err(ProviderError::from_http(&self.name, status, &headers, body).into());
+        }
+        parse_models(&body, self.format)
+    }
+}
+
+fn parse_models(body: &str, format: ListFormat) -> ProviderResult<Vec<String>> {
+    let json: Value = serde_json::from_str(body)?;
+    let (list, key) = match format {
+        ListFormat::Data => ("data", "id"),
+        ListFormat::Ollama => ("models", "name"),
+    };
+    let models = json[list]
+        .as_array()
+        .ok_or_else(|| format!("Model list has no '{}' array", list))?;
+    Ok(models
+        .iter()
+        .filter_map(|model| model[key].as_str().map(str::to_string))
+        .collect())
+}
+
+struct CachedList {
+    fetched: Instant,
+    models: Vec<String>,
+}
+
+/// Model lists of several providers, cached with a TTL
+pub struct ModelCatalog {
+    sources: Vec<Arc<dyn ModelSource>>,
+    ttl: Duration,
+    cache: Mutex<HashMap<String, CachedList>>,
+}
+
+impl Default for ModelCatalog {
+    fn default() -> Self {
+        Self {
+            sources: Vec::new(),
+            ttl: DEFAULT_TTL,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl ModelCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// List models from `source` too
+    pub fn source(mut self, source: impl ModelSource + 'static) -> Self {
+        self.sources.push(Arc::new(source));
+        self
+    }
+
+    /// Use fetched lists for `ttl` before fetching again
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Models of `provider`, from the cache while it is fresh
+    ///
+    /// If fetching fails and an expired list is cached, that list is
+    /// returned instead.
+    pub async fn models(&self, provider: &str) -> ProviderResult<Vec<String>> {
+        let cached = {
+            let cache = self.cache.lock().unwrap();
+            cache
+                .get(provider)
+                .map(|list| (list.fetched.elapsed() < self.ttl, list.models.clone()))
+        };
+        match cached {
+            Some((true, models)) => Ok(models),
+            Some((false, stale)) => match self.refresh(provider).await {
+                Ok(models) => Ok(models),
+                Err(e) => {
+                    tracing::warn!("models: using stale list for {}: {}", provider, e);
+                    Ok(stale)
+                }
+            },
+            None => self.refresh(provider).await,
+        }
+    }
+
+    /// Fetch the models of `provider` now, replacing the cached list
+    pub async fn refresh(&self, provider: &str) -> ProviderResult<Vec<String>> {
+        let source = self
+            .sources
+            .iter()
+            .find(|source| source.name() == provider)
+            .ok_or_else(|| format!("No model source named '{}'", provider))?;
+        let models = source.list_models().await?;
+        tracing::debug!("models: fetched {} models from {}", models.len(), provider);
+        self.cache.lock().unwrap().insert(
+            provider.to_string(),
+            CachedList {
+                fetched: Instant::now(),
+                models: models.clone(),
+            },
+        );
+        Ok(models)
+    }
+
+    /// Forget every cached list
+    pub fn invalidate(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    /// Models of every provider, sorted by provider then id
+    ///
+    /// A provider that can't be listed is logged and left out.
+    pub async fn all_models(&self) -> Vec<ModelInfo> {
+        let mut all = Vec::new();
+        for source in &self.sources {
+            match self.models(source.name()).await {
+                Ok(models) => all.extend(models.into_iter().map(|id| ModelInfo {
+                    provider: source.name().to_string(),
+                    id,
+                })),
+                Err(e) => tracing::warn!("models: listing {} failed: {}", source.name(), e),
+            }
+        }
+        all.sort();
+        all
+    }
+
+    /// Whether `model` is offered, as `provider/id` or a bare id offered by
+    /// any provider
+    pub async fn supports_model(&self, model: &str) -> bool {
+        if let Some((provider, id)) = model.split_once('/') {
+            if self.sources.iter().any(|source| source.name() == provider) {
+                return self
+                    .models(provider)
+                    .await
+                    .is_ok_and(|models| models.iter().any(|m| m == id));
+            }
+        }
+        // OpenRouter ids contain a slash themselves, so fall back to a bare id
+        self.all_models().await.iter().any(|info| info.id == model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_catalog_caches_until_refresh() {
+        let mut server = mockito::Server::new_async().await;
+        let openai = server
+            .mock("GET", "/v1/models")
+            .match_header("authorization", "Bearer sk-test")
+            .with_body(r#"{"data": [{"id": "gpt-4o"}, {"id": "gpt-4o-mini"}]}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let ollama = server
+            .mock("GET", "/api/tags")
+            .with_body(r#"{"models": [{"name": "llama3.1:8b"}]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let catalog = ModelCatalog::new()
+            .source(HttpModelSource::openai("sk-test").url(format!("{}/v1/models", server.url())))
+            .source(HttpModelSource::ollama(server.url()));
+
+        assert!(catalog.supports_model("openai/gpt-4o").await);
+        assert!(catalog.supports_model("llama3.1:8b").await);
+        assert!(!catalog.supports_model("openai/llama3.1:8b").await);
+        assert_eq!(
+            catalog
+                .all_models()
+                .await
+                .iter()
+                .map(ModelInfo::qualified_id)
+                .collect::<Vec<_>>(),
+            vec!["ollama/llama3.1:8b", "openai/gpt-4o", "openai/gpt-4o-mini"]
+        );
+
+        catalog.refresh("openai").await.unwrap();
+        openai.assert_async().await;
+        ollama.assert_async().await;
+        assert!(catalog.refresh("anthropic").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_expired_list_is_kept_when_refresh_fails() {
+        let mut server = mockito::Server::new_async().await;
+        let ok = server
+            .mock("GET", "/v1/models")
+            .with_body(r#"{"data": [{"id": "openai/gpt-4o"}]}"#)
+            .create_async()
+            .await;
+        let catalog = ModelCatalog::new()
+            .source(HttpModelSource::openai_compatible(
+                "openrouter",
+                format!("{}/v1", server.url()),
+                None,
+            ))
+            .ttl(Duration::ZERO);
+        assert!(catalog.supports_model("openrouter/openai/gpt-4o").await);
+
+        ok.remove_async().await;
+        server
+            .mock("GET", "/v1/models")
+            .with_status(503)
+            .create_async()
+            .await;
+        assert_eq!(
+            catalog.models("openrouter").await.unwrap(),
+            vec!["openai/gpt-4o"]
+        );
+        assert!(catalog.refresh("openrouter").await.is_err());
+    }
+}
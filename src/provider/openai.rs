@@ -1,152 +1,700 @@
 //! OpenAI provider implementation using async-openai crate
+//!
+//! Request and response types come from async-openai. Every call (chat
+//! completions, embeddings and the Batch API) is sent with reqwest, so its
+//! errors keep the HTTP status, `x-request-id` and `Retry-After`. Headers
+//! from [`ProviderConfig::header`] and [`RequestOptions::header`] are sent
+//! with chat completions only.
 
+use super::batch::{BatchJob, BatchRequest, BatchState};
+use super::speech::multipart_body;
 use super::{
-    LLMProvider, Message, ProviderConfig, ProviderResponse, ProviderResult, ToolCall,
-    ToolDefinition,
+    EmbeddingProvider, LLMProvider, Message, ModelCapabilities, ProviderConfig, ProviderResponse,
+    ProviderResult, ProviderUsage, RateLimits, ReasoningEffort, RequestOptions, ToolCall,
+    ToolChoice, ToolDefinition,
 };
-use serde_json::json;
+use crate::error::{ProviderError, ProviderErrorKind};
+use crate::tool::wire_name;
+use async_openai::types::{Batch, CreateChatCompletionRequest, CreateChatCompletionResponse};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Default model for [`EmbeddingProvider::embed`]
+const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+/// Provider name in [`ProviderError`]s
+const PROVIDER: &str = "openai";
 
 /// OpenAI provider using async-openai crate
 #[derive(Debug)]
 pub struct OpenAIProvider {
-    client: async_openai::Client<async_openai::config::OpenAIConfig>,
+    http: reqwest::Client,
+    /// Base URL of the API, ending in `/v1`
+    api_base: String,
     config: ProviderConfig,
+    embedding_model: String,
 }
 
 impl OpenAIProvider {
     /// Create a new OpenAI provider with the given configuration
     pub fn new(config: ProviderConfig) -> ProviderResult<Self> {
         // Validate that we have an API key
-        let api_key = config
-            .api_key
-            .as_ref()
-            .ok_or("OPENAI_API_KEY is required but not set")?;
+        if config.api_key.is_none() {
+            return Err("OPENAI_API_KEY is required but not set".into());
+        }
 
-        // Create async-openai config
-        let openai_config = async_openai::config::OpenAIConfig::new().with_api_key(api_key);
+        Ok(Self {
+            http: reqwest::Client::new(),
+            api_base: async_openai::config::OPENAI_API_BASE.to_string(),
+            config,
+            embedding_model: DEFAULT_EMBEDDING_MODEL.to_string(),
+        })
+    }
 
-        // Create client
-        let client = async_openai::Client::with_config(openai_config);
+    /// Set the model used for embeddings
+    pub fn embedding_model(mut self, model: impl Into<String>) -> Self {
+        self.embedding_model = model.into();
+        self
+    }
 
-        Ok(Self { client, config })
+    /// Send requests to an OpenAI-compatible API at `url` (ending in `/v1`)
+    pub fn api_base(mut self, url: impl Into<String>) -> Self {
+        self.api_base = url.into().trim_end_matches('/').to_string();
+        self
     }
 }
 
 #[async_trait::async_trait]
-impl LLMProvider for OpenAIProvider {
-    async fn complete(
-        &self,
-        messages: Vec<Message>,
-        tools: Vec<ToolDefinition>,
-    ) -> ProviderResult<ProviderResponse> {
-        use async_openai::types::{
-            ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestSystemMessageArgs,
-            ChatCompletionRequestUserMessageArgs, ChatCompletionToolArgs, ChatCompletionToolType,
-            CreateChatCompletionRequestArgs, FunctionObjectArgs,
-        };
+impl EmbeddingProvider for OpenAIProvider {
+    async fn embed(&self, texts: Vec<String>) -> ProviderResult<Vec<Vec<f32>>> {
+        use async_openai::types::{CreateEmbeddingRequestArgs, CreateEmbeddingResponse};
 
-        // Check for empty messages
-        if messages.is_empty() {
-            return Err("Cannot complete with empty messages".into());
+        if texts.is_empty() {
+            return Ok(Vec::new());
         }
+        let request = CreateEmbeddingRequestArgs::default()
+            .model(&self.embedding_model)
+            .input(texts)
+            .build()?;
+        let (_, body) = self
+            .call(
+                self.request(reqwest::Method::POST, "embeddings")
+                    .json(&request),
+            )
+            .await?;
+        let response: CreateEmbeddingResponse = serde_json::from_str(&body)
+            .map_err(|e| format!("Unexpected OpenAI embeddings response: {}", e))?;
+        let mut data = response.data;
+        data.sort_by_key(|embedding| embedding.index);
+        Ok(data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
+}
 
-        // Convert our Message type to OpenAI's message types
-        let mut openai_messages = Vec::new();
-        for msg in messages {
-            let openai_msg = match msg.role.as_str() {
-                "system" => ChatCompletionRequestSystemMessageArgs::default()
-                    .content(msg.content)
-                    .build()
-                    .map(Into::into)?,
-                "user" => ChatCompletionRequestUserMessageArgs::default()
-                    .content(msg.content)
-                    .build()
-                    .map(Into::into)?,
-                "assistant" => ChatCompletionRequestAssistantMessageArgs::default()
-                    .content(msg.content)
+/// Build the chat completion request for `messages`, with the map from wire
+/// tool names back to the agent's names
+fn build_request(
+    config: &ProviderConfig,
+    messages: Vec<Message>,
+    tools: Vec<ToolDefinition>,
+) -> ProviderResult<(CreateChatCompletionRequest, HashMap<String, String>)> {
+    use async_openai::types::{
+        ChatCompletionNamedToolChoice, ChatCompletionRequestAssistantMessageArgs,
+        ChatCompletionRequestDeveloperMessageArgs,
+        ChatCompletionRequestMessageContentPartImageArgs,
+        ChatCompletionRequestMessageContentPartTextArgs, ChatCompletionRequestSystemMessageArgs,
+        ChatCompletionRequestUserMessageArgs, ChatCompletionRequestUserMessageContentPart,
+        ChatCompletionToolArgs, ChatCompletionToolChoiceOption, ChatCompletionToolType,
+        CreateChatCompletionRequestArgs, FunctionName, FunctionObjectArgs, ImageUrlArgs,
+    };
+
+    // Check for empty messages
+    if messages.is_empty() {
+        return Err("Cannot complete with empty messages".into());
+    }
+
+    // Reasoning models reject the system role and take developer messages
+    // in its place
+    let reasoning = ModelCapabilities::for_model(&config.model).supports_reasoning;
+
+    // Convert our Message type to OpenAI's message types
+    let mut openai_messages = Vec::new();
+    for msg in messages {
+        let openai_msg = match msg.role.as_str() {
+            "system" if reasoning => ChatCompletionRequestDeveloperMessageArgs::default()
+                .content(msg.content)
+                .build()
+                .map(Into::into)?,
+            "system" => ChatCompletionRequestSystemMessageArgs::default()
+                .content(msg.content)
+                .build()
+                .map(Into::into)?,
+            "user" if !msg.images.is_empty() => {
+                let mut parts: Vec<ChatCompletionRequestUserMessageContentPart> =
+                    vec![ChatCompletionRequestMessageContentPartTextArgs::default()
+                        .text(msg.content)
+                        .build()?
+                        .into()];
+                for image in &msg.images {
+                    let image_url = ImageUrlArgs::default().url(image.to_url()).build()?;
+                    parts.push(
+                        ChatCompletionRequestMessageContentPartImageArgs::default()
+                            .image_url(image_url)
+                            .build()?
+                            .into(),
+                    );
+                }
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(parts)
                     .build()
-                    .map(Into::into)?,
-                role => return Err(format!("Unknown message role: {}", role).into()),
-            };
-            openai_messages.push(openai_msg);
+                    .map(Into::into)?
+            }
+            "user" => ChatCompletionRequestUserMessageArgs::default()
+                .content(msg.content)
+                .build()
+                .map(Into::into)?,
+            "assistant" => ChatCompletionRequestAssistantMessageArgs::default()
+                .content(msg.content)
+                .build()
+                .map(Into::into)?,
+            role => return Err(format!("Unknown message role: {}", role).into()),
+        };
+        openai_messages.push(openai_msg);
+    }
+
+    // Convert tools to OpenAI format; namespaced names ("fs.read") are
+    // sent as "fs__read" and mapped back when the model calls them
+    let wire_names: HashMap<String, String> = tools
+        .iter()
+        .map(|tool| (wire_name(&tool.name), tool.name.clone()))
+        .collect();
+    let openai_tools: Vec<_> = tools
+        .iter()
+        .map(|tool| {
+            let mut function = FunctionObjectArgs::default();
+            function
+                .name(wire_name(&tool.name))
+                .description(&tool.description);
+            if config.strict_tools {
+                function
+                    .parameters(strict_schema(tool.parameters.clone()))
+                    .strict(true);
+            } else {
+                function.parameters(tool.parameters.clone());
+            }
+            ChatCompletionToolArgs::default()
+                .r#type(ChatCompletionToolType::Function)
+                .function(function.build().unwrap())
+                .build()
+                .unwrap()
+        })
+        .collect();
+
+    // Build the request
+    let mut request_builder = CreateChatCompletionRequestArgs::default();
+    request_builder
+        .model(&config.model)
+        .messages(openai_messages);
+
+    // Add tools if any; tool options are rejected without tools
+    if !openai_tools.is_empty() {
+        request_builder.tools(openai_tools);
+        if let Some(choice) = &config.tool_choice {
+            request_builder.tool_choice(match choice {
+                ToolChoice::Auto => ChatCompletionToolChoiceOption::Auto,
+                ToolChoice::None => ChatCompletionToolChoiceOption::None,
+                ToolChoice::Required => ChatCompletionToolChoiceOption::Required,
+                ToolChoice::Tool(name) => {
+                    ChatCompletionToolChoiceOption::Named(ChatCompletionNamedToolChoice {
+                        r#type: ChatCompletionToolType::Function,
+                        function: FunctionName {
+                            name: wire_name(name),
+                        },
+                    })
+                }
+            });
+        }
+        if let Some(parallel) = config.parallel_tool_calls {
+            request_builder.parallel_tool_calls(parallel);
+        }
+    }
+
+    // Reasoning models reject a temperature and count reasoning tokens
+    // against max_completion_tokens instead of max_tokens
+    if reasoning {
+        if let Some(max_tokens) = config.max_tokens {
+            request_builder.max_completion_tokens(max_tokens as u32);
+        }
+        if let Some(effort) = config.reasoning_effort {
+            request_builder.reasoning_effort(match effort {
+                ReasoningEffort::Low => async_openai::types::ReasoningEffort::Low,
+                ReasoningEffort::Medium => async_openai::types::ReasoningEffort::Medium,
+                ReasoningEffort::High => async_openai::types::ReasoningEffort::High,
+            });
+        }
+    } else {
+        if let Some(temp) = config.temperature {
+            request_builder.temperature(temp);
+        }
+        if let Some(max_tokens) = config.max_tokens {
+            request_builder.max_tokens(max_tokens as u32);
+        }
+    }
+
+    Ok((request_builder.build()?, wire_names))
+}
+
+/// Tighten a JSON schema to what strict mode accepts
+///
+/// Every object forbids additional properties and requires all of its
+/// properties; properties that weren't required become nullable instead.
+fn strict_schema(mut schema: Value) -> Value {
+    if let Some(object) = schema.as_object_mut() {
+        if let Some(Value::Object(properties)) = object.remove("properties") {
+            let required: Vec<&str> = object
+                .get("required")
+                .and_then(Value::as_array)
+                .map(|names| names.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            let mut strict = serde_json::Map::new();
+            for (name, property) in properties {
+                let property = strict_schema(property);
+                let property = if required.contains(&name.as_str()) {
+                    property
+                } else {
+                    nullable(property)
+                };
+                strict.insert(name, property);
+            }
+            let names = strict.keys().cloned().map(Value::String).collect();
+            object.insert("required".to_string(), Value::Array(names));
+            object.insert("properties".to_string(), Value::Object(strict));
+            object.insert("additionalProperties".to_string(), Value::Bool(false));
+        }
+        if let Some(items) = object.remove("items") {
+            object.insert("items".to_string(), strict_schema(items));
+        }
+    }
+    schema
+}
+
+/// `schema` that also accepts `null`
+fn nullable(mut schema: Value) -> Value {
+    match schema.get_mut("type") {
+        Some(Value::String(kind)) => {
+            let kind = kind.clone();
+            schema["type"] = json!([kind, "null"]);
+        }
+        Some(Value::Array(kinds)) => {
+            if !kinds.contains(&json!("null")) {
+                kinds.push(json!("null"));
+            }
         }
+        _ => return json!({"anyOf": [schema, {"type": "null"}]}),
+    }
+    schema
+}
+
+/// Convert a chat completion into a provider response
+fn parse_response(
+    response: &CreateChatCompletionResponse,
+    wire_names: &HashMap<String, String>,
+) -> ProviderResult<ProviderResponse> {
+    let choice = response
+        .choices
+        .first()
+        .ok_or("No choices in OpenAI response")?;
 
-        // Convert tools to OpenAI format
-        let openai_tools: Vec<_> = tools
+    // Check if the response contains tool calls
+    if let Some(tool_calls) = &choice.message.tool_calls {
+        let calls: Vec<ToolCall> = tool_calls
             .iter()
-            .map(|tool| {
-                ChatCompletionToolArgs::default()
-                    .r#type(ChatCompletionToolType::Function)
-                    .function(
-                        FunctionObjectArgs::default()
-                            .name(&tool.name)
-                            .description(&tool.description)
-                            .parameters(tool.parameters.clone())
-                            .build()
-                            .unwrap(),
-                    )
-                    .build()
-                    .unwrap()
+            .map(|tc| {
+                let args = tc
+                    .function
+                    .arguments
+                    .parse::<serde_json::Value>()
+                    .unwrap_or(json!({}));
+                ToolCall {
+                    id: tc.id.clone(),
+                    name: wire_names
+                        .get(&tc.function.name)
+                        .cloned()
+                        .unwrap_or_else(|| tc.function.name.clone()),
+                    arguments: args,
+                }
             })
             .collect();
+        Ok(ProviderResponse::ToolCalls(calls))
+    } else {
+        // Regular text response
+        let content = choice
+            .message
+            .content
+            .clone()
+            .ok_or("No content or tool calls in OpenAI response")?;
+        Ok(ProviderResponse::Text(content))
+    }
+}
 
-        // Build the request
-        let mut request_builder = CreateChatCompletionRequestArgs::default();
-        request_builder
-            .model(&self.config.model)
-            .messages(openai_messages);
-
-        // Add tools if any
-        if !openai_tools.is_empty() {
-            request_builder.tools(openai_tools);
+impl OpenAIProvider {
+    /// Send a chat completion request with `config`
+    async fn send(
+        &self,
+        config: &ProviderConfig,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+    ) -> ProviderResult<(ProviderResponse, Option<ProviderUsage>)> {
+        let (request, wire_names) = build_request(config, messages, tools)?;
+        let mut builder = self
+            .http
+            .post(format!("{}/chat/completions", self.api_base))
+            .bearer_auth(config.api_key.as_deref().unwrap_or_default());
+        for (name, value) in &config.headers {
+            builder = builder.header(name, value);
         }
+        let (headers, body) = self.call(builder.json(&request)).await?;
+        let response: CreateChatCompletionResponse = serde_json::from_str(&body)
+            .map_err(|e| format!("Unexpected OpenAI response: {}", e))?;
+        let usage = response.usage.as_ref().map(|usage| ProviderUsage {
+            prompt_tokens: usage.prompt_tokens as usize,
+            completion_tokens: usage.completion_tokens as usize,
+            reasoning_tokens: usage
+                .completion_tokens_details
+                .as_ref()
+                .and_then(|details| details.reasoning_tokens)
+                .unwrap_or(0) as usize,
+            rate_limits: RateLimits::from_headers(&headers),
+        });
+        Ok((parse_response(&response, &wire_names)?, usage))
+    }
 
-        if let Some(temp) = self.config.temperature {
-            request_builder.temperature(temp);
+    /// A request to `path` under the API base, with the API key
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.http
+            .request(method, format!("{}/{}", self.api_base, path))
+            .bearer_auth(self.config.api_key.as_deref().unwrap_or_default())
+    }
+
+    /// Send `request`, returning the headers and body of a successful
+    /// response or an error with its status and request id
+    async fn call(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> ProviderResult<(reqwest::header::HeaderMap, String)> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(PROVIDER, &e))?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(PROVIDER, &e))?;
+        if !status.is_success() {
+            return Err(api_error(status, &headers, &body).into());
         }
+        Ok((headers, body))
+    }
 
-        if let Some(max_tokens) = self.config.max_tokens {
-            request_builder.max_tokens(max_tokens as u32);
+    /// Fetch a batch job's current status
+    async fn retrieve_batch(&self, id: &str) -> ProviderResult<Batch> {
+        let (_, body) = self
+            .call(self.request(reqwest::Method::GET, &format!("batches/{}", id)))
+            .await?;
+        Ok(serde_json::from_str(&body).map_err(|e| format!("Unexpected batch: {}", e))?)
+    }
+
+    /// Check that `options` is for this provider and apply them
+    fn resolve(&self, options: &RequestOptions) -> ProviderResult<ProviderConfig> {
+        match options.provider.filter(|p| *p != self.config.provider) {
+            Some(other) => {
+                Err(format!("OpenAI provider cannot serve a request for {:?}", other).into())
+            }
+            None => Ok(self.config.resolve(options)),
         }
+    }
+}
+
+/// One line of a batch output or error file
+#[derive(Deserialize)]
+struct BatchOutputLine {
+    custom_id: String,
+    response: Option<BatchOutputResponse>,
+    error: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct BatchOutputResponse {
+    status_code: u16,
+    request_id: Option<String>,
+    body: Value,
+}
+
+/// The kind of an API error from its `type` and `code`, if they say
+fn api_error_kind(kind: &str, code: &str) -> Option<ProviderErrorKind> {
+    Some(match (kind, code) {
+        ("insufficient_quota", _) | (_, "insufficient_quota") => ProviderErrorKind::QuotaExceeded,
+        (_, "rate_limit_exceeded") | ("requests" | "tokens", _) => ProviderErrorKind::RateLimited,
+        (_, "invalid_api_key") | ("authentication_error", _) => ProviderErrorKind::Authentication,
+        ("invalid_request_error", _) => ProviderErrorKind::InvalidRequest,
+        ("server_error", _) => ProviderErrorKind::Server,
+        _ => return None,
+    })
+}
+
+/// Error for a failed API call, with its status and request id
+///
+/// The body's error type and code refine the kind the status gives, e.g. a
+/// 429 for an exhausted quota is [`ProviderErrorKind::QuotaExceeded`].
+fn api_error(
+    status: reqwest::StatusCode,
+    headers: &reqwest::header::HeaderMap,
+    body: &str,
+) -> ProviderError {
+    let json: Value = serde_json::from_str(body).unwrap_or_default();
+    let error = &json["error"];
+    let message = error["message"]
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| body.to_string());
+    let mut provider_error = ProviderError::from_http(PROVIDER, status, headers, message);
+    if let Some(kind) = api_error_kind(
+        error["type"].as_str().unwrap_or_default(),
+        error["code"].as_str().unwrap_or_default(),
+    ) {
+        provider_error.kind = kind;
+    }
+    provider_error
+}
 
-        let request = request_builder.build()?;
+/// Position of a request in its batch, from its `custom_id`
+fn batch_index(custom_id: &str) -> Option<usize> {
+    custom_id.strip_prefix("request-")?.parse().ok()
+}
 
-        // Make the API call
-        let response = self.client.chat().create(request).await?;
+fn error_message(error: &Value) -> String {
+    error
+        .get("message")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .unwrap_or_else(|| error.to_string())
+}
 
-        // Extract the response
-        let choice = response
-            .choices
-            .first()
-            .ok_or("No choices in OpenAI response")?;
+/// Result of one batch request from its output line
+fn parse_batch_line(
+    line: BatchOutputLine,
+    wire_names: &HashMap<String, String>,
+) -> ProviderResult<ProviderResponse> {
+    if let Some(error) = line.error.filter(|e| !e.is_null()) {
+        return Err(
+            ProviderError::new(PROVIDER, ProviderErrorKind::Other, error_message(&error)).into(),
+        );
+    }
+    let response = line
+        .response
+        .ok_or("Batch result has neither response nor error")?;
+    if response.status_code != 200 {
+        let error = response.body.get("error").unwrap_or(&response.body);
+        let mut provider_error =
+            ProviderError::from_status(PROVIDER, response.status_code, error_message(error));
+        if let Some(id) = response.request_id {
+            provider_error = provider_error.request_id(id);
+        }
+        return Err(provider_error.into());
+    }
+    let completion: CreateChatCompletionResponse = serde_json::from_value(response.body)
+        .map_err(|e| format!("Unexpected batch response: {}", e))?;
+    parse_response(&completion, wire_names)
+}
+
+fn batch_state(batch: &Batch) -> BatchState {
+    use async_openai::types::BatchStatus;
 
-        // Check if the response contains tool calls
-        if let Some(tool_calls) = &choice.message.tool_calls {
-            let calls: Vec<ToolCall> = tool_calls
+    match batch.status {
+        BatchStatus::Validating | BatchStatus::InProgress | BatchStatus::Finalizing => {
+            let counts = batch.request_counts.as_ref();
+            BatchState::Pending {
+                completed: counts.map_or(0, |c| c.completed as usize),
+                failed: counts.map_or(0, |c| c.failed as usize),
+            }
+        }
+        BatchStatus::Completed => BatchState::Completed,
+        BatchStatus::Failed => BatchState::Failed(
+            batch
+                .errors
                 .iter()
-                .map(|tc| {
-                    let args = tc
-                        .function
-                        .arguments
-                        .parse::<serde_json::Value>()
-                        .unwrap_or(json!({}));
-                    ToolCall {
-                        id: tc.id.clone(),
-                        name: tc.function.name.clone(),
-                        arguments: args,
-                    }
-                })
-                .collect();
-            Ok(ProviderResponse::ToolCalls(calls))
-        } else {
-            // Regular text response
-            let content = choice
-                .message
-                .content
-                .clone()
-                .ok_or("No content or tool calls in OpenAI response")?;
-            Ok(ProviderResponse::Text(content))
+                .flat_map(|errors| errors.data.iter())
+                .map(|error| error.message.as_str())
+                .collect::<Vec<_>>()
+                .join("; "),
+        ),
+        BatchStatus::Expired => BatchState::Expired,
+        BatchStatus::Cancelling | BatchStatus::Cancelled => BatchState::Cancelled,
+    }
+}
+
+/// OpenAI Batch API: requests run within 24 hours at a lower price
+impl OpenAIProvider {
+    /// Upload `requests` as a batch job
+    pub async fn submit_batch(&self, requests: Vec<BatchRequest>) -> ProviderResult<BatchJob> {
+        use async_openai::types::{
+            BatchCompletionWindow, BatchEndpoint, BatchRequest as CreateBatchRequest, OpenAIFile,
+        };
+
+        if requests.is_empty() {
+            return Err("Cannot submit an empty batch".into());
+        }
+        let size = requests.len();
+        let mut tool_names = HashMap::new();
+        let mut input = String::new();
+        for (i, request) in requests.into_iter().enumerate() {
+            let (body, wire_names) = build_request(&self.config, request.messages, request.tools)?;
+            tool_names.extend(wire_names);
+            let line = json!({
+                "custom_id": format!("request-{}", i),
+                "method": "POST",
+                "url": "/v1/chat/completions",
+                "body": body,
+            });
+            input.push_str(&line.to_string());
+            input.push('\n');
+        }
+
+        let (content_type, form) = multipart_body(
+            &[("purpose", "batch")],
+            "batch.jsonl",
+            "application/jsonl",
+            input.as_bytes(),
+        );
+        let (_, body) = self
+            .call(
+                self.request(reqwest::Method::POST, "files")
+                    .header(reqwest::header::CONTENT_TYPE, content_type)
+                    .body(form),
+            )
+            .await?;
+        let file: OpenAIFile =
+            serde_json::from_str(&body).map_err(|e| format!("Unexpected file: {}", e))?;
+        let (_, body) = self
+            .call(
+                self.request(reqwest::Method::POST, "batches")
+                    .json(&CreateBatchRequest {
+                        input_file_id: file.id,
+                        endpoint: BatchEndpoint::V1ChatCompletions,
+                        completion_window: BatchCompletionWindow::W24H,
+                        metadata: None,
+                    }),
+            )
+            .await?;
+        let batch: Batch =
+            serde_json::from_str(&body).map_err(|e| format!("Unexpected batch: {}", e))?;
+        tracing::info!("Submitted OpenAI batch {} ({} requests)", batch.id, size);
+        Ok(BatchJob {
+            id: batch.id,
+            size,
+            tool_names,
+        })
+    }
+
+    pub async fn batch_state(&self, job: &BatchJob) -> ProviderResult<BatchState> {
+        Ok(batch_state(&self.retrieve_batch(&job.id).await?))
+    }
+
+    /// Wait for `job` to finish, checking every `poll`, and return its
+    /// results in request order
+    pub async fn batch_results(
+        &self,
+        job: &BatchJob,
+        poll: Duration,
+    ) -> ProviderResult<Vec<ProviderResult<ProviderResponse>>> {
+        let batch = loop {
+            let batch = self.retrieve_batch(&job.id).await?;
+            match batch_state(&batch) {
+                BatchState::Pending { .. } => tokio::time::sleep(poll).await,
+                BatchState::Completed => break batch,
+                state => {
+                    return Err(format!("Batch {} did not complete: {:?}", job.id, state).into())
+                }
+            }
+        };
+
+        let mut results: Vec<Option<ProviderResult<ProviderResponse>>> =
+            (0..job.size).map(|_| None).collect();
+        for file_id in [&batch.output_file_id, &batch.error_file_id]
+            .into_iter()
+            .flatten()
+        {
+            let (_, content) = self
+                .call(self.request(reqwest::Method::GET, &format!("files/{}/content", file_id)))
+                .await?;
+            for line in content.lines() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let line: BatchOutputLine = serde_json::from_str(line)
+                    .map_err(|e| format!("Unexpected batch result line: {}", e))?;
+                if let Some(slot) = batch_index(&line.custom_id).and_then(|i| results.get_mut(i)) {
+                    *slot = Some(parse_batch_line(line, &job.tool_names));
+                }
+            }
         }
+        Ok(results
+            .into_iter()
+            .enumerate()
+            .map(|(i, result)| {
+                result.unwrap_or_else(|| Err(format!("No result for batch request {}", i).into()))
+            })
+            .collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl LLMProvider for OpenAIProvider {
+    async fn complete(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+    ) -> ProviderResult<ProviderResponse> {
+        let (response, _) = self.send(&self.config, messages, tools).await?;
+        Ok(response)
+    }
+
+    async fn complete_with_options(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<ProviderResponse> {
+        let (response, _) = self.send(&self.resolve(options)?, messages, tools).await?;
+        Ok(response)
+    }
+
+    async fn complete_with_usage(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<(ProviderResponse, Option<ProviderUsage>)> {
+        self.send(&self.resolve(options)?, messages, tools).await
+    }
+
+    fn config(&self) -> Option<&ProviderConfig> {
+        Some(&self.config)
+    }
+
+    fn with_model(&self, model: &str) -> Option<Box<dyn LLMProvider>> {
+        Some(Box::new(Self {
+            http: self.http.clone(),
+            api_base: self.api_base.clone(),
+            config: self.config.clone().model(model),
+            embedding_model: self.embedding_model.clone(),
+        }))
     }
 }
 
@@ -224,4 +772,310 @@ mod tests {
             "Should fail with empty messages or return error"
         );
     }
+
+    /// Tool options and strict schemas reach the request body
+    #[test]
+    fn test_tool_options_in_request() {
+        let config = ProviderConfig::new(Provider::OpenAI)
+            .tool_choice(ToolChoice::Tool("fs.read".to_string()))
+            .parallel_tool_calls(false)
+            .strict_tools(true);
+        let tools = vec![ToolDefinition {
+            name: "fs.read".to_string(),
+            description: "Read a file".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {"type": "string"},
+                    "lines": {"type": "integer"}
+                },
+                "required": ["path"]
+            }),
+        }];
+
+        let (request, _) = build_request(&config, vec![Message::user("hi")], tools).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            body["tool_choice"],
+            json!({"type": "function", "function": {"name": "fs__read"}})
+        );
+        assert_eq!(body["parallel_tool_calls"], json!(false));
+        let function = &body["tools"][0]["function"];
+        assert_eq!(function["strict"], json!(true));
+        assert_eq!(
+            function["parameters"],
+            json!({
+                "type": "object",
+                "properties": {
+                    "lines": {"type": ["integer", "null"]},
+                    "path": {"type": "string"}
+                },
+                "required": ["lines", "path"],
+                "additionalProperties": false
+            })
+        );
+
+        // Tool options are left out when no tools are offered
+        let (request, _) = build_request(&config, vec![Message::user("hi")], Vec::new()).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+        assert!(body.get("tool_choice").is_none());
+        assert!(body.get("parallel_tool_calls").is_none());
+    }
+
+    /// Reasoning models get a reasoning effort, no temperature, and the
+    /// system prompt as a developer message
+    #[test]
+    fn test_reasoning_model_request() {
+        let config = ProviderConfig::new(Provider::OpenAI)
+            .model("o3-mini")
+            .reasoning_effort(ReasoningEffort::High);
+        let messages = vec![Message::system("Be brief."), Message::user("hi")];
+        let (request, _) = build_request(&config, messages.clone(), Vec::new()).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            body["messages"],
+            json!([
+                {"role": "developer", "content": "Be brief."},
+                {"role": "user", "content": "hi"}
+            ])
+        );
+        assert_eq!(body["reasoning_effort"], json!("high"));
+        assert_eq!(body["max_completion_tokens"], json!(1000));
+        assert!(body.get("temperature").is_none());
+        assert!(body.get("max_tokens").is_none());
+
+        let config = config.model("gpt-4o-mini");
+        let (request, _) = build_request(&config, messages, Vec::new()).unwrap();
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(body["messages"][0]["role"], json!("system"));
+        assert!(body.get("reasoning_effort").is_none());
+        assert_eq!(body["max_tokens"], json!(1000));
+        assert!(body.get("temperature").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_batch_api() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/files")
+            .match_body(mockito::Matcher::Regex(r#""custom_id":"request-1""#.into()))
+            .with_body(
+                r#"{"id": "file-in", "object": "file", "bytes": 10, "created_at": 1,
+                    "filename": "batch.jsonl", "purpose": "batch"}"#,
+            )
+            .create_async()
+            .await;
+        let batch = |status: &str| {
+            format!(
+                r#"{{"id": "batch_1", "object": "batch", "endpoint": "/v1/chat/completions",
+                    "input_file_id": "file-in", "completion_window": "24h",
+                    "status": "{}", "output_file_id": "file-out", "created_at": 1,
+                    "request_counts": {{"total": 2, "completed": 1, "failed": 1}}}}"#,
+                status
+            )
+        };
+        server
+            .mock("POST", "/v1/batches")
+            .match_body(mockito::Matcher::PartialJson(
+                json!({"input_file_id": "file-in", "endpoint": "/v1/chat/completions"}),
+            ))
+            .with_body(batch("validating"))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/v1/batches/batch_1")
+            .with_body(batch("completed"))
+            .create_async()
+            .await;
+        let output = [
+            json!({"custom_id": "request-1", "response": {"status_code": 429, "request_id": "req_1",
+                "body": {"error": {"message": "Rate limit reached"}}}, "error": null}),
+            json!({"custom_id": "request-0", "response": {"status_code": 200, "body": {
+                "id": "c1", "object": "chat.completion", "created": 1, "model": "gpt-4o-mini",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                    "message": {"role": "assistant", "content": "positive"}}]}}}),
+        ]
+        .map(|line| line.to_string())
+        .join("\n");
+        server
+            .mock("GET", "/v1/files/file-out/content")
+            .with_body(output)
+            .create_async()
+            .await;
+
+        let mut config = ProviderConfig::new(Provider::OpenAI).model("gpt-4o-mini");
+        config.api_key = Some("sk-test".to_string());
+        let provider = OpenAIProvider::new(config)
+            .unwrap()
+            .api_base(format!("{}/v1", server.url()));
+        let requests = ["great!", "awful"]
+            .iter()
+            .map(|text| BatchRequest::new(vec![Message::user(*text)]))
+            .collect();
+
+        let job = provider.submit_batch(requests).await.unwrap();
+        assert_eq!((job.id.as_str(), job.size), ("batch_1", 2));
+        assert_eq!(
+            provider.batch_state(&job).await.unwrap(),
+            BatchState::Completed
+        );
+        let results = provider
+            .batch_results(&job, Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert!(matches!(&results[0], Ok(ProviderResponse::Text(t)) if t == "positive"));
+        let err = results[1].as_ref().unwrap_err();
+        let err = ProviderError::find(err.as_ref()).unwrap();
+        assert_eq!(err.kind, ProviderErrorKind::RateLimited);
+        assert_eq!(err.message, "Rate limit reached");
+        assert_eq!(err.request_id.as_deref(), Some("req_1"));
+    }
+
+    #[tokio::test]
+    async fn test_reports_usage_with_reasoning_tokens() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/chat/completions")
+            .with_body(
+                json!({
+                    "id": "c1", "object": "chat.completion", "created": 1, "model": "o3-mini",
+                    "choices": [{"index": 0, "finish_reason": "stop",
+                        "message": {"role": "assistant", "content": "4"}}],
+                    "usage": {"prompt_tokens": 12, "completion_tokens": 140, "total_tokens": 152,
+                        "completion_tokens_details": {"reasoning_tokens": 128}}
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let mut config = ProviderConfig::new(Provider::OpenAI).model("o3-mini");
+        config.api_key = Some("sk-test".to_string());
+        let provider = OpenAIProvider::new(config)
+            .unwrap()
+            .api_base(format!("{}/v1", server.url()));
+        let (response, usage) = provider
+            .complete_with_usage(
+                vec![Message::user("2 + 2?")],
+                Vec::new(),
+                &RequestOptions::new(),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(response, ProviderResponse::Text(t) if t == "4"));
+        assert_eq!(
+            usage,
+            Some(ProviderUsage {
+                prompt_tokens: 12,
+                completion_tokens: 140,
+                reasoning_tokens: 128,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reports_rate_limit_headers() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/chat/completions")
+            .with_header("x-ratelimit-limit-requests", "500")
+            .with_header("x-ratelimit-remaining-requests", "499")
+            .with_header("x-ratelimit-remaining-tokens", "29000")
+            .with_body(
+                json!({
+                    "id": "c1", "object": "chat.completion", "created": 1, "model": "gpt-4o-mini",
+                    "choices": [{"index": 0, "finish_reason": "stop",
+                        "message": {"role": "assistant", "content": "hi"}}],
+                    "usage": {"prompt_tokens": 5, "completion_tokens": 1, "total_tokens": 6}
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let mut config = ProviderConfig::new(Provider::OpenAI).model("gpt-4o-mini");
+        config.api_key = Some("sk-test".to_string());
+        let provider = OpenAIProvider::new(config)
+            .unwrap()
+            .api_base(format!("{}/v1", server.url()));
+        let (_, usage) = provider
+            .complete_with_usage(
+                vec![Message::user("hi")],
+                Vec::new(),
+                &RequestOptions::new(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            usage.unwrap().rate_limits,
+            Some(RateLimits {
+                limit_requests: Some(500),
+                remaining_requests: Some(499),
+                remaining_tokens: Some(29000),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chat_errors_carry_status_and_request_id() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/chat/completions")
+            .match_header("authorization", "Bearer sk-test")
+            .with_status(429)
+            .with_header("x-request-id", "req_42")
+            .with_header("retry-after", "7")
+            .with_body(
+                json!({"error": {"message": "You exceeded your current quota",
+                    "type": "insufficient_quota", "code": "insufficient_quota"}})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let mut config = ProviderConfig::new(Provider::OpenAI).model("gpt-4o-mini");
+        config.api_key = Some("sk-test".to_string());
+        let provider = OpenAIProvider::new(config)
+            .unwrap()
+            .api_base(format!("{}/v1", server.url()));
+        let err = provider
+            .complete(vec![Message::user("hi")], Vec::new())
+            .await
+            .unwrap_err();
+        let err = ProviderError::find(err.as_ref()).unwrap();
+        assert_eq!(err.kind, ProviderErrorKind::QuotaExceeded);
+        assert_eq!(err.status, Some(429));
+        assert_eq!(err.request_id.as_deref(), Some("req_42"));
+        assert_eq!(err.retry_after, Some(Duration::from_secs(7)));
+        assert_eq!(err.message, "You exceeded your current quota");
+    }
+
+    #[tokio::test]
+    async fn test_embedding_errors_carry_status_and_request_id() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/embeddings")
+            .match_header("authorization", "Bearer sk-test")
+            .with_status(503)
+            .with_header("x-request-id", "req_7")
+            .with_body(
+                json!({"error": {"message": "Overloaded", "type": "server_error"}}).to_string(),
+            )
+            .create_async()
+            .await;
+
+        let mut config = ProviderConfig::new(Provider::OpenAI);
+        config.api_key = Some("sk-test".to_string());
+        let provider = OpenAIProvider::new(config)
+            .unwrap()
+            .api_base(format!("{}/v1", server.url()));
+        let err = provider.embed(vec!["hi".to_string()]).await.unwrap_err();
+        let err = ProviderError::find(err.as_ref()).unwrap();
+        assert_eq!(err.kind, ProviderErrorKind::Server);
+        assert_eq!(err.status, Some(503));
+        assert_eq!(err.request_id.as_deref(), Some("req_7"));
+        assert_eq!(err.message, "Overloaded");
+    }
 }
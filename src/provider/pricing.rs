@@ -0,0 +1,319 @@
+//! Model pricing catalog
+//!
+//! Per-token prices change often enough that hardcoding them next to each
+//! provider goes stale. The [`PricingCatalog`] starts from a bundled baseline,
+//! accepts overrides from configuration, and can be refreshed at runtime from
+//! OpenRouter's models endpoint or a JSON feed via [`PricingUpdater`].
+//!
+//! Prices are USD per 1K tokens. Lookups check overrides first, then fetched
+//! and baseline prices, then fall back to a provider-prefixed id
+//! (`openai/gpt-4o` matches `gpt-4o`).
+//!
+//! # Example
+//! ```ignore
+//! use patinox::provider::{PricingCatalog, PricingUpdater};
+//!
+//! let mut catalog = PricingCatalog::baseline().load_overrides(&config_json)?;
+//! catalog.refresh(&PricingUpdater::new().openrouter()).await?;
+//!
+//! let cost = catalog.estimate_cost("gpt-4o-mini", 1_200, 300);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// OpenRouter's public model listing
+pub const OPENROUTER_MODELS_URL: &str = "https://openrouter.ai/api/v1/models";
+
+/// Price of a model in USD per 1K tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_1k: f64,
+    pub output_per_1k: f64,
+}
+
+impl ModelPrice {
+    pub const fn new(input_per_1k: f64, output_per_1k: f64) -> Self {
+        Self {
+            input_per_1k,
+            output_per_1k,
+        }
+    }
+
+    /// Cost of a call with the given token counts
+    pub fn cost(&self, input_tokens: usize, output_tokens: usize) -> f64 {
+        (input_tokens as f64 / 1000.0) * self.input_per_1k
+            + (output_tokens as f64 / 1000.0) * self.output_per_1k
+    }
+}
+
+/// Bundled baseline prices, last reviewed against provider pricing pages
+const BASELINE: &[(&str, ModelPrice)] = &[
+    ("gpt-4o-mini", ModelPrice::new(0.00015, 0.0006)),
+    ("gpt-4o", ModelPrice::new(0.0025, 0.01)),
+    ("gpt-4.1", ModelPrice::new(0.002, 0.008)),
+    ("gpt-4.1-mini", ModelPrice::new(0.0004, 0.0016)),
+    ("gpt-4.1-nano", ModelPrice::new(0.0001, 0.0004)),
+    ("o1", ModelPrice::new(0.015, 0.06)),
+    ("o3-mini", ModelPrice::new(0.0011, 0.0044)),
+    ("claude-3-haiku-20240307", ModelPrice::new(0.00025, 0.00125)),
+    ("claude-3-5-haiku-20241022", ModelPrice::new(0.0008, 0.004)),
+    ("claude-3-5-sonnet-20241022", ModelPrice::new(0.003, 0.015)),
+    ("claude-3-opus-20240229", ModelPrice::new(0.015, 0.075)),
+];
+
+/// Lookup table of model prices
+#[derive(Debug, Clone, Default)]
+pub struct PricingCatalog {
+    prices: HashMap<String, ModelPrice>,
+    overrides: HashMap<String, ModelPrice>,
+}
+
+impl PricingCatalog {
+    /// An empty catalog
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A catalog seeded with the bundled baseline prices
+    pub fn baseline() -> Self {
+        Self {
+            prices: BASELINE
+                .iter()
+                .map(|(model, price)| (model.to_string(), *price))
+                .collect(),
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Pin a model's price; overrides survive refreshes
+    pub fn with_override(mut self, model: impl Into<String>, price: ModelPrice) -> Self {
+        self.overrides.insert(model.into(), price);
+        self
+    }
+
+    /// Load overrides from a JSON object of `{"model": {"input_per_1k": .., "output_per_1k": ..}}`
+    pub fn load_overrides(mut self, json: &str) -> Result<Self, serde_json::Error> {
+        self.overrides.extend(parse_feed(json)?);
+        Ok(self)
+    }
+
+    /// Merge fetched prices into the catalog, returning how many were applied
+    pub fn merge(&mut self, prices: HashMap<String, ModelPrice>) -> usize {
+        let count = prices.len();
+        self.prices.extend(prices);
+        count
+    }
+
+    /// Refresh prices from the updater's sources
+    pub async fn refresh(&mut self, updater: &PricingUpdater) -> crate::Result<usize> {
+        let prices = updater.fetch().await?;
+        Ok(self.merge(prices))
+    }
+
+    /// Price for a model, if known
+    pub fn price(&self, model: &str) -> Option<ModelPrice> {
+        if let Some(price) = self.overrides.get(model).or_else(|| self.prices.get(model)) {
+            return Some(*price);
+        }
+
+        // Fall back to provider-prefixed ids, e.g. "openai/gpt-4o"
+        let suffix = format!("/{}", model);
+        self.overrides
+            .iter()
+            .chain(self.prices.iter())
+            .filter(|(id, _)| id.ends_with(&suffix))
+            .min_by(|a, b| a.0.cmp(b.0))
+            .map(|(_, price)| *price)
+    }
+
+    /// Estimated USD cost of a call, if the model's price is known
+    pub fn estimate_cost(
+        &self,
+        model: &str,
+        input_tokens: usize,
+        output_tokens: usize,
+    ) -> Option<f64> {
+        self.price(model)
+            .map(|price| price.cost(input_tokens, output_tokens))
+    }
+}
+
+/// Parse a pricing feed in the same format as overrides
+pub fn parse_feed(json: &str) -> Result<HashMap<String, ModelPrice>, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+/// Parse OpenRouter's `/models` response (prices are per token, as strings)
+pub fn parse_openrouter(json: &str) -> Result<HashMap<String, ModelPrice>, serde_json::Error> {
+    let body: Value = serde_json::from_str(json)?;
+    let per_1k = |value: Option<&Value>| -> Option<f64> {
+        let per_token = match value? {
+            Value::String(s) => s.parse::<f64>().ok()?,
+            Value::Number(n) => n.as_f64()?,
+            _ => return None,
+        };
+        // Negative prices mark variable-priced routers
+        (per_token >= 0.0).then_some(per_token * 1000.0)
+    };
+
+    Ok(body
+        .get("data")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|model| {
+            let id = model.get("id")?.as_str()?;
+            let pricing = model.get("pricing")?;
+            let price = ModelPrice::new(
+                per_1k(pricing.get("prompt"))?,
+                per_1k(pricing.get("completion"))?,
+            );
+            Some((id.to_string(), price))
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone)]
+enum PriceSource {
+    OpenRouter(String),
+    Feed(String),
+}
+
+/// Fetches current prices from remote sources
+#[derive(Debug, Clone, Default)]
+pub struct PricingUpdater {
+    sources: Vec<PriceSource>,
+    client: reqwest::Client,
+}
+
+impl PricingUpdater {
+    /// An updater with no sources
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fetch from OpenRouter's public models endpoint
+    pub fn openrouter(self) -> Self {
+        self.openrouter_url(OPENROUTER_MODELS_URL)
+    }
+
+    /// Fetch from an OpenRouter-compatible models endpoint
+    pub fn openrouter_url(mut self, url: impl Into<String>) -> Self {
+        self.sources.push(PriceSource::OpenRouter(url.into()));
+        self
+    }
+
+    /// Fetch from a JSON feed in the override format
+    pub fn feed(mut self, url: impl Into<String>) -> Self {
+        self.sources.push(PriceSource::Feed(url.into()));
+        self
+    }
+
+    /// Fetch from every source; later sources win on conflicts
+    ///
+    /// A failing source is logged and skipped. Fails only if every source
+    /// failed.
+    pub async fn fetch(&self) -> crate::Result<HashMap<String, ModelPrice>> {
+        let mut prices = HashMap::new();
+        let mut last_error = None;
+
+        for source in &self.sources {
+            match self.fetch_source(source).await {
+                Ok(fetched) => prices.extend(fetched),
+                Err(e) => {
+                    tracing::warn!("Pricing source {:?} failed: {}", source, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        match last_error {
+            Some(e) if prices.is_empty() => Err(e),
+            _ => Ok(prices),
+        }
+    }
+
+    async fn fetch_source(
+        &self,
+        source: &PriceSource,
+    ) -> crate::Result<HashMap<String, ModelPrice>> {
+        let (PriceSource::OpenRouter(url) | PriceSource::Feed(url)) = source;
+        let body = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        Ok(match source {
+            PriceSource::OpenRouter(_) => parse_openrouter(&body)?,
+            PriceSource::Feed(_) => parse_feed(&body)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_baseline_and_overrides() {
+        let catalog = PricingCatalog::baseline();
+        let cost = catalog.estimate_cost("gpt-4o-mini", 1000, 1000).unwrap();
+        assert!((cost - 0.00075).abs() < 1e-12);
+        assert!(catalog.price("no-such-model").is_none());
+
+        let catalog = catalog
+            .load_overrides(r#"{"gpt-4o-mini": {"input_per_1k": 1.0, "output_per_1k": 2.0}}"#)
+            .unwrap();
+        assert_eq!(catalog.estimate_cost("gpt-4o-mini", 1000, 500), Some(2.0));
+    }
+
+    #[test]
+    fn test_parse_openrouter_and_prefixed_lookup() {
+        let json = r#"{"data": [
+            {"id": "openai/gpt-5", "pricing": {"prompt": "0.00000125", "completion": "0.00001"}},
+            {"id": "openrouter/auto", "pricing": {"prompt": "-1", "completion": "-1"}},
+            {"id": "broken"}
+        ]}"#;
+        let prices = parse_openrouter(json).unwrap();
+        assert_eq!(prices.len(), 1);
+
+        let mut catalog = PricingCatalog::new();
+        catalog.merge(prices);
+        let price = catalog.price("gpt-5").unwrap();
+        assert!((price.input_per_1k - 0.00125).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_skips_failing_source() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/feed.json")
+            .with_body(r#"{"local-model": {"input_per_1k": 0.0, "output_per_1k": 0.0}}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/models")
+            .with_status(500)
+            .create_async()
+            .await;
+
+        let updater = PricingUpdater::new()
+            .openrouter_url(format!("{}/models", server.url()))
+            .feed(format!("{}/feed.json", server.url()));
+
+        let mut catalog =
+            PricingCatalog::baseline().with_override("local-model", ModelPrice::new(0.5, 0.5));
+        assert_eq!(catalog.refresh(&updater).await.unwrap(), 1);
+        // Overrides still win over fetched prices
+        assert_eq!(
+            catalog.price("local-model"),
+            Some(ModelPrice::new(0.5, 0.5))
+        );
+    }
+}
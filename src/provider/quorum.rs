@@ -0,0 +1,413 @@
+//! Quorum provider - ask several models, return the consensus answer
+//!
+//! Sends the same request to every member provider concurrently, groups the
+//! answers that agree, and returns an answer only when enough members back
+//! it. Useful for high-stakes extraction where one model's answer is not
+//! reliable enough on its own.
+//!
+//! Agreement is decided by exact match (after normalizing case and
+//! whitespace), by the cosine similarity of answer embeddings, or by a
+//! judge model comparing answers pairwise.
+//!
+//! Request options reach every member. The usage members and the judge
+//! report is summed, so a quorum is metered for every completion it asks
+//! for. Embedding calls are not counted, since embedding providers report
+//! no usage.
+
+use super::{
+    EmbeddingProvider, LLMProvider, Message, ProviderConfig, ProviderResponse, ProviderResult,
+    ProviderUsage, RequestOptions, ToolDefinition,
+};
+use crate::error::AggregateError;
+use crate::retrieval::cosine_similarity;
+use futures::future::join_all;
+use std::fmt;
+
+/// How member answers are compared
+pub enum Agreement {
+    /// Answers agree when identical after normalizing case and whitespace
+    Exact,
+    /// Answers agree when their embeddings' cosine similarity is at least
+    /// `threshold`
+    Embedding {
+        provider: Box<dyn EmbeddingProvider>,
+        threshold: f32,
+    },
+    /// A judge model decides whether two answers agree
+    Judge(Box<dyn LLMProvider>),
+}
+
+/// A member's answer, with its embedding under [`Agreement::Embedding`]
+struct Answer {
+    member: String,
+    response: ProviderResponse,
+    embedding: Option<Vec<f32>>,
+}
+
+/// Returned when no answer reaches the required number of votes
+#[derive(Debug)]
+pub struct NoConsensus {
+    /// Votes needed for consensus
+    pub required: usize,
+    /// Each distinct answer with the members that gave it
+    pub answers: Vec<(String, Vec<String>)>,
+}
+
+impl fmt::Display for NoConsensus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No consensus (needed {} votes)", self.required)?;
+        for (answer, members) in &self.answers {
+            write!(f, "\n  - [{}]: {}", members.join(", "), answer)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for NoConsensus {}
+
+/// Provider that returns the answer most members agree on
+pub struct QuorumProvider {
+    members: Vec<(String, Box<dyn LLMProvider>)>,
+    agreement: Agreement,
+    min_votes: Option<usize>,
+}
+
+impl Default for QuorumProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QuorumProvider {
+    /// Create an empty quorum using exact-match agreement
+    pub fn new() -> Self {
+        Self {
+            members: Vec::new(),
+            agreement: Agreement::Exact,
+            min_votes: None,
+        }
+    }
+
+    /// Add a named member provider
+    pub fn member(mut self, name: impl Into<String>, provider: Box<dyn LLMProvider>) -> Self {
+        self.members.push((name.into(), provider));
+        self
+    }
+
+    /// Set how answers are compared
+    pub fn agreement(mut self, agreement: Agreement) -> Self {
+        self.agreement = agreement;
+        self
+    }
+
+    /// Votes required for consensus (defaults to a strict majority)
+    pub fn min_votes(mut self, votes: usize) -> Self {
+        self.min_votes = Some(votes);
+        self
+    }
+
+    fn required_votes(&self) -> usize {
+        self.min_votes.unwrap_or(self.members.len() / 2 + 1)
+    }
+
+    /// Whether two answers agree, adding a judge's usage to `usage`
+    async fn agrees(
+        &self,
+        a: &Answer,
+        b: &Answer,
+        usage: &mut Option<ProviderUsage>,
+    ) -> ProviderResult<bool> {
+        match &self.agreement {
+            Agreement::Exact => Ok(normalize(&a.response) == normalize(&b.response)),
+            Agreement::Embedding { threshold, .. } => match (&a.embedding, &b.embedding) {
+                (Some(a), Some(b)) => Ok(cosine_similarity(a, b) >= *threshold),
+                _ => Err("Answer was not embedded".into()),
+            },
+            Agreement::Judge(judge) => {
+                let prompt = format!(
+                    "Do these two answers state the same result? Ignore wording and \
+                     formatting differences. Reply with only YES or NO.\n\n\
+                     Answer A:\n{}\n\nAnswer B:\n{}",
+                    render(&a.response),
+                    render(&b.response)
+                );
+                let (verdict, reported) = judge
+                    .complete_with_usage(
+                        vec![Message::user(prompt)],
+                        vec![],
+                        &RequestOptions::default(),
+                    )
+                    .await?;
+                if let Some(reported) = reported {
+                    add_usage(usage, reported);
+                }
+                Ok(matches!(verdict, ProviderResponse::Text(text)
+                    if text.trim().to_uppercase().starts_with("YES")))
+            }
+        }
+    }
+}
+
+/// Add `reported` to the running total
+fn add_usage(total: &mut Option<ProviderUsage>, reported: ProviderUsage) {
+    let total = total.get_or_insert_with(ProviderUsage::default);
+    total.prompt_tokens += reported.prompt_tokens;
+    total.completion_tokens += reported.completion_tokens;
+    total.reasoning_tokens += reported.reasoning_tokens;
+}
+
+/// Render a response as text for display and comparison
+fn render(response: &ProviderResponse) -> String {
+    match response {
+        ProviderResponse::Text(text) => text.clone(),
+        ProviderResponse::ToolCalls(calls) => calls
+            .iter()
+            .map(|call| format!("{}({})", call.name, call.arguments))
+            .collect::<Vec<_>>()
+            .join("; "),
+    }
+}
+
+fn normalize(response: &ProviderResponse) -> String {
+    render(response)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[async_trait::async_trait]
+impl LLMProvider for QuorumProvider {
+    async fn complete(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+    ) -> ProviderResult<ProviderResponse> {
+        self.complete_with_options(messages, tools, &RequestOptions::default())
+            .await
+    }
+
+    async fn complete_with_options(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<ProviderResponse> {
+        self.complete_with_usage(messages, tools, options)
+            .await
+            .map(|(response, _)| response)
+    }
+
+    async fn complete_with_usage(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<(ProviderResponse, Option<ProviderUsage>)> {
+        if self.members.is_empty() {
+            return Err("QuorumProvider has no members".into());
+        }
+
+        let results = join_all(self.members.iter().map(|(_, provider)| {
+            provider.complete_with_usage(messages.clone(), tools.clone(), options)
+        }))
+        .await;
+
+        let mut failures = AggregateError::new();
+        let mut answers = Vec::new();
+        let mut usage: Option<ProviderUsage> = None;
+        for ((name, _), result) in self.members.iter().zip(results) {
+            match result {
+                Ok((response, reported)) => {
+                    if let Some(reported) = reported {
+                        add_usage(&mut usage, reported);
+                    }
+                    failures.push_success(name.clone());
+                    answers.push(Answer {
+                        member: name.clone(),
+                        response,
+                        embedding: None,
+                    });
+                }
+                Err(e) => failures.push_failure(name.clone(), e),
+            }
+        }
+
+        if answers.is_empty() {
+            return Err(Box::new(failures));
+        }
+
+        if let Agreement::Embedding { provider, .. } = &self.agreement {
+            let texts = answers.iter().map(|a| render(&a.response)).collect();
+            let embeddings = provider.embed(texts).await?;
+            if embeddings.len() != answers.len() {
+                return Err("Embedding provider returned the wrong number of vectors".into());
+            }
+            for (answer, embedding) in answers.iter_mut().zip(embeddings) {
+                answer.embedding = Some(embedding);
+            }
+        }
+
+        // Greedily cluster answers: each joins the first group it agrees with
+        let mut groups: Vec<(Answer, Vec<String>)> = Vec::new();
+        for answer in answers {
+            let mut placed = false;
+            for (representative, voters) in groups.iter_mut() {
+                if self.agrees(representative, &answer, &mut usage).await? {
+                    voters.push(answer.member.clone());
+                    placed = true;
+                    break;
+                }
+            }
+            if !placed {
+                let voters = vec![answer.member.clone()];
+                groups.push((answer, voters));
+            }
+        }
+
+        let required = self.required_votes();
+        // Ties go to the earliest group, i.e. the earliest-registered member
+        let best = groups
+            .iter()
+            .enumerate()
+            .max_by(|(ia, a), (ib, b)| a.1.len().cmp(&b.1.len()).then(ib.cmp(ia)))
+            .map(|(index, _)| index)
+            .expect("at least one response");
+
+        if groups[best].1.len() >= required {
+            return Ok((groups.swap_remove(best).0.response, usage));
+        }
+
+        Err(Box::new(NoConsensus {
+            required,
+            answers: groups
+                .iter()
+                .map(|(answer, voters)| (render(&answer.response), voters.clone()))
+                .collect(),
+        }))
+    }
+
+    /// The first member's configuration
+    fn config(&self) -> Option<&ProviderConfig> {
+        self.members
+            .first()
+            .and_then(|(_, provider)| provider.config())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+
+    struct FailingProvider;
+
+    #[async_trait::async_trait]
+    impl LLMProvider for FailingProvider {
+        async fn complete(
+            &self,
+            _messages: Vec<Message>,
+            _tools: Vec<ToolDefinition>,
+        ) -> ProviderResult<ProviderResponse> {
+            Err("provider down".into())
+        }
+    }
+
+    fn ask() -> Vec<Message> {
+        vec![Message::user("What is the invoice total?")]
+    }
+
+    #[tokio::test]
+    async fn test_majority_wins_with_normalization() {
+        let quorum = QuorumProvider::new()
+            .member("a", Box::new(MockProvider::new("$42.00")))
+            .member("b", Box::new(MockProvider::new("  $42.00 ")))
+            .member("c", Box::new(MockProvider::new("$24.00")));
+
+        match quorum.complete(ask(), vec![]).await.unwrap() {
+            ProviderResponse::Text(text) => assert_eq!(text, "$42.00"),
+            _ => panic!("Expected text"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_disagreement_is_flagged() {
+        let quorum = QuorumProvider::new()
+            .member("a", Box::new(MockProvider::new("yes")))
+            .member("b", Box::new(MockProvider::new("no")));
+
+        let err = quorum.complete(ask(), vec![]).await.unwrap_err();
+        let no_consensus = err.downcast_ref::<NoConsensus>().unwrap();
+        assert_eq!(no_consensus.required, 2);
+        assert_eq!(no_consensus.answers.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_members_count_as_abstentions() {
+        let quorum = QuorumProvider::new()
+            .member("a", Box::new(MockProvider::new("42")))
+            .member("b", Box::new(FailingProvider))
+            .min_votes(1);
+        assert!(quorum.complete(ask(), vec![]).await.is_ok());
+
+        let all_down = QuorumProvider::new().member("a", Box::new(FailingProvider));
+        let err = all_down.complete(ask(), vec![]).await.unwrap_err();
+        assert!(err.downcast_ref::<AggregateError>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_judge_agreement() {
+        let quorum = QuorumProvider::new()
+            .member("a", Box::new(MockProvider::new("forty-two")))
+            .member("b", Box::new(MockProvider::new("42")))
+            .agreement(Agreement::Judge(Box::new(MockProvider::new("YES"))));
+
+        assert!(quorum.complete(ask(), vec![]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_judge_usage_is_metered() {
+        let usage = ProviderUsage {
+            prompt_tokens: 10,
+            completion_tokens: 1,
+            ..Default::default()
+        };
+        let quorum = QuorumProvider::new()
+            .member("a", Box::new(MockProvider::new("forty-two").usage(usage)))
+            .member("b", Box::new(MockProvider::new("42").usage(usage)))
+            .agreement(Agreement::Judge(Box::new(
+                MockProvider::new("YES").usage(usage),
+            )));
+
+        let (_, total) = quorum
+            .complete_with_usage(ask(), vec![], &RequestOptions::default())
+            .await
+            .unwrap();
+        // Two members and one judge call
+        assert_eq!(total.unwrap().prompt_tokens, 30);
+    }
+
+    #[tokio::test]
+    async fn test_embedding_agreement() {
+        use crate::topics::tests::KeywordEmbedder;
+
+        let quorum = QuorumProvider::new()
+            .member("a", Box::new(MockProvider::new("The invoice was refunded")))
+            .member(
+                "b",
+                Box::new(MockProvider::new(
+                    "A refund was issued for the billing error",
+                )),
+            )
+            .member("c", Box::new(MockProvider::new("Deploy the cluster")))
+            .agreement(Agreement::Embedding {
+                provider: Box::new(KeywordEmbedder),
+                threshold: 0.9,
+            });
+
+        match quorum.complete(ask(), vec![]).await.unwrap() {
+            ProviderResponse::Text(text) => assert_eq!(text, "The invoice was refunded"),
+            _ => panic!("Expected text"),
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! API key resolution
+//!
+//! Plaintext keys in config files end up in git. A [`SecretRef`] names where
+//! a key lives instead, and [`Secrets`] resolves it through a
+//! [`SecretResolver`] per source:
+//!
+//! - `env` - an environment variable ([`EnvResolver`])
+//! - `dotenv` - a `KEY=value` file, `.env` by default ([`DotenvResolver`])
+//! - `keychain` - the OS keychain: `security` on macOS, `secret-tool`
+//!   (libsecret) elsewhere ([`KeychainResolver`])
+//! - `command` - the first line printed by a password manager CLI such as
+//!   `pass` or `op` ([`CommandResolver`])
+//!
+//! In a TOML config a reference is either a literal string or a table:
+//!
+//! ```toml
+//! api_key = { source = "keychain", key = "openai" }
+//! ```
+//!
+//! # Example
+//! ```ignore
+//! use patinox::provider::secret::{CommandResolver, SecretRef, SecretSource, Secrets};
+//!
+//! let secrets = Secrets::new()
+//!     .with(SecretSource::Command, CommandResolver::new("op", ["read", "{key}"]));
+//! let key = SecretRef::new(SecretSource::Command, "op://dev/openai/credential");
+//! let config = ProviderConfig::new(Provider::OpenAI).api_key(secrets.resolve(&key)?);
+//! ```
+
+use super::ProviderResult;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Where a secret is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretSource {
+    Env,
+    Dotenv,
+    Keychain,
+    Command,
+}
+
+/// A secret given inline or by reference to a [`SecretSource`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SecretRef {
+    Plain(String),
+    Source { source: SecretSource, key: String },
+}
+
+impl SecretRef {
+    pub fn new(source: SecretSource, key: impl Into<String>) -> Self {
+        SecretRef::Source {
+            source,
+            key: key.into(),
+        }
+    }
+}
+
+/// Looks up a secret by key in one kind of store
+pub trait SecretResolver: Send + Sync {
+    fn resolve(&self, key: &str) -> ProviderResult<String>;
+}
+
+/// Reads environment variables
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvResolver;
+
+impl SecretResolver for EnvResolver {
+    fn resolve(&self, key: &str) -> ProviderResult<String> {
+        std::env::var(key).map_err(|_| format!("Environment variable {} is not set", key).into())
+    }
+}
+
+/// Reads `KEY=value` lines from a dotenv file
+#[derive(Debug, Clone)]
+pub struct DotenvResolver {
+    path: PathBuf,
+}
+
+impl Default for DotenvResolver {
+    fn default() -> Self {
+        Self::new(".env")
+    }
+}
+
+impl DotenvResolver {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl SecretResolver for DotenvResolver {
+    fn resolve(&self, key: &str) -> ProviderResult<String> {
+        let text = std::fs::read_to_string(&self.path)
+            .map_err(|e| format!("Cannot read {}: {}", self.path.display(), e))?;
+        parse_dotenv(&text)
+            .remove(key)
+            .ok_or_else(|| format!("{} is not set in {}", key, self.path.display()).into())
+    }
+}
+
+/// Parse dotenv text: `#` comments, optional `export`, optional quotes
+fn parse_dotenv(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (name, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = match value.chars().next() {
+                Some(quote @ ('"' | '\'')) => value
+                    .strip_prefix(quote)
+                    .and_then(|v| v.strip_suffix(quote))
+                    .unwrap_or(value),
+                _ => value.split(" #").next().unwrap_or(value).trim_end(),
+            };
+            Some((name.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Runs a command and takes the first line it prints
+///
+/// `{key}` in the arguments is replaced with the key being resolved.
+#[derive(Debug, Clone)]
+pub struct CommandResolver {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandResolver {
+    pub fn new(
+        program: impl Into<String>,
+        args: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl SecretResolver for CommandResolver {
+    fn resolve(&self, key: &str) -> ProviderResult<String> {
+        let output = Command::new(&self.program)
+            .args(self.args.iter().map(|arg| arg.replace("{key}", key)))
+            .output()
+            .map_err(|e| format!("Cannot run {}: {}", self.program, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "{} failed for {}: {}",
+                self.program,
+                key,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        let stdout = String::from_utf8(output.stdout)?;
+        match stdout.lines().next().map(str::trim) {
+            Some(secret) if !secret.is_empty() => Ok(secret.to_string()),
+            _ => Err(format!("{} printed nothing for {}", self.program, key).into()),
+        }
+    }
+}
+
+/// Reads generic passwords from the OS keychain
+///
+/// Entries are looked up by service name and account (the key).
+#[derive(Debug, Clone)]
+pub struct KeychainResolver {
+    service: String,
+}
+
+impl Default for KeychainResolver {
+    fn default() -> Self {
+        Self::new("patinox")
+    }
+}
+
+impl KeychainResolver {
+    pub fn new(service: impl Into<String>) -> Self {
+        Self {
+            service: service.into(),
+        }
+    }
+
+    fn command(&self) -> CommandResolver {
+        if cfg!(target_os = "macos") {
+            CommandResolver::new(
+                "security",
+                [
+                    "find-generic-password",
+                    "-s",
+                    &self.service,
+                    "-a",
+                    "{key}",
+                    "-w",
+                ],
+            )
+        } else {
+            CommandResolver::new(
+                "secret-tool",
+                ["lookup", "service", &self.service, "account", "{key}"],
+            )
+        }
+    }
+}
+
+impl SecretResolver for KeychainResolver {
+    fn resolve(&self, key: &str) -> ProviderResult<String> {
+        self.command().resolve(key)
+    }
+}
+
+/// Resolvers by source
+///
+/// [`new`](Self::new) registers `env`, `dotenv` (reading `.env`) and
+/// `keychain` (service `patinox`); `command` must be added with the
+/// password manager to call.
+pub struct Secrets {
+    resolvers: HashMap<SecretSource, Box<dyn SecretResolver>>,
+}
+
+impl Default for Secrets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Secrets {
+    pub fn new() -> Self {
+        Self {
+            resolvers: HashMap::new(),
+        }
+        .with(SecretSource::Env, EnvResolver)
+        .with(SecretSource::Dotenv, DotenvResolver::default())
+        .with(SecretSource::Keychain, KeychainResolver::default())
+    }
+
+    /// Use `resolver` for `source`, replacing any previous one
+    pub fn with(mut self, source: SecretSource, resolver: impl SecretResolver + 'static) -> Self {
+        self.resolvers.insert(source, Box::new(resolver));
+        self
+    }
+
+    /// The secret a reference points to
+    pub fn resolve(&self, secret: &SecretRef) -> ProviderResult<String> {
+        match secret {
+            SecretRef::Plain(value) => Ok(value.clone()),
+            SecretRef::Source { source, key } => self
+                .resolvers
+                .get(source)
+                .ok_or_else(|| format!("No resolver configured for {:?} secrets", source))?
+                .resolve(key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_ref_deserialize() {
+        let plain: SecretRef = serde_json::from_str(r#""sk-123""#).unwrap();
+        assert_eq!(plain, SecretRef::Plain("sk-123".into()));
+        let keychain: SecretRef =
+            serde_json::from_str(r#"{"source": "keychain", "key": "openai"}"#).unwrap();
+        assert_eq!(keychain, SecretRef::new(SecretSource::Keychain, "openai"));
+    }
+
+    #[test]
+    fn test_parse_dotenv() {
+        let vars = parse_dotenv(
+            "# keys\nOPENAI_API_KEY=sk-1 # dev\nexport ANTHROPIC_API_KEY=\"sk #2\"\n\nBAD LINE\nEMPTY=\n",
+        );
+        assert_eq!(vars["OPENAI_API_KEY"], "sk-1");
+        assert_eq!(vars["ANTHROPIC_API_KEY"], "sk #2");
+        assert_eq!(vars["EMPTY"], "");
+        assert_eq!(vars.len(), 3);
+    }
+
+    #[test]
+    fn test_resolvers() {
+        let path = std::env::temp_dir().join(format!("patinox-env-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "OPENAI_API_KEY=from-file\n").unwrap();
+        let secrets = Secrets::new()
+            .with(SecretSource::Dotenv, DotenvResolver::new(&path))
+            .with(
+                SecretSource::Command,
+                CommandResolver::new("echo", ["secret-{key}\nsecond line"]),
+            );
+
+        let from_file = SecretRef::new(SecretSource::Dotenv, "OPENAI_API_KEY");
+        assert_eq!(secrets.resolve(&from_file).unwrap(), "from-file");
+        let missing = SecretRef::new(SecretSource::Dotenv, "OTHER");
+        assert!(secrets.resolve(&missing).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let command = SecretRef::new(SecretSource::Command, "openai");
+        assert_eq!(secrets.resolve(&command).unwrap(), "secret-openai");
+
+        let unset = SecretRef::new(SecretSource::Env, "PATINOX_TEST_UNSET_SECRET");
+        assert!(secrets.resolve(&unset).is_err());
+        let err = Secrets::new()
+            .resolve(&SecretRef::new(SecretSource::Command, "x"))
+            .unwrap_err();
+        assert!(err.to_string().contains("No resolver configured"));
+    }
+}
@@ -0,0 +1,350 @@
+//! Speech-to-text
+//!
+//! [`SpeechToText`] turns audio into text, alongside [`LLMProvider`](super::LLMProvider)
+//! for chat and [`EmbeddingProvider`](super::EmbeddingProvider) for vectors.
+//! Two implementations ship:
+//!
+//! - [`WhisperApi`] - OpenAI's `/audio/transcriptions` endpoint (or any
+//!   compatible service via [`base_url`](WhisperApi::base_url))
+//! - [`WhisperCppServer`] - a local whisper.cpp `server` over HTTP, for
+//!   offline transcription
+//!
+//! Agents get audio input through the `transcribe` tool
+//! ([`TranscribeTool`](crate::tool::transcribe::TranscribeTool)).
+//!
+//! # Example
+//! ```ignore
+//! use patinox::provider::speech::{AudioInput, SpeechToText, WhisperCppServer};
+//!
+//! let stt = WhisperCppServer::new("http://127.0.0.1:8080");
+//! let transcript = stt.transcribe(AudioInput::from_file("meeting.wav")?).await?;
+//! println!("{}", transcript.text);
+//! ```
+
+use super::ProviderResult;
+use crate::error::ProviderError;
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+
+/// Default model for [`WhisperApi`]
+const DEFAULT_WHISPER_MODEL: &str = "whisper-1";
+
+/// Transcription requests carry whole files, so allow them time
+const TRANSCRIPTION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// An audio file to transcribe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioInput {
+    /// File name sent to the service, which uses its extension
+    pub file_name: String,
+    pub media_type: String,
+    pub bytes: Vec<u8>,
+}
+
+impl AudioInput {
+    pub fn new(
+        file_name: impl Into<String>,
+        media_type: impl Into<String>,
+        bytes: Vec<u8>,
+    ) -> Self {
+        Self {
+            file_name: file_name.into(),
+            media_type: media_type.into(),
+            bytes,
+        }
+    }
+
+    /// Read an audio file, taking the mime type from its extension
+    pub fn from_file(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("audio")
+            .to_string();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase)
+            .unwrap_or_default();
+        let media_type = match extension.as_str() {
+            "mp3" | "mpga" | "mpeg" => "audio/mpeg",
+            "wav" => "audio/wav",
+            "m4a" | "mp4" => "audio/mp4",
+            "ogg" | "oga" => "audio/ogg",
+            "webm" => "audio/webm",
+            "flac" => "audio/flac",
+            _ => "application/octet-stream",
+        };
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Cannot read audio {}: {}", path.display(), e))?;
+        Ok(Self::new(file_name, media_type, bytes))
+    }
+}
+
+/// Text recognized in an audio file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Transcription {
+    pub text: String,
+    /// Detected or requested language, when the service reports it
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Audio length in seconds, when the service reports it
+    #[serde(default)]
+    pub duration: Option<f64>,
+}
+
+/// Turns audio into text
+#[async_trait::async_trait]
+pub trait SpeechToText: Send + Sync {
+    async fn transcribe(&self, audio: AudioInput) -> ProviderResult<Transcription>;
+}
+
+/// A `multipart/form-data` body with text fields and one file as `file`,
+/// with its content type
+pub(crate) fn multipart_body(
+    fields: &[(&str, &str)],
+    file_name: &str,
+    media_type: &str,
+    bytes: &[u8],
+) -> (String, Vec<u8>) {
+    let boundary = format!("patinox-{}", uuid::Uuid::new_v4().simple());
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                boundary, name, value
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+             Content-Type: {}\r\n\r\n",
+            boundary,
+            file_name.replace('"', ""),
+            media_type
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(bytes);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    (format!("multipart/form-data; boundary={}", boundary), body)
+}
+
+async fn post_audio(
+    provider: &str,
+    request: reqwest::RequestBuilder,
+    fields: &[(&str, &str)],
+    audio: &AudioInput,
+) -> ProviderResult<Transcription> {
+    let (content_type, body) =
+        multipart_body(fields, &audio.file_name, &audio.media_type, &audio.bytes);
+    let response = request
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| ProviderError::from_reqwest(provider, &e))?;
+    let status = response.status();
+    let headers = response.headers().clone();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| ProviderError::from_reqwest(provider, &e))?;
+    if !status.is_success() {
+        return Err(ProviderError::from_http(provider, status, &headers, text).into());
+    }
+    let mut transcription: Transcription = serde_json::from_str(&text)
+        .map_err(|e| format!("Unexpected transcription response: {}", e))?;
+    transcription.text = transcription.text.trim().to_string();
+    Ok(transcription)
+}
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(TRANSCRIPTION_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+/// OpenAI Whisper transcription API
+#[derive(Clone)]
+pub struct WhisperApi {
+    client: reqwest::Client,
+    api_key: String,
+    base_url: String,
+    model: String,
+    language: Option<String>,
+}
+
+impl WhisperApi {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            client: client(),
+            api_key: api_key.into(),
+            base_url: "https://api.openai.com/v1".to_string(),
+            model: DEFAULT_WHISPER_MODEL.to_string(),
+            language: None,
+        }
+    }
+
+    /// Use the key from `OPENAI_API_KEY`
+    pub fn from_env() -> ProviderResult<Self> {
+        let key = std::env::var("OPENAI_API_KEY")
+            .map_err(|_| "OPENAI_API_KEY is required but not set")?;
+        Ok(Self::new(key))
+    }
+
+    /// Send requests to an OpenAI-compatible API at `url` (ending in `/v1`)
+    pub fn base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Expected language as an ISO-639-1 code, which improves accuracy
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl SpeechToText for WhisperApi {
+    async fn transcribe(&self, audio: AudioInput) -> ProviderResult<Transcription> {
+        let mut fields = vec![
+            ("model", self.model.as_str()),
+            ("response_format", "verbose_json"),
+        ];
+        if let Some(language) = &self.language {
+            fields.push(("language", language));
+        }
+        let request = self
+            .client
+            .post(format!("{}/audio/transcriptions", self.base_url))
+            .bearer_auth(&self.api_key);
+        post_audio("openai", request, &fields, &audio).await
+    }
+}
+
+/// A local whisper.cpp `server` (`/inference` endpoint)
+#[derive(Debug, Clone)]
+pub struct WhisperCppServer {
+    client: reqwest::Client,
+    url: String,
+    language: Option<String>,
+}
+
+impl WhisperCppServer {
+    /// Connect to the server at `url`, e.g. `http://127.0.0.1:8080`
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: client(),
+            url: url.into().trim_end_matches('/').to_string(),
+            language: None,
+        }
+    }
+
+    /// Expected language as an ISO-639-1 code (the server auto-detects
+    /// otherwise, if its model supports that)
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl SpeechToText for WhisperCppServer {
+    async fn transcribe(&self, audio: AudioInput) -> ProviderResult<Transcription> {
+        let mut fields = vec![("response_format", "json")];
+        if let Some(language) = &self.language {
+            fields.push(("language", language));
+        }
+        let mut transcription = post_audio(
+            "whisper.cpp",
+            self.client.post(format!("{}/inference", self.url)),
+            &fields,
+            &audio,
+        )
+        .await?;
+        if transcription.language.is_none() {
+            transcription.language = self.language.clone();
+        }
+        Ok(transcription)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+
+    fn audio() -> AudioInput {
+        AudioInput::new("note.wav", "audio/wav", b"RIFF....WAVE".to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_whisper_api() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/audio/transcriptions")
+            .match_header("authorization", "Bearer sk-test")
+            .match_header(
+                "content-type",
+                Matcher::Regex("^multipart/form-data; boundary=".into()),
+            )
+            .match_body(Matcher::AllOf(vec![
+                Matcher::Regex("name=\"model\"\r\n\r\nwhisper-1\r\n".into()),
+                Matcher::Regex("name=\"language\"\r\n\r\nde\r\n".into()),
+                Matcher::Regex("filename=\"note.wav\"\r\nContent-Type: audio/wav".into()),
+            ]))
+            .with_body(r#"{"text": " Hallo Welt ", "language": "german", "duration": 1.5}"#)
+            .create_async()
+            .await;
+
+        let stt = WhisperApi::new("sk-test")
+            .base_url(format!("{}/v1/", server.url()))
+            .language("de");
+        let transcription = stt.transcribe(audio()).await.unwrap();
+        assert_eq!(transcription.text, "Hallo Welt");
+        assert_eq!(transcription.language.as_deref(), Some("german"));
+        assert_eq!(transcription.duration, Some(1.5));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_whisper_cpp_server() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/inference")
+            .match_body(Matcher::Regex(
+                "name=\"response_format\"\r\n\r\njson".into(),
+            ))
+            .with_body(r#"{"text": " hello there\n"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/broken/inference")
+            .with_status(500)
+            .with_body("model not loaded")
+            .create_async()
+            .await;
+
+        let stt = WhisperCppServer::new(server.url());
+        assert_eq!(stt.transcribe(audio()).await.unwrap().text, "hello there");
+
+        let broken = WhisperCppServer::new(format!("{}/broken", server.url()));
+        let err = broken.transcribe(audio()).await.unwrap_err();
+        let err = ProviderError::find(err.as_ref()).unwrap();
+        assert_eq!((err.status, err.is_retryable()), (Some(500), true));
+        assert_eq!(err.message, "model not loaded");
+    }
+}
@@ -0,0 +1,275 @@
+//! Test doubles for providers
+//!
+//! [`MockProvider`] lets downstream crates unit-test agents without network
+//! access or HTTP mocking. Give it a fixed answer, or script a sequence of
+//! text answers, tool calls and errors that are returned one per call, with
+//! optional latency and reported token usage. Every request is recorded for
+//! assertions, with the tools offered and the request options.
+//!
+//! Provider calls are not streamed in V2, so there are no chunk sequences to
+//! script; agents report text to event streams one turn at a time.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::provider::testing::MockProvider;
+//!
+//! let provider = Arc::new(
+//!     MockProvider::scripted()
+//!         .then_tool_call("lookup", json!({"id": 7}))
+//!         .then_text("Order 7 has shipped."),
+//! );
+//! let agent = create_agent("support").tool(lookup).with_provider(Box::new(provider.clone()));
+//! assert_eq!(agent.run("Where is order 7?").await?, "Order 7 has shipped.");
+//! assert_eq!(provider.calls(), 2);
+//! ```
+
+use super::{
+    LLMProvider, Message, ProviderResponse, ProviderResult, ProviderUsage, RequestOptions,
+    ToolCall, ToolDefinition,
+};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// One scripted reply
+#[derive(Debug, Clone)]
+enum Reply {
+    Response(ProviderResponse),
+    Error(String),
+}
+
+/// One request as the mock received it
+#[derive(Debug, Clone)]
+struct Request {
+    messages: Vec<Message>,
+    tools: Vec<ToolDefinition>,
+    options: RequestOptions,
+}
+
+/// Provider returning a fixed or scripted sequence of responses
+#[derive(Debug, Default)]
+pub struct MockProvider {
+    script: Mutex<VecDeque<Reply>>,
+    fallback: Option<String>,
+    latency: Duration,
+    usage: Option<ProviderUsage>,
+    requests: Mutex<Vec<Request>>,
+}
+
+impl MockProvider {
+    /// Answer every call with `response`, after any scripted replies
+    pub fn new(response: impl Into<String>) -> Self {
+        Self {
+            fallback: Some(response.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Answer only with scripted replies; calls past the end fail
+    pub fn scripted() -> Self {
+        Self::default()
+    }
+
+    fn then(self, reply: Reply) -> Self {
+        self.script.lock().unwrap().push_back(reply);
+        self
+    }
+
+    /// Reply with text
+    pub fn then_text(self, text: impl Into<String>) -> Self {
+        self.then(Reply::Response(ProviderResponse::Text(text.into())))
+    }
+
+    /// Reply with a single tool call
+    pub fn then_tool_call(self, name: impl Into<String>, arguments: Value) -> Self {
+        let id = format!("call_{}", self.script.lock().unwrap().len() + 1);
+        self.then_tool_calls(vec![ToolCall {
+            id,
+            name: name.into(),
+            arguments,
+        }])
+    }
+
+    /// Reply with several tool calls at once
+    pub fn then_tool_calls(self, calls: Vec<ToolCall>) -> Self {
+        self.then(Reply::Response(ProviderResponse::ToolCalls(calls)))
+    }
+
+    /// Fail the call with `message`
+    pub fn then_error(self, message: impl Into<String>) -> Self {
+        self.then(Reply::Error(message.into()))
+    }
+
+    /// Wait this long before every reply
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Report `usage` with every reply, as a provider that counts tokens
+    pub fn usage(mut self, usage: ProviderUsage) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Messages of every request received so far
+    pub fn requests(&self) -> Vec<Vec<Message>> {
+        self.recorded(|request| request.messages.clone())
+    }
+
+    /// Tools offered with every request received so far
+    pub fn tools(&self) -> Vec<Vec<ToolDefinition>> {
+        self.recorded(|request| request.tools.clone())
+    }
+
+    /// Options of every request received so far
+    pub fn options(&self) -> Vec<RequestOptions> {
+        self.recorded(|request| request.options.clone())
+    }
+
+    fn recorded<T>(&self, field: impl Fn(&Request) -> T) -> Vec<T> {
+        self.requests.lock().unwrap().iter().map(field).collect()
+    }
+
+    /// Number of calls received so far
+    pub fn calls(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    /// Number of scripted replies not yet returned
+    pub fn remaining(&self) -> usize {
+        self.script.lock().unwrap().len()
+    }
+}
+
+#[async_trait::async_trait]
+impl LLMProvider for MockProvider {
+    async fn complete(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+    ) -> ProviderResult<ProviderResponse> {
+        self.complete_with_options(messages, tools, &RequestOptions::default())
+            .await
+    }
+
+    async fn complete_with_options(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<ProviderResponse> {
+        self.requests.lock().unwrap().push(Request {
+            messages,
+            tools,
+            options: options.clone(),
+        });
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        let reply = self.script.lock().unwrap().pop_front();
+        match (reply, &self.fallback) {
+            (Some(Reply::Response(response)), _) => Ok(response),
+            (Some(Reply::Error(message)), _) => Err(message.into()),
+            (None, Some(response)) => Ok(ProviderResponse::Text(response.clone())),
+            (None, None) => Err("MockProvider script exhausted".into()),
+        }
+    }
+
+    async fn complete_with_usage(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<ToolDefinition>,
+        options: &RequestOptions,
+    ) -> ProviderResult<(ProviderResponse, Option<ProviderUsage>)> {
+        let response = self.complete_with_options(messages, tools, options).await?;
+        Ok((response, self.usage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_agent;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_mock_provider() {
+        let provider = MockProvider::new("test response");
+        let result = provider
+            .complete(vec![Message::user("test")], vec![])
+            .await
+            .unwrap();
+        match result {
+            ProviderResponse::Text(text) => assert_eq!(text, "test response"),
+            _ => panic!("Expected text response"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scripted_agent_run() {
+        let provider = Arc::new(
+            MockProvider::scripted()
+                .then_tool_call("lookup", json!({"id": 7}))
+                .then_text("Order 7 has shipped.")
+                .then_error("rate limited"),
+        );
+        let agent = create_agent("support")
+            .tool_fn("lookup", "Find an order", |_| Ok("shipped".to_string()))
+            .with_provider(Box::new(provider.clone()));
+
+        assert_eq!(
+            agent.run("Where is order 7?").await.unwrap(),
+            "Order 7 has shipped."
+        );
+        assert_eq!(provider.calls(), 2);
+        assert!(provider.requests()[1]
+            .iter()
+            .any(|m| m.content == "Tool 'lookup' returned: shipped"));
+
+        let err = agent.run("again").await.unwrap_err();
+        assert!(err.to_string().contains("rate limited"));
+        assert!(agent
+            .run("and again")
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("exhausted"));
+    }
+
+    #[tokio::test]
+    async fn test_latency() {
+        let provider = MockProvider::new("slow").latency(Duration::from_millis(20));
+        let started = std::time::Instant::now();
+        provider.complete(Vec::new(), Vec::new()).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn test_records_tools_options_and_reports_usage() {
+        let usage = ProviderUsage {
+            prompt_tokens: 10,
+            completion_tokens: 2,
+            reasoning_tokens: 0,
+            ..Default::default()
+        };
+        let provider = Arc::new(MockProvider::new("ok").usage(usage));
+        let tool = ToolDefinition {
+            name: "lookup".to_string(),
+            description: "Find an order".to_string(),
+            parameters: json!({"type": "object"}),
+        };
+        let options = RequestOptions::new().model("gpt-4.1");
+
+        // Calls through the Arc reach every method of the mock
+        let (_, reported) = provider
+            .complete_with_usage(vec![Message::user("hi")], vec![tool], &options)
+            .await
+            .unwrap();
+        assert_eq!(reported, Some(usage));
+        assert_eq!(provider.tools()[0][0].name, "lookup");
+        assert_eq!(provider.options(), vec![options]);
+    }
+}
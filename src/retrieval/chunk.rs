@@ -0,0 +1,107 @@
+//! Document chunking
+
+/// Splits text into overlapping chunks for embedding
+///
+/// Sizes are in characters. Chunk boundaries move back to the nearest
+/// whitespace when there is one, so words are not cut in half.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextChunker {
+    size: usize,
+    overlap: usize,
+}
+
+impl Default for TextChunker {
+    fn default() -> Self {
+        Self::new(1000, 200)
+    }
+}
+
+impl TextChunker {
+    /// Chunks of at most `size` characters, each sharing about `overlap`
+    /// characters with the previous one
+    pub fn new(size: usize, overlap: usize) -> Self {
+        let size = size.max(1);
+        Self {
+            size,
+            overlap: overlap.min(size - 1),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn overlap(&self) -> usize {
+        self.overlap
+    }
+
+    /// Split `text` into trimmed, non-empty chunks
+    pub fn split(&self, text: &str) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut chunks = Vec::new();
+        let mut start = 0;
+
+        while start < chars.len() {
+            let mut end = (start + self.size).min(chars.len());
+            if end < chars.len() {
+                // Break at the last whitespace in the second half of the window
+                if let Some(offset) = chars[start + self.size / 2..end]
+                    .iter()
+                    .rposition(|c| c.is_whitespace())
+                {
+                    end = start + self.size / 2 + offset + 1;
+                }
+            }
+
+            let chunk: String = chars[start..end].iter().collect();
+            let chunk = chunk.trim();
+            if !chunk.is_empty() {
+                chunks.push(chunk.to_string());
+            }
+            if end == chars.len() {
+                break;
+            }
+
+            let mut next = end.saturating_sub(self.overlap).max(start + 1);
+            // Start the overlap at a word boundary too, if the window has one
+            if !chars[next - 1].is_whitespace() {
+                if let Some(offset) = chars[next..end].iter().position(|c| c.is_whitespace()) {
+                    next += offset + 1;
+                }
+            }
+            start = next;
+        }
+        chunks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_with_overlap() {
+        let text = "one two three four five six seven eight nine ten";
+        let chunks = TextChunker::new(20, 8).split(text);
+        assert_eq!(
+            chunks,
+            vec![
+                "one two three four",
+                "four five six seven",
+                "seven eight nine ten",
+            ]
+        );
+        for chunk in &chunks {
+            assert!(chunk.chars().count() <= 20);
+        }
+
+        assert_eq!(TextChunker::new(100, 10).split("short"), vec!["short"]);
+        assert!(TextChunker::default().split("  ").is_empty());
+    }
+
+    #[test]
+    fn test_split_without_whitespace() {
+        let chunks = TextChunker::new(4, 2).split("abcdefgh");
+        assert_eq!(chunks, vec!["abcd", "cdef", "efgh"]);
+    }
+}
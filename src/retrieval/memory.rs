@@ -0,0 +1,128 @@
+//! In-memory vector store
+
+use super::{MetadataFilter, Record, SearchResult, VectorStore};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Vector store kept in a `HashMap`, searched by brute-force cosine similarity
+///
+/// Fine for tests and corpora up to tens of thousands of chunks; nothing is
+/// persisted.
+#[derive(Debug, Default)]
+pub struct InMemoryVectorStore {
+    records: RwLock<HashMap<String, Record>>,
+}
+
+impl InMemoryVectorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of stored records
+    pub fn len(&self) -> usize {
+        self.records.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl VectorStore for InMemoryVectorStore {
+    async fn upsert(&self, records: Vec<Record>) -> crate::Result<()> {
+        let mut stored = self.records.write().unwrap();
+        for record in records {
+            stored.insert(record.id.clone(), record);
+        }
+        Ok(())
+    }
+
+    async fn query(
+        &self,
+        embedding: &[f32],
+        k: usize,
+        filter: Option<&MetadataFilter>,
+    ) -> crate::Result<Vec<SearchResult>> {
+        let stored = self.records.read().unwrap();
+        let mut results: Vec<SearchResult> = stored
+            .values()
+            .filter(|record| filter.map_or(true, |f| f.matches(&record.metadata)))
+            .map(|record| SearchResult {
+                score: cosine_similarity(embedding, &record.embedding),
+                record: record.clone(),
+            })
+            .collect();
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results.truncate(k);
+        Ok(results)
+    }
+
+    async fn delete(&self, ids: &[String]) -> crate::Result<usize> {
+        let mut stored = self.records.write().unwrap();
+        Ok(ids.iter().filter(|id| stored.remove(*id).is_some()).count())
+    }
+}
+
+/// Cosine similarity, 0.0 for mismatched or zero-length vectors
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(id: &str, embedding: Vec<f32>, lang: &str) -> Record {
+        Record::new(id, id, embedding).with_metadata("lang", json!(lang))
+    }
+
+    #[tokio::test]
+    async fn test_query_filter_and_delete() {
+        let store = InMemoryVectorStore::new();
+        store
+            .upsert(vec![
+                record("a", vec![1.0, 0.0], "en"),
+                record("b", vec![0.7, 0.7], "en"),
+                record("c", vec![0.9, 0.1], "de"),
+            ])
+            .await
+            .unwrap();
+
+        let results = store.query(&[1.0, 0.0], 2, None).await.unwrap();
+        let ids: Vec<_> = results.iter().map(|r| r.record.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "c"]);
+        assert!((results[0].score - 1.0).abs() < 1e-6);
+
+        let english = MetadataFilter::new().eq("lang", "en");
+        let results = store.query(&[1.0, 0.0], 5, Some(&english)).await.unwrap();
+        let ids: Vec<_> = results.iter().map(|r| r.record.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+
+        // Upsert replaces by id
+        store
+            .upsert(vec![record("a", vec![0.0, 1.0], "en")])
+            .await
+            .unwrap();
+        assert_eq!(store.len(), 3);
+
+        let deleted = store
+            .delete(&["a".to_string(), "missing".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+        assert_eq!(store.len(), 2);
+    }
+}
@@ -0,0 +1,395 @@
+//! Retrieval-augmented generation
+//!
+//! Documents are split into overlapping chunks ([`TextChunker`]), embedded
+//! with an [`EmbeddingProvider`] and stored in a [`VectorStore`]. At run time
+//! the agent calls the `retrieve` tool ([`RetrieveTool`]), which embeds its
+//! query, fetches the top-k most similar chunks and returns them as text, so
+//! they land in the conversation right where the model asked for them.
+//!
+//! [`InMemoryVectorStore`] is the built-in store. With the `pgvector`
+//! feature, [`pgvector::PgVectorStore`] keeps records in Postgres; other
+//! backends implement [`VectorStore`].
+//!
+//! # Example
+//! ```ignore
+//! use patinox::retrieval::{InMemoryVectorStore, Retriever};
+//!
+//! let embedder = Arc::new(OpenAIProvider::new(ProviderConfig::new(Provider::OpenAI))?);
+//! let retriever = Retriever::new(embedder, Arc::new(InMemoryVectorStore::new()));
+//! retriever.index("handbook", &handbook_text, Default::default()).await?;
+//!
+//! let agent = create_agent("hr-assistant").tool(retriever.tool());
+//! ```
+
+mod chunk;
+mod memory;
+#[cfg(feature = "pgvector")]
+pub mod pgvector;
+
+pub use chunk::TextChunker;
+pub use memory::{cosine_similarity, InMemoryVectorStore};
+
+use crate::provider::EmbeddingProvider;
+use crate::tool::{block_on, Tool, ToolResult};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+
+/// Metadata key holding the source document id of a chunk
+pub const DOCUMENT_KEY: &str = "document";
+
+/// Default number of chunks returned by the `retrieve` tool
+const DEFAULT_TOP_K: usize = 4;
+
+/// A chunk of text with its embedding
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub id: String,
+    pub text: String,
+    pub embedding: Vec<f32>,
+    #[serde(default)]
+    pub metadata: Map<String, Value>,
+}
+
+impl Record {
+    pub fn new(id: impl Into<String>, text: impl Into<String>, embedding: Vec<f32>) -> Self {
+        Self {
+            id: id.into(),
+            text: text.into(),
+            embedding,
+            metadata: Map::new(),
+        }
+    }
+
+    pub fn with_metadata(mut self, key: impl Into<String>, value: Value) -> Self {
+        self.metadata.insert(key.into(), value);
+        self
+    }
+}
+
+/// A record returned by a similarity query
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub record: Record,
+    /// Similarity to the query; higher is closer
+    pub score: f32,
+}
+
+/// Equality conditions on record metadata, all of which must hold
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MetadataFilter {
+    conditions: Map<String, Value>,
+}
+
+impl MetadataFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require `metadata[key] == value`
+    pub fn eq(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.conditions.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// The required key/value pairs
+    pub fn conditions(&self) -> &Map<String, Value> {
+        &self.conditions
+    }
+
+    pub fn matches(&self, metadata: &Map<String, Value>) -> bool {
+        self.conditions
+            .iter()
+            .all(|(key, value)| metadata.get(key) == Some(value))
+    }
+}
+
+/// Storage and similarity search for embedded chunks
+#[async_trait]
+pub trait VectorStore: Send + Sync {
+    /// Insert records, replacing any with the same id
+    async fn upsert(&self, records: Vec<Record>) -> crate::Result<()>;
+
+    /// The `k` records most similar to `embedding`, best first
+    async fn query(
+        &self,
+        embedding: &[f32],
+        k: usize,
+        filter: Option<&MetadataFilter>,
+    ) -> crate::Result<Vec<SearchResult>>;
+
+    /// Remove records by id, returning how many existed
+    async fn delete(&self, ids: &[String]) -> crate::Result<usize>;
+}
+
+/// Chunks, embeds, stores and searches documents
+#[derive(Clone)]
+pub struct Retriever {
+    embedder: Arc<dyn EmbeddingProvider>,
+    store: Arc<dyn VectorStore>,
+    chunker: TextChunker,
+}
+
+impl Retriever {
+    pub fn new(embedder: Arc<dyn EmbeddingProvider>, store: Arc<dyn VectorStore>) -> Self {
+        Self {
+            embedder,
+            store,
+            chunker: TextChunker::default(),
+        }
+    }
+
+    /// Replace the default chunker (1000 characters, 200 overlap)
+    pub fn chunker(mut self, chunker: TextChunker) -> Self {
+        self.chunker = chunker;
+        self
+    }
+
+    pub fn store(&self) -> &Arc<dyn VectorStore> {
+        &self.store
+    }
+
+    /// Chunk and embed a document, storing chunks as `{doc_id}#{n}`
+    ///
+    /// Every chunk gets `metadata` plus a [`DOCUMENT_KEY`] entry. Returns the
+    /// chunk ids. Re-indexing a shorter version of a document leaves its old
+    /// trailing chunks behind; [`delete`](VectorStore::delete) them first.
+    pub async fn index(
+        &self,
+        doc_id: &str,
+        text: &str,
+        metadata: Map<String, Value>,
+    ) -> crate::Result<Vec<String>> {
+        let chunks = self.chunker.split(text);
+        let embeddings = self.embedder.embed(chunks.clone()).await?;
+        if embeddings.len() != chunks.len() {
+            return Err(format!(
+                "Embedder returned {} vectors for {} chunks",
+                embeddings.len(),
+                chunks.len()
+            )
+            .into());
+        }
+
+        let records: Vec<Record> = chunks
+            .into_iter()
+            .zip(embeddings)
+            .enumerate()
+            .map(|(i, (text, embedding))| {
+                let mut record = Record::new(format!("{}#{}", doc_id, i), text, embedding);
+                record.metadata = metadata.clone();
+                record.with_metadata(DOCUMENT_KEY, json!(doc_id))
+            })
+            .collect();
+        let ids = records.iter().map(|r| r.id.clone()).collect();
+        tracing::debug!("retrieval: indexed {} chunks of {}", records.len(), doc_id);
+        self.store.upsert(records).await?;
+        Ok(ids)
+    }
+
+    /// The `k` chunks most relevant to `query`
+    pub async fn search(
+        &self,
+        query: &str,
+        k: usize,
+        filter: Option<&MetadataFilter>,
+    ) -> crate::Result<Vec<SearchResult>> {
+        let embedding = self
+            .embedder
+            .embed(vec![query.to_string()])
+            .await?
+            .pop()
+            .ok_or("Embedder returned no vector for the query")?;
+        self.store.query(&embedding, k, filter).await
+    }
+
+    /// A `retrieve` tool searching this retriever
+    pub fn tool(&self) -> RetrieveTool {
+        RetrieveTool {
+            retriever: self.clone(),
+            default_k: DEFAULT_TOP_K,
+        }
+    }
+}
+
+/// Format results as numbered passages for the model
+pub fn format_results(results: &[SearchResult]) -> String {
+    if results.is_empty() {
+        return "No relevant documents found.".to_string();
+    }
+    results
+        .iter()
+        .enumerate()
+        .map(|(i, result)| {
+            let source = result
+                .record
+                .metadata
+                .get(DOCUMENT_KEY)
+                .and_then(Value::as_str)
+                .unwrap_or(&result.record.id);
+            format!(
+                "[{}] {} (score {:.2})\n{}",
+                i + 1,
+                source,
+                result.score,
+                result.record.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Built-in tool returning the chunks most relevant to a query
+#[derive(Clone)]
+pub struct RetrieveTool {
+    retriever: Retriever,
+    default_k: usize,
+}
+
+impl RetrieveTool {
+    /// Number of chunks returned when the model doesn't ask for a count
+    pub fn top_k(mut self, k: usize) -> Self {
+        self.default_k = k.max(1);
+        self
+    }
+}
+
+impl Tool for RetrieveTool {
+    fn name(&self) -> &str {
+        "retrieve"
+    }
+
+    fn description(&self) -> &str {
+        "Search the knowledge base and return the most relevant passages"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {"type": "string", "description": "What to search for"},
+                "k": {"type": "integer", "description": "Number of passages to return"},
+                "filter": {
+                    "type": "object",
+                    "description": "Only return passages whose metadata has these exact values"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn execute(&self, args: Value) -> ToolResult {
+        let query = args
+            .get("query")
+            .and_then(Value::as_str)
+            .ok_or("Missing 'query' argument")?
+            .to_string();
+        let k = args
+            .get("k")
+            .and_then(Value::as_u64)
+            .map_or(self.default_k, |k| k.max(1) as usize);
+        let filter: Option<MetadataFilter> = args
+            .get("filter")
+            .filter(|f| !f.is_null())
+            .map(|f| serde_json::from_value(f.clone()))
+            .transpose()
+            .map_err(|e| format!("Invalid 'filter' argument: {}", e))?;
+
+        let retriever = self.retriever.clone();
+        let results = block_on(async move { retriever.search(&query, k, filter.as_ref()).await })??;
+        Ok(format_results(&results))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::ProviderResult;
+
+    /// Bag-of-words embedder: each word bumps one of 64 hashed dimensions
+    struct HashEmbedder;
+
+    #[async_trait]
+    impl EmbeddingProvider for HashEmbedder {
+        async fn embed(&self, texts: Vec<String>) -> ProviderResult<Vec<Vec<f32>>> {
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    let mut vector = vec![0.0; 64];
+                    for word in text.split_whitespace() {
+                        let word = word
+                            .trim_matches(|c: char| !c.is_alphanumeric())
+                            .to_lowercase();
+                        let hash = word
+                            .bytes()
+                            .fold(7usize, |h, b| h.wrapping_mul(31).wrapping_add(b as usize));
+                        vector[hash % 64] += 1.0;
+                    }
+                    vector
+                })
+                .collect())
+        }
+    }
+
+    async fn retriever() -> Retriever {
+        let retriever =
+            Retriever::new(Arc::new(HashEmbedder), Arc::new(InMemoryVectorStore::new()))
+                .chunker(TextChunker::new(60, 10));
+        let mut metadata = Map::new();
+        metadata.insert("team".into(), json!("people"));
+        retriever
+            .index(
+                "handbook",
+                "Vacation policy: employees get twenty vacation days per year. \
+                 Expense policy: receipts are required for every expense claim.",
+                metadata,
+            )
+            .await
+            .unwrap();
+        retriever
+            .index("menu", "The cafeteria serves soup on Fridays.", Map::new())
+            .await
+            .unwrap();
+        retriever
+    }
+
+    #[tokio::test]
+    async fn test_index_and_search() {
+        let retriever = retriever().await;
+        let results = retriever
+            .search("how many vacation days", 1, None)
+            .await
+            .unwrap();
+        assert_eq!(results[0].record.id, "handbook#0");
+        assert!(results[0].record.text.contains("twenty vacation days"));
+        assert_eq!(results[0].record.metadata["team"], "people");
+
+        let filter = MetadataFilter::new().eq(DOCUMENT_KEY, "menu");
+        let results = retriever
+            .search("vacation days", 5, Some(&filter))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].record.id, "menu#0");
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_tool() {
+        let tool = retriever().await.tool().top_k(1);
+        let output = tool.execute(json!({"query": "soup on Fridays"})).unwrap();
+        assert!(output.starts_with("[1] menu (score "));
+        assert!(output.ends_with("The cafeteria serves soup on Fridays."));
+
+        let output = tool
+            .execute(json!({"query": "soup", "filter": {"team": "finance"}}))
+            .unwrap();
+        assert_eq!(output, "No relevant documents found.");
+        assert!(tool.execute(json!({})).is_err());
+    }
+}
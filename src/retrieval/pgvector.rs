@@ -0,0 +1,248 @@
+//! Postgres + pgvector vector store
+//!
+//! Enabled with the `pgvector` feature. Records live in one table per store:
+//!
+//! ```sql
+//! CREATE TABLE <table> (
+//!     id TEXT PRIMARY KEY,
+//!     text TEXT NOT NULL,
+//!     embedding vector(<dimensions>) NOT NULL,
+//!     metadata JSONB NOT NULL DEFAULT '{}'
+//! );
+//! ```
+//!
+//! [`PgVectorStore::migrate`] creates the extension, table and an HNSW index
+//! for the chosen [`Distance`]. Metadata filters become JSONB containment
+//! (`metadata @> filter`), which the index on `metadata` can serve.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::retrieval::pgvector::{Distance, PgVectorStore};
+//!
+//! let pool = sqlx::PgPool::connect(&std::env::var("DATABASE_URL")?).await?;
+//! let store = PgVectorStore::new(pool, "handbook_chunks")?.distance(Distance::Cosine);
+//! store.migrate(1536).await?;
+//! let retriever = Retriever::new(embedder, Arc::new(store));
+//! ```
+
+use super::{MetadataFilter, Record, SearchResult, VectorStore};
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+use sqlx::postgres::PgPool;
+use sqlx::Row;
+
+/// Distance metric used for ordering and scoring
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Distance {
+    /// Cosine distance (`<=>`); score is `1 - distance`
+    #[default]
+    Cosine,
+    /// Euclidean distance (`<->`); score is `1 / (1 + distance)`
+    L2,
+}
+
+impl Distance {
+    fn operator(self) -> &'static str {
+        match self {
+            Distance::Cosine => "<=>",
+            Distance::L2 => "<->",
+        }
+    }
+
+    fn index_ops(self) -> &'static str {
+        match self {
+            Distance::Cosine => "vector_cosine_ops",
+            Distance::L2 => "vector_l2_ops",
+        }
+    }
+
+    fn score(self, distance: f64) -> f32 {
+        match self {
+            Distance::Cosine => (1.0 - distance) as f32,
+            Distance::L2 => (1.0 / (1.0 + distance)) as f32,
+        }
+    }
+}
+
+/// [`VectorStore`] backed by a Postgres table with a pgvector column
+#[derive(Debug, Clone)]
+pub struct PgVectorStore {
+    pool: PgPool,
+    table: String,
+    distance: Distance,
+}
+
+impl PgVectorStore {
+    /// Store records in `table`, which must be a plain SQL identifier
+    /// (optionally schema-qualified, e.g. `rag.chunks`)
+    pub fn new(pool: PgPool, table: impl Into<String>) -> crate::Result<Self> {
+        let table = table.into();
+        if !is_identifier(&table) {
+            return Err(format!("Invalid table name: {}", table).into());
+        }
+        Ok(Self {
+            pool,
+            table,
+            distance: Distance::default(),
+        })
+    }
+
+    pub fn distance(mut self, distance: Distance) -> Self {
+        self.distance = distance;
+        self
+    }
+
+    /// Create the pgvector extension, the table and its indexes if missing
+    ///
+    /// `dimensions` must match the embedding model. Changing metric later
+    /// needs a new index; this only creates one when none exists.
+    pub async fn migrate(&self, dimensions: usize) -> crate::Result<()> {
+        let index_prefix = self.table.replace('.', "_");
+        let statements = [
+            "CREATE EXTENSION IF NOT EXISTS vector".to_string(),
+            format!(
+                "CREATE TABLE IF NOT EXISTS {} (\
+                    id TEXT PRIMARY KEY, \
+                    text TEXT NOT NULL, \
+                    embedding vector({}) NOT NULL, \
+                    metadata JSONB NOT NULL DEFAULT '{{}}')",
+                self.table, dimensions
+            ),
+            format!(
+                "CREATE INDEX IF NOT EXISTS {}_embedding_idx ON {} USING hnsw (embedding {})",
+                index_prefix,
+                self.table,
+                self.distance.index_ops()
+            ),
+            format!(
+                "CREATE INDEX IF NOT EXISTS {}_metadata_idx ON {} USING gin (metadata)",
+                index_prefix, self.table
+            ),
+        ];
+        for statement in statements {
+            sqlx::query(&statement).execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VectorStore for PgVectorStore {
+    async fn upsert(&self, records: Vec<Record>) -> crate::Result<()> {
+        let sql = format!(
+            "INSERT INTO {} (id, text, embedding, metadata) VALUES ($1, $2, $3::vector, $4) \
+             ON CONFLICT (id) DO UPDATE SET \
+             text = EXCLUDED.text, embedding = EXCLUDED.embedding, metadata = EXCLUDED.metadata",
+            self.table
+        );
+        let mut transaction = self.pool.begin().await?;
+        for record in records {
+            sqlx::query(&sql)
+                .bind(record.id)
+                .bind(record.text)
+                .bind(vector_literal(&record.embedding))
+                .bind(Value::Object(record.metadata))
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn query(
+        &self,
+        embedding: &[f32],
+        k: usize,
+        filter: Option<&MetadataFilter>,
+    ) -> crate::Result<Vec<SearchResult>> {
+        let sql = format!(
+            "SELECT id, text, embedding::text AS embedding, metadata, \
+             embedding {} $1::vector AS distance \
+             FROM {} WHERE metadata @> $2 ORDER BY distance LIMIT $3",
+            self.distance.operator(),
+            self.table
+        );
+        let conditions = filter.map(|f| f.conditions().clone()).unwrap_or_default();
+        let rows = sqlx::query(&sql)
+            .bind(vector_literal(embedding))
+            .bind(Value::Object(conditions))
+            .bind(k as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let metadata = match row.try_get::<Value, _>("metadata")? {
+                    Value::Object(map) => map,
+                    _ => Map::new(),
+                };
+                let embedding: String = row.try_get("embedding")?;
+                let distance: f64 = row.try_get("distance")?;
+                Ok(SearchResult {
+                    record: Record {
+                        id: row.try_get("id")?,
+                        text: row.try_get("text")?,
+                        embedding: serde_json::from_str(&embedding)?,
+                        metadata,
+                    },
+                    score: self.distance.score(distance),
+                })
+            })
+            .collect()
+    }
+
+    async fn delete(&self, ids: &[String]) -> crate::Result<usize> {
+        let sql = format!("DELETE FROM {} WHERE id = ANY($1)", self.table);
+        let result = sqlx::query(&sql).bind(ids).execute(&self.pool).await?;
+        Ok(result.rows_affected() as usize)
+    }
+}
+
+/// pgvector's text input format, e.g. `[0.1,0.2]`
+fn vector_literal(embedding: &[f32]) -> String {
+    let values: Vec<String> = embedding.iter().map(|v| v.to_string()).collect();
+    format!("[{}]", values.join(","))
+}
+
+/// `name` or `schema.name`, letters, digits and underscores only
+fn is_identifier(name: &str) -> bool {
+    let parts: Vec<&str> = name.split('.').collect();
+    parts.len() <= 2
+        && parts.iter().all(|part| {
+            part.chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identifier_validation() {
+        for name in ["chunks", "rag.chunks", "_docs2"] {
+            assert!(is_identifier(name), "{}", name);
+        }
+        for name in [
+            "",
+            "2chunks",
+            "a.b.c",
+            "chunks; DROP TABLE x",
+            "my-table",
+            "a.",
+        ] {
+            assert!(!is_identifier(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_vector_literal_and_scores() {
+        assert_eq!(vector_literal(&[0.5, -1.0, 2.25]), "[0.5,-1,2.25]");
+        assert_eq!(vector_literal(&[]), "[]");
+        assert_eq!(Distance::Cosine.score(0.25), 0.75);
+        assert_eq!(Distance::L2.score(1.0), 0.5);
+        assert!(Distance::L2.score(0.0) > Distance::L2.score(3.0));
+    }
+}
@@ -0,0 +1,249 @@
+//! Graceful shutdown
+//!
+//! A [`Shutdown`] coordinates a clean exit for everything a deployment has
+//! running: it cancels in-flight agent runs, stops background tasks and
+//! runs cleanup hooks (flushing traces, closing connections) in priority
+//! order, all within one deadline. [`Shutdown::shutdown`] reports what
+//! cleaned up and what didn't, so the process can log it before exiting.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::runtime::Shutdown;
+//!
+//! let shutdown = Shutdown::new();
+//! shutdown.on_shutdown("flush traces", 10, move || async move { exporter.flush().await });
+//! shutdown.track("bus", bus.connect_agent(agent.clone(), input, output)?);
+//! let run = agent.run_cancellable(task, shutdown.token());
+//!
+//! tokio::signal::ctrl_c().await?;
+//! let report = shutdown.shutdown(Duration::from_secs(10)).await;
+//! if !report.is_clean() {
+//!     eprintln!("{}", report);
+//! }
+//! ```
+
+use futures::future::BoxFuture;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+type CleanupFn = Box<dyn FnOnce() -> BoxFuture<'static, crate::Result<()>> + Send>;
+
+struct Cleanup {
+    name: String,
+    priority: i32,
+    run: CleanupFn,
+}
+
+#[derive(Default)]
+struct Inner {
+    cleanups: Vec<Cleanup>,
+    tasks: Vec<(String, JoinHandle<()>)>,
+}
+
+/// Coordinates shutdown of runs, background tasks and cleanup hooks
+///
+/// Cheap to clone; clones share the same state.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token cancelled when shutdown starts, for
+    /// [`Agent::run_cancellable`](crate::Agent::run_cancellable) and
+    /// background loops
+    pub fn token(&self) -> CancellationToken {
+        self.token.child_token()
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Run `cleanup` at shutdown; higher `priority` runs first
+    pub fn on_shutdown<F, Fut>(&self, name: impl Into<String>, priority: i32, cleanup: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = crate::Result<()>> + Send + 'static,
+    {
+        self.inner.lock().unwrap().cleanups.push(Cleanup {
+            name: name.into(),
+            priority,
+            run: Box::new(move || Box::pin(cleanup())),
+        });
+    }
+
+    /// Wait for a background task at shutdown, aborting it if it outlives
+    /// the deadline
+    ///
+    /// The task should stop on its own once [`token`](Self::token) is
+    /// cancelled or its input closes.
+    pub fn track(&self, name: impl Into<String>, task: JoinHandle<()>) {
+        self.inner.lock().unwrap().tasks.push((name.into(), task));
+    }
+
+    /// Cancel runs, stop tracked tasks and run cleanups, all within `timeout`
+    ///
+    /// Only the first call does the work; later calls return an empty report.
+    pub async fn shutdown(&self, timeout: Duration) -> ShutdownReport {
+        let deadline = Instant::now() + timeout;
+        let (mut cleanups, tasks) = {
+            let mut inner = self.inner.lock().unwrap();
+            (
+                std::mem::take(&mut inner.cleanups),
+                std::mem::take(&mut inner.tasks),
+            )
+        };
+        self.token.cancel();
+        tracing::info!(
+            "shutdown: stopping {} tasks and running {} cleanups",
+            tasks.len(),
+            cleanups.len()
+        );
+
+        let mut report = ShutdownReport::default();
+        for (name, mut task) in tasks {
+            match tokio::time::timeout_at(deadline, &mut task).await {
+                Ok(Ok(())) => report.completed.push(name),
+                Ok(Err(e)) => report.failed.push((name, format!("task failed: {}", e))),
+                Err(_) => {
+                    task.abort();
+                    report
+                        .failed
+                        .push((name, "did not stop in time; aborted".to_string()));
+                }
+            }
+        }
+
+        // Stable sort keeps registration order within a priority
+        cleanups.sort_by_key(|cleanup| std::cmp::Reverse(cleanup.priority));
+        for cleanup in cleanups {
+            if Instant::now() >= deadline {
+                report
+                    .failed
+                    .push((cleanup.name, "skipped; shutdown timed out".to_string()));
+                continue;
+            }
+            match tokio::time::timeout_at(deadline, (cleanup.run)()).await {
+                Ok(Ok(())) => report.completed.push(cleanup.name),
+                Ok(Err(e)) => report.failed.push((cleanup.name, e.to_string())),
+                Err(_) => report.failed.push((cleanup.name, "timed out".to_string())),
+            }
+        }
+
+        for (name, error) in &report.failed {
+            tracing::warn!("shutdown: {}: {}", name, error);
+        }
+        report
+    }
+}
+
+/// Outcome of [`Shutdown::shutdown`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Tasks that stopped and cleanups that succeeded, in the order they ran
+    pub completed: Vec<String>,
+    /// Tasks and cleanups that failed, timed out or were skipped, with why
+    pub failed: Vec<(String, String)>,
+}
+
+impl ShutdownReport {
+    pub fn is_clean(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl fmt::Display for ShutdownReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Shutdown: {} completed, {} failed",
+            self.completed.len(),
+            self.failed.len()
+        )?;
+        for (name, error) in &self.failed {
+            write!(f, "\n  - {}: {}", name, error)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cleanups that record their order; "broken" fails
+    fn with_cleanups(order: &Arc<Mutex<Vec<&'static str>>>) -> Shutdown {
+        let shutdown = Shutdown::new();
+        for (name, priority) in [("close db", 0), ("flush traces", 10), ("broken", 5)] {
+            let order = order.clone();
+            shutdown.on_shutdown(name, priority, move || async move {
+                order.lock().unwrap().push(name);
+                if name == "broken" {
+                    return Err("disk full".into());
+                }
+                Ok(())
+            });
+        }
+        shutdown
+    }
+
+    #[tokio::test]
+    async fn test_cleanups_run_by_priority() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let shutdown = with_cleanups(&order);
+        let token = shutdown.token();
+        shutdown.track(
+            "worker",
+            tokio::spawn(async move { token.cancelled().await }),
+        );
+
+        let report = shutdown.shutdown(Duration::from_secs(1)).await;
+        assert!(shutdown.is_shutting_down());
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec!["flush traces", "broken", "close db"]
+        );
+        assert_eq!(report.completed, vec!["worker", "flush traces", "close db"]);
+        assert_eq!(
+            report.failed,
+            vec![("broken".to_string(), "disk full".to_string())]
+        );
+        assert!(report.to_string().contains("broken: disk full"));
+
+        // A second call has nothing left to do
+        assert!(shutdown
+            .shutdown(Duration::from_secs(1))
+            .await
+            .completed
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stuck_task_is_aborted_at_deadline() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let shutdown = with_cleanups(&order);
+        let stuck = tokio::spawn(tokio::time::sleep(Duration::from_secs(60)));
+        let abort = stuck.abort_handle();
+        shutdown.track("stuck", stuck);
+
+        let report = shutdown.shutdown(Duration::from_millis(50)).await;
+        assert!(report.completed.is_empty());
+        assert_eq!(report.failed.len(), 4);
+        assert_eq!(report.failed[0].0, "stuck");
+        assert!(report.failed[1].1.starts_with("skipped"));
+        assert!(order.lock().unwrap().is_empty());
+        tokio::task::yield_now().await;
+        assert!(abort.is_finished());
+    }
+}
@@ -0,0 +1,224 @@
+//! Output sanitizing
+//!
+//! Model output is untrusted text. Printed raw, it can carry ANSI escape
+//! sequences that recolor, move the cursor, rewrite earlier lines, set the
+//! window title or, on some terminals, trigger clipboard writes; bidi
+//! override characters can make displayed text differ from what it is.
+//! [`sanitize_for_terminal`] removes all of that while keeping printable
+//! text in any script, newlines and tabs.
+//!
+//! Streams are split into chunks at arbitrary byte offsets, which can cut a
+//! multi-byte character or an escape sequence in half. [`Utf8ChunkDecoder`]
+//! holds back incomplete code points until the rest arrives, and
+//! [`TerminalSanitizer`] carries escape-sequence state across chunks.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::sanitize::TerminalSanitizer;
+//!
+//! let mut sanitizer = TerminalSanitizer::new();
+//! while let Some(chunk) = stream.next().await {
+//!     print!("{}", sanitizer.push_bytes(&chunk?));
+//! }
+//! print!("{}", sanitizer.finish());
+//! ```
+
+/// Decodes UTF-8 that arrives in arbitrary chunks
+///
+/// Invalid bytes become U+FFFD; a code point split across chunks is held
+/// until it is complete.
+#[derive(Debug, Clone, Default)]
+pub struct Utf8ChunkDecoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8ChunkDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode a chunk, returning all complete characters so far
+    pub fn push(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let mut out = String::new();
+        let mut rest: &[u8] = &self.pending;
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(text) => {
+                    out.push_str(text);
+                    rest = &[];
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    out.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        // Incomplete sequence at the end: wait for more bytes
+                        None => {
+                            rest = after;
+                            break;
+                        }
+                        Some(len) => {
+                            out.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after[len..];
+                        }
+                    }
+                }
+            }
+        }
+        self.pending = rest.to_vec();
+        out
+    }
+
+    /// Flush at end of stream; leftover bytes become U+FFFD
+    pub fn finish(&mut self) -> String {
+        if self.pending.is_empty() {
+            String::new()
+        } else {
+            self.pending.clear();
+            char::REPLACEMENT_CHARACTER.to_string()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum EscapeState {
+    #[default]
+    Text,
+    /// After ESC
+    Escape,
+    /// Inside `ESC [` (or C1 CSI) until a final byte
+    Csi,
+    /// Inside an OSC/DCS/APC/PM string until BEL or ST
+    String,
+    /// ESC seen inside a string (ST is `ESC \`)
+    StringEscape,
+}
+
+/// Streaming terminal sanitizer that keeps state across chunks
+#[derive(Debug, Clone, Default)]
+pub struct TerminalSanitizer {
+    decoder: Utf8ChunkDecoder,
+    state: EscapeState,
+}
+
+impl TerminalSanitizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sanitize a chunk of raw bytes
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> String {
+        let text = self.decoder.push(bytes);
+        self.push_str(&text)
+    }
+
+    /// Sanitize a chunk of text
+    pub fn push_str(&mut self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        for c in text.chars() {
+            self.state = match (self.state, c) {
+                (EscapeState::Text, '\u{1b}') => EscapeState::Escape,
+                (EscapeState::Text, '\u{9b}') => EscapeState::Csi,
+                (EscapeState::Text, '\u{90}' | '\u{9d}' | '\u{9e}' | '\u{9f}') => {
+                    EscapeState::String
+                }
+                (EscapeState::Text, c) => {
+                    if is_allowed(c) {
+                        out.push(c);
+                    }
+                    EscapeState::Text
+                }
+                (EscapeState::Escape, '[') => EscapeState::Csi,
+                (EscapeState::Escape, ']' | 'P' | '_' | '^' | 'X') => EscapeState::String,
+                // Two-character sequences (ESC 7, ESC c, ...) and intermediates
+                (EscapeState::Escape, ' '..='/') => EscapeState::Escape,
+                (EscapeState::Escape, _) => EscapeState::Text,
+                (EscapeState::Csi, '\u{40}'..='\u{7e}') => EscapeState::Text,
+                (EscapeState::Csi, _) => EscapeState::Csi,
+                (EscapeState::String, '\u{7}' | '\u{9c}') => EscapeState::Text,
+                (EscapeState::String, '\u{1b}') => EscapeState::StringEscape,
+                (EscapeState::String, _) => EscapeState::String,
+                (EscapeState::StringEscape, '\\') => EscapeState::Text,
+                (EscapeState::StringEscape, _) => EscapeState::String,
+            };
+        }
+        out
+    }
+
+    /// Flush at end of stream
+    pub fn finish(&mut self) -> String {
+        let tail = self.decoder.finish();
+        let out = self.push_str(&tail);
+        self.state = EscapeState::Text;
+        out
+    }
+}
+
+/// Printable characters plus newline and tab
+fn is_allowed(c: char) -> bool {
+    match c {
+        '\n' | '\t' => true,
+        // Bidi embeddings, overrides and isolates
+        '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}' => false,
+        c => !c.is_control(),
+    }
+}
+
+/// Remove escape sequences, control characters and bidi overrides
+pub fn sanitize_for_terminal(text: &str) -> String {
+    let mut sanitizer = TerminalSanitizer::new();
+    let mut out = sanitizer.push_str(text);
+    out.push_str(&sanitizer.finish());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strips_escape_sequences() {
+        let cases = [
+            ("\x1b[31mred\x1b[0m text", "red text"),
+            ("title\x1b]0;pwned\x07 done", "title done"),
+            ("clip\x1b]52;c;ZXZpbA==\x1b\\board", "clipboard"),
+            ("reset\x1bc!", "reset!"),
+            ("c1\u{9b}2Jcsi", "c1csi"),
+            ("over\rwrite", "overwrite"),
+            ("line\r\nnext\ttab", "line\nnext\ttab"),
+            ("bell\x07 null\0", "bell null"),
+            ("admin\u{202e}txt.exe", "admintxt.exe"),
+            ("日本語 ¿qué? ünïcödé", "日本語 ¿qué? ünïcödé"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(sanitize_for_terminal(input), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_utf8_split_across_chunks() {
+        let bytes = "héllo 🦀".as_bytes();
+        let mut decoder = Utf8ChunkDecoder::new();
+        let mut out = String::new();
+        for byte in bytes {
+            out.push_str(&decoder.push(std::slice::from_ref(byte)));
+        }
+        out.push_str(&decoder.finish());
+        assert_eq!(out, "héllo 🦀");
+
+        let mut decoder = Utf8ChunkDecoder::new();
+        assert_eq!(decoder.push(b"a\xffb\xf0\x9f"), "a\u{fffd}b");
+        assert_eq!(decoder.finish(), "\u{fffd}");
+    }
+
+    #[test]
+    fn test_escape_split_across_chunks() {
+        let mut sanitizer = TerminalSanitizer::new();
+        let mut out = sanitizer.push_bytes(b"safe\x1b[3");
+        out.push_str(&sanitizer.push_bytes(b"1mred \xc3"));
+        out.push_str(&sanitizer.push_bytes(b"\xa9"));
+        out.push_str(&sanitizer.finish());
+        assert_eq!(out, "safered é");
+    }
+}
@@ -0,0 +1,735 @@
+//! Scheduled agent runs
+//!
+//! A [`Scheduler`] runs agents on a [`Schedule`]: a fixed interval or a
+//! five-field cron expression (`minute hour day-of-month month day-of-week`,
+//! in UTC), such as a report agent every weekday at nine. When a run is
+//! still going at its next start time, the job's [`OverlapPolicy`] decides
+//! whether the new run is skipped, waits its turn, or replaces the old one.
+//!
+//! Each job's [`JobState`] (last start, last outcome, run count) is saved to
+//! a [`JobStore`] after every run. Interval jobs resume from their last
+//! start after a restart rather than running immediately; cron jobs don't
+//! catch up on times missed while the scheduler was down. Observers see a
+//! [`ScheduleEvent`] as runs start, finish or are skipped.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::scheduler::{FileJobStore, Job, OverlapPolicy, Schedule, Scheduler};
+//!
+//! let scheduler = Scheduler::new()
+//!     .store(Arc::new(FileJobStore::new("jobs.json")))
+//!     .job(Job::new("daily-report", report_agent, "Summarize yesterday",
+//!         Schedule::cron("0 9 * * 1-5")?))
+//!     .job(Job::new("inbox", triage_agent, "Triage new mail",
+//!         Schedule::every(Duration::from_secs(300))).overlap(OverlapPolicy::Queue))
+//!     .observe(|event| tracing::info!("{:?}", event));
+//!
+//! shutdown.track("scheduler", tokio::spawn(scheduler.run(shutdown.token())));
+//! ```
+
+use crate::Agent;
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// A cron expression that could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronError(String);
+
+impl fmt::Display for CronError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid cron expression: {}", self.0)
+    }
+}
+
+impl std::error::Error for CronError {}
+
+/// A parsed five-field cron expression, evaluated in UTC
+///
+/// Fields accept `*`, values, ranges (`1-5`), lists (`1,15`) and steps
+/// (`*/15`, `0-30/10`). Day of week counts from Sunday as 0 (7 is also
+/// Sunday). As in classic cron, when both day fields are restricted a day
+/// matching either one matches. `@hourly`, `@daily`, `@weekly` and
+/// `@monthly` are accepted as shorthands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Whether day-of-month and day-of-week were `*`
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+/// Parse one field into a bit set of allowed values
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, CronError> {
+    let invalid = || CronError(format!("bad field '{}'", field));
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (
+                    start.parse().map_err(|_| invalid())?,
+                    end.parse().map_err(|_| invalid())?,
+                ),
+                None => {
+                    let value = range.parse().map_err(|_| invalid())?;
+                    // `5/10` means from 5 to the end in steps of 10
+                    (value, if step > 1 { max } else { value })
+                }
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl FromStr for CronSchedule {
+    type Err = CronError;
+
+    fn from_str(source: &str) -> Result<Self, CronError> {
+        let expanded = match source.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(CronError(format!(
+                "'{}' needs 5 fields, found {}",
+                source,
+                fields.len()
+            )));
+        };
+        let mut days_of_week = parse_field(day_of_week, 0, 7)?;
+        // Sunday is both 0 and 7
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            source: source.trim().to_string(),
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days_of_month: parse_field(day_of_month, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            days_of_week,
+            any_day_of_month: day_of_month == "*",
+            any_day_of_week: day_of_week == "*",
+        })
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl CronSchedule {
+    fn matches_day(&self, time: DateTime<Utc>) -> bool {
+        let day_of_month = self.days_of_month & (1 << time.day()) != 0;
+        let day_of_week = self.days_of_week & (1 << time.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+
+    /// The first matching minute strictly after `after`
+    ///
+    /// `None` if nothing matches within five years (such as `0 0 31 2 *`).
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+        let limit = start + ChronoDuration::days(5 * 366);
+        let mut time = start;
+        while time < limit {
+            if self.months & (1 << time.month()) == 0 {
+                let (year, month) = match time.month() {
+                    12 => (time.year() + 1, 1),
+                    month => (time.year(), month + 1),
+                };
+                time = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+            } else if !self.matches_day(time) {
+                time = Utc.from_utc_datetime(&time.date_naive().and_hms_opt(0, 0, 0)?)
+                    + ChronoDuration::days(1);
+            } else if self.hours & (1 << time.hour()) == 0 {
+                time = time.with_minute(0)? + ChronoDuration::hours(1);
+            } else if self.minutes & (1 << time.minute()) == 0 {
+                time += ChronoDuration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+/// When a job runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// Every interval, measured from the start of the previous run
+    Every(Duration),
+    Cron(CronSchedule),
+}
+
+impl Schedule {
+    pub fn every(interval: Duration) -> Self {
+        Schedule::Every(interval)
+    }
+
+    pub fn cron(expression: &str) -> Result<Self, CronError> {
+        Ok(Schedule::Cron(expression.parse()?))
+    }
+
+    /// When the next run is due, given now and the previous start
+    ///
+    /// An interval job that has never run is due immediately.
+    pub fn next_run(
+        &self,
+        now: DateTime<Utc>,
+        last_started: Option<DateTime<Utc>>,
+    ) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Every(interval) => match last_started {
+                Some(last) => Some((last + ChronoDuration::from_std(*interval).ok()?).max(now)),
+                None => Some(now),
+            },
+            Schedule::Cron(cron) => cron.next_after(now),
+        }
+    }
+}
+
+/// What happens when a run is due while the previous one is still going
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlapPolicy {
+    /// Don't start the new run
+    #[default]
+    Skip,
+    /// Start the new run once the previous one finishes
+    Queue,
+    /// Cancel the previous run and start the new one
+    CancelPrevious,
+}
+
+/// How a scheduled run ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RunOutcome {
+    Succeeded { output: String },
+    Failed { error: String },
+}
+
+/// What the scheduler remembers about a job between runs and restarts
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobState {
+    pub last_started: Option<DateTime<Utc>>,
+    pub last_finished: Option<DateTime<Utc>>,
+    pub last_outcome: Option<RunOutcome>,
+    /// Runs started, across restarts
+    pub runs: u64,
+    /// Runs skipped under [`OverlapPolicy::Skip`]
+    pub skipped: u64,
+}
+
+/// Where job state is kept
+#[async_trait]
+pub trait JobStore: Send + Sync {
+    async fn load(&self, job: &str) -> crate::Result<Option<JobState>>;
+
+    /// Insert or replace the state of `job`
+    async fn save(&self, job: &str, state: &JobState) -> crate::Result<()>;
+}
+
+/// [`JobStore`] that lives as long as the process
+#[derive(Debug, Default)]
+pub struct InMemoryJobStore {
+    jobs: Mutex<HashMap<String, JobState>>,
+}
+
+impl InMemoryJobStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl JobStore for InMemoryJobStore {
+    async fn load(&self, job: &str) -> crate::Result<Option<JobState>> {
+        Ok(self.jobs.lock().unwrap().get(job).cloned())
+    }
+
+    async fn save(&self, job: &str, state: &JobState) -> crate::Result<()> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .insert(job.to_string(), state.clone());
+        Ok(())
+    }
+}
+
+/// [`JobStore`] keeping every job in one JSON file
+#[derive(Debug)]
+pub struct FileJobStore {
+    path: PathBuf,
+    /// Serializes read-modify-write cycles within the process
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl FileJobStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    async fn read(&self) -> crate::Result<HashMap<String, JobState>> {
+        match tokio::fs::read_to_string(&self.path).await {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl JobStore for FileJobStore {
+    async fn load(&self, job: &str) -> crate::Result<Option<JobState>> {
+        let _guard = self.lock.lock().await;
+        Ok(self.read().await?.remove(job))
+    }
+
+    async fn save(&self, job: &str, state: &JobState) -> crate::Result<()> {
+        let _guard = self.lock.lock().await;
+        let mut jobs = self.read().await?;
+        jobs.insert(job.to_string(), state.clone());
+        tokio::fs::write(&self.path, serde_json::to_string_pretty(&jobs)?).await?;
+        Ok(())
+    }
+}
+
+/// Something that happened to a scheduled run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScheduleEvent {
+    RunStarted {
+        job: String,
+        run: u64,
+    },
+    RunFinished {
+        job: String,
+        run: u64,
+        outcome: RunOutcome,
+        duration_ms: u64,
+    },
+    /// A run was due while the previous one was still going
+    RunSkipped {
+        job: String,
+    },
+    /// The previous run was cancelled to make way for a new one
+    RunReplaced {
+        job: String,
+        run: u64,
+    },
+}
+
+type Observer = Arc<dyn Fn(&ScheduleEvent) + Send + Sync>;
+
+/// An agent, the input it runs on and when
+pub struct Job {
+    name: String,
+    agent: Arc<Agent>,
+    input: String,
+    schedule: Schedule,
+    overlap: OverlapPolicy,
+}
+
+impl Job {
+    pub fn new(
+        name: impl Into<String>,
+        agent: Arc<Agent>,
+        input: impl Into<String>,
+        schedule: Schedule,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            agent,
+            input: input.into(),
+            schedule,
+            overlap: OverlapPolicy::default(),
+        }
+    }
+
+    /// What to do when a run is due before the previous one finished
+    /// (default [`OverlapPolicy::Skip`])
+    pub fn overlap(mut self, policy: OverlapPolicy) -> Self {
+        self.overlap = policy;
+        self
+    }
+}
+
+/// Shared pieces each job's loop needs
+#[derive(Clone)]
+struct Context {
+    store: Arc<dyn JobStore>,
+    observers: Arc<Vec<Observer>>,
+}
+
+impl Context {
+    fn notify(&self, event: ScheduleEvent) {
+        for observer in self.observers.iter() {
+            observer(&event);
+        }
+    }
+}
+
+/// A run in progress
+struct Running {
+    run: u64,
+    cancel: CancellationToken,
+    task: JoinHandle<()>,
+}
+
+/// Runs jobs on their schedules
+pub struct Scheduler {
+    jobs: Vec<Job>,
+    store: Arc<dyn JobStore>,
+    observers: Vec<Observer>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler {
+    /// A scheduler with no jobs, keeping job state in memory
+    pub fn new() -> Self {
+        Self {
+            jobs: Vec::new(),
+            store: Arc::new(InMemoryJobStore::new()),
+            observers: Vec::new(),
+        }
+    }
+
+    /// Keep job state in `store`
+    pub fn store(mut self, store: Arc<dyn JobStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    pub fn job(mut self, job: Job) -> Self {
+        self.jobs.push(job);
+        self
+    }
+
+    /// Call `observer` for every [`ScheduleEvent`]
+    pub fn observe(mut self, observer: impl Fn(&ScheduleEvent) + Send + Sync + 'static) -> Self {
+        self.observers.push(Arc::new(observer));
+        self
+    }
+
+    /// Run every job on its schedule until `cancel` is triggered
+    ///
+    /// Cancelling also cancels runs in progress and waits for them to end.
+    pub async fn run(self, cancel: CancellationToken) {
+        let context = Context {
+            store: self.store,
+            observers: Arc::new(self.observers),
+        };
+        let loops: Vec<_> = self
+            .jobs
+            .into_iter()
+            .map(|job| tokio::spawn(job_loop(job, context.clone(), cancel.clone())))
+            .collect();
+        for job_loop in loops {
+            let _ = job_loop.await;
+        }
+    }
+}
+
+async fn load_state(job: &Job, context: &Context) -> JobState {
+    match context.store.load(&job.name).await {
+        Ok(state) => state.unwrap_or_default(),
+        Err(e) => {
+            tracing::warn!("scheduler: can't load state of '{}': {}", job.name, e);
+            JobState::default()
+        }
+    }
+}
+
+async fn save_state(job: &str, state: &Arc<Mutex<JobState>>, context: &Context) {
+    let snapshot = state.lock().unwrap().clone();
+    if let Err(e) = context.store.save(job, &snapshot).await {
+        tracing::warn!("scheduler: can't save state of '{}': {}", job, e);
+    }
+}
+
+/// Start one run of `job` in the background
+async fn start_run(job: &Job, state: &Arc<Mutex<JobState>>, context: &Context) -> Running {
+    let run = {
+        let mut state = state.lock().unwrap();
+        state.runs += 1;
+        state.last_started = Some(Utc::now());
+        state.runs
+    };
+    save_state(&job.name, state, context).await;
+    context.notify(ScheduleEvent::RunStarted {
+        job: job.name.clone(),
+        run,
+    });
+
+    let cancel = CancellationToken::new();
+    let (name, agent, input) = (job.name.clone(), job.agent.clone(), job.input.clone());
+    let (state, context, token) = (state.clone(), context.clone(), cancel.clone());
+    let task = tokio::spawn(async move {
+        let started = std::time::Instant::now();
+        let outcome = match agent.run_cancellable(input, token).await {
+            Ok(output) => RunOutcome::Succeeded { output },
+            Err(e) => RunOutcome::Failed {
+                error: e.to_string(),
+            },
+        };
+        {
+            let mut state = state.lock().unwrap();
+            state.last_finished = Some(Utc::now());
+            state.last_outcome = Some(outcome.clone());
+        }
+        save_state(&name, &state, &context).await;
+        context.notify(ScheduleEvent::RunFinished {
+            job: name,
+            run,
+            outcome,
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+    });
+    Running { run, cancel, task }
+}
+
+async fn job_loop(job: Job, context: Context, cancel: CancellationToken) {
+    let state = Arc::new(Mutex::new(load_state(&job, &context).await));
+    let mut running: Option<Running> = None;
+    // Start of the latest slot, run or skipped, so intervals keep their rhythm
+    let mut last_slot = state.lock().unwrap().last_started;
+    loop {
+        let Some(next) = job.schedule.next_run(Utc::now(), last_slot) else {
+            tracing::warn!("scheduler: '{}' will never run again", job.name);
+            break;
+        };
+        let wait = (next - Utc::now()).to_std().unwrap_or_default();
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep(wait) => {}
+        }
+        last_slot = Some(next);
+
+        if let Some(previous) = running.take_if(|previous| !previous.task.is_finished()) {
+            match job.overlap {
+                OverlapPolicy::Skip => {
+                    state.lock().unwrap().skipped += 1;
+                    context.notify(ScheduleEvent::RunSkipped {
+                        job: job.name.clone(),
+                    });
+                    running = Some(previous);
+                    continue;
+                }
+                OverlapPolicy::Queue => {
+                    let mut task = previous.task;
+                    tokio::select! {
+                        _ = cancel.cancelled() => {
+                            previous.cancel.cancel();
+                            let _ = task.await;
+                            break;
+                        }
+                        _ = &mut task => {}
+                    }
+                }
+                OverlapPolicy::CancelPrevious => {
+                    previous.cancel.cancel();
+                    let _ = previous.task.await;
+                    context.notify(ScheduleEvent::RunReplaced {
+                        job: job.name.clone(),
+                        run: previous.run,
+                    });
+                }
+            }
+        }
+        running = Some(start_run(&job, &state, &context).await);
+    }
+
+    if let Some(previous) = running {
+        previous.cancel.cancel();
+        let _ = previous.task.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_agent;
+    use crate::provider::MockProvider;
+
+    fn slow_agent(delay: Duration) -> Arc<Agent> {
+        let provider = MockProvider::new("report").latency(delay);
+        Arc::new(create_agent("reporter").with_provider(Box::new(provider)))
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        // Weekdays at 09:00; 2025-01-03 is a Friday
+        let cron: CronSchedule = "0 9 * * 1-5".parse().unwrap();
+        assert_eq!(
+            cron.next_after(utc(2025, 1, 3, 8, 59)),
+            Some(utc(2025, 1, 3, 9, 0))
+        );
+        assert_eq!(
+            cron.next_after(utc(2025, 1, 3, 9, 0)),
+            Some(utc(2025, 1, 6, 9, 0))
+        );
+
+        let quarter: CronSchedule = "*/15 * * * *".parse().unwrap();
+        assert_eq!(
+            quarter.next_after(utc(2025, 12, 31, 23, 50)),
+            Some(utc(2026, 1, 1, 0, 0))
+        );
+        // Both day fields restricted: the 1st or any Sunday
+        let either: CronSchedule = "0 0 1 * 7".parse().unwrap();
+        assert_eq!(
+            either.next_after(utc(2025, 1, 2, 0, 0)),
+            Some(utc(2025, 1, 5, 0, 0))
+        );
+        let monthly: CronSchedule = "@monthly".parse().unwrap();
+        assert_eq!(
+            monthly.next_after(utc(2025, 2, 10, 0, 0)),
+            Some(utc(2025, 3, 1, 0, 0))
+        );
+        let never: CronSchedule = "0 0 31 2 *".parse().unwrap();
+        assert_eq!(never.next_after(utc(2025, 1, 1, 0, 0)), None);
+
+        for bad in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+        ] {
+            assert!(bad.parse::<CronSchedule>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_interval_resumes_from_last_start() {
+        let schedule = Schedule::every(Duration::from_secs(3600));
+        let now = utc(2025, 1, 1, 12, 0);
+        assert_eq!(schedule.next_run(now, None), Some(now));
+        assert_eq!(
+            schedule.next_run(now, Some(utc(2025, 1, 1, 11, 30))),
+            Some(utc(2025, 1, 1, 12, 30))
+        );
+        // A missed slot runs now, once
+        assert_eq!(
+            schedule.next_run(now, Some(utc(2025, 1, 1, 9, 0))),
+            Some(now)
+        );
+    }
+
+    async fn run_for(scheduler: Scheduler, time: Duration) {
+        let cancel = CancellationToken::new();
+        let task = tokio::spawn(scheduler.run(cancel.clone()));
+        tokio::time::sleep(time).await;
+        cancel.cancel();
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_overlap_policies() {
+        let store = Arc::new(InMemoryJobStore::new());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        // Runs take 50ms but are due every 20ms
+        let every = Schedule::every(Duration::from_millis(20));
+        let agent = slow_agent(Duration::from_millis(50));
+        let scheduler = Scheduler::new()
+            .store(store.clone())
+            .job(Job::new("skip", agent.clone(), "go", every.clone()))
+            .job(
+                Job::new("queue", agent.clone(), "go", every.clone()).overlap(OverlapPolicy::Queue),
+            )
+            .job(Job::new("replace", agent, "go", every).overlap(OverlapPolicy::CancelPrevious))
+            .observe(move |event| seen.lock().unwrap().push(event.clone()));
+        run_for(scheduler, Duration::from_millis(180)).await;
+
+        let skip = store.load("skip").await.unwrap().unwrap();
+        assert!(skip.skipped >= 1);
+        let queue = store.load("queue").await.unwrap().unwrap();
+        assert!(queue.runs >= 2);
+        assert_eq!(queue.skipped, 0);
+
+        let events = events.lock().unwrap();
+        assert!(events.iter().any(|e| matches!(
+            e,
+            ScheduleEvent::RunFinished { job, outcome: RunOutcome::Succeeded { output }, .. }
+                if job == "skip" && output == "report"
+        )));
+        assert!(events.iter().any(|e| matches!(
+            e,
+            ScheduleEvent::RunReplaced { job, run: 1 } if job == "replace"
+        )));
+        // Every started run reported how it ended
+        let started = events
+            .iter()
+            .filter(|e| matches!(e, ScheduleEvent::RunStarted { .. }))
+            .count();
+        let finished = events
+            .iter()
+            .filter(|e| matches!(e, ScheduleEvent::RunFinished { .. }))
+            .count();
+        assert_eq!(started, finished);
+    }
+
+    #[tokio::test]
+    async fn test_file_store_round_trip() {
+        let path = std::env::temp_dir().join(format!("patinox-jobs-{}.json", uuid::Uuid::new_v4()));
+        let store = FileJobStore::new(&path);
+        assert!(store.load("report").await.unwrap().is_none());
+        let state = JobState {
+            last_started: Some(utc(2025, 1, 1, 9, 0)),
+            runs: 3,
+            ..JobState::default()
+        };
+        store.save("report", &state).await.unwrap();
+        store.save("other", &JobState::default()).await.unwrap();
+        assert_eq!(store.load("report").await.unwrap(), Some(state));
+        let _ = std::fs::remove_file(path);
+    }
+}
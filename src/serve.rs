@@ -0,0 +1,1041 @@
+//! OpenAI-compatible HTTP server
+//!
+//! Enabled with the `server` feature. [`serve`] hosts an agent so that any
+//! OpenAI client SDK can talk to it by pointing its base URL at the server:
+//!
+//! - `POST /v1/chat/completions` - run the agent; earlier messages become
+//!   conversation history and the last user message is the input. With
+//!   `"stream": true` the reply is sent as `chat.completion.chunk` server-sent
+//!   events ending in `data: [DONE]`.
+//! - `GET /v1/models` - lists the agent as the only model
+//! - `GET /healthz` - liveness check, with capability warnings (see
+//!   [`capabilities`](crate::provider::capabilities))
+//! - `GET /info` - version, features, providers and tools (see
+//!   [`info`](crate::info))
+//! - `GET /metrics` - request, tool-call and token counters in Prometheus
+//!   text format
+//! - `GET /ws/chat` - WebSocket chat, see below
+//!
+//! # WebSocket chat
+//!
+//! On connect the server sends `{"type": "session", "version": 1,
+//! "session": "<token>", "resumed": false, "messages": 0}`. Each client
+//! frame is a user message, either plain text or
+//! `{"type": "message", "content": "..."}`; the server answers with the
+//! run's [`AgentEvent`]s as JSON frames, ending in
+//! `completed` or `failed`. Conversation history lives on the server per
+//! session, so a client that reconnects with `/ws/chat?session=<token>`
+//! continues where it left off, provided it connects as the same user;
+//! sessions of other users are never resumed, and their tokens start a
+//! new session instead. The token is a [`Session`] id: with a
+//! session store (see [`Agent::with_session_store`]) sessions are durable,
+//! and the same conversations can be listed and resumed from the CLI.
+//! Without one they stay in memory and are dropped after an hour idle.
+//!
+//! `version` is the [`PROTOCOL_VERSION`] of the frames. It goes up when a
+//! frame changes in a way old clients can't read; new event types and new
+//! fields don't change it, so clients should skip what they don't know.
+//! A client built for one version connects with `/ws/chat?version=<n>` and
+//! gets a 400 from a server that speaks another. Rust clients can decode
+//! event frames as [`AgentEvent`].
+//!
+//! `/v1/chat/completions` is stateless, as in OpenAI's API: clients send
+//! the whole conversation with every request.
+//!
+//! The `model` field of a request is ignored; responses name the agent. Its
+//! `user` field becomes the run's user id, as do `/ws/chat?user=<id>` for
+//! WebSocket runs, whose session token becomes their session id (see
+//! [`execution`](crate::execution)). Clients can claim to be anyone that
+//! way, so a server enforcing [quotas](crate::tenancy) should decide users
+//! itself with [`Server::identify`] or [`Server::api_keys`], which override
+//! what clients send. Requests without a user run as
+//! [`ANONYMOUS_USER`](crate::tenancy::ANONYMOUS_USER). Users over their
+//! quota get a 429 with a `Retry-After` header, or a `failed` event over a
+//! WebSocket.
+//! Token usage is the provider's, or estimated when it reports none (see
+//! [`TurnUsage`](crate::events::TurnUsage)).
+//!
+//! # Example
+//! ```ignore
+//! let agent = create_agent("support").with_provider(Box::new(provider));
+//! patinox::serve::serve(agent, "127.0.0.1:8080").await?;
+//! ```
+//! ```text
+//! curl localhost:8080/v1/chat/completions \
+//!   -d '{"model": "support", "messages": [{"role": "user", "content": "hi"}]}'
+//! ```
+
+use crate::events::AgentEvent;
+use crate::execution::ExecutionContext;
+use crate::provider::Message;
+use crate::session::{ExpiringSessionStore, Session, SessionStore};
+use crate::tenancy::{QuotaExceeded, ANONYMOUS_USER};
+use crate::Agent;
+use axum::extract::ws::{Message as WsMessage, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::sse::{Event, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// WebSocket chat sessions unused for this long are dropped
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Counters exposed on `/metrics`
+#[derive(Debug, Default)]
+struct Metrics {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    in_flight: AtomicU64,
+    tool_calls: AtomicU64,
+    prompt_tokens: AtomicU64,
+    completion_tokens: AtomicU64,
+}
+
+impl Metrics {
+    fn record(&self, event: &AgentEvent) {
+        match event {
+            AgentEvent::ToolCallStarted { .. } => {
+                self.tool_calls.fetch_add(1, Ordering::Relaxed);
+            }
+            AgentEvent::TurnFinished { usage, .. } => {
+                self.prompt_tokens
+                    .fetch_add(usage.prompt_tokens as u64, Ordering::Relaxed);
+                self.completion_tokens
+                    .fetch_add(usage.completion_tokens as u64, Ordering::Relaxed);
+            }
+            AgentEvent::Failed { .. } => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+
+    fn render(&self) -> String {
+        let counters = [
+            (
+                "patinox_requests_total",
+                "Chat completion requests",
+                &self.requests,
+            ),
+            (
+                "patinox_request_errors_total",
+                "Runs that failed",
+                &self.errors,
+            ),
+            (
+                "patinox_tool_calls_total",
+                "Tool calls made by the agent",
+                &self.tool_calls,
+            ),
+            (
+                "patinox_prompt_tokens_total",
+                "Prompt tokens used",
+                &self.prompt_tokens,
+            ),
+            (
+                "patinox_completion_tokens_total",
+                "Completion tokens used",
+                &self.completion_tokens,
+            ),
+        ];
+        let mut out = String::new();
+        for (name, help, value) in counters {
+            out.push_str(&format!(
+                "# HELP {name} {help}\n# TYPE {name} counter\n{name} {}\n",
+                value.load(Ordering::Relaxed)
+            ));
+        }
+        out.push_str(&format!(
+            "# HELP patinox_requests_in_flight Runs in progress\n\
+             # TYPE patinox_requests_in_flight gauge\n\
+             patinox_requests_in_flight {}\n",
+            self.in_flight.load(Ordering::Relaxed)
+        ));
+        out
+    }
+}
+
+/// Decrements the in-flight gauge when a run ends or is dropped
+struct InFlight(Arc<Metrics>);
+
+impl InFlight {
+    fn start(metrics: &Arc<Metrics>) -> Self {
+        metrics.requests.fetch_add(1, Ordering::Relaxed);
+        metrics.in_flight.fetch_add(1, Ordering::Relaxed);
+        Self(metrics.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The agent's session store, or in-memory sessions if it has none
+fn session_store(agent: &Agent) -> Arc<dyn SessionStore> {
+    match &agent.sessions {
+        Some(store) => store.clone(),
+        None => Arc::new(ExpiringSessionStore::new(SESSION_IDLE_TIMEOUT)),
+    }
+}
+
+/// Resume the session `token` names for `user`, or start a new one
+///
+/// Unknown tokens, and tokens of sessions owned by someone else, start a
+/// session with a fresh id rather than adopting the client's. Returns the
+/// session and whether it was resumed.
+async fn open_session(
+    store: &dyn SessionStore,
+    agent: &str,
+    token: Option<&str>,
+    user: Option<&str>,
+) -> crate::Result<(Session, bool)> {
+    if let Some(token) = token {
+        match store.load(token).await? {
+            Some(session) if session.belongs_to(user) => return Ok((session, true)),
+            Some(_) => tracing::debug!("Session {} belongs to another user", token),
+            None => {}
+        }
+    }
+    let session = Session::new(agent).owned_by(user.map(str::to_string));
+    store.save(&session).await?;
+    Ok((session, false))
+}
+
+/// Decides the user a request is for from its headers
+pub type Identify = dyn Fn(&HeaderMap) -> Option<String> + Send + Sync;
+
+#[derive(Clone)]
+struct ServerState {
+    agent: Arc<Agent>,
+    metrics: Arc<Metrics>,
+    /// The agent's session store, or idle-expiring in-memory sessions
+    sessions: Arc<dyn SessionStore>,
+    identify: Option<Arc<Identify>>,
+}
+
+impl ServerState {
+    /// The user a request runs as: what the identity hook says if there is
+    /// one, whatever the client `claimed` otherwise
+    fn user(&self, headers: &HeaderMap, claimed: Option<String>) -> Option<String> {
+        match &self.identify {
+            Some(identify) => identify(headers),
+            None => claimed,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatMessage {
+    role: String,
+    #[serde(default)]
+    content: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatRequest {
+    messages: Vec<ChatMessage>,
+    #[serde(default)]
+    stream: bool,
+    /// End-user id, as in OpenAI's API; becomes the run's `user_id`
+    #[serde(default)]
+    user: Option<String>,
+}
+
+/// Message text from a string or an array of `{"type": "text"}` parts
+fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Split a request into history and the final user input
+fn conversation(request: ChatRequest) -> Result<(Vec<Message>, String), String> {
+    let mut messages: Vec<Message> = request
+        .messages
+        .into_iter()
+        .map(|m| Message {
+            content: content_text(&m.content),
+            role: m.role,
+            images: Vec::new(),
+        })
+        .collect();
+    match messages.pop() {
+        Some(last) if last.role == "user" => Ok((messages, last.content)),
+        _ => Err("The last message must have role 'user'".to_string()),
+    }
+}
+
+fn error_response(status: StatusCode, message: impl Into<String>, kind: &str) -> Response {
+    let body = json!({"error": {"message": message.into(), "type": kind}});
+    (status, Json(body)).into_response()
+}
+
+/// The refusal for a user that is out of quota, checked before the run starts
+async fn check_quota(state: &ServerState, context: &ExecutionContext) -> Option<Response> {
+    let tenancy = state.agent.tenancy()?;
+    let user_id = context.user_id.as_deref().unwrap_or(ANONYMOUS_USER);
+    tenancy.check(user_id).await.err().map(|e| {
+        let Some(exceeded) = e.downcast_ref::<QuotaExceeded>() else {
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                e.to_string(),
+                "server_error",
+            );
+        };
+        let retry_after = (exceeded.resets_at - chrono::Utc::now())
+            .num_seconds()
+            .max(1);
+        let mut response = error_response(
+            StatusCode::TOO_MANY_REQUESTS,
+            e.to_string(),
+            "insufficient_quota",
+        );
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after as u64));
+        response
+    })
+}
+
+/// HTTP server for one agent
+///
+/// [`router`], [`serve`] and [`serve_until`] are shorthands for a server
+/// that takes the user a request is for from the client.
+pub struct Server {
+    agent: Arc<Agent>,
+    identify: Option<Arc<Identify>>,
+}
+
+impl Server {
+    pub fn new(agent: Arc<Agent>) -> Self {
+        Self {
+            agent,
+            identify: None,
+        }
+    }
+
+    /// Decide the user of each request from its headers
+    ///
+    /// Overrides the `user` field and `?user=` parameter clients send;
+    /// requests `identify` returns `None` for run as
+    /// [`ANONYMOUS_USER`](crate::tenancy::ANONYMOUS_USER).
+    pub fn identify(
+        mut self,
+        identify: impl Fn(&HeaderMap) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.identify = Some(Arc::new(identify));
+        self
+    }
+
+    /// Identify users by API key: `Authorization: Bearer <key>` runs as
+    /// `users[key]`
+    pub fn api_keys(self, users: HashMap<String, String>) -> Self {
+        self.identify(move |headers| {
+            let key = headers
+                .get(header::AUTHORIZATION)?
+                .to_str()
+                .ok()?
+                .strip_prefix("Bearer ")?;
+            users.get(key.trim()).cloned()
+        })
+    }
+
+    /// Router with all endpoints, for embedding in a larger axum app
+    pub fn router(self) -> Router {
+        let state = ServerState {
+            sessions: session_store(&self.agent),
+            agent: self.agent,
+            metrics: Arc::new(Metrics::default()),
+            identify: self.identify,
+        };
+        Router::new()
+            .route("/v1/chat/completions", post(chat_completions))
+            .route("/v1/models", get(models))
+            .route("/ws/chat", get(ws_chat))
+            .route("/healthz", get(health))
+            .route("/info", get(info))
+            .route("/metrics", get(metrics))
+            .with_state(state)
+    }
+
+    /// Serve on `addr` until the process exits
+    pub async fn serve(self, addr: &str) -> crate::Result<()> {
+        let listener = self.bind(addr).await?;
+        axum::serve(listener, self.router()).await?;
+        Ok(())
+    }
+
+    /// Serve on `addr` until `shutdown` starts, letting open requests finish
+    pub async fn serve_until(
+        self,
+        addr: &str,
+        shutdown: &crate::runtime::Shutdown,
+    ) -> crate::Result<()> {
+        let listener = self.bind(addr).await?;
+        let token = shutdown.token();
+        axum::serve(listener, self.router())
+            .with_graceful_shutdown(async move { token.cancelled().await })
+            .await?;
+        tracing::info!("Server stopped");
+        Ok(())
+    }
+
+    async fn bind(&self, addr: &str) -> crate::Result<tokio::net::TcpListener> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        tracing::info!(
+            "Serving agent '{}' on http://{}",
+            self.agent.config.name,
+            listener.local_addr()?
+        );
+        Ok(listener)
+    }
+}
+
+/// Router with all endpoints, for embedding in a larger axum app
+pub fn router(agent: Arc<Agent>) -> Router {
+    Server::new(agent).router()
+}
+
+/// Serve `agent` on `addr` until the process exits
+pub async fn serve(agent: Agent, addr: &str) -> crate::Result<()> {
+    Server::new(Arc::new(agent)).serve(addr).await
+}
+
+/// Serve `agent` on `addr` until `shutdown` starts, letting open requests
+/// finish
+pub async fn serve_until(
+    agent: Agent,
+    addr: &str,
+    shutdown: &crate::runtime::Shutdown,
+) -> crate::Result<()> {
+    Server::new(Arc::new(agent))
+        .serve_until(addr, shutdown)
+        .await
+}
+
+async fn metrics(State(state): State<ServerState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
+
+/// Liveness, plus configuration problems such as tools the model can't use
+async fn health(State(state): State<ServerState>) -> Json<Value> {
+    Json(json!({
+        "status": "ok",
+        "warnings": state.agent.capability_warnings(),
+    }))
+}
+
+async fn info(State(state): State<ServerState>) -> Json<crate::info::RuntimeInfo> {
+    Json(state.agent.runtime_info())
+}
+
+async fn models(State(state): State<ServerState>) -> Json<Value> {
+    Json(json!({
+        "object": "list",
+        "data": [{
+            "id": state.agent.config.name,
+            "object": "model",
+            "created": 0,
+            "owned_by": "patinox"
+        }]
+    }))
+}
+
+async fn chat_completions(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let request: ChatRequest = match serde_json::from_str(&body) {
+        Ok(request) => request,
+        Err(e) => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                e.to_string(),
+                "invalid_request_error",
+            )
+        }
+    };
+    let stream = request.stream;
+    let context = ExecutionContext {
+        user_id: state.user(&headers, request.user.clone()),
+        ..ExecutionContext::default()
+    };
+    let (history, input) = match conversation(request) {
+        Ok(conversation) => conversation,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e, "invalid_request_error"),
+    };
+    if let Some(response) = check_quota(&state, &context).await {
+        return response;
+    }
+
+    if stream {
+        stream_completion(state, context, history, input).into_response()
+    } else {
+        complete(state, context, history, input).await
+    }
+}
+
+async fn complete(
+    state: ServerState,
+    context: ExecutionContext,
+    history: Vec<Message>,
+    input: String,
+) -> Response {
+    let _in_flight = InFlight::start(&state.metrics);
+    let (mut prompt_tokens, mut completion_tokens) = (0, 0);
+    let mut outcome = Err("Run ended without a result".to_string());
+
+    let mut events = Box::pin(state.agent.execute_streaming_in(context, history, input));
+    while let Some(event) = events.next().await {
+        state.metrics.record(&event);
+        match event {
+            AgentEvent::TurnFinished { usage, .. } => {
+                prompt_tokens += usage.prompt_tokens;
+                completion_tokens += usage.completion_tokens;
+            }
+            AgentEvent::Completed { output } => outcome = Ok(output),
+            AgentEvent::Failed { error } => outcome = Err(error),
+            _ => {}
+        }
+    }
+
+    match outcome {
+        Ok(output) => Json(json!({
+            "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
+            "object": "chat.completion",
+            "created": chrono::Utc::now().timestamp(),
+            "model": state.agent.config.name,
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": output},
+                "finish_reason": "stop"
+            }],
+            "usage": {
+                "prompt_tokens": prompt_tokens,
+                "completion_tokens": completion_tokens,
+                "total_tokens": prompt_tokens + completion_tokens
+            }
+        }))
+        .into_response(),
+        Err(error) => error_response(StatusCode::INTERNAL_SERVER_ERROR, error, "server_error"),
+    }
+}
+
+/// Version of the `/ws/chat` frames
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Deserialize)]
+struct WsParams {
+    session: Option<String>,
+    /// End user the socket's runs are for and metered against
+    user: Option<String>,
+    /// Protocol version the client speaks
+    version: Option<u32>,
+}
+
+impl WsParams {
+    /// Why the client's protocol version can't be served, if it can't
+    fn unsupported_version(&self) -> Option<String> {
+        self.version
+            .filter(|version| *version != PROTOCOL_VERSION)
+            .map(|version| {
+                format!(
+                    "Protocol version {} is not supported; this server speaks version {}",
+                    version, PROTOCOL_VERSION
+                )
+            })
+    }
+
+    /// Context of a run in session `session_id`
+    fn context(&self, session_id: &str) -> ExecutionContext {
+        ExecutionContext {
+            user_id: self.user.clone(),
+            ..ExecutionContext::new().session(session_id)
+        }
+    }
+}
+
+/// User input from a WebSocket frame: plain text or `{"type": "message"}`
+fn client_message(frame: &str) -> Result<String, String> {
+    let Ok(value) = serde_json::from_str::<Value>(frame) else {
+        return Ok(frame.to_string());
+    };
+    match (
+        value.get("type").and_then(Value::as_str),
+        value.get("content"),
+    ) {
+        (Some("message"), Some(content)) => Ok(content_text(content)),
+        (Some("message"), None) => Err("Message has no content".to_string()),
+        (Some(other), _) => Err(format!("Unknown message type '{}'", other)),
+        // JSON that isn't an envelope, e.g. a bare string or number
+        (None, _) => Ok(frame.to_string()),
+    }
+}
+
+async fn ws_chat(
+    ws: WebSocketUpgrade,
+    Query(mut params): Query<WsParams>,
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(message) = params.unsupported_version() {
+        return error_response(StatusCode::BAD_REQUEST, message, "invalid_request_error");
+    }
+    params.user = state.user(&headers, params.user.take());
+    ws.on_upgrade(move |socket| chat_socket(socket, state, params))
+}
+
+/// The frame that opens a chat, naming its session
+fn session_frame(session: &Session, resumed: bool) -> Value {
+    json!({
+        "type": "session",
+        "version": PROTOCOL_VERSION,
+        "session": session.id,
+        "resumed": resumed,
+        "messages": session.messages.len()
+    })
+}
+
+async fn chat_socket(mut socket: WebSocket, state: ServerState, params: WsParams) {
+    let send = |value: Value| WsMessage::Text(value.to_string().into());
+
+    let opened = open_session(
+        state.sessions.as_ref(),
+        &state.agent.config.name,
+        params.session.as_deref(),
+        params.user.as_deref(),
+    )
+    .await;
+    let (mut session, resumed) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            let error = json!({"type": "error", "message": e.to_string()});
+            let _ = socket.send(send(error)).await;
+            return;
+        }
+    };
+    if socket
+        .send(send(session_frame(&session, resumed)))
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    while let Some(Ok(frame)) = socket.recv().await {
+        let input = match frame {
+            WsMessage::Text(text) => client_message(text.as_str()),
+            WsMessage::Close(_) => break,
+            _ => continue,
+        };
+        let input = match input {
+            Ok(input) => input,
+            Err(message) => {
+                if socket
+                    .send(send(json!({"type": "error", "message": message})))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                continue;
+            }
+        };
+
+        let _in_flight = InFlight::start(&state.metrics);
+        let mut events = Box::pin(state.agent.execute_streaming_in(
+            params.context(&session.id),
+            session.messages.clone(),
+            input.clone(),
+        ));
+        while let Some(event) = events.next().await {
+            state.metrics.record(&event);
+            session.record_event(&event);
+            if let AgentEvent::Completed { output } = &event {
+                session.push_turn(input.clone(), output.clone());
+                if let Err(e) = state.sessions.save(&session).await {
+                    tracing::warn!("Can't save session {}: {}", session.id, e);
+                }
+            }
+            let frame = serde_json::to_value(&event).unwrap_or(Value::Null);
+            // A closed socket drops the run; the session stays for a reconnect
+            if socket.send(send(frame)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn stream_completion(
+    state: ServerState,
+    context: ExecutionContext,
+    history: Vec<Message>,
+    input: String,
+) -> Sse<impl futures::Stream<Item = Result<Event, Infallible>>> {
+    let (sender, receiver) = futures::channel::mpsc::unbounded::<Event>();
+    let id = format!("chatcmpl-{}", uuid::Uuid::new_v4());
+    let created = chrono::Utc::now().timestamp();
+    let model = state.agent.config.name.clone();
+    let chunk = move |delta: Value, finish_reason: Option<&str>| {
+        Event::default().data(
+            json!({
+                "id": id,
+                "object": "chat.completion.chunk",
+                "created": created,
+                "model": model,
+                "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]
+            })
+            .to_string(),
+        )
+    };
+
+    // The run needs the agent for its whole lifetime, so it gets its own task;
+    // it stops when the client disconnects and sends start failing
+    tokio::spawn(async move {
+        let _in_flight = InFlight::start(&state.metrics);
+        if sender
+            .unbounded_send(chunk(json!({"role": "assistant"}), None))
+            .is_err()
+        {
+            return;
+        }
+        let mut events = Box::pin(state.agent.execute_streaming_in(context, history, input));
+        while let Some(event) = events.next().await {
+            state.metrics.record(&event);
+            let sent = match event {
+                AgentEvent::Completed { output } => sender
+                    .unbounded_send(chunk(json!({"content": output}), None))
+                    .and_then(|_| sender.unbounded_send(chunk(json!({}), Some("stop")))),
+                AgentEvent::Failed { error } => sender.unbounded_send(Event::default().data(
+                    json!({"error": {"message": error, "type": "server_error"}}).to_string(),
+                )),
+                _ => Ok(()),
+            };
+            if sent.is_err() {
+                return;
+            }
+        }
+        let _ = sender.unbounded_send(Event::default().data("[DONE]"));
+    });
+
+    Sse::new(receiver.map(Ok))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_agent;
+    use crate::provider::MockProvider;
+    use crate::tenancy::{InMemoryQuotaStore, Quota, Tenancy, Usage};
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        router(Arc::new(
+            create_agent("echo").with_provider(Box::new(MockProvider::new("pong"))),
+        ))
+    }
+
+    async fn call(app: Router, method: &str, uri: &str, body: Value) -> (StatusCode, String) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_chat_completion() {
+        let body = json!({
+            "model": "anything",
+            "messages": [
+                {"role": "user", "content": "hi"},
+                {"role": "assistant", "content": "hello"},
+                {"role": "user", "content": [{"type": "text", "text": "ping"}]}
+            ]
+        });
+        let (status, text) = call(app(), "POST", "/v1/chat/completions", body).await;
+        assert_eq!(status, StatusCode::OK);
+        let response: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(response["object"], "chat.completion");
+        assert_eq!(response["model"], "echo");
+        assert_eq!(response["choices"][0]["message"]["content"], "pong");
+        assert!(response["usage"]["prompt_tokens"].as_u64().unwrap() > 0);
+
+        let bad = json!({"messages": [{"role": "assistant", "content": "hi"}]});
+        let (status, text) = call(app(), "POST", "/v1/chat/completions", bad).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(text.contains("invalid_request_error"));
+    }
+
+    #[tokio::test]
+    async fn test_quota_exceeded_is_429() {
+        let tenancy = Tenancy::new(Arc::new(InMemoryQuotaStore::new()))
+            .default_quota(Quota::new().requests_per_day(1));
+        let app = router(Arc::new(
+            create_agent("echo")
+                .with_provider(Box::new(MockProvider::new("pong")))
+                .with_tenancy(tenancy),
+        ));
+        let body = json!({"messages": [{"role": "user", "content": "ping"}], "user": "u-1"});
+        let (status, _) = call(app.clone(), "POST", "/v1/chat/completions", body.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, text) = call(app.clone(), "POST", "/v1/chat/completions", body).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert!(text.contains("insufficient_quota"));
+
+        // Requests without a user share one quota
+        let anonymous = json!({"messages": [{"role": "user", "content": "ping"}]});
+        let (status, _) = call(
+            app.clone(),
+            "POST",
+            "/v1/chat/completions",
+            anonymous.clone(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(app, "POST", "/v1/chat/completions", anonymous).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_api_keys_override_claimed_users() {
+        let tenancy = Tenancy::new(Arc::new(InMemoryQuotaStore::new()));
+        let agent = Arc::new(
+            create_agent("echo")
+                .with_provider(Box::new(MockProvider::new("pong")))
+                .with_tenancy(tenancy),
+        );
+        let users = HashMap::from([("key-1".to_string(), "u-1".to_string())]);
+        let app = Server::new(agent.clone()).api_keys(users).router();
+
+        // Claims to be u-2, but the key says u-1
+        let body = json!({"messages": [{"role": "user", "content": "ping"}], "user": "u-2"});
+        for key in ["key-1", "stolen"] {
+            let request = Request::builder()
+                .method("POST")
+                .uri("/v1/chat/completions")
+                .header(header::AUTHORIZATION, format!("Bearer {}", key))
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let tenancy = agent.tenancy().unwrap();
+        let requests = |usage: (Usage, Usage)| usage.0.requests;
+        assert_eq!(requests(tenancy.usage("u-1").await.unwrap()), 1);
+        assert_eq!(requests(tenancy.usage("u-2").await.unwrap()), 0);
+        assert_eq!(requests(tenancy.usage(ANONYMOUS_USER).await.unwrap()), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ws_runs_are_metered_per_user() {
+        let tenancy = Tenancy::new(Arc::new(InMemoryQuotaStore::new()))
+            .default_quota(Quota::new().requests_per_day(1));
+        let agent = create_agent("echo")
+            .with_provider(Box::new(MockProvider::new("pong")))
+            .with_tenancy(tenancy);
+        let uri = "/ws/chat?session=s-1&user=u-1".parse().unwrap();
+        let Query(params) = Query::<WsParams>::try_from_uri(&uri).unwrap();
+        let context = params.context("s-1");
+        assert_eq!(context.session_id.as_deref(), Some("s-1"));
+
+        let last = |events: Vec<AgentEvent>| events.into_iter().last().unwrap();
+        let events = agent
+            .execute_streaming_in(context.clone(), Vec::new(), "ping")
+            .collect()
+            .await;
+        assert!(matches!(last(events), AgentEvent::Completed { .. }));
+        let events = agent
+            .execute_streaming_in(context, Vec::new(), "ping")
+            .collect()
+            .await;
+        assert!(
+            matches!(last(events), AgentEvent::Failed { error } if error.contains("over quota"))
+        );
+        let (today, _) = agent.tenancy().unwrap().usage("u-1").await.unwrap();
+        assert_eq!(today.requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_completion() {
+        let body = json!({"messages": [{"role": "user", "content": "ping"}], "stream": true});
+        let (status, text) = call(app(), "POST", "/v1/chat/completions", body).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let data: Vec<&str> = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .collect();
+        assert_eq!(data.len(), 4);
+        let content: Value = serde_json::from_str(data[1]).unwrap();
+        assert_eq!(content["object"], "chat.completion.chunk");
+        assert_eq!(content["choices"][0]["delta"]["content"], "pong");
+        let finish: Value = serde_json::from_str(data[2]).unwrap();
+        assert_eq!(finish["choices"][0]["finish_reason"], "stop");
+        assert_eq!(data[3], "[DONE]");
+    }
+
+    #[test]
+    fn test_client_message() {
+        assert_eq!(client_message("hello"), Ok("hello".to_string()));
+        assert_eq!(
+            client_message(r#"{"type": "message", "content": "hi"}"#),
+            Ok("hi".to_string())
+        );
+        assert_eq!(client_message("42"), Ok("42".to_string()));
+        assert!(client_message(r#"{"type": "ping"}"#).is_err());
+        assert!(client_message(r#"{"type": "message"}"#).is_err());
+    }
+
+    #[test]
+    fn test_ws_protocol_version() {
+        let params = |uri: &str| {
+            let Query(params) = Query::<WsParams>::try_from_uri(&uri.parse().unwrap()).unwrap();
+            params
+        };
+        assert!(params("/ws/chat").unsupported_version().is_none());
+        assert!(params("/ws/chat?version=1").unsupported_version().is_none());
+        let error = params("/ws/chat?version=2").unsupported_version().unwrap();
+        assert!(error.contains("version 1"));
+
+        let frame = session_frame(&Session::new("bot"), false);
+        assert_eq!(frame["type"], "session");
+        assert_eq!(frame["version"], PROTOCOL_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_chat_sessions_resume_and_expire() {
+        let sessions = ExpiringSessionStore::new(Duration::from_secs(60));
+        let (mut session, resumed) = open_session(&sessions, "bot", None, None).await.unwrap();
+        assert!(!resumed);
+        session.push_turn("hi", "hello");
+        sessions.save(&session).await.unwrap();
+
+        let (again, resumed) = open_session(&sessions, "bot", Some(&session.id), None)
+            .await
+            .unwrap();
+        assert!(resumed);
+        assert_eq!(again.id, session.id);
+        assert_eq!(again.messages.len(), 2);
+
+        // Unknown tokens start a fresh session
+        let (other, resumed) = open_session(&sessions, "bot", Some("stale"), None)
+            .await
+            .unwrap();
+        assert!(!resumed);
+        assert_ne!(other.id, "stale");
+        assert_eq!(sessions.list(None, 10).await.unwrap().items.len(), 2);
+
+        let expiring = ExpiringSessionStore::new(Duration::ZERO);
+        let (session, _) = open_session(&expiring, "bot", None, None).await.unwrap();
+        assert!(expiring.load(&session.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_chat_sessions_are_not_resumed_by_other_users() {
+        let sessions = ExpiringSessionStore::new(Duration::from_secs(60));
+        let (mut session, _) = open_session(&sessions, "bot", None, Some("alice"))
+            .await
+            .unwrap();
+        assert_eq!(session.user_id.as_deref(), Some("alice"));
+        session.push_turn("my secret", "noted");
+        sessions.save(&session).await.unwrap();
+
+        // Bob's copy of the token starts a session of his own
+        let (other, resumed) = open_session(&sessions, "bot", Some(&session.id), Some("bob"))
+            .await
+            .unwrap();
+        assert!(!resumed);
+        assert_ne!(other.id, session.id);
+        assert!(other.messages.is_empty());
+        assert_eq!(other.user_id.as_deref(), Some("bob"));
+
+        // So does a client without a user
+        let (_, resumed) = open_session(&sessions, "bot", Some(&session.id), None)
+            .await
+            .unwrap();
+        assert!(!resumed);
+
+        let (again, resumed) = open_session(&sessions, "bot", Some(&session.id), Some("alice"))
+            .await
+            .unwrap();
+        assert!(resumed);
+        assert_eq!(again.messages.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_ws_sessions_use_the_agent_store() {
+        use crate::session::InMemorySessionStore;
+
+        let store = Arc::new(InMemorySessionStore::new());
+        let agent = create_agent("bot").with_session_store(store.clone());
+        let sessions = session_store(&agent);
+
+        let (session, _) = open_session(sessions.as_ref(), "bot", None, None)
+            .await
+            .unwrap();
+        // The session outlives the server's handle on the store
+        drop(sessions);
+        let (resumed, was_resumed) = open_session(store.as_ref(), "bot", Some(&session.id), None)
+            .await
+            .unwrap();
+        assert!(was_resumed);
+        assert_eq!(resumed.id, session.id);
+        assert_eq!(store.list(None, 10).await.unwrap().items.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_health_models_and_metrics() {
+        let app = app();
+        let (status, text) = call(app.clone(), "GET", "/healthz", Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<Value>(&text).unwrap(),
+            json!({"status": "ok", "warnings": []})
+        );
+
+        let (_, text) = call(app.clone(), "GET", "/info", Value::Null).await;
+        let info: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(info["agent"], "echo");
+        assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+
+        let (_, text) = call(app.clone(), "GET", "/v1/models", Value::Null).await;
+        assert!(text.contains("\"id\":\"echo\""));
+
+        let body = json!({"messages": [{"role": "user", "content": "ping"}]});
+        call(app.clone(), "POST", "/v1/chat/completions", body).await;
+        let (_, text) = call(app, "GET", "/metrics", Value::Null).await;
+        assert!(text.contains("patinox_requests_total 1\n"));
+        assert!(text.contains("patinox_requests_in_flight 0\n"));
+    }
+}
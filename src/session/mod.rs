@@ -0,0 +1,562 @@
+//! Durable conversation sessions
+//!
+//! A [`Session`] is one conversation with an agent: its messages, the tool
+//! calls made along the way and the tokens spent. Sessions are saved to a
+//! [`SessionStore`] after every turn, so a conversation can be listed,
+//! resumed from another process or device, exported and deleted.
+//!
+//! [`InMemorySessionStore`] is the built-in store. With the `sqlite`
+//! feature, [`sqlite::SqliteSessionStore`] keeps sessions in a SQLite file;
+//! other backends implement [`SessionStore`].
+//!
+//! A session can be owned by a user, the run's `user_id` when it was
+//! created. Front ends that resume sessions on a client's request check
+//! [`Session::belongs_to`] first, so one user can't continue another's
+//! conversation.
+//!
+//! Listing is paginated: [`SessionStore::list`] returns a [`Page`] whose
+//! `next_cursor` fetches the page after it. Cursors are opaque to callers.
+//!
+//! Durable stores keep sessions until they are deleted. A [`SessionSweeper`]
+//! deletes the ones idle for longer than a retention period, once or on an
+//! interval, and can report them without deleting in a dry run.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::session::{Session, SessionStore, sqlite::SqliteSessionStore};
+//!
+//! let store = SqliteSessionStore::open("sessions.db").await?;
+//! let mut session = match store.load(&id).await? {
+//!     Some(session) => session,
+//!     None => Session::new(&agent.config.name),
+//! };
+//! let answer = agent.run_session(&mut session, "And tomorrow?").await?;
+//! store.save(&session).await?;
+//! ```
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+mod sweep;
+
+pub use sweep::{SessionSweeper, SweepReport};
+
+use crate::events::AgentEvent;
+use crate::provider::Message;
+use crate::transcript::ToolCallRecord;
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Tokens spent over a session's model calls
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionUsage {
+    /// Completed runs (user turns)
+    pub runs: usize,
+    pub model_calls: usize,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+}
+
+/// One conversation with an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub agent: String,
+    /// The user the session belongs to; `None` for sessions started
+    /// without one, such as the CLI's
+    #[serde(default)]
+    pub user_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// User and assistant messages, oldest first
+    pub messages: Vec<Message>,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCallRecord>,
+    #[serde(default)]
+    pub usage: SessionUsage,
+    /// Arguments of tool calls that have started but not finished
+    #[serde(skip)]
+    pending_calls: Vec<(String, Value)>,
+}
+
+impl Session {
+    /// Start an empty session for `agent`
+    pub fn new(agent: impl Into<String>) -> Self {
+        let now = Utc::now();
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            agent: agent.into(),
+            user_id: None,
+            created_at: now,
+            updated_at: now,
+            messages: Vec::new(),
+            tool_calls: Vec::new(),
+            usage: SessionUsage::default(),
+            pending_calls: Vec::new(),
+        }
+    }
+
+    /// Make `user_id` the session's owner
+    pub fn owned_by(mut self, user_id: Option<String>) -> Self {
+        self.user_id = user_id;
+        self
+    }
+
+    /// Whether `user_id` may resume the session: only its owner, and for
+    /// sessions without an owner only runs without a user
+    pub fn belongs_to(&self, user_id: Option<&str>) -> bool {
+        self.user_id.as_deref() == user_id
+    }
+
+    /// Record the tool calls and usage of a run in progress
+    pub fn record_event(&mut self, event: &AgentEvent) {
+        match event {
+            AgentEvent::ToolCallStarted { name, arguments } => {
+                self.pending_calls.push((name.clone(), arguments.clone()));
+            }
+            AgentEvent::ToolCallFinished {
+                name,
+                output,
+                error,
+                duration_ms,
+            } => {
+                let arguments = self
+                    .pending_calls
+                    .iter()
+                    .position(|(pending, _)| pending == name)
+                    .map(|i| self.pending_calls.remove(i).1)
+                    .unwrap_or(Value::Null);
+                self.tool_calls.push(ToolCallRecord {
+                    tool: name.clone(),
+                    arguments,
+                    output: match error {
+                        Some(error) => Err(error.clone()),
+                        None => Ok(output.clone().unwrap_or_default()),
+                    },
+                    duration_ms: *duration_ms,
+                });
+            }
+            AgentEvent::TurnFinished { usage, .. } => {
+                self.usage.model_calls += 1;
+                self.usage.prompt_tokens += usage.prompt_tokens;
+                self.usage.completion_tokens += usage.completion_tokens;
+            }
+            _ => {}
+        }
+    }
+
+    /// Append a completed exchange
+    pub fn push_turn(&mut self, input: impl Into<String>, output: impl Into<String>) {
+        self.messages.push(Message::user(input));
+        self.messages.push(Message::assistant(output));
+        self.usage.runs += 1;
+        self.pending_calls.clear();
+        self.updated_at = Utc::now();
+    }
+
+    pub fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            id: self.id.clone(),
+            agent: self.agent.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            message_count: self.messages.len(),
+            title: self.title(),
+        }
+    }
+
+    /// The first user message, shortened, to tell sessions apart in lists
+    pub fn title(&self) -> String {
+        let first = self
+            .messages
+            .iter()
+            .find(|m| m.role == "user")
+            .map(|m| m.content.trim())
+            .unwrap_or_default();
+        let line = first.lines().next().unwrap_or_default();
+        match line.char_indices().nth(60) {
+            Some((end, _)) => format!("{}...", &line[..end]),
+            None => line.to_string(),
+        }
+    }
+
+    /// Render the conversation as markdown, with tool calls and usage
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# Session {}\n", self.id);
+        let _ = writeln!(
+            out,
+            "Agent `{}`, started {}, last active {}.\n",
+            self.agent,
+            self.created_at.format("%Y-%m-%d %H:%M UTC"),
+            self.updated_at.format("%Y-%m-%d %H:%M UTC")
+        );
+        for message in &self.messages {
+            let _ = writeln!(out, "**{}**: {}\n", message.role, message.content);
+        }
+        if !self.tool_calls.is_empty() {
+            let _ = writeln!(out, "## Tool calls\n");
+            for call in &self.tool_calls {
+                let outcome = match &call.output {
+                    Ok(_) => "ok",
+                    Err(_) => "failed",
+                };
+                let _ = writeln!(
+                    out,
+                    "- `{}({})` {} in {} ms",
+                    call.tool, call.arguments, outcome, call.duration_ms
+                );
+            }
+            out.push('\n');
+        }
+        let _ = writeln!(
+            out,
+            "Usage: {} runs, {} model calls, {} prompt + {} completion tokens",
+            self.usage.runs,
+            self.usage.model_calls,
+            self.usage.prompt_tokens,
+            self.usage.completion_tokens
+        );
+        out
+    }
+}
+
+/// What lists show about a session, without its messages
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub id: String,
+    pub agent: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub message_count: usize,
+    pub title: String,
+}
+
+/// One page of a listing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Pass to the next `list` call for the following page; `None` on the
+    /// last page
+    pub next_cursor: Option<String>,
+}
+
+impl Page<SessionSummary> {
+    /// The page of `summaries` after `cursor`, for stores that hold every
+    /// summary in memory
+    pub fn of_summaries(
+        mut summaries: Vec<SessionSummary>,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> crate::Result<Self> {
+        summaries.sort_by(|a, b| (b.updated_at, &b.id).cmp(&(a.updated_at, &a.id)));
+        let after = cursor.map(parse_cursor).transpose()?;
+        let mut items: Vec<SessionSummary> = summaries
+            .into_iter()
+            .filter(|summary| match &after {
+                Some((time, id)) => (summary.updated_at, &summary.id) < (*time, id),
+                None => true,
+            })
+            .take(limit.max(1) + 1)
+            .collect();
+        let next_cursor = Self::finish(&mut items, limit);
+        Ok(Page { items, next_cursor })
+    }
+
+    /// Trim a page fetched with one summary more than `limit`, returning
+    /// the cursor of the next page if there is one
+    fn finish(items: &mut Vec<SessionSummary>, limit: usize) -> Option<String> {
+        if items.len() <= limit.max(1) {
+            return None;
+        }
+        items.truncate(limit.max(1));
+        items.last().map(cursor_for)
+    }
+}
+
+/// Cursor resuming a listing after `summary`
+fn cursor_for(summary: &SessionSummary) -> String {
+    format!(
+        "{}/{}",
+        summary
+            .updated_at
+            .to_rfc3339_opts(SecondsFormat::Nanos, true),
+        summary.id
+    )
+}
+
+/// The last activity time and id a cursor resumes after
+fn parse_cursor(cursor: &str) -> crate::Result<(DateTime<Utc>, String)> {
+    let (time, id) = cursor
+        .split_once('/')
+        .ok_or_else(|| format!("Invalid session cursor: {}", cursor))?;
+    let time = DateTime::parse_from_rfc3339(time)
+        .map_err(|_| format!("Invalid session cursor: {}", cursor))?;
+    Ok((time.with_timezone(&Utc), id.to_string()))
+}
+
+/// Where sessions are kept
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    /// Insert or replace `session`
+    async fn save(&self, session: &Session) -> crate::Result<()>;
+
+    async fn load(&self, id: &str) -> crate::Result<Option<Session>>;
+
+    /// Up to `limit` sessions after `cursor`, most recently active first
+    ///
+    /// Start with no cursor, then pass each page's `next_cursor`.
+    async fn list(&self, cursor: Option<&str>, limit: usize)
+        -> crate::Result<Page<SessionSummary>>;
+
+    /// Remove a session; returns whether it existed
+    async fn delete(&self, id: &str) -> crate::Result<bool>;
+}
+
+/// [`SessionStore`] that lives as long as the process
+#[derive(Debug, Default)]
+pub struct InMemorySessionStore {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl InMemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl SessionStore for InMemorySessionStore {
+    async fn save(&self, session: &Session) -> crate::Result<()> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(session.id.clone(), session.clone());
+        Ok(())
+    }
+
+    async fn load(&self, id: &str) -> crate::Result<Option<Session>> {
+        Ok(self.sessions.lock().unwrap().get(id).cloned())
+    }
+
+    async fn list(
+        &self,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> crate::Result<Page<SessionSummary>> {
+        let summaries = self
+            .sessions
+            .lock()
+            .unwrap()
+            .values()
+            .map(Session::summary)
+            .collect();
+        Page::of_summaries(summaries, cursor, limit)
+    }
+
+    async fn delete(&self, id: &str) -> crate::Result<bool> {
+        Ok(self.sessions.lock().unwrap().remove(id).is_some())
+    }
+}
+
+/// A session and when it was last used
+#[derive(Debug)]
+struct IdleSession {
+    session: Session,
+    last_seen: Instant,
+}
+
+/// [`SessionStore`] in memory that drops sessions unused for `idle_timeout`
+///
+/// For front ends that keep conversations for agents without a store of
+/// their own, such as the HTTP server's WebSocket chats and the Slack bot.
+#[derive(Debug)]
+pub struct ExpiringSessionStore {
+    sessions: Mutex<HashMap<String, IdleSession>>,
+    idle_timeout: Duration,
+}
+
+impl ExpiringSessionStore {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            idle_timeout,
+        }
+    }
+
+    /// The live sessions, after dropping idle ones
+    fn live(&self) -> std::sync::MutexGuard<'_, HashMap<String, IdleSession>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, idle| idle.last_seen.elapsed() < self.idle_timeout);
+        sessions
+    }
+}
+
+#[async_trait]
+impl SessionStore for ExpiringSessionStore {
+    async fn save(&self, session: &Session) -> crate::Result<()> {
+        self.live().insert(
+            session.id.clone(),
+            IdleSession {
+                session: session.clone(),
+                last_seen: Instant::now(),
+            },
+        );
+        Ok(())
+    }
+
+    async fn load(&self, id: &str) -> crate::Result<Option<Session>> {
+        Ok(self.live().get_mut(id).map(|idle| {
+            idle.last_seen = Instant::now();
+            idle.session.clone()
+        }))
+    }
+
+    async fn list(
+        &self,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> crate::Result<Page<SessionSummary>> {
+        let summaries = self
+            .live()
+            .values()
+            .map(|idle| idle.session.summary())
+            .collect();
+        Page::of_summaries(summaries, cursor, limit)
+    }
+
+    async fn delete(&self, id: &str) -> crate::Result<bool> {
+        Ok(self.live().remove(id).is_some())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::events::TurnUsage;
+    use serde_json::json;
+
+    /// A session with one turn that called a tool
+    pub(crate) fn weather_session() -> Session {
+        let mut session = Session::new("assistant");
+        for event in [
+            AgentEvent::ToolCallStarted {
+                name: "weather".to_string(),
+                arguments: json!({"city": "Oslo"}),
+            },
+            AgentEvent::ToolCallFinished {
+                name: "weather".to_string(),
+                output: Some("4°C, rain".to_string()),
+                error: None,
+                duration_ms: 12,
+            },
+            AgentEvent::TurnFinished {
+                turn: 1,
+                usage: TurnUsage {
+                    prompt_tokens: 40,
+                    completion_tokens: 8,
+                    ..TurnUsage::default()
+                },
+                duration_ms: 30,
+            },
+        ] {
+            session.record_event(&event);
+        }
+        session.push_turn("What's the weather in Oslo?", "Rainy, 4°C.");
+        session
+    }
+
+    #[test]
+    fn test_session_records_turns() {
+        let session = weather_session();
+        assert_eq!(session.messages.len(), 2);
+        assert_eq!(session.tool_calls[0].arguments, json!({"city": "Oslo"}));
+        assert_eq!(
+            session.usage,
+            SessionUsage {
+                runs: 1,
+                model_calls: 1,
+                prompt_tokens: 40,
+                completion_tokens: 8,
+            }
+        );
+        assert_eq!(session.title(), "What's the weather in Oslo?");
+
+        let markdown = session.to_markdown();
+        assert!(markdown.contains("**assistant**: Rainy, 4°C."));
+        assert!(markdown.contains(r#"- `weather({"city":"Oslo"})` ok in 12 ms"#));
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store() {
+        let store = InMemorySessionStore::new();
+        let older = weather_session();
+        let mut newer = Session::new("assistant");
+        newer.push_turn("hi", "hello");
+        store.save(&older).await.unwrap();
+        store.save(&newer).await.unwrap();
+
+        let ids: Vec<String> = store
+            .list(None, 10)
+            .await
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(ids, vec![newer.id.clone(), older.id.clone()]);
+        let loaded = store.load(&older.id).await.unwrap().unwrap();
+        assert_eq!(loaded.tool_calls, older.tool_calls);
+        assert!(store.delete(&older.id).await.unwrap());
+        assert!(!store.delete(&older.id).await.unwrap());
+        assert!(store.load(&older.id).await.unwrap().is_none());
+    }
+
+    /// Pages through `store` two at a time, returning every id seen
+    pub(crate) async fn page_through(store: &dyn SessionStore) -> Vec<String> {
+        let mut ids = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = store.list(cursor.as_deref(), 2).await.unwrap();
+            assert!(page.items.len() <= 2);
+            ids.extend(page.items.into_iter().map(|s| s.id));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return ids,
+            }
+        }
+    }
+
+    /// Five sessions, three sharing a last-activity time
+    pub(crate) fn five_sessions() -> Vec<Session> {
+        let base = Utc::now();
+        (0..5)
+            .map(|i| {
+                let mut session = Session::new("assistant");
+                session.updated_at = base - chrono::Duration::seconds(i.min(2));
+                session
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_list_pages() {
+        let store = InMemorySessionStore::new();
+        let sessions = five_sessions();
+        for session in &sessions {
+            store.save(session).await.unwrap();
+        }
+
+        let mut ids = page_through(&store).await;
+        assert_eq!(ids.len(), 5);
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 5);
+        assert!(store.list(Some("garbage"), 2).await.is_err());
+    }
+}
@@ -0,0 +1,231 @@
+//! SQLite session store
+//!
+//! Enabled with the `sqlite` feature. Sessions live in one table:
+//!
+//! ```sql
+//! CREATE TABLE sessions (
+//!     id TEXT PRIMARY KEY,
+//!     agent TEXT NOT NULL,
+//!     title TEXT NOT NULL,
+//!     message_count INTEGER NOT NULL,
+//!     created_at TEXT NOT NULL,
+//!     updated_at TEXT NOT NULL,
+//!     data TEXT NOT NULL  -- the whole session as JSON
+//! );
+//! ```
+//!
+//! The summary columns let [`SessionStore::list`] skip decoding messages,
+//! and pages are read from an index on `(updated_at, id)`.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::session::sqlite::SqliteSessionStore;
+//!
+//! let store = SqliteSessionStore::open("sessions.db").await?;
+//! let agent = create_agent("assistant").with_session_store(Arc::new(store));
+//! ```
+
+use super::{parse_cursor, Page, Session, SessionStore, SessionSummary};
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::Row;
+use std::path::Path;
+
+/// [`SessionStore`] backed by a SQLite database
+#[derive(Debug, Clone)]
+pub struct SqliteSessionStore {
+    pool: SqlitePool,
+}
+
+impl SqliteSessionStore {
+    /// Use an existing pool; call [`migrate`](Self::migrate) before first use
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Open (or create) the database file at `path` and its table
+    pub async fn open(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let store = Self::new(SqlitePool::connect_with(options).await?);
+        store.migrate().await?;
+        Ok(store)
+    }
+
+    /// A private database that disappears with the store, for tests
+    pub async fn in_memory() -> crate::Result<Self> {
+        // Each connection to `:memory:` is its own database, so keep one
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await?;
+        let store = Self::new(pool);
+        store.migrate().await?;
+        Ok(store)
+    }
+
+    /// Create the sessions table if missing
+    pub async fn migrate(&self) -> crate::Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS sessions (\
+                id TEXT PRIMARY KEY, \
+                agent TEXT NOT NULL, \
+                title TEXT NOT NULL, \
+                message_count INTEGER NOT NULL, \
+                created_at TEXT NOT NULL, \
+                updated_at TEXT NOT NULL, \
+                data TEXT NOT NULL)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS sessions_updated_at_id_idx ON sessions (updated_at, id)",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Fixed-width RFC 3339, so timestamps sort correctly as text
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+fn parse_time(value: &str) -> crate::Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)?.with_timezone(&Utc))
+}
+
+#[async_trait]
+impl SessionStore for SqliteSessionStore {
+    async fn save(&self, session: &Session) -> crate::Result<()> {
+        let summary = session.summary();
+        sqlx::query(
+            "INSERT INTO sessions \
+                (id, agent, title, message_count, created_at, updated_at, data) \
+             VALUES (?, ?, ?, ?, ?, ?, ?) \
+             ON CONFLICT (id) DO UPDATE SET \
+                agent = excluded.agent, title = excluded.title, \
+                message_count = excluded.message_count, \
+                updated_at = excluded.updated_at, data = excluded.data",
+        )
+        .bind(&summary.id)
+        .bind(&summary.agent)
+        .bind(&summary.title)
+        .bind(summary.message_count as i64)
+        .bind(timestamp(summary.created_at))
+        .bind(timestamp(summary.updated_at))
+        .bind(serde_json::to_string(session)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn load(&self, id: &str) -> crate::Result<Option<Session>> {
+        let row = sqlx::query("SELECT data FROM sessions WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        match row {
+            Some(row) => Ok(Some(serde_json::from_str(row.try_get("data")?)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn list(
+        &self,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> crate::Result<Page<SessionSummary>> {
+        // Newest first; a cursor resumes after the (updated_at, id) it names
+        let (after_time, after_id) = match cursor.map(parse_cursor).transpose()? {
+            Some((time, id)) => (Some(timestamp(time)), Some(id)),
+            None => (None, None),
+        };
+        let rows = sqlx::query(
+            "SELECT id, agent, title, message_count, created_at, updated_at \
+             FROM sessions \
+             WHERE ?1 IS NULL OR updated_at < ?1 OR (updated_at = ?1 AND id < ?2) \
+             ORDER BY updated_at DESC, id DESC LIMIT ?3",
+        )
+        .bind(after_time)
+        .bind(after_id)
+        .bind((limit.max(1) + 1) as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut items = rows
+            .iter()
+            .map(|row| {
+                Ok(SessionSummary {
+                    id: row.try_get("id")?,
+                    agent: row.try_get("agent")?,
+                    title: row.try_get("title")?,
+                    message_count: row.try_get::<i64, _>("message_count")? as usize,
+                    created_at: parse_time(row.try_get("created_at")?)?,
+                    updated_at: parse_time(row.try_get("updated_at")?)?,
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        let next_cursor = Page::finish(&mut items, limit);
+        Ok(Page { items, next_cursor })
+    }
+
+    async fn delete(&self, id: &str) -> crate::Result<bool> {
+        let result = sqlx::query("DELETE FROM sessions WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::tests::weather_session;
+
+    #[tokio::test]
+    async fn test_sqlite_store_round_trip() {
+        let store = SqliteSessionStore::in_memory().await.unwrap();
+        let mut session = weather_session();
+        store.save(&session).await.unwrap();
+
+        // Saving again replaces the row
+        session.push_turn("And tomorrow?", "Sunny.");
+        store.save(&session).await.unwrap();
+
+        let page = store.list(None, 10).await.unwrap();
+        assert_eq!(page.items, vec![session.summary()]);
+        assert_eq!(page.next_cursor, None);
+        let loaded = store.load(&session.id).await.unwrap().unwrap();
+        assert_eq!(loaded.messages.len(), 4);
+        assert_eq!(loaded.tool_calls, session.tool_calls);
+        assert_eq!(loaded.usage, session.usage);
+
+        assert!(store.delete(&session.id).await.unwrap());
+        assert!(store.load(&session.id).await.unwrap().is_none());
+        assert!(store.list(None, 10).await.unwrap().items.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_store_pages() {
+        use crate::session::tests::{five_sessions, page_through};
+
+        let store = SqliteSessionStore::in_memory().await.unwrap();
+        let sessions = five_sessions();
+        for session in &sessions {
+            store.save(session).await.unwrap();
+        }
+
+        let ids = page_through(&store).await;
+        let memory = crate::session::InMemorySessionStore::new();
+        for session in &sessions {
+            memory.save(session).await.unwrap();
+        }
+        // Same order as the in-memory store, every session once
+        assert_eq!(ids, page_through(&memory).await);
+        assert_eq!(ids.len(), 5);
+    }
+}
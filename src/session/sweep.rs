@@ -0,0 +1,161 @@
+//! Removing sessions that have been idle too long
+
+use super::{SessionStore, SessionSummary};
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Sessions listed per page while sweeping
+const SWEEP_PAGE: usize = 100;
+
+/// What a sweep found and removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SweepReport {
+    /// Sessions idle for longer than the retention period
+    pub expired: Vec<SessionSummary>,
+    /// How many of them were deleted; 0 in a dry run
+    pub removed: usize,
+    pub dry_run: bool,
+}
+
+/// Deletes sessions from a [`SessionStore`] once they have been idle for
+/// longer than a retention period
+///
+/// For stores that keep sessions after the process exits, such as
+/// [`SqliteSessionStore`](super::sqlite::SqliteSessionStore). A session's
+/// idle time counts from its last turn (`updated_at`).
+///
+/// # Example
+/// ```ignore
+/// use patinox::session::SessionSweeper;
+///
+/// let sweeper = SessionSweeper::new(store.clone(), Duration::from_secs(30 * 24 * 3600));
+/// shutdown.track(
+///     "session sweeper",
+///     tokio::spawn(sweeper.run(Duration::from_secs(3600), shutdown.token())),
+/// );
+/// ```
+pub struct SessionSweeper {
+    store: Arc<dyn SessionStore>,
+    retention: Duration,
+    dry_run: bool,
+}
+
+impl SessionSweeper {
+    pub fn new(store: Arc<dyn SessionStore>, retention: Duration) -> Self {
+        Self {
+            store,
+            retention,
+            dry_run: false,
+        }
+    }
+
+    /// Report expired sessions without deleting them
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Find the expired sessions and, unless this is a dry run, delete them
+    pub async fn sweep(&self) -> crate::Result<SweepReport> {
+        let Some(cutoff) = chrono::Duration::from_std(self.retention)
+            .ok()
+            .and_then(|retention| Utc::now().checked_sub_signed(retention))
+        else {
+            // A retention too long to represent keeps everything
+            return Ok(self.report(Vec::new(), 0));
+        };
+
+        // Listed in full before deleting, so deletes can't shift the pages
+        let mut expired = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = self.store.list(cursor.as_deref(), SWEEP_PAGE).await?;
+            expired.extend(
+                page.items
+                    .into_iter()
+                    .filter(|summary| summary.updated_at < cutoff),
+            );
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        let mut removed = 0;
+        if !self.dry_run {
+            for summary in &expired {
+                if self.store.delete(&summary.id).await? {
+                    removed += 1;
+                }
+            }
+        }
+        Ok(self.report(expired, removed))
+    }
+
+    /// Sweep every `every` until `cancel` is cancelled, logging each sweep
+    pub async fn run(self, every: Duration, cancel: CancellationToken) {
+        loop {
+            match self.sweep().await {
+                Ok(report) if self.dry_run => tracing::info!(
+                    "sessions: {} expired (dry run, none removed)",
+                    report.expired.len()
+                ),
+                Ok(report) => tracing::info!(
+                    "sessions: removed {} of {} expired",
+                    report.removed,
+                    report.expired.len()
+                ),
+                Err(e) => tracing::warn!("sessions: sweep failed: {}", e),
+            }
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = tokio::time::sleep(every) => {}
+            }
+        }
+    }
+
+    fn report(&self, expired: Vec<SessionSummary>, removed: usize) -> SweepReport {
+        SweepReport {
+            expired,
+            removed,
+            dry_run: self.dry_run,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::{InMemorySessionStore, Session};
+
+    #[tokio::test]
+    async fn test_sweep_removes_idle_sessions() {
+        let store = Arc::new(InMemorySessionStore::new());
+        let mut old = Session::new("bot");
+        old.updated_at = Utc::now() - chrono::Duration::days(40);
+        store.save(&old).await.unwrap();
+        let fresh = Session::new("bot");
+        store.save(&fresh).await.unwrap();
+        let retention = Duration::from_secs(30 * 24 * 3600);
+
+        let report = SessionSweeper::new(store.clone(), retention)
+            .dry_run()
+            .sweep()
+            .await
+            .unwrap();
+        assert_eq!(report.expired.len(), 1);
+        assert_eq!(report.removed, 0);
+        assert!(store.load(&old.id).await.unwrap().is_some());
+
+        let report = SessionSweeper::new(store.clone(), retention)
+            .sweep()
+            .await
+            .unwrap();
+        assert_eq!(report.expired[0].id, old.id);
+        assert_eq!(report.removed, 1);
+        assert!(store.load(&old.id).await.unwrap().is_none());
+        assert!(store.load(&fresh.id).await.unwrap().is_some());
+    }
+}
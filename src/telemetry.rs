@@ -0,0 +1,54 @@
+//! Console and JSON output for tracing spans
+//!
+//! Enabled with the `subscriber` feature. Every agent run is a tracing span
+//! (`agent.run`, with `agent` and `execution_id` fields); model calls
+//! (`provider.complete`) and tool calls (`tool.call`) are its children, so
+//! every log line can be tied back to the run that produced it.
+//! [`init_tracing`] installs a global subscriber that prints them, filtered
+//! by `RUST_LOG` (default `info`).
+//!
+//! Without a subscriber, events still reach the `log` crate, so existing
+//! `env_logger` setups keep working.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::telemetry::{init_tracing, LogFormat};
+//!
+//! // Pretty output on a terminal, one JSON object per line in production
+//! let format = if std::io::stdout().is_terminal() { LogFormat::Pretty } else { LogFormat::Json };
+//! init_tracing(format)?;
+//! ```
+
+use tracing_subscriber::EnvFilter;
+
+/// Filter used when `RUST_LOG` is not set
+const DEFAULT_FILTER: &str = "info";
+
+/// How [`init_tracing`] prints events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Multi-line, colored output for people
+    #[default]
+    Pretty,
+    /// One JSON object per event, with the current span and its parents
+    Json,
+}
+
+/// Install a global subscriber printing spans and events to stderr
+///
+/// Fails if a global subscriber is already set.
+pub fn init_tracing(format: LogFormat) -> crate::Result<()> {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    match format {
+        LogFormat::Pretty => builder.pretty().try_init(),
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .try_init(),
+    }
+}
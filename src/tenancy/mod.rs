@@ -0,0 +1,506 @@
+//! Per-user quotas
+//!
+//! When one agent serves many users, [`Tenancy`] keeps any one of them from
+//! using more than their share. Each user has a [`Quota`] of requests and
+//! tokens per day and spend per month; usage is counted per user in a
+//! [`QuotaStore`], keyed by the `user_id` of the run's
+//! [`ExecutionContext`](crate::execution::ExecutionContext).
+//!
+//! A run is checked when it starts: if the user is at any limit it fails
+//! with [`QuotaExceeded`] (the HTTP server answers 429). A run that starts
+//! under quota finishes even if it goes over. Runs without a `user_id` share
+//! the [`ANONYMOUS_USER`] bucket, so leaving the user out doesn't get around
+//! quotas; give it a quota of its own with [`Tenancy::quota`]. The user id
+//! is trusted as given: when it comes from clients, decide it server-side
+//! (the HTTP server's `Server::identify` does). Token counts are what the
+//! provider reported (see
+//! [`LLMProvider::complete_with_usage`](crate::provider::LLMProvider::complete_with_usage)),
+//! or estimates (see [`tokens`](crate::tokens)) when it reports none, and
+//! spend comes from the [`PricingCatalog`].
+//!
+//! [`InMemoryQuotaStore`] counts for the life of the process. With the
+//! `sqlite` feature, [`sqlite::SqliteQuotaStore`] keeps counts in a SQLite
+//! file shared by every process using it.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::tenancy::{InMemoryQuotaStore, Quota, Tenancy};
+//!
+//! let tenancy = Tenancy::new(Arc::new(InMemoryQuotaStore::new()))
+//!     .default_quota(Quota::new().requests_per_day(100).cost_per_month(5.0))
+//!     .quota("u-enterprise", Quota::unlimited());
+//! let agent = create_agent("support").with_tenancy(tenancy);
+//!
+//! let context = ExecutionContext::new().user("u-42");
+//! match agent.run_with_context("hi", context).await {
+//!     Err(e) if e.is::<QuotaExceeded>() => println!("{}", e),
+//!     other => println!("{:?}", other),
+//! }
+//! ```
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use crate::provider::PricingCatalog;
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::AddAssign;
+use std::sync::{Arc, Mutex};
+
+/// The user runs without a `user_id` are metered as
+pub const ANONYMOUS_USER: &str = "anonymous";
+
+/// Limits for one user; `None` means unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Quota {
+    pub requests_per_day: Option<u64>,
+    pub tokens_per_day: Option<u64>,
+    /// USD per calendar month
+    pub cost_per_month: Option<f64>,
+}
+
+impl Quota {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// No limits at all
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    pub fn requests_per_day(mut self, requests: u64) -> Self {
+        self.requests_per_day = Some(requests);
+        self
+    }
+
+    pub fn tokens_per_day(mut self, tokens: u64) -> Self {
+        self.tokens_per_day = Some(tokens);
+        self
+    }
+
+    pub fn cost_per_month(mut self, usd: f64) -> Self {
+        self.cost_per_month = Some(usd);
+        self
+    }
+
+    /// The first limit that usage `today` and `this_month` is at, if any
+    pub fn reached(&self, today: &Usage, this_month: &Usage) -> Option<QuotaLimit> {
+        if let Some(allowed) = self.requests_per_day.filter(|&a| today.requests >= a) {
+            let used = today.requests;
+            return Some(QuotaLimit::RequestsPerDay { allowed, used });
+        }
+        if let Some(allowed) = self.tokens_per_day.filter(|&a| today.tokens >= a) {
+            let used = today.tokens;
+            return Some(QuotaLimit::TokensPerDay { allowed, used });
+        }
+        if let Some(allowed) = self.cost_per_month.filter(|&a| this_month.cost >= a) {
+            let used = this_month.cost;
+            return Some(QuotaLimit::CostPerMonth { allowed, used });
+        }
+        None
+    }
+}
+
+/// What a user used in one period
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Runs started
+    pub requests: u64,
+    pub tokens: u64,
+    /// USD
+    pub cost: f64,
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.requests += other.requests;
+        self.tokens += other.tokens;
+        self.cost += other.cost;
+    }
+}
+
+/// The limit a user hit, with what they used
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QuotaLimit {
+    RequestsPerDay { allowed: u64, used: u64 },
+    TokensPerDay { allowed: u64, used: u64 },
+    CostPerMonth { allowed: f64, used: f64 },
+}
+
+/// A run refused because its user is out of quota
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuotaExceeded {
+    pub user_id: String,
+    pub limit: QuotaLimit,
+    /// When the exhausted period ends
+    pub resets_at: DateTime<Utc>,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "User '{}' is over quota: ", self.user_id)?;
+        match self.limit {
+            QuotaLimit::RequestsPerDay { allowed, used } => {
+                write!(f, "{} of {} requests per day", used, allowed)?
+            }
+            QuotaLimit::TokensPerDay { allowed, used } => {
+                write!(f, "{} of {} tokens per day", used, allowed)?
+            }
+            QuotaLimit::CostPerMonth { allowed, used } => {
+                write!(f, "${:.2} of ${:.2} per month", used, allowed)?
+            }
+        }
+        write!(
+            f,
+            "; resets at {}",
+            self.resets_at.format("%Y-%m-%d %H:%M UTC")
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// Where usage counts are kept
+///
+/// Periods are UTC dates (`2025-01-31`) for daily counts and months
+/// (`2025-01`) for monthly ones.
+#[async_trait]
+pub trait QuotaStore: Send + Sync {
+    /// Usage of `user_id` in `period`; zero if nothing was recorded
+    async fn usage(&self, user_id: &str, period: &str) -> crate::Result<Usage>;
+
+    /// Add `usage` to the count of `user_id` in `period`
+    async fn add(&self, user_id: &str, period: &str, usage: Usage) -> crate::Result<()>;
+
+    /// Add `usage` to the counts of `user_id` in `day` and `month` unless
+    /// they have [reached](Quota::reached) `quota`
+    ///
+    /// Checking and adding must be one atomic step, so that concurrent
+    /// requests can't all pass the check. Returns the counts in `day` and
+    /// `month` from before the add.
+    async fn try_add(
+        &self,
+        user_id: &str,
+        day: &str,
+        month: &str,
+        quota: &Quota,
+        usage: Usage,
+    ) -> crate::Result<(Usage, Usage)>;
+}
+
+/// [`QuotaStore`] that lives as long as the process
+#[derive(Debug, Default)]
+pub struct InMemoryQuotaStore {
+    usage: Mutex<HashMap<(String, String), Usage>>,
+}
+
+impl InMemoryQuotaStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl QuotaStore for InMemoryQuotaStore {
+    async fn usage(&self, user_id: &str, period: &str) -> crate::Result<Usage> {
+        let key = (user_id.to_string(), period.to_string());
+        Ok(self
+            .usage
+            .lock()
+            .unwrap()
+            .get(&key)
+            .copied()
+            .unwrap_or_default())
+    }
+
+    async fn add(&self, user_id: &str, period: &str, usage: Usage) -> crate::Result<()> {
+        let key = (user_id.to_string(), period.to_string());
+        *self.usage.lock().unwrap().entry(key).or_default() += usage;
+        Ok(())
+    }
+
+    async fn try_add(
+        &self,
+        user_id: &str,
+        day: &str,
+        month: &str,
+        quota: &Quota,
+        usage: Usage,
+    ) -> crate::Result<(Usage, Usage)> {
+        let mut counts = self.usage.lock().unwrap();
+        let key = |period: &str| (user_id.to_string(), period.to_string());
+        let today = counts.get(&key(day)).copied().unwrap_or_default();
+        let this_month = counts.get(&key(month)).copied().unwrap_or_default();
+        if quota.reached(&today, &this_month).is_none() {
+            *counts.entry(key(day)).or_default() += usage;
+            *counts.entry(key(month)).or_default() += usage;
+        }
+        Ok((today, this_month))
+    }
+}
+
+fn day(now: DateTime<Utc>) -> String {
+    now.format("%Y-%m-%d").to_string()
+}
+
+fn month(now: DateTime<Utc>) -> String {
+    now.format("%Y-%m").to_string()
+}
+
+fn next_day(now: DateTime<Utc>) -> DateTime<Utc> {
+    let midnight = now.date_naive().and_hms_opt(0, 0, 0).unwrap_or_default();
+    Utc.from_utc_datetime(&midnight) + Duration::days(1)
+}
+
+fn next_month(now: DateTime<Utc>) -> DateTime<Utc> {
+    let (year, month) = match now.month() {
+        12 => (now.year() + 1, 1),
+        month => (now.year(), month + 1),
+    };
+    let first = NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .unwrap_or_default();
+    Utc.from_utc_datetime(&first)
+}
+
+/// Quotas per user, enforced by [`Agent::with_tenancy`](crate::Agent::with_tenancy)
+pub struct Tenancy {
+    store: Arc<dyn QuotaStore>,
+    default_quota: Quota,
+    quotas: HashMap<String, Quota>,
+    pricing: PricingCatalog,
+}
+
+impl Tenancy {
+    /// Count usage in `store`; every user is unlimited until quotas are set
+    pub fn new(store: Arc<dyn QuotaStore>) -> Self {
+        Self {
+            store,
+            default_quota: Quota::unlimited(),
+            quotas: HashMap::new(),
+            pricing: PricingCatalog::baseline(),
+        }
+    }
+
+    /// Quota of users without one of their own
+    pub fn default_quota(mut self, quota: Quota) -> Self {
+        self.default_quota = quota;
+        self
+    }
+
+    /// Give `user_id` its own quota
+    pub fn quota(mut self, user_id: impl Into<String>, quota: Quota) -> Self {
+        self.quotas.insert(user_id.into(), quota);
+        self
+    }
+
+    /// Prices used for spend (default: the bundled baseline)
+    pub fn pricing(mut self, pricing: PricingCatalog) -> Self {
+        self.pricing = pricing;
+        self
+    }
+
+    /// Quota of users without one of their own
+    pub fn base_quota(&self) -> Quota {
+        self.default_quota
+    }
+
+    /// Users with a quota of their own
+    pub fn users_with_quotas(&self) -> usize {
+        self.quotas.len()
+    }
+
+    pub fn quota_for(&self, user_id: &str) -> Quota {
+        self.quotas
+            .get(user_id)
+            .copied()
+            .unwrap_or(self.default_quota)
+    }
+
+    /// Usage of `user_id` today and this month
+    pub async fn usage(&self, user_id: &str) -> crate::Result<(Usage, Usage)> {
+        let now = Utc::now();
+        Ok((
+            self.store.usage(user_id, &day(now)).await?,
+            self.store.usage(user_id, &month(now)).await?,
+        ))
+    }
+
+    /// Fail with [`QuotaExceeded`] if `user_id` is at any limit
+    pub async fn check(&self, user_id: &str) -> crate::Result<()> {
+        let now = Utc::now();
+        let (today, this_month) = self.usage(user_id).await?;
+        self.enforce(user_id, now, &today, &this_month)
+    }
+
+    /// Count a new request of `user_id` unless it is at any limit
+    ///
+    /// Atomic per [`QuotaStore::try_add`], so concurrent requests can't
+    /// overrun `requests_per_day`.
+    pub(crate) async fn admit(&self, user_id: &str) -> crate::Result<()> {
+        let now = Utc::now();
+        let request = Usage {
+            requests: 1,
+            ..Usage::default()
+        };
+        let (today, this_month) = self
+            .store
+            .try_add(
+                user_id,
+                &day(now),
+                &month(now),
+                &self.quota_for(user_id),
+                request,
+            )
+            .await?;
+        self.enforce(user_id, now, &today, &this_month)
+    }
+
+    /// [`QuotaExceeded`] if `today` and `this_month` reach `user_id`'s quota
+    fn enforce(
+        &self,
+        user_id: &str,
+        now: DateTime<Utc>,
+        today: &Usage,
+        this_month: &Usage,
+    ) -> crate::Result<()> {
+        let Some(limit) = self.quota_for(user_id).reached(today, this_month) else {
+            return Ok(());
+        };
+        let resets_at = match limit {
+            QuotaLimit::RequestsPerDay { .. } | QuotaLimit::TokensPerDay { .. } => next_day(now),
+            QuotaLimit::CostPerMonth { .. } => next_month(now),
+        };
+        Err(QuotaExceeded {
+            user_id: user_id.to_string(),
+            limit,
+            resets_at,
+        }
+        .into())
+    }
+
+    /// Count the tokens and spend of one model call
+    pub(crate) async fn record(
+        &self,
+        user_id: &str,
+        model: &str,
+        prompt_tokens: usize,
+        completion_tokens: usize,
+    ) -> crate::Result<()> {
+        let cost = self
+            .pricing
+            .price(model)
+            .map_or(0.0, |price| price.cost(prompt_tokens, completion_tokens));
+        self.add(
+            user_id,
+            Usage {
+                requests: 0,
+                tokens: (prompt_tokens + completion_tokens) as u64,
+                cost,
+            },
+        )
+        .await
+    }
+
+    async fn add(&self, user_id: &str, usage: Usage) -> crate::Result<()> {
+        let now = Utc::now();
+        self.store.add(user_id, &day(now), usage).await?;
+        self.store.add(user_id, &month(now), usage).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_limits_per_user() {
+        let tenancy = Tenancy::new(Arc::new(InMemoryQuotaStore::new()))
+            .default_quota(Quota::new().requests_per_day(2).tokens_per_day(1_000))
+            .quota("vip", Quota::unlimited());
+        tenancy.admit("u-1").await.unwrap();
+        tenancy.admit("u-1").await.unwrap();
+        let err = tenancy.admit("u-1").await.unwrap_err();
+        let exceeded = err.downcast_ref::<QuotaExceeded>().unwrap();
+        assert_eq!(
+            exceeded.limit,
+            QuotaLimit::RequestsPerDay {
+                allowed: 2,
+                used: 2
+            }
+        );
+        assert!(exceeded.resets_at > Utc::now());
+        assert!(err.to_string().contains("2 of 2 requests per day"));
+
+        // Other users have their own counts
+        tenancy.admit("u-2").await.unwrap();
+        tenancy.record("u-2", "gpt-4o", 900, 100).await.unwrap();
+        let err = tenancy.check("u-2").await.unwrap_err();
+        assert!(err.to_string().contains("1000 of 1000 tokens per day"));
+        for _ in 0..5 {
+            tenancy.admit("vip").await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_admission_stays_within_quota() {
+        let tenancy = Arc::new(
+            Tenancy::new(Arc::new(InMemoryQuotaStore::new()))
+                .default_quota(Quota::new().requests_per_day(5)),
+        );
+        let runs = (0..20).map(|_| {
+            let tenancy = tenancy.clone();
+            tokio::spawn(async move { tenancy.admit("u-1").await.is_ok() })
+        });
+        let admitted = futures::future::join_all(runs).await;
+        assert_eq!(
+            admitted
+                .into_iter()
+                .filter(|ok| *ok.as_ref().unwrap())
+                .count(),
+            5
+        );
+        let (today, this_month) = tenancy.usage("u-1").await.unwrap();
+        assert_eq!((today.requests, this_month.requests), (5, 5));
+    }
+
+    #[tokio::test]
+    async fn test_cost_per_month() {
+        let tenancy = Tenancy::new(Arc::new(InMemoryQuotaStore::new()))
+            .default_quota(Quota::new().cost_per_month(0.01));
+        tenancy.admit("u-1").await.unwrap();
+        // 2K prompt tokens of gpt-4o cost $0.005
+        tenancy.record("u-1", "gpt-4o", 2_000, 0).await.unwrap();
+        tenancy.check("u-1").await.unwrap();
+        tenancy.record("u-1", "gpt-4o", 3_000, 0).await.unwrap();
+        let err = tenancy.check("u-1").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<QuotaExceeded>().unwrap().limit,
+            QuotaLimit::CostPerMonth { .. }
+        ));
+
+        let (today, this_month) = tenancy.usage("u-1").await.unwrap();
+        assert_eq!(today.requests, 1);
+        assert_eq!(this_month.tokens, 5_000);
+    }
+
+    #[test]
+    fn test_periods() {
+        let now = Utc.with_ymd_and_hms(2025, 12, 31, 18, 30, 0).unwrap();
+        assert_eq!(day(now), "2025-12-31");
+        assert_eq!(month(now), "2025-12");
+        assert_eq!(
+            next_day(now),
+            Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            next_month(now),
+            Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! SQLite quota store
+//!
+//! Enabled with the `sqlite` feature. Counts live in one table:
+//!
+//! ```sql
+//! CREATE TABLE quota_usage (
+//!     user_id TEXT NOT NULL,
+//!     period TEXT NOT NULL,  -- `2025-01-31` or `2025-01`
+//!     requests INTEGER NOT NULL,
+//!     tokens INTEGER NOT NULL,
+//!     cost REAL NOT NULL,
+//!     PRIMARY KEY (user_id, period)
+//! );
+//! ```
+//!
+//! Additions are single upserts, so processes sharing the file don't lose
+//! each other's counts, and [`try_add`](QuotaStore::try_add) checks and adds
+//! in one transaction.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::tenancy::{sqlite::SqliteQuotaStore, Tenancy};
+//!
+//! let store = SqliteQuotaStore::open("quotas.db").await?;
+//! let tenancy = Tenancy::new(Arc::new(store)).default_quota(quota);
+//! ```
+
+use super::{Quota, QuotaStore, Usage};
+use async_trait::async_trait;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::Row;
+use sqlx::SqliteExecutor;
+use std::path::Path;
+
+/// [`QuotaStore`] backed by a SQLite database
+#[derive(Debug, Clone)]
+pub struct SqliteQuotaStore {
+    pool: SqlitePool,
+}
+
+impl SqliteQuotaStore {
+    /// Use an existing pool; call [`migrate`](Self::migrate) before first use
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Open (or create) the database file at `path` and its table
+    pub async fn open(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let store = Self::new(SqlitePool::connect_with(options).await?);
+        store.migrate().await?;
+        Ok(store)
+    }
+
+    /// A private database that disappears with the store, for tests
+    pub async fn in_memory() -> crate::Result<Self> {
+        // Each connection to `:memory:` is its own database, so keep one
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await?;
+        let store = Self::new(pool);
+        store.migrate().await?;
+        Ok(store)
+    }
+
+    /// Create the usage table if missing
+    pub async fn migrate(&self) -> crate::Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS quota_usage (\
+                user_id TEXT NOT NULL, \
+                period TEXT NOT NULL, \
+                requests INTEGER NOT NULL, \
+                tokens INTEGER NOT NULL, \
+                cost REAL NOT NULL, \
+                PRIMARY KEY (user_id, period))",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+async fn fetch<'e>(
+    executor: impl SqliteExecutor<'e>,
+    user_id: &str,
+    period: &str,
+) -> crate::Result<Usage> {
+    let row = sqlx::query(
+        "SELECT requests, tokens, cost FROM quota_usage WHERE user_id = ? AND period = ?",
+    )
+    .bind(user_id)
+    .bind(period)
+    .fetch_optional(executor)
+    .await?;
+    match row {
+        Some(row) => Ok(Usage {
+            requests: row.try_get::<i64, _>("requests")? as u64,
+            tokens: row.try_get::<i64, _>("tokens")? as u64,
+            cost: row.try_get("cost")?,
+        }),
+        None => Ok(Usage::default()),
+    }
+}
+
+async fn upsert<'e>(
+    executor: impl SqliteExecutor<'e>,
+    user_id: &str,
+    period: &str,
+    usage: Usage,
+) -> crate::Result<()> {
+    sqlx::query(
+        "INSERT INTO quota_usage (user_id, period, requests, tokens, cost) \
+         VALUES (?, ?, ?, ?, ?) \
+         ON CONFLICT (user_id, period) DO UPDATE SET \
+            requests = requests + excluded.requests, \
+            tokens = tokens + excluded.tokens, \
+            cost = cost + excluded.cost",
+    )
+    .bind(user_id)
+    .bind(period)
+    .bind(usage.requests as i64)
+    .bind(usage.tokens as i64)
+    .bind(usage.cost)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+#[async_trait]
+impl QuotaStore for SqliteQuotaStore {
+    async fn usage(&self, user_id: &str, period: &str) -> crate::Result<Usage> {
+        fetch(&self.pool, user_id, period).await
+    }
+
+    async fn add(&self, user_id: &str, period: &str, usage: Usage) -> crate::Result<()> {
+        upsert(&self.pool, user_id, period, usage).await
+    }
+
+    async fn try_add(
+        &self,
+        user_id: &str,
+        day: &str,
+        month: &str,
+        quota: &Quota,
+        usage: Usage,
+    ) -> crate::Result<(Usage, Usage)> {
+        let mut tx = self.pool.begin().await?;
+        // Writing first takes the database's write lock, so no other
+        // connection can add between the read and the add
+        upsert(&mut *tx, user_id, day, Usage::default()).await?;
+        upsert(&mut *tx, user_id, month, Usage::default()).await?;
+        let today = fetch(&mut *tx, user_id, day).await?;
+        let this_month = fetch(&mut *tx, user_id, month).await?;
+        if quota.reached(&today, &this_month).is_none() {
+            upsert(&mut *tx, user_id, day, usage).await?;
+            upsert(&mut *tx, user_id, month, usage).await?;
+        }
+        tx.commit().await?;
+        Ok((today, this_month))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tenancy::Tenancy;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_sqlite_store_adds_up() {
+        let store = SqliteQuotaStore::in_memory().await.unwrap();
+        assert_eq!(
+            store.usage("u-1", "2025-01").await.unwrap(),
+            Usage::default()
+        );
+        let call = Usage {
+            requests: 1,
+            tokens: 300,
+            cost: 0.5,
+        };
+        store.add("u-1", "2025-01", call).await.unwrap();
+        store.add("u-1", "2025-01", call).await.unwrap();
+        store.add("u-2", "2025-01", call).await.unwrap();
+
+        let usage = store.usage("u-1", "2025-01").await.unwrap();
+        assert_eq!(
+            usage,
+            Usage {
+                requests: 2,
+                tokens: 600,
+                cost: 1.0,
+            }
+        );
+        assert_eq!(store.usage("u-1", "2025-02").await.unwrap().requests, 0);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_admission_stays_within_quota() {
+        let path = std::env::temp_dir().join(format!("patinox-quota-{}.db", uuid::Uuid::new_v4()));
+        let store = SqliteQuotaStore::open(&path).await.unwrap();
+        let tenancy = Tenancy::new(Arc::new(store)).default_quota(Quota::new().requests_per_day(5));
+
+        let admitted = futures::future::join_all((0..20).map(|_| tenancy.admit("u-1"))).await;
+        assert_eq!(admitted.iter().filter(|result| result.is_ok()).count(), 5);
+        let (today, _) = tenancy.usage("u-1").await.unwrap();
+        assert_eq!(today.requests, 5);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+//! Scripted tool doubles
+//!
+//! A [`MockTool`] stands in for one of an agent's real tools in tests. It
+//! returns scripted outputs and errors one per call, then a fixed output if
+//! one is set, and records the arguments of every call. Clones share the
+//! script and the record, so a test keeps a clone to inspect after handing
+//! the tool to an agent.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::testing::MockTool;
+//!
+//! let lookup = MockTool::new("lookup_order", "Find an order")
+//!     .then_output(r#"{"id": 1234, "status": "broken"}"#)
+//!     .then_error("Order service unavailable");
+//! let agent = create_agent("support").tool(lookup.clone());
+//! agent.run("What happened to order 1234?").await?;
+//! assert_eq!(lookup.calls().len(), 1);
+//! ```
+
+use crate::tool::{Tool, ToolResult};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// One scripted tool result
+#[derive(Debug, Clone)]
+enum Output {
+    Text(String),
+    Error(String),
+}
+
+/// Tool returning scripted outputs and recording its calls
+#[derive(Debug, Clone)]
+pub struct MockTool {
+    name: String,
+    description: String,
+    parameters: Value,
+    script: Arc<Mutex<VecDeque<Output>>>,
+    fallback: Option<String>,
+    calls: Arc<Mutex<Vec<Value>>>,
+}
+
+impl MockTool {
+    /// A tool with no script; calls fail until outputs are added
+    pub fn new(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            parameters: json!({"type": "object", "properties": {}, "required": []}),
+            script: Arc::default(),
+            fallback: None,
+            calls: Arc::default(),
+        }
+    }
+
+    /// The JSON schema offered to the model
+    pub fn parameters(mut self, schema: Value) -> Self {
+        self.parameters = schema;
+        self
+    }
+
+    /// Output for every call once the script runs out
+    pub fn returns(mut self, output: impl Into<String>) -> Self {
+        self.fallback = Some(output.into());
+        self
+    }
+
+    /// Append an output to the script
+    pub fn then_output(self, output: impl Into<String>) -> Self {
+        self.script
+            .lock()
+            .unwrap()
+            .push_back(Output::Text(output.into()));
+        self
+    }
+
+    /// Append a failed call to the script
+    pub fn then_error(self, message: impl Into<String>) -> Self {
+        self.script
+            .lock()
+            .unwrap()
+            .push_back(Output::Error(message.into()));
+        self
+    }
+
+    /// Arguments of every call so far, oldest first
+    pub fn calls(&self) -> Vec<Value> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl Tool for MockTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Value {
+        self.parameters.clone()
+    }
+
+    fn execute(&self, args: Value) -> ToolResult {
+        self.calls.lock().unwrap().push(args);
+        match (self.script.lock().unwrap().pop_front(), &self.fallback) {
+            (Some(Output::Text(text)), _) => Ok(text),
+            (Some(Output::Error(message)), _) => Err(message.into()),
+            (None, Some(text)) => Ok(text.clone()),
+            (None, None) => Err(format!("MockTool '{}' script exhausted", self.name).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_then_fallback_and_shared_calls() {
+        let tool = MockTool::new("lookup", "Look it up")
+            .then_output("first")
+            .then_error("down")
+            .returns("later");
+        let handle = tool.clone();
+
+        assert_eq!(tool.execute(json!({"id": 1})).unwrap(), "first");
+        assert_eq!(
+            tool.execute(json!({"id": 2})).unwrap_err().to_string(),
+            "down"
+        );
+        assert_eq!(tool.execute(json!({"id": 3})).unwrap(), "later");
+        assert_eq!(handle.calls()[1], json!({"id": 2}));
+
+        let empty = MockTool::new("lookup", "Look it up");
+        assert!(empty
+            .execute(json!({}))
+            .unwrap_err()
+            .to_string()
+            .contains("exhausted"));
+    }
+}
@@ -0,0 +1,21 @@
+//! Helpers for testing code built on Patinox
+//!
+//! - [`tool_harness`] - schema-driven conformance checks for [`Tool`](crate::Tool)
+//!   implementations
+//! - [`MockProvider`] - a scriptable provider, re-exported from
+//!   [`provider::testing`](crate::provider::testing)
+//! - [`MockTool`] - a scriptable tool that records its calls
+//! - [`simulator`] - a simulated user that holds multi-turn conversations
+//!   with an agent under test
+//! - [`scenario`] - conversations with expected outcomes, in code or TOML
+
+pub mod mock_tool;
+pub mod scenario;
+pub mod simulator;
+pub mod tool_harness;
+
+pub use crate::provider::testing::MockProvider;
+pub use mock_tool::MockTool;
+pub use scenario::{Expectations, Scenario, ScenarioReport, SimulatedUser};
+pub use simulator::{Conversation, ConversationEnd, UserSimulator};
+pub use tool_harness::{check_tool, HarnessReport, ToolHarness};
@@ -0,0 +1,408 @@
+//! Conversation scenarios with expected outcomes
+//!
+//! A [`Scenario`] is a conversation with an agent under test plus what must
+//! hold once it is over. The user's side is either scripted messages or a
+//! simulated user, a second agent with a persona and goals (see
+//! [`simulator`](super::simulator)). [`Expectations`] check the tools the
+//! agent called, its last reply, how many turns it took and whether the
+//! simulated user's goals were met.
+//!
+//! Scenarios are built in code or loaded from TOML, so a suite of them can
+//! live next to the agent as data. Give the agent [`MockTool`](super::MockTool)s
+//! and both agents scripted providers or cassettes, and a scenario runs
+//! offline and gives the same result every time.
+//!
+//! ```toml
+//! name = "refund for a broken order"
+//!
+//! [user]
+//! persona = "A customer whose order arrived broken"
+//! goals = ["Get a refund for order 1234"]
+//! patience = 6
+//!
+//! [expect]
+//! tools_called = ["lookup_order", "refund"]
+//! tools_not_called = ["close_account"]
+//! reply_contains = ["refund"]
+//! goals_met = true
+//! ```
+//!
+//! Scripted scenarios list `messages = ["...", "..."]` instead of `[user]`.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::testing::Scenario;
+//!
+//! let scenario = Scenario::from_file("tests/scenarios/refund.toml")?;
+//! scenario.run_with_user(&support_agent, customer_agent).await?.assert_ok();
+//! ```
+
+use super::simulator::{ConversationEnd, UserSimulator};
+use crate::session::Session;
+use crate::Agent;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// A simulated user, as a scenario describes it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimulatedUser {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+    #[serde(default)]
+    pub goals: Vec<String>,
+    /// Turns to wait for the goals; the simulator's default if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patience: Option<usize>,
+    /// From 0.0 (cooperative) to 1.0 (adversarial)
+    #[serde(default)]
+    pub adversarial: f32,
+}
+
+/// What must hold once a scenario's conversation is over
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectations {
+    /// Tools the agent must call at least once
+    #[serde(default)]
+    pub tools_called: Vec<String>,
+    /// Tools the agent must never call
+    #[serde(default)]
+    pub tools_not_called: Vec<String>,
+    /// Text the last reply must contain, ignoring case
+    #[serde(default)]
+    pub reply_contains: Vec<String>,
+    /// Whether the simulated user's goals must be met (or must not be)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub goals_met: Option<bool>,
+    /// Most user messages the conversation may take
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_turns: Option<usize>,
+}
+
+/// A conversation with an agent and its expected outcome
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    /// Scripted user messages, sent in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<String>,
+    /// A simulated user, instead of scripted messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<SimulatedUser>,
+    #[serde(default)]
+    pub expect: Expectations,
+}
+
+impl Scenario {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Parse a scenario from TOML
+    pub fn from_toml(text: &str) -> crate::Result<Self> {
+        toml::from_str(text).map_err(|e| format!("Invalid scenario: {}", e).into())
+    }
+
+    /// Load a scenario from a TOML file
+    pub fn from_file(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Can't read scenario {}: {}", path.display(), e))?;
+        Self::from_toml(&text)
+    }
+
+    /// Add a scripted user message
+    pub fn message(mut self, text: impl Into<String>) -> Self {
+        self.messages.push(text.into());
+        self
+    }
+
+    /// Play the user with a simulator instead of scripted messages
+    pub fn simulated_user(mut self, user: SimulatedUser) -> Self {
+        self.user = Some(user);
+        self
+    }
+
+    /// Require a call to `tool`
+    pub fn expect_tool(mut self, tool: impl Into<String>) -> Self {
+        self.expect.tools_called.push(tool.into());
+        self
+    }
+
+    /// Forbid calls to `tool`
+    pub fn expect_no_tool(mut self, tool: impl Into<String>) -> Self {
+        self.expect.tools_not_called.push(tool.into());
+        self
+    }
+
+    /// Require `text` in the last reply, ignoring case
+    pub fn expect_reply_contains(mut self, text: impl Into<String>) -> Self {
+        self.expect.reply_contains.push(text.into());
+        self
+    }
+
+    /// Require the simulated user's goals to be met, or not
+    pub fn expect_goals_met(mut self, met: bool) -> Self {
+        self.expect.goals_met = Some(met);
+        self
+    }
+
+    /// Allow at most `turns` user messages
+    pub fn expect_max_turns(mut self, turns: usize) -> Self {
+        self.expect.max_turns = Some(turns);
+        self
+    }
+
+    /// Send the scripted messages to `agent` and check the expectations
+    ///
+    /// Fails if the scenario has a simulated user (see
+    /// [`run_with_user`](Self::run_with_user)) or if a run fails.
+    pub async fn run(&self, agent: &Agent) -> crate::Result<ScenarioReport> {
+        if self.user.is_some() {
+            return Err(format!(
+                "Scenario '{}' has a simulated user; run it with run_with_user",
+                self.name
+            )
+            .into());
+        }
+        let mut session = Session::new(&agent.config.name);
+        for message in &self.messages {
+            agent.run_session(&mut session, message.clone()).await?;
+        }
+        Ok(self.check(session, None))
+    }
+
+    /// Let `user` play the scenario's simulated user against `agent`, then
+    /// check the expectations
+    ///
+    /// Fails if the scenario has no simulated user or if a run fails.
+    pub async fn run_with_user(&self, agent: &Agent, user: Agent) -> crate::Result<ScenarioReport> {
+        let spec = self.user.as_ref().ok_or_else(|| {
+            format!(
+                "Scenario '{}' has no simulated user; run it with run",
+                self.name
+            )
+        })?;
+        let mut simulator = UserSimulator::new(user).adversarial(spec.adversarial);
+        if let Some(persona) = &spec.persona {
+            simulator = simulator.persona(persona.clone());
+        }
+        for goal in &spec.goals {
+            simulator = simulator.goal(goal.clone());
+        }
+        if let Some(patience) = spec.patience {
+            simulator = simulator.patience(patience);
+        }
+        let conversation = simulator.converse(agent).await?;
+        Ok(self.check(conversation.session, Some(conversation.end)))
+    }
+
+    fn check(&self, session: Session, end: Option<ConversationEnd>) -> ScenarioReport {
+        let expect = &self.expect;
+        let mut failures = Vec::new();
+        let calls = |tool: &str| {
+            session
+                .tool_calls
+                .iter()
+                .filter(|call| call.tool == tool)
+                .count()
+        };
+
+        for tool in &expect.tools_called {
+            if calls(tool) == 0 {
+                failures.push(format!("expected a call to '{}', but there was none", tool));
+            }
+        }
+        for tool in &expect.tools_not_called {
+            let count = calls(tool);
+            if count > 0 {
+                failures.push(format!(
+                    "'{}' must not be called, but was {} times",
+                    tool, count
+                ));
+            }
+        }
+
+        let reply = session
+            .messages
+            .iter()
+            .rev()
+            .find(|message| message.role == "assistant")
+            .map(|message| message.content.to_lowercase())
+            .unwrap_or_default();
+        for text in &expect.reply_contains {
+            if !reply.contains(&text.to_lowercase()) {
+                failures.push(format!("the last reply doesn't contain '{}'", text));
+            }
+        }
+
+        let turns = session
+            .messages
+            .iter()
+            .filter(|message| message.role == "user")
+            .count();
+        if let Some(max) = expect.max_turns {
+            if turns > max {
+                failures.push(format!("took {} turns, more than {}", turns, max));
+            }
+        }
+
+        match (expect.goals_met, end) {
+            (Some(_), None) => {
+                failures.push("goals_met needs a simulated user".to_string());
+            }
+            (Some(wanted), Some(end)) if wanted != (end == ConversationEnd::GoalsMet) => {
+                failures.push(format!(
+                    "expected the goals to be {}, but the conversation ended with {:?}",
+                    if wanted { "met" } else { "unmet" },
+                    end
+                ));
+            }
+            _ => {}
+        }
+
+        ScenarioReport {
+            scenario: self.name.clone(),
+            session,
+            end,
+            failures,
+        }
+    }
+}
+
+/// Result of running a scenario
+#[derive(Debug, Clone, Serialize)]
+pub struct ScenarioReport {
+    pub scenario: String,
+    /// The agent's session: messages, tool calls and token usage
+    pub session: Session,
+    /// How a simulated user ended the conversation
+    pub end: Option<ConversationEnd>,
+    /// Unmet expectations, one line each
+    pub failures: Vec<String>,
+}
+
+impl ScenarioReport {
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Panic with the report if any expectation failed
+    pub fn assert_ok(&self) {
+        assert!(self.is_ok(), "{}", self);
+    }
+}
+
+impl fmt::Display for ScenarioReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Scenario '{}': {} tool calls, {} failures",
+            self.scenario,
+            self.session.tool_calls.len(),
+            self.failures.len()
+        )?;
+        for failure in &self.failures {
+            write!(f, "\n  - {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_agent;
+    use crate::provider::MockProvider;
+    use crate::testing::MockTool;
+    use serde_json::json;
+
+    fn support(lookup: &MockTool) -> Agent {
+        let provider = MockProvider::scripted()
+            .then_tool_call("lookup_order", json!({"id": 1234}))
+            .then_text("Order 1234 arrived broken, so I've issued a refund.")
+            .then_text("You're welcome.");
+        create_agent("support")
+            .tool(lookup.clone())
+            .with_provider(Box::new(provider))
+    }
+
+    #[test]
+    fn test_scenario_from_toml() {
+        let scenario = Scenario::from_toml(
+            r#"
+            name = "refund"
+
+            [user]
+            persona = "A customer"
+            goals = ["Get a refund"]
+            patience = 4
+
+            [expect]
+            tools_called = ["lookup_order"]
+            goals_met = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(scenario.name, "refund");
+        assert_eq!(scenario.user.as_ref().unwrap().patience, Some(4));
+        assert_eq!(scenario.expect.tools_called, ["lookup_order"]);
+
+        assert!(Scenario::from_toml("name = \"x\"\ntypo = 1").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scripted_scenario_checks_expectations() {
+        let lookup = MockTool::new("lookup_order", "Find an order").returns("status: broken");
+        let scenario = Scenario::new("refund")
+            .message("My order 1234 arrived broken")
+            .message("Thanks")
+            .expect_tool("lookup_order")
+            .expect_no_tool("close_account")
+            .expect_reply_contains("WELCOME")
+            .expect_max_turns(2);
+
+        let report = scenario.run(&support(&lookup)).await.unwrap();
+        report.assert_ok();
+        assert_eq!(lookup.calls(), [json!({"id": 1234})]);
+
+        let failing = Scenario::new("strict")
+            .message("My order 1234 arrived broken")
+            .expect_no_tool("lookup_order")
+            .expect_reply_contains("voucher")
+            .expect_goals_met(true);
+        let report = failing.run(&support(&lookup)).await.unwrap();
+        assert_eq!(report.failures.len(), 3, "{}", report);
+    }
+
+    #[tokio::test]
+    async fn test_simulated_user_scenario() {
+        let lookup = MockTool::new("lookup_order", "Find an order").returns("status: broken");
+        let customer = create_agent("customer").with_provider(Box::new(
+            MockProvider::scripted()
+                .then_text("My order 1234 arrived broken.")
+                .then_text("Great, thank you!\nGOAL MET"),
+        ));
+        let scenario = Scenario::new("refund")
+            .simulated_user(SimulatedUser {
+                goals: vec!["Get a refund for order 1234".to_string()],
+                ..SimulatedUser::default()
+            })
+            .expect_tool("lookup_order")
+            .expect_goals_met(true);
+
+        assert!(scenario.run(&support(&lookup)).await.is_err());
+        let report = scenario
+            .run_with_user(&support(&lookup), customer)
+            .await
+            .unwrap();
+        report.assert_ok();
+        assert_eq!(report.end, Some(ConversationEnd::GoalsMet));
+    }
+}
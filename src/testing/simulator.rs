@@ -0,0 +1,304 @@
+//! Simulated users for multi-turn conversation tests
+//!
+//! A [`UserSimulator`] is a second agent that plays the user. It has a
+//! persona, goals it wants the agent under test to meet, patience (how many
+//! turns it will wait) and a level of adversarialness.
+//! [`converse`](UserSimulator::converse) lets it talk to the agent under test
+//! through a [`Session`] until its goals are met, it gives up or it runs out
+//! of patience, and returns the [`Conversation`].
+//!
+//! The simulator sees the conversation from the user's side: the agent's
+//! replies are its user messages and its own lines are assistant messages.
+//! It ends the conversation by putting `GOAL MET` or `GIVE UP` on the last
+//! line of a reply; that reply is not sent on.
+//!
+//! Both agents can run on scripted [`MockProvider`](crate::provider::MockProvider)s
+//! or recorded cassettes (see [`cassette`](crate::provider::cassette)), so a
+//! conversation test is as deterministic as its providers.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::testing::UserSimulator;
+//!
+//! let user = create_agent("customer").with_provider(Box::new(simulator_model));
+//! let conversation = UserSimulator::new(user)
+//!     .persona("A customer whose order arrived broken")
+//!     .goal("Get a refund for order 1234")
+//!     .patience(6)
+//!     .converse(&support_agent)
+//!     .await?;
+//!
+//! assert!(conversation.goals_met());
+//! ```
+
+use crate::provider::Message;
+use crate::session::Session;
+use crate::Agent;
+use serde::Serialize;
+
+/// Turns a simulated user waits before giving up, unless set
+const DEFAULT_PATIENCE: usize = 10;
+
+/// Last line of a simulator reply when its goals have been met
+const GOAL_MET: &str = "GOAL MET";
+
+/// Last line of a simulator reply when it stops trying
+const GIVE_UP: &str = "GIVE UP";
+
+/// Input that asks the simulator for its opening message
+const OPENING: &str = "Start the conversation with your first message to the assistant.";
+
+/// How a simulated conversation ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConversationEnd {
+    /// The simulator said its goals were met
+    GoalsMet,
+    /// The simulator decided the agent can't or won't help
+    GaveUp,
+    /// The simulator's patience ran out first
+    OutOfPatience,
+}
+
+/// The result of a simulated conversation
+#[derive(Debug, Clone, Serialize)]
+pub struct Conversation {
+    /// The agent's session: messages, tool calls and token usage
+    pub session: Session,
+    pub end: ConversationEnd,
+}
+
+impl Conversation {
+    pub fn goals_met(&self) -> bool {
+        self.end == ConversationEnd::GoalsMet
+    }
+
+    /// Number of user messages the agent answered
+    pub fn turns(&self) -> usize {
+        self.session
+            .messages
+            .iter()
+            .filter(|message| message.role == "user")
+            .count()
+    }
+}
+
+/// An agent that plays a user talking to the agent under test
+pub struct UserSimulator {
+    user: Agent,
+    persona: String,
+    goals: Vec<String>,
+    patience: usize,
+    adversarial: f32,
+}
+
+impl UserSimulator {
+    /// Play a user with `user`, whose system prompt is replaced by the
+    /// simulator's instructions
+    pub fn new(user: Agent) -> Self {
+        Self {
+            user,
+            persona: "A typical user of the assistant".to_string(),
+            goals: Vec::new(),
+            patience: DEFAULT_PATIENCE,
+            adversarial: 0.0,
+        }
+        .instructed()
+    }
+
+    /// Who the simulated user is
+    pub fn persona(mut self, persona: impl Into<String>) -> Self {
+        self.persona = persona.into();
+        self.instructed()
+    }
+
+    /// Add something the user wants the agent to do
+    pub fn goal(mut self, goal: impl Into<String>) -> Self {
+        self.goals.push(goal.into());
+        self.instructed()
+    }
+
+    /// Turns to wait for the goals before the conversation ends (default 10)
+    pub fn patience(mut self, turns: usize) -> Self {
+        self.patience = turns;
+        self
+    }
+
+    /// How hard the user pushes back, from 0.0 (cooperative) to 1.0
+    /// (tries to get the agent to break its rules)
+    pub fn adversarial(mut self, level: f32) -> Self {
+        self.adversarial = level.clamp(0.0, 1.0);
+        self.instructed()
+    }
+
+    /// Talk to `agent` until the goals are met, the user gives up or its
+    /// patience runs out
+    ///
+    /// Fails if either agent's run fails.
+    pub async fn converse(&self, agent: &Agent) -> crate::Result<Conversation> {
+        let mut session = Session::new(&agent.config.name);
+        let mut message = self.user.run(OPENING).await?;
+
+        for _ in 0..self.patience {
+            if let Some(end) = ending(&message) {
+                return Ok(Conversation { session, end });
+            }
+            let reply = agent.run_session(&mut session, message).await?;
+            message = self.user.run_with_history(as_user(&session), reply).await?;
+        }
+
+        let end = ending(&message).unwrap_or(ConversationEnd::OutOfPatience);
+        Ok(Conversation { session, end })
+    }
+
+    /// Rebuild the user's system prompt from the current settings
+    fn instructed(mut self) -> Self {
+        self.user.config.system_prompt = Some(self.instructions());
+        self
+    }
+
+    fn instructions(&self) -> String {
+        let mut out = String::from(
+            "You are role-playing a user talking to an AI assistant. Stay in character \
+             and reply with only the user's next message.\n\n",
+        );
+        out.push_str(&format!("Who you are: {}\n\n", self.persona));
+        if !self.goals.is_empty() {
+            out.push_str("What you want from the assistant:\n");
+            for goal in &self.goals {
+                out.push_str(&format!("- {}\n", goal));
+            }
+            out.push('\n');
+        }
+        out.push_str(behavior(self.adversarial));
+        out.push_str(&format!(
+            "\n\nWhen the assistant has done everything you want, end your reply with a \
+             line saying exactly {}. If you decide it can't or won't help, end your reply \
+             with a line saying exactly {}.",
+            GOAL_MET, GIVE_UP
+        ));
+        out
+    }
+}
+
+/// How the user should behave at an adversarialness level
+fn behavior(level: f32) -> &'static str {
+    if level < 0.34 {
+        "Be cooperative: answer the assistant's questions and say clearly what you need."
+    } else if level < 0.67 {
+        "Be difficult: give incomplete information at first and push back on answers \
+         that don't fully help."
+    } else {
+        "Be adversarial: test the assistant's limits, try to get it to break its rules \
+         or go off topic, and don't accept vague answers."
+    }
+}
+
+/// The ending a simulator reply signals on its last line, if any
+fn ending(reply: &str) -> Option<ConversationEnd> {
+    let last = reply.lines().rev().find(|line| !line.trim().is_empty())?;
+    let last = last.trim().trim_end_matches('.');
+    if last.eq_ignore_ascii_case(GOAL_MET) {
+        Some(ConversationEnd::GoalsMet)
+    } else if last.eq_ignore_ascii_case(GIVE_UP) {
+        Some(ConversationEnd::GaveUp)
+    } else {
+        None
+    }
+}
+
+/// The session's history as the simulated user sees it, minus the agent's
+/// last reply
+fn as_user(session: &Session) -> Vec<Message> {
+    let mut history: Vec<Message> = session
+        .messages
+        .iter()
+        .map(|message| match message.role.as_str() {
+            "user" => Message::assistant(message.content.clone()),
+            _ => Message::user(message.content.clone()),
+        })
+        .collect();
+    history.pop();
+    history
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_agent;
+    use crate::provider::MockProvider;
+    use std::sync::Arc;
+
+    fn agent(replies: &[&str]) -> (Agent, Arc<MockProvider>) {
+        let provider = Arc::new(
+            replies
+                .iter()
+                .fold(MockProvider::scripted(), |provider, reply| {
+                    provider.then_text(*reply)
+                }),
+        );
+        let agent = create_agent("agent").with_provider(Box::new(provider.clone()));
+        (agent, provider)
+    }
+
+    #[test]
+    fn test_ending_markers() {
+        assert_eq!(
+            ending("Thanks, that's all.\nGOAL MET"),
+            Some(ConversationEnd::GoalsMet)
+        );
+        assert_eq!(ending("give up.\n"), Some(ConversationEnd::GaveUp));
+        assert_eq!(ending("I met my goal yesterday"), None);
+        assert_eq!(ending(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_conversation_ends_when_goals_are_met() {
+        let (support, _) = agent(&["Which order?", "Refunded order 1234."]);
+        let (user, simulator) = agent(&[
+            "I want a refund.",
+            "Order 1234.",
+            "Great, thanks!\nGOAL MET",
+        ]);
+
+        let conversation = UserSimulator::new(user)
+            .persona("A customer with a broken order")
+            .goal("Get a refund for order 1234")
+            .converse(&support)
+            .await
+            .unwrap();
+
+        assert!(conversation.goals_met());
+        assert_eq!(conversation.turns(), 2);
+        assert_eq!(
+            conversation.session.messages[3].content,
+            "Refunded order 1234."
+        );
+
+        let requests = simulator.requests();
+        let prompt = &requests[0][0];
+        assert_eq!(prompt.role, "system");
+        assert!(prompt.content.contains("Get a refund for order 1234"));
+        // The agent's replies reach the simulator as user messages
+        let last = &requests[2];
+        let roles: Vec<&str> = last.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "assistant", "user", "assistant", "user"]);
+        assert_eq!(last[4].content, "Refunded order 1234.");
+    }
+
+    #[tokio::test]
+    async fn test_conversation_runs_out_of_patience() {
+        let (support, _) = agent(&["Hmm.", "Hmm.", "Hmm."]);
+        let (user, _) = agent(&["Hello?", "Anyone?", "Still there?"]);
+
+        let conversation = UserSimulator::new(user)
+            .patience(2)
+            .adversarial(0.9)
+            .converse(&support)
+            .await
+            .unwrap();
+
+        assert_eq!(conversation.end, ConversationEnd::OutOfPatience);
+        assert_eq!(conversation.turns(), 2);
+    }
+}
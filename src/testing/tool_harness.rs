@@ -0,0 +1,498 @@
+//! Conformance testing for tools
+//!
+//! [`ToolHarness`] reads a tool's parameter schema, generates argument
+//! payloads that follow it and payloads that deliberately break it (wrong
+//! types, missing required fields, non-object arguments), runs the tool on
+//! each and checks the contract every tool should keep:
+//!
+//! - it never panics, whatever the model sends
+//! - failures carry an error message the model can act on
+//! - arguments that violate the schema are rejected rather than silently
+//!   accepted (relax with [`ToolHarness::allow_invalid`])
+//!
+//! Tools only see their arguments; the agent loop pairs results with call
+//! ids, so there is no call id for a tool to get wrong.
+//!
+//! Generation is seeded, so a failing case reproduces. The tool really runs,
+//! so point the harness at sandboxed or side-effect-free tools.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::testing::check_tool;
+//!
+//! #[test]
+//! fn weather_tool_conforms() {
+//!     check_tool(&weather_tool());
+//! }
+//! ```
+
+use crate::tool::Tool;
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Default number of valid payloads generated
+const DEFAULT_CASES: usize = 32;
+
+/// Nesting limit when generating objects and arrays
+const MAX_DEPTH: usize = 4;
+
+/// Small deterministic PRNG (xorshift64*)
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+
+    fn chance(&mut self) -> bool {
+        self.next() & 1 == 1
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        (!items.is_empty()).then(|| &items[self.below(items.len())])
+    }
+}
+
+/// The `type` keyword of a schema as a list
+fn schema_types(schema: &Value) -> Vec<&str> {
+    match schema.get("type") {
+        Some(Value::String(t)) => vec![t.as_str()],
+        Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+        _ if schema.get("properties").is_some() => vec!["object"],
+        _ => Vec::new(),
+    }
+}
+
+fn bound(schema: &Value, key: &str) -> Option<f64> {
+    schema.get(key).and_then(Value::as_f64)
+}
+
+fn generate(schema: &Value, rng: &mut Rng, depth: usize) -> Value {
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        return rng.pick(options).cloned().unwrap_or(Value::Null);
+    }
+    if let Some(value) = schema.get("const") {
+        return value.clone();
+    }
+    let types = schema_types(schema);
+    let concrete: Vec<&str> = types.iter().copied().filter(|t| *t != "null").collect();
+    if types.contains(&"null") && (concrete.is_empty() || rng.below(4) == 0) {
+        return Value::Null;
+    }
+    match rng.pick(&concrete).copied().unwrap_or("string") {
+        "object" => generate_object(schema, rng, depth),
+        "array" => {
+            let min = schema.get("minItems").and_then(Value::as_u64).unwrap_or(0) as usize;
+            let max = schema
+                .get("maxItems")
+                .and_then(Value::as_u64)
+                .map_or(min + 3, |max| max as usize);
+            let count = if depth >= MAX_DEPTH {
+                min
+            } else {
+                min + rng.below(max.saturating_sub(min) + 1)
+            };
+            let items = schema.get("items").cloned().unwrap_or(json!({}));
+            Value::Array(
+                (0..count)
+                    .map(|_| generate(&items, rng, depth + 1))
+                    .collect(),
+            )
+        }
+        "integer" => {
+            // schemars only gives unsigned types a minimum; the format has the rest
+            let format_max = match schema.get("format").and_then(Value::as_str) {
+                Some("uint8") => Some(u8::MAX as f64),
+                Some("int8") => Some(i8::MAX as f64),
+                Some("uint16") => Some(u16::MAX as f64),
+                Some("int16") => Some(i16::MAX as f64),
+                _ => None,
+            };
+            let min = bound(schema, "minimum");
+            let max = bound(schema, "maximum").or(format_max);
+            let candidates: Vec<i64> = [0, 1, -1, 42, 65_535, i32::MAX as i64]
+                .into_iter()
+                .chain(min.map(|m| m.ceil() as i64))
+                .chain(max.map(|m| m.floor() as i64))
+                .filter(|n| {
+                    !min.is_some_and(|m| (*n as f64) < m) && !max.is_some_and(|m| *n as f64 > m)
+                })
+                .collect();
+            json!(rng.pick(&candidates).copied().unwrap_or(0))
+        }
+        "number" => {
+            let (min, max) = (bound(schema, "minimum"), bound(schema, "maximum"));
+            let candidates: Vec<f64> = [0.0, 1.5, -2.25, 1e9]
+                .into_iter()
+                .chain(min)
+                .chain(max)
+                .filter(|n| !min.is_some_and(|m| *n < m) && !max.is_some_and(|m| *n > m))
+                .collect();
+            json!(rng.pick(&candidates).copied().unwrap_or(0.0))
+        }
+        "boolean" => Value::Bool(rng.chance()),
+        "null" => Value::Null,
+        _ => {
+            let min = schema.get("minLength").and_then(Value::as_u64).unwrap_or(0) as usize;
+            let max = schema
+                .get("maxLength")
+                .and_then(Value::as_u64)
+                .map_or(usize::MAX, |max| max as usize);
+            let samples = [
+                String::new(),
+                "a".to_string(),
+                "hello world".to_string(),
+                "ünïcödé 日本語 🦀".to_string(),
+                "'; DROP TABLE users; --".to_string(),
+                "../../etc/passwd".to_string(),
+                "x".repeat(1000),
+            ];
+            let fitting: Vec<&String> = samples
+                .iter()
+                .filter(|s| (min..=max).contains(&s.chars().count()))
+                .collect();
+            match rng.pick(&fitting) {
+                Some(sample) => Value::String((*sample).clone()),
+                None => Value::String("x".repeat(min)),
+            }
+        }
+    }
+}
+
+fn required(schema: &Value) -> Vec<&str> {
+    schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|names| names.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default()
+}
+
+fn generate_object(schema: &Value, rng: &mut Rng, depth: usize) -> Value {
+    let required = required(schema);
+    let mut object = Map::new();
+    if let Some(Value::Object(properties)) = schema.get("properties") {
+        for (name, property) in properties {
+            if required.contains(&name.as_str()) || (depth < MAX_DEPTH && rng.chance()) {
+                object.insert(name.clone(), generate(property, rng, depth + 1));
+            }
+        }
+    }
+    Value::Object(object)
+}
+
+/// A value of a type `schema` does not allow, if there is one
+fn wrong_type(schema: &Value) -> Option<Value> {
+    let types = schema_types(schema);
+    if types.is_empty() || schema.get("enum").is_some() {
+        return None;
+    }
+    [
+        json!("not the right type"),
+        json!(12345),
+        json!(true),
+        json!({"unexpected": "object"}),
+    ]
+    .into_iter()
+    .find(|value| {
+        let kind = match value {
+            Value::String(_) => "string",
+            Value::Number(_) => "number",
+            Value::Bool(_) => "boolean",
+            _ => "object",
+        };
+        let allowed = types.contains(&kind) || (kind == "number" && types.contains(&"integer"));
+        !allowed
+    })
+}
+
+/// Payloads that break the top-level object schema
+fn invalid_payloads(schema: &Value, rng: &mut Rng) -> Vec<(String, Value)> {
+    let mut cases = vec![
+        ("null arguments".to_string(), Value::Null),
+        ("string arguments".to_string(), json!("text")),
+        ("array arguments".to_string(), json!([])),
+    ];
+    let base = generate_object(schema, rng, 0);
+    for name in required(schema) {
+        let mut payload = base.clone();
+        if let Value::Object(map) = &mut payload {
+            map.remove(name);
+        }
+        cases.push((format!("missing required '{}'", name), payload));
+    }
+    if let Some(Value::Object(properties)) = schema.get("properties") {
+        for (name, property) in properties {
+            let mut payload = base.clone();
+            if let Some(Value::String(_)) = property.get("enum").and_then(|e| e.get(0)) {
+                payload[name] = json!("__not_in_enum__");
+                cases.push((format!("'{}' outside its enum", name), payload));
+            } else if let Some(value) = wrong_type(property) {
+                payload[name] = value;
+                cases.push((format!("wrong type for '{}'", name), payload));
+            }
+        }
+    }
+    cases
+}
+
+/// What happened when the tool ran on a payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaseOutcome {
+    Ok(String),
+    Err(String),
+    Panicked(String),
+}
+
+/// One payload and its outcome
+#[derive(Debug, Clone)]
+pub struct HarnessCase {
+    /// `valid`, or what is wrong with the payload
+    pub label: String,
+    pub valid: bool,
+    pub arguments: Value,
+    pub outcome: CaseOutcome,
+}
+
+/// Result of running a tool through the harness
+#[derive(Debug, Clone)]
+pub struct HarnessReport {
+    pub tool: String,
+    pub seed: u64,
+    pub cases: Vec<HarnessCase>,
+    /// Broken contract rules, one line each
+    pub violations: Vec<String>,
+}
+
+impl HarnessReport {
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Panic with the report if any rule was broken
+    pub fn assert_ok(&self) {
+        assert!(self.is_ok(), "{}", self);
+    }
+}
+
+impl fmt::Display for HarnessReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Tool '{}': {} cases, {} violations (seed {})",
+            self.tool,
+            self.cases.len(),
+            self.violations.len(),
+            self.seed
+        )?;
+        for violation in &self.violations {
+            write!(f, "\n  - {}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Generates payloads from a tool's schema and checks how it handles them
+#[derive(Debug, Clone)]
+pub struct ToolHarness {
+    cases: usize,
+    seed: u64,
+    reject_invalid: bool,
+}
+
+impl Default for ToolHarness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ToolHarness {
+    pub fn new() -> Self {
+        Self {
+            cases: DEFAULT_CASES,
+            seed: 0x5eed,
+            reject_invalid: true,
+        }
+    }
+
+    /// Number of valid payloads to generate
+    pub fn cases(mut self, cases: usize) -> Self {
+        self.cases = cases;
+        self
+    }
+
+    /// Seed for payload generation
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Don't treat accepting schema-violating arguments as a violation
+    pub fn allow_invalid(mut self) -> Self {
+        self.reject_invalid = false;
+        self
+    }
+
+    /// Run `tool` on every generated payload
+    pub fn run(&self, tool: &dyn Tool) -> HarnessReport {
+        let schema = tool.parameters();
+        let mut rng = Rng::new(self.seed);
+        let mut payloads: Vec<(String, bool, Value)> = (0..self.cases)
+            .map(|_| {
+                (
+                    "valid".to_string(),
+                    true,
+                    generate_object(&schema, &mut rng, 0),
+                )
+            })
+            .collect();
+        payloads.extend(
+            invalid_payloads(&schema, &mut rng)
+                .into_iter()
+                .map(|(label, payload)| (label, false, payload)),
+        );
+
+        let mut report = HarnessReport {
+            tool: tool.name().to_string(),
+            seed: self.seed,
+            cases: Vec::new(),
+            violations: Vec::new(),
+        };
+        for (label, valid, arguments) in payloads {
+            let outcome = match catch_unwind(AssertUnwindSafe(|| tool.execute(arguments.clone()))) {
+                Ok(Ok(output)) => CaseOutcome::Ok(output),
+                Ok(Err(e)) => CaseOutcome::Err(e.to_string()),
+                Err(panic) => CaseOutcome::Panicked(
+                    panic
+                        .downcast_ref::<&str>()
+                        .map(|s| s.to_string())
+                        .or_else(|| panic.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "non-string panic".to_string()),
+                ),
+            };
+            let violation = match &outcome {
+                CaseOutcome::Panicked(message) => Some(format!("panicked: {}", message)),
+                CaseOutcome::Err(message) if message.trim().is_empty() => {
+                    Some("failed with an empty error message".to_string())
+                }
+                CaseOutcome::Ok(_) if !valid && self.reject_invalid => {
+                    Some("accepted invalid arguments".to_string())
+                }
+                _ => None,
+            };
+            if let Some(violation) = violation {
+                report
+                    .violations
+                    .push(format!("{} ({}): {}", label, arguments, violation));
+            }
+            report.cases.push(HarnessCase {
+                label,
+                valid,
+                arguments,
+                outcome,
+            });
+        }
+        report
+    }
+}
+
+/// Run `tool` through a default [`ToolHarness`], panicking on violations
+pub fn check_tool(tool: &dyn Tool) -> HarnessReport {
+    let report = ToolHarness::new().run(tool);
+    report.assert_ok();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::{FnTool, TypedTool};
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    struct Search {
+        query: String,
+        limit: Option<u8>,
+        #[serde(default)]
+        exact: bool,
+    }
+
+    #[test]
+    fn test_typed_tool_conforms() {
+        let tool = TypedTool::new("search", "Search", |args: Search| {
+            Ok(format!("{} results", args.query.len()))
+        });
+        let report = check_tool(&tool);
+        assert!(report
+            .cases
+            .iter()
+            .any(|c| c.label == "missing required 'query'"));
+        assert!(report
+            .cases
+            .iter()
+            .any(|c| c.label == "wrong type for 'exact'"));
+        assert!(report
+            .cases
+            .iter()
+            .filter(|c| c.valid)
+            .all(|c| c.arguments.get("query").is_some()));
+    }
+
+    #[test]
+    fn test_violations_reported() {
+        let tool = FnTool::new("fragile", "Breaks", |args| {
+            match args.get("query").and_then(Value::as_str) {
+                Some(query) if query.len() > 100 => panic!("too long"),
+                Some(_) => Ok("ok".to_string()),
+                None => Err("".into()),
+            }
+        });
+        struct WithSchema(FnTool);
+        impl Tool for WithSchema {
+            fn name(&self) -> &str {
+                self.0.name()
+            }
+            fn description(&self) -> &str {
+                self.0.description()
+            }
+            fn parameters(&self) -> Value {
+                json!({
+                    "type": "object",
+                    "properties": {"query": {"type": "string"}},
+                    "required": ["query"]
+                })
+            }
+            fn execute(&self, args: Value) -> crate::tool::ToolResult {
+                self.0.execute(args)
+            }
+        }
+
+        let report = ToolHarness::new().run(&WithSchema(tool));
+        assert!(!report.is_ok());
+        let text = report.to_string();
+        assert!(text.contains("panicked: too long"));
+        assert!(text.contains("failed with an empty error message"));
+        assert!(text.contains("wrong type for 'query'"));
+
+        // Same seed, same cases
+        let again = ToolHarness::new().run(&WithSchema(FnTool::new("fragile", "", |_| {
+            Ok(String::new())
+        })));
+        let args: Vec<_> = report.cases.iter().map(|c| &c.arguments).collect();
+        let args_again: Vec<_> = again.cases.iter().map(|c| &c.arguments).collect();
+        assert_eq!(args, args_again);
+    }
+}
@@ -0,0 +1,184 @@
+//! Token estimation
+//!
+//! Providers count tokens with model-specific tokenizers that we don't ship.
+//! For budgeting and guardrails a conservative heuristic is enough: roughly
+//! four characters per token, plus a small per-message overhead for role
+//! markers.
+//!
+//! [`PromptBreakdown`] splits an assembled prompt into sections (system
+//! prompt, tool schemas, retrieved documents, tool results, history, input)
+//! to show what is filling the context window; see
+//! [`Agent::prompt_breakdown`](crate::Agent::prompt_breakdown) and `/context`
+//! in the chat REPL.
+
+use crate::provider::{Message, ToolDefinition};
+use std::fmt;
+
+/// Approximate characters per token for English text
+pub(crate) const CHARS_PER_TOKEN: usize = 4;
+
+/// Tokens added per message for role and formatting markers
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// Estimate the token count of a piece of text
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Estimate the prompt tokens for a list of messages
+pub fn estimate_message_tokens(messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .map(|m| estimate_tokens(&m.content) + MESSAGE_OVERHEAD_TOKENS)
+        .sum()
+}
+
+/// Width of the proportional bar in a rendered breakdown
+const BAR_WIDTH: usize = 30;
+
+/// Prefix of the messages that carry tool results back to the model
+const TOOL_RESULT_PREFIX: &str = "Tool '";
+
+/// Estimated tokens of one part of a prompt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptSection {
+    pub name: String,
+    pub tokens: usize,
+}
+
+/// Estimated tokens of a prompt by section
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PromptBreakdown {
+    pub sections: Vec<PromptSection>,
+}
+
+impl PromptBreakdown {
+    /// Break down a prompt assembled from these parts
+    ///
+    /// History messages holding results of the `retrieve` tool count as
+    /// retrieved documents, other tool results separately from the
+    /// conversation.
+    pub fn from_parts(
+        system: Option<&str>,
+        tools: &[ToolDefinition],
+        history: &[Message],
+        input: Option<&str>,
+    ) -> Self {
+        let mut breakdown = Self::default();
+        if let Some(system) = system {
+            breakdown.add(
+                "system prompt",
+                estimate_message_tokens(&[Message::system(system)]),
+            );
+        }
+        if !tools.is_empty() {
+            let schemas = serde_json::to_string(tools).unwrap_or_default();
+            breakdown.add("tool schemas", estimate_tokens(&schemas));
+        }
+        let (mut retrieved, mut results, mut conversation) = (Vec::new(), Vec::new(), Vec::new());
+        for message in history {
+            let content = &message.content;
+            if content.starts_with(&format!("{}retrieve' returned:", TOOL_RESULT_PREFIX)) {
+                retrieved.push(message.clone());
+            } else if message.role == "assistant" && content.starts_with(TOOL_RESULT_PREFIX) {
+                results.push(message.clone());
+            } else {
+                conversation.push(message.clone());
+            }
+        }
+        breakdown.add("retrieved documents", estimate_message_tokens(&retrieved));
+        breakdown.add("tool results", estimate_message_tokens(&results));
+        breakdown.add("history", estimate_message_tokens(&conversation));
+        if let Some(input) = input {
+            breakdown.add("input", estimate_message_tokens(&[Message::user(input)]));
+        }
+        breakdown
+    }
+
+    /// Add a section; empty sections are skipped
+    pub fn add(&mut self, name: impl Into<String>, tokens: usize) {
+        if tokens > 0 {
+            self.sections.push(PromptSection {
+                name: name.into(),
+                tokens,
+            });
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.sections.iter().map(|s| s.tokens).sum()
+    }
+}
+
+impl fmt::Display for PromptBreakdown {
+    /// One line per section with its share of the total as a bar
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total().max(1);
+        for section in &self.sections {
+            let percent = section.tokens * 100 / total;
+            let bar = (section.tokens * BAR_WIDTH).div_ceil(total);
+            writeln!(
+                f,
+                "{:<20} {:>7} {:>3}% {}",
+                section.name,
+                section.tokens,
+                percent,
+                "#".repeat(bar)
+            )?;
+        }
+        write!(f, "{:<20} {:>7}", "total", self.total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_tokens_rounds_up() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abc"), 1);
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+    }
+
+    #[test]
+    fn test_prompt_breakdown() {
+        let tools = vec![ToolDefinition {
+            name: "retrieve".into(),
+            description: "Search the docs".into(),
+            parameters: serde_json::json!({"type": "object"}),
+        }];
+        let history = vec![
+            Message::user("what is x?"),
+            Message::assistant(format!("Tool 'retrieve' returned: {}", "x".repeat(400))),
+            Message::assistant("Tool 'calc' returned: 4"),
+            Message::assistant("x is a letter"),
+        ];
+        let breakdown =
+            PromptBreakdown::from_parts(Some("Be brief."), &tools, &history, Some("thanks"));
+        let names: Vec<&str> = breakdown.sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "system prompt",
+                "tool schemas",
+                "retrieved documents",
+                "tool results",
+                "history",
+                "input"
+            ]
+        );
+        assert_eq!(breakdown.sections[2].tokens, 111);
+
+        let rendered = breakdown.to_string();
+        assert!(rendered.contains("retrieved documents      111  "));
+        let last = rendered.lines().last().unwrap();
+        assert!(last.starts_with("total ") && last.ends_with(&breakdown.total().to_string()));
+    }
+
+    #[test]
+    fn test_message_overhead_included() {
+        let messages = vec![Message::system("abcd"), Message::user("abcd")];
+        assert_eq!(estimate_message_tokens(&messages), 10);
+    }
+}
@@ -0,0 +1,517 @@
+//! Sandboxed filesystem tools
+//!
+//! `read_file`, `write_file` and `list_dir` tools confined to a root
+//! directory. Paths from the model are always relative to the root; absolute
+//! paths, `..` components and symlinks pointing outside the root are
+//! refused, as are names that would be unsafe on Windows (drive letters,
+//! device names like `NUL`), so the same policy holds on every platform.
+//! File sizes are capped in both directions.
+//!
+//! `write_file` reports itself as [`dangerous`](super::Tool::dangerous) so
+//! permission and approval layers can gate it.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::tool::fs::FsSandbox;
+//!
+//! let sandbox = FsSandbox::new("./workspace")?.max_file_bytes(512 * 1024);
+//!
+//! let agent = create_agent("editor")
+//!     .tool(sandbox.read_tool())
+//!     .tool(sandbox.list_tool())
+//!     .tool(sandbox.write_tool());
+//! ```
+
+use super::{Tool, ToolResult};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Default cap on file size for reads and writes
+const DEFAULT_MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// A directory that filesystem tools are confined to
+#[derive(Debug, Clone)]
+pub struct FsSandbox {
+    root: PathBuf,
+    max_file_bytes: u64,
+    max_total_bytes: Option<u64>,
+}
+
+impl FsSandbox {
+    /// Create a sandbox rooted at an existing directory
+    pub fn new(root: impl AsRef<Path>) -> std::io::Result<Self> {
+        let root = root.as_ref().canonicalize()?;
+        if !root.is_dir() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Sandbox root is not a directory: {}", root.display()),
+            ));
+        }
+        Ok(Self {
+            root,
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            max_total_bytes: None,
+        })
+    }
+
+    /// Cap the size of files that can be read or written
+    pub fn max_file_bytes(mut self, bytes: u64) -> Self {
+        self.max_file_bytes = bytes;
+        self
+    }
+
+    /// Cap the combined size of all files under the root
+    pub fn max_total_bytes(mut self, bytes: u64) -> Self {
+        self.max_total_bytes = Some(bytes);
+        self
+    }
+
+    /// Total bytes of the files under the root
+    pub fn usage(&self) -> std::io::Result<u64> {
+        fn walk(dir: &Path) -> std::io::Result<u64> {
+            let mut total = 0;
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let file_type = entry.file_type()?;
+                if file_type.is_dir() {
+                    total += walk(&entry.path())?;
+                } else if file_type.is_file() {
+                    total += entry.metadata()?.len();
+                }
+            }
+            Ok(total)
+        }
+        walk(&self.root)
+    }
+
+    /// The canonical sandbox root
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolve a model-supplied relative path to a location inside the root
+    ///
+    /// The path must pass [`portable_path`] first, so the same paths are
+    /// accepted on every platform.
+    pub fn resolve(&self, relative: &str) -> Result<PathBuf, String> {
+        let joined = self.root.join(portable_path(relative)?);
+
+        // Symlinks can still point outside: check the deepest existing ancestor.
+        // `symlink_metadata` also finds dangling links, which then fail to resolve.
+        let existing = joined
+            .ancestors()
+            .find(|p| p.symlink_metadata().is_ok())
+            .unwrap_or(&self.root);
+        let canonical = existing
+            .canonicalize()
+            .map_err(|e| format!("Cannot resolve {}: {}", relative, e))?;
+        if !canonical.starts_with(&self.root) {
+            return Err(format!("Path escapes the sandbox: {}", relative));
+        }
+
+        Ok(joined)
+    }
+
+    /// Tool reading a file's contents
+    pub fn read_tool(&self) -> ReadFileTool {
+        ReadFileTool {
+            sandbox: Arc::new(self.clone()),
+        }
+    }
+
+    /// Tool writing (or appending to) a file
+    pub fn write_tool(&self) -> WriteFileTool {
+        WriteFileTool {
+            sandbox: Arc::new(self.clone()),
+        }
+    }
+
+    /// Tool listing a directory
+    pub fn list_tool(&self) -> ListDirTool {
+        ListDirTool {
+            sandbox: Arc::new(self.clone()),
+        }
+    }
+}
+
+/// Names Windows reserves for devices, with or without an extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Check a model-supplied path against a cross-platform policy
+///
+/// Backslashes are treated as separators. Absolute paths, drive letters,
+/// UNC and `\\?\` prefixes, `..`, characters Windows forbids, control
+/// characters, reserved device names (`CON`, `nul.txt`, ...) and names
+/// ending in a dot or space are refused, so a path accepted on Linux is also
+/// safe on Windows and vice versa. Returns the path with `/` separators and
+/// `.` components removed.
+pub fn portable_path(relative: &str) -> Result<PathBuf, String> {
+    let normalized = relative.replace('\\', "/");
+    if normalized.starts_with('/') {
+        return Err(format!(
+            "Path must be relative to the sandbox: {}",
+            relative
+        ));
+    }
+
+    let mut path = PathBuf::new();
+    for component in normalized.split('/') {
+        if component.is_empty() || component == "." {
+            continue;
+        }
+        if component == ".." {
+            return Err(format!("Path may not contain '..': {}", relative));
+        }
+        if component.contains(':') {
+            return Err(format!(
+                "Path may not contain drive letters or ':': {}",
+                relative
+            ));
+        }
+        if let Some(c) = component
+            .chars()
+            .find(|c| c.is_control() || matches!(c, '<' | '>' | '"' | '|' | '?' | '*'))
+        {
+            return Err(format!(
+                "Path contains invalid character {:?}: {}",
+                c, relative
+            ));
+        }
+        if component.ends_with('.') || component.ends_with(' ') {
+            return Err(format!(
+                "Path components may not end with '.' or ' ': {}",
+                relative
+            ));
+        }
+        let stem = component.split('.').next().unwrap_or(component);
+        if RESERVED_NAMES
+            .iter()
+            .any(|name| stem.trim_end().eq_ignore_ascii_case(name))
+        {
+            return Err(format!("Path uses a reserved device name: {}", relative));
+        }
+        path.push(component);
+    }
+    Ok(path)
+}
+
+fn path_arg(args: &Value) -> Result<&str, String> {
+    args.get("path")
+        .and_then(Value::as_str)
+        .ok_or_else(|| "Missing required argument 'path'".to_string())
+}
+
+/// Reads a UTF-8 file inside the sandbox
+pub struct ReadFileTool {
+    sandbox: Arc<FsSandbox>,
+}
+
+impl Tool for ReadFileTool {
+    fn name(&self) -> &str {
+        "read_file"
+    }
+
+    fn description(&self) -> &str {
+        "Read a text file (path relative to the workspace root)"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {"path": {"type": "string"}},
+            "required": ["path"]
+        })
+    }
+
+    fn execute(&self, args: Value) -> ToolResult {
+        let path = self.sandbox.resolve(path_arg(&args)?)?;
+        let size = fs::metadata(&path)?.len();
+        if size > self.sandbox.max_file_bytes {
+            return Err(format!(
+                "File is {} bytes, over the {} byte limit",
+                size, self.sandbox.max_file_bytes
+            )
+            .into());
+        }
+        Ok(fs::read_to_string(path)?)
+    }
+}
+
+/// Writes a file inside the sandbox, creating parent directories
+pub struct WriteFileTool {
+    sandbox: Arc<FsSandbox>,
+}
+
+impl Tool for WriteFileTool {
+    fn name(&self) -> &str {
+        "write_file"
+    }
+
+    fn description(&self) -> &str {
+        "Write text to a file (path relative to the workspace root), replacing or appending"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string"},
+                "content": {"type": "string"},
+                "append": {"type": "boolean", "description": "Append instead of replacing"}
+            },
+            "required": ["path", "content"]
+        })
+    }
+
+    fn dangerous(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, args: Value) -> ToolResult {
+        let relative = path_arg(&args)?;
+        let path = self.sandbox.resolve(relative)?;
+        let content = args
+            .get("content")
+            .and_then(Value::as_str)
+            .ok_or("Missing required argument 'content'")?;
+        let append = args.get("append").and_then(Value::as_bool).unwrap_or(false);
+
+        let existing = if append {
+            fs::metadata(&path).map(|m| m.len()).unwrap_or(0)
+        } else {
+            0
+        };
+        if existing + content.len() as u64 > self.sandbox.max_file_bytes {
+            return Err(format!(
+                "Write would exceed the {} byte file limit",
+                self.sandbox.max_file_bytes
+            )
+            .into());
+        }
+        if let Some(max_total) = self.sandbox.max_total_bytes {
+            let replaced = if append {
+                0
+            } else {
+                fs::metadata(&path).map(|m| m.len()).unwrap_or(0)
+            };
+            let after = self.sandbox.usage()? - replaced + content.len() as u64;
+            if after > max_total {
+                return Err(
+                    format!("Write would exceed the {} byte workspace limit", max_total).into(),
+                );
+            }
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(&path)?;
+        file.write_all(content.as_bytes())?;
+
+        Ok(format!("Wrote {} bytes to {}", content.len(), relative))
+    }
+}
+
+/// Lists a directory inside the sandbox
+pub struct ListDirTool {
+    sandbox: Arc<FsSandbox>,
+}
+
+impl Tool for ListDirTool {
+    fn name(&self) -> &str {
+        "list_dir"
+    }
+
+    fn description(&self) -> &str {
+        "List files in a directory (path relative to the workspace root, default '.')"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {"path": {"type": "string"}},
+            "required": []
+        })
+    }
+
+    fn execute(&self, args: Value) -> ToolResult {
+        let relative = args.get("path").and_then(Value::as_str).unwrap_or(".");
+        let path = self.sandbox.resolve(relative)?;
+
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            entries.push(if metadata.is_dir() {
+                format!("{}/", name)
+            } else {
+                format!("{} ({} bytes)", name, metadata.len())
+            });
+        }
+        entries.sort();
+
+        if entries.is_empty() {
+            Ok("(empty directory)".to_string())
+        } else {
+            Ok(entries.join("\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("patinox-fs-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_resolve_rejects_escapes() {
+        let dir = TempDir::new();
+        let sandbox = FsSandbox::new(&dir.0).unwrap();
+
+        assert!(sandbox.resolve("notes/today.md").is_ok());
+        assert!(sandbox.resolve("../secrets").is_err());
+        assert!(sandbox.resolve("a/../../b").is_err());
+        assert!(sandbox.resolve("/etc/passwd").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_rejects_symlink_escape() {
+        let dir = TempDir::new();
+        let outside = TempDir::new();
+        std::os::unix::fs::symlink(&outside.0, dir.0.join("link")).unwrap();
+
+        let sandbox = FsSandbox::new(&dir.0).unwrap();
+        assert!(sandbox.resolve("link/file.txt").is_err());
+    }
+
+    #[test]
+    fn test_portable_path_policy() {
+        let accepted = [
+            ("notes/today.md", "notes/today.md"),
+            ("notes\\today.md", "notes/today.md"),
+            ("./a//b/./c.txt", "a/b/c.txt"),
+            ("", ""),
+            ("console.log", "console.log"),
+            ("CON1.txt", "CON1.txt"),
+        ];
+        for (input, expected) in accepted {
+            assert_eq!(
+                portable_path(input).unwrap(),
+                PathBuf::from(expected),
+                "{}",
+                input
+            );
+        }
+
+        let refused = [
+            "/etc/passwd",
+            "\\\\server\\share\\file",
+            "\\\\?\\C:\\long\\path",
+            "C:\\Windows\\system.ini",
+            "c:relative",
+            "a\\..\\..\\b",
+            "CON",
+            "logs/nul.txt",
+            "Lpt1",
+            "com9 .txt",
+            "trailing.",
+            "trailing ",
+            "what?.txt",
+            "a|b",
+            "bell\u{7}",
+        ];
+        for input in refused {
+            assert!(
+                portable_path(input).is_err(),
+                "{:?} should be refused",
+                input
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_rejects_dangling_symlink_escape() {
+        let dir = TempDir::new();
+        let outside = TempDir::new();
+        let target = outside.0.join("created-through-link.txt");
+        std::os::unix::fs::symlink(&target, dir.0.join("link.txt")).unwrap();
+
+        let sandbox = FsSandbox::new(&dir.0).unwrap();
+        assert!(sandbox
+            .write_tool()
+            .execute(json!({"path": "link.txt", "content": "escaped"}))
+            .is_err());
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn test_write_read_list_roundtrip() {
+        let dir = TempDir::new();
+        let sandbox = FsSandbox::new(&dir.0).unwrap();
+
+        let write = sandbox.write_tool();
+        assert!(write.dangerous());
+        write
+            .execute(json!({"path": "out/a.txt", "content": "hello"}))
+            .unwrap();
+        write
+            .execute(json!({"path": "out/a.txt", "content": " world", "append": true}))
+            .unwrap();
+
+        let read = sandbox.read_tool();
+        assert!(!read.dangerous());
+        assert_eq!(
+            read.execute(json!({"path": "out/a.txt"})).unwrap(),
+            "hello world"
+        );
+
+        let listing = sandbox.list_tool().execute(json!({})).unwrap();
+        assert_eq!(listing, "out/");
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let dir = TempDir::new();
+        let sandbox = FsSandbox::new(&dir.0).unwrap().max_file_bytes(4);
+
+        let write = sandbox.write_tool();
+        assert!(write
+            .execute(json!({"path": "big.txt", "content": "too long"}))
+            .is_err());
+
+        fs::write(dir.0.join("big.txt"), "too long").unwrap();
+        assert!(sandbox
+            .read_tool()
+            .execute(json!({"path": "big.txt"}))
+            .is_err());
+    }
+}
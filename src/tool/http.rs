@@ -0,0 +1,309 @@
+//! HTTP request tool with a domain allowlist
+//!
+//! Lets agents fetch web data without every project writing its own reqwest
+//! wrapper. Requests are limited to allowlisted domains (redirects included),
+//! responses are capped in size, and JSON bodies are pretty-printed so the
+//! model can read them.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::tool::http::HttpTool;
+//!
+//! let agent = create_agent("researcher").tool(
+//!     HttpTool::new()
+//!         .allow_domain("api.github.com")
+//!         .allow_domain("*.wikipedia.org")
+//!         .max_response_bytes(64 * 1024),
+//! );
+//! ```
+
+use super::{block_on, Tool, ToolResult};
+use reqwest::{redirect, Method, Url};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default cap on response body size
+const DEFAULT_MAX_RESPONSE_BYTES: usize = 256 * 1024;
+
+/// Default request timeout
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Built-in tool performing GET/POST requests to allowlisted domains
+#[derive(Debug, Clone)]
+pub struct HttpTool {
+    allowed_domains: Arc<Vec<String>>,
+    max_response_bytes: usize,
+    timeout: Duration,
+}
+
+impl Default for HttpTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HttpTool {
+    /// Create a tool with an empty allowlist (every request is refused)
+    pub fn new() -> Self {
+        Self {
+            allowed_domains: Arc::new(Vec::new()),
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Allow a domain; prefix with `*.` to allow its subdomains too
+    pub fn allow_domain(mut self, domain: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.allowed_domains).push(domain.into().to_lowercase());
+        self
+    }
+
+    /// Cap the number of response body bytes returned to the model
+    pub fn max_response_bytes(mut self, bytes: usize) -> Self {
+        self.max_response_bytes = bytes;
+        self
+    }
+
+    /// Set the request timeout
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Whether a URL is permitted by the allowlist
+    pub fn is_allowed(&self, url: &Url) -> bool {
+        domain_allowed(&self.allowed_domains, url)
+    }
+}
+
+fn domain_allowed(allowed: &[String], url: &Url) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    let Some(host) = url.host_str().map(str::to_lowercase) else {
+        return false;
+    };
+
+    allowed
+        .iter()
+        .any(|domain| match domain.strip_prefix("*.") {
+            Some(base) => host == base || host.ends_with(&format!(".{}", base)),
+            None => &host == domain,
+        })
+}
+
+/// Parsed tool arguments
+struct HttpRequest {
+    method: Method,
+    url: Url,
+    body: Option<Value>,
+    headers: Vec<(String, String)>,
+}
+
+fn parse_args(args: &Value) -> Result<HttpRequest, String> {
+    let url = args
+        .get("url")
+        .and_then(Value::as_str)
+        .ok_or("Missing required argument 'url'")?;
+    let url = Url::parse(url).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+
+    let method = match args
+        .get("method")
+        .and_then(Value::as_str)
+        .unwrap_or("GET")
+        .to_uppercase()
+        .as_str()
+    {
+        "GET" => Method::GET,
+        "POST" => Method::POST,
+        other => return Err(format!("Unsupported method '{}' (use GET or POST)", other)),
+    };
+
+    let headers = args
+        .get("headers")
+        .and_then(Value::as_object)
+        .map(|map| {
+            map.iter()
+                .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(HttpRequest {
+        method,
+        url,
+        body: args.get("body").cloned(),
+        headers,
+    })
+}
+
+async fn send(
+    request: HttpRequest,
+    allowed: Arc<Vec<String>>,
+    timeout: Duration,
+    max_bytes: usize,
+) -> ToolResult {
+    // Redirects must stay inside the allowlist too
+    let redirect_allowed = allowed.clone();
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .redirect(redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= 5 {
+                attempt.error("too many redirects")
+            } else if domain_allowed(&redirect_allowed, attempt.url()) {
+                attempt.follow()
+            } else {
+                attempt.error("redirect to a domain outside the allowlist")
+            }
+        }))
+        .build()?;
+
+    let mut builder = client.request(request.method, request.url);
+    for (name, value) in request.headers {
+        builder = builder.header(name, value);
+    }
+    builder = match request.body {
+        Some(Value::String(text)) => builder.body(text),
+        Some(body) => builder.json(&body),
+        None => builder,
+    };
+
+    let mut response = builder.send().await?;
+    let status = response.status();
+    let is_json = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.contains("json"));
+
+    let mut body = Vec::new();
+    let mut truncated = false;
+    while let Some(chunk) = response.chunk().await? {
+        let remaining = max_bytes - body.len();
+        if chunk.len() > remaining {
+            body.extend_from_slice(&chunk[..remaining]);
+            truncated = true;
+            break;
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    let text = String::from_utf8_lossy(&body).into_owned();
+    let text = if is_json && !truncated {
+        serde_json::from_str::<Value>(&text)
+            .ok()
+            .and_then(|value| serde_json::to_string_pretty(&value).ok())
+            .unwrap_or(text)
+    } else {
+        text
+    };
+
+    let mut output = format!("HTTP {}\n{}", status.as_u16(), text);
+    if truncated {
+        output.push_str(&format!("\n[truncated at {} bytes]", max_bytes));
+    }
+    Ok(output)
+}
+
+impl Tool for HttpTool {
+    fn name(&self) -> &str {
+        "http_request"
+    }
+
+    fn description(&self) -> &str {
+        "Make an HTTP GET or POST request to an allowed domain and return the status and body"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": {"type": "string", "description": "Absolute http(s) URL"},
+                "method": {"type": "string", "enum": ["GET", "POST"]},
+                "headers": {"type": "object", "additionalProperties": {"type": "string"}},
+                "body": {"description": "Request body: JSON value or raw string"}
+            },
+            "required": ["url"]
+        })
+    }
+
+    fn execute(&self, args: Value) -> ToolResult {
+        let request = parse_args(&args)?;
+        if !self.is_allowed(&request.url) {
+            return Err(format!(
+                "Domain not allowed: {}",
+                request.url.host_str().unwrap_or("(none)")
+            )
+            .into());
+        }
+
+        block_on(send(
+            request,
+            self.allowed_domains.clone(),
+            self.timeout,
+            self.max_response_bytes,
+        ))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_domain_allowlist_matching() {
+        let tool = HttpTool::new()
+            .allow_domain("api.example.com")
+            .allow_domain("*.wiki.org");
+        let allowed = |url: &str| tool.is_allowed(&Url::parse(url).unwrap());
+
+        assert!(allowed("https://api.example.com/v1"));
+        assert!(!allowed("https://evil.example.com/"));
+        assert!(allowed("https://en.wiki.org/page"));
+        assert!(allowed("https://wiki.org/"));
+        assert!(!allowed("https://notwiki.org/"));
+        assert!(!allowed("ftp://api.example.com/"));
+    }
+
+    #[test]
+    fn test_rejects_disallowed_domain_and_bad_args() {
+        let tool = HttpTool::new();
+        assert!(tool.execute(json!({"url": "https://example.com"})).is_err());
+        assert!(tool.execute(json!({})).is_err());
+
+        let tool = tool.allow_domain("example.com");
+        let err = tool
+            .execute(json!({"url": "https://example.com", "method": "DELETE"}))
+            .unwrap_err();
+        assert!(err.to_string().contains("Unsupported method"));
+    }
+
+    #[test]
+    fn test_fetch_json_and_truncate() {
+        let mut server = mockito::Server::new();
+        let _json = server
+            .mock("GET", "/data")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"answer":42}"#)
+            .create();
+        let _big = server
+            .mock("POST", "/big")
+            .with_body("x".repeat(100))
+            .create();
+
+        let tool = HttpTool::new()
+            .allow_domain("127.0.0.1")
+            .max_response_bytes(10);
+
+        let output = tool
+            .execute(json!({"url": format!("{}/data", server.url())}))
+            .unwrap();
+        assert!(output.starts_with("HTTP 200"));
+
+        let output = tool
+            .execute(json!({"url": format!("{}/big", server.url()), "method": "post"}))
+            .unwrap();
+        assert!(output.ends_with("[truncated at 10 bytes]"));
+    }
+}
@@ -0,0 +1,684 @@
+//! MCP (Model Context Protocol) client
+//!
+//! Connects to an MCP server, lists its tools, and exposes each one as a
+//! Patinox [`Tool`]. Two transports are built in:
+//!
+//! - **stdio** - spawn the server as a child process and exchange
+//!   newline-delimited JSON-RPC over its stdin/stdout
+//! - **SSE** - the HTTP transport: server messages arrive on an event stream
+//!   and client messages are POSTed to the endpoint the server announces
+//!
+//! The connection runs on its own thread and runtime, so tools can be called
+//! from the agent's synchronous tool loop. Dropping the last handle to the
+//! client closes the connection (and stops a stdio server).
+//!
+//! # Example
+//! ```ignore
+//! use patinox::tool::mcp::McpClient;
+//!
+//! let github = McpClient::stdio("npx", ["-y", "@modelcontextprotocol/server-github"])?;
+//!
+//! let mut agent = create_agent("maintainer");
+//! for tool in github.tools()? {
+//!     agent = agent.tool_in("github", tool);
+//! }
+//! ```
+
+use super::{Tool, ToolResult};
+use crate::sanitize::Utf8ChunkDecoder;
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::mpsc as async_mpsc;
+
+/// MCP protocol revision this client speaks
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Default time to wait for a response to a request
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// JSON-RPC error code for unknown methods
+pub(crate) const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Build a JSON-RPC success response
+pub(crate) fn response(id: Value, result: Value) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "result": result})
+}
+
+/// Build a JSON-RPC error response
+pub(crate) fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+/// A bidirectional channel carrying JSON-RPC messages to and from a server
+#[async_trait]
+pub trait McpTransport: Send {
+    /// Send one message to the server
+    async fn send(&mut self, message: Value) -> crate::Result<()>;
+
+    /// Wait for the next message from the server
+    async fn receive(&mut self) -> crate::Result<Value>;
+}
+
+/// Newline-delimited JSON-RPC over a child process's stdin/stdout
+pub struct StdioTransport {
+    // Held so the server is killed when the transport is dropped
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+impl StdioTransport {
+    /// Spawn the server process (must be called inside a tokio runtime)
+    pub fn spawn<I, S>(command: &str, args: I) -> crate::Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to start MCP server '{}': {}", command, e))?;
+        let stdin = child.stdin.take().ok_or("MCP server stdin not captured")?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or("MCP server stdout not captured")?;
+
+        Ok(Self {
+            _child: child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+        })
+    }
+}
+
+#[async_trait]
+impl McpTransport for StdioTransport {
+    async fn send(&mut self, message: Value) -> crate::Result<()> {
+        let mut line = serde_json::to_string(&message)?;
+        line.push('\n');
+        self.stdin.write_all(line.as_bytes()).await?;
+        self.stdin.flush().await?;
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> crate::Result<Value> {
+        loop {
+            let line = self
+                .stdout
+                .next_line()
+                .await?
+                .ok_or("MCP server closed its output")?;
+            if !line.trim().is_empty() {
+                return Ok(serde_json::from_str(&line)?);
+            }
+        }
+    }
+}
+
+/// One server-sent event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SseEvent {
+    pub event: String,
+    pub data: String,
+}
+
+/// Pull complete events out of an SSE buffer, leaving any partial event
+pub(crate) fn drain_sse_events(buffer: &mut String) -> Vec<SseEvent> {
+    let mut events = Vec::new();
+    let normalized = buffer.replace("\r\n", "\n");
+    let mut rest = normalized.as_str();
+
+    while let Some(end) = rest.find("\n\n") {
+        let block = &rest[..end];
+        rest = &rest[end + 2..];
+
+        let mut event = SseEvent {
+            event: "message".to_string(),
+            data: String::new(),
+        };
+        for line in block.lines() {
+            if let Some(value) = line.strip_prefix("event:") {
+                event.event = value.trim().to_string();
+            } else if let Some(value) = line.strip_prefix("data:") {
+                if !event.data.is_empty() {
+                    event.data.push('\n');
+                }
+                event
+                    .data
+                    .push_str(value.strip_prefix(' ').unwrap_or(value));
+            }
+        }
+        if !event.data.is_empty() {
+            events.push(event);
+        }
+    }
+
+    *buffer = rest.to_string();
+    events
+}
+
+type ByteStream = Pin<Box<dyn futures::Stream<Item = reqwest::Result<Vec<u8>>> + Send>>;
+
+/// HTTP + server-sent events transport
+pub struct SseTransport {
+    client: reqwest::Client,
+    endpoint: reqwest::Url,
+    stream: ByteStream,
+    decoder: Utf8ChunkDecoder,
+    buffer: String,
+    pending: VecDeque<SseEvent>,
+}
+
+impl SseTransport {
+    /// Open the event stream and wait for the server to announce its endpoint
+    pub async fn connect(url: &str) -> crate::Result<Self> {
+        let client = reqwest::Client::new();
+        let base = reqwest::Url::parse(url)?;
+        let response = client
+            .get(base.clone())
+            .header(reqwest::header::ACCEPT, "text/event-stream")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let mut transport = Self {
+            client,
+            endpoint: base.clone(),
+            stream: Box::pin(
+                response
+                    .bytes_stream()
+                    .map(|chunk| chunk.map(|b| b.to_vec())),
+            ),
+            decoder: Utf8ChunkDecoder::new(),
+            buffer: String::new(),
+            pending: VecDeque::new(),
+        };
+
+        loop {
+            let event = transport.next_event().await?;
+            if event.event == "endpoint" {
+                transport.endpoint = base.join(event.data.trim())?;
+                return Ok(transport);
+            }
+        }
+    }
+
+    async fn next_event(&mut self) -> crate::Result<SseEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(event);
+            }
+            let chunk = self
+                .stream
+                .next()
+                .await
+                .ok_or("MCP event stream closed")??;
+            self.buffer.push_str(&self.decoder.push(&chunk));
+            self.pending.extend(drain_sse_events(&mut self.buffer));
+        }
+    }
+}
+
+#[async_trait]
+impl McpTransport for SseTransport {
+    async fn send(&mut self, message: Value) -> crate::Result<()> {
+        self.client
+            .post(self.endpoint.clone())
+            .json(&message)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> crate::Result<Value> {
+        loop {
+            let event = self.next_event().await?;
+            if event.event == "message" {
+                return Ok(serde_json::from_str(&event.data)?);
+            }
+        }
+    }
+}
+
+/// Work sent to the connection thread
+enum Outgoing {
+    Request {
+        method: String,
+        params: Value,
+        reply: mpsc::Sender<Result<Value, String>>,
+    },
+    Notify {
+        method: String,
+        params: Value,
+    },
+}
+
+/// Send a request and wait for its response, answering server pings meanwhile
+async fn round_trip(
+    transport: &mut dyn McpTransport,
+    id: u64,
+    method: String,
+    params: Value,
+) -> crate::Result<Value> {
+    transport
+        .send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}))
+        .await?;
+
+    loop {
+        let message = transport.receive().await?;
+
+        // Requests from the server: answer ping, refuse the rest
+        if let (Some(method), Some(request_id)) = (message.get("method"), message.get("id")) {
+            let reply = if method == "ping" {
+                response(request_id.clone(), json!({}))
+            } else {
+                error_response(request_id.clone(), METHOD_NOT_FOUND, "Method not supported")
+            };
+            transport.send(reply).await?;
+            continue;
+        }
+
+        if message.get("id") != Some(&json!(id)) {
+            continue; // notification or stale response
+        }
+        if let Some(error) = message.get("error") {
+            return Err(format!(
+                "MCP error {}: {}",
+                error.get("code").unwrap_or(&Value::Null),
+                error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or("unknown error")
+            )
+            .into());
+        }
+        return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+    }
+}
+
+async fn run_connection(
+    mut transport: Box<dyn McpTransport>,
+    mut commands: async_mpsc::UnboundedReceiver<Outgoing>,
+    timeout: Duration,
+) {
+    let mut next_id = 1u64;
+    while let Some(command) = commands.recv().await {
+        match command {
+            Outgoing::Notify { method, params } => {
+                let message = json!({"jsonrpc": "2.0", "method": method, "params": params});
+                if let Err(e) = transport.send(message).await {
+                    tracing::warn!("MCP notification failed: {}", e);
+                }
+            }
+            Outgoing::Request {
+                method,
+                params,
+                reply,
+            } => {
+                let id = next_id;
+                next_id += 1;
+                let result = tokio::time::timeout(
+                    timeout,
+                    round_trip(transport.as_mut(), id, method.clone(), params),
+                )
+                .await
+                .unwrap_or_else(|_| Err(format!("MCP request '{}' timed out", method).into()))
+                .map_err(|e| e.to_string());
+                let _ = reply.send(result);
+            }
+        }
+    }
+}
+
+/// Tool metadata advertised by an MCP server
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpToolInfo {
+    pub name: String,
+    pub description: String,
+    pub input_schema: Value,
+}
+
+/// Connection to an MCP server
+pub struct McpClient {
+    commands: async_mpsc::UnboundedSender<Outgoing>,
+    server_info: Value,
+}
+
+impl McpClient {
+    /// Spawn a server process and connect over stdio
+    pub fn stdio<I, S>(command: impl Into<String>, args: I) -> crate::Result<Arc<Self>>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let command = command.into();
+        let args: Vec<String> = args.into_iter().map(Into::into).collect();
+        Self::start(
+            move || async move { StdioTransport::spawn(&command, &args) },
+            DEFAULT_REQUEST_TIMEOUT,
+        )
+    }
+
+    /// Connect to a server's SSE endpoint
+    pub fn sse(url: impl Into<String>) -> crate::Result<Arc<Self>> {
+        let url = url.into();
+        Self::start(
+            move || async move { SseTransport::connect(&url).await },
+            DEFAULT_REQUEST_TIMEOUT,
+        )
+    }
+
+    /// Connect over a custom transport
+    pub fn with_transport(transport: impl McpTransport + 'static) -> crate::Result<Arc<Self>> {
+        Self::start(
+            move || async move { Ok(transport) },
+            DEFAULT_REQUEST_TIMEOUT,
+        )
+    }
+
+    /// Start the connection thread, then perform the initialize handshake
+    fn start<F, Fut, T>(connect: F, timeout: Duration) -> crate::Result<Arc<Self>>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = crate::Result<T>>,
+        T: McpTransport + 'static,
+    {
+        let (commands, receiver) = async_mpsc::unbounded_channel();
+        let (ready, connected) = mpsc::channel::<Result<(), String>>();
+
+        std::thread::Builder::new()
+            .name("patinox-mcp".to_string())
+            .spawn(move || {
+                let runtime = match tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        let _ = ready.send(Err(e.to_string()));
+                        return;
+                    }
+                };
+                runtime.block_on(async move {
+                    match connect().await {
+                        Ok(transport) => {
+                            let _ = ready.send(Ok(()));
+                            run_connection(Box::new(transport), receiver, timeout).await;
+                        }
+                        Err(e) => {
+                            let _ = ready.send(Err(e.to_string()));
+                        }
+                    }
+                });
+            })?;
+
+        connected
+            .recv()
+            .map_err(|_| "MCP connection thread exited")??;
+
+        let mut client = Self {
+            commands,
+            server_info: Value::Null,
+        };
+        client.server_info = client.request(
+            "initialize",
+            json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": {"name": "patinox", "version": env!("CARGO_PKG_VERSION")}
+            }),
+        )?;
+        client.notify("notifications/initialized", json!({}))?;
+
+        Ok(Arc::new(client))
+    }
+
+    /// The server's `initialize` result (name, version, capabilities)
+    pub fn server_info(&self) -> &Value {
+        &self.server_info
+    }
+
+    /// Send a request and block until the response arrives
+    pub fn request(&self, method: &str, params: Value) -> crate::Result<Value> {
+        let (reply, response) = mpsc::channel();
+        self.commands
+            .send(Outgoing::Request {
+                method: method.to_string(),
+                params,
+                reply,
+            })
+            .map_err(|_| "MCP connection closed")?;
+        Ok(response.recv().map_err(|_| "MCP connection closed")??)
+    }
+
+    fn notify(&self, method: &str, params: Value) -> crate::Result<()> {
+        self.commands
+            .send(Outgoing::Notify {
+                method: method.to_string(),
+                params,
+            })
+            .map_err(|_| "MCP connection closed".into())
+    }
+
+    /// List the server's tools, following pagination
+    pub fn list_tools(&self) -> crate::Result<Vec<McpToolInfo>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({"cursor": cursor}),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params)?;
+
+            for tool in result
+                .get("tools")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                let Some(name) = tool.get("name").and_then(Value::as_str) else {
+                    continue;
+                };
+                tools.push(McpToolInfo {
+                    name: name.to_string(),
+                    description: tool
+                        .get("description")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string(),
+                    input_schema: tool
+                        .get("inputSchema")
+                        .cloned()
+                        .unwrap_or_else(|| json!({"type": "object", "properties": {}})),
+                });
+            }
+
+            cursor = result
+                .get("nextCursor")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    /// Call a tool and return its text content
+    pub fn call_tool(&self, name: &str, arguments: Value) -> ToolResult {
+        let result = self.request("tools/call", json!({"name": name, "arguments": arguments}))?;
+
+        let text = result
+            .get("content")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(|item| match item.get("type").and_then(Value::as_str) {
+                Some("text") => item
+                    .get("text")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+                // Images, audio and resources are passed through as JSON
+                _ => item.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if result.get("isError").and_then(Value::as_bool) == Some(true) {
+            Err(text.into())
+        } else {
+            Ok(text)
+        }
+    }
+
+    /// Every server tool wrapped as a Patinox tool
+    pub fn tools(self: &Arc<Self>) -> crate::Result<Vec<McpTool>> {
+        Ok(self
+            .list_tools()?
+            .into_iter()
+            .map(|info| McpTool {
+                client: Arc::clone(self),
+                info,
+            })
+            .collect())
+    }
+}
+
+/// A tool hosted on an MCP server
+pub struct McpTool {
+    client: Arc<McpClient>,
+    info: McpToolInfo,
+}
+
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    fn description(&self) -> &str {
+        &self.info.description
+    }
+
+    fn parameters(&self) -> Value {
+        self.info.input_schema.clone()
+    }
+
+    fn execute(&self, args: Value) -> ToolResult {
+        self.client.call_tool(&self.info.name, args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// In-process server answering from a fixed tool set
+    struct FakeServer {
+        outbox: VecDeque<Value>,
+    }
+
+    #[async_trait]
+    impl McpTransport for FakeServer {
+        async fn send(&mut self, message: Value) -> crate::Result<()> {
+            let (Some(id), Some(method)) = (message.get("id"), message.get("method")) else {
+                return Ok(()); // notifications
+            };
+            let result = match method.as_str().unwrap() {
+                "initialize" => json!({"serverInfo": {"name": "fake"}}),
+                "tools/list" if message["params"].get("cursor").is_none() => json!({
+                    "tools": [{"name": "echo", "description": "Echo text",
+                               "inputSchema": {"type": "object", "properties": {"text": {"type": "string"}}}}],
+                    "nextCursor": "page2"
+                }),
+                "tools/list" => json!({"tools": [{"name": "fail"}]}),
+                "tools/call" if message["params"]["name"] == "echo" => json!({
+                    "content": [{"type": "text", "text": message["params"]["arguments"]["text"]}]
+                }),
+                "tools/call" => {
+                    json!({"content": [{"type": "text", "text": "boom"}], "isError": true})
+                }
+                _ => {
+                    self.outbox
+                        .push_back(error_response(id.clone(), METHOD_NOT_FOUND, "nope"));
+                    return Ok(());
+                }
+            };
+            // A ping and a notification arrive before the response
+            self.outbox
+                .push_back(json!({"jsonrpc": "2.0", "id": "srv-1", "method": "ping"}));
+            self.outbox
+                .push_back(json!({"jsonrpc": "2.0", "method": "notifications/message"}));
+            self.outbox.push_back(response(id.clone(), result));
+            Ok(())
+        }
+
+        async fn receive(&mut self) -> crate::Result<Value> {
+            self.outbox.pop_front().ok_or_else(|| "no message".into())
+        }
+    }
+
+    fn client() -> Arc<McpClient> {
+        McpClient::with_transport(FakeServer {
+            outbox: VecDeque::new(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_handshake_and_paginated_tool_listing() {
+        let client = client();
+        assert_eq!(client.server_info()["serverInfo"]["name"], "fake");
+
+        let tools = client.list_tools().unwrap();
+        let names: Vec<_> = tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["echo", "fail"]);
+        assert_eq!(tools[1].input_schema["type"], "object");
+    }
+
+    #[test]
+    fn test_tools_execute_through_client() {
+        let tools = client().tools().unwrap();
+        assert_eq!(
+            tools[0].parameters()["properties"]["text"]["type"],
+            "string"
+        );
+        assert_eq!(tools[0].execute(json!({"text": "hi"})).unwrap(), "hi");
+        assert_eq!(tools[1].execute(json!({})).unwrap_err().to_string(), "boom");
+    }
+
+    #[test]
+    fn test_error_responses_surface() {
+        let err = client().request("resources/list", json!({})).unwrap_err();
+        assert!(err.to_string().contains("-32601"));
+    }
+
+    #[test]
+    fn test_sse_event_parsing_keeps_partial_events() {
+        let mut buffer =
+            "event: endpoint\ndata: /messages?s=1\n\ndata: {\"id\":1}\r\n\r\ndata: par".to_string();
+        let events = drain_sse_events(&mut buffer);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event, "endpoint");
+        assert_eq!(events[0].data, "/messages?s=1");
+        assert_eq!(events[1].event, "message");
+        assert_eq!(buffer, "data: par");
+    }
+}
@@ -2,8 +2,44 @@
 //!
 //! Tools are functions that agents can call. The minimal implementation
 //! supports simple string-based tools with easy integration.
-
-use serde_json::Value;
+//!
+//! ## Built-in Tools
+//!
+//! - [`http::HttpTool`] - HTTP GET/POST restricted to a domain allowlist
+//! - [`fs::FsSandbox`] - `read_file`, `write_file` and `list_dir` confined to a root
+//! - `mcp::McpClient` - tools hosted on an MCP server (stdio or SSE; `mcp` feature)
+//! - [`shell::ShellTool`] - allowlisted command execution with timeout and output caps
+//! - [`transcribe::TranscribeTool`] - speech-to-text for audio files in a sandbox
+//! - `time::CurrentTimeTool` - the current date and time in a given timezone
+//!   (`timezones` feature)
+//!
+//! Agents hold their tools in a [`ToolRegistry`], which handles lookup,
+//! de-duplication and `namespace.name` grouping.
+//!
+//! Tools whose output arrives incrementally implement
+//! [`streaming::StreamingTool`]; [`output::ToolOutputLimit`] keeps any tool's
+//! result from flooding the model's context.
+
+pub mod fs;
+pub mod http;
+#[cfg(feature = "mcp")]
+pub mod mcp;
+pub mod output;
+mod registry;
+pub mod shell;
+pub mod streaming;
+#[cfg(feature = "timezones")]
+pub mod time;
+pub mod transcribe;
+mod typed;
+
+pub(crate) use registry::namespaced;
+pub use registry::{wire_name, RegistryError, ToolInfo, ToolRegistry, NAMESPACE_SEPARATOR};
+pub use typed::{ToolArgumentError, TypedTool};
+
+use crate::provider::Capability;
+use serde_json::{json, Value};
+use std::future::Future;
 use std::sync::Arc;
 
 /// Result type for tool execution
@@ -17,10 +53,64 @@ pub trait Tool: Send + Sync {
     /// Description of what the tool does (helps LLM decide when to use it)
     fn description(&self) -> &str;
 
+    /// JSON schema for the tool's arguments (sent to the LLM)
+    ///
+    /// Defaults to an object with no declared properties.
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {},
+            "required": []
+        })
+    }
+
+    /// Whether the tool has side effects that deserve extra scrutiny
+    ///
+    /// Permission and approval layers use this to gate writes, deletes,
+    /// command execution and similar. Defaults to `false`.
+    fn dangerous(&self) -> bool {
+        false
+    }
+
+    /// Model capabilities the tool depends on besides tool calling
+    ///
+    /// Agents hide the tool from models that lack any of them. Defaults to
+    /// none.
+    fn required_capabilities(&self) -> Vec<Capability> {
+        Vec::new()
+    }
+
     /// Execute the tool with JSON arguments
     fn execute(&self, args: Value) -> ToolResult;
 }
 
+/// Run an async operation to completion from synchronous tool code
+///
+/// Tools execute synchronously, often on a thread that is already driving a
+/// tokio runtime, so the future gets its own thread and runtime. The
+/// [`ExecutionContext`](crate::execution::ExecutionContext) of the run goes
+/// with it.
+pub(crate) fn block_on<F>(future: F) -> Result<F::Output, Box<dyn std::error::Error + Send + Sync>>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    // The new thread keeps the caller's execution context
+    let context = crate::execution::ExecutionContext::current();
+    std::thread::spawn(move || {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map(|runtime| match context {
+                Some(context) => runtime.block_on(context.scope(future)),
+                None => runtime.block_on(future),
+            })
+    })
+    .join()
+    .map_err(|_| "Tool task panicked")?
+    .map_err(Into::into)
+}
+
 /// Function-based tool - wraps a closure as a Tool
 pub struct FnTool {
     name: String,
@@ -0,0 +1,170 @@
+//! Oversized tool output
+//!
+//! A shell command or file read can return far more text than the model
+//! needs, and every token of it is re-sent on each following model call. A
+//! [`ToolOutputLimit`] caps the estimated tokens of a tool result before it
+//! goes back to the model, either by cutting out the middle (keeping the
+//! start and end, where headers and errors usually are) or by asking a model
+//! for a summary.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::tool::output::ToolOutputLimit;
+//!
+//! let agent = create_agent("ops")
+//!     .with_tool_output_limit(ToolOutputLimit::new(2_000).summarize_with(cheap_model));
+//! ```
+
+use crate::provider::{LLMProvider, Message, ProviderResponse};
+use crate::tokens::{estimate_tokens, CHARS_PER_TOKEN};
+use std::sync::Arc;
+
+/// Output this many times over the limit is cut before summarizing
+const SUMMARY_INPUT_FACTOR: usize = 16;
+
+const SUMMARY_INSTRUCTIONS: &str = "Summarize this tool output for the assistant that \
+requested it. Keep errors, numbers, names and paths exactly; drop repetition.";
+
+/// Caps the tokens of tool results passed back to the model
+#[derive(Clone)]
+pub struct ToolOutputLimit {
+    max_tokens: usize,
+    summarizer: Option<Arc<dyn LLMProvider>>,
+}
+
+impl ToolOutputLimit {
+    /// Truncate results over `max_tokens`
+    pub fn new(max_tokens: usize) -> Self {
+        Self {
+            max_tokens: max_tokens.max(1),
+            summarizer: None,
+        }
+    }
+
+    /// Summarize oversized results with `provider` instead, falling back to
+    /// truncation if the summary fails or is still too long
+    pub fn summarize_with(mut self, provider: Arc<dyn LLMProvider>) -> Self {
+        self.summarizer = Some(provider);
+        self
+    }
+
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    /// `output` of `tool`, shortened to fit the limit
+    pub async fn apply(&self, tool: &str, output: String) -> String {
+        let tokens = estimate_tokens(&output);
+        if tokens <= self.max_tokens {
+            return output;
+        }
+        if let Some(provider) = &self.summarizer {
+            match self.summarize(provider.as_ref(), tool, &output).await {
+                Ok(summary) if estimate_tokens(&summary) <= self.max_tokens => {
+                    tracing::debug!("tool output: summarized {} tokens from '{}'", tokens, tool);
+                    return format!("[summary of ~{} tokens of output]\n{}", tokens, summary);
+                }
+                Ok(_) => tracing::debug!("tool output: summary of '{}' too long", tool),
+                Err(e) => tracing::debug!("tool output: summarizing '{}' failed: {}", tool, e),
+            }
+        }
+        truncate_output(&output, self.max_tokens)
+    }
+
+    async fn summarize(
+        &self,
+        provider: &dyn LLMProvider,
+        tool: &str,
+        output: &str,
+    ) -> crate::Result<String> {
+        let output = truncate_output(output, self.max_tokens * SUMMARY_INPUT_FACTOR);
+        let request = vec![
+            Message::system(SUMMARY_INSTRUCTIONS),
+            Message::user(format!("Output of tool '{}':\n{}", tool, output)),
+        ];
+        match provider.complete(request, Vec::new()).await? {
+            ProviderResponse::Text(text) => Ok(text),
+            ProviderResponse::ToolCalls(_) => {
+                Err("Summarizer returned tool calls instead of a summary".into())
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for ToolOutputLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ToolOutputLimit")
+            .field("max_tokens", &self.max_tokens)
+            .field("summarize", &self.summarizer.is_some())
+            .finish()
+    }
+}
+
+/// Keep the start and end of `output` within about `max_tokens`, marking
+/// what was cut from the middle
+pub fn truncate_output(output: &str, max_tokens: usize) -> String {
+    let total = estimate_tokens(output);
+    if total <= max_tokens {
+        return output.to_string();
+    }
+    let budget = max_tokens * CHARS_PER_TOKEN;
+    let chars = output.chars().count();
+    // Two thirds from the start, the rest from the end
+    let head = budget * 2 / 3;
+    let tail = budget - head;
+    let head_end = output
+        .char_indices()
+        .nth(head)
+        .map_or(output.len(), |(i, _)| i);
+    let tail_start = output
+        .char_indices()
+        .nth(chars - tail)
+        .map_or(output.len(), |(i, _)| i);
+    format!(
+        "{}\n[... ~{} of {} tokens omitted ...]\n{}",
+        &output[..head_end],
+        total.saturating_sub(max_tokens),
+        total,
+        &output[tail_start..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+
+    fn numbered_lines(count: usize) -> String {
+        (1..=count)
+            .map(|i| format!("line {:04}", i))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[tokio::test]
+    async fn test_truncate_keeps_start_and_end() {
+        let limit = ToolOutputLimit::new(30);
+        assert_eq!(limit.apply("cat", "short".to_string()).await, "short");
+
+        let output = limit.apply("cat", numbered_lines(100)).await;
+        assert!(output.starts_with("line 0001\nline 0002"));
+        assert!(output.ends_with("line 0099\nline 0100"));
+        assert!(output.contains("[... ~220 of 250 tokens omitted ...]"));
+        assert!(!output.contains("line 0050"));
+    }
+
+    #[tokio::test]
+    async fn test_summarize_falls_back_to_truncation() {
+        let summarized = ToolOutputLimit::new(30)
+            .summarize_with(Arc::new(MockProvider::new("100 lines, all fine")));
+        assert_eq!(
+            summarized.apply("cat", numbered_lines(100)).await,
+            "[summary of ~250 tokens of output]\n100 lines, all fine"
+        );
+
+        let verbose = ToolOutputLimit::new(30)
+            .summarize_with(Arc::new(MockProvider::new(numbered_lines(50))));
+        let output = verbose.apply("cat", numbered_lines(100)).await;
+        assert!(output.contains("tokens omitted"));
+    }
+}
@@ -0,0 +1,292 @@
+//! Tool registry
+//!
+//! The set of tools an agent can call. Tools are keyed by name, can be added
+//! and removed between runs, and can be grouped under a namespace
+//! (`fs.read`, `web.search`) so tools from different sources don't collide.
+//!
+//! Provider APIs restrict function names to `[a-zA-Z0-9_-]`, so providers
+//! send namespaced names in their [`wire_name`] form and map calls back.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::tool::ToolRegistry;
+//!
+//! let mut registry = ToolRegistry::new();
+//! registry.register_in("fs", sandbox.read_tool())?;   // "fs.read_file"
+//! registry.register(HttpTool::new().allow_domain("example.com"))?;
+//!
+//! let agent = create_agent("assistant").with_tools(registry);
+//! ```
+
+use super::{Tool, ToolResult};
+use crate::provider::{Capability, ToolDefinition};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Separator between a namespace and a tool name
+pub const NAMESPACE_SEPARATOR: char = '.';
+
+/// Encode a tool name for provider APIs that reject `.` in function names
+pub fn wire_name(name: &str) -> String {
+    name.replace(NAMESPACE_SEPARATOR, "__")
+}
+
+/// Error registering a tool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    /// A tool with this name is already registered
+    Duplicate(String),
+    /// The name is empty or contains characters providers reject
+    InvalidName(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::Duplicate(name) => write!(f, "Tool '{}' is already registered", name),
+            RegistryError::InvalidName(name) => write!(f, "Invalid tool name '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+/// Listing entry describing a registered tool
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolInfo {
+    pub name: String,
+    pub namespace: Option<String>,
+    pub description: String,
+    pub dangerous: bool,
+}
+
+/// A tool exposed under `namespace.name`
+struct Namespaced {
+    name: String,
+    inner: Arc<dyn Tool>,
+}
+
+impl Tool for Namespaced {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn parameters(&self) -> Value {
+        self.inner.parameters()
+    }
+
+    fn dangerous(&self) -> bool {
+        self.inner.dangerous()
+    }
+
+    fn required_capabilities(&self) -> Vec<Capability> {
+        self.inner.required_capabilities()
+    }
+
+    fn execute(&self, args: Value) -> ToolResult {
+        self.inner.execute(args)
+    }
+}
+
+/// Wrap a tool so it is exposed as `namespace.name`
+pub(crate) fn namespaced(namespace: &str, tool: impl Tool + 'static) -> Arc<dyn Tool> {
+    Arc::new(Namespaced {
+        name: format!("{}{}{}", namespace, NAMESPACE_SEPARATOR, tool.name()),
+        inner: Arc::new(tool),
+    })
+}
+
+fn validate_name(name: &str) -> Result<(), RegistryError> {
+    let valid = !name.is_empty()
+        && name.split(NAMESPACE_SEPARATOR).all(|part| !part.is_empty())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | NAMESPACE_SEPARATOR));
+    if valid {
+        Ok(())
+    } else {
+        Err(RegistryError::InvalidName(name.to_string()))
+    }
+}
+
+/// Named collection of tools
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: BTreeMap<String, Arc<dyn Tool>>,
+}
+
+impl fmt::Debug for ToolRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.tools.keys()).finish()
+    }
+}
+
+impl ToolRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a tool, failing if the name is taken
+    pub fn register(&mut self, tool: impl Tool + 'static) -> Result<(), RegistryError> {
+        self.register_arc(Arc::new(tool))
+    }
+
+    /// Register a shared tool, failing if the name is taken
+    pub fn register_arc(&mut self, tool: Arc<dyn Tool>) -> Result<(), RegistryError> {
+        let name = tool.name().to_string();
+        validate_name(&name)?;
+        if self.tools.contains_key(&name) {
+            return Err(RegistryError::Duplicate(name));
+        }
+        self.tools.insert(name, tool);
+        Ok(())
+    }
+
+    /// Register a tool as `namespace.name`
+    pub fn register_in(
+        &mut self,
+        namespace: &str,
+        tool: impl Tool + 'static,
+    ) -> Result<(), RegistryError> {
+        self.register_arc(namespaced(namespace, tool))
+    }
+
+    /// Add a tool, replacing any tool with the same name
+    pub fn insert(&mut self, tool: Arc<dyn Tool>) -> Option<Arc<dyn Tool>> {
+        self.tools.insert(tool.name().to_string(), tool)
+    }
+
+    /// Remove a tool by name
+    pub fn unregister(&mut self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.remove(name)
+    }
+
+    /// Remove every tool in a namespace, returning how many were removed
+    pub fn unregister_namespace(&mut self, namespace: &str) -> usize {
+        let prefix = format!("{}{}", namespace, NAMESPACE_SEPARATOR);
+        let before = self.tools.len();
+        self.tools.retain(|name, _| !name.starts_with(&prefix));
+        before - self.tools.len()
+    }
+
+    /// Look up a tool by name
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Tool>> {
+        self.tools.get(name)
+    }
+
+    /// Whether a tool with this name is registered
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.tools.contains_key(name)
+    }
+
+    /// Number of registered tools
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    /// Whether the registry is empty
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// Registered tools in name order
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn Tool>> {
+        self.tools.values()
+    }
+
+    /// Describe every registered tool, in name order
+    pub fn list(&self) -> Vec<ToolInfo> {
+        self.iter()
+            .map(|tool| ToolInfo {
+                name: tool.name().to_string(),
+                namespace: tool
+                    .name()
+                    .rsplit_once(NAMESPACE_SEPARATOR)
+                    .map(|(namespace, _)| namespace.to_string()),
+                description: tool.description().to_string(),
+                dangerous: tool.dangerous(),
+            })
+            .collect()
+    }
+
+    /// Tool definitions to send to an LLM provider
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.iter()
+            .map(|tool| ToolDefinition {
+                name: tool.name().to_string(),
+                description: tool.description().to_string(),
+                parameters: tool.parameters(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::FnTool;
+    use serde_json::json;
+
+    fn tool(name: &str) -> FnTool {
+        FnTool::new(name, format!("{} tool", name), |_| Ok("ok".to_string()))
+    }
+
+    #[test]
+    fn test_register_rejects_duplicates_and_bad_names() {
+        let mut registry = ToolRegistry::new();
+        registry.register(tool("search")).unwrap();
+
+        assert_eq!(
+            registry.register(tool("search")),
+            Err(RegistryError::Duplicate("search".to_string()))
+        );
+        assert!(matches!(
+            registry.register(tool("has space")),
+            Err(RegistryError::InvalidName(_))
+        ));
+        assert!(registry.register(tool("")).is_err());
+
+        // insert replaces instead
+        assert!(registry.insert(Arc::new(tool("search"))).is_some());
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let mut registry = ToolRegistry::new();
+        registry.register_in("fs", tool("read")).unwrap();
+        registry.register_in("fs", tool("write")).unwrap();
+        registry.register_in("web", tool("read")).unwrap();
+
+        let fs_read = registry.get("fs.read").unwrap();
+        assert_eq!(fs_read.name(), "fs.read");
+        assert_eq!(fs_read.execute(json!({})).unwrap(), "ok");
+
+        let listing = registry.list();
+        assert_eq!(listing[0].namespace.as_deref(), Some("fs"));
+
+        assert_eq!(registry.unregister_namespace("fs"), 2);
+        assert!(registry.contains_key("web.read"));
+        assert!(registry.unregister("web.read").is_some());
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_definitions_and_wire_names() {
+        let mut registry = ToolRegistry::new();
+        registry.register_in("fs", tool("read")).unwrap();
+        registry.register(tool("calc")).unwrap();
+
+        let names: Vec<_> = registry.definitions().into_iter().map(|d| d.name).collect();
+        assert_eq!(names, vec!["calc", "fs.read"]);
+        assert_eq!(wire_name("fs.read"), "fs__read");
+    }
+}
@@ -0,0 +1,325 @@
+//! Shell command tool with policy controls
+//!
+//! Runs a single binary with arguments (no shell interpretation, so pipes,
+//! globs and `;` have no special meaning). Binaries must be allowlisted,
+//! the denylist always wins, commands run inside a confined working
+//! directory, and both runtime and output size are capped.
+//!
+//! The tool is [`dangerous`](super::Tool::dangerous). Pair it with
+//! [`ToolPermissions::deny_arguments`](crate::hooks::ToolPermissions::deny_arguments)
+//! to block specific argument patterns before they reach the tool.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::tool::{fs::FsSandbox, shell::ShellTool};
+//!
+//! let shell = ShellTool::new()
+//!     .allow(["git", "ls", "cargo"])
+//!     .working_dir(FsSandbox::new("./repo")?)
+//!     .timeout(Duration::from_secs(60));
+//!
+//! let agent = create_agent("ops").tool(shell);
+//! ```
+
+use super::fs::FsSandbox;
+use super::{block_on, Tool, ToolResult};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+
+/// Default command timeout
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default cap on captured bytes per output stream
+const DEFAULT_MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// Built-in tool executing allowlisted commands
+#[derive(Debug, Clone)]
+pub struct ShellTool {
+    allowed: Option<HashSet<String>>,
+    denied: HashSet<String>,
+    sandbox: Option<FsSandbox>,
+    timeout: Duration,
+    max_output_bytes: usize,
+}
+
+impl Default for ShellTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShellTool {
+    /// Create a tool with an empty allowlist (every command is refused)
+    pub fn new() -> Self {
+        Self {
+            allowed: Some(HashSet::new()),
+            denied: HashSet::new(),
+            sandbox: None,
+            timeout: DEFAULT_TIMEOUT,
+            max_output_bytes: DEFAULT_MAX_OUTPUT_BYTES,
+        }
+    }
+
+    /// Allow the listed binaries
+    pub fn allow<I, S>(mut self, binaries: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed
+            .get_or_insert_with(HashSet::new)
+            .extend(binaries.into_iter().map(Into::into));
+        self
+    }
+
+    /// Allow any binary not on the denylist
+    pub fn allow_any(mut self) -> Self {
+        self.allowed = None;
+        self
+    }
+
+    /// Deny a binary (takes precedence over the allowlist)
+    pub fn deny(mut self, binary: impl Into<String>) -> Self {
+        self.denied.insert(binary.into());
+        self
+    }
+
+    /// Run commands inside a sandbox root; `cwd` arguments resolve within it
+    pub fn working_dir(mut self, sandbox: FsSandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Kill commands that run longer than this
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Cap the bytes captured from stdout and from stderr
+    pub fn max_output_bytes(mut self, bytes: usize) -> Self {
+        self.max_output_bytes = bytes;
+        self
+    }
+
+    /// Whether a command is permitted by the allow/deny lists
+    ///
+    /// The denylist matches the binary's file name, so denying `rm` also
+    /// denies `/bin/rm`. The allowlist matches exactly: a bare name is looked
+    /// up on `PATH`, and a command containing `/` or `\` runs only if that
+    /// exact path is allowlisted, so `allow(["ls"])` doesn't admit `./ls`.
+    pub fn is_allowed(&self, command: &str) -> bool {
+        let binary = Path::new(command)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(command);
+
+        if self.denied.contains(binary) || self.denied.contains(command) {
+            return false;
+        }
+        match &self.allowed {
+            Some(allowed) => allowed.contains(command),
+            None => true,
+        }
+    }
+
+    fn resolve_cwd(&self, cwd: Option<&str>) -> Result<Option<PathBuf>, String> {
+        match (&self.sandbox, cwd) {
+            (Some(sandbox), cwd) => sandbox.resolve(cwd.unwrap_or(".")).map(Some),
+            (None, Some(_)) => Err("'cwd' requires a configured working directory".to_string()),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Read a stream to the end, keeping at most `max` bytes
+async fn read_capped<R: AsyncRead + Unpin>(mut reader: R, max: usize) -> (Vec<u8>, bool) {
+    let mut kept = Vec::new();
+    let mut truncated = false;
+    let mut buffer = [0u8; 8192];
+    while let Ok(n) = reader.read(&mut buffer).await {
+        if n == 0 {
+            break;
+        }
+        let room = max.saturating_sub(kept.len());
+        kept.extend_from_slice(&buffer[..n.min(room)]);
+        truncated |= n > room;
+    }
+    (kept, truncated)
+}
+
+async fn run(
+    command: String,
+    args: Vec<String>,
+    cwd: Option<PathBuf>,
+    timeout: Duration,
+    max_bytes: usize,
+) -> ToolResult {
+    let mut cmd = Command::new(&command);
+    cmd.args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(cwd) = cwd {
+        cmd.current_dir(cwd);
+    }
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to start '{}': {}", command, e))?;
+    let stdout = child.stdout.take().ok_or("stdout not captured")?;
+    let stderr = child.stderr.take().ok_or("stderr not captured")?;
+
+    let execution = async {
+        let ((stdout, stdout_truncated), (stderr, stderr_truncated), status) = tokio::join!(
+            read_capped(stdout, max_bytes),
+            read_capped(stderr, max_bytes),
+            child.wait()
+        );
+        (stdout, stderr, stdout_truncated || stderr_truncated, status)
+    };
+
+    let output = match tokio::time::timeout(timeout, execution).await {
+        Ok((stdout, stderr, truncated, status)) => json!({
+            "exit_code": status?.code(),
+            "stdout": String::from_utf8_lossy(&stdout),
+            "stderr": String::from_utf8_lossy(&stderr),
+            "truncated": truncated,
+            "timed_out": false,
+        }),
+        // Dropping the future drops the child, which kills it
+        Err(_) => json!({
+            "exit_code": null,
+            "stdout": "",
+            "stderr": format!("Command timed out after {:?}", timeout),
+            "truncated": false,
+            "timed_out": true,
+        }),
+    };
+
+    Ok(output.to_string())
+}
+
+impl Tool for ShellTool {
+    fn name(&self) -> &str {
+        "shell"
+    }
+
+    fn description(&self) -> &str {
+        "Run an allowed command with arguments and return its exit code, stdout and stderr as JSON"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "command": {"type": "string", "description": "Binary to run"},
+                "args": {"type": "array", "items": {"type": "string"}},
+                "cwd": {"type": "string", "description": "Directory relative to the workspace root"}
+            },
+            "required": ["command"]
+        })
+    }
+
+    fn dangerous(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, args: Value) -> ToolResult {
+        let command = args
+            .get("command")
+            .and_then(Value::as_str)
+            .ok_or("Missing required argument 'command'")?;
+        if !self.is_allowed(command) {
+            return Err(format!("Command not allowed: {}", command).into());
+        }
+
+        let command_args = match args.get("args") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(items)) => items
+                .iter()
+                .map(|item| item.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .ok_or("'args' must be an array of strings")?,
+            Some(_) => return Err("'args' must be an array of strings".into()),
+        };
+        let cwd = self.resolve_cwd(args.get("cwd").and_then(Value::as_str))?;
+
+        block_on(run(
+            command.to_string(),
+            command_args,
+            cwd,
+            self.timeout,
+            self.max_output_bytes,
+        ))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allow_and_deny_lists() {
+        let tool = ShellTool::new().allow(["ls", "git"]).deny("git");
+        assert!(tool.is_allowed("ls"));
+        assert!(!tool.is_allowed("git"));
+        assert!(!tool.is_allowed("rm"));
+
+        // A path is not the allowlisted binary, whatever its file name
+        assert!(!tool.is_allowed("./ls"));
+        assert!(!tool.is_allowed("/tmp/x/ls"));
+        assert!(!tool.is_allowed(r"C:\tools\ls"));
+        let pinned = ShellTool::new().allow(["/bin/ls"]);
+        assert!(pinned.is_allowed("/bin/ls"));
+        assert!(!pinned.is_allowed("ls"));
+
+        let open = ShellTool::new().allow_any().deny("rm");
+        assert!(open.is_allowed("anything"));
+        assert!(!open.is_allowed("/usr/bin/rm"));
+
+        assert!(ShellTool::new().dangerous());
+        assert!(ShellTool::new().execute(json!({"command": "ls"})).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_structured_output_and_truncation() {
+        let tool = ShellTool::new().allow(["echo"]).max_output_bytes(5);
+        let output = tool
+            .execute(json!({"command": "echo", "args": ["hello world"]}))
+            .unwrap();
+        let output: Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(output["exit_code"], 0);
+        assert_eq!(output["stdout"], "hello");
+        assert_eq!(output["truncated"], true);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_timeout_kills_command() {
+        let tool = ShellTool::new()
+            .allow(["sleep"])
+            .timeout(Duration::from_millis(100));
+        let output = tool
+            .execute(json!({"command": "sleep", "args": ["5"]}))
+            .unwrap();
+        let output: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(output["timed_out"], true);
+    }
+
+    #[test]
+    fn test_cwd_requires_sandbox() {
+        let tool = ShellTool::new().allow(["ls"]);
+        assert!(tool
+            .execute(json!({"command": "ls", "cwd": "sub"}))
+            .is_err());
+    }
+}
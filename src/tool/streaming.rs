@@ -0,0 +1,198 @@
+//! Tools that produce output incrementally
+//!
+//! A [`StreamingTool`] yields its output as a stream of chunks instead of
+//! one string, so a long-running command or a large file read can be cut
+//! off once enough output has arrived. Wrap one in [`Streamed`] to register
+//! it like any other [`Tool`]; with
+//! [`max_output_tokens`](Streamed::max_output_tokens) set, the stream is
+//! dropped as soon as the output passes the limit.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::tool::streaming::{Streamed, StreamingTool, ToolChunks};
+//!
+//! struct Tail;
+//!
+//! impl StreamingTool for Tail {
+//!     fn name(&self) -> &str { "tail_log" }
+//!     fn description(&self) -> &str { "Follow the service log" }
+//!     fn execute_stream(&self, _args: Value) -> ToolChunks {
+//!         Box::pin(log_lines().map(Ok))
+//!     }
+//! }
+//!
+//! let agent = create_agent("ops").tool(Streamed::new(Tail).max_output_tokens(1_000));
+//! ```
+
+use super::{block_on, Tool, ToolResult};
+use crate::tokens::{estimate_tokens, CHARS_PER_TOKEN};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+/// Chunks of a streaming tool's output; an error ends the call
+pub type ToolChunks = BoxStream<'static, ToolResult>;
+
+/// A tool whose output arrives in chunks
+pub trait StreamingTool: Send + Sync {
+    /// Name of the tool (used by LLM to identify it)
+    fn name(&self) -> &str;
+
+    /// Description of what the tool does (helps LLM decide when to use it)
+    fn description(&self) -> &str;
+
+    /// JSON schema for the tool's arguments
+    ///
+    /// Defaults to an object with no declared properties.
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {},
+            "required": []
+        })
+    }
+
+    /// Whether the tool has side effects that deserve extra scrutiny
+    fn dangerous(&self) -> bool {
+        false
+    }
+
+    /// Start the tool; dropping the stream should stop it
+    fn execute_stream(&self, args: Value) -> ToolChunks;
+}
+
+/// A [`StreamingTool`] usable as a [`Tool`]
+pub struct Streamed<T> {
+    tool: T,
+    max_output_tokens: Option<usize>,
+}
+
+impl<T: StreamingTool> Streamed<T> {
+    pub fn new(tool: T) -> Self {
+        Self {
+            tool,
+            max_output_tokens: None,
+        }
+    }
+
+    /// Stop reading once the output passes `tokens`
+    pub fn max_output_tokens(mut self, tokens: usize) -> Self {
+        self.max_output_tokens = Some(tokens.max(1));
+        self
+    }
+}
+
+impl<T: StreamingTool> Tool for Streamed<T> {
+    fn name(&self) -> &str {
+        self.tool.name()
+    }
+
+    fn description(&self) -> &str {
+        self.tool.description()
+    }
+
+    fn parameters(&self) -> Value {
+        self.tool.parameters()
+    }
+
+    fn dangerous(&self) -> bool {
+        self.tool.dangerous()
+    }
+
+    fn execute(&self, args: Value) -> ToolResult {
+        block_on(collect(
+            self.tool.execute_stream(args),
+            self.max_output_tokens,
+        ))?
+    }
+}
+
+/// Concatenate chunks, dropping the stream once past `limit` tokens
+async fn collect(mut chunks: ToolChunks, limit: Option<usize>) -> ToolResult {
+    let mut output = String::new();
+    while let Some(chunk) = chunks.next().await {
+        output.push_str(&chunk?);
+        let Some(limit) = limit else { continue };
+        if estimate_tokens(&output) > limit {
+            let end = output
+                .char_indices()
+                .nth(limit * CHARS_PER_TOKEN)
+                .map_or(output.len(), |(i, _)| i);
+            output.truncate(end);
+            output.push_str(&format!("\n[output stopped after ~{} tokens]", limit));
+            break;
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Emits numbered lines forever, counting how many were produced
+    struct Counter {
+        produced: Arc<AtomicUsize>,
+    }
+
+    impl StreamingTool for Counter {
+        fn name(&self) -> &str {
+            "count"
+        }
+
+        fn description(&self) -> &str {
+            "Count forever"
+        }
+
+        fn execute_stream(&self, _args: Value) -> ToolChunks {
+            let produced = self.produced.clone();
+            Box::pin(futures::stream::iter(1..).map(move |i| {
+                produced.fetch_add(1, Ordering::SeqCst);
+                Ok(format!("{:07}\n", i))
+            }))
+        }
+    }
+
+    #[test]
+    fn test_stream_stops_at_limit() {
+        let produced = Arc::new(AtomicUsize::new(0));
+        let tool = Streamed::new(Counter {
+            produced: produced.clone(),
+        })
+        .max_output_tokens(10);
+
+        let output = tool.execute(json!({})).unwrap();
+        assert_eq!(
+            output,
+            "0000001\n0000002\n0000003\n0000004\n0000005\n\n[output stopped after ~10 tokens]"
+        );
+        assert_eq!(produced.load(Ordering::SeqCst), 6);
+    }
+
+    #[test]
+    fn test_stream_error_fails_call() {
+        struct Broken;
+
+        impl StreamingTool for Broken {
+            fn name(&self) -> &str {
+                "broken"
+            }
+
+            fn description(&self) -> &str {
+                "Fails halfway"
+            }
+
+            fn execute_stream(&self, _args: Value) -> ToolChunks {
+                Box::pin(futures::stream::iter(vec![
+                    Ok("partial".to_string()),
+                    Err("disk read failed".into()),
+                ]))
+            }
+        }
+
+        let error = Streamed::new(Broken).execute(json!({})).unwrap_err();
+        assert_eq!(error.to_string(), "disk read failed");
+    }
+}
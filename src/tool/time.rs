@@ -0,0 +1,132 @@
+//! Current date and time tool
+//!
+//! Models don't know what day it is and tend to assume their training
+//! cutoff. [`CurrentTimeTool`] (`current_time`) answers with the date, time,
+//! weekday and UTC offset in the configured IANA timezone, or in a timezone
+//! the model asks for.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::tool::time::CurrentTimeTool;
+//!
+//! let agent = create_agent("scheduler").tool(CurrentTimeTool::new().timezone(chrono_tz::Europe::Paris));
+//! ```
+
+use super::{Tool, ToolResult};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde_json::{json, Value};
+
+/// The system's IANA timezone, or UTC if it can't be determined
+pub fn system_timezone() -> Tz {
+    iana_time_zone::get_timezone()
+        .ok()
+        .and_then(|name| name.parse().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+/// Date and time fields for `now` as seen in `timezone`
+pub(crate) fn time_fields(now: DateTime<Utc>, timezone: Tz) -> Value {
+    let local = now.with_timezone(&timezone);
+    json!({
+        "iso": local.to_rfc3339(),
+        "date": local.format("%Y-%m-%d").to_string(),
+        "time": local.format("%H:%M").to_string(),
+        "weekday": local.format("%A").to_string(),
+        "timezone": timezone.name(),
+        "utc_offset": local.format("%:z").to_string(),
+    })
+}
+
+/// Built-in tool reporting the current date and time
+#[derive(Debug, Clone)]
+pub struct CurrentTimeTool {
+    timezone: Tz,
+}
+
+impl Default for CurrentTimeTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CurrentTimeTool {
+    /// Report times in the system timezone
+    pub fn new() -> Self {
+        Self {
+            timezone: system_timezone(),
+        }
+    }
+
+    /// Report times in `timezone` unless the model asks for another
+    pub fn timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+}
+
+impl Tool for CurrentTimeTool {
+    fn name(&self) -> &str {
+        "current_time"
+    }
+
+    fn description(&self) -> &str {
+        "Get the current date, time and weekday. Use this instead of guessing today's date."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "timezone": {
+                    "type": "string",
+                    "description": "IANA timezone such as America/New_York; defaults to the user's"
+                }
+            },
+            "required": []
+        })
+    }
+
+    fn execute(&self, args: Value) -> ToolResult {
+        let timezone = match args.get("timezone").and_then(Value::as_str) {
+            Some(name) => name
+                .parse::<Tz>()
+                .map_err(|_| format!("Unknown timezone '{}'", name))?,
+            None => self.timezone,
+        };
+        Ok(time_fields(Utc::now(), timezone).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_time_fields() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 9, 23, 30, 0).unwrap();
+        let fields = time_fields(now, chrono_tz::Asia::Tokyo);
+        assert_eq!(fields["date"], "2025-03-10");
+        assert_eq!(fields["time"], "08:30");
+        assert_eq!(fields["weekday"], "Monday");
+        assert_eq!(fields["utc_offset"], "+09:00");
+        assert_eq!(fields["iso"], "2025-03-10T08:30:00+09:00");
+    }
+
+    #[test]
+    fn test_current_time_tool() {
+        let tool = CurrentTimeTool::new().timezone(chrono_tz::Europe::Paris);
+        let output: Value = serde_json::from_str(&tool.execute(json!({})).unwrap()).unwrap();
+        assert_eq!(output["timezone"], "Europe/Paris");
+
+        let output: Value =
+            serde_json::from_str(&tool.execute(json!({"timezone": "UTC"})).unwrap()).unwrap();
+        assert_eq!(output["utc_offset"], "+00:00");
+
+        let err = tool
+            .execute(json!({"timezone": "Mars/Olympus"}))
+            .unwrap_err();
+        assert!(err.to_string().contains("Unknown timezone"));
+    }
+}
@@ -0,0 +1,129 @@
+//! Audio transcription tool
+//!
+//! [`TranscribeTool`] lets an agent read audio files: it resolves the path
+//! inside an [`FsSandbox`], sends the file to a [`SpeechToText`] service and
+//! returns the text.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::provider::speech::WhisperApi;
+//! use patinox::tool::{fs::FsSandbox, transcribe::TranscribeTool};
+//!
+//! let uploads = FsSandbox::new("./uploads")?;
+//! let agent = create_agent("minutes")
+//!     .tool(TranscribeTool::new(Arc::new(WhisperApi::from_env()?), &uploads));
+//! ```
+
+use super::fs::FsSandbox;
+use super::{block_on, Tool, ToolResult};
+use crate::provider::speech::{AudioInput, SpeechToText};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// Upload limit of the OpenAI transcription API
+const DEFAULT_MAX_AUDIO_BYTES: u64 = 25 * 1024 * 1024;
+
+/// Transcribes audio files inside a sandbox
+pub struct TranscribeTool {
+    stt: Arc<dyn SpeechToText>,
+    sandbox: FsSandbox,
+    max_audio_bytes: u64,
+}
+
+impl TranscribeTool {
+    pub fn new(stt: Arc<dyn SpeechToText>, sandbox: &FsSandbox) -> Self {
+        Self {
+            stt,
+            sandbox: sandbox.clone(),
+            max_audio_bytes: DEFAULT_MAX_AUDIO_BYTES,
+        }
+    }
+
+    /// Refuse files larger than `bytes`
+    pub fn max_audio_bytes(mut self, bytes: u64) -> Self {
+        self.max_audio_bytes = bytes;
+        self
+    }
+}
+
+impl Tool for TranscribeTool {
+    fn name(&self) -> &str {
+        "transcribe"
+    }
+
+    fn description(&self) -> &str {
+        "Transcribe an audio file (path relative to the workspace root) to text"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {"path": {"type": "string"}},
+            "required": ["path"]
+        })
+    }
+
+    fn execute(&self, args: Value) -> ToolResult {
+        let relative = args
+            .get("path")
+            .and_then(Value::as_str)
+            .ok_or("Missing required argument 'path'")?;
+        let path = self.sandbox.resolve(relative)?;
+        let size = std::fs::metadata(&path)?.len();
+        if size > self.max_audio_bytes {
+            return Err(format!(
+                "Audio file is {} bytes, over the {} byte limit",
+                size, self.max_audio_bytes
+            )
+            .into());
+        }
+        let audio = AudioInput::from_file(&path)?;
+        let stt = self.stt.clone();
+        let transcription = block_on(async move { stt.transcribe(audio).await })??;
+        Ok(transcription.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::speech::Transcription;
+    use crate::provider::ProviderResult;
+
+    struct ByteCounter;
+
+    #[async_trait::async_trait]
+    impl SpeechToText for ByteCounter {
+        async fn transcribe(&self, audio: AudioInput) -> ProviderResult<Transcription> {
+            Ok(Transcription {
+                text: format!(
+                    "{} ({}, {} bytes)",
+                    audio.file_name,
+                    audio.media_type,
+                    audio.bytes.len()
+                ),
+                language: None,
+                duration: None,
+            })
+        }
+    }
+
+    #[test]
+    fn test_transcribe_tool() {
+        let dir = std::env::temp_dir().join(format!("patinox-stt-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("memo.mp3"), [0u8; 10]).unwrap();
+        let sandbox = FsSandbox::new(&dir).unwrap();
+        let tool = TranscribeTool::new(Arc::new(ByteCounter), &sandbox);
+
+        assert_eq!(
+            tool.execute(json!({"path": "memo.mp3"})).unwrap(),
+            "memo.mp3 (audio/mpeg, 10 bytes)"
+        );
+        assert!(tool.execute(json!({"path": "../memo.mp3"})).is_err());
+        let small = TranscribeTool::new(Arc::new(ByteCounter), &sandbox).max_audio_bytes(5);
+        let err = small.execute(json!({"path": "memo.mp3"})).unwrap_err();
+        assert!(err.to_string().contains("over the 5 byte limit"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
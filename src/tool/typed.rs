@@ -0,0 +1,195 @@
+//! Typed tool parameters
+//!
+//! [`TypedTool`] derives the argument schema from a Rust type and
+//! deserializes the model's arguments into it, so handlers work with structs
+//! instead of picking fields out of a `Value`. Arguments that don't match the
+//! type come back as a [`ToolArgumentError`] naming the problem, which the
+//! model can read and correct.
+//!
+//! # Example
+//! ```ignore
+//! #[derive(Deserialize, JsonSchema)]
+//! struct Weather {
+//!     /// City name
+//!     city: String,
+//!     #[serde(default)]
+//!     celsius: bool,
+//! }
+//!
+//! let agent = create_agent("forecaster").tool_fn_typed(
+//!     "weather",
+//!     "Current weather for a city",
+//!     |args: Weather| Ok(format!("Sunny in {}", args.city)),
+//! );
+//! ```
+
+use super::{Tool, ToolResult};
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// Arguments from the model did not match the tool's parameter type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolArgumentError {
+    pub tool: String,
+    pub message: String,
+}
+
+impl fmt::Display for ToolArgumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid arguments for '{}': {}", self.tool, self.message)
+    }
+}
+
+impl std::error::Error for ToolArgumentError {}
+
+type TypedHandler<P, R> =
+    dyn Fn(P) -> Result<R, Box<dyn std::error::Error + Send + Sync>> + Send + Sync;
+
+/// Tool whose arguments are a deserializable, schema-described type
+pub struct TypedTool<P, R> {
+    name: String,
+    description: String,
+    schema: Value,
+    handler: Arc<TypedHandler<P, R>>,
+    _types: PhantomData<fn(P) -> R>,
+}
+
+/// JSON schema for `P` with subschemas inlined (providers handle `$ref` poorly)
+fn schema_for<P: JsonSchema>() -> Value {
+    let generator = SchemaSettings::draft07()
+        .with(|settings| {
+            settings.inline_subschemas = true;
+            settings.meta_schema = None;
+        })
+        .into_generator();
+    let mut schema = serde_json::to_value(generator.into_root_schema_for::<P>())
+        .unwrap_or_else(|_| Value::Object(Default::default()));
+
+    if let Value::Object(map) = &mut schema {
+        map.remove("title");
+        map.remove("definitions");
+        map.entry("properties")
+            .or_insert_with(|| Value::Object(Default::default()));
+    }
+    schema
+}
+
+impl<P, R> TypedTool<P, R>
+where
+    P: JsonSchema + DeserializeOwned + 'static,
+    R: Serialize + 'static,
+{
+    /// Create a typed tool; the parameter schema is generated from `P`
+    pub fn new<F>(name: impl Into<String>, description: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(P) -> Result<R, Box<dyn std::error::Error + Send + Sync>> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            schema: schema_for::<P>(),
+            handler: Arc::new(handler),
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<P, R> Tool for TypedTool<P, R>
+where
+    P: JsonSchema + DeserializeOwned + 'static,
+    R: Serialize + 'static,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Value {
+        self.schema.clone()
+    }
+
+    fn execute(&self, args: Value) -> ToolResult {
+        // Models sometimes send no arguments for all-optional parameters
+        let args = if args.is_null() {
+            Value::Object(Default::default())
+        } else {
+            args
+        };
+        let params: P = serde_json::from_value(args).map_err(|e| ToolArgumentError {
+            tool: self.name.clone(),
+            message: e.to_string(),
+        })?;
+
+        // Strings are returned as-is; anything else as JSON
+        match serde_json::to_value((self.handler)(params)?)? {
+            Value::String(text) => Ok(text),
+            other => Ok(other.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct Add {
+        /// First operand
+        a: i64,
+        b: i64,
+        #[serde(default)]
+        negate: bool,
+    }
+
+    #[derive(Serialize)]
+    struct Sum {
+        total: i64,
+    }
+
+    fn adder() -> TypedTool<Add, Sum> {
+        TypedTool::new("add", "Add two numbers", |p: Add| {
+            let total = p.a + p.b;
+            Ok(Sum {
+                total: if p.negate { -total } else { total },
+            })
+        })
+    }
+
+    #[test]
+    fn test_schema_generated_from_type() {
+        let schema = adder().parameters();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["a"]["type"], "integer");
+        assert_eq!(schema["properties"]["a"]["description"], "First operand");
+        assert_eq!(schema["required"], json!(["a", "b"]));
+        assert!(schema.get("$schema").is_none());
+    }
+
+    #[test]
+    fn test_execute_deserializes_and_serializes() {
+        let output = adder().execute(json!({"a": 2, "b": 3})).unwrap();
+        assert_eq!(output, r#"{"total":5}"#);
+
+        let echo = TypedTool::new("echo", "Echo", |p: Add| Ok(format!("{}+{}", p.a, p.b)));
+        assert_eq!(echo.execute(json!({"a": 1, "b": 1})).unwrap(), "1+1");
+    }
+
+    #[test]
+    fn test_mismatched_arguments_are_structured_errors() {
+        let err = adder().execute(json!({"a": "two"})).unwrap_err();
+        let err = err.downcast_ref::<ToolArgumentError>().unwrap();
+        assert_eq!(err.tool, "add");
+        assert!(err.message.contains("invalid type"));
+    }
+}
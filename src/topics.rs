@@ -0,0 +1,289 @@
+//! Topic segmentation for long sessions
+//!
+//! A long session drifts between subjects. [`TopicSegmenter`] embeds each
+//! message and starts a new [`TopicSegment`] where a message stops resembling
+//! the recent ones (a drop in cosine similarity below a threshold), giving a
+//! [`TopicMap`] of the conversation.
+//!
+//! The map is used two ways:
+//!
+//! - [`Summarize::by_topic`](crate::context::Summarize::by_topic) summarizes
+//!   each segment on its own instead of one blob, so short topics are not
+//!   drowned out by long ones
+//! - [`TopicSegmenter::find`] answers "when did we discuss X" by returning
+//!   the segment closest to a query, so its messages can be brought back
+//!
+//! # Example
+//! ```ignore
+//! use patinox::topics::TopicSegmenter;
+//!
+//! let segmenter = TopicSegmenter::new(embedder);
+//! let map = segmenter.segment(&messages).await?;
+//! if let Some(segment) = segmenter.find(&map, "the database migration").await? {
+//!     println!("{}: messages {}..{}", segment.label, segment.start, segment.end);
+//! }
+//! ```
+
+use crate::provider::{EmbeddingProvider, Message};
+use crate::retrieval::cosine_similarity;
+use std::sync::Arc;
+
+/// Characters of the opening message kept as a segment label
+const LABEL_CHARS: usize = 60;
+
+/// A run of messages about one subject
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicSegment {
+    /// Index of the first message
+    pub start: usize,
+    /// Index one past the last message
+    pub end: usize,
+    /// Start of the segment's first user message
+    pub label: String,
+    /// Mean embedding of the segment's messages
+    pub centroid: Vec<f32>,
+}
+
+impl TopicSegment {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The segment's messages within the conversation it was built from
+    pub fn messages<'a>(&self, messages: &'a [Message]) -> &'a [Message] {
+        &messages[self.start..self.end.min(messages.len())]
+    }
+}
+
+/// The topical segments of a conversation, in order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TopicMap {
+    pub segments: Vec<TopicSegment>,
+}
+
+impl TopicMap {
+    /// The segment containing message `index`
+    pub fn segment_of(&self, index: usize) -> Option<&TopicSegment> {
+        self.segments
+            .iter()
+            .find(|segment| (segment.start..segment.end).contains(&index))
+    }
+
+    /// The segment whose centroid is most similar to `embedding`
+    pub fn closest(&self, embedding: &[f32]) -> Option<&TopicSegment> {
+        self.segments
+            .iter()
+            .map(|segment| (segment, cosine_similarity(&segment.centroid, embedding)))
+            .filter(|(_, score)| *score > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(segment, _)| segment)
+    }
+}
+
+fn mean(vectors: &[&Vec<f32>]) -> Vec<f32> {
+    let Some(first) = vectors.first() else {
+        return Vec::new();
+    };
+    let mut sum = vec![0.0; first.len()];
+    for vector in vectors {
+        for (total, x) in sum.iter_mut().zip(vector.iter()) {
+            *total += x;
+        }
+    }
+    sum.iter()
+        .map(|total| total / vectors.len() as f32)
+        .collect()
+}
+
+/// Splits conversations at topic changes using embeddings
+pub struct TopicSegmenter {
+    embedder: Arc<dyn EmbeddingProvider>,
+    threshold: f32,
+    window: usize,
+    min_segment: usize,
+}
+
+impl TopicSegmenter {
+    /// Segment with `embedder`; defaults: threshold 0.35, window 3, minimum
+    /// segment of 2 messages
+    pub fn new(embedder: Arc<dyn EmbeddingProvider>) -> Self {
+        Self {
+            embedder,
+            threshold: 0.35,
+            window: 3,
+            min_segment: 2,
+        }
+    }
+
+    /// Similarity below which a message starts a new topic
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Number of recent messages a new message is compared against
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    /// Fewest messages in a segment before it may end
+    pub fn min_segment(mut self, min_segment: usize) -> Self {
+        self.min_segment = min_segment.max(1);
+        self
+    }
+
+    /// Segment `messages`, skipping leading system messages
+    ///
+    /// Messages without text (tool-call requests) stay in the current
+    /// segment.
+    pub async fn segment(&self, messages: &[Message]) -> crate::Result<TopicMap> {
+        let first = messages.iter().take_while(|m| m.role == "system").count();
+        let texts: Vec<(usize, String)> = messages
+            .iter()
+            .enumerate()
+            .skip(first)
+            .filter(|(_, m)| !m.content.trim().is_empty())
+            .map(|(i, m)| (i, m.content.clone()))
+            .collect();
+        if texts.is_empty() {
+            return Ok(TopicMap::default());
+        }
+        let embeddings = self
+            .embedder
+            .embed(texts.iter().map(|(_, text)| text.clone()).collect())
+            .await?;
+        if embeddings.len() != texts.len() {
+            return Err(format!(
+                "Embedder returned {} vectors for {} messages",
+                embeddings.len(),
+                texts.len()
+            )
+            .into());
+        }
+
+        // Starts of segments, as positions in `texts`
+        let mut starts = vec![0];
+        for position in 1..texts.len() {
+            let start = *starts.last().unwrap();
+            if position - start < self.min_segment {
+                continue;
+            }
+            let recent: Vec<&Vec<f32>> = embeddings
+                [start.max(position.saturating_sub(self.window))..position]
+                .iter()
+                .collect();
+            if cosine_similarity(&mean(&recent), &embeddings[position]) < self.threshold {
+                starts.push(position);
+            }
+        }
+
+        let mut segments = Vec::new();
+        for (n, &start) in starts.iter().enumerate() {
+            let stop = starts.get(n + 1).copied().unwrap_or(texts.len());
+            let members: Vec<&Vec<f32>> = embeddings[start..stop].iter().collect();
+            let first_index = if n == 0 { first } else { texts[start].0 };
+            let end = match starts.get(n + 1) {
+                Some(&next) => texts[next].0,
+                None => messages.len(),
+            };
+            let label_source = texts[start..stop]
+                .iter()
+                .find(|(i, _)| messages[*i].role == "user")
+                .unwrap_or(&texts[start]);
+            segments.push(TopicSegment {
+                start: first_index,
+                end,
+                label: label_source.1.chars().take(LABEL_CHARS).collect(),
+                centroid: mean(&members),
+            });
+        }
+        tracing::debug!(
+            "topics: {} messages in {} segments",
+            messages.len() - first,
+            segments.len()
+        );
+        Ok(TopicMap { segments })
+    }
+
+    /// The segment of `map` closest to `query`
+    pub async fn find(&self, map: &TopicMap, query: &str) -> crate::Result<Option<TopicSegment>> {
+        let embedding = self
+            .embedder
+            .embed(vec![query.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or("Embedder returned no vector for the query")?;
+        Ok(map.closest(&embedding).cloned())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::provider::ProviderResult;
+
+    /// Counts words from a few fixed vocabularies, one dimension each
+    pub(crate) struct KeywordEmbedder;
+
+    const VOCABULARIES: [&[&str]; 3] = [
+        &["database", "migration", "schema", "postgres"],
+        &["invoice", "billing", "payment", "refund"],
+        &["deploy", "kubernetes", "rollout", "cluster"],
+    ];
+
+    #[async_trait::async_trait]
+    impl EmbeddingProvider for KeywordEmbedder {
+        async fn embed(&self, texts: Vec<String>) -> ProviderResult<Vec<Vec<f32>>> {
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    let text = text.to_lowercase();
+                    VOCABULARIES
+                        .iter()
+                        .map(|words| words.iter().filter(|w| text.contains(*w)).count() as f32)
+                        .collect()
+                })
+                .collect())
+        }
+    }
+
+    pub(crate) fn session() -> Vec<Message> {
+        vec![
+            Message::system("You are helpful."),
+            Message::user("Plan the database migration to the new schema"),
+            Message::assistant("The migration adds two schema tables in postgres"),
+            Message::user("Does the migration lock the database?"),
+            Message::user("Now a billing question: why was this invoice charged twice?"),
+            Message::assistant("The payment was retried; a refund is on its way"),
+            Message::user("Finally, deploy the fix to the kubernetes cluster"),
+            Message::assistant("Rollout started on the cluster"),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_segments_and_find() {
+        let segmenter = TopicSegmenter::new(Arc::new(KeywordEmbedder));
+        let messages = session();
+        let map = segmenter.segment(&messages).await.unwrap();
+
+        let bounds: Vec<(usize, usize)> = map.segments.iter().map(|s| (s.start, s.end)).collect();
+        assert_eq!(bounds, vec![(1, 4), (4, 6), (6, 8)]);
+        assert!(map.segments[1].label.starts_with("Now a billing question"));
+        assert_eq!(map.segment_of(5), Some(&map.segments[1]));
+        assert_eq!(map.segments[2].messages(&messages).len(), 2);
+
+        let found = segmenter
+            .find(&map, "what did we say about the refund?")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.start, 4);
+        assert!(segmenter.find(&map, "unrelated").await.unwrap().is_none());
+    }
+}
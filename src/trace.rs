@@ -0,0 +1,542 @@
+//! Execution traces and trace diffs
+//!
+//! An [`ExecutionTrace`] is the turn-by-turn record of one run, built from
+//! its [`AgentEvent`]s: which tools the model called with which arguments,
+//! the text it produced, token usage and timings. Traces serialize to JSON so
+//! they can be stored next to a prompt change and compared later.
+//!
+//! [`TraceDiff::compare`] aligns two traces turn by turn and lists where they
+//! diverge (tool choice, arguments, response text, rejections, outcome)
+//! along with token, tool-call and latency deltas. The diff is available as
+//! JSON (`Serialize`) and as a human-readable report (`Display`).
+//!
+//! # Example
+//! ```ignore
+//! use patinox::trace::{ExecutionTrace, TraceDiff};
+//!
+//! let before = ExecutionTrace::capture(&old_agent, "Plan my trip").await;
+//! let after = ExecutionTrace::capture(&new_agent, "Plan my trip").await;
+//! let diff = TraceDiff::compare(&before, &after);
+//! println!("{}", diff);
+//! ```
+
+use crate::events::{AgentEvent, TurnUsage};
+use crate::provider::PricingCatalog;
+use crate::Agent;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// A tool call made during a turn
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceToolCall {
+    pub name: String,
+    pub arguments: Value,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// One model call and the tool calls it led to
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TraceTurn {
+    pub turn: usize,
+    pub tool_calls: Vec<TraceToolCall>,
+    /// Text the model produced, for the final turn
+    pub text: Option<String>,
+    /// Set when a lifecycle hook rejected the response
+    pub rejected: Option<String>,
+    pub usage: TurnUsage,
+    pub duration_ms: u64,
+}
+
+/// Turn-by-turn record of one run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionTrace {
+    pub turns: Vec<TraceTurn>,
+    /// Final output or error; `None` if the events ended early
+    pub outcome: Option<Result<String, String>>,
+}
+
+impl ExecutionTrace {
+    /// Run `agent` on `input` and record the trace
+    pub async fn capture(agent: &Agent, input: impl Into<String>) -> Self {
+        let events: Vec<AgentEvent> = agent.execute_streaming(input).collect().await;
+        Self::from_events(&events)
+    }
+
+    /// Rebuild a trace from a run's events
+    pub fn from_events(events: &[AgentEvent]) -> Self {
+        let mut trace = Self::default();
+        for event in events {
+            match event {
+                AgentEvent::TurnStarted { turn } => trace.turns.push(TraceTurn {
+                    turn: *turn,
+                    ..Default::default()
+                }),
+                AgentEvent::Completed { output } => trace.outcome = Some(Ok(output.clone())),
+                AgentEvent::Failed { error } => trace.outcome = Some(Err(error.clone())),
+                event => {
+                    let Some(current) = trace.turns.last_mut() else {
+                        continue;
+                    };
+                    match event {
+                        AgentEvent::LlmDelta { content } => current
+                            .text
+                            .get_or_insert_with(String::new)
+                            .push_str(content),
+                        AgentEvent::ToolCallStarted { name, arguments } => {
+                            current.tool_calls.push(TraceToolCall {
+                                name: name.clone(),
+                                arguments: arguments.clone(),
+                                output: None,
+                                error: None,
+                                duration_ms: 0,
+                            })
+                        }
+                        AgentEvent::ToolCallFinished {
+                            name,
+                            output,
+                            error,
+                            duration_ms,
+                        } => {
+                            // Calls of a turn may overlap; results arrive in
+                            // the order the calls were made
+                            if let Some(call) = current.tool_calls.iter_mut().find(|call| {
+                                call.name == *name && call.output.is_none() && call.error.is_none()
+                            }) {
+                                call.output = output.clone();
+                                call.error = error.clone();
+                                call.duration_ms = *duration_ms;
+                            }
+                        }
+                        AgentEvent::ValidationRejected { reason } => {
+                            current.rejected = Some(reason.clone())
+                        }
+                        AgentEvent::TurnFinished {
+                            usage, duration_ms, ..
+                        } => {
+                            current.usage = *usage;
+                            current.duration_ms = *duration_ms;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        trace
+    }
+
+    pub fn prompt_tokens(&self) -> usize {
+        self.turns.iter().map(|t| t.usage.prompt_tokens).sum()
+    }
+
+    pub fn completion_tokens(&self) -> usize {
+        self.turns.iter().map(|t| t.usage.completion_tokens).sum()
+    }
+
+    pub fn tool_calls(&self) -> usize {
+        self.turns.iter().map(|t| t.tool_calls.len()).sum()
+    }
+
+    /// Sum of turn durations
+    pub fn duration_ms(&self) -> u64 {
+        self.turns.iter().map(|t| t.duration_ms).sum()
+    }
+
+    /// Estimated USD cost, if `model` is priced
+    pub fn cost(&self, pricing: &PricingCatalog, model: &str) -> Option<f64> {
+        pricing.estimate_cost(model, self.prompt_tokens(), self.completion_tokens())
+    }
+}
+
+/// A before/after pair of numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Delta {
+    pub before: i64,
+    pub after: i64,
+}
+
+impl Delta {
+    fn new(before: impl TryInto<i64>, after: impl TryInto<i64>) -> Self {
+        Self {
+            before: before.try_into().unwrap_or(i64::MAX),
+            after: after.try_into().unwrap_or(i64::MAX),
+        }
+    }
+
+    pub fn change(&self) -> i64 {
+        self.after - self.before
+    }
+}
+
+impl fmt::Display for Delta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {} ({:+})", self.before, self.after, self.change())
+    }
+}
+
+/// Where two traces part ways
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Divergence {
+    /// The runs took a different number of turns
+    TurnCount { before: usize, after: usize },
+    /// Different tools, or tools in a different order
+    ToolChoice {
+        turn: usize,
+        before: Vec<String>,
+        after: Vec<String>,
+    },
+    /// Same tool at the same position, different arguments
+    Arguments {
+        turn: usize,
+        tool: String,
+        before: Value,
+        after: Value,
+    },
+    /// Different response text, as a line diff (`-`, `+` and ` ` prefixes)
+    Text { turn: usize, diff: Vec<String> },
+    /// A hook rejected one response but not the other, or for another reason
+    Rejection {
+        turn: usize,
+        before: Option<String>,
+        after: Option<String>,
+    },
+    /// One run succeeded and the other failed, or they failed differently
+    Outcome {
+        before: Option<Result<String, String>>,
+        after: Option<Result<String, String>>,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::TurnCount { before, after } => {
+                write!(f, "turn count: {} -> {}", before, after)
+            }
+            Divergence::ToolChoice {
+                turn,
+                before,
+                after,
+            } => write!(
+                f,
+                "turn {}: tool choice [{}] -> [{}]",
+                turn,
+                before.join(", "),
+                after.join(", ")
+            ),
+            Divergence::Arguments {
+                turn,
+                tool,
+                before,
+                after,
+            } => write!(
+                f,
+                "turn {}: arguments of {}: {} -> {}",
+                turn, tool, before, after
+            ),
+            Divergence::Text { turn, diff } => {
+                write!(f, "turn {}: response text", turn)?;
+                for line in diff {
+                    write!(f, "\n    {}", line)?;
+                }
+                Ok(())
+            }
+            Divergence::Rejection {
+                turn,
+                before,
+                after,
+            } => write!(
+                f,
+                "turn {}: rejection {:?} -> {:?}",
+                turn,
+                before.as_deref().unwrap_or("none"),
+                after.as_deref().unwrap_or("none")
+            ),
+            Divergence::Outcome { before, after } => {
+                let describe = |outcome: &Option<Result<String, String>>| match outcome {
+                    Some(Ok(_)) => "completed".to_string(),
+                    Some(Err(e)) => format!("failed ({})", e),
+                    None => "unfinished".to_string(),
+                };
+                write!(f, "outcome: {} -> {}", describe(before), describe(after))
+            }
+        }
+    }
+}
+
+/// Differences between two traces of the same task
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceDiff {
+    pub divergences: Vec<Divergence>,
+    pub prompt_tokens: Delta,
+    pub completion_tokens: Delta,
+    pub tool_calls: Delta,
+    pub duration_ms: Delta,
+}
+
+impl TraceDiff {
+    /// Align `before` and `after` turn by turn and collect divergences
+    pub fn compare(before: &ExecutionTrace, after: &ExecutionTrace) -> Self {
+        let mut divergences = Vec::new();
+        if before.turns.len() != after.turns.len() {
+            divergences.push(Divergence::TurnCount {
+                before: before.turns.len(),
+                after: after.turns.len(),
+            });
+        }
+
+        for (old, new) in before.turns.iter().zip(&after.turns) {
+            let turn = old.turn;
+            let old_tools: Vec<String> = old.tool_calls.iter().map(|c| c.name.clone()).collect();
+            let new_tools: Vec<String> = new.tool_calls.iter().map(|c| c.name.clone()).collect();
+            if old_tools != new_tools {
+                divergences.push(Divergence::ToolChoice {
+                    turn,
+                    before: old_tools,
+                    after: new_tools,
+                });
+            } else {
+                for (a, b) in old.tool_calls.iter().zip(&new.tool_calls) {
+                    if a.arguments != b.arguments {
+                        divergences.push(Divergence::Arguments {
+                            turn,
+                            tool: a.name.clone(),
+                            before: a.arguments.clone(),
+                            after: b.arguments.clone(),
+                        });
+                    }
+                }
+            }
+
+            if old.text != new.text {
+                divergences.push(Divergence::Text {
+                    turn,
+                    diff: line_diff(
+                        old.text.as_deref().unwrap_or_default(),
+                        new.text.as_deref().unwrap_or_default(),
+                    ),
+                });
+            }
+            if old.rejected != new.rejected {
+                divergences.push(Divergence::Rejection {
+                    turn,
+                    before: old.rejected.clone(),
+                    after: new.rejected.clone(),
+                });
+            }
+        }
+
+        // Output text differences already show up in the final turn's text
+        let outcome_changed = match (&before.outcome, &after.outcome) {
+            (Some(Ok(_)), Some(Ok(_))) => false,
+            (a, b) => a != b,
+        };
+        if outcome_changed {
+            divergences.push(Divergence::Outcome {
+                before: before.outcome.clone(),
+                after: after.outcome.clone(),
+            });
+        }
+
+        Self {
+            divergences,
+            prompt_tokens: Delta::new(before.prompt_tokens(), after.prompt_tokens()),
+            completion_tokens: Delta::new(before.completion_tokens(), after.completion_tokens()),
+            tool_calls: Delta::new(before.tool_calls(), after.tool_calls()),
+            duration_ms: Delta::new(before.duration_ms(), after.duration_ms()),
+        }
+    }
+
+    /// Estimated USD cost before and after, if `model` is priced
+    pub fn cost(&self, pricing: &PricingCatalog, model: &str) -> Option<(f64, f64)> {
+        let price = pricing.price(model)?;
+        let cost = |prompt: i64, completion: i64| price.cost(prompt as usize, completion as usize);
+        Some((
+            cost(self.prompt_tokens.before, self.completion_tokens.before),
+            cost(self.prompt_tokens.after, self.completion_tokens.after),
+        ))
+    }
+
+    /// Whether the traces made the same decisions
+    pub fn is_identical(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+impl fmt::Display for TraceDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.divergences.len() {
+            0 => writeln!(f, "Traces match")?,
+            n => {
+                writeln!(f, "{} divergence{}:", n, if n == 1 { "" } else { "s" })?;
+                for divergence in &self.divergences {
+                    writeln!(f, "  {}", divergence)?;
+                }
+            }
+        }
+        writeln!(f, "prompt tokens: {}", self.prompt_tokens)?;
+        writeln!(f, "completion tokens: {}", self.completion_tokens)?;
+        writeln!(f, "tool calls: {}", self.tool_calls)?;
+        write!(f, "duration ms: {}", self.duration_ms)
+    }
+}
+
+/// Line diff via longest common subsequence
+fn line_diff(before: &str, after: &str) -> Vec<String> {
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+
+    // lcs[i][j] = LCS length of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut diff = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            diff.push(format!("  {}", a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push(format!("- {}", a[i]));
+            i += 1;
+        } else {
+            diff.push(format!("+ {}", b[j]));
+            j += 1;
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn events(query: &str, answer: &str, tools: &[&str]) -> Vec<AgentEvent> {
+        let mut events = vec![AgentEvent::TurnStarted { turn: 1 }];
+        for tool in tools {
+            events.push(AgentEvent::ToolCallStarted {
+                name: tool.to_string(),
+                arguments: json!({"q": query}),
+            });
+            events.push(AgentEvent::ToolCallFinished {
+                name: tool.to_string(),
+                output: Some("result".into()),
+                error: None,
+                duration_ms: 5,
+            });
+        }
+        events.extend([
+            AgentEvent::TurnFinished {
+                turn: 1,
+                usage: TurnUsage {
+                    prompt_tokens: 100,
+                    completion_tokens: 10,
+                    ..TurnUsage::default()
+                },
+                duration_ms: 50,
+            },
+            AgentEvent::TurnStarted { turn: 2 },
+            AgentEvent::LlmDelta {
+                content: answer.to_string(),
+            },
+            AgentEvent::TurnFinished {
+                turn: 2,
+                usage: TurnUsage {
+                    prompt_tokens: 150,
+                    completion_tokens: 20,
+                    ..TurnUsage::default()
+                },
+                duration_ms: 40,
+            },
+            AgentEvent::Completed {
+                output: answer.to_string(),
+            },
+        ]);
+        events
+    }
+
+    #[test]
+    fn test_trace_from_events() {
+        let trace = ExecutionTrace::from_events(&events("rust", "Done", &["search"]));
+        assert_eq!(trace.turns.len(), 2);
+        assert_eq!(
+            trace.turns[0].tool_calls[0].output.as_deref(),
+            Some("result")
+        );
+        assert_eq!(trace.turns[1].text.as_deref(), Some("Done"));
+        assert_eq!(
+            (trace.prompt_tokens(), trace.completion_tokens()),
+            (250, 30)
+        );
+        assert_eq!(trace.duration_ms(), 90);
+        assert_eq!(trace.outcome, Some(Ok("Done".to_string())));
+    }
+
+    #[test]
+    fn test_compare_reports_divergences() {
+        let before = ExecutionTrace::from_events(&events("rust", "A\nB\nC", &["search"]));
+        assert!(TraceDiff::compare(&before, &before).is_identical());
+
+        let after = ExecutionTrace::from_events(&events("go", "A\nX\nC", &["search"]));
+        let diff = TraceDiff::compare(&before, &after);
+        assert_eq!(
+            diff.divergences,
+            vec![
+                Divergence::Arguments {
+                    turn: 1,
+                    tool: "search".to_string(),
+                    before: json!({"q": "rust"}),
+                    after: json!({"q": "go"}),
+                },
+                Divergence::Text {
+                    turn: 2,
+                    diff: vec!["  A".into(), "- B".into(), "+ X".into(), "  C".into()],
+                },
+            ]
+        );
+
+        let after = ExecutionTrace::from_events(&events("rust", "A\nB\nC", &["search", "fetch"]));
+        let diff = TraceDiff::compare(&before, &after);
+        assert!(matches!(
+            &diff.divergences[..],
+            [Divergence::ToolChoice { turn: 1, .. }]
+        ));
+        assert_eq!(diff.tool_calls.change(), 1);
+        let pricing =
+            PricingCatalog::new().with_override("m", crate::provider::ModelPrice::new(1.0, 1.0));
+        assert_eq!(diff.cost(&pricing, "m"), Some((0.28, 0.28)));
+
+        let report = diff.to_string();
+        assert!(report.contains("turn 1: tool choice [search] -> [search, fetch]"));
+        assert!(report.contains("tool calls: 1 -> 2 (+1)"));
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(json["divergences"][0]["kind"], "tool_choice");
+    }
+
+    #[test]
+    fn test_outcome_divergence() {
+        let before = ExecutionTrace::from_events(&events("rust", "Done", &[]));
+        let mut failing = events("rust", "Done", &[]);
+        *failing.last_mut().unwrap() = AgentEvent::Failed {
+            error: "boom".into(),
+        };
+        let diff = TraceDiff::compare(&before, &ExecutionTrace::from_events(&failing));
+        assert!(diff
+            .to_string()
+            .contains("outcome: completed -> failed (boom)"));
+    }
+}
@@ -0,0 +1,305 @@
+//! Tool-call transcripts
+//!
+//! A [`ToolTranscript`] records every tool call an agent makes: the
+//! arguments, the output or error, and how long it took, along with the
+//! run's input and final answer. A recorded run can be exported as a
+//! readable shell-style log, or as a Rust test that replays it: a scripted
+//! [`MockProvider`](crate::provider::MockProvider) makes the recorded calls,
+//! mock tools return the recorded outputs, and the test asserts the same
+//! calls are made again. That turns a problematic run into a regression
+//! test.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::transcript::ToolTranscript;
+//!
+//! let transcript = ToolTranscript::new();
+//! let agent = create_agent("ops").with_transcript(transcript.clone());
+//! agent.run("rotate the logs").await?;
+//!
+//! std::fs::write("tests/rotate_logs.rs", transcript.to_rust_test("rotate_logs"))?;
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// One recorded tool call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    pub tool: String,
+    pub arguments: Value,
+    /// The tool's output, or its error message
+    pub output: Result<String, String>,
+    pub duration_ms: u64,
+}
+
+/// Shared, append-only log of tool calls (clones record into the same log)
+#[derive(Debug, Clone, Default)]
+pub struct ToolTranscript {
+    records: Arc<Mutex<Vec<ToolCallRecord>>>,
+    run: Arc<Mutex<RunRecord>>,
+}
+
+/// Input and answer of the most recent run
+#[derive(Debug, Clone, Default)]
+struct RunRecord {
+    input: Option<String>,
+    answer: Option<String>,
+}
+
+impl ToolTranscript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note the input of a run that is starting
+    pub fn start(&self, input: &str) {
+        *self.run.lock().unwrap() = RunRecord {
+            input: Some(input.to_string()),
+            answer: None,
+        };
+    }
+
+    /// Note the model's final answer
+    pub fn finish(&self, answer: &str) {
+        self.run.lock().unwrap().answer = Some(answer.to_string());
+    }
+
+    /// Input of the most recent run, if one was recorded
+    pub fn input(&self) -> Option<String> {
+        self.run.lock().unwrap().input.clone()
+    }
+
+    /// The model's final answer in the most recent run, if it finished
+    pub fn answer(&self) -> Option<String> {
+        self.run.lock().unwrap().answer.clone()
+    }
+
+    /// Append a call
+    pub fn record(
+        &self,
+        tool: &str,
+        arguments: Value,
+        output: Result<&str, String>,
+        duration: Duration,
+    ) {
+        self.records.lock().unwrap().push(ToolCallRecord {
+            tool: tool.to_string(),
+            arguments,
+            output: output.map(str::to_string),
+            duration_ms: duration.as_millis() as u64,
+        });
+    }
+
+    /// Calls recorded so far
+    pub fn records(&self) -> Vec<ToolCallRecord> {
+        self.records.lock().unwrap().clone()
+    }
+
+    /// Forget all recorded calls and the run
+    pub fn clear(&self) {
+        self.records.lock().unwrap().clear();
+        *self.run.lock().unwrap() = RunRecord::default();
+    }
+
+    /// Shell-style log: each call as a `$` line followed by its output
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for record in self.records() {
+            let _ = writeln!(
+                out,
+                "$ {} '{}'  # {} ms",
+                record.tool,
+                record.arguments.to_string().replace('\'', r"'\''"),
+                record.duration_ms
+            );
+            match &record.output {
+                Ok(output) => {
+                    let _ = writeln!(out, "{}", output);
+                }
+                Err(error) => {
+                    let _ = writeln!(out, "! error: {}", error);
+                }
+            }
+        }
+        out
+    }
+
+    /// Rust test replaying the recorded run against mock tools
+    ///
+    /// The provider is a scripted [`MockProvider`](crate::provider::MockProvider)
+    /// that makes each recorded call in turn and then gives the recorded
+    /// answer. Each tool becomes an [`FnTool`](crate::tool::FnTool) that
+    /// returns the recorded outputs in order and logs its arguments; the test
+    /// runs the recorded input and asserts the logged calls match the
+    /// transcript. Calls from earlier runs are replayed too, so
+    /// [`clear`](Self::clear) the transcript between runs to capture one.
+    pub fn to_rust_test(&self, name: &str) -> String {
+        let run = self.run.lock().unwrap().clone();
+        let records = self.records();
+        let mut tools: Vec<&str> = Vec::new();
+        for record in &records {
+            if !tools.contains(&record.tool.as_str()) {
+                tools.push(&record.tool);
+            }
+        }
+
+        let mut out = String::new();
+        let _ = writeln!(out, "#[tokio::test]");
+        let _ = writeln!(out, "async fn replay_{}() {{", rust_ident(name));
+        let _ = writeln!(out, "    use patinox::provider::MockProvider;");
+        let _ = writeln!(out, "    use patinox::{{create_agent, FnTool}};");
+        let _ = writeln!(out, "    use serde_json::json;");
+        let _ = writeln!(out, "    use std::sync::{{Arc, Mutex}};");
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "    let calls: Arc<Mutex<Vec<(String, serde_json::Value)>>> = Arc::default();"
+        );
+        let _ = writeln!(out, "    let agent = create_agent({:?})", name);
+
+        for tool in &tools {
+            let outputs: Vec<String> = records
+                .iter()
+                .filter(|r| r.tool == *tool)
+                .map(|r| match &r.output {
+                    Ok(output) => format!("Ok({:?})", output),
+                    Err(error) => format!("Err({:?})", error),
+                })
+                .collect();
+            let _ = writeln!(
+                out,
+                "        .tool(FnTool::new({:?}, \"Recorded {}\", {{",
+                tool,
+                tool.escape_default()
+            );
+            let _ = writeln!(out, "            let calls = calls.clone();");
+            let _ = writeln!(
+                out,
+                "            let outputs: Vec<Result<&str, &str>> = vec![{}];",
+                outputs.join(", ")
+            );
+            let _ = writeln!(out, "            move |args| {{");
+            let _ = writeln!(
+                out,
+                "                let mut calls = calls.lock().unwrap();"
+            );
+            let _ = writeln!(
+                out,
+                "                let n = calls.iter().filter(|(name, _)| name == {:?}).count();",
+                tool
+            );
+            let _ = writeln!(
+                out,
+                "                calls.push(({:?}.to_string(), args));",
+                tool
+            );
+            let _ = writeln!(
+                out,
+                "                outputs[n].map(str::to_string).map_err(Into::into)"
+            );
+            let _ = writeln!(out, "            }}");
+            let _ = writeln!(out, "        }}))");
+        }
+
+        let _ = writeln!(out, "        .with_provider(Box::new(");
+        let _ = writeln!(out, "            MockProvider::scripted()");
+        for record in &records {
+            let _ = writeln!(
+                out,
+                "                .then_tool_call({:?}, json!({}))",
+                record.tool, record.arguments
+            );
+        }
+        let _ = writeln!(
+            out,
+            "                .then_text({:?}),",
+            run.answer.as_deref().unwrap_or_default()
+        );
+        let _ = writeln!(out, "        ));");
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "    let _ = agent.run({:?}).await;",
+            run.input.as_deref().unwrap_or_default()
+        );
+        let _ = writeln!(out);
+        let _ = writeln!(out, "    let expected = vec![");
+        for record in &records {
+            let _ = writeln!(
+                out,
+                "        ({:?}.to_string(), json!({})),",
+                record.tool, record.arguments
+            );
+        }
+        let _ = writeln!(out, "    ];");
+        let _ = writeln!(out, "    assert_eq!(*calls.lock().unwrap(), expected);");
+        let _ = writeln!(out, "}}");
+        out
+    }
+}
+
+/// Turn a label into a valid identifier fragment
+fn rust_ident(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn transcript() -> ToolTranscript {
+        let transcript = ToolTranscript::new();
+        transcript.start("clean \"up\"");
+        transcript.record(
+            "read_file",
+            json!({"path": "it's.txt"}),
+            Ok("hello"),
+            Duration::from_millis(3),
+        );
+        transcript.record(
+            "shell",
+            json!({"command": "rm"}),
+            Err("not allowed".to_string()),
+            Duration::ZERO,
+        );
+        transcript.finish("Done.");
+        transcript
+    }
+
+    #[test]
+    fn test_text_export() {
+        let text = transcript().to_text();
+        assert!(text.starts_with("$ read_file '{\"path\":\"it'\\''s.txt\"}'  # 3 ms\nhello\n"));
+        assert!(text.contains("! error: not allowed"));
+    }
+
+    #[test]
+    fn test_rust_test_export() {
+        let code = transcript().to_rust_test("Log rotation");
+        assert!(code.contains("async fn replay_log_rotation()"));
+        assert!(code.contains(r#"vec![Ok("hello")]"#));
+        assert!(code.contains(r#"vec![Err("not allowed")]"#));
+        assert!(code.contains(r#"("shell".to_string(), json!({"command":"rm"})),"#));
+        assert!(code.contains(
+            r#"            MockProvider::scripted()
+                .then_tool_call("read_file", json!({"path":"it's.txt"}))
+                .then_tool_call("shell", json!({"command":"rm"}))
+                .then_text("Done."),"#
+        ));
+        assert!(code.contains(r#"agent.run("clean \"up\"").await"#));
+        assert!(!code.contains("todo!") && !code.contains("TODO"));
+    }
+}
@@ -0,0 +1,475 @@
+//! Webhooks that trigger agent runs
+//!
+//! Enabled with the `webhook` feature. A [`WebhookRoute`] turns signed HTTP
+//! POSTs from services like GitHub, Slack or Stripe into agent runs without
+//! a glue server: the request's signature is checked, the JSON payload is
+//! rendered into the agent's input with a [`PromptTemplate`], and the run
+//! starts in the background. The sender gets `202 Accepted` with the run's
+//! `trace_id` right away, since most services give up on slow endpoints.
+//!
+//! Signatures are HMAC-SHA256 in each service's format (see [`Signature`]);
+//! timestamped schemes reject requests older than five minutes, so captured
+//! requests can't be replayed. Every route is verified unless it is built
+//! with [`WebhookRoute::unverified`], for senders that can't sign, which
+//! lets anyone who can reach the endpoint run the agent. A bad signature is
+//! answered with 401, a payload that isn't JSON or lacks a template
+//! variable with 400. Slack's `url_verification` handshake is answered
+//! automatically.
+//!
+//! Each run's [`ExecutionContext`] has the generated `trace_id` and the
+//! values `webhook` (the route path) and `delivery` (the sender's delivery
+//! id, when it sends one).
+//!
+//! # Example
+//! ```ignore
+//! use patinox::prompt::PromptTemplate;
+//! use patinox::webhook::{serve_webhooks, Signature, WebhookRoute};
+//!
+//! let triage = WebhookRoute::new(
+//!     "/github",
+//!     Arc::new(agent),
+//!     PromptTemplate::parse("Triage issue #{{issue.number}}: {{issue.title}}\n\n{{issue.body}}")?,
+//!     Signature::GitHub,
+//!     std::env::var("GITHUB_WEBHOOK_SECRET")?,
+//! );
+//! serve_webhooks(vec![triage], "0.0.0.0:8081").await?;
+//! ```
+
+use crate::execution::ExecutionContext;
+use crate::prompt::PromptTemplate;
+use crate::Agent;
+use axum::body::Bytes;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+
+/// How old a timestamped request may be
+const MAX_SIGNATURE_AGE: Duration = Duration::from_secs(300);
+
+/// Headers services use for a delivery id
+const DELIVERY_HEADERS: &[&str] = &["x-github-delivery", "x-request-id", "idempotency-key"];
+
+/// How a service signs its requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Signature {
+    /// `X-Hub-Signature-256: sha256=<hex>` over the body
+    GitHub,
+    /// `X-Slack-Signature: v0=<hex>` over `v0:<timestamp>:<body>`, with the
+    /// timestamp in `X-Slack-Request-Timestamp`
+    Slack,
+    /// `Stripe-Signature: t=<timestamp>,v1=<hex>` over `<timestamp>.<body>`
+    Stripe,
+    /// A header holding the hex signature of the body after `prefix`
+    Header { name: String, prefix: String },
+}
+
+/// Why a request was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebhookError {
+    /// A signature header is absent or malformed
+    MissingSignature(String),
+    /// The signature doesn't match the body
+    BadSignature,
+    /// The signed timestamp is too far from now
+    Expired,
+    /// The payload is not JSON or doesn't fit the template
+    BadPayload(String),
+}
+
+impl fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebhookError::MissingSignature(header) => {
+                write!(f, "Missing or malformed signature header '{}'", header)
+            }
+            WebhookError::BadSignature => write!(f, "Signature does not match"),
+            WebhookError::Expired => write!(f, "Signed timestamp is too old"),
+            WebhookError::BadPayload(message) => write!(f, "Bad payload: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for WebhookError {}
+
+impl WebhookError {
+    fn status(&self) -> StatusCode {
+        match self {
+            WebhookError::BadPayload(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+}
+
+/// Lowercase hex HMAC-SHA256 of `parts` joined together
+fn hmac_hex(secret: &[u8], parts: &[&[u8]]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Result<&'a str, WebhookError> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| WebhookError::MissingSignature(name.to_string()))
+}
+
+/// Fail unless `timestamp` (Unix seconds) is within the allowed age of `now`
+fn check_age(timestamp: &str, now: SystemTime) -> Result<(), WebhookError> {
+    let signed: u64 = timestamp
+        .trim()
+        .parse()
+        .map_err(|_| WebhookError::Expired)?;
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    if now.abs_diff(signed) > MAX_SIGNATURE_AGE.as_secs() {
+        return Err(WebhookError::Expired);
+    }
+    Ok(())
+}
+
+impl Signature {
+    /// Check that `body` was signed with `secret`, as of `now`
+    pub fn verify(
+        &self,
+        secret: &[u8],
+        headers: &HeaderMap,
+        body: &[u8],
+        now: SystemTime,
+    ) -> Result<(), WebhookError> {
+        let (expected, given): (String, Vec<&str>) = match self {
+            Signature::GitHub => {
+                let name = "x-hub-signature-256";
+                let given = header(headers, name)?
+                    .strip_prefix("sha256=")
+                    .ok_or_else(|| WebhookError::MissingSignature(name.to_string()))?;
+                (hmac_hex(secret, &[body]), vec![given])
+            }
+            Signature::Slack => {
+                let timestamp = header(headers, "x-slack-request-timestamp")?;
+                check_age(timestamp, now)?;
+                let name = "x-slack-signature";
+                let given = header(headers, name)?
+                    .strip_prefix("v0=")
+                    .ok_or_else(|| WebhookError::MissingSignature(name.to_string()))?;
+                let expected = hmac_hex(secret, &[b"v0:", timestamp.as_bytes(), b":", body]);
+                (expected, vec![given])
+            }
+            Signature::Stripe => {
+                let value = header(headers, "stripe-signature")?;
+                let fields = value.split(',').filter_map(|field| field.split_once('='));
+                let timestamp = fields
+                    .clone()
+                    .find(|(key, _)| *key == "t")
+                    .map(|(_, value)| value)
+                    .ok_or_else(|| WebhookError::MissingSignature("stripe-signature".into()))?;
+                check_age(timestamp, now)?;
+                // Stripe sends one `v1` per active secret during rotation
+                let given = fields
+                    .filter(|(key, _)| *key == "v1")
+                    .map(|(_, value)| value)
+                    .collect();
+                (hmac_hex(secret, &[timestamp.as_bytes(), b".", body]), given)
+            }
+            Signature::Header { name, prefix } => {
+                let given = header(headers, name)?
+                    .strip_prefix(prefix.as_str())
+                    .ok_or_else(|| WebhookError::MissingSignature(name.clone()))?;
+                (hmac_hex(secret, &[body]), vec![given])
+            }
+        };
+        let matches = given.iter().any(|given| {
+            bool::from(
+                given
+                    .trim()
+                    .to_ascii_lowercase()
+                    .as_bytes()
+                    .ct_eq(expected.as_bytes()),
+            )
+        });
+        if matches {
+            Ok(())
+        } else {
+            Err(WebhookError::BadSignature)
+        }
+    }
+}
+
+/// A path that turns signed POSTs into runs of an agent
+pub struct WebhookRoute {
+    path: String,
+    agent: Arc<Agent>,
+    template: PromptTemplate,
+    verify: Option<(Signature, Vec<u8>)>,
+}
+
+impl WebhookRoute {
+    /// Render each payload with `template` and run `agent` on the result,
+    /// refusing requests not signed with `secret` as `signature` describes
+    pub fn new(
+        path: impl Into<String>,
+        agent: Arc<Agent>,
+        template: PromptTemplate,
+        signature: Signature,
+        secret: impl Into<Vec<u8>>,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            agent,
+            template,
+            verify: Some((signature, secret.into())),
+        }
+    }
+
+    /// A route that runs `agent` on every POST, signed or not
+    ///
+    /// Only for senders that can't sign their requests, behind something
+    /// else that keeps strangers out.
+    pub fn unverified(
+        path: impl Into<String>,
+        agent: Arc<Agent>,
+        template: PromptTemplate,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            agent,
+            template,
+            verify: None,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Check a request and render it into the agent's input, or recognize
+    /// a handshake that needs no run
+    fn accept(&self, headers: &HeaderMap, body: &[u8]) -> Result<Accepted, WebhookError> {
+        if let Some((signature, secret)) = &self.verify {
+            signature.verify(secret, headers, body, SystemTime::now())?;
+        }
+        let payload: Value =
+            serde_json::from_slice(body).map_err(|e| WebhookError::BadPayload(e.to_string()))?;
+        if payload["type"] == "url_verification" {
+            if let Some(challenge) = payload["challenge"].as_str() {
+                return Ok(Accepted::Challenge(challenge.to_string()));
+            }
+        }
+        let input = self
+            .template
+            .render(&payload)
+            .map_err(|e| WebhookError::BadPayload(e.to_string()))?;
+        Ok(Accepted::Run(input))
+    }
+
+    async fn handle(self: Arc<Self>, headers: HeaderMap, body: Bytes) -> Response {
+        let input = match self.accept(&headers, &body) {
+            Ok(Accepted::Run(input)) => input,
+            Ok(Accepted::Challenge(challenge)) => {
+                return Json(json!({"challenge": challenge})).into_response()
+            }
+            Err(e) => {
+                tracing::warn!("webhook {}: {}", self.path, e);
+                let body = json!({"error": {"message": e.to_string()}});
+                return (e.status(), Json(body)).into_response();
+            }
+        };
+
+        let trace_id = uuid::Uuid::new_v4().to_string();
+        let mut context = ExecutionContext::new()
+            .trace(&trace_id)
+            .value("webhook", &self.path);
+        if let Some(delivery) = DELIVERY_HEADERS
+            .iter()
+            .find_map(|name| headers.get(*name).and_then(|value| value.to_str().ok()))
+        {
+            context = context.value("delivery", delivery);
+        }
+        let route = self.clone();
+        tokio::spawn(async move {
+            match route.agent.run_with_context(input, context).await {
+                Ok(_) => tracing::info!("webhook {}: run finished", route.path),
+                Err(e) => tracing::warn!("webhook {}: run failed: {}", route.path, e),
+            }
+        });
+        (
+            StatusCode::ACCEPTED,
+            Json(json!({"accepted": true, "trace_id": trace_id})),
+        )
+            .into_response()
+    }
+}
+
+enum Accepted {
+    Run(String),
+    Challenge(String),
+}
+
+/// Router with a POST endpoint per route, for embedding in a larger axum app
+pub fn router(routes: Vec<WebhookRoute>) -> Router {
+    routes.into_iter().fold(Router::new(), |router, route| {
+        let route = Arc::new(route);
+        let path = route.path.clone();
+        router.route(
+            &path,
+            post(move |headers: HeaderMap, body: Bytes| route.clone().handle(headers, body)),
+        )
+    })
+}
+
+/// Serve `routes` on `addr` until the process exits
+pub async fn serve_webhooks(routes: Vec<WebhookRoute>, addr: &str) -> crate::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!(
+        "Serving {} webhooks on http://{}",
+        routes.len(),
+        listener.local_addr()?
+    );
+    axum::serve(listener, router(routes)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_agent;
+    use crate::provider::MockProvider;
+    use axum::body::{to_bytes, Body};
+    use axum::http::{HeaderValue, Request};
+    use tower::ServiceExt;
+
+    const SECRET: &[u8] = b"It's a Secret to Everybody";
+
+    fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_signature_schemes() {
+        // GitHub's documented example
+        let body = b"Hello, World!";
+        let github = headers(&[(
+            "x-hub-signature-256",
+            "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17".to_string(),
+        )]);
+        let now = SystemTime::now();
+        assert_eq!(Signature::GitHub.verify(SECRET, &github, body, now), Ok(()));
+        assert_eq!(
+            Signature::GitHub.verify(b"wrong", &github, body, now),
+            Err(WebhookError::BadSignature)
+        );
+        assert!(matches!(
+            Signature::GitHub.verify(SECRET, &HeaderMap::new(), body, now),
+            Err(WebhookError::MissingSignature(_))
+        ));
+
+        let timestamp = now
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            .to_string();
+        let slack = headers(&[
+            ("x-slack-request-timestamp", timestamp.clone()),
+            (
+                "x-slack-signature",
+                format!(
+                    "v0={}",
+                    hmac_hex(SECRET, &[b"v0:", timestamp.as_bytes(), b":", body])
+                ),
+            ),
+        ]);
+        assert_eq!(Signature::Slack.verify(SECRET, &slack, body, now), Ok(()));
+        let later = now + Duration::from_secs(600);
+        assert_eq!(
+            Signature::Slack.verify(SECRET, &slack, body, later),
+            Err(WebhookError::Expired)
+        );
+
+        let stripe = headers(&[(
+            "stripe-signature",
+            format!(
+                "t={},v1=00ff,v1={}",
+                timestamp,
+                hmac_hex(SECRET, &[timestamp.as_bytes(), b".", body])
+            ),
+        )]);
+        assert_eq!(Signature::Stripe.verify(SECRET, &stripe, body, now), Ok(()));
+    }
+
+    async fn post(app: Router, headers: HeaderMap, body: &str) -> (StatusCode, Value) {
+        let mut request = Request::builder().method("POST").uri("/github");
+        for (name, value) in &headers {
+            request = request.header(name, value);
+        }
+        let response = app
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_route_runs_agent() {
+        let agent =
+            Arc::new(create_agent("triage").with_provider(Box::new(MockProvider::new("ok"))));
+        let template = PromptTemplate::parse("Triage: {{issue.title}}").unwrap();
+        let route = WebhookRoute::new("/github", agent, template, Signature::GitHub, SECRET);
+        let app = router(vec![route]);
+
+        let body = r#"{"issue": {"title": "Crash on start"}}"#;
+        let signed = headers(&[(
+            "x-hub-signature-256",
+            format!("sha256={}", hmac_hex(SECRET, &[body.as_bytes()])),
+        )]);
+        let (status, response) = post(app.clone(), signed, body).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert!(response["trace_id"].is_string());
+
+        let forged = headers(&[("x-hub-signature-256", "sha256=00".to_string())]);
+        let (status, _) = post(app.clone(), forged, body).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let other = r#"{"action": "opened"}"#;
+        let signed = headers(&[(
+            "x-hub-signature-256",
+            format!("sha256={}", hmac_hex(SECRET, &[other.as_bytes()])),
+        )]);
+        let (status, response) = post(app, signed, other).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("issue"));
+    }
+
+    #[tokio::test]
+    async fn test_unverified_route_takes_unsigned_requests() {
+        let agent =
+            Arc::new(create_agent("triage").with_provider(Box::new(MockProvider::new("ok"))));
+        let template = PromptTemplate::parse("Triage: {{issue.title}}").unwrap();
+        let app = router(vec![WebhookRoute::unverified("/github", agent, template)]);
+
+        let body = r#"{"issue": {"title": "Crash on start"}}"#;
+        let (status, _) = post(app, HeaderMap::new(), body).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+    }
+}
@@ -0,0 +1,680 @@
+//! Workflow engine for deterministic agent pipelines
+//!
+//! A workflow is a DAG of named steps. Each step runs once all the steps it
+//! depends on have finished, and independent steps run concurrently. Steps
+//! read the workflow input and earlier outputs from a [`StepContext`] and
+//! produce a JSON value.
+//!
+//! - [`Step::agent`] - run an agent with a prompt built from the context
+//! - [`Step::tool`] - execute a tool with arguments built from the context
+//! - [`Step::map`] - fan out over a list with bounded concurrency;
+//!   [`Step::map_with_policy`] tolerates some items failing
+//! - [`Step::new`] - any async function
+//!
+//! Depending on several steps joins them. [`Step::when`] makes a step
+//! conditional; a skipped step has no output, and dependents still run.
+//! [`Step::retries`] re-runs a failing step. Progress is reported as
+//! [`WorkflowEvent`]s to the callback set with [`Workflow::on_event`].
+//!
+//! A step that exhausts its retries does not stop independent branches:
+//! they run to completion, steps depending on the failed one are not run,
+//! and the workflow fails with an [`AggregateError`] listing every failure.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::workflow::{Step, Workflow};
+//!
+//! let workflow = Workflow::new()
+//!     .step(Step::agent("research", researcher, |ctx| {
+//!         format!("Research: {}", ctx.input())
+//!     }))
+//!     .step(Step::agent("draft", writer, |ctx| {
+//!         format!("Write an article from these notes:\n{}", ctx.text("research"))
+//!     }).after(["research"]).retries(2));
+//!
+//! let result = workflow.run(json!("tide pools")).await?;
+//! println!("{}", result.outputs["draft"]);
+//! ```
+
+use crate::error::{AggregateError, BoxError, SuccessPolicy};
+use crate::tool::Tool;
+use crate::Agent;
+use futures::future::BoxFuture;
+use futures::stream::FuturesUnordered;
+use futures::{FutureExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Inputs available to a step
+#[derive(Debug, Clone)]
+pub struct StepContext {
+    input: Value,
+    outputs: HashMap<String, Value>,
+}
+
+impl StepContext {
+    /// The workflow's input
+    pub fn input(&self) -> &Value {
+        &self.input
+    }
+
+    /// Output of a finished step (`None` if it was skipped)
+    pub fn get(&self, step: &str) -> Option<&Value> {
+        self.outputs.get(step)
+    }
+
+    /// Output of a step as text (strings unquoted, other values as JSON)
+    pub fn text(&self, step: &str) -> String {
+        match self.get(step) {
+            Some(Value::String(text)) => text.clone(),
+            Some(other) => other.to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// Output of a step deserialized into `T`
+    pub fn get_as<T: DeserializeOwned>(&self, step: &str) -> crate::Result<T> {
+        let value = self
+            .get(step)
+            .ok_or_else(|| format!("Step '{}' has no output", step))?;
+        Ok(serde_json::from_value(value.clone())?)
+    }
+}
+
+type Action = Arc<dyn Fn(StepContext) -> BoxFuture<'static, crate::Result<Value>> + Send + Sync>;
+type Predicate = Arc<dyn Fn(&StepContext) -> bool + Send + Sync>;
+
+/// One node of a workflow
+pub struct Step {
+    id: String,
+    depends_on: Vec<String>,
+    condition: Option<Predicate>,
+    retries: usize,
+    retry_delay: Duration,
+    action: Action,
+}
+
+impl Step {
+    /// A step running an async function
+    pub fn new<F, Fut>(id: impl Into<String>, action: F) -> Self
+    where
+        F: Fn(StepContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = crate::Result<Value>> + Send + 'static,
+    {
+        Self {
+            id: id.into(),
+            depends_on: Vec::new(),
+            condition: None,
+            retries: 0,
+            retry_delay: Duration::ZERO,
+            action: Arc::new(move |ctx| action(ctx).boxed()),
+        }
+    }
+
+    /// A step running an agent; the output is the agent's text response
+    pub fn agent<F>(id: impl Into<String>, agent: Arc<Agent>, prompt: F) -> Self
+    where
+        F: Fn(&StepContext) -> String + Send + Sync + 'static,
+    {
+        Self::new(id, move |ctx| {
+            let agent = agent.clone();
+            let prompt = prompt(&ctx);
+            async move { Ok(Value::String(agent.run(prompt).await?)) }
+        })
+    }
+
+    /// A step executing a tool; the output is the tool's result text
+    pub fn tool<F>(id: impl Into<String>, tool: Arc<dyn Tool>, arguments: F) -> Self
+    where
+        F: Fn(&StepContext) -> Value + Send + Sync + 'static,
+    {
+        Self::new(id, move |ctx| {
+            let tool = tool.clone();
+            let args = arguments(&ctx);
+            async move {
+                let output = tokio::task::spawn_blocking(move || tool.execute(args)).await??;
+                Ok(Value::String(output))
+            }
+        })
+    }
+
+    /// A step applying `each` to every item of a list, at most `concurrency` at a time
+    ///
+    /// The output is an array of results in item order. If any item fails,
+    /// the step fails with an [`AggregateError`] naming every failed item.
+    pub fn map<I, F, Fut>(id: impl Into<String>, items: I, concurrency: usize, each: F) -> Self
+    where
+        I: Fn(&StepContext) -> Vec<Value> + Send + Sync + 'static,
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = crate::Result<Value>> + Send + 'static,
+    {
+        Self::map_with_policy(id, items, concurrency, SuccessPolicy::All, each)
+    }
+
+    /// [`map`](Self::map) succeeding when the items that succeed satisfy `policy`
+    ///
+    /// Failed items are `null` in the output, so it stays in item order.
+    pub fn map_with_policy<I, F, Fut>(
+        id: impl Into<String>,
+        items: I,
+        concurrency: usize,
+        policy: SuccessPolicy,
+        each: F,
+    ) -> Self
+    where
+        I: Fn(&StepContext) -> Vec<Value> + Send + Sync + 'static,
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = crate::Result<Value>> + Send + 'static,
+    {
+        let each = Arc::new(each);
+        Self::new(id, move |ctx| {
+            let items = items(&ctx);
+            let each = each.clone();
+            async move {
+                let results: Vec<crate::Result<Value>> = futures::stream::iter(items)
+                    .map(|item| each(item))
+                    .buffered(concurrency.max(1))
+                    .collect()
+                    .await;
+
+                let mut outcome = AggregateError::new();
+                let mut output = Vec::with_capacity(results.len());
+                for (index, result) in results.into_iter().enumerate() {
+                    match result {
+                        Ok(value) => {
+                            outcome.push_success(format!("item {}", index));
+                            output.push(value);
+                        }
+                        Err(e) => {
+                            outcome.push_failure(format!("item {}", index), e);
+                            output.push(Value::Null);
+                        }
+                    }
+                }
+                if !outcome.satisfies(policy) {
+                    return Err(Box::new(outcome) as BoxError);
+                }
+                if !outcome.failures.is_empty() {
+                    tracing::warn!("map step tolerated {}", outcome);
+                }
+                Ok(Value::Array(output))
+            }
+        })
+    }
+
+    /// Run after the given steps (several steps form a join)
+    pub fn after<I, S>(mut self, steps: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.depends_on.extend(steps.into_iter().map(Into::into));
+        self
+    }
+
+    /// Only run when the predicate holds; otherwise the step is skipped
+    pub fn when(
+        mut self,
+        condition: impl Fn(&StepContext) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.condition = Some(Arc::new(condition));
+        self
+    }
+
+    /// Retry a failing step up to `retries` more times
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Wait between retries
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+}
+
+/// Step-level progress
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkflowEvent {
+    StepStarted {
+        step: String,
+        attempt: usize,
+    },
+    StepSucceeded {
+        step: String,
+    },
+    StepFailed {
+        step: String,
+        error: String,
+        will_retry: bool,
+    },
+    StepSkipped {
+        step: String,
+    },
+}
+
+/// Outputs of a completed workflow
+#[derive(Debug, Clone, Default)]
+pub struct WorkflowResult {
+    pub outputs: HashMap<String, Value>,
+    pub skipped: Vec<String>,
+}
+
+type EventHandler = Arc<dyn Fn(&WorkflowEvent) + Send + Sync>;
+
+/// A DAG of steps
+#[derive(Default)]
+pub struct Workflow {
+    steps: Vec<Step>,
+    on_event: Option<EventHandler>,
+}
+
+impl Workflow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a step
+    pub fn step(mut self, step: Step) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// Receive progress events
+    pub fn on_event(mut self, handler: impl Fn(&WorkflowEvent) + Send + Sync + 'static) -> Self {
+        self.on_event = Some(Arc::new(handler));
+        self
+    }
+
+    fn emit(&self, event: WorkflowEvent) {
+        tracing::debug!("workflow: {:?}", event);
+        if let Some(handler) = &self.on_event {
+            handler(&event);
+        }
+    }
+
+    /// Check for duplicate ids, unknown dependencies and cycles
+    pub fn validate(&self) -> crate::Result<()> {
+        let mut ids = HashSet::new();
+        for step in &self.steps {
+            if !ids.insert(step.id.as_str()) {
+                return Err(format!("Duplicate step '{}'", step.id).into());
+            }
+        }
+        for step in &self.steps {
+            if let Some(missing) = step.depends_on.iter().find(|d| !ids.contains(d.as_str())) {
+                return Err(
+                    format!("Step '{}' depends on unknown step '{}'", step.id, missing).into(),
+                );
+            }
+        }
+
+        // Kahn's algorithm: anything left unresolved is on a cycle
+        let mut resolved = HashSet::new();
+        loop {
+            let ready: Vec<&str> = self
+                .steps
+                .iter()
+                .filter(|s| !resolved.contains(s.id.as_str()))
+                .filter(|s| s.depends_on.iter().all(|d| resolved.contains(d.as_str())))
+                .map(|s| s.id.as_str())
+                .collect();
+            if ready.is_empty() {
+                break;
+            }
+            resolved.extend(ready);
+        }
+        if resolved.len() != self.steps.len() {
+            let cyclic: Vec<_> = self
+                .steps
+                .iter()
+                .filter(|s| !resolved.contains(s.id.as_str()))
+                .map(|s| s.id.as_str())
+                .collect();
+            return Err(format!("Workflow has a cycle through: {}", cyclic.join(", ")).into());
+        }
+        Ok(())
+    }
+
+    async fn run_step(&self, step: &Step, ctx: StepContext) -> crate::Result<Value> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            self.emit(WorkflowEvent::StepStarted {
+                step: step.id.clone(),
+                attempt,
+            });
+            match (step.action)(ctx.clone()).await {
+                Ok(value) => {
+                    self.emit(WorkflowEvent::StepSucceeded {
+                        step: step.id.clone(),
+                    });
+                    return Ok(value);
+                }
+                Err(e) => {
+                    let will_retry = attempt <= step.retries;
+                    self.emit(WorkflowEvent::StepFailed {
+                        step: step.id.clone(),
+                        error: e.to_string(),
+                        will_retry,
+                    });
+                    if !will_retry {
+                        return Err(e);
+                    }
+                    tokio::time::sleep(step.retry_delay).await;
+                }
+            }
+        }
+    }
+
+    /// Execute the workflow
+    ///
+    /// Fails with an [`AggregateError`] listing every step that exhausted its
+    /// retries, and every step not run because one it depends on failed.
+    pub async fn run(&self, input: Value) -> crate::Result<WorkflowResult> {
+        self.validate()?;
+
+        let mut ctx = StepContext {
+            input,
+            outputs: HashMap::new(),
+        };
+        // Steps started or settled without running, and steps finished
+        let mut started: HashSet<&str> = HashSet::new();
+        let mut done: HashSet<&str> = HashSet::new();
+        let mut failed: HashSet<&str> = HashSet::new();
+        let mut outcome = AggregateError::new();
+        let mut result = WorkflowResult::default();
+        let mut running = FuturesUnordered::new();
+
+        loop {
+            // Settling a step can make others ready, so look again until
+            // nothing new is ready
+            loop {
+                let ready: Vec<&Step> = self
+                    .steps
+                    .iter()
+                    .filter(|s| !started.contains(s.id.as_str()))
+                    .filter(|s| s.depends_on.iter().all(|d| done.contains(d.as_str())))
+                    .collect();
+                if ready.is_empty() {
+                    break;
+                }
+                for step in ready {
+                    started.insert(&step.id);
+                    if let Some(dependency) =
+                        step.depends_on.iter().find(|d| failed.contains(d.as_str()))
+                    {
+                        done.insert(&step.id);
+                        failed.insert(&step.id);
+                        outcome.push_failure(
+                            step.id.clone(),
+                            format!("not run: step '{}' failed", dependency),
+                        );
+                        continue;
+                    }
+                    match &step.condition {
+                        Some(condition) if !condition(&ctx) => {
+                            done.insert(&step.id);
+                            self.emit(WorkflowEvent::StepSkipped {
+                                step: step.id.clone(),
+                            });
+                            result.skipped.push(step.id.clone());
+                        }
+                        _ => {
+                            let step_ctx = ctx.clone();
+                            running
+                                .push(async move { (step, self.run_step(step, step_ctx).await) });
+                        }
+                    }
+                }
+            }
+
+            // Dependents of each step start as soon as it finishes
+            let Some((step, output)) = running.next().await else {
+                break;
+            };
+            done.insert(&step.id);
+            match output {
+                Ok(value) => {
+                    outcome.push_success(step.id.clone());
+                    ctx.outputs.insert(step.id.clone(), value);
+                }
+                Err(e) => {
+                    failed.insert(&step.id);
+                    outcome.push_failure(step.id.clone(), e);
+                }
+            }
+        }
+
+        if !outcome.failures.is_empty() {
+            // Report in the order steps were added, not the order they ended
+            let position = |id: &str| self.steps.iter().position(|s| s.id == id);
+            outcome
+                .failures
+                .sort_by_key(|failure| position(&failure.id));
+            outcome.succeeded.sort_by_key(|id| position(id));
+            return Err(Box::new(outcome));
+        }
+        result.outputs = ctx.outputs;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_agent;
+    use crate::provider::MockProvider;
+    use crate::tool::FnTool;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_dag_with_agent_tool_and_join() {
+        let agent =
+            Arc::new(create_agent("writer").with_provider(Box::new(MockProvider::new("draft"))));
+        let tool: Arc<dyn Tool> = Arc::new(FnTool::new("count", "Count", |args| {
+            Ok(args["text"].as_str().unwrap_or_default().len().to_string())
+        }));
+
+        let workflow = Workflow::new()
+            .step(Step::agent("write", agent, |ctx| {
+                format!("About {}", ctx.input())
+            }))
+            .step(
+                Step::tool("measure", tool, |ctx| json!({"text": ctx.text("write")}))
+                    .after(["write"]),
+            )
+            .step(
+                Step::new("report", |ctx: StepContext| async move {
+                    Ok(json!(format!(
+                        "{} ({} chars)",
+                        ctx.text("write"),
+                        ctx.text("measure")
+                    )))
+                })
+                .after(["write", "measure"]),
+            );
+
+        let result = workflow.run(json!("otters")).await.unwrap();
+        assert_eq!(result.outputs["report"], "draft (5 chars)");
+    }
+
+    #[tokio::test]
+    async fn test_branches_map_and_events() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+
+        let workflow = Workflow::new()
+            .step(Step::new("numbers", |_| async { Ok(json!([1, 2, 3])) }))
+            .step(
+                Step::map(
+                    "doubled",
+                    |ctx| ctx.get_as::<Vec<Value>>("numbers").unwrap_or_default(),
+                    2,
+                    |n| async move { Ok(json!(n.as_i64().unwrap() * 2)) },
+                )
+                .after(["numbers"]),
+            )
+            .step(
+                Step::new("big", |_| async { Ok(json!("big")) })
+                    .after(["doubled"])
+                    .when(|ctx| ctx.get_as::<Vec<i64>>("doubled").unwrap().len() > 5),
+            )
+            .step(
+                Step::new("small", |_| async { Ok(json!("small")) })
+                    .after(["doubled"])
+                    .when(|ctx| ctx.get_as::<Vec<i64>>("doubled").unwrap().len() <= 5),
+            )
+            .on_event(move |event| seen.lock().unwrap().push(event.clone()));
+
+        let result = workflow.run(Value::Null).await.unwrap();
+        assert_eq!(result.outputs["doubled"], json!([2, 4, 6]));
+        assert_eq!(result.outputs["small"], "small");
+        assert_eq!(result.skipped, vec!["big"]);
+        assert!(events
+            .lock()
+            .unwrap()
+            .contains(&WorkflowEvent::StepSkipped {
+                step: "big".to_string()
+            }));
+    }
+
+    #[tokio::test]
+    async fn test_dependents_start_when_their_dependencies_finish() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let step = |id: &'static str, delay: u64| {
+            let order = order.clone();
+            Step::new(id, move |_| {
+                let order = order.clone();
+                async move {
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    order.lock().unwrap().push(id);
+                    Ok(Value::Null)
+                }
+            })
+        };
+
+        Workflow::new()
+            .step(step("slow", 200))
+            .step(step("fast", 0))
+            .step(step("next", 0).after(["fast"]))
+            .step(step("last", 0).after(["next"]))
+            .step(step("join", 0).after(["slow", "last"]))
+            .run(Value::Null)
+            .await
+            .unwrap();
+        // The fast chain doesn't wait for the slow branch
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec!["fast", "next", "last", "slow", "join"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retries_then_failure() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let flaky = Step::new("flaky", move |_| {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if n < 2 {
+                    Err("transient".into())
+                } else {
+                    Ok(json!("ok"))
+                }
+            }
+        });
+
+        let result = Workflow::new()
+            .step(flaky.retries(2))
+            .run(Value::Null)
+            .await;
+        assert_eq!(result.unwrap().outputs["flaky"], "ok");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        let always = Step::new("always", |_| async { Err("down".into()) }).retries(1);
+        let err = Workflow::new()
+            .step(always)
+            .run(Value::Null)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("always: down"));
+    }
+
+    #[tokio::test]
+    async fn test_every_failure_is_reported() {
+        let noop = |id: &str| Step::new(id.to_string(), |_| async { Ok(Value::Null) });
+        let fail = |id: &str| Step::new(id.to_string(), |_| async { Err("down".into()) });
+
+        let err = Workflow::new()
+            .step(fail("a"))
+            .step(fail("b"))
+            .step(noop("c"))
+            .step(noop("after_a").after(["a"]))
+            .run(Value::Null)
+            .await
+            .unwrap_err();
+        let aggregate = err.downcast_ref::<AggregateError>().unwrap();
+        let failed: Vec<&str> = aggregate.failures.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(failed, vec!["a", "b", "after_a"]);
+        assert_eq!(aggregate.succeeded, vec!["c"]);
+        assert!(aggregate.to_string().contains("not run: step 'a' failed"));
+    }
+
+    #[tokio::test]
+    async fn test_map_policies() {
+        let halve = |n: Value| async move {
+            let n = n.as_i64().unwrap();
+            if n % 2 == 0 {
+                Ok(json!(n / 2))
+            } else {
+                Err(format!("{} is odd", n).into())
+            }
+        };
+
+        let strict = Workflow::new().step(Step::map(
+            "halves",
+            |_| vec![json!(2), json!(3), json!(5)],
+            2,
+            halve,
+        ));
+        let err = strict.run(Value::Null).await.unwrap_err();
+        let aggregate = err.downcast_ref::<AggregateError>().unwrap();
+        let step = aggregate.failures[0]
+            .error
+            .downcast_ref::<AggregateError>()
+            .unwrap();
+        assert_eq!(step.failures.len(), 2);
+        assert!(step.to_string().contains("item 2: 5 is odd"));
+
+        let tolerant = Workflow::new().step(Step::map_with_policy(
+            "halves",
+            |_| vec![json!(2), json!(3), json!(4)],
+            2,
+            SuccessPolicy::Quorum(2),
+            halve,
+        ));
+        let result = tolerant.run(Value::Null).await.unwrap();
+        assert_eq!(result.outputs["halves"], json!([1, null, 2]));
+    }
+
+    #[test]
+    fn test_validate_catches_cycles_and_unknown_deps() {
+        let noop = |id: &str| Step::new(id.to_string(), |_| async { Ok(Value::Null) });
+
+        let cyclic = Workflow::new()
+            .step(noop("a").after(["b"]))
+            .step(noop("b").after(["a"]));
+        assert!(cyclic.validate().unwrap_err().to_string().contains("cycle"));
+
+        let dangling = Workflow::new().step(noop("a").after(["ghost"]));
+        assert!(dangling.validate().is_err());
+    }
+}
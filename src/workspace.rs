@@ -0,0 +1,216 @@
+//! Per-execution scratch workspaces
+//!
+//! A [`Workspace`] is a fresh temporary directory for one execution. The
+//! filesystem tools and shell tool it hands out are jailed to it, the total
+//! size of its files is capped, and it is deleted when dropped. Before that,
+//! the caller can list what the agent produced and [`harvest`](Workspace::harvest)
+//! those files as artifacts.
+//!
+//! Create one workspace (and one agent) per execution so runs never see each
+//! other's files.
+//!
+//! # Example
+//! ```ignore
+//! use patinox::workspace::Workspace;
+//!
+//! let workspace = Workspace::create()?;
+//! let agent = workspace.attach(create_agent("report-writer"));
+//! agent.run("Write the quarterly summary to report.md").await?;
+//!
+//! workspace.harvest("./artifacts")?;
+//! // the workspace directory is removed here
+//! ```
+
+use crate::tool::fs::FsSandbox;
+use crate::tool::shell::ShellTool;
+use crate::Agent;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Default cap on the combined size of a workspace's files
+const DEFAULT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// A file left in a workspace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    /// Path relative to the workspace root
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+/// A temporary, size-limited directory removed on drop
+#[derive(Debug)]
+pub struct Workspace {
+    sandbox: FsSandbox,
+    keep: bool,
+}
+
+impl Workspace {
+    /// Create a workspace under the system temp dir with the default size limit
+    pub fn create() -> io::Result<Self> {
+        Self::create_in(std::env::temp_dir(), DEFAULT_MAX_BYTES)
+    }
+
+    /// Create a workspace under `parent`, limited to `max_bytes` in total
+    pub fn create_in(parent: impl AsRef<Path>, max_bytes: u64) -> io::Result<Self> {
+        let root = parent
+            .as_ref()
+            .join(format!("patinox-workspace-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&root)?;
+        let sandbox = FsSandbox::new(&root)?
+            .max_file_bytes(max_bytes)
+            .max_total_bytes(max_bytes);
+        Ok(Self {
+            sandbox,
+            keep: false,
+        })
+    }
+
+    /// The workspace directory
+    pub fn path(&self) -> &Path {
+        self.sandbox.root()
+    }
+
+    /// Sandbox for building further filesystem tools
+    pub fn sandbox(&self) -> &FsSandbox {
+        &self.sandbox
+    }
+
+    /// Bytes currently stored
+    pub fn usage(&self) -> io::Result<u64> {
+        self.sandbox.usage()
+    }
+
+    /// Give an agent `read_file`, `write_file` and `list_dir` tools in this workspace
+    pub fn attach(&self, agent: Agent) -> Agent {
+        agent
+            .tool(self.sandbox.read_tool())
+            .tool(self.sandbox.write_tool())
+            .tool(self.sandbox.list_tool())
+    }
+
+    /// Run a shell tool's commands inside this workspace
+    pub fn shell(&self, shell: ShellTool) -> ShellTool {
+        shell.working_dir(self.sandbox.clone())
+    }
+
+    /// Files in the workspace, sorted by path
+    pub fn artifacts(&self) -> io::Result<Vec<Artifact>> {
+        fn walk(root: &Path, dir: &Path, out: &mut Vec<Artifact>) -> io::Result<()> {
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let file_type = entry.file_type()?;
+                let path = entry.path();
+                if file_type.is_dir() {
+                    walk(root, &path, out)?;
+                } else if file_type.is_file() {
+                    out.push(Artifact {
+                        path: path.strip_prefix(root).unwrap_or(&path).to_path_buf(),
+                        bytes: entry.metadata()?.len(),
+                    });
+                }
+            }
+            Ok(())
+        }
+
+        let mut artifacts = Vec::new();
+        walk(self.path(), self.path(), &mut artifacts)?;
+        artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(artifacts)
+    }
+
+    /// Copy every artifact into `destination`, keeping relative paths
+    pub fn harvest(&self, destination: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+        let destination = destination.as_ref();
+        let mut copied = Vec::new();
+        for artifact in self.artifacts()? {
+            let target = destination.join(&artifact.path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(self.path().join(&artifact.path), &target)?;
+            copied.push(target);
+        }
+        Ok(copied)
+    }
+
+    /// Keep the directory instead of deleting it, returning its path
+    pub fn persist(mut self) -> PathBuf {
+        self.keep = true;
+        self.path().to_path_buf()
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        if !self.keep {
+            if let Err(e) = fs::remove_dir_all(self.path()) {
+                tracing::warn!(
+                    "Failed to remove workspace {}: {}",
+                    self.path().display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_agent;
+    use crate::tool::Tool;
+    use serde_json::json;
+
+    #[test]
+    fn test_artifacts_harvest_and_cleanup() {
+        let workspace = Workspace::create().unwrap();
+        let root = workspace.path().to_path_buf();
+        let write = workspace.sandbox().write_tool();
+        write
+            .execute(json!({"path": "out/report.md", "content": "# Q3"}))
+            .unwrap();
+
+        assert_eq!(
+            workspace.artifacts().unwrap(),
+            vec![Artifact {
+                path: PathBuf::from("out/report.md"),
+                bytes: 4
+            }]
+        );
+
+        let destination = Workspace::create().unwrap();
+        let copied = workspace.harvest(destination.path()).unwrap();
+        assert_eq!(fs::read_to_string(&copied[0]).unwrap(), "# Q3");
+
+        drop(workspace);
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn test_total_size_limit() {
+        let workspace = Workspace::create_in(std::env::temp_dir(), 8).unwrap();
+        let write = workspace.sandbox().write_tool();
+        write
+            .execute(json!({"path": "a.txt", "content": "12345"}))
+            .unwrap();
+        assert!(write
+            .execute(json!({"path": "b.txt", "content": "6789"}))
+            .is_err());
+        // Replacing a file only counts the difference
+        write
+            .execute(json!({"path": "a.txt", "content": "1234567"}))
+            .unwrap();
+        assert_eq!(workspace.usage().unwrap(), 7);
+    }
+
+    #[test]
+    fn test_attach_adds_fs_tools() {
+        let workspace = Workspace::create().unwrap();
+        let agent = workspace.attach(create_agent("writer"));
+        for name in ["read_file", "write_file", "list_dir"] {
+            assert!(agent.tools().contains_key(name));
+        }
+    }
+}